@@ -15,6 +15,10 @@ fuzz_target!(|data: &[u8]| {
             // Modify these values to explore additional parts of wasmer.
             simd: false,
             threads: false,
+            bulk_memory: false,
+            multi_value: false,
+            deny_floats: false,
+            deterministic: false,
         },
     );
 });
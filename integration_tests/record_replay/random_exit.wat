@@ -0,0 +1,8 @@
+(module
+  (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  ;; exits with a random byte
+  (func (export "_start")
+    (drop (call $random_get (i32.const 0) (i32.const 1)))
+    (call $proc_exit (i32.load8_u (i32.const 0)))))
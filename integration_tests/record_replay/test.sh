@@ -0,0 +1,29 @@
+#! /bin/bash
+
+trace=$(mktemp)
+module=./integration_tests/record_replay/random_exit.wat
+
+./target/release/wasmer run $module --disable-cache --record $trace
+recorded=$?
+if [ ! -s $trace ]
+then
+    echo "record and replay integration test failed: nothing was recorded"
+    rm $trace
+    exit -1
+fi
+
+for i in 1 2 3
+do
+    ./target/release/wasmer run $module --disable-cache --replay $trace
+    replayed=$?
+    if [ $replayed -ne $recorded ]
+    then
+        echo "record and replay integration test failed: exited with $replayed instead of $recorded"
+        rm $trace
+        exit -1
+    fi
+done
+
+echo "record and replay integration test succeeded"
+rm $trace
+exit 0
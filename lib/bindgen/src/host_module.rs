@@ -0,0 +1,219 @@
+//! `#[wasmer_host_module]`, turning the methods of an impl block into the
+//! host functions of a namespace.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use syn::{
+    spanned::Spanned, AttributeArgs, FnArg, ImplItem, ImplItemMethod, ItemImpl, Lit, Meta,
+    NestedMeta, Type,
+};
+
+/// The string value of `name = "value"`, or `None` for other metas
+fn string_value(meta: &NestedMeta, name: &str) -> syn::Result<Option<String>> {
+    match meta {
+        NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident(name) => {
+            match &name_value.lit {
+                Lit::Str(value) => Ok(Some(value.value())),
+                lit => Err(syn::Error::new(
+                    lit.span(),
+                    format!("`{}` must be a string", name),
+                )),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The import name of a method, or `None` if it is skipped, removing its
+/// `#[host_function(...)]` attribute
+fn take_import_name(method: &mut ImplItemMethod) -> syn::Result<Option<String>> {
+    let mut name = Some(method.sig.ident.to_string());
+    let mut attrs = vec![];
+    for attr in method.attrs.drain(..) {
+        if !attr.path.is_ident("host_function") {
+            attrs.push(attr);
+            continue;
+        }
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            meta => {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    "expected `#[host_function(name = \"...\")]` or `#[host_function(skip)]`",
+                ))
+            }
+        };
+        for meta in &nested {
+            match meta {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => name = None,
+                _ => match string_value(meta, "name")? {
+                    Some(import_name) => name = Some(import_name),
+                    None => {
+                        return Err(syn::Error::new(
+                            meta.span(),
+                            "unknown `host_function` argument, expected `name` or `skip`",
+                        ))
+                    }
+                },
+            }
+        }
+    }
+    method.attrs = attrs;
+    Ok(name)
+}
+
+/// Whether `ty` is `&mut Ctx`
+fn is_ctx(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(reference) if reference.mutability.is_some() => match &*reference.elem {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .map_or(false, |segment| segment.ident == "Ctx"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The registration in the namespace of the host function calling `method`
+fn generate_registration(method: &ImplItemMethod, import_name: &str) -> syn::Result<TokenStream> {
+    let sig = &method.sig;
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "host functions can't be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "host functions can't be `async`",
+        ));
+    }
+
+    let mut has_self = false;
+    let mut param_types = vec![];
+    for (index, input) in sig.inputs.iter().enumerate() {
+        match input {
+            FnArg::Receiver(receiver) => {
+                if receiver.reference.is_none() || receiver.mutability.is_some() {
+                    return Err(syn::Error::new(
+                        receiver.span(),
+                        "host functions take `&self`, the module being shared by \
+                         its functions; use interior mutability for state",
+                    ));
+                }
+                has_self = true;
+            }
+            FnArg::Typed(pat_type) => param_types.push((index, &*pat_type.ty)),
+        }
+    }
+
+    // `&mut Ctx`, if it is there, comes first and is the `Ctx` of the call
+    let takes_ctx = param_types.first().map_or(false, |(_, ty)| is_ctx(ty));
+    let wasm_params = param_types
+        .iter()
+        .skip(if takes_ctx { 1 } else { 0 })
+        .map(|(index, ty)| {
+            (
+                Ident::new(&format!("__arg{}", index), Span::call_site()),
+                ty,
+            )
+        })
+        .collect::<Vec<_>>();
+    let params = wasm_params.iter().map(|(ident, ty)| quote!(#ident: #ty));
+    let mut args = wasm_params
+        .iter()
+        .map(|(ident, _)| quote!(#ident))
+        .collect::<Vec<_>>();
+    if takes_ctx {
+        args.insert(0, quote!(__ctx));
+    }
+
+    let ident = &sig.ident;
+    let call = if has_self {
+        quote!(__module.#ident(#(#args),*))
+    } else {
+        quote!(Self::#ident(#(#args),*))
+    };
+    Ok(quote! {
+        let __module = ::std::sync::Arc::clone(&__host_module);
+        namespace.insert(
+            #import_name,
+            ::wasmer_runtime_core::typed_func::Func::new(
+                move |__ctx: &mut ::wasmer_runtime_core::vm::Ctx, #(#params),*| #call,
+            ),
+        );
+    })
+}
+
+pub fn expand(args: AttributeArgs, mut item: ItemImpl) -> syn::Result<TokenStream> {
+    let mut namespace = "env".to_string();
+    for arg in &args {
+        match string_value(arg, "namespace")? {
+            Some(value) => namespace = value,
+            None => {
+                return Err(syn::Error::new(
+                    arg.span(),
+                    "unknown `wasmer_host_module` argument, expected `namespace`",
+                ))
+            }
+        }
+    }
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new(
+            path.span(),
+            "`wasmer_host_module` goes on an inherent impl block, not a trait impl",
+        ));
+    }
+
+    let mut registrations = vec![];
+    for impl_item in &mut item.items {
+        if let ImplItem::Method(method) = impl_item {
+            if let Some(import_name) = take_import_name(method)? {
+                registrations.push(generate_registration(method, &import_name)?);
+            }
+        }
+    }
+
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+    let namespace_doc = format!(
+        "The namespace of the host functions, to merge into another `ImportObject` \
+         under `{}` or another name",
+        namespace
+    );
+    let import_object_doc = format!(
+        "An `ImportObject` with the host functions in the `{}` namespace",
+        namespace
+    );
+    Ok(quote! {
+        #item
+
+        impl #impl_generics #self_ty #where_clause {
+            #[doc = #namespace_doc]
+            pub fn into_namespace(self) -> ::wasmer_runtime_core::import::Namespace
+            where
+                Self: 'static,
+            {
+                let __host_module = ::std::sync::Arc::new(self);
+                #[allow(unused_mut)]
+                let mut namespace = ::wasmer_runtime_core::import::Namespace::new();
+                #(#registrations)*
+                namespace
+            }
+
+            #[doc = #import_object_doc]
+            pub fn into_import_object(self) -> ::wasmer_runtime_core::import::ImportObject
+            where
+                Self: 'static,
+            {
+                let mut import_object = ::wasmer_runtime_core::import::ImportObject::new();
+                import_object.register(#namespace, self.into_namespace());
+                import_object
+            }
+        }
+    })
+}
@@ -0,0 +1,557 @@
+#![deny(
+    dead_code,
+    nonstandard_style,
+    unused_imports,
+    unused_mut,
+    unused_variables,
+    unused_unsafe,
+    unreachable_patterns
+)]
+#![doc(html_favicon_url = "https://wasmer.io/static/icons/favicon.ico")]
+#![doc(html_logo_url = "https://avatars3.githubusercontent.com/u/44205449?s=200&v=4")]
+
+//! Generates the glue of host functions, so large host APIs don't have to be
+//! written with `imports!` and `func!`.
+//!
+//! # `#[wasmer_host_module]`
+//!
+//! On an impl block, `#[wasmer_host_module(namespace = "env")]` turns each
+//! method into a host function of the namespace, `env` by default, and adds
+//! `into_import_object` and `into_namespace` to build them in one call:
+//!
+//! ```ignore
+//! struct Logger {
+//!     lines: Mutex<Vec<String>>,
+//! }
+//!
+//! #[wasmer_host_module(namespace = "env")]
+//! impl Logger {
+//!     fn log(&self, ctx: &mut Ctx, message: WasmPtr<u8, Array>, len: u32) {
+//!         if let Some(message) = message.get_utf8_string(ctx.memory(0), len) {
+//!             self.lines.lock().unwrap().push(message.to_string());
+//!         }
+//!     }
+//!
+//!     #[host_function(name = "line_count")]
+//!     fn count(&self) -> u32 {
+//!         self.lines.lock().unwrap().len() as u32
+//!     }
+//! }
+//!
+//! let import_object = Logger { lines: Mutex::new(vec![]) }.into_import_object();
+//! ```
+//!
+//! Methods take `&self`, the struct being shared by its host functions, and
+//! may take no receiver. A first `&mut Ctx` parameter is given the `Ctx` of
+//! the call; the other parameters and the result are those `func!` accepts,
+//! a `Result` trapping on `Err`. `#[host_function(name = "...")]` renames the
+//! import and `#[host_function(skip)]` leaves a method out.
+//!
+//! # `wasmer_bindgen!`
+//!
+//! From the WIT interface describing them, host APIs are implemented with
+//! Rust types instead of `WasmPtr`s.
+//!
+//! `wasmer_bindgen!("my-api.wit")`, with a path relative to the crate's
+//! manifest, generates:
+//!
+//! - a `MyApi` trait, named after the file, with a method per function;
+//! - a Rust enum per WIT enum, converted from its `u32` discriminant with
+//!   `TryFrom<u32>`;
+//! - a `MyApiImports` builder of the `ImportObject` importing the functions
+//!   from the `my-api` namespace, or another one given to `namespace`.
+//!
+//! The generated code uses `wasmer_runtime_core`, which must be a dependency
+//! of the crate.
+//!
+//! ## WIT
+//!
+//! A WIT file declares enums and functions, with `//` comments:
+//!
+//! ```text
+//! enum error {
+//!     not-found,
+//!     denied,
+//! }
+//!
+//! add: function(a: u32, b: u32) -> u32
+//! log: function(message: string)
+//! lookup: function(key: string) -> expected<list<u8>, error>
+//! ```
+//!
+//! The types are `u8` to `u64`, `s8` to `s64`, `f32`, `f64`, `bool`,
+//! `string`, `list<T>` of an integer or float `T`, the declared enums, and,
+//! as results, `expected<T, E>` of a value `T`, or `_` for none, and an error
+//! enum `E`. WITX files are not supported.
+//!
+//! ## ABI
+//!
+//! Parameters and results cross the boundary as follows:
+//!
+//! - integers and floats as themselves, `bool`s and enums as `u32`s;
+//! - `string` and `list<T>` parameters as a pointer and a length, in bytes
+//!   for strings and in items for lists;
+//! - `string` and `list<T>` results through two more parameters, the pointer
+//!   and capacity of a guest buffer. The function returns the length, and
+//!   only writes the result if the buffer is large enough, so the guest can
+//!   retry with a larger one;
+//! - `expected<T, E>` results as a `u32` that is 0 on success and the error's
+//!   discriminant plus 1 otherwise. Other values are written through one more
+//!   parameter, a pointer to the value, and strings and lists through three
+//!   more, the buffer's pointer and capacity, and a pointer to the length.
+//!
+//! Strings and lists out of bounds of the memory, strings that aren't UTF-8
+//! and unknown enum discriminants trap.
+
+extern crate proc_macro;
+
+mod host_module;
+mod parse;
+
+use crate::parse::{Enum, Function, Interface, Scalar, Type};
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::quote;
+use std::path::PathBuf;
+use syn::{parse_macro_input, AttributeArgs, ItemImpl, LitStr};
+
+/// Generates the trait, enums and `ImportObject` builder of the host
+/// functions of a WIT file, see the [crate documentation](index.html)
+#[proc_macro]
+pub fn wasmer_bindgen(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+    match generate(&path.value()) {
+        Ok(tokens) => tokens.into(),
+        Err(message) => syn::Error::new(path.span(), message)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// Turns the methods of an impl block into the host functions of a namespace,
+/// see the [crate documentation](index.html)
+#[proc_macro_attribute]
+pub fn wasmer_host_module(
+    args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(args as AttributeArgs);
+    let item = parse_macro_input!(item as ItemImpl);
+    match host_module::expand(args, item) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Reads and parses the WIT file at `relative_path` and generates its glue
+fn generate(relative_path: &str) -> Result<TokenStream, String> {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|_| "`CARGO_MANIFEST_DIR` is not set".to_string())?;
+    let path = PathBuf::from(manifest_dir).join(relative_path);
+    let source = std::fs::read_to_string(&path)
+        .map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    let interface = parse::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .ok_or_else(|| format!("{} has no file name", path.display()))?;
+    Ok(generate_interface(
+        &interface,
+        &name,
+        &path.to_string_lossy(),
+    ))
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+fn words(name: &str) -> impl Iterator<Item = &str> {
+    name.split(|c| c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+}
+
+/// The `snake_case` identifier of a WIT name, with a `_` after keywords
+fn snake_ident(name: &str) -> Ident {
+    let mut snake = words(name)
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    if KEYWORDS.contains(&snake.as_str()) {
+        snake.push('_');
+    }
+    Ident::new(&snake, Span::call_site())
+}
+
+/// The `UpperCamelCase` identifier of a WIT name
+fn camel_ident(name: &str) -> Ident {
+    let camel = words(name)
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first)
+                .chain(chars.map(|c| c.to_ascii_lowercase()))
+                .collect::<String>()
+        })
+        .collect::<String>();
+    Ident::new(&camel, Span::call_site())
+}
+
+fn suffixed_ident(ident: &Ident, suffix: &str) -> Ident {
+    Ident::new(&format!("{}_{}", ident, suffix), Span::call_site())
+}
+
+fn scalar_type(scalar: Scalar) -> TokenStream {
+    let ident = Ident::new(scalar.rust_type(), Span::call_site());
+    quote!(#ident)
+}
+
+/// The Rust type of a parameter or result in the host trait
+fn host_type(ty: &Type) -> TokenStream {
+    match ty {
+        Type::Scalar(scalar) => scalar_type(*scalar),
+        Type::Bool => quote!(bool),
+        Type::String => quote!(::std::string::String),
+        Type::List(scalar) => {
+            let element = scalar_type(*scalar);
+            quote!(::std::vec::Vec<#element>)
+        }
+        Type::Enum(name) => {
+            let ident = camel_ident(name);
+            quote!(#ident)
+        }
+        Type::Expected(value, error) => {
+            let value = match value {
+                Some(value) => host_type(value),
+                None => quote!(()),
+            };
+            let error = camel_ident(error);
+            quote!(::std::result::Result<#value, #error>)
+        }
+    }
+}
+
+fn generate_enum(enum_: &Enum) -> TokenStream {
+    let ident = camel_ident(&enum_.name);
+    let doc = format!("The `{}` WIT enum", enum_.name);
+    let cases = enum_
+        .cases
+        .iter()
+        .map(|case| camel_ident(case))
+        .collect::<Vec<_>>();
+    let discriminants = (0..cases.len() as u32).map(Literal::u32_unsuffixed);
+    let wit_name = &enum_.name;
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        #[repr(u32)]
+        pub enum #ident {
+            #(#cases),*
+        }
+
+        impl ::std::convert::TryFrom<u32> for #ident {
+            type Error = ::std::string::String;
+
+            fn try_from(value: u32) -> ::std::result::Result<Self, ::std::string::String> {
+                match value {
+                    #(#discriminants => ::std::result::Result::Ok(#ident::#cases),)*
+                    _ => ::std::result::Result::Err(::std::format!(
+                        "{} is not a `{}` case",
+                        value,
+                        #wit_name
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// The method of the host trait and the registration in the namespace of
+/// `function`
+fn generate_function(function: &Function, helpers: &Ident) -> (TokenStream, TokenStream) {
+    let ident = snake_ident(&function.name);
+    let mut host_params = vec![];
+    let mut wasm_params = vec![];
+    let mut lifts = vec![];
+    let mut args = vec![];
+    for (name, ty) in &function.params {
+        let param = snake_ident(name);
+        let param_type = host_type(ty);
+        host_params.push(quote!(#param: #param_type));
+        match ty {
+            Type::Scalar(scalar) => {
+                let wasm_type = scalar_type(*scalar);
+                wasm_params.push(quote!(#param: #wasm_type));
+            }
+            Type::Bool => {
+                wasm_params.push(quote!(#param: u32));
+                lifts.push(quote!(let #param = #param != 0;));
+            }
+            Type::Enum(_) => {
+                wasm_params.push(quote!(#param: u32));
+                lifts.push(quote! {
+                    let #param = <#param_type as ::std::convert::TryFrom<u32>>::try_from(#param)?;
+                });
+            }
+            Type::String | Type::List(_) => {
+                let ptr = suffixed_ident(&param, "ptr");
+                let len = suffixed_ident(&param, "len");
+                wasm_params.push(quote!(#ptr: u32));
+                wasm_params.push(quote!(#len: u32));
+                lifts.push(match ty {
+                    Type::String => quote! {
+                        let #param = #helpers::lift_string(__memory, #ptr, #len)?;
+                    },
+                    _ => quote! {
+                        let #param = #helpers::lift_list(__memory, #ptr, #len)?;
+                    },
+                });
+            }
+            Type::Expected(..) => unreachable!("parameters can't be `expected`"),
+        }
+        args.push(param);
+    }
+
+    let call = quote!(__host.#ident(#(#args),*));
+    let (host_result, wasm_result, body) = match &function.result {
+        None => (
+            quote!(()),
+            quote!(()),
+            quote!(#call; ::std::result::Result::Ok(())),
+        ),
+        Some(result) => {
+            let host_result = host_type(result);
+            let (wasm_result, body) = match result {
+                Type::Scalar(scalar) => (
+                    scalar_type(*scalar),
+                    quote!(::std::result::Result::Ok(#call)),
+                ),
+                Type::Bool | Type::Enum(_) => {
+                    (quote!(u32), quote!(::std::result::Result::Ok(#call as u32)))
+                }
+                Type::String | Type::List(_) => {
+                    wasm_params.push(quote!(__ret_ptr: u32));
+                    wasm_params.push(quote!(__ret_cap: u32));
+                    let items = match result {
+                        Type::String => quote!(__result.as_bytes()),
+                        _ => quote!(&__result),
+                    };
+                    (
+                        quote!(u32),
+                        quote! {
+                            let __result = #call;
+                            #helpers::lower_list(__memory, __ret_ptr, __ret_cap, #items)
+                        },
+                    )
+                }
+                Type::Expected(value, _) => {
+                    let store = match value.as_ref().map(|value| &**value) {
+                        None => quote!(let () = __value;),
+                        Some(Type::Scalar(_)) => {
+                            wasm_params.push(quote!(__ret_ptr: u32));
+                            quote!(#helpers::store(__memory, __ret_ptr, __value)?;)
+                        }
+                        Some(Type::Bool) | Some(Type::Enum(_)) => {
+                            wasm_params.push(quote!(__ret_ptr: u32));
+                            quote!(#helpers::store(__memory, __ret_ptr, __value as u32)?;)
+                        }
+                        Some(value @ Type::String) | Some(value @ Type::List(_)) => {
+                            wasm_params.push(quote!(__ret_ptr: u32));
+                            wasm_params.push(quote!(__ret_cap: u32));
+                            wasm_params.push(quote!(__ret_len_ptr: u32));
+                            let items = match value {
+                                Type::String => quote!(__value.as_bytes()),
+                                _ => quote!(&__value),
+                            };
+                            quote! {
+                                let __len = #helpers::lower_list(__memory, __ret_ptr, __ret_cap, #items)?;
+                                #helpers::store(__memory, __ret_len_ptr, __len)?;
+                            }
+                        }
+                        Some(Type::Expected(..)) => unreachable!("`expected` can't be nested"),
+                    };
+                    (
+                        quote!(u32),
+                        quote! {
+                            match #call {
+                                ::std::result::Result::Ok(__value) => {
+                                    #store
+                                    ::std::result::Result::Ok(0)
+                                }
+                                ::std::result::Result::Err(__error) => {
+                                    ::std::result::Result::Ok(__error as u32 + 1)
+                                }
+                            }
+                        },
+                    )
+                }
+            };
+            (host_result, wasm_result, body)
+        }
+    };
+
+    let doc = format!("The `{}` WIT function", function.name);
+    let method = quote! {
+        #[doc = #doc]
+        fn #ident(&self, #(#host_params),*) -> #host_result;
+    };
+    let wit_name = &function.name;
+    let registration = quote! {
+        let __host = ::std::sync::Arc::clone(&self.host);
+        namespace.insert(
+            #wit_name,
+            ::wasmer_runtime_core::typed_func::Func::new(
+                move |__ctx: &mut ::wasmer_runtime_core::vm::Ctx, #(#wasm_params),*|
+                    -> ::std::result::Result<#wasm_result, ::std::string::String> {
+                    #[allow(unused_variables)]
+                    let __memory = __ctx.memory(0);
+                    #(#lifts)*
+                    #body
+                },
+            ),
+        );
+    };
+    (method, registration)
+}
+
+fn generate_interface(interface: &Interface, name: &str, path: &str) -> TokenStream {
+    let trait_ident = camel_ident(name);
+    let imports_ident = Ident::new(&format!("{}Imports", trait_ident), Span::call_site());
+    let helpers = Ident::new(
+        &format!("__{}_bindgen", snake_ident(name)),
+        Span::call_site(),
+    );
+    let enums = interface.enums.iter().map(generate_enum);
+    let (methods, registrations): (Vec<_>, Vec<_>) = interface
+        .functions
+        .iter()
+        .map(|function| generate_function(function, &helpers))
+        .unzip();
+    let trait_doc = format!("The host functions of the `{}` WIT interface", name);
+    let imports_doc = format!("Builds the `ImportObject` of the `{}` host functions", name);
+    let namespace_doc = format!(
+        "Imports the functions from `namespace` instead of `{}`",
+        name
+    );
+
+    quote! {
+        // Rebuilds the crate when the WIT file changes
+        const _: &str = include_str!(#path);
+
+        #(#enums)*
+
+        #[doc = #trait_doc]
+        pub trait #trait_ident: Send + Sync + 'static {
+            #(#methods)*
+        }
+
+        #[doc = #imports_doc]
+        pub struct #imports_ident<T> {
+            host: ::std::sync::Arc<T>,
+            namespace: ::std::string::String,
+        }
+
+        impl<T: #trait_ident> #imports_ident<T> {
+            /// Imports the functions `host` implements
+            pub fn new(host: T) -> Self {
+                Self {
+                    host: ::std::sync::Arc::new(host),
+                    namespace: #name.to_string(),
+                }
+            }
+
+            #[doc = #namespace_doc]
+            pub fn namespace(mut self, namespace: &str) -> Self {
+                self.namespace = namespace.to_string();
+                self
+            }
+
+            /// The namespace of the functions, to merge into another
+            /// `ImportObject`
+            pub fn to_namespace(&self) -> ::wasmer_runtime_core::import::Namespace {
+                let mut namespace = ::wasmer_runtime_core::import::Namespace::new();
+                #(#registrations)*
+                namespace
+            }
+
+            /// An `ImportObject` with the functions in their namespace
+            pub fn build(&self) -> ::wasmer_runtime_core::import::ImportObject {
+                let mut import_object = ::wasmer_runtime_core::import::ImportObject::new();
+                import_object.register(self.namespace.clone(), self.to_namespace());
+                import_object
+            }
+        }
+
+        #[doc(hidden)]
+        #[allow(dead_code)]
+        mod #helpers {
+            use ::wasmer_runtime_core::memory::{
+                ptr::{Array, Item, WasmPtr},
+                Memory,
+            };
+            use ::wasmer_runtime_core::types::ValueType;
+
+            pub fn lift_string(memory: &Memory, ptr: u32, len: u32) -> Result<String, String> {
+                WasmPtr::<u8, Array>::new(ptr)
+                    .get_utf8_string(memory, len)
+                    .map(|string| string.to_string())
+                    .ok_or_else(|| {
+                        format!(
+                            "the string at {} of {} bytes is out of bounds or not UTF-8",
+                            ptr, len
+                        )
+                    })
+            }
+
+            pub fn lift_list<T: ValueType>(
+                memory: &Memory,
+                ptr: u32,
+                len: u32,
+            ) -> Result<Vec<T>, String> {
+                WasmPtr::<T, Array>::new(ptr)
+                    .slice(memory, len)
+                    .map(|items| items.to_vec())
+                    .ok_or_else(|| {
+                        format!(
+                            "the list at {} of {} items is out of bounds or misaligned",
+                            ptr, len
+                        )
+                    })
+            }
+
+            /// Writes `items` to the buffer at `ptr` if its capacity `cap`
+            /// fits them, returning how many there are
+            pub fn lower_list<T: ValueType>(
+                memory: &Memory,
+                ptr: u32,
+                cap: u32,
+                items: &[T],
+            ) -> Result<u32, String> {
+                let len = items.len() as u32;
+                if len <= cap {
+                    WasmPtr::<T, Array>::new(ptr)
+                        .slice(memory, len)
+                        .ok_or_else(|| {
+                            format!(
+                                "the buffer at {} of {} items is out of bounds or misaligned",
+                                ptr, len
+                            )
+                        })?
+                        .copy_from_slice(items);
+                }
+                Ok(len)
+            }
+
+            pub fn store<T: ValueType>(memory: &Memory, ptr: u32, value: T) -> Result<(), String> {
+                WasmPtr::<T, Item>::new(ptr)
+                    .deref(memory)
+                    .ok_or_else(|| format!("the result at {} is out of bounds", ptr))?
+                    .set(value);
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Parsing of the WIT subset `wasmer_bindgen!` supports.
+
+use std::collections::HashSet;
+
+/// An integer or float type
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scalar {
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "u8" => Scalar::U8,
+            "u16" => Scalar::U16,
+            "u32" => Scalar::U32,
+            "u64" => Scalar::U64,
+            "s8" => Scalar::S8,
+            "s16" => Scalar::S16,
+            "s32" => Scalar::S32,
+            "s64" => Scalar::S64,
+            "f32" => Scalar::F32,
+            "f64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    /// The Rust type the scalar is, both on the host and across the boundary
+    pub fn rust_type(self) -> &'static str {
+        match self {
+            Scalar::U8 => "u8",
+            Scalar::U16 => "u16",
+            Scalar::U32 => "u32",
+            Scalar::U64 => "u64",
+            Scalar::S8 => "i8",
+            Scalar::S16 => "i16",
+            Scalar::S32 => "i32",
+            Scalar::S64 => "i64",
+            Scalar::F32 => "f32",
+            Scalar::F64 => "f64",
+        }
+    }
+}
+
+/// The type of a parameter or result
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Scalar(Scalar),
+    Bool,
+    String,
+    List(Scalar),
+    /// An enum, by name
+    Enum(String),
+    /// A value or an error enum, by name; the value may be `_`
+    Expected(Option<Box<Type>>, String),
+}
+
+#[derive(Debug)]
+pub struct Enum {
+    pub name: String,
+    pub cases: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<(String, Type)>,
+    pub result: Option<Type>,
+}
+
+/// The enums and functions of a WIT file
+#[derive(Debug, Default)]
+pub struct Interface {
+    pub enums: Vec<Enum>,
+    pub functions: Vec<Function>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Punct(&'static str),
+}
+
+/// Splits `source` into tokens, with the line each starts on
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    const PUNCTS: &[&str] = &["->", "{", "}", "(", ")", "<", ">", ",", ":"];
+
+    let mut tokens = vec![];
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = match line.find("//") {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            if let Some(punct) = PUNCTS.iter().find(|punct| rest.starts_with(*punct)) {
+                tokens.push((Token::Punct(*punct), line_number));
+                rest = &rest[punct.len()..];
+            } else if rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                // `-` only joins words, so `a->` is `a` then `->`
+                let bytes = rest.as_bytes();
+                let mut end = 0;
+                while end < bytes.len() {
+                    let joins_words = bytes[end] == b'-'
+                        && bytes
+                            .get(end + 1)
+                            .map_or(false, |next| next.is_ascii_alphanumeric());
+                    if bytes[end].is_ascii_alphanumeric() || bytes[end] == b'_' || joins_words {
+                        end += 1;
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((Token::Ident(rest[..end].to_string()), line_number));
+                rest = &rest[end..];
+            } else {
+                return Err(format!(
+                    "line {}: unexpected character `{}`",
+                    line_number,
+                    rest.chars().next().unwrap()
+                ));
+            }
+            rest = rest.trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or_else(|| self.tokens.last())
+            .map(|(_, line)| *line)
+            .unwrap_or(1)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line(), message))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next_ident(&mut self, what: &str) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.position += 1;
+                Ok(ident)
+            }
+            _ => self.error(&format!("expected {}", what)),
+        }
+    }
+
+    fn eat(&mut self, punct: &'static str) -> bool {
+        if self.peek() == Some(&Token::Punct(punct)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            self.error(&format!("expected `{}`", punct))
+        }
+    }
+
+    fn parse_enum(&mut self) -> Result<Enum, String> {
+        let name = self.next_ident("an enum name")?;
+        self.expect("{")?;
+        let mut cases = vec![];
+        while !self.eat("}") {
+            cases.push(self.next_ident("an enum case")?);
+            if !self.eat(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        if cases.is_empty() {
+            return self.error(&format!("enum `{}` has no cases", name));
+        }
+        Ok(Enum { name, cases })
+    }
+
+    fn parse_type(&mut self) -> Result<Type, String> {
+        let name = self.next_ident("a type")?;
+        if let Some(scalar) = Scalar::from_name(&name) {
+            return Ok(Type::Scalar(scalar));
+        }
+        Ok(match name.as_str() {
+            "bool" => Type::Bool,
+            "string" => Type::String,
+            "list" => {
+                self.expect("<")?;
+                let element = self.next_ident("a list element type")?;
+                let scalar = match Scalar::from_name(&element) {
+                    Some(scalar) => scalar,
+                    None => return self.error("list elements must be integers or floats"),
+                };
+                self.expect(">")?;
+                Type::List(scalar)
+            }
+            "expected" => {
+                self.expect("<")?;
+                let value = if self.peek() == Some(&Token::Ident("_".to_string())) {
+                    self.position += 1;
+                    None
+                } else {
+                    match self.parse_type()? {
+                        Type::Expected(..) => return self.error("`expected` can't be nested"),
+                        value => Some(Box::new(value)),
+                    }
+                };
+                self.expect(",")?;
+                let error = self.next_ident("an error enum")?;
+                self.expect(">")?;
+                Type::Expected(value, error)
+            }
+            _ => Type::Enum(name),
+        })
+    }
+
+    fn parse_function(&mut self, name: String) -> Result<Function, String> {
+        self.expect(":")?;
+        if self.next_ident("`function`")? != "function" {
+            self.position -= 1;
+            return self.error("expected `function`");
+        }
+        self.expect("(")?;
+        let mut params = vec![];
+        while !self.eat(")") {
+            let param = self.next_ident("a parameter name")?;
+            self.expect(":")?;
+            let ty = self.parse_type()?;
+            if let Type::Expected(..) = ty {
+                return self.error("parameters can't be `expected`");
+            }
+            params.push((param, ty));
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        let result = if self.eat("->") {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        Ok(Function {
+            name,
+            params,
+            result,
+        })
+    }
+}
+
+/// Parses the enums and functions of a WIT file, checking the types they use
+/// are defined
+pub fn parse(source: &str) -> Result<Interface, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+    };
+    let mut interface = Interface::default();
+    while parser.peek().is_some() {
+        let ident = parser.next_ident("`enum` or a function name")?;
+        if ident == "enum" {
+            interface.enums.push(parser.parse_enum()?);
+        } else {
+            interface.functions.push(parser.parse_function(ident)?);
+        }
+    }
+
+    let mut names = HashSet::new();
+    for name in interface
+        .enums
+        .iter()
+        .map(|e| &e.name)
+        .chain(interface.functions.iter().map(|f| &f.name))
+    {
+        if !names.insert(name) {
+            return Err(format!("`{}` is defined more than once", name));
+        }
+    }
+    let enums = interface
+        .enums
+        .iter()
+        .map(|e| e.name.as_str())
+        .collect::<HashSet<_>>();
+    let check_enum = |name: &str| {
+        if enums.contains(name) {
+            Ok(())
+        } else {
+            Err(format!("unknown type `{}`", name))
+        }
+    };
+    for function in &interface.functions {
+        let types = function
+            .params
+            .iter()
+            .map(|(_, ty)| ty)
+            .chain(function.result.as_ref());
+        for ty in types {
+            match ty {
+                Type::Enum(name) => check_enum(name)?,
+                Type::Expected(value, error) => {
+                    check_enum(error)?;
+                    if let Some(Type::Enum(name)) = value.as_ref().map(|value| &**value) {
+                        check_enum(name)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(interface)
+}
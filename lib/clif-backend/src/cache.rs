@@ -37,6 +37,10 @@ impl CacheGen for CacheGenerator {
             compiled_code,
         ))
     }
+
+    fn shared_code_len(&self) -> Option<usize> {
+        self.backend_cache.reloc_table
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -52,6 +56,9 @@ pub struct BackendCache {
     pub offsets: Map<LocalFuncIndex, usize>,
     pub trap_sink: Arc<TrapSink>,
     pub trampolines: TrampolineCache,
+    /// The offset of the table holding the addresses of the externals in the compiled
+    /// code, when the code is position independent.
+    pub reloc_table: Option<usize>,
 }
 
 impl BackendCache {
@@ -2,8 +2,8 @@
 // and subject to the license https://github.com/CraneStation/cranelift/blob/c47ca7bafc8fc48358f1baa72360e61fc1f7a0f2/cranelift-wasm/LICENSE
 
 use crate::{
-    cache::CacheGenerator, get_isa, module, module::Converter, relocation::call_names,
-    resolver::FuncResolverBuilder, signal::Caller, trampoline::Trampolines,
+    cache::CacheGenerator, get_isa, get_target_isa, module, module::Converter,
+    relocation::call_names, resolver::FuncResolverBuilder, signal::Caller, trampoline::Trampolines,
 };
 
 use cranelift_codegen::entity::EntityRef;
@@ -18,7 +18,7 @@ use std::mem;
 use std::sync::{Arc, RwLock};
 use wasmer_runtime_core::error::CompileError;
 use wasmer_runtime_core::{
-    backend::{Backend, CacheGen, Token},
+    backend::{Backend, CacheGen, CompilerConfig, SpectreMitigations, Token},
     cache::{Artifact, Error as CacheError},
     codegen::*,
     memory::MemoryType,
@@ -38,6 +38,7 @@ pub struct CraneliftModuleCodeGenerator {
     pub clif_signatures: Map<SigIndex, ir::Signature>,
     function_signatures: Option<Arc<Map<FuncIndex, SigIndex>>>,
     functions: Vec<CraneliftFunctionCodeGenerator>,
+    spectre_mitigations: SpectreMitigations,
 }
 
 impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
@@ -51,6 +52,7 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
             functions: vec![],
             function_signatures: None,
             signatures: None,
+            spectre_mitigations: SpectreMitigations::default(),
         }
     }
 
@@ -62,7 +64,48 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         Backend::Cranelift
     }
 
-    fn check_precondition(&mut self, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
+    fn feed_compiler_config(&mut self, config: &CompilerConfig) -> Result<(), CodegenError> {
+        if config.no_signals {
+            return Err(CodegenError {
+                message: "cranelift traps rely on signal handlers".to_string(),
+            });
+        }
+        if config.spectre_mitigations.fence_indirect_calls {
+            return Err(CodegenError {
+                message: "cranelift can't fence indirect calls".to_string(),
+            });
+        }
+        self.spectre_mitigations = config.spectre_mitigations;
+        if config.triple.is_some() || config.cpu_features.is_some() || config.features.deterministic
+        {
+            self.isa = get_target_isa(
+                config.triple.as_ref().map(String::as_str),
+                config.cpu_features.as_ref().map(String::as_str),
+                config.features.deterministic,
+            )
+            .map_err(|message| CodegenError { message })?;
+        }
+        Ok(())
+    }
+
+    fn check_precondition(&mut self, module_info: &ModuleInfo) -> Result<(), CodegenError> {
+        // The bounds checks of dynamic memories are generated by cranelift-wasm.
+        let has_dynamic_memory = module_info
+            .memories
+            .iter()
+            .map(|(_, desc)| desc)
+            .chain(
+                module_info
+                    .imported_memories
+                    .iter()
+                    .map(|(_, (_, desc))| desc),
+            )
+            .any(|desc| desc.memory_type() == MemoryType::Dynamic);
+        if self.spectre_mitigations.mask_memory_accesses && has_dynamic_memory {
+            return Err(CodegenError {
+                message: "cranelift can't mask accesses to dynamic memories".to_string(),
+            });
+        }
         Ok(())
     }
 
@@ -98,6 +141,7 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
                 module_info: Arc::clone(&module_info),
                 target_config: self.isa.frontend_config().clone(),
                 clif_signatures: self.clif_signatures.clone(),
+                spectre_mitigations: self.spectre_mitigations,
             },
         };
 
@@ -245,6 +289,7 @@ pub struct FunctionEnvironment {
     module_info: Arc<RwLock<ModuleInfo>>,
     target_config: isa::TargetFrontendConfig,
     clif_signatures: Map<SigIndex, ir::Signature>,
+    spectre_mitigations: SpectreMitigations,
 }
 
 impl TargetEnvironment for FunctionEnvironment {
@@ -621,6 +666,24 @@ impl FuncEnvironment for FunctionEnvironment {
         // Get the pointer type based on machine's pointer size.
         let ptr_type = self.pointer_type();
 
+        let callee = if self.spectre_mitigations.mask_table_indices {
+            // Check the bounds here, and clamp the index to zero with a data
+            // dependency on the check, so that a mispredicted bounds check
+            // can't load an entry past the table.
+            let bound_gv = pos.func.tables[table].bound_gv;
+            let bound = pos.ins().global_value(ptr_type, bound_gv);
+            let index = pos.ins().uextend(ptr_type, callee);
+            let in_bounds = pos
+                .ins()
+                .icmp(ir::condcodes::IntCC::UnsignedLessThan, index, bound);
+            pos.ins().trapz(in_bounds, ir::TrapCode::TableOutOfBounds);
+            let mask = pos.ins().bint(ir::types::I32, in_bounds);
+            let mask = pos.ins().ineg(mask);
+            pos.ins().band(callee, mask)
+        } else {
+            callee
+        };
+
         // The `callee` value is an index into a table of Anyfunc structures.
         let entry_addr = pos.ins().table_addr(ptr_type, table, callee, 0);
 
@@ -937,28 +1000,34 @@ impl FuncEnvironment for FunctionEnvironment {
 
         Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
+    /// Generates code corresponding to wasm `memory.copy`, as a call to
+    /// the runtime copying with `memmove`.
     fn translate_memory_copy(
         &mut self,
-        _pos: FuncCursor,
-        _clif_mem_index: cranelift_wasm::MemoryIndex,
+        pos: FuncCursor,
+        clif_mem_index: cranelift_wasm::MemoryIndex,
         _heap: ir::Heap,
-        _dst: ir::Value,
-        _src: ir::Value,
-        _len: ir::Value,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("memory.copy not yet implemented");
+        self.call_bulk_memory_op(pos, clif_mem_index, call_names::MEM_COPY, dst, src, len);
+        Ok(())
     }
 
+    /// Generates code corresponding to wasm `memory.fill`, as a call to
+    /// the runtime filling with `memset`.
     fn translate_memory_fill(
         &mut self,
-        _pos: FuncCursor,
-        _clif_mem_index: cranelift_wasm::MemoryIndex,
+        pos: FuncCursor,
+        clif_mem_index: cranelift_wasm::MemoryIndex,
         _heap: ir::Heap,
-        _dst: ir::Value,
-        _val: ir::Value,
-        _len: ir::Value,
+        dst: ir::Value,
+        val: ir::Value,
+        len: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("memory.fill not yet implemented");
+        self.call_bulk_memory_op(pos, clif_mem_index, call_names::MEM_FILL, dst, val, len);
+        Ok(())
     }
 
     fn translate_memory_init(
@@ -1054,6 +1123,59 @@ impl FunctionEnvironment {
         // Return signature
         signature
     }
+
+    /// Call the `memory.copy` or `memory.fill` vmcall `name_index` of the
+    /// memory `clif_mem_index`, trapping if it reports an access out of
+    /// bounds.
+    fn call_bulk_memory_op(
+        &self,
+        mut pos: FuncCursor,
+        clif_mem_index: cranelift_wasm::MemoryIndex,
+        name_index: u32,
+        a: ir::Value,
+        b: ir::Value,
+        len: ir::Value,
+    ) {
+        let signature = pos.func.import_signature(ir::Signature {
+            call_conv: self.target_config().default_call_conv,
+            params: vec![
+                ir::AbiParam::special(self.pointer_type(), ir::ArgumentPurpose::VMContext),
+                ir::AbiParam::new(ir::types::I32),
+                ir::AbiParam::new(ir::types::I32),
+                ir::AbiParam::new(ir::types::I32),
+                ir::AbiParam::new(ir::types::I32),
+            ],
+            returns: vec![ir::AbiParam::new(ir::types::I32)],
+        });
+
+        let mem_index: MemoryIndex = Converter(clif_mem_index).into();
+        let (namespace, mem_index) =
+            match mem_index.local_or_import(&self.module_info.read().unwrap()) {
+                LocalOrImport::Local(local_mem_index) => {
+                    (call_names::LOCAL_NAMESPACE, local_mem_index.index())
+                }
+                LocalOrImport::Import(import_mem_index) => {
+                    (call_names::IMPORT_NAMESPACE, import_mem_index.index())
+                }
+            };
+
+        let func = pos.func.import_function(ir::ExtFuncData {
+            name: ir::ExternalName::user(namespace, name_index),
+            signature,
+            colocated: false,
+        });
+
+        let const_mem_index = pos.ins().iconst(ir::types::I32, mem_index as i64);
+        let vmctx = pos
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("missing vmctx parameter");
+
+        let call_inst = pos.ins().call(func, &[vmctx, const_mem_index, a, b, len]);
+        let out_of_bounds = *pos.func.dfg.inst_results(call_inst).first().unwrap();
+        pos.ins()
+            .trapnz(out_of_bounds, ir::TrapCode::HeapOutOfBounds);
+    }
 }
 
 impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
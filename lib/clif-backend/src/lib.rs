@@ -28,7 +28,9 @@ use cranelift_codegen::{
     isa,
     settings::{self, Configurable},
 };
+use std::str::FromStr;
 use target_lexicon::Triple;
+use wasmer_runtime_core::backend::CpuFeatures;
 
 #[macro_use]
 extern crate serde_derive;
@@ -37,6 +39,18 @@ extern crate rayon;
 extern crate serde;
 
 fn get_isa() -> Box<dyn isa::TargetIsa> {
+    get_target_isa(None, None, false).unwrap()
+}
+
+/// Create the ISA for `triple`, or the host, with the given LLVM-style
+/// `cpu_features`.  Features Cranelift doesn't know about are ignored.
+/// With `nan_canonicalization`, floating point instructions return the
+/// canonical NaN instead of the one the hardware produces.
+fn get_target_isa(
+    triple: Option<&str>,
+    cpu_features: Option<&str>,
+    nan_canonicalization: bool,
+) -> Result<Box<dyn isa::TargetIsa>, String> {
     let flags = {
         let mut builder = settings::builder();
         builder.set("opt_level", "speed_and_size").unwrap();
@@ -45,12 +59,46 @@ fn get_isa() -> Box<dyn isa::TargetIsa> {
         if cfg!(not(test)) {
             builder.set("enable_verifier", "false").unwrap();
         }
+        if nan_canonicalization {
+            builder
+                .set("enable_nan_canonicalization", "true")
+                .map_err(|e| format!("can't canonicalize NaNs: {:?}", e))?;
+        }
 
         let flags = settings::Flags::new(builder);
         debug_assert_eq!(flags.opt_level(), settings::OptLevel::SpeedAndSize);
         flags
     };
-    isa::lookup(Triple::host()).unwrap().finish(flags)
+
+    let triple = match triple {
+        Some(triple) => Triple::from_str(triple)
+            .map_err(|e| format!("invalid target triple `{}`: {}", triple, e))?,
+        None => Triple::host(),
+    };
+    let mut builder = isa::lookup(triple.clone())
+        .map_err(|e| format!("unsupported target `{}`: {:?}", triple, e))?;
+
+    if let Some(cpu_features) = cpu_features {
+        let cpu_features = CpuFeatures::from_str(cpu_features)?;
+        for feature in cpu_features.iter() {
+            let setting = match feature {
+                "sse3" => "has_sse3",
+                "ssse3" => "has_ssse3",
+                "sse4.1" => "has_sse41",
+                "sse4.2" => "has_sse42",
+                "popcnt" => "has_popcnt",
+                "bmi" => "has_bmi1",
+                "bmi2" => "has_bmi2",
+                "lzcnt" => "has_lzcnt",
+                _ => continue,
+            };
+            builder
+                .enable(setting)
+                .map_err(|e| format!("cannot enable `{}` for `{}`: {:?}", feature, triple, e))?;
+        }
+    }
+
+    Ok(builder.finish(flags))
 }
 
 /// The current version of this crate
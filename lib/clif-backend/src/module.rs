@@ -45,6 +45,7 @@ impl Module {
             runnable_module: Arc::new(Box::new(runnable_module)),
             cache_gen,
             info,
+            compile_info: None,
         })
     }
 }
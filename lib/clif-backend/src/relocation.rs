@@ -20,6 +20,8 @@ pub mod call_names {
     pub const SHARED_STATIC_MEM_SIZE: u32 = 3;
     pub const DYNAMIC_MEM_GROW: u32 = 4;
     pub const DYNAMIC_MEM_SIZE: u32 = 5;
+    pub const MEM_COPY: u32 = 6;
+    pub const MEM_FILL: u32 = 7;
 }
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
@@ -73,6 +75,9 @@ pub enum VmCallKind {
 
     DynamicMemoryGrow,
     DynamicMemorySize,
+
+    MemoryCopy,
+    MemoryFill,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -146,6 +151,9 @@ impl binemit::RelocSink for RelocSink {
 
                         DYNAMIC_MEM_GROW => VmCallKind::DynamicMemoryGrow,
                         DYNAMIC_MEM_SIZE => VmCallKind::DynamicMemorySize,
+
+                        MEM_COPY => VmCallKind::MemoryCopy,
+                        MEM_FILL => VmCallKind::MemoryFill,
                         _ => unimplemented!("reloc_external VmCall::Local {}", index),
                     })),
                     IMPORT_NAMESPACE => RelocationType::VmCall(VmCall::Import(match index {
@@ -157,6 +165,9 @@ impl binemit::RelocSink for RelocSink {
 
                         DYNAMIC_MEM_GROW => VmCallKind::DynamicMemoryGrow,
                         DYNAMIC_MEM_SIZE => VmCallKind::DynamicMemorySize,
+
+                        MEM_COPY => VmCallKind::MemoryCopy,
+                        MEM_FILL => VmCallKind::MemoryFill,
                         _ => unimplemented!("reloc_external VmCall::Import {}", index),
                     })),
                     SIG_NAMESPACE => RelocationType::Signature(SigIndex::new(index as usize)),
@@ -58,6 +58,7 @@ pub struct FuncResolverBuilder {
     local_relocs: Map<LocalFuncIndex, Box<[LocalRelocation]>>,
     external_relocs: Map<LocalFuncIndex, Box<[ExternalRelocation]>>,
     import_len: usize,
+    reloc_table: Option<usize>,
 }
 
 pub struct NoopStackmapSink {}
@@ -71,8 +72,11 @@ impl FuncResolverBuilder {
         mut code: Memory,
         info: &ModuleInfo,
     ) -> Result<(Self, Arc<Trampolines>, HandlerData), CacheError> {
+        // Position independent code may be mapped shared and read-only, only its
+        // relocation table is written to.
+        let writable = backend_cache.reloc_table.unwrap_or(0);
         unsafe {
-            code.protect(.., Protect::ReadWrite)
+            code.protect(writable.., Protect::ReadWrite)
                 .map_err(|e| CacheError::Unknown(e.to_string()))?;
         }
 
@@ -86,6 +90,7 @@ impl FuncResolverBuilder {
                 local_relocs: Map::new(),
                 external_relocs: backend_cache.external_relocs,
                 import_len: info.imported_functions.len(),
+                reloc_table: backend_cache.reloc_table,
             },
             Arc::new(Trampolines::from_trampoline_cache(
                 backend_cache.trampolines,
@@ -149,7 +154,37 @@ impl FuncResolverBuilder {
             external_relocs.push(reloc_sink.external_relocs.into_boxed_slice());
         }
 
-        let mut memory = Memory::with_size(total_size)
+        // When every external relocation can be redirected through it, the addresses
+        // of the functions outside of the module are stored in a table following the
+        // code, so the code itself doesn't depend on where they are in this process.
+        let position_independent =
+            code_bufs
+                .iter()
+                .zip(external_relocs.iter())
+                .all(|(code_buf, (_, relocs))| {
+                    relocs.iter().all(|reloc| match reloc.reloc {
+                        Reloc::Abs8 => is_movabs(code_buf, reloc.offset as usize),
+                        Reloc::X86CallPCRel4 => true,
+                        Reloc::X86PCRel4 => false,
+                    })
+                });
+        let num_external_relocs: usize = external_relocs.iter().map(|(_, r)| r.len()).sum();
+        let num_stubs = external_relocs
+            .iter()
+            .flat_map(|(_, relocs)| relocs.iter())
+            .filter(|reloc| reloc.reloc == Reloc::X86CallPCRel4)
+            .count();
+        let (reloc_table, memory_size) = if position_independent {
+            let reloc_table = round_up(total_size + num_stubs * STUB_SIZE, page_size::get());
+            (
+                Some(reloc_table),
+                reloc_table + num_external_relocs * mem::size_of::<u64>(),
+            )
+        } else {
+            (None, total_size)
+        };
+
+        let mut memory = Memory::with_size_for_code(memory_size)
             .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
         unsafe {
             memory
@@ -193,13 +228,50 @@ impl FuncResolverBuilder {
             local_relocs,
             external_relocs,
             import_len: info.imported_functions.len(),
+            reloc_table,
         };
 
+        if let Some(reloc_table) = reloc_table {
+            func_resolver_builder.redirect_externals(total_size, reloc_table);
+        }
         func_resolver_builder.relocate_locals();
 
         Ok((func_resolver_builder, handler_data))
     }
 
+    /// Redirect the external relocations to the table at `reloc_table`, the `n`th
+    /// relocation to the `n`th slot.
+    ///
+    /// The `movabs` instructions loading an address become RIP-relative loads from
+    /// the table, and calls jump to a stub placed after the code at `stubs`, which
+    /// jumps through the table.
+    fn redirect_externals(&mut self, stubs: usize, reloc_table: usize) {
+        let code = unsafe { self.memory.as_slice_mut() };
+        let mut slot = reloc_table;
+        let mut stub = stubs;
+        for (index, relocs) in self.external_relocs.iter() {
+            for reloc in relocs.iter() {
+                let reloc_offset = self.map[index] + reloc.offset as usize;
+                match reloc.reloc {
+                    Reloc::Abs8 => load_from_slot(code, reloc_offset, slot),
+                    Reloc::X86CallPCRel4 => {
+                        jump_through_slot(code, stub, slot);
+                        let reloc_delta = (stub as isize)
+                            .wrapping_sub(reloc_offset as isize)
+                            .wrapping_add(reloc.addend as isize);
+                        LittleEndian::write_u32(
+                            &mut code[reloc_offset..reloc_offset + 4],
+                            reloc_delta as u32,
+                        );
+                        stub += STUB_SIZE;
+                    }
+                    Reloc::X86PCRel4 => unreachable!("PC relative reference to an external"),
+                }
+                slot += mem::size_of::<u64>();
+            }
+        }
+    }
+
     fn relocate_locals(&mut self) {
         for (index, relocs) in self.local_relocs.iter() {
             for ref reloc in relocs.iter() {
@@ -232,6 +304,7 @@ impl FuncResolverBuilder {
         trampolines: Arc<Trampolines>,
         handler_data: HandlerData,
     ) -> CompileResult<(FuncResolver, BackendCache)> {
+        let mut slot = self.reloc_table;
         for (index, relocs) in self.external_relocs.iter() {
             for ref reloc in relocs.iter() {
                 let target_func_address: isize = match reloc.target {
@@ -266,6 +339,8 @@ impl FuncResolverBuilder {
                             VmCallKind::DynamicMemorySize => {
                                 vmcalls::local_dynamic_memory_size as _
                             }
+                            VmCallKind::MemoryCopy => vmcalls::local_memory_copy as _,
+                            VmCallKind::MemoryFill => vmcalls::local_memory_fill as _,
                         },
                         VmCall::Import(kind) => match kind {
                             VmCallKind::StaticMemoryGrow | VmCallKind::SharedStaticMemoryGrow => {
@@ -280,6 +355,8 @@ impl FuncResolverBuilder {
                             VmCallKind::DynamicMemorySize => {
                                 vmcalls::imported_dynamic_memory_size as _
                             }
+                            VmCallKind::MemoryCopy => vmcalls::imported_memory_copy as _,
+                            VmCallKind::MemoryFill => vmcalls::imported_memory_fill as _,
                         },
                     },
                     RelocationType::Signature(sig_index) => {
@@ -289,6 +366,24 @@ impl FuncResolverBuilder {
                     }
                 };
 
+                // Position independent code finds the address in its slot of the
+                // relocation table, which is private to this process.
+                if let Some(slot_offset) = slot {
+                    let address = match reloc.reloc {
+                        Reloc::Abs8 => target_func_address.wrapping_add(reloc.addend as isize),
+                        // The stub jumps to where the call would have.
+                        Reloc::X86PCRel4 | Reloc::X86CallPCRel4 => target_func_address
+                            .wrapping_add(reloc.addend as isize)
+                            .wrapping_add(4),
+                    };
+                    LittleEndian::write_u64(
+                        unsafe { &mut self.memory.as_slice_mut()[slot_offset..slot_offset + 8] },
+                        address as u64,
+                    );
+                    slot = Some(slot_offset + mem::size_of::<u64>());
+                    continue;
+                }
+
                 // We need the address of the current function
                 // because some of these calls are relative.
                 let func_addr = lookup_func(&self.map, &self.memory, index)
@@ -321,8 +416,17 @@ impl FuncResolverBuilder {
         }
 
         unsafe {
+            let code_end = match self.reloc_table {
+                Some(reloc_table) if reloc_table < self.memory.size() => {
+                    self.memory
+                        .protect(reloc_table.., Protect::Read)
+                        .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+                    reloc_table
+                }
+                _ => self.memory.size(),
+            };
             self.memory
-                .protect(.., Protect::ReadExec)
+                .protect(..code_end, Protect::ReadExec)
                 .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
         }
 
@@ -331,6 +435,7 @@ impl FuncResolverBuilder {
             offsets: self.map.clone(),
             trap_sink: handler_data.trap_data,
             trampolines: trampolines.to_trampoline_cache(),
+            reloc_table: self.reloc_table,
         };
 
         Ok((
@@ -358,6 +463,45 @@ impl FuncResolver {
     }
 }
 
+/// The size of a stub jumping through the relocation table: a `jmp [rip + disp32]`
+/// padded with `int3`.
+const STUB_SIZE: usize = 8;
+
+/// Whether the 8 byte immediate at `offset` of `code` is that of a
+/// `movabs r64, imm64`, as emitted to load the address of an external.
+fn is_movabs(code: &[u8], offset: usize) -> bool {
+    offset >= 2
+        && offset + 8 <= code.len()
+        && code[offset - 2] & 0xfe == 0x48
+        && code[offset - 1] & 0xf8 == 0xb8
+}
+
+/// Rewrite the `movabs r64, imm64` whose immediate is at `offset` of `code` into a
+/// `mov r64, [rip + disp32]` loading from `slot`, padded with a 3 byte `nop` to the
+/// same 10 bytes.
+fn load_from_slot(code: &mut [u8], offset: usize, slot: usize) {
+    let start = offset - 2;
+    let rex_b = code[start] & 1;
+    let reg = code[start + 1] & 7;
+    let disp = (slot as isize).wrapping_sub((start + 7) as isize);
+
+    code[start] = 0x48 | rex_b << 2;
+    code[start + 1] = 0x8b;
+    code[start + 2] = 0x05 | reg << 3;
+    LittleEndian::write_u32(&mut code[start + 3..start + 7], disp as u32);
+    code[start + 7..start + 10].copy_from_slice(&[0x0f, 0x1f, 0x00]);
+}
+
+/// Write a stub at `stub` of `code` jumping to the address in `slot`.
+fn jump_through_slot(code: &mut [u8], stub: usize, slot: usize) {
+    let disp = (slot as isize).wrapping_sub((stub + 6) as isize);
+
+    code[stub] = 0xff;
+    code[stub + 1] = 0x25;
+    LittleEndian::write_u32(&mut code[stub + 2..stub + 6], disp as u32);
+    code[stub + 6..stub + STUB_SIZE].copy_from_slice(&[0xcc, 0xcc]);
+}
+
 #[inline]
 fn round_up(n: usize, multiple: usize) -> usize {
     (n + multiple - 1) & !(multiple - 1)
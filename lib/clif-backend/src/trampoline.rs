@@ -39,7 +39,7 @@ impl Trampolines {
         //     offsets: HashMap<SigIndex, usize>,
         // }
 
-        let mut memory = Memory::with_size(cache.code.len()).unwrap();
+        let mut memory = Memory::with_size_for_code(cache.code.len()).unwrap();
         unsafe {
             memory.protect(.., Protect::ReadWrite).unwrap();
 
@@ -106,7 +106,7 @@ impl Trampolines {
             compiled_functions.push((sig_index, code_buf));
         }
 
-        let mut memory = Memory::with_size(total_size).unwrap();
+        let mut memory = Memory::with_size_for_code(total_size).unwrap();
         unsafe {
             memory.protect(.., Protect::ReadWrite).unwrap();
         }
@@ -2,7 +2,10 @@
 mod tests {
     use std::sync::Arc;
     use wabt::wat2wasm;
-    use wasmer_emscripten::is_emscripten_module;
+    use wasmer_emscripten::{
+        generate_emscripten_env, is_emscripten_module, run_emscripten_instance, EmscriptenGlobals,
+        NetworkAccess,
+    };
     use wasmer_runtime::compile;
 
     #[test]
@@ -22,4 +25,94 @@ mod tests {
         let module = Arc::new(module);
         assert!(!is_emscripten_module(&module));
     }
+
+    fn run_main(wast_bytes: &[u8]) -> EmscriptenGlobals {
+        run_main_with(wast_bytes, |_| {})
+    }
+
+    fn run_main_with<F>(wast_bytes: &[u8], set_up: F) -> EmscriptenGlobals
+    where
+        F: FnOnce(&mut EmscriptenGlobals),
+    {
+        let wasm_binary = wat2wasm(wast_bytes.to_vec()).expect("Can't convert to wasm");
+        let module = compile(&wasm_binary[..]).expect("WASM can't be compiled");
+        let mut globals = EmscriptenGlobals::new(&module).expect("globals are valid");
+        set_up(&mut globals);
+        let import_object = generate_emscripten_env(&mut globals);
+        let mut instance = module
+            .instantiate(&import_object)
+            .expect("WASM can't be instantiated");
+        run_emscripten_instance(
+            &module,
+            &mut instance,
+            &mut globals,
+            "test",
+            vec![],
+            None,
+            vec![],
+        )
+        .expect("run_emscripten_instance finishes");
+        globals
+    }
+
+    #[test]
+    fn should_grow_memory_for_sbrk() {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/memory_growth.wast");
+        let globals = run_main(WAST_BYTES);
+        // 16MiB doubles to 64MiB for the first block, then to 128MiB
+        assert_eq!(globals.memory.size().0, 2048);
+        let view = globals.memory.view::<i32>();
+        let (first, second) = (view[4].get(), view[5].get());
+        assert_ne!(first, -1);
+        assert_eq!(second, first + 33554432);
+        assert_eq!(view[(first as usize + 33554428) / 4].get(), 42);
+        assert_eq!(view[(second as usize + 67108860) / 4].get(), 42);
+    }
+
+    #[test]
+    fn should_fail_sbrk_past_the_maximum_memory() {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/memory_growth_max.wast");
+        let globals = run_main(WAST_BYTES);
+        assert_eq!(globals.memory.size().0, 256);
+        assert_eq!(globals.memory.view::<i32>()[4].get(), -1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_deny_sockets_without_network_access() {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/network_connect.wast");
+        let globals = run_main(WAST_BYTES);
+        let view = globals.memory.view::<i32>();
+        // EACCES
+        assert_eq!(view[4].get(), -13);
+        assert_eq!(view[5].get(), -13);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_deny_connecting_outside_the_allowlist() {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/network_connect.wast");
+        let globals = run_main_with(WAST_BYTES, |globals| {
+            let rule = "127.0.0.1:1".parse().expect("the rule is valid");
+            globals.set_network_access(NetworkAccess::Allowlist(vec![rule]));
+        });
+        let view = globals.memory.view::<i32>();
+        assert!(view[4].get() >= 0);
+        assert_eq!(view[5].get(), -13);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_read_preloaded_files() {
+        const WAST_BYTES: &[u8] = include_bytes!("tests/read_preloaded_file.wast");
+        let globals = run_main_with(WAST_BYTES, |globals| {
+            globals
+                .preload_file("data/hello.txt", b"hello")
+                .expect("the file can be preloaded");
+        });
+        let view = globals.memory.view::<u8>();
+        assert_eq!(globals.memory.view::<i32>()[4].get(), 5);
+        let contents: Vec<u8> = view[256..261].iter().map(|byte| byte.get()).collect();
+        assert_eq!(&contents[..], b"hello");
+    }
 }
@@ -0,0 +1,28 @@
+(module
+ (import "env" "memory" (memory $0 256))
+ (import "env" "table" (table 0 anyfunc))
+ (import "env" "_emscripten_memcpy_big" (func $_emscripten_memcpy_big (param i32 i32 i32) (result i32)))
+ (import "env" "_sbrk" (func $_sbrk (param i32) (result i32)))
+ (export "_main" (func $_main))
+ ;; Takes 32MiB and then 64MiB from sbrk, past the initial 16MiB, and writes
+ ;; to the last word of each block. sbrk's results are stored at 16 and 20.
+ (func $_main
+  (local $block i32)
+  (i32.store
+   (i32.const 16)
+   (tee_local $block (call $_sbrk (i32.const 33554432)))
+  )
+  (i32.store
+   (i32.add (get_local $block) (i32.const 33554428))
+   (i32.const 42)
+  )
+  (i32.store
+   (i32.const 20)
+   (tee_local $block (call $_sbrk (i32.const 67108864)))
+  )
+  (i32.store
+   (i32.add (get_local $block) (i32.const 67108860))
+   (i32.const 42)
+  )
+ )
+)
@@ -0,0 +1,15 @@
+(module
+ (import "env" "memory" (memory $0 256 256))
+ (import "env" "table" (table 0 anyfunc))
+ (import "env" "_emscripten_memcpy_big" (func $_emscripten_memcpy_big (param i32 i32 i32) (result i32)))
+ (import "env" "_sbrk" (func $_sbrk (param i32) (result i32)))
+ (export "_main" (func $_main))
+ ;; Takes 32MiB from sbrk, which the maximum of 16MiB doesn't allow. sbrk's
+ ;; result is stored at 16.
+ (func $_main
+  (i32.store
+   (i32.const 16)
+   (call $_sbrk (i32.const 33554432))
+  )
+ )
+)
@@ -0,0 +1,28 @@
+(module
+ (import "env" "memory" (memory $0 256))
+ (import "env" "table" (table 0 anyfunc))
+ (import "env" "___syscall102" (func $___syscall102 (param i32 i32) (result i32)))
+ (export "_main" (func $_main))
+ ;; socketcall varargs for socket(AF_INET, SOCK_STREAM, 0)
+ (data (i32.const 32) "\01\00\00\00\30\00\00\00")
+ (data (i32.const 48) "\02\00\00\00\01\00\00\00\00\00\00\00")
+ ;; sockaddr_in for 127.0.0.1:9
+ (data (i32.const 64) "\02\00\00\09\7f\00\00\01\00\00\00\00\00\00\00\00")
+ ;; socketcall varargs for connect(fd, 64, 16), the fd being stored at 112
+ (data (i32.const 96) "\03\00\00\00\70\00\00\00")
+ (data (i32.const 116) "\40\00\00\00\10\00\00\00")
+ ;; Creates a socket and connects it to 127.0.0.1:9. The results are stored
+ ;; at 16 and 20.
+ (func $_main
+  (local $fd i32)
+  (i32.store
+   (i32.const 16)
+   (tee_local $fd (call $___syscall102 (i32.const 102) (i32.const 32)))
+  )
+  (i32.store (i32.const 112) (get_local $fd))
+  (i32.store
+   (i32.const 20)
+   (call $___syscall102 (i32.const 102) (i32.const 96))
+  )
+ )
+)
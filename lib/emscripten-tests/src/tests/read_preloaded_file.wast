@@ -0,0 +1,27 @@
+(module
+ (import "env" "memory" (memory $0 256))
+ (import "env" "table" (table 0 anyfunc))
+ (import "env" "___syscall3" (func $___syscall3 (param i32 i32) (result i32)))
+ (import "env" "___syscall5" (func $___syscall5 (param i32 i32) (result i32)))
+ (import "env" "___syscall6" (func $___syscall6 (param i32 i32) (result i32)))
+ (export "_main" (func $_main))
+ ;; open varargs for open("data/hello.txt", O_RDONLY, 0)
+ (data (i32.const 32) "\80\00\00\00\00\00\00\00\00\00\00\00")
+ ;; read varargs for read(fd, 256, 16), the fd being stored at 48
+ (data (i32.const 52) "\00\01\00\00\10\00\00\00")
+ (data (i32.const 128) "data/hello.txt\00")
+ ;; Opens data/hello.txt and reads up to 16 bytes of it to 256. The number
+ ;; of bytes read is stored at 16.
+ (func $_main
+  (local $fd i32)
+  (i32.store
+   (i32.const 48)
+   (tee_local $fd (call $___syscall5 (i32.const 5) (i32.const 32)))
+  )
+  (i32.store
+   (i32.const 16)
+   (call $___syscall3 (i32.const 3) (i32.const 48))
+  )
+  (drop (call $___syscall6 (i32.const 6) (i32.const 48)))
+ )
+)
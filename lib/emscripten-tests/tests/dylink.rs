@@ -0,0 +1,79 @@
+use wabt::wat2wasm;
+use wasmer_emscripten::{generate_emscripten_env, run_emscripten_instance, EmscriptenGlobals};
+use wasmer_runtime::{compile, default_compiler};
+
+// Loads `side.wasm`, calls its `twice` through the table, and stores the
+// handle, the result and what `dlclose` returned at 4, 8 and 12.
+const MAIN_MODULE: &str = r#"
+(module
+  (type $twice (func (param i32) (result i32)))
+  (import "env" "memory" (memory 256 256))
+  (import "env" "table" (table 0 anyfunc))
+  (import "env" "_dlopen" (func $dlopen (param i32 i32) (result i32)))
+  (import "env" "_dlsym" (func $dlsym (param i32 i32) (result i32)))
+  (import "env" "_dlclose" (func $dlclose (param i32) (result i32)))
+  (data (i32.const 16) "side.wasm\00")
+  (data (i32.const 32) "twice\00")
+
+  (func (export "_main") (result i32)
+    (local $handle i32)
+    (set_local $handle (call $dlopen (i32.const 16) (i32.const 0)))
+    (i32.store (i32.const 4) (get_local $handle))
+    (i32.store (i32.const 8)
+      (call_indirect (type $twice)
+        (i32.const 21)
+        (call $dlsym (get_local $handle) (i32.const 32))))
+    (i32.store (i32.const 12) (call $dlclose (get_local $handle)))
+    (i32.const 0)))
+"#;
+
+// fastcomp prefixes C symbols with an underscore.
+const SIDE_MODULE: &str = r#"
+(module
+  (func (export "_twice") (param i32) (result i32)
+    (i32.mul (get_local 0) (i32.const 2))))
+"#;
+
+/// A `dylink` section for a side module without data or table elements.
+const DYLINK_SECTION: &[u8] = &[0, 11, 6, b'd', b'y', b'l', b'i', b'n', b'k', 0, 0, 0, 0];
+
+#[test]
+fn dlopen_loads_a_side_module() {
+    let main_wasm = wat2wasm(MAIN_MODULE).unwrap();
+    let mut side_wasm = wat2wasm(SIDE_MODULE).unwrap();
+    side_wasm.extend_from_slice(DYLINK_SECTION);
+
+    let module = compile(&main_wasm).unwrap();
+    let mut globals = EmscriptenGlobals::new(&module).unwrap();
+    globals
+        .add_side_module("side.wasm", &side_wasm, &default_compiler())
+        .unwrap();
+    let import_object = generate_emscripten_env(&mut globals);
+    let mut instance = module.instantiate(&import_object).unwrap();
+
+    run_emscripten_instance(
+        &module,
+        &mut instance,
+        &mut globals,
+        "main",
+        vec![],
+        None,
+        vec![],
+    )
+    .unwrap();
+
+    let view = globals.memory.view::<i32>();
+    assert_eq!(view[1].get(), 1, "dlopen returns the first handle");
+    assert_eq!(view[2].get(), 42, "twice(21) is called through the table");
+    assert_eq!(view[3].get(), 0, "dlclose succeeds");
+}
+
+#[test]
+fn side_modules_need_a_dylink_section() {
+    let side_wasm = wat2wasm(SIDE_MODULE).unwrap();
+    let module = compile(&wat2wasm(MAIN_MODULE).unwrap()).unwrap();
+    let mut globals = EmscriptenGlobals::new(&module).unwrap();
+    assert!(globals
+        .add_side_module("side.wasm", &side_wasm, &default_compiler())
+        .is_err());
+}
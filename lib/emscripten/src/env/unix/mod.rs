@@ -90,14 +90,21 @@ pub fn _getpwnam(ctx: &mut Ctx, name_ptr: c_int) -> c_int {
     unsafe {
         let passwd = &*libc_getpwnam(name.as_ptr());
         let passwd_struct_offset = call_malloc(ctx, mem::size_of::<GuestPasswd>() as _);
+        // Copying the strings may grow the memory and move it, so the
+        // struct's pointer is taken afterwards
+        let pw_name = copy_cstr_into_wasm(ctx, passwd.pw_name);
+        let pw_passwd = copy_cstr_into_wasm(ctx, passwd.pw_passwd);
+        let pw_gecos = copy_cstr_into_wasm(ctx, passwd.pw_gecos);
+        let pw_dir = copy_cstr_into_wasm(ctx, passwd.pw_dir);
+        let pw_shell = copy_cstr_into_wasm(ctx, passwd.pw_shell);
 
         let passwd_struct_ptr =
             emscripten_memory_pointer!(ctx.memory(0), passwd_struct_offset) as *mut GuestPasswd;
-        (*passwd_struct_ptr).pw_name = copy_cstr_into_wasm(ctx, passwd.pw_name);
-        (*passwd_struct_ptr).pw_passwd = copy_cstr_into_wasm(ctx, passwd.pw_passwd);
-        (*passwd_struct_ptr).pw_gecos = copy_cstr_into_wasm(ctx, passwd.pw_gecos);
-        (*passwd_struct_ptr).pw_dir = copy_cstr_into_wasm(ctx, passwd.pw_dir);
-        (*passwd_struct_ptr).pw_shell = copy_cstr_into_wasm(ctx, passwd.pw_shell);
+        (*passwd_struct_ptr).pw_name = pw_name;
+        (*passwd_struct_ptr).pw_passwd = pw_passwd;
+        (*passwd_struct_ptr).pw_gecos = pw_gecos;
+        (*passwd_struct_ptr).pw_dir = pw_dir;
+        (*passwd_struct_ptr).pw_shell = pw_shell;
         (*passwd_struct_ptr).pw_uid = passwd.pw_uid;
         (*passwd_struct_ptr).pw_gid = passwd.pw_gid;
 
@@ -125,13 +132,18 @@ pub fn _getgrnam(ctx: &mut Ctx, name_ptr: c_int) -> c_int {
     unsafe {
         let group = &*libc_getgrnam(name.as_ptr());
         let group_struct_offset = call_malloc(ctx, mem::size_of::<GuestGroup>() as _);
+        // Copying the strings may grow the memory and move it, so the
+        // struct's pointer is taken afterwards
+        let gr_name = copy_cstr_into_wasm(ctx, group.gr_name);
+        let gr_passwd = copy_cstr_into_wasm(ctx, group.gr_passwd);
+        let gr_mem = copy_terminated_array_of_cstrs(ctx, group.gr_mem);
 
         let group_struct_ptr =
             emscripten_memory_pointer!(ctx.memory(0), group_struct_offset) as *mut GuestGroup;
-        (*group_struct_ptr).gr_name = copy_cstr_into_wasm(ctx, group.gr_name);
-        (*group_struct_ptr).gr_passwd = copy_cstr_into_wasm(ctx, group.gr_passwd);
+        (*group_struct_ptr).gr_name = gr_name;
+        (*group_struct_ptr).gr_passwd = gr_passwd;
         (*group_struct_ptr).gr_gid = group.gr_gid;
-        (*group_struct_ptr).gr_mem = copy_terminated_array_of_cstrs(ctx, group.gr_mem);
+        (*group_struct_ptr).gr_mem = gr_mem;
 
         group_struct_offset as c_int
     }
@@ -1,13 +1,16 @@
-use super::super::env::call_malloc;
+use super::super::env::{call_malloc, get_emscripten_data};
 use super::super::utils::copy_cstr_into_wasm;
-use libc::{chroot as _chroot, getpwuid as _getpwuid, printf as _printf};
-use std::mem;
+use crate::stdio::{write_captured, StdioSink};
+use libc::{chroot as _chroot, getpwuid as _getpwuid, printf as _printf, snprintf as _snprintf};
+use std::{mem, ptr};
 
 use wasmer_runtime_core::vm::Ctx;
 
 /// putchar
-pub fn putchar(_ctx: &mut Ctx, chr: i32) {
-    unsafe { libc::putchar(chr) };
+pub fn putchar(ctx: &mut Ctx, chr: i32) {
+    if write_captured(ctx, 1, &[chr as u8]).is_none() {
+        unsafe { libc::putchar(chr) };
+    }
 }
 
 /// printf
@@ -15,7 +18,18 @@ pub fn printf(ctx: &mut Ctx, memory_offset: i32, extra: i32) -> i32 {
     debug!("emscripten::printf {}, {}", memory_offset, extra);
     unsafe {
         let addr = emscripten_memory_pointer!(ctx.memory(0), memory_offset) as _;
-        _printf(addr, extra)
+        if let StdioSink::Inherit = get_emscripten_data(ctx).globals.stdout {
+            return _printf(addr, extra);
+        }
+        // Format into a buffer to send it to the captured stdout
+        let len = _snprintf(ptr::null_mut(), 0, addr, extra);
+        if len < 0 {
+            return len;
+        }
+        let mut buffer = vec![0u8; len as usize + 1];
+        _snprintf(buffer.as_mut_ptr() as _, buffer.len(), addr, extra);
+        write_captured(ctx, 1, &buffer[..len as usize]);
+        len
     }
 }
 
@@ -45,17 +59,24 @@ pub fn getpwuid(ctx: &mut Ctx, uid: i32) -> i32 {
     unsafe {
         let passwd = &*_getpwuid(uid as _);
         let passwd_struct_offset = call_malloc(ctx, mem::size_of::<GuestPasswd>() as _);
+        // Copying the strings may grow the memory and move it, so the
+        // struct's pointer is taken afterwards
+        let pw_name = copy_cstr_into_wasm(ctx, passwd.pw_name);
+        let pw_passwd = copy_cstr_into_wasm(ctx, passwd.pw_passwd);
+        let pw_gecos = copy_cstr_into_wasm(ctx, passwd.pw_gecos);
+        let pw_dir = copy_cstr_into_wasm(ctx, passwd.pw_dir);
+        let pw_shell = copy_cstr_into_wasm(ctx, passwd.pw_shell);
         let passwd_struct_ptr =
             emscripten_memory_pointer!(ctx.memory(0), passwd_struct_offset) as *mut GuestPasswd;
         assert_eq!(
             passwd_struct_ptr as usize % std::mem::align_of::<GuestPasswd>(),
             0
         );
-        (*passwd_struct_ptr).pw_name = copy_cstr_into_wasm(ctx, passwd.pw_name);
-        (*passwd_struct_ptr).pw_passwd = copy_cstr_into_wasm(ctx, passwd.pw_passwd);
-        (*passwd_struct_ptr).pw_gecos = copy_cstr_into_wasm(ctx, passwd.pw_gecos);
-        (*passwd_struct_ptr).pw_dir = copy_cstr_into_wasm(ctx, passwd.pw_dir);
-        (*passwd_struct_ptr).pw_shell = copy_cstr_into_wasm(ctx, passwd.pw_shell);
+        (*passwd_struct_ptr).pw_name = pw_name;
+        (*passwd_struct_ptr).pw_passwd = pw_passwd;
+        (*passwd_struct_ptr).pw_gecos = pw_gecos;
+        (*passwd_struct_ptr).pw_dir = pw_dir;
+        (*passwd_struct_ptr).pw_shell = pw_shell;
         (*passwd_struct_ptr).pw_uid = passwd.pw_uid;
         (*passwd_struct_ptr).pw_gid = passwd.pw_gid;
 
@@ -1,3 +1,4 @@
+use crate::stdio::write_captured;
 use wasmer_runtime_core::vm::Ctx;
 
 // This may be problematic for msvc which uses inline functions for the printf family
@@ -14,8 +15,10 @@ use wasmer_runtime_core::vm::Ctx;
 //}
 
 /// putchar
-pub fn putchar(_ctx: &mut Ctx, chr: i32) {
-    unsafe { libc::putchar(chr) };
+pub fn putchar(ctx: &mut Ctx, chr: i32) {
+    if write_captured(ctx, 1, &[chr as u8]).is_none() {
+        unsafe { libc::putchar(chr) };
+    }
 }
 
 /// printf
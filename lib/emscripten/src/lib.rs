@@ -16,9 +16,10 @@ extern crate wasmer_runtime_core;
 use lazy_static::lazy_static;
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::{f64, ffi::c_void};
 use wasmer_runtime_core::{
+    backend::Compiler,
     error::{CallError, CallResult, ResolveError},
     export::Export,
     func,
@@ -60,10 +61,12 @@ mod linking;
 mod lock;
 mod math;
 mod memory;
+mod networking;
 mod process;
 mod pthread;
 mod ptr;
 mod signal;
+mod stdio;
 mod storage;
 mod syscalls;
 mod time;
@@ -72,10 +75,14 @@ mod unistd;
 mod utils;
 mod varargs;
 
+pub use self::linking::{DylinkInfo, DynamicLinker, SideModule};
+pub use self::networking::{NetworkAccess, NetworkRule, NetworkRuleError};
+pub use self::stdio::StdioSink;
 pub use self::storage::{align_memory, static_alloc};
 pub use self::utils::{
-    allocate_cstr_on_stack, allocate_on_stack, get_emscripten_memory_size, get_emscripten_metadata,
-    get_emscripten_table_size, is_emscripten_module,
+    allocate_cstr_on_stack, allocate_on_stack, get_emscripten_memory_size,
+    get_emscripten_memory_type, get_emscripten_metadata, get_emscripten_table_size,
+    is_emscripten_module,
 };
 
 // TODO: Magic number - how is this calculated?
@@ -171,6 +178,10 @@ pub struct EmscriptenData<'a> {
     pub stack_restore: Option<Func<'a, i32>>,
     pub set_threw: Option<Func<'a, (i32, i32)>>,
     pub mapped_dirs: HashMap<String, PathBuf>,
+
+    pub dynamic_linker: Option<DynamicLinker>,
+    pub dl_error: Option<String>,
+    pub dl_error_ptr: u32,
 }
 
 impl<'a> EmscriptenData<'a> {
@@ -321,6 +332,10 @@ impl<'a> EmscriptenData<'a> {
             stack_restore,
             set_threw,
             mapped_dirs,
+
+            dynamic_linker: None,
+            dl_error: None,
+            dl_error_ptr: 0,
         }
     }
 }
@@ -389,7 +404,22 @@ pub fn run_emscripten_instance(
     entrypoint: Option<String>,
     mapped_dirs: Vec<(String, PathBuf)>,
 ) -> CallResult<()> {
-    let mut data = EmscriptenData::new(instance, &globals.data, mapped_dirs.into_iter().collect());
+    let dynamic_linker = if globals.side_modules.is_empty() {
+        None
+    } else {
+        Some(DynamicLinker::new(globals, instance))
+    };
+    let mapped_dirs =
+        globals
+            .mapped_dirs
+            .clone()
+            .into_iter()
+            .chain(mapped_dirs.into_iter().map(|(guest_path, host_path)| {
+                (utils::normalize_guest_path(&guest_path), host_path)
+            }))
+            .collect();
+    let mut data = EmscriptenData::new(instance, &globals.data, mapped_dirs);
+    data.dynamic_linker = dynamic_linker;
     let data_ptr = &mut data as *mut _ as *mut c_void;
     instance.context_mut().data = data_ptr;
 
@@ -455,6 +485,9 @@ pub struct EmscriptenGlobalsData {
     table_base: u32,
     temp_double_ptr: u32,
     use_old_abort_on_cannot_grow_memory: bool,
+    stdout: StdioSink,
+    stderr: StdioSink,
+    network: NetworkAccess,
 }
 
 pub struct EmscriptenGlobals {
@@ -466,6 +499,12 @@ pub struct EmscriptenGlobals {
     pub memory_min: Pages,
     pub memory_max: Option<Pages>,
     pub null_func_names: Vec<String>,
+    // The side modules `dlopen` can load, by name
+    pub side_modules: HashMap<String, SideModule>,
+    // The host paths the guest sees at other paths, by guest path
+    pub mapped_dirs: HashMap<String, PathBuf>,
+    // The directory holding the preloaded files, removed with the globals
+    preload_dir: Option<tempfile::TempDir>,
 }
 
 impl EmscriptenGlobals {
@@ -494,8 +533,9 @@ impl EmscriptenGlobals {
         let (table_min, table_max) = get_emscripten_table_size(&module)?;
         let (memory_min, memory_max, shared) = get_emscripten_memory_size(&module)?;
 
-        // Memory initialization
-        let memory_type = MemoryDescriptor::new(memory_min, memory_max, shared)?;
+        // Memory initialization, with the memory type the module was compiled for
+        let mut memory_type = MemoryDescriptor::new(memory_min, memory_max, shared)?;
+        memory_type.memory_type = get_emscripten_memory_type(&module)?;
         let memory = Memory::new(memory_type).unwrap();
 
         let table_type = TableDescriptor {
@@ -538,6 +578,9 @@ impl EmscriptenGlobals {
                 table_base,
                 temp_double_ptr,
                 use_old_abort_on_cannot_grow_memory,
+                stdout: StdioSink::Inherit,
+                stderr: StdioSink::Inherit,
+                network: NetworkAccess::Denied,
             }
         };
 
@@ -566,8 +609,109 @@ impl EmscriptenGlobals {
             memory_min,
             memory_max,
             null_func_names,
+            side_modules: HashMap::new(),
+            mapped_dirs: HashMap::new(),
+            preload_dir: None,
         })
     }
+
+    /// Sends what the guest writes to its standard output to `sink` instead of
+    /// the host's. Set it before creating the `EmscriptenData` of an instance.
+    pub fn set_stdout(&mut self, sink: StdioSink) {
+        self.data.stdout = sink;
+    }
+
+    /// Sends what the guest writes to its standard error to `sink` instead of
+    /// the host's. Set it before creating the `EmscriptenData` of an instance.
+    pub fn set_stderr(&mut self, sink: StdioSink) {
+        self.data.stderr = sink;
+    }
+
+    /// Lets the guest's sockets use the host's network, as far as `access`
+    /// allows. Set it before creating the `EmscriptenData` of an instance.
+    pub fn set_network_access(&mut self, access: NetworkAccess) {
+        self.data.network = access;
+    }
+
+    /// Lets the guest see the host directory, or file, `host_path` at
+    /// `guest_path`, like `--mapdir`
+    pub fn map_dir(&mut self, guest_path: &str, host_path: PathBuf) {
+        self.mapped_dirs
+            .insert(utils::normalize_guest_path(guest_path), host_path);
+    }
+
+    /// Preloads a file with `contents` that the guest sees at `guest_path`.
+    /// Like emscripten's `--preload-file`, the guest's changes to it don't
+    /// reach the host.
+    pub fn preload_file(&mut self, guest_path: &str, contents: &[u8]) -> Result<(), String> {
+        let host_path = self.preload_path(guest_path)?;
+        std::fs::write(&host_path, contents)
+            .map_err(|e| format!("Can't preload {}: {}", guest_path, e))?;
+        self.map_dir(guest_path, host_path);
+        Ok(())
+    }
+
+    /// Preloads a copy of the host directory `host_path` that the guest sees
+    /// at `guest_path`
+    pub fn preload_dir(&mut self, guest_path: &str, host_path: &Path) -> Result<(), String> {
+        let copy_path = self.preload_path(guest_path)?;
+        utils::copy_dir(host_path, &copy_path)
+            .map_err(|e| format!("Can't preload {}: {}", host_path.display(), e))?;
+        self.map_dir(guest_path, copy_path);
+        Ok(())
+    }
+
+    /// Where the preloaded copy of `guest_path` is kept on the host
+    fn preload_path(&mut self, guest_path: &str) -> Result<PathBuf, String> {
+        if self.preload_dir.is_none() {
+            let preload_dir = tempfile::Builder::new()
+                .prefix("wasmer-emscripten-")
+                .tempdir()
+                .map_err(|e| format!("Can't create the preload directory: {}", e))?;
+            self.preload_dir = Some(preload_dir);
+        }
+        let guest_path = Path::new(guest_path);
+        let mut host_path =
+            self.preload_dir
+                .as_ref()
+                .unwrap()
+                .path()
+                .join(if guest_path.is_absolute() {
+                    "absolute"
+                } else {
+                    "relative"
+                });
+        for component in guest_path.components() {
+            match component {
+                Component::Normal(part) => host_path.push(part),
+                Component::ParentDir => {
+                    return Err(format!(
+                        "Can't preload {}: `..` is not allowed",
+                        guest_path.display()
+                    ))
+                }
+                _ => {}
+            }
+        }
+        if let Some(parent) = host_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Can't preload {}: {}", guest_path.display(), e))?;
+        }
+        Ok(host_path)
+    }
+
+    /// Compiles a side module, built with `-s SIDE_MODULE`, that `dlopen` can
+    /// then load by `name`
+    pub fn add_side_module(
+        &mut self,
+        name: &str,
+        wasm: &[u8],
+        compiler: &dyn Compiler,
+    ) -> Result<(), String> {
+        let side_module = SideModule::new(wasm, compiler)?;
+        self.side_modules.insert(name.to_string(), side_module);
+        Ok(())
+    }
 }
 
 pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject {
@@ -1056,10 +1200,13 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
 pub fn nullfunc(ctx: &mut Ctx, _x: u32) {
     use crate::process::abort_with_message;
     debug!("emscripten::nullfunc_i {}", _x);
-    abort_with_message(ctx, "Invalid function pointer. Perhaps this is an invalid value \
+    abort_with_message(
+        ctx,
+        "Invalid function pointer. Perhaps this is an invalid value \
     (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an \
     incorrect type, which will fail? (it is worth building your source files with -Werror (\
-    warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+    warnings are errors), as warnings can indicate undefined behavior which can cause this)",
+    );
 }
 
 /// The current version of this crate
@@ -1,27 +1,525 @@
-use wasmer_runtime_core::vm::Ctx;
+//! Dynamic linking of side modules, for modules built with `-s MAIN_MODULE`.
+//!
+//! Side modules (built with `-s SIDE_MODULE`) share the memory and the table
+//! of the main module. `dlopen` reserves room for a side module in both,
+//! passes it the bases as `__memory_base` and `__table_base`, and resolves its
+//! `GOT.mem` and `GOT.func` imports against the symbols loaded so far.
 
-// TODO: Need to implement.
+use crate::env::{call_malloc, get_emscripten_data};
+use crate::utils::copy_cstr_into_wasm;
+use crate::{generate_emscripten_env, EmscriptenGlobals};
+use libc::c_char;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::path::Path;
+use wasmer_runtime_core::{
+    backend::Compiler,
+    compile_with,
+    export::Export,
+    global::Global,
+    import::ImportObject,
+    table::{Anyfunc, Table},
+    types::{FuncRef, Value},
+    vm::Ctx,
+    Instance, Module,
+};
+
+/// The functions a side module runs once it's instantiated, in this order
+const SIDE_MODULE_INITIALIZERS: &[&str] = &[
+    "__post_instantiate",
+    "__wasm_apply_relocs",
+    "__wasm_call_ctors",
+];
+
+/// The memory and table a side module needs, from its `dylink` section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DylinkInfo {
+    /// The size of the module's data, in bytes
+    pub memory_size: u32,
+    /// The alignment of the module's data, as a power of 2
+    pub memory_align: u32,
+    /// The number of table elements the module uses
+    pub table_size: u32,
+    /// The alignment of the module's table elements, as a power of 2
+    pub table_align: u32,
+}
+
+impl DylinkInfo {
+    /// Reads the `dylink` section of a side module, or the memory info
+    /// subsection of a `dylink.0` section
+    pub fn new(module: &Module) -> Result<Self, String> {
+        if let Some(section) = module.custom_sections("dylink").first() {
+            Self::parse(section)
+        } else if let Some(section) = module.custom_sections("dylink.0").first() {
+            let mut offset = 0;
+            while offset < section.len() {
+                let kind = section[offset];
+                offset += 1;
+                let len = read_leb(section, &mut offset)? as usize;
+                // WASM_DYLINK_MEM_INFO
+                if kind == 1 {
+                    return Self::parse(&section[offset..]);
+                }
+                offset += len;
+            }
+            Err("The dylink.0 section has no memory info".to_string())
+        } else {
+            Err("The module has no dylink section, is it a side module?".to_string())
+        }
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut offset = 0;
+        Ok(DylinkInfo {
+            memory_size: read_leb(bytes, &mut offset)?,
+            memory_align: read_leb(bytes, &mut offset)?,
+            table_size: read_leb(bytes, &mut offset)?,
+            table_align: read_leb(bytes, &mut offset)?,
+        })
+    }
+}
+
+fn read_leb(bytes: &[u8], offset: &mut usize) -> Result<u32, String> {
+    let mut result = 0u32;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes
+            .get(*offset)
+            .ok_or_else(|| "The dylink section is truncated".to_string())?;
+        *offset += 1;
+        if shift >= 32 {
+            return Err("The dylink section has an invalid LEB128 value".to_string());
+        }
+        result |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+/// A side module `dlopen` can load
+#[derive(Clone)]
+pub struct SideModule {
+    /// The compiled side module
+    pub module: Module,
+    /// The memory and table it needs
+    pub dylink: DylinkInfo,
+}
+
+impl SideModule {
+    /// Compiles a side module from its bytes
+    pub fn new(wasm: &[u8], compiler: &dyn Compiler) -> Result<Self, String> {
+        let module = compile_with(wasm, compiler)
+            .map_err(|e| format!("Can't compile the side module: {:?}", e))?;
+        let dylink = DylinkInfo::new(&module)?;
+        Ok(SideModule { module, dylink })
+    }
+}
+
+struct LoadedModule {
+    name: String,
+    instance: Box<Instance>,
+    memory_base: u32,
+    /// The table indices of the functions `dlsym` returned
+    function_indices: HashMap<String, u32>,
+    references: u32,
+}
+
+/// A `GOT.mem` or `GOT.func` import of a side module
+struct GotEntry {
+    symbol: String,
+    is_function: bool,
+    global: Global,
+}
+
+/// The state of `dlopen` and friends
+pub struct DynamicLinker {
+    side_modules: HashMap<String, SideModule>,
+    /// The emscripten environment, which side modules import as the main
+    /// module does
+    env: Vec<(String, String, Export)>,
+    table: Table,
+    /// The functions exported so far, the main module's first
+    functions: HashMap<String, Export>,
+    /// The addresses of the data exported so far, the main module's first
+    data_symbols: HashMap<String, u32>,
+    /// The table indices given to `GOT.func` entries
+    function_indices: HashMap<String, u32>,
+    loaded: Vec<LoadedModule>,
+}
+
+impl DynamicLinker {
+    /// Creates the linker of the main module `instance`, which loads the side
+    /// modules of `globals`
+    pub fn new(globals: &mut EmscriptenGlobals, instance: &Instance) -> Self {
+        let memory_base = globals.data.memory_base;
+        let env = generate_emscripten_env(globals).into_iter().collect();
+
+        let mut functions = HashMap::new();
+        let mut data_symbols = HashMap::new();
+        for (name, export) in instance.exports() {
+            match &export {
+                Export::Function { .. } => {
+                    functions.insert(name, export);
+                }
+                // The main module's data is relocated to its memory base too
+                Export::Global(global) => {
+                    if let Value::I32(address) = global.get() {
+                        data_symbols.insert(name, memory_base.wrapping_add(address as u32));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        DynamicLinker {
+            side_modules: globals.side_modules.clone(),
+            env,
+            table: globals.table.clone(),
+            functions,
+            data_symbols,
+            function_indices: HashMap::new(),
+            loaded: vec![],
+        }
+    }
+
+    fn find_side_module(&self, filename: &str) -> Option<(String, SideModule)> {
+        let file_name = Path::new(filename)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(filename);
+        [filename, file_name].iter().find_map(|name| {
+            self.side_modules
+                .get(*name)
+                .map(|side_module| (name.to_string(), side_module.clone()))
+        })
+    }
+
+    /// Loads a side module, or finds it if it's loaded already, and returns
+    /// its handle
+    fn open(&mut self, ctx: &mut Ctx, filename: &str) -> Result<u32, String> {
+        let (name, side_module) = self
+            .find_side_module(filename)
+            .ok_or_else(|| format!("{}: no such side module was provided", filename))?;
+        if let Some(index) = self.loaded.iter().position(|loaded| loaded.name == name) {
+            self.loaded[index].references += 1;
+            return Ok(index as u32 + 1);
+        }
+
+        let DylinkInfo {
+            memory_size,
+            memory_align,
+            table_size,
+            ..
+        } = side_module.dylink;
+        let memory_base = if memory_size == 0 {
+            0
+        } else {
+            if get_emscripten_data(ctx).malloc.is_none() {
+                return Err(format!("{}: the main module doesn't export malloc", name));
+            }
+            let align = 1u32.checked_shl(memory_align).unwrap_or(0).max(16);
+            let size = memory_size
+                .checked_add(align)
+                .ok_or_else(|| format!("{}: the side module's data is too big", name))?;
+            let pointer = call_malloc(ctx, size);
+            if pointer == 0 {
+                return Err(format!("{}: can't allocate the side module's data", name));
+            }
+            let memory_base = (pointer + align - 1) & !(align - 1);
+            let view = ctx.memory(0).view::<u8>();
+            for cell in &view[memory_base as usize..(memory_base + memory_size) as usize] {
+                cell.set(0);
+            }
+            memory_base
+        };
+        let table_base = self
+            .table
+            .grow(table_size)
+            .map_err(|_| format!("{}: can't grow the table", name))?;
+
+        let module = &side_module.module;
+        let info = module.info();
+        let mut got = vec![];
+        for (_, (import_name, _)) in info.imported_globals.iter() {
+            let is_function = match info.namespace_table.get(import_name.namespace_index) {
+                "GOT.mem" => false,
+                "GOT.func" => true,
+                _ => continue,
+            };
+            got.push(GotEntry {
+                symbol: info.name_table.get(import_name.name_index).to_string(),
+                is_function,
+                global: Global::new_mutable(Value::I32(0)),
+            });
+        }
+
+        let mut imports = ImportObject::new();
+        imports.extend(self.env.iter().cloned());
+        imports.extend(
+            self.functions
+                .iter()
+                .map(|(name, export)| ("env".to_string(), name.clone(), export.clone())),
+        );
+        let mut bases = vec![];
+        for base_name in &["__memory_base", "memoryBase", "gb"] {
+            bases.push((base_name, Global::new(Value::I32(memory_base as i32))));
+        }
+        for base_name in &["__table_base", "tableBase", "fb"] {
+            bases.push((base_name, Global::new(Value::I32(table_base as i32))));
+        }
+        imports.extend(
+            bases.into_iter().map(|(name, global)| {
+                ("env".to_string(), name.to_string(), Export::Global(global))
+            }),
+        );
+        imports.extend(got.iter().map(|entry| {
+            let namespace = if entry.is_function {
+                "GOT.func"
+            } else {
+                "GOT.mem"
+            };
+            (
+                namespace.to_string(),
+                entry.symbol.clone(),
+                Export::Global(entry.global.clone()),
+            )
+        }));
+
+        let mut instance = Box::new(
+            module
+                .instantiate(&imports)
+                .map_err(|e| format!("{}: can't instantiate the side module: {:?}", name, e))?,
+        );
+        // The side module calls the emscripten environment with its own `Ctx`
+        instance.context_mut().data = ctx.data;
+
+        let mut side_functions = HashMap::new();
+        let mut side_data = HashMap::new();
+        for (export_name, export) in instance.exports() {
+            match &export {
+                Export::Function { .. } => {
+                    side_functions.insert(export_name, export);
+                }
+                Export::Global(global) => {
+                    if let Value::I32(address) = global.get() {
+                        side_data.insert(export_name, memory_base.wrapping_add(address as u32));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Check everything resolves before the table gets any new functions
+        for entry in &got {
+            let defined = if entry.is_function {
+                self.functions.contains_key(&entry.symbol)
+                    || side_functions.contains_key(&entry.symbol)
+            } else {
+                self.data_symbols.contains_key(&entry.symbol)
+                    || side_data.contains_key(&entry.symbol)
+            };
+            if !defined {
+                return Err(format!("{}: undefined symbol {}", name, entry.symbol));
+            }
+        }
+        for (symbol, export) in side_functions {
+            self.functions.entry(symbol).or_insert(export);
+        }
+        for (symbol, address) in side_data {
+            self.data_symbols.entry(symbol).or_insert(address);
+        }
+        for entry in &got {
+            let value = if entry.is_function {
+                self.function_index(&entry.symbol)?
+            } else {
+                self.data_symbols[&entry.symbol]
+            };
+            entry
+                .global
+                .set(Value::I32(value as i32))
+                .map_err(|e| format!("{}: can't set {}: {:?}", name, entry.symbol, e))?;
+        }
+
+        for initializer in SIDE_MODULE_INITIALIZERS {
+            if instance.dyn_func(initializer).is_ok() {
+                instance
+                    .call(initializer, &[])
+                    .map_err(|e| format!("{}: {} failed: {:?}", name, initializer, e))?;
+            }
+        }
+
+        self.loaded.push(LoadedModule {
+            name,
+            instance,
+            memory_base,
+            function_indices: HashMap::new(),
+            references: 1,
+        });
+        Ok(self.loaded.len() as u32)
+    }
+
+    /// The table index of a `GOT.func` entry, adding the function to the
+    /// table the first time
+    fn function_index(&mut self, symbol: &str) -> Result<u32, String> {
+        if let Some(index) = self.function_indices.get(symbol) {
+            return Ok(*index);
+        }
+        let index = add_to_table(&self.table, symbol, &self.functions[symbol])?;
+        self.function_indices.insert(symbol.to_string(), index);
+        Ok(index)
+    }
+
+    fn loaded_module(&mut self, handle: u32) -> Result<&mut LoadedModule, String> {
+        match handle.checked_sub(1) {
+            Some(index) => self
+                .loaded
+                .get_mut(index as usize)
+                .filter(|loaded| loaded.references > 0),
+            None => None,
+        }
+        .ok_or_else(|| format!("Invalid handle {}", handle))
+    }
+
+    /// The address of a symbol of a side module: the table index of a
+    /// function or the address of data
+    fn symbol(&mut self, handle: u32, symbol: &str) -> Result<u32, String> {
+        let table = self.table.clone();
+        let loaded = self.loaded_module(handle)?;
+        if let Some(index) = loaded.function_indices.get(symbol) {
+            return Ok(*index);
+        }
+        // fastcomp prefixes C symbols with an underscore
+        let prefixed = format!("_{}", symbol);
+        let export = loaded
+            .instance
+            .exports()
+            .find(|(name, _)| name == symbol)
+            .or_else(|| {
+                loaded
+                    .instance
+                    .exports()
+                    .find(|(name, _)| *name == prefixed)
+            })
+            .map(|(_, export)| export)
+            .ok_or_else(|| format!("{}: undefined symbol {}", loaded.name, symbol))?;
+        match export {
+            Export::Function { .. } => {
+                let index = add_to_table(&table, symbol, &export)?;
+                loaded.function_indices.insert(symbol.to_string(), index);
+                Ok(index)
+            }
+            Export::Global(global) => match global.get() {
+                Value::I32(address) => Ok(loaded.memory_base.wrapping_add(address as u32)),
+                _ => Err(format!("{}: {} isn't an address", loaded.name, symbol)),
+            },
+            _ => Err(format!(
+                "{}: {} isn't a function or data",
+                loaded.name, symbol
+            )),
+        }
+    }
+
+    fn close(&mut self, handle: u32) -> Result<(), String> {
+        // Functions of the module may still be in the table, so the
+        // instance stays alive
+        self.loaded_module(handle)?.references -= 1;
+        Ok(())
+    }
+}
+
+fn add_to_table(table: &Table, symbol: &str, export: &Export) -> Result<u32, String> {
+    let anyfunc = FuncRef::from_export(export)
+        .map(Anyfunc::from)
+        .ok_or_else(|| format!("{} can't be called through the table", symbol))?;
+    let index = table
+        .grow(1)
+        .map_err(|_| format!("Can't grow the table for {}", symbol))?;
+    table
+        .set(index, anyfunc)
+        .map_err(|_| format!("Can't add {} to the table", symbol))?;
+    Ok(index)
+}
+
+/// Runs `f` with the dynamic linker, recording its error for `dlerror`
+fn with_linker<F>(ctx: &mut Ctx, f: F) -> Option<u32>
+where
+    F: FnOnce(&mut DynamicLinker, &mut Ctx) -> Result<u32, String>,
+{
+    // The linker is taken out for the call, since loading a side module
+    // calls into the instance
+    let mut linker = match get_emscripten_data(ctx).dynamic_linker.take() {
+        Some(linker) => linker,
+        None => {
+            get_emscripten_data(ctx).dl_error = Some("No side modules were provided".to_string());
+            return None;
+        }
+    };
+    let result = f(&mut linker, ctx);
+    get_emscripten_data(ctx).dynamic_linker = Some(linker);
+    match result {
+        Ok(value) => Some(value),
+        Err(error) => {
+            debug!("=> {}", error);
+            get_emscripten_data(ctx).dl_error = Some(error);
+            None
+        }
+    }
+}
+
+fn read_string(ctx: &mut Ctx, pointer: u32) -> String {
+    let pointer = emscripten_memory_pointer!(ctx.memory(0), pointer) as *const c_char;
+    unsafe { CStr::from_ptr(pointer) }
+        .to_string_lossy()
+        .into_owned()
+}
 
 /// emscripten: dlopen(filename: *const c_char, flag: c_int) -> *mut c_void
-pub fn _dlopen(_ctx: &mut Ctx, _filename: u32, _flag: u32) -> i32 {
+pub fn _dlopen(ctx: &mut Ctx, filename: u32, _flag: u32) -> i32 {
     debug!("emscripten::_dlopen");
-    -1
+    if filename == 0 {
+        get_emscripten_data(ctx).dl_error =
+            Some("dlopen of the main module isn't supported".to_string());
+        return 0;
+    }
+    let filename = read_string(ctx, filename);
+    debug!("=> filename({})", filename);
+    with_linker(ctx, |linker, ctx| linker.open(ctx, &filename)).unwrap_or(0) as i32
 }
 
 /// emscripten: dlclose(handle: *mut c_void) -> c_int
-pub fn _dlclose(_ctx: &mut Ctx, _filename: u32) -> i32 {
+pub fn _dlclose(ctx: &mut Ctx, handle: u32) -> i32 {
     debug!("emscripten::_dlclose");
-    -1
+    match with_linker(ctx, |linker, _| linker.close(handle).map(|()| 0)) {
+        Some(_) => 0,
+        None => -1,
+    }
 }
 
 /// emscripten: dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void
-pub fn _dlsym(_ctx: &mut Ctx, _filepath: u32, _symbol: u32) -> i32 {
+pub fn _dlsym(ctx: &mut Ctx, handle: u32, symbol: u32) -> i32 {
     debug!("emscripten::_dlsym");
-    -1
+    let symbol = read_string(ctx, symbol);
+    debug!("=> symbol({})", symbol);
+    with_linker(ctx, |linker, _| linker.symbol(handle, &symbol)).unwrap_or(0) as i32
 }
 
 /// emscripten: dlerror() -> *mut c_char
-pub fn _dlerror(_ctx: &mut Ctx) -> i32 {
+pub fn _dlerror(ctx: &mut Ctx) -> i32 {
     debug!("emscripten::_dlerror");
-    -1
+    let error = match get_emscripten_data(ctx).dl_error.take() {
+        Some(error) => error,
+        None => return 0,
+    };
+    let previous = get_emscripten_data(ctx).dl_error_ptr;
+    if previous != 0 {
+        if let Some(free) = &get_emscripten_data(ctx).free {
+            let _ = free.call(previous);
+        }
+    }
+    let error = CString::new(error.replace('\0', "")).unwrap();
+    let error_ptr = unsafe { copy_cstr_into_wasm(ctx, error.as_ptr()) };
+    get_emscripten_data(ctx).dl_error_ptr = error_ptr;
+    error_ptr as i32
 }
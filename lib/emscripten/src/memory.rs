@@ -43,6 +43,22 @@ pub fn _emscripten_resize_heap(ctx: &mut Ctx, requested_size: u32) -> u32 {
     debug!("emscripten::_emscripten_resize_heap {}", requested_size);
     let current_memory_pages = ctx.memory(0).size();
     let current_memory = current_memory_pages.bytes().0 as u32;
+    // Modules built with `-s ALLOW_MEMORY_GROWTH=1` may still set a maximum
+    let maximum_memory = ctx
+        .memory(0)
+        .descriptor()
+        .maximum
+        .map_or(WASM_PAGE_SIZE * WASM_MAX_PAGES, |pages| pages.bytes().0);
+    if requested_size as usize > maximum_memory {
+        debug!(
+            "=> {} is over the maximum of {}",
+            requested_size, maximum_memory
+        );
+        return 0;
+    }
+    if requested_size <= current_memory {
+        return 1;
+    }
 
     // implementation from emscripten
     let mut new_size = usize::max(current_memory as usize, WASM_MIN_PAGES * WASM_PAGE_SIZE);
@@ -56,6 +72,7 @@ pub fn _emscripten_resize_heap(ctx: &mut Ctx, requested_size: u32) -> u32 {
             );
         }
     }
+    let new_size = usize::min(new_size, maximum_memory);
 
     let amount_to_grow = (new_size - current_memory as usize) / WASM_PAGE_SIZE;
     if let Ok(_pages_allocated) = ctx.memory(0).grow(Pages(amount_to_grow as u32)) {
@@ -71,11 +88,12 @@ pub fn sbrk(ctx: &mut Ctx, increment: i32) -> i32 {
     debug!("emscripten::sbrk");
     // let old_dynamic_top = 0;
     // let new_dynamic_top = 0;
-    let globals = get_emscripten_data(ctx).globals;
-    let dynamictop_ptr = (globals.dynamictop_ptr) as usize;
-    let old_dynamic_top = ctx.memory(0).view::<u32>()[dynamictop_ptr].get() as i32;
-    let new_dynamic_top: i32 = old_dynamic_top + increment;
-    let total_memory = _emscripten_get_heap_size(ctx) as i32;
+    let dynamictop_ptr = get_emscripten_data(ctx).globals.dynamictop_ptr;
+    // DYNAMICTOP_PTR is an address, the view is of u32s
+    let dynamictop_index = (dynamictop_ptr / 4) as usize;
+    let old_dynamic_top = ctx.memory(0).view::<u32>()[dynamictop_index].get() as i32;
+    let new_dynamic_top: i32 = old_dynamic_top.wrapping_add(increment);
+    let total_memory = _emscripten_get_heap_size(ctx);
     debug!(
         " => PTR {}, old: {}, new: {}, increment: {}, total: {}",
         dynamictop_ptr, old_dynamic_top, new_dynamic_top, increment, total_memory
@@ -84,30 +102,31 @@ pub fn sbrk(ctx: &mut Ctx, increment: i32) -> i32 {
         abort_on_cannot_grow_memory_old(ctx);
         return -1;
     }
-    if new_dynamic_top > total_memory {
+    if new_dynamic_top as u32 > total_memory {
         let resized = _emscripten_resize_heap(ctx, new_dynamic_top as u32);
         if resized == 0 {
             return -1;
         }
     }
-    ctx.memory(0).view::<u32>()[dynamictop_ptr].set(new_dynamic_top as u32);
+    // The memory may have moved while growing, so the view is taken again
+    ctx.memory(0).view::<u32>()[dynamictop_index].set(new_dynamic_top as u32);
     return old_dynamic_top as _;
 }
 
 /// emscripten: getTotalMemory
-pub fn get_total_memory(_ctx: &mut Ctx) -> u32 {
+pub fn get_total_memory(ctx: &mut Ctx) -> u32 {
     debug!("emscripten::get_total_memory");
-    // instance.memories[0].current_pages()
-    // TODO: Fix implementation
-    _ctx.memory(0).size().bytes().0 as u32
+    ctx.memory(0).size().bytes().0 as u32
 }
 
 /// emscripten: enlargeMemory
-pub fn enlarge_memory(_ctx: &mut Ctx) -> u32 {
+/// Grows the memory to fit the dynamic top, as older versions of emscripten
+/// do with `-s ALLOW_MEMORY_GROWTH=1`
+pub fn enlarge_memory(ctx: &mut Ctx) -> u32 {
     debug!("emscripten::enlarge_memory");
-    // instance.memories[0].grow(100);
-    // TODO: Fix implementation
-    0
+    let dynamictop_ptr = get_emscripten_data(ctx).globals.dynamictop_ptr;
+    let dynamic_top = ctx.memory(0).view::<u32>()[(dynamictop_ptr / 4) as usize].get();
+    _emscripten_resize_heap(ctx, dynamic_top)
 }
 
 /// emscripten: abortOnCannotGrowMemory
@@ -0,0 +1,116 @@
+//! Opt-in access to the host's network for the guest's sockets.
+//!
+//! Networking is denied unless the embedder allows it, and then only the
+//! hosts and ports of the allowlist can be connected, bound or sent to.
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+
+/// Whether the guest may create sockets, and which addresses they may use
+#[derive(Clone, Debug)]
+pub enum NetworkAccess {
+    /// Every socket call fails with `EACCES`, the default
+    Denied,
+    /// Sockets may use the addresses matched by one of the rules
+    Allowlist(Vec<NetworkRule>),
+}
+
+impl NetworkAccess {
+    /// Whether the guest may create sockets at all
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            NetworkAccess::Denied => false,
+            NetworkAccess::Allowlist(_) => true,
+        }
+    }
+
+    /// Whether the guest may connect, bind or send to `address`
+    pub fn allows(&self, address: &SocketAddr) -> bool {
+        match self {
+            NetworkAccess::Denied => false,
+            NetworkAccess::Allowlist(rules) => rules.iter().any(|rule| rule.matches(address)),
+        }
+    }
+}
+
+impl Default for NetworkAccess {
+    fn default() -> Self {
+        NetworkAccess::Denied
+    }
+}
+
+/// An allowlist entry, parsed from `host`, `host:port`, `*` or `*:port`,
+/// where `host` is an IP address or a name resolved when parsing
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkRule {
+    /// The addresses matched, or `None` for any address
+    pub hosts: Option<Vec<IpAddr>>,
+    /// The port matched, or `None` for any port
+    pub port: Option<u16>,
+}
+
+impl NetworkRule {
+    /// Whether the rule allows `address`
+    pub fn matches(&self, address: &SocketAddr) -> bool {
+        let host_matches = match &self.hosts {
+            Some(hosts) => hosts.contains(&address.ip()),
+            None => true,
+        };
+        let port_matches = match self.port {
+            Some(port) => port == address.port(),
+            None => true,
+        };
+        host_matches && port_matches
+    }
+}
+
+/// The error of parsing a `NetworkRule`
+#[derive(Debug)]
+pub struct NetworkRuleError(String);
+
+impl fmt::Display for NetworkRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NetworkRuleError {}
+
+impl FromStr for NetworkRule {
+    type Err = NetworkRuleError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        if let Ok(address) = rule.parse::<SocketAddr>() {
+            return Ok(NetworkRule {
+                hosts: Some(vec![address.ip()]),
+                port: Some(address.port()),
+            });
+        }
+        if let Ok(ip) = rule.parse::<IpAddr>() {
+            return Ok(NetworkRule {
+                hosts: Some(vec![ip]),
+                port: None,
+            });
+        }
+
+        let (host, port) = match rule.rfind(':') {
+            Some(colon) => {
+                let port = rule[colon + 1..].parse::<u16>().map_err(|_| {
+                    NetworkRuleError(format!("invalid port in network rule `{}`", rule))
+                })?;
+                (&rule[..colon], Some(port))
+            }
+            None => (rule, None),
+        };
+        let hosts = if host == "*" {
+            None
+        } else {
+            let addresses = (host, port.unwrap_or(0)).to_socket_addrs().map_err(|e| {
+                NetworkRuleError(format!("can't resolve `{}` in network rule: {}", host, e))
+            })?;
+            Some(addresses.map(|address| address.ip()).collect())
+        };
+        Ok(NetworkRule { hosts, port })
+    }
+}
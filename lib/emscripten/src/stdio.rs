@@ -0,0 +1,57 @@
+//! Capturing what the guest writes to its standard output and error, instead
+//! of inheriting the host's streams.
+
+use crate::env::get_emscripten_data;
+use std::sync::{Arc, Mutex};
+use wasmer_runtime_core::vm::Ctx;
+
+/// Where the guest's writes to its standard output or error go
+#[derive(Clone)]
+pub enum StdioSink {
+    /// The host's stream, the default
+    Inherit,
+    /// A buffer every write is appended to
+    Buffer(Arc<Mutex<Vec<u8>>>),
+    /// A callback called with the bytes of every write
+    Callback(Arc<Mutex<dyn FnMut(&[u8]) + Send>>),
+}
+
+impl StdioSink {
+    /// A sink appending to a new buffer, and the buffer to read the output from
+    pub fn buffer() -> (Self, Arc<Mutex<Vec<u8>>>) {
+        let buffer = Arc::new(Mutex::new(vec![]));
+        (StdioSink::Buffer(Arc::clone(&buffer)), buffer)
+    }
+
+    /// A sink calling `callback` with the bytes of every write
+    pub fn callback<F>(callback: F) -> Self
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        StdioSink::Callback(Arc::new(Mutex::new(callback)))
+    }
+}
+
+impl Default for StdioSink {
+    fn default() -> Self {
+        StdioSink::Inherit
+    }
+}
+
+/// Sends a write to `fd` to its sink when `fd` is a captured standard stream,
+/// returning the number of bytes written, or `None` if the write should go
+/// to the host's `fd`.
+pub(crate) fn write_captured(ctx: &mut Ctx, fd: i32, bytes: &[u8]) -> Option<usize> {
+    let globals = get_emscripten_data(ctx).globals;
+    let sink = match fd {
+        1 => &globals.stdout,
+        2 => &globals.stderr,
+        _ => return None,
+    };
+    match sink {
+        StdioSink::Inherit => return None,
+        StdioSink::Buffer(buffer) => buffer.lock().unwrap().extend_from_slice(bytes),
+        StdioSink::Callback(callback) => (&mut *callback.lock().unwrap())(bytes),
+    }
+    Some(bytes.len())
+}
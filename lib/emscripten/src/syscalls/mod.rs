@@ -12,6 +12,7 @@ pub use self::windows::*;
 
 use crate::{
     ptr::{Array, WasmPtr},
+    stdio::write_captured,
     utils::{copy_stat_into_wasm, get_cstr_path, get_current_directory},
 };
 
@@ -82,6 +83,10 @@ pub fn ___syscall4(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
     let count: i32 = varargs.get(ctx);
     debug!("=> fd: {}, buf: {}, count: {}", fd, buf, count);
     let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *const c_void;
+    let bytes = unsafe { slice::from_raw_parts(buf_addr as *const u8, count as usize) };
+    if let Some(written) = write_captured(ctx, fd, bytes) {
+        return written as i32;
+    }
     unsafe { write(fd, buf_addr, count as _) as i32 }
 }
 
@@ -489,7 +494,11 @@ pub fn ___syscall146(ctx: &mut Ctx, _which: i32, mut varargs: VarArgs) -> i32 {
                 as *const c_void;
             let iov_len = (*guest_iov_addr).iov_len as _;
             // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
-            let curr = write(fd, iov_base, iov_len);
+            let bytes = slice::from_raw_parts(iov_base as *const u8, iov_len);
+            let curr = match write_captured(ctx, fd, bytes) {
+                Some(written) => written as _,
+                None => write(fd, iov_base, iov_len),
+            };
             debug!(
                 "=> iov_base: {}, iov_len: {}, curr = {}",
                 (*guest_iov_addr).iov_base,
@@ -19,6 +19,7 @@ use libc::{
     fchmod,
     fchown,
     fcntl,
+    fd_set,
     // ENOTTY,
     fsync,
     getegid,
@@ -66,15 +67,20 @@ use libc::{
     socklen_t,
     stat,
     symlink,
+    timeval,
     uid_t,
     uname,
     utsname,
+    EACCES,
     EINVAL,
     // sockaddr_in,
     FIOCLEX,
     FIONBIO,
     F_GETFD,
+    F_GETFL,
     F_SETFD,
+    F_SETFL,
+    O_NONBLOCK,
     SOL_SOCKET,
     TIOCGWINSZ,
     TIOCSPGRP,
@@ -114,11 +120,12 @@ fn translate_ioctl(wasm_ioctl: u32) -> c_ulong {
 use std::ffi::CStr;
 use wasmer_runtime_core::vm::Ctx;
 
-use crate::env::EmSockAddr;
+use crate::env::{get_emscripten_data, EmSockAddr};
 use crate::utils::{self, get_cstr_path};
 #[allow(unused_imports)]
 use std::io::Error;
 use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 // Linking to functions that are not provided by rust libc
 #[cfg(target_os = "macos")]
@@ -491,6 +498,52 @@ pub fn ___syscall54(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int
 const SOCK_NON_BLOCK: i32 = 2048;
 const SOCK_CLOEXC: i32 = 0x80000;
 
+// Address families as laid out by emscripten, which follows Linux
+const WASM_AF_INET: u16 = 2;
+const WASM_AF_INET6: u16 = 10;
+
+/// Reads the IPv4 or IPv6 address of the guest `sockaddr` at `address`
+fn read_guest_socket_addr(ctx: &mut Ctx, address: u32, address_len: u32) -> Option<SocketAddr> {
+    let view = ctx.memory(0).view::<u8>();
+    let start = address as usize;
+    let bytes: Vec<u8> = view
+        .get(start..start.checked_add(address_len as usize)?)?
+        .iter()
+        .map(|byte| byte.get())
+        .collect();
+    if bytes.len() < 4 {
+        return None;
+    }
+    let family = u16::from_le_bytes([bytes[0], bytes[1]]);
+    let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+    let ip = match family {
+        WASM_AF_INET if bytes.len() >= 8 => {
+            IpAddr::V4(Ipv4Addr::new(bytes[4], bytes[5], bytes[6], bytes[7]))
+        }
+        WASM_AF_INET6 if bytes.len() >= 24 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes[8..24]);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Whether the network access the embedder gave lets the guest use the
+/// `sockaddr` at `address`
+fn network_allows(ctx: &mut Ctx, address: u32, address_len: u32) -> bool {
+    let socket_addr = read_guest_socket_addr(ctx, address, address_len);
+    debug!("=> address: {:?}", socket_addr);
+    match socket_addr {
+        Some(socket_addr) => get_emscripten_data(ctx)
+            .globals
+            .network
+            .allows(&socket_addr),
+        None => false,
+    }
+}
+
 // socketcall
 #[allow(clippy::cast_ptr_alignment)]
 pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
@@ -520,8 +573,15 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             let domain: i32 = socket_varargs.get(ctx);
             let ty_and_flags: i32 = socket_varargs.get(ctx);
             let protocol: i32 = socket_varargs.get(ctx);
+            if !get_emscripten_data(ctx).globals.network.is_enabled() {
+                debug!("=> networking is not enabled");
+                return -EACCES;
+            }
             let ty = ty_and_flags & (!SOCK_NON_BLOCK) & (!SOCK_CLOEXC);
             let fd = unsafe { socket(domain, ty, protocol) };
+            if fd == -1 {
+                return fd;
+            }
 
             if ty_and_flags & SOCK_CLOEXC != 0 {
                 // set_cloexec
@@ -531,8 +591,10 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             }
 
             if ty_and_flags & SOCK_NON_BLOCK != 0 {
-                // do something here
-                unimplemented!("non blocking sockets");
+                unsafe {
+                    let flags = fcntl(fd, F_GETFL);
+                    fcntl(fd, F_SETFL, flags | O_NONBLOCK);
+                };
             }
 
             // why is this here?
@@ -561,6 +623,9 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             let socket = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
             let address_len = socket_varargs.get(ctx);
+            if !network_allows(ctx, address, address_len) {
+                return -EACCES;
+            }
             let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
 
             // Debug received address
@@ -586,6 +651,9 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             let socket = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
             let address_len = socket_varargs.get(ctx);
+            if !network_allows(ctx, address, address_len) {
+                return -EACCES;
+            }
             let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
             unsafe { connect(socket, address, address_len) }
         }
@@ -693,13 +761,21 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             // sendto (socket: c_int, buf: *const c_void, len: size_t, flags: c_int, addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
             let socket = socket_varargs.get(ctx);
             let buf: u32 = socket_varargs.get(ctx);
-            let flags = socket_varargs.get(ctx);
-            let len: i32 = socket_varargs.get(ctx);
+            let len: u32 = socket_varargs.get(ctx);
+            let flags: i32 = socket_varargs.get(ctx);
             let address: u32 = socket_varargs.get(ctx);
             let address_len = socket_varargs.get(ctx);
+            // `send` has no address, the socket being connected already
+            let address = if address == 0 {
+                std::ptr::null()
+            } else {
+                if !network_allows(ctx, address, address_len) {
+                    return -EACCES;
+                }
+                emscripten_memory_pointer!(ctx.memory(0), address) as *const sockaddr
+            };
             let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            unsafe { sendto(socket, buf_addr, flags, len, address, address_len) as i32 }
+            unsafe { sendto(socket, buf_addr, len as usize, flags, address, address_len) as i32 }
         }
         12 => {
             debug!("socket: recvfrom");
@@ -711,9 +787,15 @@ pub fn ___syscall102(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
             let address: u32 = socket_varargs.get(ctx);
             let address_len: u32 = socket_varargs.get(ctx);
             let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
+            // `recv` has no address to fill in
+            let (address, address_len_addr) = if address == 0 {
+                (std::ptr::null_mut(), std::ptr::null_mut())
+            } else {
+                (
+                    emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr,
+                    emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t,
+                )
+            };
             unsafe {
                 recvfrom(
                     socket,
@@ -935,18 +1017,43 @@ pub fn ___syscall142(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_in
     let readfds: u32 = varargs.get(ctx);
     let writefds: u32 = varargs.get(ctx);
     let exceptfds: u32 = varargs.get(ctx);
-    let _timeout: i32 = varargs.get(ctx);
+    let timeout: u32 = varargs.get(ctx);
 
     if nfds > 1024 {
         // EINVAL
         return -22;
     }
-    assert!(exceptfds == 0, "`exceptfds` is not supporrted");
 
-    let readfds_ptr = emscripten_memory_pointer!(ctx.memory(0), readfds) as _;
-    let writefds_ptr = emscripten_memory_pointer!(ctx.memory(0), writefds) as _;
+    // The guest's `fd_set`s are 1024 bits, like the host's, so they are
+    // passed along; missing sets and timeout are null pointers.
+    let memory = ctx.memory(0);
+    let fd_set_ptr = |fds: u32| -> *mut fd_set {
+        if fds == 0 {
+            std::ptr::null_mut()
+        } else {
+            emscripten_memory_pointer!(memory, fds) as *mut fd_set
+        }
+    };
+    let (readfds_ptr, writefds_ptr, exceptfds_ptr) = (
+        fd_set_ptr(readfds),
+        fd_set_ptr(writefds),
+        fd_set_ptr(exceptfds),
+    );
 
-    unsafe { select(nfds, readfds_ptr, writefds_ptr, 0 as _, 0 as _) }
+    // The guest's `timeval` has 32 bit seconds and microseconds
+    let mut host_timeout: timeval = unsafe { mem::zeroed() };
+    let timeout_ptr = if timeout == 0 {
+        std::ptr::null_mut()
+    } else {
+        let view = memory.view::<i32>();
+        host_timeout.tv_sec = view[timeout as usize / 4].get() as _;
+        host_timeout.tv_usec = view[timeout as usize / 4 + 1].get() as _;
+        &mut host_timeout as *mut timeval
+    };
+
+    let ret = unsafe { select(nfds, readfds_ptr, writefds_ptr, exceptfds_ptr, timeout_ptr) };
+    debug!("=> nfds: {} = {}", nfds, ret);
+    ret
 }
 
 /// fdatasync
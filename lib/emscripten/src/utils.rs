@@ -5,9 +5,9 @@ use libc::stat;
 use std::ffi::CStr;
 use std::mem::size_of;
 use std::os::raw::c_char;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::slice;
-use wasmer_runtime_core::memory::Memory;
+use wasmer_runtime_core::memory::{Memory, MemoryType};
 use wasmer_runtime_core::{
     module::Module,
     structures::TypedIndex,
@@ -51,6 +51,14 @@ pub fn get_emscripten_memory_size(module: &Module) -> Result<(Pages, Option<Page
     Ok((memory.minimum, memory.maximum, memory.shared))
 }
 
+pub fn get_emscripten_memory_type(module: &Module) -> Result<MemoryType, String> {
+    if module.info().imported_memories.len() == 0 {
+        return Err("Emscripten requires at least one imported memory".to_string());
+    }
+    let (_, memory) = &module.info().imported_memories[ImportedMemoryIndex::new(0)];
+    Ok(memory.memory_type())
+}
+
 /// Reads values written by `-s EMIT_EMSCRIPTEN_METADATA=1`
 /// Assumes values start from the end in this order:
 /// Last export: Dynamic Base
@@ -233,40 +241,68 @@ pub fn read_string_from_wasm(memory: &Memory, offset: u32) -> String {
     String::from_utf8_lossy(&v).to_owned().to_string()
 }
 
+/// The components of a guest path, relative paths starting with `.` so they
+/// are looked up from the mapping of the current directory
+fn guest_path_components(path: &Path) -> Vec<Component> {
+    let mut components = path.components().collect::<Vec<_>>();
+    if path.is_relative() && components.first() != Some(&Component::CurDir) {
+        components.insert(0, Component::CurDir);
+    }
+    components
+}
+
+/// Normalizes a mapped guest path the way `get_cstr_path` looks paths up,
+/// so `data`, `./data` and `data/` are the same mapping
+pub(crate) fn normalize_guest_path(path: &str) -> String {
+    guest_path_components(Path::new(path))
+        .into_iter()
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Copies the directory `from`, and everything in it, to `to`
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let to = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else {
+            std::fs::copy(entry.path(), to)?;
+        }
+    }
+    Ok(())
+}
+
 /// This function trys to find an entry in mapdir
 /// translating paths into their correct value
 pub fn get_cstr_path(ctx: &mut Ctx, path: *const i8) -> Option<std::ffi::CString> {
-    use std::collections::VecDeque;
-
     let path_str =
         unsafe { std::ffi::CStr::from_ptr(path as *const _).to_str().unwrap() }.to_string();
     let data = get_emscripten_data(ctx);
     let path = PathBuf::from(path_str);
-    let mut prefix_added = false;
-    let mut components = path.components().collect::<VecDeque<_>>();
-    // TODO(mark): handle absolute/non-canonical/non-relative paths too (this
-    // functionality should be shared among the abis)
-    if components.len() == 1 {
-        components.push_front(std::path::Component::CurDir);
-        prefix_added = true;
-    }
+    let components = guest_path_components(&path);
+    // The longest mapped prefix wins, so a file preloaded within a mapped
+    // directory is found
+    let mut rebased_path = None;
     let mut cumulative_path = PathBuf::new();
-    for c in components.into_iter() {
+    for (i, c) in components.iter().enumerate() {
         cumulative_path.push(c);
         if let Some(val) = data
             .mapped_dirs
             .get(&cumulative_path.to_string_lossy().to_string())
         {
-            let rest_of_path = if !prefix_added {
-                path.strip_prefix(cumulative_path).ok()?
+            let rest_of_path = components[i + 1..].iter().collect::<PathBuf>();
+            rebased_path = Some(if rest_of_path.as_os_str().is_empty() {
+                val.clone()
             } else {
-                &path
-            };
-            let rebased_path = val.join(rest_of_path);
-            return std::ffi::CString::new(rebased_path.to_string_lossy().as_bytes()).ok();
+                val.join(rest_of_path)
+            });
         }
     }
-    None
+    std::ffi::CString::new(rebased_path?.to_string_lossy().as_bytes()).ok()
 }
 
 /// gets the current directory
@@ -0,0 +1,260 @@
+//! The instructions wasm functions are translated to, which the interpreter
+//! runs, and which are cached as they are.
+
+use wasmer_runtime_core::{codegen::BreakpointHandler, wasmparser::Operator};
+
+/// Defines the instructions without immediates, whose operators have the
+/// same names, with the number of values each pops and pushes.
+macro_rules! simple_instrs {
+    ($($name:ident: $pops:expr => $pushes:expr,)*) => {
+        /// A numeric instruction, without immediates
+        #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+        pub enum Simple {
+            $($name,)*
+        }
+
+        impl Simple {
+            /// The instruction of `op`, if it has no immediates
+            pub fn from_operator(op: &Operator) -> Option<Self> {
+                match *op {
+                    $(Operator::$name => Some(Simple::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// The number of values the instruction pops and pushes
+            pub fn stack_effect(self) -> (usize, usize) {
+                match self {
+                    $(Simple::$name => ($pops, $pushes),)*
+                }
+            }
+        }
+    };
+}
+
+simple_instrs! {
+    I32Eqz: 1 => 1,
+    I32Eq: 2 => 1,
+    I32Ne: 2 => 1,
+    I32LtS: 2 => 1,
+    I32LtU: 2 => 1,
+    I32GtS: 2 => 1,
+    I32GtU: 2 => 1,
+    I32LeS: 2 => 1,
+    I32LeU: 2 => 1,
+    I32GeS: 2 => 1,
+    I32GeU: 2 => 1,
+    I64Eqz: 1 => 1,
+    I64Eq: 2 => 1,
+    I64Ne: 2 => 1,
+    I64LtS: 2 => 1,
+    I64LtU: 2 => 1,
+    I64GtS: 2 => 1,
+    I64GtU: 2 => 1,
+    I64LeS: 2 => 1,
+    I64LeU: 2 => 1,
+    I64GeS: 2 => 1,
+    I64GeU: 2 => 1,
+    F32Eq: 2 => 1,
+    F32Ne: 2 => 1,
+    F32Lt: 2 => 1,
+    F32Gt: 2 => 1,
+    F32Le: 2 => 1,
+    F32Ge: 2 => 1,
+    F64Eq: 2 => 1,
+    F64Ne: 2 => 1,
+    F64Lt: 2 => 1,
+    F64Gt: 2 => 1,
+    F64Le: 2 => 1,
+    F64Ge: 2 => 1,
+    I32Clz: 1 => 1,
+    I32Ctz: 1 => 1,
+    I32Popcnt: 1 => 1,
+    I32Add: 2 => 1,
+    I32Sub: 2 => 1,
+    I32Mul: 2 => 1,
+    I32DivS: 2 => 1,
+    I32DivU: 2 => 1,
+    I32RemS: 2 => 1,
+    I32RemU: 2 => 1,
+    I32And: 2 => 1,
+    I32Or: 2 => 1,
+    I32Xor: 2 => 1,
+    I32Shl: 2 => 1,
+    I32ShrS: 2 => 1,
+    I32ShrU: 2 => 1,
+    I32Rotl: 2 => 1,
+    I32Rotr: 2 => 1,
+    I64Clz: 1 => 1,
+    I64Ctz: 1 => 1,
+    I64Popcnt: 1 => 1,
+    I64Add: 2 => 1,
+    I64Sub: 2 => 1,
+    I64Mul: 2 => 1,
+    I64DivS: 2 => 1,
+    I64DivU: 2 => 1,
+    I64RemS: 2 => 1,
+    I64RemU: 2 => 1,
+    I64And: 2 => 1,
+    I64Or: 2 => 1,
+    I64Xor: 2 => 1,
+    I64Shl: 2 => 1,
+    I64ShrS: 2 => 1,
+    I64ShrU: 2 => 1,
+    I64Rotl: 2 => 1,
+    I64Rotr: 2 => 1,
+    F32Abs: 1 => 1,
+    F32Neg: 1 => 1,
+    F32Ceil: 1 => 1,
+    F32Floor: 1 => 1,
+    F32Trunc: 1 => 1,
+    F32Nearest: 1 => 1,
+    F32Sqrt: 1 => 1,
+    F32Add: 2 => 1,
+    F32Sub: 2 => 1,
+    F32Mul: 2 => 1,
+    F32Div: 2 => 1,
+    F32Min: 2 => 1,
+    F32Max: 2 => 1,
+    F32Copysign: 2 => 1,
+    F64Abs: 1 => 1,
+    F64Neg: 1 => 1,
+    F64Ceil: 1 => 1,
+    F64Floor: 1 => 1,
+    F64Trunc: 1 => 1,
+    F64Nearest: 1 => 1,
+    F64Sqrt: 1 => 1,
+    F64Add: 2 => 1,
+    F64Sub: 2 => 1,
+    F64Mul: 2 => 1,
+    F64Div: 2 => 1,
+    F64Min: 2 => 1,
+    F64Max: 2 => 1,
+    F64Copysign: 2 => 1,
+    I32WrapI64: 1 => 1,
+    I32TruncF32S: 1 => 1,
+    I32TruncF32U: 1 => 1,
+    I32TruncF64S: 1 => 1,
+    I32TruncF64U: 1 => 1,
+    I64ExtendI32S: 1 => 1,
+    I64ExtendI32U: 1 => 1,
+    I64TruncF32S: 1 => 1,
+    I64TruncF32U: 1 => 1,
+    I64TruncF64S: 1 => 1,
+    I64TruncF64U: 1 => 1,
+    F32ConvertI32S: 1 => 1,
+    F32ConvertI32U: 1 => 1,
+    F32ConvertI64S: 1 => 1,
+    F32ConvertI64U: 1 => 1,
+    F32DemoteF64: 1 => 1,
+    F64ConvertI32S: 1 => 1,
+    F64ConvertI32U: 1 => 1,
+    F64ConvertI64S: 1 => 1,
+    F64ConvertI64U: 1 => 1,
+    F64PromoteF32: 1 => 1,
+    I32ReinterpretF32: 1 => 1,
+    I64ReinterpretF64: 1 => 1,
+    F32ReinterpretI32: 1 => 1,
+    F64ReinterpretI64: 1 => 1,
+    I32Extend8S: 1 => 1,
+    I32Extend16S: 1 => 1,
+    I64Extend8S: 1 => 1,
+    I64Extend16S: 1 => 1,
+    I64Extend32S: 1 => 1,
+    I32TruncSatF32S: 1 => 1,
+    I32TruncSatF32U: 1 => 1,
+    I32TruncSatF64S: 1 => 1,
+    I32TruncSatF64U: 1 => 1,
+    I64TruncSatF32S: 1 => 1,
+    I64TruncSatF32U: 1 => 1,
+    I64TruncSatF64S: 1 => 1,
+    I64TruncSatF64U: 1 => 1,
+}
+
+/// The width and extension of a memory access
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Access {
+    I32,
+    I64,
+    F32,
+    F64,
+    I32S8,
+    I32U8,
+    I32S16,
+    I32U16,
+    I64S8,
+    I64U8,
+    I64S16,
+    I64U16,
+    I64S32,
+    I64U32,
+}
+
+/// A jump to an enclosing block, keeping the values it yields
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Branch {
+    /// The instruction jumped to
+    pub target: u32,
+    /// The number of values below the kept ones that are dropped
+    pub drop: u32,
+    /// The number of values on top of the stack the block yields
+    pub keep: u32,
+}
+
+/// A global, by where the `Ctx` keeps it
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum GlobalRef {
+    Local(u32),
+    Imported(u32),
+}
+
+/// An instruction of the interpreter, a wasm operator whose immediates are
+/// resolved or a structured control operator turned into jumps
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Instr {
+    Unreachable,
+    Jump(u32),
+    JumpIfZero(u32),
+    Br(Branch),
+    BrIf(Branch),
+    /// The branches of the indices, then the default one
+    BrTable(Box<[Branch]>),
+    Return,
+    /// Calls a local function
+    Call(u32),
+    /// Calls an imported function
+    CallImport(u32),
+    /// Calls the function at an index of table 0, of a signature
+    CallIndirect(u32),
+    Drop,
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(GlobalRef),
+    GlobalSet(GlobalRef),
+    Load(Access, u32),
+    Store(Access, u32),
+    MemorySize,
+    MemoryGrow,
+    /// A constant, as the raw value the stack holds
+    Const(u64),
+    Simple(Simple),
+    GetInternal(u32),
+    SetInternal(u32),
+    /// Calls a handler of the function's breakpoints
+    Breakpoint(u32),
+}
+
+/// A translated local function
+#[derive(Serialize, Deserialize)]
+pub struct Function {
+    pub params: u32,
+    pub results: u32,
+    /// The number of locals after the parameters
+    pub locals: u32,
+    pub body: Vec<Instr>,
+    /// The handlers middlewares emitted, which don't survive caching
+    #[serde(skip)]
+    pub breakpoints: Vec<BreakpointHandler>,
+}
@@ -0,0 +1,515 @@
+use crate::code::{Access, Branch, Function, GlobalRef, Instr, Simple};
+use crate::runnable::{CacheImage, InterpreterCache, InterpreterModule};
+use std::{
+    mem,
+    sync::{Arc, RwLock},
+};
+use wasmer_runtime_core::{
+    backend::{Backend, CacheGen, CompilerConfig, Token},
+    cache::{Artifact, Error as CacheError},
+    codegen::*,
+    module::{ModuleInfo, ModuleInner},
+    structures::{Map, TypedIndex},
+    types::{FuncIndex, FuncSig, SigIndex},
+    wasmparser::{MemoryImmediate, Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType},
+};
+
+#[derive(Debug)]
+pub struct CodegenError {
+    pub message: String,
+}
+
+pub struct InterpreterModuleCodeGenerator {
+    functions: Vec<InterpreterFunctionCode>,
+    signatures: Option<Arc<Map<SigIndex, FuncSig>>>,
+    function_signatures: Option<Arc<Map<FuncIndex, SigIndex>>>,
+    canonicalize_nans: bool,
+}
+
+#[derive(Debug)]
+enum ControlKind {
+    Block,
+    Loop { start: usize },
+    If { else_jump: usize },
+    Else,
+}
+
+/// An enclosing block of the instruction being translated
+#[derive(Debug)]
+struct ControlFrame {
+    kind: ControlKind,
+    /// The height of the operand stack below the block's parameters
+    height: usize,
+    params: usize,
+    results: usize,
+    /// The instructions, and the slots of their branch tables, jumping to the
+    /// end of the block
+    end_jumps: Vec<(usize, usize)>,
+}
+
+pub struct InterpreterFunctionCode {
+    signatures: Arc<Map<SigIndex, FuncSig>>,
+    function_signatures: Arc<Map<FuncIndex, SigIndex>>,
+    imported_functions: usize,
+    imported_globals: usize,
+    params: usize,
+    results: usize,
+    locals: usize,
+    body: Vec<Instr>,
+    breakpoints: Vec<BreakpointHandler>,
+    control: Vec<ControlFrame>,
+    /// The height of the operand stack, above the locals
+    height: usize,
+    unreachable_depth: usize,
+}
+
+impl ModuleCodeGenerator<InterpreterFunctionCode, InterpreterModule, CodegenError>
+    for InterpreterModuleCodeGenerator
+{
+    fn new() -> Self {
+        Self {
+            functions: vec![],
+            signatures: None,
+            function_signatures: None,
+            canonicalize_nans: false,
+        }
+    }
+
+    /// The interpreter runs on the host whatever the target.
+    fn new_with_target(_: Option<String>, _: Option<String>, _: Option<String>) -> Self {
+        Self::new()
+    }
+
+    fn backend_id() -> Backend {
+        Backend::Interpreter
+    }
+
+    fn feed_compiler_config(&mut self, config: &CompilerConfig) -> Result<(), CodegenError> {
+        if config.features.simd {
+            return Err(CodegenError {
+                message: format!("the interpreter doesn't support SIMD"),
+            });
+        }
+        if config.features.threads {
+            return Err(CodegenError {
+                message: format!("the interpreter doesn't support threads"),
+            });
+        }
+        self.canonicalize_nans = config.features.deterministic;
+        Ok(())
+    }
+
+    fn feed_import_function(&mut self) -> Result<(), CodegenError> {
+        Ok(())
+    }
+
+    fn feed_signatures(&mut self, signatures: Map<SigIndex, FuncSig>) -> Result<(), CodegenError> {
+        self.signatures = Some(Arc::new(signatures));
+        Ok(())
+    }
+
+    fn feed_function_signatures(
+        &mut self,
+        assoc: Map<FuncIndex, SigIndex>,
+    ) -> Result<(), CodegenError> {
+        self.function_signatures = Some(Arc::new(assoc));
+        Ok(())
+    }
+
+    fn check_precondition(&mut self, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
+        Ok(())
+    }
+
+    fn next_function(
+        &mut self,
+        _module_info: Arc<RwLock<ModuleInfo>>,
+    ) -> Result<&mut InterpreterFunctionCode, CodegenError> {
+        let code = InterpreterFunctionCode {
+            signatures: self.signatures.as_ref().unwrap().clone(),
+            function_signatures: self.function_signatures.as_ref().unwrap().clone(),
+            imported_functions: 0,
+            imported_globals: 0,
+            params: 0,
+            results: 0,
+            locals: 0,
+            body: vec![],
+            breakpoints: vec![],
+            control: vec![],
+            height: 0,
+            unreachable_depth: 0,
+        };
+        self.functions.push(code);
+        Ok(self.functions.last_mut().unwrap())
+    }
+
+    fn finalize(
+        self,
+        module_info: &ModuleInfo,
+    ) -> Result<(InterpreterModule, Box<dyn CacheGen>), CodegenError> {
+        let functions = self
+            .functions
+            .into_iter()
+            .map(|code| Function {
+                params: code.params as u32,
+                results: code.results as u32,
+                locals: code.locals as u32,
+                body: code.body,
+                breakpoints: code.breakpoints,
+            })
+            .collect();
+        let image = CacheImage {
+            functions,
+            canonicalize_nans: self.canonicalize_nans,
+        };
+        let cache = InterpreterCache {
+            buffer: Arc::from(
+                bincode::serialize(&image)
+                    .map_err(|e| CodegenError {
+                        message: format!("serializing the code failed: {:?}", e),
+                    })?
+                    .into_boxed_slice(),
+            ),
+        };
+        Ok((InterpreterModule::new(module_info, image), Box::new(cache)))
+    }
+
+    unsafe fn from_cache(artifact: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
+        let (info, _, memory) = artifact.consume();
+
+        let image: CacheImage = bincode::deserialize(memory.as_slice())
+            .map_err(|x| CacheError::DeserializeError(format!("{:?}", x)))?;
+        let module = InterpreterModule::new(&info, image);
+        Ok(ModuleInner {
+            runnable_module: Arc::new(Box::new(module)),
+            cache_gen: Box::new(InterpreterCache {
+                buffer: Arc::from(memory.as_slice().to_vec().into_boxed_slice()),
+            }),
+            info,
+            compile_info: None,
+        })
+    }
+}
+
+impl InterpreterFunctionCode {
+    fn emit(&mut self, instr: Instr, pops: usize, pushes: usize) {
+        self.height = self.height - pops + pushes;
+        self.body.push(instr);
+    }
+
+    fn load(&mut self, access: Access, memarg: &MemoryImmediate) {
+        self.emit(Instr::Load(access, memarg.offset), 1, 1);
+    }
+
+    fn store(&mut self, access: Access, memarg: &MemoryImmediate) {
+        self.emit(Instr::Store(access, memarg.offset), 2, 0);
+    }
+
+    fn signature(&self, index: SigIndex) -> Result<(usize, usize), CodegenError> {
+        self.signatures
+            .get(index)
+            .map(|sig| (sig.params().len(), sig.returns().len()))
+            .ok_or_else(|| CodegenError {
+                message: format!("unknown signature {}", index.index()),
+            })
+    }
+
+    /// The numbers of parameters and results of a block
+    fn block_type(&self, ty: WpTypeOrFuncType) -> Result<(usize, usize), CodegenError> {
+        match ty {
+            WpTypeOrFuncType::Type(WpType::EmptyBlockType) => Ok((0, 0)),
+            WpTypeOrFuncType::Type(_) => Ok((0, 1)),
+            WpTypeOrFuncType::FuncType(index) => self.signature(SigIndex::new(index as usize)),
+        }
+    }
+
+    fn push_block(&mut self, kind: ControlKind, params: usize, results: usize) {
+        self.control.push(ControlFrame {
+            kind,
+            height: self.height - params,
+            params,
+            results,
+            end_jumps: vec![],
+        });
+    }
+
+    /// The branch to the block `depth` blocks out, to be emitted at
+    /// `instr`, in `slot` of its branch table
+    fn branch(&mut self, depth: u32, instr: usize, slot: usize) -> Result<Branch, CodegenError> {
+        let height = self.height;
+        let frame = self
+            .control
+            .len()
+            .checked_sub(depth as usize + 1)
+            .map(|index| &mut self.control[index])
+            .ok_or_else(|| CodegenError {
+                message: format!("branch to depth {} is out of the function", depth),
+            })?;
+        let (keep, target) = match frame.kind {
+            ControlKind::Loop { start } => (frame.params, start),
+            _ => {
+                frame.end_jumps.push((instr, slot));
+                (frame.results, 0)
+            }
+        };
+        Ok(Branch {
+            target: target as u32,
+            drop: (height - keep - frame.height) as u32,
+            keep: keep as u32,
+        })
+    }
+
+    /// Points the jump at `instr`, or `slot` of its branch table, to `target`
+    fn patch(&mut self, instr: usize, slot: usize, target: usize) {
+        let target = target as u32;
+        match &mut self.body[instr] {
+            Instr::Jump(to) | Instr::JumpIfZero(to) => *to = target,
+            Instr::Br(branch) | Instr::BrIf(branch) => branch.target = target,
+            Instr::BrTable(branches) => branches[slot].target = target,
+            instr => unreachable!("{:?} doesn't jump", instr),
+        }
+    }
+
+    fn global(&self, index: u32) -> GlobalRef {
+        match (index as usize).checked_sub(self.imported_globals) {
+            Some(local) => GlobalRef::Local(local as u32),
+            None => GlobalRef::Imported(index),
+        }
+    }
+
+    fn translate(&mut self, op: &Operator) -> Result<(), CodegenError> {
+        if self.unreachable_depth > 0 {
+            match *op {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    self.unreachable_depth += 1;
+                    return Ok(());
+                }
+                Operator::Else | Operator::End if self.unreachable_depth == 1 => {
+                    self.unreachable_depth = 0;
+                }
+                Operator::End => {
+                    self.unreachable_depth -= 1;
+                    return Ok(());
+                }
+                _ => return Ok(()),
+            }
+        }
+
+        match *op {
+            Operator::Unreachable => {
+                self.emit(Instr::Unreachable, 0, 0);
+                self.unreachable_depth = 1;
+            }
+            Operator::Nop => {}
+            Operator::Block { ty } => {
+                let (params, results) = self.block_type(ty)?;
+                self.push_block(ControlKind::Block, params, results);
+            }
+            Operator::Loop { ty } => {
+                let (params, results) = self.block_type(ty)?;
+                let start = self.body.len();
+                self.push_block(ControlKind::Loop { start }, params, results);
+            }
+            Operator::If { ty } => {
+                let (params, results) = self.block_type(ty)?;
+                let else_jump = self.body.len();
+                self.emit(Instr::JumpIfZero(0), 1, 0);
+                self.push_block(ControlKind::If { else_jump }, params, results);
+            }
+            Operator::Else => {
+                let jump = self.body.len();
+                self.body.push(Instr::Jump(0));
+                let else_start = self.body.len();
+                let frame = self.control.last_mut().unwrap();
+                let else_jump = match mem::replace(&mut frame.kind, ControlKind::Else) {
+                    ControlKind::If { else_jump } => else_jump,
+                    kind => {
+                        return Err(CodegenError {
+                            message: format!("else in {:?}", kind),
+                        })
+                    }
+                };
+                frame.end_jumps.push((jump, 0));
+                self.height = frame.height + frame.params;
+                self.patch(else_jump, 0, else_start);
+            }
+            Operator::End => {
+                let frame = self.control.pop().unwrap();
+                let end = self.body.len();
+                for &(instr, slot) in &frame.end_jumps {
+                    self.patch(instr, slot, end);
+                }
+                if let ControlKind::If { else_jump } = frame.kind {
+                    self.patch(else_jump, 0, end);
+                }
+                self.height = frame.height + frame.results;
+                if self.control.is_empty() {
+                    self.body.push(Instr::Return);
+                }
+            }
+            Operator::Br { relative_depth } => {
+                let branch = self.branch(relative_depth, self.body.len(), 0)?;
+                self.body.push(Instr::Br(branch));
+                self.unreachable_depth = 1;
+            }
+            Operator::BrIf { relative_depth } => {
+                self.height -= 1;
+                let branch = self.branch(relative_depth, self.body.len(), 0)?;
+                self.body.push(Instr::BrIf(branch));
+            }
+            Operator::BrTable { ref table } => {
+                let (targets, default) = table.read_table().map_err(|e| CodegenError {
+                    message: format!("BrTable read_table: {:?}", e),
+                })?;
+                self.height -= 1;
+                let instr = self.body.len();
+                let branches = targets
+                    .iter()
+                    .chain(Some(&default))
+                    .enumerate()
+                    .map(|(slot, &depth)| self.branch(depth, instr, slot))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.body.push(Instr::BrTable(branches.into_boxed_slice()));
+                self.unreachable_depth = 1;
+            }
+            Operator::Return => {
+                self.body.push(Instr::Return);
+                self.unreachable_depth = 1;
+            }
+            Operator::Call { function_index } => {
+                let sig_index = *self
+                    .function_signatures
+                    .get(FuncIndex::new(function_index as usize))
+                    .ok_or_else(|| CodegenError {
+                        message: format!("unknown function {}", function_index),
+                    })?;
+                let (params, results) = self.signature(sig_index)?;
+                let instr = match (function_index as usize).checked_sub(self.imported_functions) {
+                    Some(local) => Instr::Call(local as u32),
+                    None => Instr::CallImport(function_index),
+                };
+                self.emit(instr, params, results);
+            }
+            Operator::CallIndirect { index, table_index } => {
+                if table_index != 0 {
+                    return Err(CodegenError {
+                        message: format!("CallIndirect: table_index is not 0"),
+                    });
+                }
+                let (params, results) = self.signature(SigIndex::new(index as usize))?;
+                self.emit(Instr::CallIndirect(index), params + 1, results);
+            }
+            Operator::Drop => self.emit(Instr::Drop, 1, 0),
+            Operator::Select => self.emit(Instr::Select, 3, 1),
+            Operator::LocalGet { local_index } => self.emit(Instr::LocalGet(local_index), 0, 1),
+            Operator::LocalSet { local_index } => self.emit(Instr::LocalSet(local_index), 1, 0),
+            Operator::LocalTee { local_index } => self.emit(Instr::LocalTee(local_index), 1, 1),
+            Operator::GlobalGet { global_index } => {
+                let global = self.global(global_index);
+                self.emit(Instr::GlobalGet(global), 0, 1);
+            }
+            Operator::GlobalSet { global_index } => {
+                let global = self.global(global_index);
+                self.emit(Instr::GlobalSet(global), 1, 0);
+            }
+            Operator::I32Load { ref memarg } => self.load(Access::I32, memarg),
+            Operator::I64Load { ref memarg } => self.load(Access::I64, memarg),
+            Operator::F32Load { ref memarg } => self.load(Access::F32, memarg),
+            Operator::F64Load { ref memarg } => self.load(Access::F64, memarg),
+            Operator::I32Load8S { ref memarg } => self.load(Access::I32S8, memarg),
+            Operator::I32Load8U { ref memarg } => self.load(Access::I32U8, memarg),
+            Operator::I32Load16S { ref memarg } => self.load(Access::I32S16, memarg),
+            Operator::I32Load16U { ref memarg } => self.load(Access::I32U16, memarg),
+            Operator::I64Load8S { ref memarg } => self.load(Access::I64S8, memarg),
+            Operator::I64Load8U { ref memarg } => self.load(Access::I64U8, memarg),
+            Operator::I64Load16S { ref memarg } => self.load(Access::I64S16, memarg),
+            Operator::I64Load16U { ref memarg } => self.load(Access::I64U16, memarg),
+            Operator::I64Load32S { ref memarg } => self.load(Access::I64S32, memarg),
+            Operator::I64Load32U { ref memarg } => self.load(Access::I64U32, memarg),
+            Operator::I32Store { ref memarg } => self.store(Access::I32, memarg),
+            Operator::I64Store { ref memarg } => self.store(Access::I64, memarg),
+            Operator::F32Store { ref memarg } => self.store(Access::F32, memarg),
+            Operator::F64Store { ref memarg } => self.store(Access::F64, memarg),
+            Operator::I32Store8 { ref memarg } => self.store(Access::I32U8, memarg),
+            Operator::I32Store16 { ref memarg } => self.store(Access::I32U16, memarg),
+            Operator::I64Store8 { ref memarg } => self.store(Access::I64U8, memarg),
+            Operator::I64Store16 { ref memarg } => self.store(Access::I64U16, memarg),
+            Operator::I64Store32 { ref memarg } => self.store(Access::I64U32, memarg),
+            Operator::MemorySize { reserved: _ } => self.emit(Instr::MemorySize, 0, 1),
+            Operator::MemoryGrow { reserved: _ } => self.emit(Instr::MemoryGrow, 1, 1),
+            Operator::I32Const { value } => self.emit(Instr::Const(value as u32 as u64), 0, 1),
+            Operator::I64Const { value } => self.emit(Instr::Const(value as u64), 0, 1),
+            Operator::F32Const { value } => self.emit(Instr::Const(value.bits() as u64), 0, 1),
+            Operator::F64Const { value } => self.emit(Instr::Const(value.bits()), 0, 1),
+            ref op => match Simple::from_operator(op) {
+                Some(simple) => {
+                    let (pops, pushes) = simple.stack_effect();
+                    self.emit(Instr::Simple(simple), pops, pushes);
+                }
+                None => {
+                    return Err(CodegenError {
+                        message: format!("the interpreter doesn't support {:?}", op),
+                    })
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+impl FunctionCodeGenerator<CodegenError> for InterpreterFunctionCode {
+    fn feed_return(&mut self, _ty: WpType) -> Result<(), CodegenError> {
+        self.results += 1;
+        Ok(())
+    }
+
+    fn feed_param(&mut self, _ty: WpType) -> Result<(), CodegenError> {
+        self.params += 1;
+        Ok(())
+    }
+
+    fn feed_local(&mut self, _ty: WpType, n: usize) -> Result<(), CodegenError> {
+        self.locals += n;
+        Ok(())
+    }
+
+    fn begin_body(&mut self, module_info: &ModuleInfo) -> Result<(), CodegenError> {
+        self.imported_functions = module_info.imported_functions.len();
+        self.imported_globals = module_info.imported_globals.len();
+        // The function's body is a block whose end returns
+        self.push_block(ControlKind::Block, 0, self.results);
+        Ok(())
+    }
+
+    fn feed_event(&mut self, ev: Event, _module_info: &ModuleInfo) -> Result<(), CodegenError> {
+        match ev {
+            Event::Wasm(op) => self.translate(op),
+            Event::WasmOwned(ref op) => self.translate(op),
+            Event::Internal(_) if self.unreachable_depth > 0 => Ok(()),
+            Event::Internal(InternalEvent::FunctionBegin(_))
+            | Event::Internal(InternalEvent::FunctionEnd) => Ok(()),
+            Event::Internal(InternalEvent::Breakpoint(handler)) => {
+                let index = self.breakpoints.len() as u32;
+                self.breakpoints.push(handler);
+                self.emit(Instr::Breakpoint(index), 0, 0);
+                Ok(())
+            }
+            Event::Internal(InternalEvent::GetInternal(index)) => {
+                self.emit(Instr::GetInternal(index), 0, 1);
+                Ok(())
+            }
+            Event::Internal(InternalEvent::SetInternal(index)) => {
+                self.emit(Instr::SetInternal(index), 1, 0);
+                Ok(())
+            }
+        }
+    }
+
+    fn finalize(&mut self) -> Result<(), CodegenError> {
+        if !self.control.is_empty() {
+            return Err(CodegenError {
+                message: format!("the function ends with {} open blocks", self.control.len()),
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,720 @@
+//! Runs translated functions, over a stack of raw values holding the locals
+//! and operands of the frames.
+//!
+//! A value is its bits zero-extended to a `u64`, as `invoke` passes them.
+
+use crate::code::{Access, Branch, Function, GlobalRef, Instr, Simple};
+use crate::native;
+use crate::runnable::{self, ModuleCode};
+use std::{any::Any, mem, ptr, slice};
+use wasmer_runtime_core::{
+    codegen::BreakpointInfo,
+    typed_func::WasmTrapInfo,
+    types::FuncSig,
+    vm::{self, Anyfunc, LocalGlobal, LocalMemory, LocalTable},
+};
+
+/// The calls deep the stack is exhausted at
+const MAX_FRAMES: usize = 100_000;
+/// The values, locals included, the stack is exhausted at
+const MAX_VALUES: usize = 1 << 24;
+
+type Trap = Box<dyn Any + Send>;
+
+fn trap(info: WasmTrapInfo) -> Trap {
+    Box::new(info)
+}
+
+fn user_error(message: String) -> Trap {
+    Box::new(message)
+}
+
+/// Calls `func` with the arguments at `args`, returning its results.
+///
+/// `signature` is needed when `func` is native rather than interpreted.
+pub unsafe fn call(
+    ctx: *mut vm::Ctx,
+    func: *const vm::Func,
+    signature: Option<&FuncSig>,
+    args: *const u64,
+) -> Result<Vec<u64>, Trap> {
+    let args_of = |len: usize| {
+        if len == 0 {
+            vec![]
+        } else {
+            slice::from_raw_parts(args, len).to_vec()
+        }
+    };
+    match runnable::lookup(func) {
+        Some(entry) => {
+            let function = &(*entry.code).functions[entry.function as usize];
+            let mut machine = Machine {
+                stack: args_of(function.params as usize),
+                frames: vec![],
+            };
+            machine.run(entry.code, entry.function, ctx)?;
+            Ok(machine.stack)
+        }
+        None => {
+            let signature = signature.ok_or_else(|| {
+                user_error(format!("the signature of the native function is unknown"))
+            })?;
+            let args = args_of(signature.params().len());
+            native::call(func, ctx, signature, &args)
+        }
+    }
+}
+
+struct Frame {
+    code: *const ModuleCode,
+    function: *const Function,
+    ctx: *mut vm::Ctx,
+    /// The next instruction
+    pc: usize,
+    /// Where the parameters and locals start on the stack
+    base: usize,
+}
+
+struct Machine {
+    stack: Vec<u64>,
+    /// The callers of the running frame
+    frames: Vec<Frame>,
+}
+
+/// A value as the bits the stack holds
+trait Raw: Copy {
+    fn from_raw(raw: u64) -> Self;
+    fn into_raw(self) -> u64;
+}
+
+macro_rules! impl_raw {
+    ($($ty:ty => |$raw:ident| $from:expr, |$value:ident| $into:expr;)*) => {
+        $(impl Raw for $ty {
+            fn from_raw($raw: u64) -> Self {
+                $from
+            }
+
+            fn into_raw(self) -> u64 {
+                let $value = self;
+                $into
+            }
+        })*
+    };
+}
+
+impl_raw! {
+    i32 => |raw| raw as i32, |value| value as u32 as u64;
+    u32 => |raw| raw as u32, |value| value as u64;
+    i64 => |raw| raw as i64, |value| value as u64;
+    u64 => |raw| raw, |value| value;
+    f32 => |raw| f32::from_bits(raw as u32), |value| value.to_bits() as u64;
+    f64 => |raw| f64::from_bits(raw), |value| value.to_bits();
+    bool => |raw| raw != 0, |value| value as u64;
+}
+
+fn pop<T: Raw>(stack: &mut Vec<u64>) -> T {
+    T::from_raw(stack.pop().unwrap())
+}
+
+fn unop<A: Raw, R: Raw>(stack: &mut Vec<u64>, f: impl FnOnce(A) -> R) {
+    let top = stack.last_mut().unwrap();
+    *top = f(A::from_raw(*top)).into_raw();
+}
+
+fn binop<A: Raw, R: Raw>(stack: &mut Vec<u64>, f: impl FnOnce(A, A) -> R) {
+    let b = pop(stack);
+    unop(stack, |a| f(a, b));
+}
+
+fn try_unop<A: Raw, R: Raw>(
+    stack: &mut Vec<u64>,
+    f: impl FnOnce(A) -> Result<R, WasmTrapInfo>,
+) -> Result<(), Trap> {
+    let top = stack.last_mut().unwrap();
+    *top = f(A::from_raw(*top)).map_err(trap)?.into_raw();
+    Ok(())
+}
+
+fn try_binop<A: Raw, R: Raw>(
+    stack: &mut Vec<u64>,
+    f: impl FnOnce(A, A) -> Result<R, WasmTrapInfo>,
+) -> Result<(), Trap> {
+    let b = pop(stack);
+    try_unop(stack, |a| f(a, b))
+}
+
+const F32_SIGN: u32 = 1 << 31;
+const F64_SIGN: u64 = 1 << 63;
+
+/// The canonical NaN for NaNs, if `canonicalize` is set
+fn nan32(canonicalize: bool, value: f32) -> f32 {
+    if canonicalize && value.is_nan() {
+        f32::from_bits(0x7fc0_0000)
+    } else {
+        value
+    }
+}
+
+fn nan64(canonicalize: bool, value: f64) -> f64 {
+    if canonicalize && value.is_nan() {
+        f64::from_bits(0x7ff8_0000_0000_0000)
+    } else {
+        value
+    }
+}
+
+macro_rules! float_helpers {
+    ($min:ident, $max:ident, $nearest:ident, $ty:ty) => {
+        /// The minimum, a NaN if either is one, and -0 of -0 and +0
+        fn $min(a: $ty, b: $ty) -> $ty {
+            if a.is_nan() || b.is_nan() {
+                a + b
+            } else if a == b {
+                <$ty>::from_bits(a.to_bits() | b.to_bits())
+            } else if a < b {
+                a
+            } else {
+                b
+            }
+        }
+
+        /// The maximum, a NaN if either is one, and +0 of -0 and +0
+        fn $max(a: $ty, b: $ty) -> $ty {
+            if a.is_nan() || b.is_nan() {
+                a + b
+            } else if a == b {
+                <$ty>::from_bits(a.to_bits() & b.to_bits())
+            } else if a > b {
+                a
+            } else {
+                b
+            }
+        }
+
+        /// Rounds to the nearest integer, ties to even
+        fn $nearest(value: $ty) -> $ty {
+            let truncated = value.trunc();
+            let fraction = value - truncated;
+            if fraction.abs() == 0.5 {
+                if truncated % 2.0 == 0.0 {
+                    truncated
+                } else {
+                    truncated + 2.0 * fraction
+                }
+            } else {
+                value.round()
+            }
+        }
+    };
+}
+
+float_helpers!(min32, max32, nearest32, f32);
+float_helpers!(min64, max64, nearest64, f64);
+
+/// The integer part of `value`, if it is in `[min, max)`
+fn trunc(value: f64, min: f64, max: f64) -> Result<f64, WasmTrapInfo> {
+    let truncated = value.trunc();
+    if truncated >= min && truncated < max {
+        Ok(truncated)
+    } else {
+        Err(WasmTrapInfo::IllegalArithmetic)
+    }
+}
+
+macro_rules! trunc_sat {
+    ($value:expr, $ty:ty, $min:expr, $max:expr) => {{
+        let value: f64 = $value;
+        if value.is_nan() {
+            0
+        } else if value.trunc() < $min {
+            <$ty>::min_value()
+        } else if value.trunc() >= $max {
+            <$ty>::max_value()
+        } else {
+            value as $ty
+        }
+    }};
+}
+
+const I32_MIN: f64 = -2147483648.0;
+const I32_END: f64 = 2147483648.0;
+const U32_END: f64 = 4294967296.0;
+const I64_MIN: f64 = -9223372036854775808.0;
+const I64_END: f64 = 9223372036854775808.0;
+const U64_END: f64 = 18446744073709551616.0;
+
+/// Runs a numeric instruction
+fn simple(op: Simple, stack: &mut Vec<u64>, canonicalize: bool) -> Result<(), Trap> {
+    use self::Simple::*;
+    use wasmer_runtime_core::typed_func::WasmTrapInfo::IllegalArithmetic;
+
+    let c = canonicalize;
+    match op {
+        I32Eqz => unop(stack, |a: u32| a == 0),
+        I32Eq => binop(stack, |a: u32, b| a == b),
+        I32Ne => binop(stack, |a: u32, b| a != b),
+        I32LtS => binop(stack, |a: i32, b| a < b),
+        I32LtU => binop(stack, |a: u32, b| a < b),
+        I32GtS => binop(stack, |a: i32, b| a > b),
+        I32GtU => binop(stack, |a: u32, b| a > b),
+        I32LeS => binop(stack, |a: i32, b| a <= b),
+        I32LeU => binop(stack, |a: u32, b| a <= b),
+        I32GeS => binop(stack, |a: i32, b| a >= b),
+        I32GeU => binop(stack, |a: u32, b| a >= b),
+        I64Eqz => unop(stack, |a: u64| a == 0),
+        I64Eq => binop(stack, |a: u64, b| a == b),
+        I64Ne => binop(stack, |a: u64, b| a != b),
+        I64LtS => binop(stack, |a: i64, b| a < b),
+        I64LtU => binop(stack, |a: u64, b| a < b),
+        I64GtS => binop(stack, |a: i64, b| a > b),
+        I64GtU => binop(stack, |a: u64, b| a > b),
+        I64LeS => binop(stack, |a: i64, b| a <= b),
+        I64LeU => binop(stack, |a: u64, b| a <= b),
+        I64GeS => binop(stack, |a: i64, b| a >= b),
+        I64GeU => binop(stack, |a: u64, b| a >= b),
+        F32Eq => binop(stack, |a: f32, b| a == b),
+        F32Ne => binop(stack, |a: f32, b| a != b),
+        F32Lt => binop(stack, |a: f32, b| a < b),
+        F32Gt => binop(stack, |a: f32, b| a > b),
+        F32Le => binop(stack, |a: f32, b| a <= b),
+        F32Ge => binop(stack, |a: f32, b| a >= b),
+        F64Eq => binop(stack, |a: f64, b| a == b),
+        F64Ne => binop(stack, |a: f64, b| a != b),
+        F64Lt => binop(stack, |a: f64, b| a < b),
+        F64Gt => binop(stack, |a: f64, b| a > b),
+        F64Le => binop(stack, |a: f64, b| a <= b),
+        F64Ge => binop(stack, |a: f64, b| a >= b),
+
+        I32Clz => unop(stack, |a: u32| a.leading_zeros()),
+        I32Ctz => unop(stack, |a: u32| a.trailing_zeros()),
+        I32Popcnt => unop(stack, |a: u32| a.count_ones()),
+        I32Add => binop(stack, |a: u32, b| a.wrapping_add(b)),
+        I32Sub => binop(stack, |a: u32, b| a.wrapping_sub(b)),
+        I32Mul => binop(stack, |a: u32, b| a.wrapping_mul(b)),
+        I32DivS => try_binop(stack, |a: i32, b| a.checked_div(b).ok_or(IllegalArithmetic))?,
+        I32DivU => try_binop(stack, |a: u32, b| a.checked_div(b).ok_or(IllegalArithmetic))?,
+        I32RemS => try_binop(stack, |a: i32, b| {
+            if b == 0 {
+                Err(IllegalArithmetic)
+            } else {
+                Ok(a.wrapping_rem(b))
+            }
+        })?,
+        I32RemU => try_binop(stack, |a: u32, b| a.checked_rem(b).ok_or(IllegalArithmetic))?,
+        I32And => binop(stack, |a: u32, b| a & b),
+        I32Or => binop(stack, |a: u32, b| a | b),
+        I32Xor => binop(stack, |a: u32, b| a ^ b),
+        I32Shl => binop(stack, |a: u32, b| a.wrapping_shl(b)),
+        I32ShrS => binop(stack, |a: i32, b| a.wrapping_shr(b as u32)),
+        I32ShrU => binop(stack, |a: u32, b| a.wrapping_shr(b)),
+        I32Rotl => binop(stack, |a: u32, b| a.rotate_left(b % 32)),
+        I32Rotr => binop(stack, |a: u32, b| a.rotate_right(b % 32)),
+        I64Clz => unop(stack, |a: u64| a.leading_zeros()),
+        I64Ctz => unop(stack, |a: u64| a.trailing_zeros()),
+        I64Popcnt => unop(stack, |a: u64| a.count_ones()),
+        I64Add => binop(stack, |a: u64, b| a.wrapping_add(b)),
+        I64Sub => binop(stack, |a: u64, b| a.wrapping_sub(b)),
+        I64Mul => binop(stack, |a: u64, b| a.wrapping_mul(b)),
+        I64DivS => try_binop(stack, |a: i64, b| a.checked_div(b).ok_or(IllegalArithmetic))?,
+        I64DivU => try_binop(stack, |a: u64, b| a.checked_div(b).ok_or(IllegalArithmetic))?,
+        I64RemS => try_binop(stack, |a: i64, b| {
+            if b == 0 {
+                Err(IllegalArithmetic)
+            } else {
+                Ok(a.wrapping_rem(b))
+            }
+        })?,
+        I64RemU => try_binop(stack, |a: u64, b| a.checked_rem(b).ok_or(IllegalArithmetic))?,
+        I64And => binop(stack, |a: u64, b| a & b),
+        I64Or => binop(stack, |a: u64, b| a | b),
+        I64Xor => binop(stack, |a: u64, b| a ^ b),
+        I64Shl => binop(stack, |a: u64, b| a.wrapping_shl(b as u32)),
+        I64ShrS => binop(stack, |a: i64, b| a.wrapping_shr(b as u32)),
+        I64ShrU => binop(stack, |a: u64, b| a.wrapping_shr(b as u32)),
+        I64Rotl => binop(stack, |a: u64, b| a.rotate_left((b % 64) as u32)),
+        I64Rotr => binop(stack, |a: u64, b| a.rotate_right((b % 64) as u32)),
+
+        F32Abs => unop(stack, |a: f32| f32::from_bits(a.to_bits() & !F32_SIGN)),
+        F32Neg => unop(stack, |a: f32| f32::from_bits(a.to_bits() ^ F32_SIGN)),
+        F32Ceil => unop(stack, |a: f32| nan32(c, a.ceil())),
+        F32Floor => unop(stack, |a: f32| nan32(c, a.floor())),
+        F32Trunc => unop(stack, |a: f32| nan32(c, a.trunc())),
+        F32Nearest => unop(stack, |a: f32| nan32(c, nearest32(a))),
+        F32Sqrt => unop(stack, |a: f32| nan32(c, a.sqrt())),
+        F32Add => binop(stack, |a: f32, b| nan32(c, a + b)),
+        F32Sub => binop(stack, |a: f32, b| nan32(c, a - b)),
+        F32Mul => binop(stack, |a: f32, b| nan32(c, a * b)),
+        F32Div => binop(stack, |a: f32, b| nan32(c, a / b)),
+        F32Min => binop(stack, |a: f32, b| nan32(c, min32(a, b))),
+        F32Max => binop(stack, |a: f32, b| nan32(c, max32(a, b))),
+        F32Copysign => binop(stack, |a: f32, b: f32| {
+            f32::from_bits(a.to_bits() & !F32_SIGN | b.to_bits() & F32_SIGN)
+        }),
+        F64Abs => unop(stack, |a: f64| f64::from_bits(a.to_bits() & !F64_SIGN)),
+        F64Neg => unop(stack, |a: f64| f64::from_bits(a.to_bits() ^ F64_SIGN)),
+        F64Ceil => unop(stack, |a: f64| nan64(c, a.ceil())),
+        F64Floor => unop(stack, |a: f64| nan64(c, a.floor())),
+        F64Trunc => unop(stack, |a: f64| nan64(c, a.trunc())),
+        F64Nearest => unop(stack, |a: f64| nan64(c, nearest64(a))),
+        F64Sqrt => unop(stack, |a: f64| nan64(c, a.sqrt())),
+        F64Add => binop(stack, |a: f64, b| nan64(c, a + b)),
+        F64Sub => binop(stack, |a: f64, b| nan64(c, a - b)),
+        F64Mul => binop(stack, |a: f64, b| nan64(c, a * b)),
+        F64Div => binop(stack, |a: f64, b| nan64(c, a / b)),
+        F64Min => binop(stack, |a: f64, b| nan64(c, min64(a, b))),
+        F64Max => binop(stack, |a: f64, b| nan64(c, max64(a, b))),
+        F64Copysign => binop(stack, |a: f64, b: f64| {
+            f64::from_bits(a.to_bits() & !F64_SIGN | b.to_bits() & F64_SIGN)
+        }),
+
+        I32WrapI64 => unop(stack, |a: u64| a as u32),
+        I32TruncF32S => try_unop(stack, |a: f32| {
+            trunc(a.into(), I32_MIN, I32_END).map(|t| t as i32)
+        })?,
+        I32TruncF32U => try_unop(stack, |a: f32| {
+            trunc(a.into(), 0.0, U32_END).map(|t| t as u32)
+        })?,
+        I32TruncF64S => try_unop(stack, |a: f64| trunc(a, I32_MIN, I32_END).map(|t| t as i32))?,
+        I32TruncF64U => try_unop(stack, |a: f64| trunc(a, 0.0, U32_END).map(|t| t as u32))?,
+        I64ExtendI32S => unop(stack, |a: i32| a as i64),
+        I64ExtendI32U => unop(stack, |a: u32| a as u64),
+        I64TruncF32S => try_unop(stack, |a: f32| {
+            trunc(a.into(), I64_MIN, I64_END).map(|t| t as i64)
+        })?,
+        I64TruncF32U => try_unop(stack, |a: f32| {
+            trunc(a.into(), 0.0, U64_END).map(|t| t as u64)
+        })?,
+        I64TruncF64S => try_unop(stack, |a: f64| trunc(a, I64_MIN, I64_END).map(|t| t as i64))?,
+        I64TruncF64U => try_unop(stack, |a: f64| trunc(a, 0.0, U64_END).map(|t| t as u64))?,
+        F32ConvertI32S => unop(stack, |a: i32| a as f32),
+        F32ConvertI32U => unop(stack, |a: u32| a as f32),
+        F32ConvertI64S => unop(stack, |a: i64| a as f32),
+        F32ConvertI64U => unop(stack, |a: u64| a as f32),
+        F32DemoteF64 => unop(stack, |a: f64| nan32(c, a as f32)),
+        F64ConvertI32S => unop(stack, |a: i32| a as f64),
+        F64ConvertI32U => unop(stack, |a: u32| a as f64),
+        F64ConvertI64S => unop(stack, |a: i64| a as f64),
+        F64ConvertI64U => unop(stack, |a: u64| a as f64),
+        F64PromoteF32 => unop(stack, |a: f32| nan64(c, a.into())),
+        // The stack holds the bits of floats already
+        I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => {}
+        I32Extend8S => unop(stack, |a: i32| a as i8 as i32),
+        I32Extend16S => unop(stack, |a: i32| a as i16 as i32),
+        I64Extend8S => unop(stack, |a: i64| a as i8 as i64),
+        I64Extend16S => unop(stack, |a: i64| a as i16 as i64),
+        I64Extend32S => unop(stack, |a: i64| a as i32 as i64),
+        I32TruncSatF32S => unop(stack, |a: f32| trunc_sat!(a.into(), i32, I32_MIN, I32_END)),
+        I32TruncSatF32U => unop(stack, |a: f32| trunc_sat!(a.into(), u32, 0.0, U32_END)),
+        I32TruncSatF64S => unop(stack, |a: f64| trunc_sat!(a, i32, I32_MIN, I32_END)),
+        I32TruncSatF64U => unop(stack, |a: f64| trunc_sat!(a, u32, 0.0, U32_END)),
+        I64TruncSatF32S => unop(stack, |a: f32| trunc_sat!(a.into(), i64, I64_MIN, I64_END)),
+        I64TruncSatF32U => unop(stack, |a: f32| trunc_sat!(a.into(), u64, 0.0, U64_END)),
+        I64TruncSatF64S => unop(stack, |a: f64| trunc_sat!(a, i64, I64_MIN, I64_END)),
+        I64TruncSatF64U => unop(stack, |a: f64| trunc_sat!(a, u64, 0.0, U64_END)),
+    }
+    Ok(())
+}
+
+impl Access {
+    fn size(self) -> usize {
+        match self {
+            Access::I64 | Access::F64 => 8,
+            Access::I32 | Access::F32 | Access::I64S32 | Access::I64U32 => 4,
+            Access::I32S16 | Access::I32U16 | Access::I64S16 | Access::I64U16 => 2,
+            Access::I32S8 | Access::I32U8 | Access::I64S8 | Access::I64U8 => 1,
+        }
+    }
+}
+
+macro_rules! read_le {
+    ($ptr:expr, $ty:ty) => {{
+        let mut bytes = [0u8; mem::size_of::<$ty>()];
+        ptr::copy_nonoverlapping($ptr, bytes.as_mut_ptr(), bytes.len());
+        <$ty>::from_le_bytes(bytes)
+    }};
+}
+
+macro_rules! write_le {
+    ($ptr:expr, $value:expr) => {{
+        let bytes = $value.to_le_bytes();
+        ptr::copy_nonoverlapping(bytes.as_ptr(), $ptr, bytes.len());
+    }};
+}
+
+unsafe fn memory(code: &ModuleCode, ctx: *mut vm::Ctx) -> *mut LocalMemory {
+    if code.imported_memory {
+        *(*ctx).internal.imported_memories
+    } else {
+        *(*ctx).internal.memories
+    }
+}
+
+/// The address of the `size` bytes at `offset` from `address` in memory 0
+unsafe fn address(
+    code: &ModuleCode,
+    ctx: *mut vm::Ctx,
+    address: u32,
+    offset: u32,
+    size: usize,
+) -> Result<*mut u8, Trap> {
+    let memory = &*memory(code, ctx);
+    let start = address as u64 + offset as u64;
+    if start + size as u64 > memory.bound as u64 {
+        return Err(trap(WasmTrapInfo::MemoryOutOfBounds));
+    }
+    Ok(memory.base.add(start as usize))
+}
+
+unsafe fn load(
+    code: &ModuleCode,
+    ctx: *mut vm::Ctx,
+    access: Access,
+    at: u32,
+    offset: u32,
+) -> Result<u64, Trap> {
+    let ptr = address(code, ctx, at, offset, access.size())?;
+    Ok(match access {
+        Access::I32 | Access::F32 | Access::I64U32 => read_le!(ptr, u32) as u64,
+        Access::I64 | Access::F64 => read_le!(ptr, u64),
+        Access::I32S8 => read_le!(ptr, i8) as i32 as u32 as u64,
+        Access::I32S16 => read_le!(ptr, i16) as i32 as u32 as u64,
+        Access::I32U8 | Access::I64U8 => read_le!(ptr, u8) as u64,
+        Access::I32U16 | Access::I64U16 => read_le!(ptr, u16) as u64,
+        Access::I64S8 => read_le!(ptr, i8) as i64 as u64,
+        Access::I64S16 => read_le!(ptr, i16) as i64 as u64,
+        Access::I64S32 => read_le!(ptr, i32) as i64 as u64,
+    })
+}
+
+unsafe fn store(
+    code: &ModuleCode,
+    ctx: *mut vm::Ctx,
+    access: Access,
+    at: u32,
+    offset: u32,
+    value: u64,
+) -> Result<(), Trap> {
+    let ptr = address(code, ctx, at, offset, access.size())?;
+    match access.size() {
+        8 => write_le!(ptr, value),
+        4 => write_le!(ptr, value as u32),
+        2 => write_le!(ptr, value as u16),
+        _ => write_le!(ptr, value as u8),
+    }
+    Ok(())
+}
+
+unsafe fn global(ctx: *mut vm::Ctx, global: GlobalRef) -> *mut LocalGlobal {
+    match global {
+        GlobalRef::Local(index) => *(*ctx).internal.globals.add(index as usize),
+        GlobalRef::Imported(index) => *(*ctx).internal.imported_globals.add(index as usize),
+    }
+}
+
+/// Drops the values a branch leaves, returning its target
+fn branch(stack: &mut Vec<u64>, branch: &Branch) -> usize {
+    if branch.drop > 0 {
+        let len = stack.len();
+        let keep = branch.keep as usize;
+        let drop = branch.drop as usize;
+        stack.copy_within(len - keep..len, len - keep - drop);
+        stack.truncate(len - drop);
+    }
+    branch.target as usize
+}
+
+impl Machine {
+    /// The frame of a call to `function` of `code`, whose arguments are on
+    /// top of the stack
+    unsafe fn enter(
+        &mut self,
+        code: *const ModuleCode,
+        function: u32,
+        ctx: *mut vm::Ctx,
+    ) -> Result<Frame, Trap> {
+        let function = &(*code).functions[function as usize];
+        let locals = function.locals as usize;
+        if self.frames.len() >= MAX_FRAMES || self.stack.len() + locals > MAX_VALUES {
+            return Err(trap(WasmTrapInfo::Unknown));
+        }
+        let base = self.stack.len() - function.params as usize;
+        self.stack.resize(self.stack.len() + locals, 0);
+        Ok(Frame {
+            code,
+            function,
+            ctx,
+            pc: 0,
+            base,
+        })
+    }
+
+    /// Calls the function `func` points to, of the signature `sig_index` of
+    /// `code`, returning the frame of the call if the function is
+    /// interpreted, or running it if it is native
+    unsafe fn call_pointer(
+        &mut self,
+        code: &ModuleCode,
+        func: *const vm::Func,
+        ctx: *mut vm::Ctx,
+        sig_index: u32,
+    ) -> Result<Option<Frame>, Trap> {
+        if let Some(entry) = runnable::lookup(func) {
+            return self.enter(entry.code, entry.function, ctx).map(Some);
+        }
+        let signature = &code.signatures[sig_index as usize];
+        let args = self.stack.len() - signature.params().len();
+        let result = native::call(func, ctx, signature, &self.stack[args..])?;
+        self.stack.truncate(args);
+        self.stack.extend(result);
+        Ok(None)
+    }
+
+    /// Runs `function` of `code` to its return, leaving its results on the
+    /// stack in place of its arguments
+    unsafe fn run(
+        &mut self,
+        code: *const ModuleCode,
+        function: u32,
+        ctx: *mut vm::Ctx,
+    ) -> Result<(), Trap> {
+        let mut frame = self.enter(code, function, ctx)?;
+        loop {
+            let code = &*frame.code;
+            let function = &*frame.function;
+            let ctx = frame.ctx;
+            let stack = &mut self.stack;
+            let instr = &function.body[frame.pc];
+            frame.pc += 1;
+
+            match *instr {
+                Instr::Unreachable => return Err(trap(WasmTrapInfo::Unreachable)),
+                Instr::Jump(target) => frame.pc = target as usize,
+                Instr::JumpIfZero(target) => {
+                    if pop::<u32>(stack) == 0 {
+                        frame.pc = target as usize;
+                    }
+                }
+                Instr::Br(ref target) => frame.pc = branch(stack, target),
+                Instr::BrIf(ref target) => {
+                    if pop::<u32>(stack) != 0 {
+                        frame.pc = branch(stack, target);
+                    }
+                }
+                Instr::BrTable(ref targets) => {
+                    let index = pop::<u32>(stack) as usize;
+                    let target = &targets[index.min(targets.len() - 1)];
+                    frame.pc = branch(stack, target);
+                }
+                Instr::Return => {
+                    let results = function.results as usize;
+                    let len = stack.len();
+                    stack.copy_within(len - results..len, frame.base);
+                    stack.truncate(frame.base + results);
+                    match self.frames.pop() {
+                        Some(caller) => frame = caller,
+                        None => return Ok(()),
+                    }
+                }
+                Instr::Call(index) => {
+                    let callee = self.enter(code, index, ctx)?;
+                    self.frames.push(mem::replace(&mut frame, callee));
+                }
+                Instr::CallImport(index) => {
+                    let imported = ((*ctx).internal.imported_funcs as *const u8)
+                        .add(index as usize * vm::ImportedFunc::size() as usize);
+                    let func = *(imported.add(vm::ImportedFunc::offset_func() as usize)
+                        as *const *const vm::Func);
+                    let func_ctx = *(imported.add(vm::ImportedFunc::offset_func_ctx() as usize)
+                        as *const *const u8);
+                    let callee_ctx = *(func_ctx.add(vm::FuncCtx::offset_vmctx() as usize)
+                        as *const *mut vm::Ctx);
+                    let sig_index = code.import_signatures[index as usize];
+                    if let Some(callee) = self.call_pointer(code, func, callee_ctx, sig_index)? {
+                        self.frames.push(mem::replace(&mut frame, callee));
+                    }
+                }
+                Instr::CallIndirect(sig_index) => {
+                    let index = pop::<u32>(stack) as usize;
+                    let table: *mut LocalTable = if code.imported_table {
+                        *(*ctx).internal.imported_tables
+                    } else {
+                        *(*ctx).internal.tables
+                    };
+                    if index >= (*table).count {
+                        return Err(trap(WasmTrapInfo::CallIndirectOOB));
+                    }
+                    let anyfunc = &*((*table).base as *const Anyfunc).add(index);
+                    let expected = *(*ctx).internal.dynamic_sigindices.add(sig_index as usize);
+                    if anyfunc.sig_id.0 != expected.0 {
+                        return Err(trap(WasmTrapInfo::IncorrectCallIndirectSignature));
+                    }
+                    if let Some(callee) =
+                        self.call_pointer(code, anyfunc.func, anyfunc.ctx, sig_index)?
+                    {
+                        self.frames.push(mem::replace(&mut frame, callee));
+                    }
+                }
+                Instr::Drop => {
+                    stack.pop();
+                }
+                Instr::Select => {
+                    let condition = pop::<u32>(stack);
+                    let b = stack.pop().unwrap();
+                    if condition == 0 {
+                        *stack.last_mut().unwrap() = b;
+                    }
+                }
+                Instr::LocalGet(index) => {
+                    let value = stack[frame.base + index as usize];
+                    stack.push(value);
+                }
+                Instr::LocalSet(index) => {
+                    let value = stack.pop().unwrap();
+                    stack[frame.base + index as usize] = value;
+                }
+                Instr::LocalTee(index) => {
+                    let value = *stack.last().unwrap();
+                    stack[frame.base + index as usize] = value;
+                }
+                Instr::GlobalGet(index) => stack.push((*global(ctx, index)).data as u64),
+                Instr::GlobalSet(index) => {
+                    (*global(ctx, index)).data = stack.pop().unwrap() as u128
+                }
+                Instr::Load(access, offset) => {
+                    let at = pop::<u32>(stack);
+                    stack.push(load(code, ctx, access, at, offset)?);
+                }
+                Instr::Store(access, offset) => {
+                    let value = stack.pop().unwrap();
+                    let at = pop::<u32>(stack);
+                    store(code, ctx, access, at, offset, value)?;
+                }
+                Instr::MemorySize => {
+                    let memory_size: unsafe extern "C" fn(*mut vm::Ctx, u32) -> u32 =
+                        mem::transmute((*(*ctx).internal.intrinsics).memory_size);
+                    stack.push(memory_size(ctx, 0) as u64);
+                }
+                Instr::MemoryGrow => {
+                    let memory_grow: unsafe extern "C" fn(*mut vm::Ctx, u32, u32) -> i32 =
+                        mem::transmute((*(*ctx).internal.intrinsics).memory_grow);
+                    let delta = pop::<u32>(stack);
+                    stack.push(memory_grow(ctx, 0, delta).into_raw());
+                }
+                Instr::Const(value) => stack.push(value),
+                Instr::Simple(op) => simple(op, stack, code.canonicalize_nans)?,
+                Instr::GetInternal(index) => {
+                    stack.push((*(*ctx).internal.internals)[index as usize])
+                }
+                Instr::SetInternal(index) => {
+                    (*(*ctx).internal.internals)[index as usize] = stack.pop().unwrap();
+                }
+                Instr::Breakpoint(index) => {
+                    // Modules loaded from the cache don't have their handlers
+                    if let Some(handler) = function.breakpoints.get(index as usize) {
+                        handler(BreakpointInfo { fault: None })?;
+                    }
+                }
+            }
+        }
+    }
+}
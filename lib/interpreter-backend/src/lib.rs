@@ -0,0 +1,49 @@
+#![deny(
+    dead_code,
+    nonstandard_style,
+    unused_imports,
+    unused_mut,
+    unused_variables,
+    unused_unsafe,
+    unreachable_patterns
+)]
+#![doc(html_favicon_url = "https://wasmer.io/static/icons/favicon.ico")]
+#![doc(html_logo_url = "https://avatars3.githubusercontent.com/u/44205449?s=200&v=4")]
+
+//! An interpreter backend for Wasmer, for platforms where generating machine
+//! code at runtime isn't allowed.
+//!
+//! Functions are translated to a compact instruction set, which is run
+//! without ever mapping memory executable. Modules compiled with it are used
+//! through the same `Module` and `Instance` API as with the other backends,
+//! middlewares and caching included, only slower.
+//!
+//! Host functions created with `func!` or `Func::new` are called through
+//! the `HostCall` registered with them, on every target and whatever their
+//! signature; host functions created otherwise, e.g. with the C API, can't
+//! be called.
+
+extern crate serde;
+
+#[macro_use]
+extern crate serde_derive;
+
+#[macro_use]
+extern crate lazy_static;
+
+mod code;
+mod codegen;
+mod interpreter;
+mod native;
+mod runnable;
+
+pub use codegen::InterpreterFunctionCode as FunctionCodeGenerator;
+pub use codegen::InterpreterModuleCodeGenerator as ModuleCodeGenerator;
+
+use wasmer_runtime_core::codegen::SimpleStreamingCompilerGen;
+pub type InterpreterCompiler = SimpleStreamingCompilerGen<
+    codegen::InterpreterModuleCodeGenerator,
+    codegen::InterpreterFunctionCode,
+    runnable::InterpreterModule,
+    codegen::CodegenError,
+>;
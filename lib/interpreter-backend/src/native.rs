@@ -0,0 +1,68 @@
+//! Calls from the interpreter to functions it didn't translate: host
+//! functions, and the functions of modules compiled by other backends.
+//!
+//! Neither is called through a function pointer cast to a signature it
+//! doesn't have. Host functions created with `func!` or `Func::new` are
+//! called through the `HostCall` registered with them, which returns their
+//! errors instead of unwinding through native frames. The functions of other
+//! modules are called through the trampoline and `invoke` of the backend
+//! that compiled them, as `Func::call` does.
+
+use std::{any::Any, ptr::NonNull};
+use wasmer_runtime_core::{
+    backend::Backend,
+    error::RuntimeError,
+    typed_func::{self, WasmTrapInfo},
+    types::FuncSig,
+    vm,
+};
+
+/// Calls the native `func` of `signature` with `args`, returning its
+/// results.
+///
+/// `ctx` is the `vm::Ctx` `func` is called with: the one of the calling
+/// instance for host functions, the one of the instance exporting it
+/// otherwise.
+pub unsafe fn call(
+    func: *const vm::Func,
+    ctx: *mut vm::Ctx,
+    signature: &FuncSig,
+    args: &[u64],
+) -> Result<Vec<u64>, Box<dyn Any + Send>> {
+    let mut rets = vec![0; signature.returns().len()];
+
+    if let Some(host_call) = typed_func::host_call(func) {
+        host_call(ctx, args, &mut rets)?;
+        return Ok(rets);
+    }
+
+    let module = &*(*ctx).module;
+    let wasm = match (NonNull::new(func as *mut vm::Func), module.info.backend) {
+        (Some(func), backend) if backend != Backend::Interpreter => module
+            .info
+            .signatures
+            .iter()
+            .find(|(_, sig)| *sig == signature)
+            .and_then(|(sig_index, _)| {
+                module
+                    .runnable_module
+                    .get_trampoline(&module.info, sig_index)
+            })
+            .map(|wasm| (func, wasm)),
+        _ => None,
+    };
+    let (func, wasm) = wasm.ok_or_else(|| {
+        Box::new(format!(
+            "the interpreter can't call the native function of signature {}: \
+             only host functions created with `func!` or `Func::new`, and \
+             WebAssembly functions, can be called",
+            signature
+        )) as Box<dyn Any + Send>
+    })?;
+
+    match wasm.call(ctx, func, args, &mut rets) {
+        Ok(()) => Ok(rets),
+        Err(RuntimeError::Error { data }) => Err(data),
+        Err(RuntimeError::Trap { msg }) => Err(Box::new(WasmTrapInfo::from_message(&msg))),
+    }
+}
@@ -0,0 +1,223 @@
+use crate::code::Function;
+use crate::interpreter;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    ffi::c_void,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    process,
+    ptr::{self, NonNull},
+    sync::{Arc, RwLock},
+};
+use wasmer_runtime_core::{
+    backend::{
+        sys::{Memory, Protect},
+        CacheGen, RunnableModule,
+    },
+    cache::Error as CacheError,
+    module::ModuleInfo,
+    structures::TypedIndex,
+    typed_func::{Trampoline, Wasm, WasmTrapInfo},
+    types::{FuncIndex, FuncSig, LocalFuncIndex, SigIndex},
+    vm,
+};
+
+/// On-disk cache format: the translated functions, which don't depend on
+/// where they are loaded.
+#[derive(Serialize, Deserialize)]
+pub struct CacheImage {
+    pub functions: Vec<Function>,
+    /// Whether float operations canonicalize the NaNs they produce
+    pub canonicalize_nans: bool,
+}
+
+pub struct InterpreterCache {
+    pub buffer: Arc<[u8]>,
+}
+
+impl CacheGen for InterpreterCache {
+    fn generate_cache(&self) -> Result<(Box<[u8]>, Memory), CacheError> {
+        let mut memory = Memory::with_size_protect(self.buffer.len(), Protect::ReadWrite)
+            .map_err(CacheError::SerializeError)?;
+
+        let buffer = &*self.buffer;
+
+        unsafe {
+            memory.as_slice_mut()[..buffer.len()].copy_from_slice(buffer);
+        }
+
+        Ok(([].as_ref().into(), memory))
+    }
+}
+
+/// The functions of a module, with the signatures of what they call.
+pub struct ModuleCode {
+    pub functions: Vec<Function>,
+    /// The signatures of the module, by `SigIndex`
+    pub signatures: Vec<FuncSig>,
+    /// The `SigIndex` of each imported function
+    pub import_signatures: Vec<u32>,
+    /// Whether memory 0 and table 0 are imported
+    pub imported_memory: bool,
+    pub imported_table: bool,
+    pub canonicalize_nans: bool,
+}
+
+/// What the pointer to a local function points to.
+///
+/// There is no machine code to point to, so `get_func` returns a pointer to
+/// the function's entry, which only the interpreter dereferences: everything
+/// else passes function pointers around without calling them, or calls them
+/// through `invoke`.
+pub struct Entry {
+    pub code: *const ModuleCode,
+    pub function: u32,
+}
+
+lazy_static! {
+    /// The address ranges of the entries of the modules alive, from their
+    /// start to their end, telling the functions of interpreted modules from
+    /// native ones.
+    static ref ENTRIES: RwLock<BTreeMap<usize, usize>> = RwLock::new(BTreeMap::new());
+}
+
+/// The entry `func` points to, if it is the function of an interpreted
+/// module
+pub fn lookup(func: *const vm::Func) -> Option<&'static Entry> {
+    let address = func as usize;
+    let entries = ENTRIES.read().unwrap();
+    let (&start, &end) = entries.range(..=address).next_back()?;
+    if address < end && (address - start) % mem::size_of::<Entry>() == 0 {
+        // The module, and so its entries, outlive the instances calling them
+        Some(unsafe { &*(address as *const Entry) })
+    } else {
+        None
+    }
+}
+
+pub struct InterpreterModule {
+    code: Box<ModuleCode>,
+    entries: Box<[Entry]>,
+}
+
+// The entries only point to the module's own code
+unsafe impl Send for InterpreterModule {}
+unsafe impl Sync for InterpreterModule {}
+
+impl InterpreterModule {
+    pub fn new(info: &ModuleInfo, image: CacheImage) -> Self {
+        let code = Box::new(ModuleCode {
+            functions: image.functions,
+            signatures: info.signatures.iter().map(|(_, sig)| sig.clone()).collect(),
+            import_signatures: (0..info.imported_functions.len())
+                .map(|index| info.func_assoc[FuncIndex::new(index)].index() as u32)
+                .collect(),
+            imported_memory: !info.imported_memories.is_empty(),
+            imported_table: !info.imported_tables.is_empty(),
+            canonicalize_nans: image.canonicalize_nans,
+        });
+        let entries = (0..code.functions.len() as u32)
+            .map(|function| Entry {
+                code: &*code,
+                function,
+            })
+            .collect::<Box<[_]>>();
+        if !entries.is_empty() {
+            let start = entries.as_ptr() as usize;
+            let end = start + entries.len() * mem::size_of::<Entry>();
+            ENTRIES.write().unwrap().insert(start, end);
+        }
+        Self { code, entries }
+    }
+}
+
+impl Drop for InterpreterModule {
+    fn drop(&mut self) {
+        if !self.entries.is_empty() {
+            ENTRIES
+                .write()
+                .unwrap()
+                .remove(&(self.entries.as_ptr() as usize));
+        }
+    }
+}
+
+/// Never called: `invoke` runs functions itself.
+unsafe extern "C" fn dummy_trampoline(
+    _: *mut vm::Ctx,
+    _: NonNull<vm::Func>,
+    _: *const u64,
+    _: *mut u64,
+) {
+    unreachable!()
+}
+
+/// Calls `func` with the arguments at `args`, writing its results to `rets`.
+///
+/// `extra` points to the signature of the call, needed when `func` is native
+/// and so doesn't tell its own.
+unsafe extern "C" fn invoke(
+    _trampoline: Trampoline,
+    ctx: *mut vm::Ctx,
+    func: NonNull<vm::Func>,
+    args: *const u64,
+    rets: *mut u64,
+    trap_info: *mut WasmTrapInfo,
+    user_error: *mut Option<Box<dyn Any + Send>>,
+    extra: Option<NonNull<c_void>>,
+) -> bool {
+    let signature = extra.map(|sig| &*(sig.as_ptr() as *const FuncSig));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        interpreter::call(ctx, func.as_ptr(), signature, args)
+    }));
+    match result {
+        Ok(Ok(results)) => {
+            if !rets.is_null() {
+                ptr::copy_nonoverlapping(results.as_ptr(), rets, results.len());
+            }
+            true
+        }
+        Ok(Err(error)) | Err(error) => {
+            match error.downcast::<WasmTrapInfo>() {
+                Ok(info) => *trap_info = *info,
+                Err(error) => *user_error = Some(error),
+            }
+            false
+        }
+    }
+}
+
+impl RunnableModule for InterpreterModule {
+    fn get_func(
+        &self,
+        _: &ModuleInfo,
+        local_func_index: LocalFuncIndex,
+    ) -> Option<NonNull<vm::Func>> {
+        self.entries
+            .get(local_func_index.index())
+            .map(|entry| NonNull::from(entry).cast())
+    }
+
+    fn get_trampoline(&self, _: &ModuleInfo, sig_index: SigIndex) -> Option<Wasm> {
+        let signature = self
+            .code
+            .signatures
+            .get(sig_index.index())
+            .map(|sig| NonNull::from(sig).cast());
+        unsafe { Some(Wasm::from_raw_parts(dummy_trampoline, invoke, signature)) }
+    }
+
+    /// The interpreter calls host functions through their `HostCall`, which
+    /// returns their errors, so this is only reached when native code calls
+    /// a host function with the `vm::Ctx` of an interpreted instance, e.g.
+    /// through a table they share. There is no interpreter frame to return
+    /// the error to there, and unwinding through the native frames of the
+    /// host function isn't allowed, so the process is aborted.
+    unsafe fn do_early_trap(&self, _: Box<dyn Any + Send>) -> ! {
+        eprintln!(
+            "a host function called by native code failed on behalf of an interpreted instance"
+        );
+        process::abort()
+    }
+}
@@ -0,0 +1,59 @@
+//! Linking interpreted modules with modules compiled by another backend.
+
+use wasmer_clif_backend::CraneliftCompiler;
+use wasmer_interpreter_backend::InterpreterCompiler;
+use wasmer_runtime_core::{backend::Compiler, compile_with, imports, Func, Instance};
+
+const EXPORTER: &str = r#"
+(module
+  (func (export "double") (param i32) (result i32)
+    get_local 0
+    get_local 0
+    i32.add))
+"#;
+
+const IMPORTER: &str = r#"
+(module
+  (import "env" "double" (func $double (param i32) (result i32)))
+  (func (export "quadruple") (param i32) (result i32)
+    get_local 0
+    call $double
+    call $double))
+"#;
+
+fn instantiate(wat: &str, compiler: &dyn Compiler, exporter: Option<Instance>) -> Instance {
+    let wasm = wabt::wat2wasm(wat).unwrap();
+    let module = compile_with(&wasm, compiler).unwrap();
+    let mut import_object = imports! {};
+    if let Some(exporter) = exporter {
+        import_object.register("env", exporter);
+    }
+    module.instantiate(&import_object).unwrap()
+}
+
+#[test]
+fn interpreted_modules_call_native_exports() {
+    let exporter = instantiate(EXPORTER, &CraneliftCompiler::new(), None);
+    let importer = instantiate(IMPORTER, &InterpreterCompiler::new(), Some(exporter));
+
+    let quadruple: Func<i32, i32> = importer.func("quadruple").unwrap();
+    assert_eq!(quadruple.call(3).unwrap(), 12);
+}
+
+#[test]
+fn interpreted_exports_cant_be_imported_by_native_modules() {
+    let exporter = instantiate(EXPORTER, &InterpreterCompiler::new(), None);
+    let wasm = wabt::wat2wasm(IMPORTER).unwrap();
+    let module = compile_with(&wasm, &CraneliftCompiler::new()).unwrap();
+    let mut import_object = imports! {};
+    import_object.register("env", exporter);
+
+    match module.instantiate(&import_object) {
+        Err(error) => assert!(
+            error.to_string().contains("interpreted instance"),
+            "unexpected error: {}",
+            error
+        ),
+        Ok(_) => panic!("the interpreted export was imported"),
+    }
+}
@@ -0,0 +1,11 @@
+//! The LLVM backend as a plugin, built as `libwasmer_llvm.so`, to load into
+//! a `wasmer` binary built without it, e.g. with
+//! `wasmer run --backend-path libwasmer_llvm.so`.
+//!
+//! The plugin must be built with the same rustc and `wasmer-runtime-core`
+//! as the binary loading it.
+
+use wasmer_llvm_backend::LLVMCompiler;
+use wasmer_runtime_core::declare_backend_plugin;
+
+declare_backend_plugin!("llvm", LLVMCompiler::new());
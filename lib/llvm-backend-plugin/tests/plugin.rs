@@ -0,0 +1,119 @@
+//! Loads the plugin built by this crate into this test, whose own copy of
+//! `wasmer-runtime-core` has no backend, and runs modules it compiles.
+
+use std::{env, path::PathBuf};
+use wasmer_runtime_core::{
+    backend::Backend,
+    compile_with,
+    error::RuntimeError,
+    func, imports,
+    plugin::{load_backend, BackendPlugin},
+    vm::Ctx,
+    Func,
+};
+
+fn plugin_path() -> PathBuf {
+    // The tests are built in `target/<profile>/deps`, where the plugin is
+    // built too, before being copied to `target/<profile>`.
+    let deps = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let name = format!(
+        "{}wasmer_llvm{}",
+        env::consts::DLL_PREFIX,
+        env::consts::DLL_SUFFIX
+    );
+    [deps.join(&name), deps.parent().unwrap().join(&name)]
+        .iter()
+        .find(|path| path.exists())
+        .cloned()
+        .expect("the plugin wasn't built")
+}
+
+fn load() -> BackendPlugin {
+    unsafe { load_backend(&plugin_path()) }.unwrap()
+}
+
+#[test]
+fn the_plugin_compiles_modules_that_run() {
+    let plugin = load();
+    assert_eq!(plugin.backend, Backend::LLVM);
+
+    let wasm = wabt::wat2wasm(
+        r#"
+        (module
+          (import "env" "double" (func $double (param i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "add_doubled") (param i32 i32) (result i32)
+            (i32.store (i32.const 0) (call $double (get_local 1)))
+            (i32.add (get_local 0) (i32.load (i32.const 0)))))
+        "#,
+    )
+    .unwrap();
+    let module = compile_with(&wasm, &*plugin.compiler).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "double" => func!(|_: &mut Ctx, x: i32| x * 2),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let add_doubled: Func<(i32, i32), i32> = instance.func("add_doubled").unwrap();
+    assert_eq!(add_doubled.call(1, 20).unwrap(), 41);
+    assert_eq!(instance.context().memory(0).view::<i32>()[0].get(), 40);
+}
+
+#[test]
+fn traps_are_caught() {
+    let plugin = load();
+    let wasm = wabt::wat2wasm(
+        r#"
+        (module
+          (func (export "trap") unreachable))
+        "#,
+    )
+    .unwrap();
+    let module = compile_with(&wasm, &*plugin.compiler).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let trap: Func<(), ()> = instance.func("trap").unwrap();
+    match trap.call() {
+        Err(RuntimeError::Trap { .. }) => {}
+        result => panic!("the trap wasn't caught: {:?}", result),
+    }
+}
+
+#[test]
+fn host_function_errors_are_returned() {
+    #[derive(Debug, PartialEq)]
+    struct HostError(u32);
+
+    let plugin = load();
+    let wasm = wabt::wat2wasm(
+        r#"
+        (module
+          (import "env" "fail" (func $fail))
+          (func (export "call_fail") (call $fail)))
+        "#,
+    )
+    .unwrap();
+    let module = compile_with(&wasm, &*plugin.compiler).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "fail" => func!(|_: &mut Ctx| -> Result<(), HostError> { Err(HostError(42)) }),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let call_fail: Func<(), ()> = instance.func("call_fail").unwrap();
+    match call_fail.call() {
+        Err(RuntimeError::Error { data }) => {
+            assert_eq!(data.downcast_ref::<HostError>(), Some(&HostError(42)))
+        }
+        result => panic!("the error wasn't returned: {:?}", result),
+    }
+}
+
+#[test]
+fn compile_errors_are_reported() {
+    let plugin = load();
+    assert!(compile_with(b"not wasm", &*plugin.compiler).is_err());
+}
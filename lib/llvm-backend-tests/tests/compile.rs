@@ -1,4 +1,4 @@
-use wasmer_llvm_backend::{InkwellModule, LLVMBackendConfig, LLVMCallbacks};
+use wasmer_llvm_backend::{InkwellModule, LLVMBackendConfig, LLVMCallbacks, LLVMOptLevel};
 use wasmer_llvm_backend_tests::{get_compiler, wat2wasm};
 use wasmer_runtime::{imports, CompilerConfig};
 use wasmer_runtime_core::{backend::BackendCompilerConfig, compile_with, compile_with_config};
@@ -51,6 +51,7 @@ fn crash_select_with_mismatched_pending() {
     let compiler_config = CompilerConfig {
         backend_specific_config: Some(BackendCompilerConfig(Box::new(LLVMBackendConfig {
             callbacks: Some(record_pre_opt_ir.clone()),
+            ..Default::default()
         }))),
         ..Default::default()
     };
@@ -66,3 +67,64 @@ fn crash_select_with_mismatched_pending() {
 "#;
     assert!(&record_pre_opt_ir.borrow().preopt_ir.contains(LLVM));
 }
+
+#[test]
+fn optimization_pipeline_config() {
+    const WAT: &str = r#"
+  (module
+    (func (export "sum") (param i32) (result i32) (local i32)
+      block
+        loop
+          local.get 0
+          i32.eqz
+          br_if 1
+          local.get 1
+          local.get 0
+          i32.add
+          local.set 1
+          local.get 0
+          i32.const 1
+          i32.sub
+          local.set 0
+          br 0
+        end
+      end
+      local.get 1))
+"#;
+    let wasm_binary = wat2wasm(WAT.as_bytes()).expect("WAST not valid or malformed");
+    let dump_ir_dir = std::env::temp_dir().join("wasmer-llvm-backend-tests-dump-ir");
+
+    for &opt_level in &[
+        LLVMOptLevel::O0,
+        LLVMOptLevel::O1,
+        LLVMOptLevel::O2,
+        LLVMOptLevel::O3,
+        LLVMOptLevel::Size,
+    ] {
+        let compiler_config = CompilerConfig {
+            backend_specific_config: Some(BackendCompilerConfig(Box::new(LLVMBackendConfig {
+                opt_level,
+                vectorize: false,
+                inline_threshold: Some(0),
+                dump_ir_dir: Some(dump_ir_dir.clone()),
+                ..Default::default()
+            }))),
+            ..Default::default()
+        };
+        let module = compile_with_config(&wasm_binary, &get_compiler(), compiler_config).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+        let sum = instance.func::<i32, i32>("sum").unwrap();
+        assert_eq!(sum.call(10), Ok(55), "{:?}", opt_level);
+    }
+
+    assert!(dump_ir_dir.join("fn0.preopt.ll").is_file());
+    assert!(dump_ir_dir.join("fn0.postopt.ll").is_file());
+}
+
+#[test]
+fn parse_opt_levels() {
+    assert_eq!("0".parse(), Ok(LLVMOptLevel::O0));
+    assert_eq!("O2".parse(), Ok(LLVMOptLevel::O2));
+    assert_eq!("s".parse(), Ok(LLVMOptLevel::Size));
+    assert!("4".parse::<LLVMOptLevel>().is_err());
+}
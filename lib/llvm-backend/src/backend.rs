@@ -120,6 +120,11 @@ fn get_callbacks() -> Callbacks {
             fn_name!("vm.memory.grow.static.import") => vmcalls::imported_static_memory_grow as _,
             fn_name!("vm.memory.size.static.import") => vmcalls::imported_static_memory_size as _,
 
+            fn_name!("vm.memory.copy.local") => vmcalls::local_memory_copy as _,
+            fn_name!("vm.memory.fill.local") => vmcalls::local_memory_fill as _,
+            fn_name!("vm.memory.copy.import") => vmcalls::imported_memory_copy as _,
+            fn_name!("vm.memory.fill.import") => vmcalls::imported_memory_fill as _,
+
             fn_name!("vm.exception.trap") => throw_trap as _,
             fn_name!("vm.breakpoint") => throw_breakpoint as _,
 
@@ -162,8 +167,9 @@ unsafe impl Sync for LLVMBackend {}
 
 pub struct LLVMBackend {
     module: *mut LLVMModule,
-    #[allow(dead_code)]
-    buffer: Arc<Buffer>,
+    /// The object file `module` was loaded from, which the loader keeps
+    /// referring to, so it has to live as long as the module.
+    _buffer: Arc<Buffer>,
     msm: Option<ModuleStateMap>,
     local_func_id_to_offset: Vec<usize>,
 }
@@ -176,6 +182,7 @@ impl LLVMBackend {
         _module_info: &ModuleInfo,
         target_machine: &TargetMachine,
         llvm_callbacks: &Option<Rc<RefCell<dyn LLVMCallbacks>>>,
+        runs_on_host: bool,
     ) -> (Self, LLVMCache) {
         let memory_buffer = target_machine
             .write_to_memory_buffer(&module.borrow_mut(), FileType::Object)
@@ -187,6 +194,20 @@ impl LLVMBackend {
                 .obj_memory_buffer_callback(&memory_buffer);
         }
 
+        // An object for another target can only be cached, not loaded here.
+        if !runs_on_host {
+            let buffer = Arc::new(Buffer::LlvmMemory(memory_buffer));
+            return (
+                Self {
+                    module: ptr::null_mut(),
+                    _buffer: Arc::clone(&buffer),
+                    msm: None,
+                    local_func_id_to_offset: vec![],
+                },
+                LLVMCache { buffer },
+            );
+        }
+
         let callbacks = get_callbacks();
         let mut module: *mut LLVMModule = ptr::null_mut();
 
@@ -312,7 +333,7 @@ impl LLVMBackend {
                 return (
                     Self {
                         module,
-                        buffer: Arc::clone(&buffer),
+                        _buffer: Arc::clone(&buffer),
                         msm: Some(msm),
                         local_func_id_to_offset,
                     },
@@ -325,7 +346,7 @@ impl LLVMBackend {
         (
             Self {
                 module,
-                buffer: Arc::clone(&buffer),
+                _buffer: Arc::clone(&buffer),
                 msm: None,
                 local_func_id_to_offset: vec![],
             },
@@ -354,7 +375,7 @@ impl LLVMBackend {
         Ok((
             Self {
                 module,
-                buffer: Arc::clone(&buffer),
+                _buffer: Arc::clone(&buffer),
                 msm: None,
                 local_func_id_to_offset: vec![],
             },
@@ -365,7 +386,9 @@ impl LLVMBackend {
 
 impl Drop for LLVMBackend {
     fn drop(&mut self) {
-        unsafe { module_delete(self.module) }
+        if !self.module.is_null() {
+            unsafe { module_delete(self.module) }
+        }
     }
 }
 
@@ -375,6 +398,9 @@ impl RunnableModule for LLVMBackend {
         info: &ModuleInfo,
         local_func_index: LocalFuncIndex,
     ) -> Option<NonNull<vm::Func>> {
+        if self.module.is_null() {
+            return None;
+        }
         let index = info.imported_functions.len() + local_func_index.index();
         let name = if cfg!(target_os = "macos") {
             format!("_fn{}", index)
@@ -389,6 +415,9 @@ impl RunnableModule for LLVMBackend {
     }
 
     fn get_trampoline(&self, _: &ModuleInfo, sig_index: SigIndex) -> Option<Wasm> {
+        if self.module.is_null() {
+            return None;
+        }
         let trampoline: Trampoline = unsafe {
             let name = if cfg!(target_os = "macos") {
                 format!("_trmp{}", sig_index.index())
@@ -411,6 +440,9 @@ impl RunnableModule for LLVMBackend {
     }
 
     fn get_code(&self) -> Option<&[u8]> {
+        if self.module.is_null() {
+            return None;
+        }
         Some(unsafe {
             std::slice::from_raw_parts(
                 llvm_backend_get_code_ptr(self.module),
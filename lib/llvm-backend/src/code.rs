@@ -5,20 +5,21 @@ use crate::{
     stackmap::{StackmapEntry, StackmapEntryKind, StackmapRegistry, ValueSemantic},
     state::{ControlFrame, ExtraInfo, IfElseState, State},
     trampolines::generate_trampolines,
-    LLVMBackendConfig, LLVMCallbacks,
+    LLVMBackendConfig, LLVMCallbacks, LLVMOptLevel,
 };
 use inkwell::{
+    attributes::{Attribute, AttributeLoc},
     builder::Builder,
     context::Context,
     module::{Linkage, Module},
-    passes::PassManager,
+    passes::{PassManager, PassManagerBuilder},
     targets::{CodeModel, InitializationConfig, RelocMode, Target, TargetMachine},
     types::{
         BasicType, BasicTypeEnum, FloatMathType, FunctionType, IntType, PointerType, VectorType,
     },
     values::{
-        BasicValue, BasicValueEnum, FloatValue, FunctionValue, IntValue, PhiValue, PointerValue,
-        VectorValue,
+        AnyValue, BasicValue, BasicValueEnum, FloatValue, FunctionValue, IntValue, PhiValue,
+        PointerValue, VectorValue,
     },
     AddressSpace, AtomicOrdering, AtomicRMWBinOp, FloatPredicate, IntPredicate, OptimizationLevel,
 };
@@ -26,13 +27,15 @@ use smallvec::SmallVec;
 use std::{
     cell::RefCell,
     collections::HashMap,
+    fs,
     mem::ManuallyDrop,
+    path::{Path, PathBuf},
     rc::Rc,
     sync::{Arc, RwLock},
 };
 
 use wasmer_runtime_core::{
-    backend::{Backend, CacheGen, CompilerConfig, Token},
+    backend::{is_host_triple, Backend, CacheGen, CompilerConfig, SpectreMitigations, Token},
     cache::{Artifact, Error as CacheError},
     codegen::*,
     memory::MemoryType,
@@ -964,8 +967,17 @@ pub struct LLVMModuleCodeGenerator<'ctx> {
     module: ManuallyDrop<Rc<RefCell<Module<'ctx>>>>,
     stackmaps: Rc<RefCell<StackmapRegistry>>,
     track_state: bool,
+    spectre_mitigations: SpectreMitigations,
     target_machine: TargetMachine,
+    target_triple: String,
+    target_cpu_name: String,
+    target_cpu_features: String,
+    runs_on_host: bool,
     llvm_callbacks: Option<Rc<RefCell<dyn LLVMCallbacks>>>,
+    opt_level: LLVMOptLevel,
+    vectorize: bool,
+    inline_threshold: Option<u32>,
+    dump_ir_dir: Option<PathBuf>,
 }
 
 pub struct LLVMFunctionCodeGenerator<'ctx> {
@@ -986,6 +998,7 @@ pub struct LLVMFunctionCodeGenerator<'ctx> {
     index: usize,
     opcode_offset: usize,
     track_state: bool,
+    fence_indirect_calls: bool,
     module: Rc<RefCell<Module<'ctx>>>,
 }
 
@@ -2128,6 +2141,9 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 );
                 builder.build_unreachable();
                 builder.position_at_end(&continue_block);
+                if self.fence_indirect_calls {
+                    builder.build_call(intrinsics.lfence, &[], "lfence");
+                }
 
                 let wasmer_fn_sig = &info.signatures[sig_index];
                 let fn_ty = signatures[sig_index];
@@ -8560,6 +8576,65 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 );
                 state.push1(result.try_as_basic_value().left().unwrap());
             }
+            Operator::MemoryCopy | Operator::MemoryFill => {
+                let (func_value, index) = match MemoryIndex::new(0).local_or_import(info) {
+                    LocalOrImport::Local(local_mem_index) => (
+                        match *op {
+                            Operator::MemoryCopy => intrinsics.memory_copy_local,
+                            _ => intrinsics.memory_fill_local,
+                        },
+                        local_mem_index.index(),
+                    ),
+                    LocalOrImport::Import(import_mem_index) => (
+                        match *op {
+                            Operator::MemoryCopy => intrinsics.memory_copy_import,
+                            _ => intrinsics.memory_fill_import,
+                        },
+                        import_mem_index.index(),
+                    ),
+                };
+
+                let memory_index_const = intrinsics
+                    .i32_ty
+                    .const_int(index as u64, false)
+                    .as_basic_value_enum();
+                // `src` is the byte value to fill with for `memory.fill`.
+                let ((dst, _), (src, _), (len, _)) = state.pop3_extra()?;
+
+                let out_of_bounds = builder
+                    .build_call(
+                        func_value,
+                        &[ctx.basic(), memory_index_const, dst, src, len],
+                        &state.var_name(),
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_int_value();
+                let out_of_bounds = builder.build_int_compare(
+                    IntPredicate::NE,
+                    out_of_bounds,
+                    intrinsics.i32_zero,
+                    &state.var_name(),
+                );
+
+                let in_bounds_block = context.append_basic_block(function, "in_bounds_block");
+                let not_in_bounds_block =
+                    context.append_basic_block(function, "not_in_bounds_block");
+                builder.build_conditional_branch(
+                    out_of_bounds,
+                    &not_in_bounds_block,
+                    &in_bounds_block,
+                );
+                builder.position_at_end(&not_in_bounds_block);
+                builder.build_call(
+                    intrinsics.throw_trap,
+                    &[intrinsics.trap_memory_oob],
+                    "throw",
+                );
+                builder.build_unreachable();
+                builder.position_at_end(&in_bounds_block);
+            }
             _ => {
                 return Err(CodegenError {
                     message: format!("Operator {:?} unimplemented", op),
@@ -8635,6 +8710,117 @@ impl Drop for LLVMModuleCodeGenerator<'_> {
     }
 }
 
+fn create_target_machine(
+    triple: &str,
+    cpu_name: &str,
+    cpu_features: &str,
+    opt_level: LLVMOptLevel,
+) -> TargetMachine {
+    let codegen_opt_level = match opt_level {
+        LLVMOptLevel::O0 => OptimizationLevel::None,
+        LLVMOptLevel::O1 => OptimizationLevel::Less,
+        LLVMOptLevel::O2 | LLVMOptLevel::Size => OptimizationLevel::Default,
+        LLVMOptLevel::O3 => OptimizationLevel::Aggressive,
+    };
+    Target::from_triple(triple)
+        .unwrap()
+        .create_target_machine(
+            triple,
+            cpu_name,
+            cpu_features,
+            codegen_opt_level,
+            RelocMode::Static,
+            CodeModel::Large,
+        )
+        .unwrap()
+}
+
+/// Writes the IR of each function defined in `module` to `<dir>/<name>.<stage>.ll`.
+fn dump_function_ir(module: &Module, dir: &Path, stage: &str) -> Result<(), CodegenError> {
+    let io_error = |e: std::io::Error| CodegenError {
+        message: format!("cannot dump the IR to {}: {}", dir.display(), e),
+    };
+    fs::create_dir_all(dir).map_err(io_error)?;
+
+    let mut function = module.get_first_function();
+    while let Some(f) = function {
+        if f.count_basic_blocks() > 0 {
+            let path = dir.join(format!("{}.{}.ll", f.get_name().to_string_lossy(), stage));
+            fs::write(path, f.print_to_string().to_string()).map_err(io_error)?;
+        }
+        function = f.get_next_function();
+    }
+    Ok(())
+}
+
+impl<'ctx> LLVMModuleCodeGenerator<'ctx> {
+    fn add_optimization_passes(&self, pass_manager: &PassManager<Module<'ctx>>) {
+        match self.opt_level {
+            LLVMOptLevel::O0 => {}
+            LLVMOptLevel::O1 => {
+                pass_manager.add_type_based_alias_analysis_pass();
+                self.add_inlining_pass(pass_manager);
+                pass_manager.add_scalar_repl_aggregates_pass();
+                pass_manager.add_instruction_combining_pass();
+                pass_manager.add_cfg_simplification_pass();
+                pass_manager.add_early_cse_pass();
+            }
+            LLVMOptLevel::O2 | LLVMOptLevel::O3 | LLVMOptLevel::Size => {
+                pass_manager.add_type_based_alias_analysis_pass();
+                pass_manager.add_ipsccp_pass();
+                pass_manager.add_prune_eh_pass();
+                pass_manager.add_dead_arg_elimination_pass();
+                self.add_inlining_pass(pass_manager);
+                pass_manager.add_lower_expect_intrinsic_pass();
+                pass_manager.add_scalar_repl_aggregates_pass();
+                pass_manager.add_instruction_combining_pass();
+                pass_manager.add_jump_threading_pass();
+                pass_manager.add_correlated_value_propagation_pass();
+                pass_manager.add_cfg_simplification_pass();
+                pass_manager.add_reassociate_pass();
+                pass_manager.add_loop_rotate_pass();
+                if self.opt_level != LLVMOptLevel::Size {
+                    pass_manager.add_loop_unswitch_pass();
+                }
+                pass_manager.add_ind_var_simplify_pass();
+                pass_manager.add_licm_pass();
+                if self.vectorize {
+                    pass_manager.add_loop_vectorize_pass();
+                }
+                pass_manager.add_instruction_combining_pass();
+                pass_manager.add_ipsccp_pass();
+                pass_manager.add_reassociate_pass();
+                pass_manager.add_cfg_simplification_pass();
+                pass_manager.add_gvn_pass();
+                pass_manager.add_memcpy_optimize_pass();
+                pass_manager.add_dead_store_elimination_pass();
+                pass_manager.add_bit_tracking_dce_pass();
+                pass_manager.add_instruction_combining_pass();
+                pass_manager.add_reassociate_pass();
+                pass_manager.add_cfg_simplification_pass();
+                if self.vectorize {
+                    pass_manager.add_slp_vectorize_pass();
+                }
+                pass_manager.add_early_cse_pass();
+            }
+        }
+    }
+
+    fn add_inlining_pass(&self, pass_manager: &PassManager<Module<'ctx>>) {
+        match self.inline_threshold {
+            None => pass_manager.add_function_inlining_pass(),
+            Some(threshold) => {
+                // The C API only takes a threshold through a pass manager builder, which adds
+                // nothing but the inliner at `OptimizationLevel::None`.
+                let builder = PassManagerBuilder::create();
+                builder.set_optimization_level(OptimizationLevel::None);
+                builder.set_inliner_with_threshold(threshold);
+                builder.populate_module_pass_manager(pass_manager);
+            }
+        }
+    }
+}
+
 impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, CodegenError>
     for LLVMModuleCodeGenerator<'ctx>
 {
@@ -8653,8 +8839,8 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
 
         let triple = triple.unwrap_or(TargetMachine::get_default_triple().to_string());
 
+        // both targets are always available, to cross-compile
         match triple {
-            #[cfg(target_arch = "x86_64")]
             _ if triple.starts_with("x86") => Target::initialize_x86(&InitializationConfig {
                 asm_parser: true,
                 asm_printer: true,
@@ -8663,7 +8849,6 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
                 info: true,
                 machine_code: true,
             }),
-            #[cfg(target_arch = "aarch64")]
             _ if triple.starts_with("aarch64") => {
                 Target::initialize_aarch64(&InitializationConfig {
                     asm_parser: true,
@@ -8677,19 +8862,13 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
             _ => unimplemented!("target {} not supported", triple),
         }
 
-        let target = Target::from_triple(&triple).unwrap();
-        let target_machine = target
-            .create_target_machine(
-                &triple,
-                &cpu_name.unwrap_or(TargetMachine::get_host_cpu_name().to_string()),
-                &cpu_features.unwrap_or(TargetMachine::get_host_cpu_features().to_string()),
-                OptimizationLevel::Aggressive,
-                RelocMode::Static,
-                CodeModel::Large,
-            )
-            .unwrap();
+        let cpu_name = cpu_name.unwrap_or(TargetMachine::get_host_cpu_name().to_string());
+        let cpu_features =
+            cpu_features.unwrap_or(TargetMachine::get_host_cpu_features().to_string());
+        let opt_level = LLVMOptLevel::default();
+        let target_machine = create_target_machine(&triple, &cpu_name, &cpu_features, opt_level);
 
-        module.set_target(&target);
+        module.set_target(&Target::from_triple(&triple).unwrap());
         module.set_data_layout(&target_machine.get_target_data().get_data_layout());
 
         let builder = context.create_builder();
@@ -8716,8 +8895,17 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
             personality_func: ManuallyDrop::new(personality_func),
             stackmaps: Rc::new(RefCell::new(StackmapRegistry::default())),
             track_state: false,
+            spectre_mitigations: SpectreMitigations::default(),
             target_machine,
+            runs_on_host: is_host_triple(&triple),
+            target_triple: triple,
+            target_cpu_name: cpu_name,
+            target_cpu_features: cpu_features,
             llvm_callbacks: None,
+            opt_level,
+            vectorize: true,
+            inline_threshold: None,
+            dump_ir_dir: None,
         }
     }
 
@@ -8753,6 +8941,17 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
 
         let function = &self.llvm_functions.borrow_mut()[&func_index];
         function.set_personality_function(*self.personality_func);
+        if self.spectre_mitigations.mask_memory_accesses
+            || self.spectre_mitigations.mask_table_indices
+        {
+            // Speculative load hardening masks every load that depends on a
+            // mispredicted branch, which covers memory and table bounds checks.
+            let slh = context.create_enum_attribute(
+                Attribute::get_named_enum_kind_id("speculative_load_hardening"),
+                0,
+            );
+            function.add_attribute(AttributeLoc::Function, slh);
+        }
 
         let mut state: State<'ctx> = State::new();
         let entry_block = context.append_basic_block(*function, "entry");
@@ -8828,6 +9027,7 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
             index: local_func_index,
             opcode_offset: 0,
             track_state: self.track_state,
+            fence_indirect_calls: self.spectre_mitigations.fence_indirect_calls,
             module: (*self.module).clone(),
         };
         self.functions.push(code);
@@ -8872,43 +9072,21 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
                 .preopt_ir_callback(&*self.module.borrow_mut());
         }
 
+        if let Some(ref dir) = self.dump_ir_dir {
+            dump_function_ir(&*self.module.borrow(), dir, "preopt")?;
+        }
+
         let pass_manager = PassManager::create(());
 
         #[cfg(feature = "test")]
         pass_manager.add_verifier_pass();
 
-        pass_manager.add_type_based_alias_analysis_pass();
-        pass_manager.add_ipsccp_pass();
-        pass_manager.add_prune_eh_pass();
-        pass_manager.add_dead_arg_elimination_pass();
-        pass_manager.add_function_inlining_pass();
-        pass_manager.add_lower_expect_intrinsic_pass();
-        pass_manager.add_scalar_repl_aggregates_pass();
-        pass_manager.add_instruction_combining_pass();
-        pass_manager.add_jump_threading_pass();
-        pass_manager.add_correlated_value_propagation_pass();
-        pass_manager.add_cfg_simplification_pass();
-        pass_manager.add_reassociate_pass();
-        pass_manager.add_loop_rotate_pass();
-        pass_manager.add_loop_unswitch_pass();
-        pass_manager.add_ind_var_simplify_pass();
-        pass_manager.add_licm_pass();
-        pass_manager.add_loop_vectorize_pass();
-        pass_manager.add_instruction_combining_pass();
-        pass_manager.add_ipsccp_pass();
-        pass_manager.add_reassociate_pass();
-        pass_manager.add_cfg_simplification_pass();
-        pass_manager.add_gvn_pass();
-        pass_manager.add_memcpy_optimize_pass();
-        pass_manager.add_dead_store_elimination_pass();
-        pass_manager.add_bit_tracking_dce_pass();
-        pass_manager.add_instruction_combining_pass();
-        pass_manager.add_reassociate_pass();
-        pass_manager.add_cfg_simplification_pass();
-        pass_manager.add_slp_vectorize_pass();
-        pass_manager.add_early_cse_pass();
+        self.add_optimization_passes(&pass_manager);
 
         pass_manager.run_on(&*self.module.borrow_mut());
+        if let Some(ref dir) = self.dump_ir_dir {
+            dump_function_ir(&*self.module.borrow(), dir, "postopt")?;
+        }
         if let Some(ref mut callbacks) = self.llvm_callbacks {
             callbacks
                 .borrow_mut()
@@ -8924,15 +9102,42 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
             module_info,
             &self.target_machine,
             &mut self.llvm_callbacks,
+            self.runs_on_host,
         );
         Ok((backend, Box::new(cache_gen)))
     }
 
     fn feed_compiler_config(&mut self, config: &CompilerConfig) -> Result<(), CodegenError> {
+        if config.no_signals {
+            return Err(CodegenError {
+                message: "the LLVM backend relies on signal handlers for stack overflows"
+                    .to_string(),
+            });
+        }
+        if config.spectre_mitigations.fence_indirect_calls
+            && !self.target_triple.starts_with("x86_64")
+        {
+            return Err(CodegenError {
+                message: "fencing indirect calls needs an x86-64 target".to_string(),
+            });
+        }
         self.track_state = config.track_state;
+        self.spectre_mitigations = config.spectre_mitigations;
         if let Some(backend_compiler_config) = &config.backend_specific_config {
             if let Some(llvm_config) = backend_compiler_config.get_specific::<LLVMBackendConfig>() {
                 self.llvm_callbacks = llvm_config.callbacks.clone();
+                self.vectorize = llvm_config.vectorize;
+                self.inline_threshold = llvm_config.inline_threshold;
+                self.dump_ir_dir = llvm_config.dump_ir_dir.clone();
+                if llvm_config.opt_level != self.opt_level {
+                    self.opt_level = llvm_config.opt_level;
+                    self.target_machine = create_target_machine(
+                        &self.target_triple,
+                        &self.target_cpu_name,
+                        &self.target_cpu_features,
+                        self.opt_level,
+                    );
+                }
             }
         }
         Ok(())
@@ -8986,6 +9191,7 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
             runnable_module: Arc::new(Box::new(backend)),
             cache_gen: Box::new(cache_gen),
             info,
+            compile_info: None,
         })
     }
 }
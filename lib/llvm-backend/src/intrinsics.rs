@@ -81,6 +81,7 @@ pub struct Intrinsics<'ctx> {
 
     pub expect_i1: FunctionValue<'ctx>,
     pub trap: FunctionValue<'ctx>,
+    pub lfence: FunctionValue<'ctx>,
 
     pub void_ty: VoidType<'ctx>,
     pub i1_ty: IntType<'ctx>,
@@ -142,6 +143,11 @@ pub struct Intrinsics<'ctx> {
     pub memory_size_static_import: FunctionValue<'ctx>,
     pub memory_size_shared_import: FunctionValue<'ctx>,
 
+    pub memory_copy_local: FunctionValue<'ctx>,
+    pub memory_fill_local: FunctionValue<'ctx>,
+    pub memory_copy_import: FunctionValue<'ctx>,
+    pub memory_fill_import: FunctionValue<'ctx>,
+
     pub throw_trap: FunctionValue<'ctx>,
     pub throw_breakpoint: FunctionValue<'ctx>,
 
@@ -311,6 +317,16 @@ impl<'ctx> Intrinsics<'ctx> {
         );
         let ret_i32_take_ctx_i32 =
             i32_ty.fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false);
+        let ret_i32_take_ctx_i32_i32_i32_i32 = i32_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
 
         let ret_i1_take_i1_i1 = i1_ty.fn_type(&[i1_ty_basic, i1_ty_basic], false);
         let intrinsics = Self {
@@ -392,6 +408,7 @@ impl<'ctx> Intrinsics<'ctx> {
 
             expect_i1: module.add_function("llvm.expect.i1", ret_i1_take_i1_i1, None),
             trap: module.add_function("llvm.trap", void_ty.fn_type(&[], false), None),
+            lfence: module.add_function("llvm.x86.sse2.lfence", void_ty.fn_type(&[], false), None),
 
             void_ty,
             i1_ty,
@@ -500,6 +517,28 @@ impl<'ctx> Intrinsics<'ctx> {
                 ret_i32_take_ctx_i32,
                 None,
             ),
+
+            memory_copy_local: module.add_function(
+                "vm.memory.copy.local",
+                ret_i32_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_fill_local: module.add_function(
+                "vm.memory.fill.local",
+                ret_i32_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_copy_import: module.add_function(
+                "vm.memory.copy.import",
+                ret_i32_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_fill_import: module.add_function(
+                "vm.memory.fill.import",
+                ret_i32_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+
             throw_trap: module.add_function(
                 "vm.exception.trap",
                 void_ty.fn_type(&[i32_ty_basic], false),
@@ -45,6 +45,68 @@ pub trait LLVMCallbacks: std::any::Any + 'static {
     fn obj_memory_buffer_callback(&mut self, _memory_buffer: &InkwellMemoryBuffer) {}
 }
 
+/// The optimization level of the LLVM backend, for both the IR pass pipeline
+/// and code generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LLVMOptLevel {
+    /// No optimization passes, for the fastest compilation.
+    O0,
+    /// Only cleanups: inlining, scalar replacement, combining and CSE.
+    O1,
+    /// The full pass pipeline, with the default code generation level.
+    O2,
+    /// The full pass pipeline, with aggressive code generation.
+    O3,
+    /// The full pass pipeline without loop unswitching, which grows code.
+    Size,
+}
+
+impl Default for LLVMOptLevel {
+    fn default() -> Self {
+        LLVMOptLevel::O3
+    }
+}
+
+impl std::str::FromStr for LLVMOptLevel {
+    type Err = String;
+
+    /// Parses `0`, `1`, `2`, `3` or `s`, optionally prefixed by `O`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_start_matches('O') {
+            "0" => Ok(LLVMOptLevel::O0),
+            "1" => Ok(LLVMOptLevel::O1),
+            "2" => Ok(LLVMOptLevel::O2),
+            "3" => Ok(LLVMOptLevel::O3),
+            "s" => Ok(LLVMOptLevel::Size),
+            _ => Err(format!(
+                "unknown optimization level `{}`, expected 0, 1, 2, 3 or s",
+                s
+            )),
+        }
+    }
+}
+
 pub struct LLVMBackendConfig {
     pub callbacks: Option<std::rc::Rc<std::cell::RefCell<dyn LLVMCallbacks>>>,
+    /// The optimization level, `O3` by default.
+    pub opt_level: LLVMOptLevel,
+    /// Whether to run the loop and SLP vectorizers, on by default.
+    pub vectorize: bool,
+    /// The threshold of the inliner, instead of LLVM's default of 225.
+    pub inline_threshold: Option<u32>,
+    /// A directory to write the IR of each function to, before and after the
+    /// optimization passes, as `fn<index>.preopt.ll` and `fn<index>.postopt.ll`.
+    pub dump_ir_dir: Option<std::path::PathBuf>,
+}
+
+impl Default for LLVMBackendConfig {
+    fn default() -> Self {
+        Self {
+            callbacks: None,
+            opt_level: LLVMOptLevel::default(),
+            vectorize: true,
+            inline_threshold: None,
+            dump_ir_dir: None,
+        }
+    }
 }
@@ -9,6 +9,11 @@ use nix::sys::signal::{
 };
 use std::ptr;
 
+/// `MAP_JIT`, without which the hardened runtime of macOS doesn't let code
+/// sections be made executable.
+#[cfg(target_os = "macos")]
+const MAP_JIT: libc::c_int = 0x0800;
+
 /// `__register_frame` and `__deregister_frame` on macos take a single fde as an
 /// argument, so we need to parse the fde table here.
 ///
@@ -87,6 +92,11 @@ pub unsafe fn alloc_memory(
     ptr_out: &mut *mut u8,
     size_out: &mut usize,
 ) -> LLVMResult {
+    #[cfg(target_os = "macos")]
+    let flags = MAP_PRIVATE | MAP_ANON | MAP_JIT;
+    #[cfg(not(target_os = "macos"))]
+    let flags = MAP_PRIVATE | MAP_ANON;
+
     let size = round_up_to_page_size(size);
     let ptr = mmap(
         ptr::null_mut(),
@@ -97,7 +107,7 @@ pub unsafe fn alloc_memory(
             MemProtect::READ_WRITE => PROT_READ | PROT_WRITE,
             MemProtect::READ_EXECUTE => PROT_READ | PROT_EXEC,
         },
-        MAP_PRIVATE | MAP_ANON,
+        flags,
         -1,
         0,
     );
@@ -1,4 +1,7 @@
-#[cfg(all(test, any(feature = "singlepass", feature = "llvm")))]
+#[cfg(all(
+    test,
+    any(feature = "singlepass", feature = "llvm", feature = "interpreter")
+))]
 mod tests {
     use wabt::wat2wasm;
 
@@ -34,7 +37,23 @@ mod tests {
         (c, Backend::Singlepass)
     }
 
-    #[cfg(not(any(feature = "llvm", feature = "clif", feature = "singlepass")))]
+    #[cfg(feature = "interpreter")]
+    fn get_compiler(limit: u64) -> (impl Compiler, Backend) {
+        use wasmer_interpreter_backend::ModuleCodeGenerator as InterpreterMCG;
+        let c: StreamingCompiler<InterpreterMCG, _, _, _, _> = StreamingCompiler::new(move || {
+            let mut chain = MiddlewareChain::new();
+            chain.push(Metering::new(limit));
+            chain
+        });
+        (c, Backend::Interpreter)
+    }
+
+    #[cfg(not(any(
+        feature = "llvm",
+        feature = "clif",
+        feature = "singlepass",
+        feature = "interpreter"
+    )))]
     compile_error!("compiler not specified, activate a compiler via features");
 
     #[cfg(feature = "clif")]
@@ -142,6 +161,7 @@ mod tests {
 
         // verify it used the correct number of points
         assert_eq!(get_points_used(&instance), 74);
+        assert_eq!(instance.stats().points_used, Some(74));
     }
 
     #[test]
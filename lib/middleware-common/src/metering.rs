@@ -1,13 +1,11 @@
 use wasmer_runtime_core::{
     codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
     module::ModuleInfo,
-    vm::{Ctx, InternalField},
+    vm::{Ctx, METERING_POINTS_USED},
     wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType},
     Instance,
 };
 
-static INTERNAL_FIELD: InternalField = InternalField::allocate();
-
 /// Metering is a compiler middleware that calculates the cost of WebAssembly instructions at compile
 /// time and will count the cost of executed instructions at runtime. Within the Metering functionality,
 /// this instruction cost is called `points`.
@@ -65,14 +63,14 @@ impl FunctionMiddleware for Metering {
                     | Operator::CallIndirect { .. }
                     | Operator::Return => {
                         sink.push(Event::Internal(InternalEvent::GetInternal(
-                            INTERNAL_FIELD.index() as _,
+                            METERING_POINTS_USED.index() as _,
                         )));
                         sink.push(Event::WasmOwned(Operator::I64Const {
                             value: self.current_block as i64,
                         }));
                         sink.push(Event::WasmOwned(Operator::I64Add));
                         sink.push(Event::Internal(InternalEvent::SetInternal(
-                            INTERNAL_FIELD.index() as _,
+                            METERING_POINTS_USED.index() as _,
                         )));
                         self.current_block = 0;
                     }
@@ -85,7 +83,7 @@ impl FunctionMiddleware for Metering {
                     | Operator::Call { .. }
                     | Operator::CallIndirect { .. } => {
                         sink.push(Event::Internal(InternalEvent::GetInternal(
-                            INTERNAL_FIELD.index() as _,
+                            METERING_POINTS_USED.index() as _,
                         )));
                         sink.push(Event::WasmOwned(Operator::I64Const {
                             value: self.limit as i64,
@@ -111,20 +109,20 @@ impl FunctionMiddleware for Metering {
 
 /// Returns the number of points used by an Instance.
 pub fn get_points_used(instance: &Instance) -> u64 {
-    instance.get_internal(&INTERNAL_FIELD)
+    instance.get_internal(&METERING_POINTS_USED)
 }
 
 /// Sets the number of points used by an Instance.
 pub fn set_points_used(instance: &mut Instance, value: u64) {
-    instance.set_internal(&INTERNAL_FIELD, value);
+    instance.set_internal(&METERING_POINTS_USED, value);
 }
 
 /// Returns the number of points used in a Ctx.
 pub fn get_points_used_ctx(ctx: &Ctx) -> u64 {
-    ctx.get_internal(&INTERNAL_FIELD)
+    ctx.get_internal(&METERING_POINTS_USED)
 }
 
 /// Sets the number of points used in a Ctx.
 pub fn set_points_used_ctx(ctx: &mut Ctx, value: u64) {
-    ctx.set_internal(&INTERNAL_FIELD, value);
+    ctx.set_internal(&METERING_POINTS_USED, value);
 }
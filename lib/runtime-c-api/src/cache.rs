@@ -0,0 +1,176 @@
+//! Load compiled modules from, and store them in, a directory on disk.
+
+use crate::{
+    error::{catch_panic, update_last_error, CApiError},
+    module::wasmer_module_t,
+    wasmer_result_t,
+};
+use libc::c_char;
+use std::{ffi::CStr, ptr, slice};
+use wasmer_runtime::{
+    cache::{Cache, FileSystemCache, WasmHash},
+    compile, Module,
+};
+
+/// Opaque pointer to a `FileSystemCache`.
+#[repr(C)]
+pub struct wasmer_cache_t;
+
+/// Creates a cache storing compiled modules in the directory at `dir_path`, which is
+/// created if it doesn't exist yet.
+///
+/// The artifacts in the directory are loaded without being checked, so it must not be
+/// writable by anyone untrusted.
+///
+/// The caller owns the object and should call `wasmer_cache_destroy` to free it.
+///
+/// Returns `NULL` upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_cache_new(dir_path: *const c_char) -> *mut wasmer_cache_t {
+    catch_panic(ptr::null_mut(), || {
+        if dir_path.is_null() {
+            update_last_error(CApiError {
+                msg: "dir_path ptr is null".to_string(),
+            });
+            return ptr::null_mut();
+        }
+
+        let dir_path = match CStr::from_ptr(dir_path).to_str() {
+            Ok(dir_path) => dir_path,
+            Err(_) => {
+                update_last_error(CApiError {
+                    msg: "dir_path is not valid UTF-8".to_string(),
+                });
+                return ptr::null_mut();
+            }
+        };
+
+        match FileSystemCache::new(dir_path) {
+            Ok(cache) => Box::into_raw(Box::new(cache)) as *mut wasmer_cache_t,
+            Err(error) => {
+                update_last_error(error);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Loads the module compiled from the given wasm bytes from the cache, or compiles
+/// them and stores the module in the cache if it isn't there yet.
+///
+/// Modules are keyed by a hash of their wasm bytes. The module is still returned if it
+/// can't be stored.
+///
+/// The caller owns the module and should call `wasmer_module_destroy` to free it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_cache_load_or_compile(
+    cache: *mut wasmer_cache_t,
+    wasm_bytes: *const u8,
+    wasm_bytes_len: u32,
+    module: *mut *mut wasmer_module_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if cache.is_null() {
+            update_last_error(CApiError {
+                msg: "cache ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if wasm_bytes.is_null() {
+            update_last_error(CApiError {
+                msg: "wasm_bytes ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let cache = &mut *(cache as *mut FileSystemCache);
+        let bytes: &[u8] = slice::from_raw_parts(wasm_bytes, wasm_bytes_len as usize);
+        let key = WasmHash::generate(bytes);
+
+        let new_module = match cache.load(key) {
+            Ok(cached_module) => cached_module,
+            Err(_) => match compile(bytes) {
+                Ok(compiled_module) => {
+                    let _ = cache.store(key, compiled_module.clone());
+                    compiled_module
+                }
+                Err(error) => {
+                    update_last_error(error);
+                    return wasmer_result_t::WASMER_ERROR;
+                }
+            },
+        };
+
+        *module = Box::into_raw(Box::new(new_module)) as *mut wasmer_module_t;
+        wasmer_result_t::WASMER_OK
+    })
+}
+
+/// Stores the given module in the cache under `key`, usually the wasm bytes it was
+/// compiled from, so that `wasmer_cache_load_or_compile` finds it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_cache_store(
+    cache: *mut wasmer_cache_t,
+    key: *const u8,
+    key_len: u32,
+    module: *const wasmer_module_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if cache.is_null() {
+            update_last_error(CApiError {
+                msg: "cache ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if key.is_null() {
+            update_last_error(CApiError {
+                msg: "key ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if module.is_null() {
+            update_last_error(CApiError {
+                msg: "module ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let cache = &mut *(cache as *mut FileSystemCache);
+        let key = WasmHash::generate(slice::from_raw_parts(key, key_len as usize));
+        let module = &*(module as *const Module);
+
+        match cache.store(key, module.clone()) {
+            Ok(()) => wasmer_result_t::WASMER_OK,
+            Err(error) => {
+                update_last_error(CApiError {
+                    msg: format!("Failed to store the module: {:?}", error),
+                });
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
+}
+
+/// Frees memory for the given cache. The modules stored in it stay on disk.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_cache_destroy(cache: *mut wasmer_cache_t) {
+    catch_panic((), || {
+        if !cache.is_null() {
+            unsafe { Box::from_raw(cache as *mut FileSystemCache) };
+        }
+    })
+}
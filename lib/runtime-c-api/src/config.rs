@@ -0,0 +1,270 @@
+//! Configure how modules are compiled, and compile them.
+
+use crate::{
+    error::{catch_panic, update_last_error, CApiError},
+    module::{wasmer_features_t, wasmer_module_t},
+    wasmer_result_t,
+};
+use std::{ptr, slice};
+use wasmer_runtime::{
+    compile_with_config_with, compiler_for_backend, metered_compiler_for_backend, select_backend,
+    Backend, BackendHints, CompilerConfig, Features, MemoryStyle,
+};
+
+/// The backend compiling modules, see `wasmer_config_set_backend`.
+#[allow(non_camel_case_types)]
+#[repr(u32)]
+#[derive(Clone, Copy)]
+pub enum wasmer_backend_t {
+    /// Picks a backend for each module, from its size and whether it's metered.
+    WASMER_BACKEND_AUTO,
+    WASMER_BACKEND_CRANELIFT,
+    WASMER_BACKEND_SINGLEPASS,
+    WASMER_BACKEND_LLVM,
+    WASMER_BACKEND_INTERPRETER,
+}
+
+impl From<wasmer_backend_t> for Backend {
+    fn from(backend: wasmer_backend_t) -> Self {
+        match backend {
+            wasmer_backend_t::WASMER_BACKEND_AUTO => Backend::Auto,
+            wasmer_backend_t::WASMER_BACKEND_CRANELIFT => Backend::Cranelift,
+            wasmer_backend_t::WASMER_BACKEND_SINGLEPASS => Backend::Singlepass,
+            wasmer_backend_t::WASMER_BACKEND_LLVM => Backend::LLVM,
+            wasmer_backend_t::WASMER_BACKEND_INTERPRETER => Backend::Interpreter,
+        }
+    }
+}
+
+/// The style of the memories of compiled modules, see `wasmer_config_set_memory_style`.
+#[allow(non_camel_case_types)]
+#[repr(u32)]
+#[derive(Clone, Copy)]
+pub enum wasmer_memory_style_t {
+    /// Memories with a maximum size are static, and the others dynamic.
+    WASMER_MEMORY_STYLE_DEFAULT,
+    /// Every memory reserves 6GiB of virtual memory, so accesses aren't bounds-checked.
+    WASMER_MEMORY_STYLE_STATIC,
+    /// Every memory only maps its current size, and accesses are bounds-checked.
+    WASMER_MEMORY_STYLE_DYNAMIC,
+}
+
+impl From<wasmer_memory_style_t> for MemoryStyle {
+    fn from(style: wasmer_memory_style_t) -> Self {
+        match style {
+            wasmer_memory_style_t::WASMER_MEMORY_STYLE_DEFAULT => MemoryStyle::Default,
+            wasmer_memory_style_t::WASMER_MEMORY_STYLE_STATIC => MemoryStyle::Static,
+            wasmer_memory_style_t::WASMER_MEMORY_STYLE_DYNAMIC => MemoryStyle::Dynamic,
+        }
+    }
+}
+
+/// How `wasmer_compile_with_config` compiles modules.
+pub(crate) struct CompileConfig {
+    backend: Backend,
+    features: Features,
+    memory_style: MemoryStyle,
+    metering_limit: Option<u64>,
+}
+
+/// Opaque pointer to a `CompileConfig`.
+#[repr(C)]
+pub struct wasmer_config_t;
+
+/// Creates a new compile configuration, which compiles modules like `wasmer_compile`
+/// until it's changed with the `wasmer_config_set_*` functions.
+///
+/// The caller owns the object and should call `wasmer_config_destroy` to free it.
+#[no_mangle]
+pub extern "C" fn wasmer_config_new() -> *mut wasmer_config_t {
+    catch_panic(ptr::null_mut(), || {
+        let config = CompileConfig {
+            backend: Backend::default(),
+            features: Features::default(),
+            memory_style: MemoryStyle::Default,
+            metering_limit: None,
+        };
+        Box::into_raw(Box::new(config)) as *mut wasmer_config_t
+    })
+}
+
+/// Sets the backend compiling modules. Whether it's enabled in this library is only
+/// checked when compiling.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_config_set_backend(
+    config: *mut wasmer_config_t,
+    backend: wasmer_backend_t,
+) {
+    catch_panic((), || {
+        if let Some(config) = (config as *mut CompileConfig).as_mut() {
+            config.backend = backend.into();
+        }
+    })
+}
+
+/// Sets the WebAssembly features modules are compiled with, which are copied.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_config_set_features(
+    config: *mut wasmer_config_t,
+    features: *const wasmer_features_t,
+) {
+    catch_panic((), || {
+        if let (Some(config), Some(features)) = (
+            (config as *mut CompileConfig).as_mut(),
+            (features as *const Features).as_ref(),
+        ) {
+            let deterministic = config.features.deterministic;
+            config.features = Features {
+                deterministic,
+                ..features.clone()
+            };
+        }
+    })
+}
+
+/// Meters the compiled modules, so that their instances trap once they've used `limit`
+/// points, as reported by `wasmer_instance_stats`. A `limit` of 0 disables metering.
+///
+/// The Cranelift backend can't meter modules.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_config_set_metering_limit(
+    config: *mut wasmer_config_t,
+    limit: u64,
+) {
+    catch_panic((), || {
+        if let Some(config) = (config as *mut CompileConfig).as_mut() {
+            config.metering_limit = Some(limit).filter(|&limit| limit != 0);
+        }
+    })
+}
+
+/// Enables or disables NaN canonicalization: every NaN produced by a floating point
+/// instruction is replaced by the canonical NaN, so the results don't depend on the
+/// hardware.
+///
+/// This is the deterministic execution profile, which also rejects modules using
+/// threads, and SIMD unless compiled by the LLVM backend.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_config_set_nan_canonicalization(
+    config: *mut wasmer_config_t,
+    enabled: bool,
+) {
+    catch_panic((), || {
+        if let Some(config) = (config as *mut CompileConfig).as_mut() {
+            config.features.deterministic = enabled;
+        }
+    })
+}
+
+/// Sets the style of the memories of the compiled modules.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_config_set_memory_style(
+    config: *mut wasmer_config_t,
+    memory_style: wasmer_memory_style_t,
+) {
+    catch_panic((), || {
+        if let Some(config) = (config as *mut CompileConfig).as_mut() {
+            config.memory_style = memory_style.into();
+        }
+    })
+}
+
+/// Frees memory for the given compile configuration.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_config_destroy(config: *mut wasmer_config_t) {
+    catch_panic((), || {
+        if !config.is_null() {
+            unsafe { Box::from_raw(config as *mut CompileConfig) };
+        }
+    })
+}
+
+/// Creates a new Module from the given wasm bytes, compiled as `config` says.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_compile_with_config(
+    module: *mut *mut wasmer_module_t,
+    wasm_bytes: *mut u8,
+    wasm_bytes_len: u32,
+    config: *const wasmer_config_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let config = match (config as *const CompileConfig).as_ref() {
+            Some(config) => config,
+            None => {
+                update_last_error(CApiError {
+                    msg: "config is null".to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        if wasm_bytes.is_null() {
+            update_last_error(CApiError {
+                msg: "wasm_bytes ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        let bytes: &[u8] = slice::from_raw_parts(wasm_bytes, wasm_bytes_len as usize);
+
+        let mut compiler_config = CompilerConfig {
+            features: config.features.clone(),
+            memory_style: config.memory_style,
+            ..Default::default()
+        };
+        let backend = if config.backend == Backend::Auto {
+            let hints = BackendHints {
+                metered: config.metering_limit.is_some(),
+                optimize: false,
+            };
+            let (backend, selection) = select_backend(bytes.len(), hints);
+            compiler_config.auto_selection = Some(selection);
+            backend
+        } else {
+            config.backend
+        };
+
+        let compiler = match config.metering_limit {
+            Some(limit) => metered_compiler_for_backend(backend, limit),
+            None => compiler_for_backend(backend),
+        };
+        let compiler = match compiler {
+            Some(compiler) => compiler,
+            None => {
+                update_last_error(CApiError {
+                    msg: format!(
+                        "the {} backend is not enabled{}",
+                        backend.to_string(),
+                        if config.metering_limit.is_some() {
+                            ", or can't meter modules"
+                        } else {
+                            ""
+                        }
+                    ),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+
+        match compile_with_config_with(bytes, compiler_config, compiler.as_ref()) {
+            Ok(new_module) => {
+                *module = Box::into_raw(Box::new(new_module)) as *mut wasmer_module_t;
+                wasmer_result_t::WASMER_OK
+            }
+            Err(error) => {
+                update_last_error(error);
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
+}
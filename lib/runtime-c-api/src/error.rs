@@ -1,27 +1,175 @@
 //! Read runtime errors.
 
-use libc::{c_char, c_int};
+use lazy_static::lazy_static;
+use libc::{c_char, c_int, c_void};
+use std::any::Any;
 use std::cell::RefCell;
 use std::error::Error;
+use std::ffi::CString;
 use std::fmt::{self, Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
 use std::slice;
+use std::sync::RwLock;
+use wasmer_runtime::error::{
+    CallError, CompileError, CreationError, Error as WasmerError, LinkError, ResolveError,
+    RuntimeError,
+};
+
+/// The kind of an error, passed to the callback set with `wasmer_set_error_callback`.
+#[allow(non_camel_case_types)]
+#[repr(u32)]
+#[derive(Clone, Copy)]
+pub enum wasmer_error_code_t {
+    /// Any error not listed below, e.g. a null pointer argument.
+    WASMER_ERROR_CODE_OTHER,
+    /// The module failed to compile or validate.
+    WASMER_ERROR_CODE_COMPILE,
+    /// An import of the module is missing, or has the wrong type.
+    WASMER_ERROR_CODE_LINK,
+    /// The instance trapped, or a host function failed.
+    WASMER_ERROR_CODE_RUNTIME,
+    /// The called function doesn't exist, or got arguments of the wrong type.
+    WASMER_ERROR_CODE_RESOLVE,
+    /// A memory, table or global couldn't be created.
+    WASMER_ERROR_CODE_CREATION,
+    /// The library panicked, which is a bug.
+    WASMER_ERROR_CODE_PANIC,
+}
+
+/// The callback set with `wasmer_set_error_callback`, and its user data.
+#[derive(Clone, Copy)]
+struct ErrorCallback {
+    callback:
+        extern "C" fn(code: wasmer_error_code_t, message: *const c_char, user_data: *mut c_void),
+    user_data: *mut c_void,
+}
+
+// The user data is only passed back to the callback, which must handle being
+// called from any thread.
+unsafe impl Send for ErrorCallback {}
+unsafe impl Sync for ErrorCallback {}
 
 thread_local! {
     static LAST_ERROR: RefCell<Option<Box<dyn Error>>> = RefCell::new(None);
 }
 
+lazy_static! {
+    static ref ERROR_CALLBACK: RwLock<Option<ErrorCallback>> = RwLock::new(None);
+}
+
 pub fn update_last_error<E: Error + 'static>(err: E) {
+    let code = error_code(&err);
+    set_last_error(code, Box::new(err));
+}
+
+/// Stores `err` as the last error of this thread, and passes it to the error
+/// callback if one is set.
+fn set_last_error(code: wasmer_error_code_t, err: Box<dyn Error>) {
+    // The lock is released before calling the callback, which may set another one.
+    let error_callback = *ERROR_CALLBACK.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(ErrorCallback {
+        callback,
+        user_data,
+    }) = error_callback
+    {
+        let message = CString::new(err.to_string()).unwrap_or_default();
+        callback(code, message.as_ptr(), user_data);
+    }
+
     LAST_ERROR.with(|prev| {
-        *prev.borrow_mut() = Some(Box::new(err));
+        *prev.borrow_mut() = Some(err);
     });
 }
 
+/// The code of the errors returned by wasmer, see `wasmer_error_code_t`.
+fn error_code(err: &(dyn Error + 'static)) -> wasmer_error_code_t {
+    if err.is::<CompileError>() {
+        wasmer_error_code_t::WASMER_ERROR_CODE_COMPILE
+    } else if err.is::<LinkError>() {
+        wasmer_error_code_t::WASMER_ERROR_CODE_LINK
+    } else if err.is::<RuntimeError>() {
+        wasmer_error_code_t::WASMER_ERROR_CODE_RUNTIME
+    } else if err.is::<ResolveError>() {
+        wasmer_error_code_t::WASMER_ERROR_CODE_RESOLVE
+    } else if err.is::<CreationError>() {
+        wasmer_error_code_t::WASMER_ERROR_CODE_CREATION
+    } else if let Some(CallError::Resolve(_)) = err.downcast_ref::<CallError>() {
+        wasmer_error_code_t::WASMER_ERROR_CODE_RESOLVE
+    } else if let Some(CallError::Runtime(_)) = err.downcast_ref::<CallError>() {
+        wasmer_error_code_t::WASMER_ERROR_CODE_RUNTIME
+    } else if let Some(err) = err.downcast_ref::<WasmerError>() {
+        match err {
+            WasmerError::CompileError(_) => wasmer_error_code_t::WASMER_ERROR_CODE_COMPILE,
+            WasmerError::LinkError(_) => wasmer_error_code_t::WASMER_ERROR_CODE_LINK,
+            WasmerError::RuntimeError(_) | WasmerError::CallError(CallError::Runtime(_)) => {
+                wasmer_error_code_t::WASMER_ERROR_CODE_RUNTIME
+            }
+            WasmerError::ResolveError(_) | WasmerError::CallError(CallError::Resolve(_)) => {
+                wasmer_error_code_t::WASMER_ERROR_CODE_RESOLVE
+            }
+            WasmerError::CreationError(_) => wasmer_error_code_t::WASMER_ERROR_CODE_CREATION,
+        }
+    } else {
+        wasmer_error_code_t::WASMER_ERROR_CODE_OTHER
+    }
+}
+
 /// Retrieve the most recent error, clearing it in the process.
 pub(crate) fn take_last_error() -> Option<Box<dyn Error>> {
     LAST_ERROR.with(|prev| prev.borrow_mut().take())
 }
 
+/// Runs the body of an entry point, or of a callback the runtime calls while
+/// running the guest, returning `on_panic` if it panics instead of unwinding
+/// into the C caller or through the guest, which is undefined behavior. The
+/// panic message becomes the last error.
+pub(crate) fn catch_panic<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let err = CApiError {
+                msg: format!("wasmer panicked: {}", panic_message(&*payload)),
+            };
+            set_last_error(wasmer_error_code_t::WASMER_ERROR_CODE_PANIC, Box::new(err));
+            on_panic
+        }
+    }
+}
+
+/// The message a panic was started with, if it's a string.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        *message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "no message"
+    }
+}
+
+/// Sets a callback called with the code and message of every error, when it becomes
+/// the last error of the thread it happened in. The message is only valid during the
+/// call, and the errors can still be read with `wasmer_last_error_message`.
+///
+/// The callback is global, so it may be called from any thread, and replaces the one
+/// set before. A `NULL` callback removes it.
+#[no_mangle]
+pub extern "C" fn wasmer_set_error_callback(
+    callback: Option<
+        extern "C" fn(code: wasmer_error_code_t, message: *const c_char, user_data: *mut c_void),
+    >,
+    user_data: *mut c_void,
+) {
+    catch_panic((), || {
+        let error_callback = callback.map(|callback| ErrorCallback {
+            callback,
+            user_data,
+        });
+        *ERROR_CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = error_callback;
+    })
+}
+
 /// Gets the length in bytes of the last error.
 /// This can be used to dynamically allocate a buffer with the correct number of
 /// bytes needed to store a message.
@@ -34,9 +182,11 @@ pub(crate) fn take_last_error() -> Option<Box<dyn Error>> {
 /// ```
 #[no_mangle]
 pub extern "C" fn wasmer_last_error_length() -> c_int {
-    LAST_ERROR.with(|prev| match *prev.borrow() {
-        Some(ref err) => err.to_string().len() as c_int + 1,
-        None => 0,
+    catch_panic(0, || {
+        LAST_ERROR.with(|prev| match *prev.borrow() {
+            Some(ref err) => err.to_string().len() as c_int + 1,
+            None => 0,
+        })
     })
 }
 
@@ -56,36 +206,38 @@ pub extern "C" fn wasmer_last_error_length() -> c_int {
 /// ```
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_last_error_message(buffer: *mut c_char, length: c_int) -> c_int {
-    if buffer.is_null() {
-        // buffer pointer is null
-        return -1;
-    }
+    catch_panic(-1, || {
+        if buffer.is_null() {
+            // buffer pointer is null
+            return -1;
+        }
 
-    let error_message = match take_last_error() {
-        Some(err) => err.to_string(),
-        None => return 0,
-    };
+        let error_message = match take_last_error() {
+            Some(err) => err.to_string(),
+            None => return 0,
+        };
 
-    let length = length as usize;
+        let length = length as usize;
 
-    if error_message.len() >= length {
-        // buffer is too small to hold the error message
-        return -1;
-    }
+        if error_message.len() >= length {
+            // buffer is too small to hold the error message
+            return -1;
+        }
 
-    let buffer = slice::from_raw_parts_mut(buffer as *mut u8, length);
+        let buffer = slice::from_raw_parts_mut(buffer as *mut u8, length);
 
-    ptr::copy_nonoverlapping(
-        error_message.as_ptr(),
-        buffer.as_mut_ptr(),
-        error_message.len(),
-    );
+        ptr::copy_nonoverlapping(
+            error_message.as_ptr(),
+            buffer.as_mut_ptr(),
+            error_message.len(),
+        );
 
-    // Add a trailing null so people using the string as a `char *` don't
-    // accidentally read into garbage.
-    buffer[error_message.len()] = 0;
+        // Add a trailing null so people using the string as a `char *` don't
+        // accidentally read into garbage.
+        buffer[error_message.len()] = 0;
 
-    error_message.len() as c_int + 1
+        error_message.len() as c_int + 1
+    })
 }
 
 #[derive(Debug)]
@@ -100,3 +252,27 @@ impl Display for CApiError {
 }
 
 impl Error for CApiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{catch_panic, take_last_error};
+
+    #[test]
+    fn test_panic_in_callback_becomes_error() {
+        let callback = |len: u32| -> Result<u32, ()> {
+            if len > 1 {
+                panic!("cannot grow to {} pages", len);
+            }
+            Ok(len)
+        };
+
+        assert_eq!(catch_panic(Err(()), || callback(1)), Ok(1));
+        assert!(take_last_error().is_none());
+
+        assert_eq!(catch_panic(Err(()), || callback(2)), Err(()));
+        assert_eq!(
+            take_last_error().map(|err| err.to_string()),
+            Some("wasmer panicked: cannot grow to 2 pages".to_string())
+        );
+    }
+}
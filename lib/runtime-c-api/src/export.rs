@@ -2,19 +2,19 @@
 //! and table) on an instance.
 
 use crate::{
-    error::{update_last_error, CApiError},
+    error::{catch_panic, update_last_error, CApiError},
     global::wasmer_global_t,
     import::wasmer_import_func_t,
     memory::wasmer_memory_t,
     module::wasmer_module_t,
     table::wasmer_table_t,
-    value::{wasmer_value, wasmer_value_t, wasmer_value_tag},
+    value::{wasmer_value_t, wasmer_value_tag},
     wasmer_byte_array, wasmer_result_t,
 };
 use libc::{c_int, c_uint};
 use std::{ptr, slice};
-use wasmer_runtime::{Instance, Module, Value};
-use wasmer_runtime_core::{export::Export, module::ExportIndex};
+use wasmer_runtime::{Instance, Module, ResolvedFunc, Value};
+use wasmer_runtime_core::{export::Export, module::ExportIndex, types::Type};
 
 /// Intermediate representation of an `Export` instance that is
 /// exposed to C.
@@ -39,6 +39,23 @@ pub struct wasmer_export_t;
 #[derive(Clone)]
 pub struct wasmer_export_func_t;
 
+/// An exported function resolved once by `wasmer_instance_get_export_func`, with
+/// buffers for the parameters and results that are reused across calls.
+pub(crate) struct ExportFuncHandle {
+    /// The resolved function, which borrows the instance.
+    pub(crate) func: ResolvedFunc<'static>,
+
+    /// The parameters of the current call.
+    pub(crate) params: Vec<Value>,
+
+    /// The results of the last call.
+    pub(crate) results: Vec<Value>,
+}
+
+/// Opaque pointer to `ExportFuncHandle`.
+#[repr(C)]
+pub struct wasmer_export_func_handle_t;
+
 /// Intermediate representation of a vector of `NamedExport` that is
 /// exposed to C.
 pub(crate) struct NamedExports(pub Vec<NamedExport>);
@@ -131,13 +148,15 @@ pub unsafe extern "C" fn wasmer_export_descriptors(
     module: *const wasmer_module_t,
     export_descriptors: *mut *mut wasmer_export_descriptors_t,
 ) {
-    let module = &*(module as *const Module);
-
-    let named_export_descriptors: Box<NamedExportDescriptors> = Box::new(NamedExportDescriptors(
-        module.info().exports.iter().map(|e| e.into()).collect(),
-    ));
-    *export_descriptors =
-        Box::into_raw(named_export_descriptors) as *mut wasmer_export_descriptors_t;
+    catch_panic((), || {
+        let module = &*(module as *const Module);
+
+        let named_export_descriptors: Box<NamedExportDescriptors> = Box::new(
+            NamedExportDescriptors(module.info().exports.iter().map(|e| e.into()).collect()),
+        );
+        *export_descriptors =
+            Box::into_raw(named_export_descriptors) as *mut wasmer_export_descriptors_t;
+    })
 }
 
 /// Frees the memory for the given export descriptors
@@ -146,9 +165,11 @@ pub unsafe extern "C" fn wasmer_export_descriptors(
 pub extern "C" fn wasmer_export_descriptors_destroy(
     export_descriptors: *mut wasmer_export_descriptors_t,
 ) {
-    if !export_descriptors.is_null() {
-        unsafe { Box::from_raw(export_descriptors as *mut NamedExportDescriptors) };
-    }
+    catch_panic((), || {
+        if !export_descriptors.is_null() {
+            unsafe { Box::from_raw(export_descriptors as *mut NamedExportDescriptors) };
+        }
+    })
 }
 
 /// Gets the length of the export descriptors
@@ -157,10 +178,12 @@ pub extern "C" fn wasmer_export_descriptors_destroy(
 pub unsafe extern "C" fn wasmer_export_descriptors_len(
     exports: *mut wasmer_export_descriptors_t,
 ) -> c_int {
-    if exports.is_null() {
-        return 0;
-    }
-    (*(exports as *mut NamedExportDescriptors)).0.len() as c_int
+    catch_panic(0, || {
+        if exports.is_null() {
+            return 0;
+        }
+        (*(exports as *mut NamedExportDescriptors)).0.len() as c_int
+    })
 }
 
 /// Gets export descriptor by index
@@ -170,12 +193,14 @@ pub unsafe extern "C" fn wasmer_export_descriptors_get(
     export_descriptors: *mut wasmer_export_descriptors_t,
     idx: c_int,
 ) -> *mut wasmer_export_descriptor_t {
-    if export_descriptors.is_null() {
-        return ptr::null_mut();
-    }
-    let named_export_descriptors = &mut *(export_descriptors as *mut NamedExportDescriptors);
-    &mut (*named_export_descriptors).0[idx as usize] as *mut NamedExportDescriptor
-        as *mut wasmer_export_descriptor_t
+    catch_panic(ptr::null_mut(), || {
+        if export_descriptors.is_null() {
+            return ptr::null_mut();
+        }
+        let named_export_descriptors = &mut *(export_descriptors as *mut NamedExportDescriptors);
+        &mut (*named_export_descriptors).0[idx as usize] as *mut NamedExportDescriptor
+            as *mut wasmer_export_descriptor_t
+    })
 }
 
 /// Gets name for the export descriptor
@@ -184,11 +209,13 @@ pub unsafe extern "C" fn wasmer_export_descriptors_get(
 pub unsafe extern "C" fn wasmer_export_descriptor_name(
     export_descriptor: *mut wasmer_export_descriptor_t,
 ) -> wasmer_byte_array {
-    let named_export_descriptor = &*(export_descriptor as *mut NamedExportDescriptor);
-    wasmer_byte_array {
-        bytes: named_export_descriptor.name.as_ptr(),
-        bytes_len: named_export_descriptor.name.len() as u32,
-    }
+    catch_panic(wasmer_byte_array::empty(), || {
+        let named_export_descriptor = &*(export_descriptor as *mut NamedExportDescriptor);
+        wasmer_byte_array {
+            bytes: named_export_descriptor.name.as_ptr(),
+            bytes_len: named_export_descriptor.name.len() as u32,
+        }
+    })
 }
 
 /// Gets export descriptor kind
@@ -197,27 +224,33 @@ pub unsafe extern "C" fn wasmer_export_descriptor_name(
 pub unsafe extern "C" fn wasmer_export_descriptor_kind(
     export: *mut wasmer_export_descriptor_t,
 ) -> wasmer_import_export_kind {
-    let named_export_descriptor = &*(export as *mut NamedExportDescriptor);
-    named_export_descriptor.kind.clone()
+    catch_panic(wasmer_import_export_kind::WASM_FUNCTION, || {
+        let named_export_descriptor = &*(export as *mut NamedExportDescriptor);
+        named_export_descriptor.kind.clone()
+    })
 }
 
 /// Frees the memory for the given exports
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_exports_destroy(exports: *mut wasmer_exports_t) {
-    if !exports.is_null() {
-        unsafe { Box::from_raw(exports as *mut NamedExports) };
-    }
+    catch_panic((), || {
+        if !exports.is_null() {
+            unsafe { Box::from_raw(exports as *mut NamedExports) };
+        }
+    })
 }
 
 /// Gets the length of the exports
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_exports_len(exports: *mut wasmer_exports_t) -> c_int {
-    if exports.is_null() {
-        return 0;
-    }
-    (*(exports as *mut NamedExports)).0.len() as c_int
+    catch_panic(0, || {
+        if exports.is_null() {
+            return 0;
+        }
+        (*(exports as *mut NamedExports)).0.len() as c_int
+    })
 }
 
 /// Gets wasmer_export by index
@@ -227,11 +260,13 @@ pub unsafe extern "C" fn wasmer_exports_get(
     exports: *mut wasmer_exports_t,
     idx: c_int,
 ) -> *mut wasmer_export_t {
-    if exports.is_null() {
-        return ptr::null_mut();
-    }
-    let named_exports = &mut *(exports as *mut NamedExports);
-    &mut (*named_exports).0[idx as usize] as *mut NamedExport as *mut wasmer_export_t
+    catch_panic(ptr::null_mut(), || {
+        if exports.is_null() {
+            return ptr::null_mut();
+        }
+        let named_exports = &mut *(exports as *mut NamedExports);
+        &mut (*named_exports).0[idx as usize] as *mut NamedExport as *mut wasmer_export_t
+    })
 }
 
 /// Gets wasmer_export kind
@@ -240,13 +275,15 @@ pub unsafe extern "C" fn wasmer_exports_get(
 pub unsafe extern "C" fn wasmer_export_kind(
     export: *mut wasmer_export_t,
 ) -> wasmer_import_export_kind {
-    let named_export = &*(export as *mut NamedExport);
-    match named_export.export {
-        Export::Table(_) => wasmer_import_export_kind::WASM_TABLE,
-        Export::Function { .. } => wasmer_import_export_kind::WASM_FUNCTION,
-        Export::Global(_) => wasmer_import_export_kind::WASM_GLOBAL,
-        Export::Memory(_) => wasmer_import_export_kind::WASM_MEMORY,
-    }
+    catch_panic(wasmer_import_export_kind::WASM_FUNCTION, || {
+        let named_export = &*(export as *mut NamedExport);
+        match named_export.export {
+            Export::Table(_) => wasmer_import_export_kind::WASM_TABLE,
+            Export::Function { .. } => wasmer_import_export_kind::WASM_FUNCTION,
+            Export::Global(_) => wasmer_import_export_kind::WASM_GLOBAL,
+            Export::Memory(_) => wasmer_import_export_kind::WASM_MEMORY,
+        }
+    })
 }
 
 /// Sets the result parameter to the arity of the params of the wasmer_export_func_t
@@ -261,17 +298,19 @@ pub unsafe extern "C" fn wasmer_export_func_params_arity(
     func: *const wasmer_export_func_t,
     result: *mut u32,
 ) -> wasmer_result_t {
-    let named_export = &*(func as *const NamedExport);
-    let export = &named_export.export;
-    if let Export::Function { ref signature, .. } = *export {
-        *result = signature.params().len() as u32;
-        wasmer_result_t::WASMER_OK
-    } else {
-        update_last_error(CApiError {
-            msg: "func ptr error in wasmer_export_func_params_arity".to_string(),
-        });
-        wasmer_result_t::WASMER_ERROR
-    }
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let named_export = &*(func as *const NamedExport);
+        let export = &named_export.export;
+        if let Export::Function { ref signature, .. } = *export {
+            *result = signature.params().len() as u32;
+            wasmer_result_t::WASMER_OK
+        } else {
+            update_last_error(CApiError {
+                msg: "func ptr error in wasmer_export_func_params_arity".to_string(),
+            });
+            wasmer_result_t::WASMER_ERROR
+        }
+    })
 }
 
 /// Sets the params buffer to the parameter types of the given wasmer_export_func_t
@@ -287,21 +326,23 @@ pub unsafe extern "C" fn wasmer_export_func_params(
     params: *mut wasmer_value_tag,
     params_len: u32,
 ) -> wasmer_result_t {
-    let named_export = &*(func as *const NamedExport);
-    let export = &named_export.export;
-    if let Export::Function { ref signature, .. } = *export {
-        let params: &mut [wasmer_value_tag] =
-            slice::from_raw_parts_mut(params, params_len as usize);
-        for (i, item) in signature.params().iter().enumerate() {
-            params[i] = item.into();
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let named_export = &*(func as *const NamedExport);
+        let export = &named_export.export;
+        if let Export::Function { ref signature, .. } = *export {
+            let params: &mut [wasmer_value_tag] =
+                slice::from_raw_parts_mut(params, params_len as usize);
+            for (i, item) in signature.params().iter().enumerate() {
+                params[i] = item.into();
+            }
+            wasmer_result_t::WASMER_OK
+        } else {
+            update_last_error(CApiError {
+                msg: "func ptr error in wasmer_export_func_params".to_string(),
+            });
+            wasmer_result_t::WASMER_ERROR
         }
-        wasmer_result_t::WASMER_OK
-    } else {
-        update_last_error(CApiError {
-            msg: "func ptr error in wasmer_export_func_params".to_string(),
-        });
-        wasmer_result_t::WASMER_ERROR
-    }
+    })
 }
 
 /// Sets the returns buffer to the parameter types of the given wasmer_export_func_t
@@ -317,21 +358,23 @@ pub unsafe extern "C" fn wasmer_export_func_returns(
     returns: *mut wasmer_value_tag,
     returns_len: u32,
 ) -> wasmer_result_t {
-    let named_export = &*(func as *const NamedExport);
-    let export = &named_export.export;
-    if let Export::Function { ref signature, .. } = *export {
-        let returns: &mut [wasmer_value_tag] =
-            slice::from_raw_parts_mut(returns, returns_len as usize);
-        for (i, item) in signature.returns().iter().enumerate() {
-            returns[i] = item.into();
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let named_export = &*(func as *const NamedExport);
+        let export = &named_export.export;
+        if let Export::Function { ref signature, .. } = *export {
+            let returns: &mut [wasmer_value_tag] =
+                slice::from_raw_parts_mut(returns, returns_len as usize);
+            for (i, item) in signature.returns().iter().enumerate() {
+                returns[i] = item.into();
+            }
+            wasmer_result_t::WASMER_OK
+        } else {
+            update_last_error(CApiError {
+                msg: "func ptr error in wasmer_export_func_returns".to_string(),
+            });
+            wasmer_result_t::WASMER_ERROR
         }
-        wasmer_result_t::WASMER_OK
-    } else {
-        update_last_error(CApiError {
-            msg: "func ptr error in wasmer_export_func_returns".to_string(),
-        });
-        wasmer_result_t::WASMER_ERROR
-    }
+    })
 }
 
 /// Sets the result parameter to the arity of the returns of the wasmer_export_func_t
@@ -346,17 +389,19 @@ pub unsafe extern "C" fn wasmer_export_func_returns_arity(
     func: *const wasmer_export_func_t,
     result: *mut u32,
 ) -> wasmer_result_t {
-    let named_export = &*(func as *const NamedExport);
-    let export = &named_export.export;
-    if let Export::Function { ref signature, .. } = *export {
-        *result = signature.returns().len() as u32;
-        wasmer_result_t::WASMER_OK
-    } else {
-        update_last_error(CApiError {
-            msg: "func ptr error in wasmer_export_func_results_arity".to_string(),
-        });
-        wasmer_result_t::WASMER_ERROR
-    }
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let named_export = &*(func as *const NamedExport);
+        let export = &named_export.export;
+        if let Export::Function { ref signature, .. } = *export {
+            *result = signature.returns().len() as u32;
+            wasmer_result_t::WASMER_OK
+        } else {
+            update_last_error(CApiError {
+                msg: "func ptr error in wasmer_export_func_results_arity".to_string(),
+            });
+            wasmer_result_t::WASMER_ERROR
+        }
+    })
 }
 
 /// Gets export func from export
@@ -365,7 +410,7 @@ pub unsafe extern "C" fn wasmer_export_func_returns_arity(
 pub unsafe extern "C" fn wasmer_export_to_func(
     export: *const wasmer_export_t,
 ) -> *const wasmer_export_func_t {
-    export as *const wasmer_export_func_t
+    catch_panic(ptr::null(), || export as *const wasmer_export_func_t)
 }
 
 /// Gets a memory pointer from an export pointer.
@@ -380,32 +425,36 @@ pub unsafe extern "C" fn wasmer_export_to_memory(
     export: *const wasmer_export_t,
     memory: *mut *mut wasmer_memory_t,
 ) -> wasmer_result_t {
-    let named_export = &*(export as *const NamedExport);
-    let export = &named_export.export;
-
-    if let Export::Memory(exported_memory) = export {
-        let mem = Box::new(exported_memory.clone());
-        *memory = Box::into_raw(mem) as *mut wasmer_memory_t;
-        wasmer_result_t::WASMER_OK
-    } else {
-        update_last_error(CApiError {
-            msg: "cannot cast the `wasmer_export_t` pointer to a  `wasmer_memory_t` \
-                  pointer because it does not represent a memory export."
-                .to_string(),
-        });
-        wasmer_result_t::WASMER_ERROR
-    }
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let named_export = &*(export as *const NamedExport);
+        let export = &named_export.export;
+
+        if let Export::Memory(exported_memory) = export {
+            let mem = Box::new(exported_memory.clone());
+            *memory = Box::into_raw(mem) as *mut wasmer_memory_t;
+            wasmer_result_t::WASMER_OK
+        } else {
+            update_last_error(CApiError {
+                msg: "cannot cast the `wasmer_export_t` pointer to a  `wasmer_memory_t` \
+                      pointer because it does not represent a memory export."
+                    .to_string(),
+            });
+            wasmer_result_t::WASMER_ERROR
+        }
+    })
 }
 
 /// Gets name from wasmer_export
 #[no_mangle]
 #[allow(clippy::cast_ptr_alignment)]
 pub unsafe extern "C" fn wasmer_export_name(export: *mut wasmer_export_t) -> wasmer_byte_array {
-    let named_export = &*(export as *mut NamedExport);
-    wasmer_byte_array {
-        bytes: named_export.name.as_ptr(),
-        bytes_len: named_export.name.len() as u32,
-    }
+    catch_panic(wasmer_byte_array::empty(), || {
+        let named_export = &*(export as *mut NamedExport);
+        wasmer_byte_array {
+            bytes: named_export.name.as_ptr(),
+            bytes_len: named_export.name.len() as u32,
+        }
+    })
 }
 
 /// Calls a `func` with the provided parameters.
@@ -424,69 +473,140 @@ pub unsafe extern "C" fn wasmer_export_func_call(
     results: *mut wasmer_value_t,
     results_len: c_uint,
 ) -> wasmer_result_t {
-    if func.is_null() {
-        update_last_error(CApiError {
-            msg: "func ptr is null".to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    }
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if func.is_null() {
+            update_last_error(CApiError {
+                msg: "func ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
 
-    if params_len > 0 && params.is_null() {
-        update_last_error(CApiError {
-            msg: "params ptr is null".to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    }
+        if params_len > 0 && params.is_null() {
+            update_last_error(CApiError {
+                msg: "params ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
 
-    let params: Vec<Value> = {
-        if params_len <= 0 {
-            vec![]
-        } else {
-            slice::from_raw_parts::<wasmer_value_t>(params, params_len as usize)
-                .iter()
-                .cloned()
-                .map(|x| x.into())
-                .collect()
+        let params: Vec<Value> = {
+            if params_len <= 0 {
+                vec![]
+            } else {
+                slice::from_raw_parts::<wasmer_value_t>(params, params_len as usize)
+                    .iter()
+                    .cloned()
+                    .map(|x| x.into())
+                    .collect()
+            }
+        };
+
+        let named_export = &*(func as *mut NamedExport);
+
+        let results: &mut [wasmer_value_t] =
+            slice::from_raw_parts_mut(results, results_len as usize);
+
+        let instance = &*named_export.instance;
+        let result = instance.call(&named_export.name, &params[..]);
+        match result {
+            Ok(results_vec) => {
+                if !results_vec.is_empty() {
+                    results[0] = results_vec[0].clone().into();
+                }
+                wasmer_result_t::WASMER_OK
+            }
+            Err(err) => {
+                update_last_error(err);
+                wasmer_result_t::WASMER_ERROR
+            }
         }
-    };
-
-    let named_export = &*(func as *mut NamedExport);
-
-    let results: &mut [wasmer_value_t] = slice::from_raw_parts_mut(results, results_len as usize);
-
-    let instance = &*named_export.instance;
-    let result = instance.call(&named_export.name, &params[..]);
-    match result {
-        Ok(results_vec) => {
-            if !results_vec.is_empty() {
-                let ret = match results_vec[0] {
-                    Value::I32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I32,
-                        value: wasmer_value { I32: x },
-                    },
-                    Value::I64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I64,
-                        value: wasmer_value { I64: x },
-                    },
-                    Value::F32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F32,
-                        value: wasmer_value { F32: x },
-                    },
-                    Value::F64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F64,
-                        value: wasmer_value { F64: x },
-                    },
-                    Value::V128(_) => unimplemented!("returning V128 type"),
-                };
-                results[0] = ret;
+    })
+}
+
+/// Calls a function handle returned by `wasmer_instance_get_export_func` with the
+/// provided parameters. Results are set using the provided `results` pointer.
+///
+/// `params` must hold as many values as the function has parameters, and `results` room
+/// for as many values as it has results. Unlike `wasmer_export_func_call`, the tags of
+/// the parameters aren't checked: each value is read as the type of its parameter.
+///
+/// A handle must not be called from multiple threads at once.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_export_func_call_fast(
+    handle: *mut wasmer_export_func_handle_t,
+    params: *const wasmer_value_t,
+    results: *mut wasmer_value_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if handle.is_null() {
+            update_last_error(CApiError {
+                msg: "handle ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let handle = &mut *(handle as *mut ExportFuncHandle);
+        let signature = handle.func.signature();
+
+        if !signature.params().is_empty() && params.is_null() {
+            update_last_error(CApiError {
+                msg: "params ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if !signature.returns().is_empty() && results.is_null() {
+            update_last_error(CApiError {
+                msg: "results ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        handle.params.clear();
+        if !params.is_null() {
+            let params = slice::from_raw_parts(params, signature.params().len());
+            for (ty, param) in signature.params().iter().zip(params) {
+                handle.params.push(match ty {
+                    Type::I32 => Value::I32(param.value.I32),
+                    Type::I64 => Value::I64(param.value.I64),
+                    Type::F32 => Value::F32(param.value.F32),
+                    Type::F64 => Value::F64(param.value.F64),
+                    Type::V128 => Value::V128(u128::from_le_bytes(param.value.V128)),
+                });
             }
-            wasmer_result_t::WASMER_OK
         }
-        Err(err) => {
-            update_last_error(err);
-            wasmer_result_t::WASMER_ERROR
+
+        match handle
+            .func
+            .call_unchecked(&handle.params, &mut handle.results)
+        {
+            Ok(()) => {
+                for (i, value) in handle.results.iter().enumerate() {
+                    *results.add(i) = value.clone().into();
+                }
+                wasmer_result_t::WASMER_OK
+            }
+            Err(err) => {
+                update_last_error(err);
+                wasmer_result_t::WASMER_ERROR
+            }
         }
-    }
+    })
+}
+
+/// Frees the memory for the given function handle.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_export_func_handle_destroy(handle: *mut wasmer_export_func_handle_t) {
+    catch_panic((), || {
+        if !handle.is_null() {
+            unsafe { Box::from_raw(handle as *mut ExportFuncHandle) };
+        }
+    })
 }
 
 impl From<(&std::string::String, &ExportIndex)> for NamedExportDescriptor {
@@ -1,7 +1,12 @@
 //! Create, set, get and destroy global variables of an instance.
 
-use crate::value::{wasmer_value_t, wasmer_value_tag};
-use wasmer_runtime::Global;
+use crate::{
+    error::{catch_panic, update_last_error, CApiError},
+    value::{wasmer_value_t, wasmer_value_tag},
+    wasmer_result_t,
+};
+use std::ptr;
+use wasmer_runtime::{Global, Value};
 
 #[repr(C)]
 #[derive(Clone)]
@@ -21,29 +26,56 @@ pub unsafe extern "C" fn wasmer_global_new(
     value: wasmer_value_t,
     mutable: bool,
 ) -> *mut wasmer_global_t {
-    let global = if mutable {
-        Global::new_mutable(value.into())
-    } else {
-        Global::new(value.into())
-    };
-    Box::into_raw(Box::new(global)) as *mut wasmer_global_t
+    catch_panic(ptr::null_mut(), || {
+        let global = if mutable {
+            Global::new_mutable(value.into())
+        } else {
+            Global::new(value.into())
+        };
+        Box::into_raw(Box::new(global)) as *mut wasmer_global_t
+    })
 }
 
 /// Gets the value stored by the given Global
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_global_get(global: *mut wasmer_global_t) -> wasmer_value_t {
-    let global = unsafe { &*(global as *mut Global) };
-    let value: wasmer_value_t = global.get().into();
-    value
+    catch_panic(Value::I32(0).into(), || {
+        let global = unsafe { &*(global as *mut Global) };
+        let value: wasmer_value_t = global.get().into();
+        value
+    })
 }
 
 /// Sets the value stored by the given Global
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure, e.g. if the Global is
+/// immutable or the value has the wrong type. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
-pub extern "C" fn wasmer_global_set(global: *mut wasmer_global_t, value: wasmer_value_t) {
-    let global = unsafe { &*(global as *mut Global) };
-    global.set(value.into());
+pub extern "C" fn wasmer_global_set(
+    global: *mut wasmer_global_t,
+    value: wasmer_value_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if global.is_null() {
+            update_last_error(CApiError {
+                msg: "global ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        let global = unsafe { &*(global as *mut Global) };
+        match global.set(value.into()) {
+            Ok(()) => wasmer_result_t::WASMER_OK,
+            Err(error) => {
+                update_last_error(error);
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
 }
 
 /// Returns a descriptor (type, mutability) of the given Global
@@ -52,19 +84,29 @@ pub extern "C" fn wasmer_global_set(global: *mut wasmer_global_t, value: wasmer_
 pub extern "C" fn wasmer_global_get_descriptor(
     global: *mut wasmer_global_t,
 ) -> wasmer_global_descriptor_t {
-    let global = unsafe { &*(global as *mut Global) };
-    let descriptor = global.descriptor();
-    wasmer_global_descriptor_t {
-        mutable: descriptor.mutable,
-        kind: descriptor.ty.into(),
-    }
+    catch_panic(
+        wasmer_global_descriptor_t {
+            mutable: false,
+            kind: wasmer_value_tag::WASM_I32,
+        },
+        || {
+            let global = unsafe { &*(global as *mut Global) };
+            let descriptor = global.descriptor();
+            wasmer_global_descriptor_t {
+                mutable: descriptor.mutable,
+                kind: descriptor.ty.into(),
+            }
+        },
+    )
 }
 
 /// Frees memory for the given Global
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_global_destroy(global: *mut wasmer_global_t) {
-    if !global.is_null() {
-        unsafe { Box::from_raw(global as *mut Global) };
-    }
+    catch_panic((), || {
+        if !global.is_null() {
+            unsafe { Box::from_raw(global as *mut Global) };
+        }
+    })
 }
@@ -4,7 +4,7 @@ use super::*;
 use crate::{get_slice_checked, instance::wasmer_instance_t, module::wasmer_module_t};
 
 use std::ptr;
-use wasmer_emscripten::{EmscriptenData, EmscriptenGlobals};
+use wasmer_emscripten::{EmscriptenData, EmscriptenGlobals, StdioSink};
 use wasmer_runtime::{Instance, Module};
 
 /// Type used to construct an import_object_t with Emscripten imports.
@@ -16,17 +16,19 @@ pub struct wasmer_emscripten_globals_t;
 pub unsafe extern "C" fn wasmer_emscripten_get_globals(
     module: *const wasmer_module_t,
 ) -> *mut wasmer_emscripten_globals_t {
-    if module.is_null() {
-        return ptr::null_mut();
-    }
-    let module = &*(module as *const Module);
-    match EmscriptenGlobals::new(module) {
-        Ok(globals) => Box::into_raw(Box::new(globals)) as *mut wasmer_emscripten_globals_t,
-        Err(msg) => {
-            update_last_error(CApiError { msg });
+    catch_panic(ptr::null_mut(), || {
+        if module.is_null() {
             return ptr::null_mut();
         }
-    }
+        let module = &*(module as *const Module);
+        match EmscriptenGlobals::new(module) {
+            Ok(globals) => Box::into_raw(Box::new(globals)) as *mut wasmer_emscripten_globals_t,
+            Err(msg) => {
+                update_last_error(CApiError { msg });
+                return ptr::null_mut();
+            }
+        }
+    })
 }
 
 /// Destroy `wasmer_emscrpten_globals_t` created by
@@ -35,10 +37,89 @@ pub unsafe extern "C" fn wasmer_emscripten_get_globals(
 pub unsafe extern "C" fn wasmer_emscripten_destroy_globals(
     globals: *mut wasmer_emscripten_globals_t,
 ) {
-    if globals.is_null() {
-        return;
-    }
-    let _ = Box::from_raw(globals);
+    catch_panic((), || {
+        if globals.is_null() {
+            return;
+        }
+        let _ = Box::from_raw(globals);
+    })
+}
+
+/// Opaque user data passed back to a stdio callback.
+struct StdioCallbackData(*mut c_void);
+
+// Manually implemented because the user data is owned by the caller, who is
+// responsible for making it safe to use from whichever thread runs the guest.
+unsafe impl Send for StdioCallbackData {}
+
+fn stdio_callback_sink(
+    callback: extern "C" fn(data: *const u8, data_length: u32, user_data: *mut c_void),
+    user_data: *mut c_void,
+) -> StdioSink {
+    let user_data = StdioCallbackData(user_data);
+    StdioSink::callback(move |bytes| {
+        catch_panic((), || {
+            callback(bytes.as_ptr(), bytes.len() as u32, user_data.0);
+        })
+    })
+}
+
+/// Sends what the guest writes to its standard output to `callback` instead
+/// of the host's stdout. The callback receives the written bytes, their
+/// length, and the given `user_data`.
+///
+/// Call it before `wasmer_emscripten_set_up`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_emscripten_set_stdout_callback(
+    globals: *mut wasmer_emscripten_globals_t,
+    callback: extern "C" fn(data: *const u8, data_length: u32, user_data: *mut c_void),
+    user_data: *mut c_void,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if globals.is_null() {
+            update_last_error(CApiError {
+                msg: "globals ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        let globals = &mut *(globals as *mut EmscriptenGlobals);
+        globals.set_stdout(stdio_callback_sink(callback, user_data));
+        wasmer_result_t::WASMER_OK
+    })
+}
+
+/// Sends what the guest writes to its standard error to `callback` instead
+/// of the host's stderr. The callback receives the written bytes, their
+/// length, and the given `user_data`.
+///
+/// Call it before `wasmer_emscripten_set_up`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_emscripten_set_stderr_callback(
+    globals: *mut wasmer_emscripten_globals_t,
+    callback: extern "C" fn(data: *const u8, data_length: u32, user_data: *mut c_void),
+    user_data: *mut c_void,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if globals.is_null() {
+            update_last_error(CApiError {
+                msg: "globals ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        let globals = &mut *(globals as *mut EmscriptenGlobals);
+        globals.set_stderr(stdio_callback_sink(callback, user_data));
+        wasmer_result_t::WASMER_OK
+    })
 }
 
 /// Execute global constructors (required if the module is compiled from C++)
@@ -51,25 +132,27 @@ pub unsafe extern "C" fn wasmer_emscripten_set_up(
     instance: *mut wasmer_instance_t,
     globals: *mut wasmer_emscripten_globals_t,
 ) -> wasmer_result_t {
-    if globals.is_null() || instance.is_null() {
-        return wasmer_result_t::WASMER_ERROR;
-    }
-    let instance = &mut *(instance as *mut Instance);
-    let globals = &*(globals as *mut EmscriptenGlobals);
-    let em_data = Box::into_raw(Box::new(EmscriptenData::new(
-        instance,
-        &globals.data,
-        Default::default(),
-    ))) as *mut c_void;
-    instance.context_mut().data = em_data;
-
-    match wasmer_emscripten::set_up_emscripten(instance) {
-        Ok(_) => wasmer_result_t::WASMER_OK,
-        Err(e) => {
-            update_last_error(e);
-            wasmer_result_t::WASMER_ERROR
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if globals.is_null() || instance.is_null() {
+            return wasmer_result_t::WASMER_ERROR;
         }
-    }
+        let instance = &mut *(instance as *mut Instance);
+        let globals = &*(globals as *mut EmscriptenGlobals);
+        let em_data = Box::into_raw(Box::new(EmscriptenData::new(
+            instance,
+            &globals.data,
+            Default::default(),
+        ))) as *mut c_void;
+        instance.context_mut().data = em_data;
+
+        match wasmer_emscripten::set_up_emscripten(instance) {
+            Ok(_) => wasmer_result_t::WASMER_OK,
+            Err(e) => {
+                update_last_error(e);
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
 }
 
 /// Convenience function for setting up arguments and calling the Emscripten
@@ -87,39 +170,41 @@ pub unsafe extern "C" fn wasmer_emscripten_call_main(
     args: *const wasmer_byte_array,
     args_len: c_uint,
 ) -> wasmer_result_t {
-    if instance.is_null() || args.is_null() {
-        return wasmer_result_t::WASMER_ERROR;
-    }
-    let instance = &mut *(instance as *mut Instance);
-
-    let arg_list = get_slice_checked(args, args_len as usize);
-    let arg_process_result: Result<Vec<&str>, _> =
-        arg_list.iter().map(|arg| arg.as_str()).collect();
-    let arg_vec = match arg_process_result.as_ref() {
-        Ok(arg_vec) => arg_vec,
-        Err(err) => {
-            update_last_error(*err);
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if instance.is_null() || args.is_null() {
             return wasmer_result_t::WASMER_ERROR;
         }
-    };
-
-    let prog_name = if let Some(prog_name) = arg_vec.first() {
-        prog_name
-    } else {
-        update_last_error(CApiError {
-            msg: "First argument (program name) is required to execute Emscripten's main function"
-                .to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    };
-
-    match wasmer_emscripten::emscripten_call_main(instance, prog_name, &arg_vec[1..]) {
-        Ok(_) => wasmer_result_t::WASMER_OK,
-        Err(e) => {
-            update_last_error(e);
-            wasmer_result_t::WASMER_ERROR
+        let instance = &mut *(instance as *mut Instance);
+
+        let arg_list = get_slice_checked(args, args_len as usize);
+        let arg_process_result: Result<Vec<&str>, _> =
+            arg_list.iter().map(|arg| arg.as_str()).collect();
+        let arg_vec = match arg_process_result.as_ref() {
+            Ok(arg_vec) => arg_vec,
+            Err(err) => {
+                update_last_error(*err);
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+
+        let prog_name = if let Some(prog_name) = arg_vec.first() {
+            prog_name
+        } else {
+            update_last_error(CApiError {
+                msg: "First argument (program name) is required to execute Emscripten's main function"
+                    .to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        };
+
+        match wasmer_emscripten::emscripten_call_main(instance, prog_name, &arg_vec[1..]) {
+            Ok(_) => wasmer_result_t::WASMER_OK,
+            Err(e) => {
+                update_last_error(e);
+                wasmer_result_t::WASMER_ERROR
+            }
         }
-    }
+    })
 }
 
 /// Create a `wasmer_import_object_t` with Emscripten imports, use
@@ -134,12 +219,14 @@ pub unsafe extern "C" fn wasmer_emscripten_call_main(
 pub unsafe extern "C" fn wasmer_emscripten_generate_import_object(
     globals: *mut wasmer_emscripten_globals_t,
 ) -> *mut wasmer_import_object_t {
-    if globals.is_null() {
-        return ptr::null_mut();
-    }
-    // TODO: figure out if we should be using UnsafeCell here or something
-    let g = &mut *(globals as *mut EmscriptenGlobals);
-    let import_object = Box::new(wasmer_emscripten::generate_emscripten_env(g));
-
-    Box::into_raw(import_object) as *mut wasmer_import_object_t
+    catch_panic(ptr::null_mut(), || {
+        if globals.is_null() {
+            return ptr::null_mut();
+        }
+        // TODO: figure out if we should be using UnsafeCell here or something
+        let g = &mut *(globals as *mut EmscriptenGlobals);
+        let import_object = Box::new(wasmer_emscripten::generate_emscripten_env(g));
+
+        Box::into_raw(import_object) as *mut wasmer_import_object_t
+    })
 }
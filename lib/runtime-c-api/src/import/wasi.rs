@@ -1,7 +1,9 @@
 use super::*;
 use crate::get_slice_checked;
-use libc::c_uchar;
-use std::{path::PathBuf, ptr, str};
+use crate::instance::wasmer_instance_t;
+use libc::{c_char, c_uchar};
+use std::{ffi::CStr, path::PathBuf, ptr, str};
+use wasmer_runtime::Instance;
 use wasmer_wasi as wasi;
 
 #[derive(Debug, PartialEq)]
@@ -34,12 +36,22 @@ impl From<c_uchar> for Version {
 
 /// Opens a directory that's visible to the WASI module as `alias` but
 /// is backed by the host file at `host_file_path`
+///
+/// If `host_file_path` is a regular file rather than a directory, only
+/// that file is exposed, and `alias` is the full path the WASI module
+/// sees it at, e.g. `/etc/hosts`.
 #[repr(C)]
 pub struct wasmer_wasi_map_dir_entry_t {
     /// What the WASI module will see in its virtual root
     pub alias: wasmer_byte_array,
     /// The backing file that the WASI module will interact with via the alias
     pub host_file_path: wasmer_byte_array,
+    /// Don't allow the WASI module to write to anything under the mapping.
+    pub read_only: bool,
+    /// Don't allow the WASI module to create anything in a mapped directory.
+    pub no_create: bool,
+    /// Don't allow the WASI module to remove anything from a mapped directory.
+    pub no_delete: bool,
 }
 
 impl wasmer_wasi_map_dir_entry_t {
@@ -68,19 +80,24 @@ pub unsafe extern "C" fn wasmer_wasi_generate_import_object(
     mapped_dirs: *const wasmer_wasi_map_dir_entry_t,
     mapped_dirs_len: c_uint,
 ) -> *mut wasmer_import_object_t {
-    let arg_list = get_slice_checked(args, args_len as usize);
-    let env_list = get_slice_checked(envs, envs_len as usize);
-    let preopened_file_list = get_slice_checked(preopened_files, preopened_files_len as usize);
-    let mapped_dir_list = get_slice_checked(mapped_dirs, mapped_dirs_len as usize);
-
-    wasmer_wasi_generate_import_object_inner(
-        Version::Latest,
-        arg_list,
-        env_list,
-        preopened_file_list,
-        mapped_dir_list,
-    )
-    .unwrap_or(ptr::null_mut())
+    catch_panic(ptr::null_mut(), || {
+        let arg_list = get_slice_checked(args, args_len as usize);
+        let env_list = get_slice_checked(envs, envs_len as usize);
+        let preopened_file_list = get_slice_checked(preopened_files, preopened_files_len as usize);
+        let mapped_dir_list = get_slice_checked(mapped_dirs, mapped_dirs_len as usize);
+
+        wasmer_wasi_generate_import_object_inner(
+            ImportObjectTarget::Version(Version::Latest),
+            arg_list,
+            env_list,
+            preopened_file_list,
+            mapped_dir_list,
+        )
+        .unwrap_or_else(|msg| {
+            update_last_error(CApiError { msg });
+            ptr::null_mut()
+        })
+    })
 }
 
 /// Creates a WASI import object for a specific version.
@@ -101,19 +118,72 @@ pub unsafe extern "C" fn wasmer_wasi_generate_import_object_for_version(
     mapped_dirs: *const wasmer_wasi_map_dir_entry_t,
     mapped_dirs_len: c_uint,
 ) -> *mut wasmer_import_object_t {
-    let arg_list = get_slice_checked(args, args_len as usize);
-    let env_list = get_slice_checked(envs, envs_len as usize);
-    let preopened_file_list = get_slice_checked(preopened_files, preopened_files_len as usize);
-    let mapped_dir_list = get_slice_checked(mapped_dirs, mapped_dirs_len as usize);
-
-    wasmer_wasi_generate_import_object_inner(
-        version.into(),
-        arg_list,
-        env_list,
-        preopened_file_list,
-        mapped_dir_list,
-    )
-    .unwrap_or(ptr::null_mut())
+    catch_panic(ptr::null_mut(), || {
+        let arg_list = get_slice_checked(args, args_len as usize);
+        let env_list = get_slice_checked(envs, envs_len as usize);
+        let preopened_file_list = get_slice_checked(preopened_files, preopened_files_len as usize);
+        let mapped_dir_list = get_slice_checked(mapped_dirs, mapped_dirs_len as usize);
+
+        wasmer_wasi_generate_import_object_inner(
+            ImportObjectTarget::Version(version.into()),
+            arg_list,
+            env_list,
+            preopened_file_list,
+            mapped_dir_list,
+        )
+        .unwrap_or_else(|msg| {
+            update_last_error(CApiError { msg });
+            ptr::null_mut()
+        })
+    })
+}
+
+/// Creates a WASI import object satisfying every version of WASI
+/// imported by the module.
+///
+/// This function is similar to `wasmer_wasi_generate_import_object`
+/// except that modules importing both `wasi_unstable` and
+/// `wasi_snapshot_preview1` get both namespaces, sharing the same WASI
+/// state.
+///
+/// Returns null if `module` is null or if the WASI state can't be
+/// created; use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_generate_import_object_for_module(
+    module: *const wasmer_module_t,
+    args: *const wasmer_byte_array,
+    args_len: c_uint,
+    envs: *const wasmer_byte_array,
+    envs_len: c_uint,
+    preopened_files: *const wasmer_byte_array,
+    preopened_files_len: c_uint,
+    mapped_dirs: *const wasmer_wasi_map_dir_entry_t,
+    mapped_dirs_len: c_uint,
+) -> *mut wasmer_import_object_t {
+    catch_panic(ptr::null_mut(), || {
+        if module.is_null() {
+            return ptr::null_mut();
+        }
+
+        let module = &*(module as *const Module);
+        let arg_list = get_slice_checked(args, args_len as usize);
+        let env_list = get_slice_checked(envs, envs_len as usize);
+        let preopened_file_list = get_slice_checked(preopened_files, preopened_files_len as usize);
+        let mapped_dir_list = get_slice_checked(mapped_dirs, mapped_dirs_len as usize);
+
+        wasmer_wasi_generate_import_object_inner(
+            ImportObjectTarget::Module(module),
+            arg_list,
+            env_list,
+            preopened_file_list,
+            mapped_dir_list,
+        )
+        .unwrap_or_else(|msg| {
+            update_last_error(CApiError { msg });
+            ptr::null_mut()
+        })
+    })
 }
 
 /// Find the version of WASI used by the module.
@@ -121,30 +191,39 @@ pub unsafe extern "C" fn wasmer_wasi_generate_import_object_for_version(
 /// In case of error, the returned version is `Version::Unknown`.
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_wasi_get_version(module: *const wasmer_module_t) -> Version {
-    if module.is_null() {
-        return Version::Unknown;
-    }
+    catch_panic(Version::Unknown, || {
+        if module.is_null() {
+            return Version::Unknown;
+        }
 
-    let module = &*(module as *const Module);
+        let module = &*(module as *const Module);
 
-    match wasi::get_wasi_version(module, false) {
-        Some(version) => match version {
-            wasi::WasiVersion::Snapshot0 => Version::Snapshot0,
-            wasi::WasiVersion::Snapshot1 => Version::Snapshot1,
-            wasi::WasiVersion::Latest => Version::Latest,
-        },
-        None => Version::Unknown,
-    }
+        match wasi::get_wasi_version(module, false) {
+            Some(version) => match version {
+                wasi::WasiVersion::Snapshot0 => Version::Snapshot0,
+                wasi::WasiVersion::Snapshot1 => Version::Snapshot1,
+                wasi::WasiVersion::Latest => Version::Latest,
+            },
+            None => Version::Unknown,
+        }
+    })
+}
+
+/// What the import object generated by
+/// `wasmer_wasi_generate_import_object_inner` must satisfy.
+enum ImportObjectTarget<'a> {
+    Version(Version),
+    Module(&'a Module),
 }
 
 /// Inner function that wraps error handling
 fn wasmer_wasi_generate_import_object_inner(
-    version: Version,
+    target: ImportObjectTarget,
     arg_list: &[wasmer_byte_array],
     env_list: &[wasmer_byte_array],
     preopened_file_list: &[wasmer_byte_array],
     mapped_dir_list: &[wasmer_wasi_map_dir_entry_t],
-) -> Result<*mut wasmer_import_object_t, str::Utf8Error> {
+) -> Result<*mut wasmer_import_object_t, String> {
     let arg_vec = arg_list.iter().map(|arg| unsafe { arg.as_vec() }).collect();
     let env_vec = env_list
         .iter()
@@ -153,26 +232,65 @@ fn wasmer_wasi_generate_import_object_inner(
     let po_file_vec = preopened_file_list
         .iter()
         .map(|po_file| Ok(unsafe { PathBuf::from(po_file.as_str()?) }.to_owned()))
-        .collect::<Result<Vec<_>, _>>()?;
-    let mapped_dir_vec = mapped_dir_list
-        .iter()
-        .map(|entry| unsafe { entry.as_tuple() })
-        .collect::<Result<Vec<_>, _>>()?;
-
-    let version = match version {
-        Version::Latest => wasi::WasiVersion::Latest,
-        Version::Snapshot0 => wasi::WasiVersion::Snapshot0,
-        Version::Snapshot1 => wasi::WasiVersion::Snapshot1,
-        _ => panic!("Version {:?} is invalid.", version),
+        .collect::<Result<Vec<_>, str::Utf8Error>>()
+        .map_err(|e| e.to_string())?;
+    let mut mapped_dir_vec = vec![];
+    let mut mapped_dir_restrictions = vec![];
+    let mut mapped_file_vec = vec![];
+    for entry in mapped_dir_list {
+        let (alias, host_path) = unsafe { entry.as_tuple() }.map_err(|e| e.to_string())?;
+        if host_path.is_file() {
+            mapped_file_vec.push((alias, host_path, entry.read_only));
+        } else {
+            mapped_dir_restrictions.push((
+                alias.clone(),
+                wasi::state::PreopenRestrictions {
+                    read_only: entry.read_only,
+                    no_create: entry.no_create,
+                    no_delete: entry.no_delete,
+                },
+            ));
+            mapped_dir_vec.push((alias, host_path));
+        }
+    }
+
+    let mut fs = wasi::state::WasiFs::new(&po_file_vec, &mapped_dir_vec)?;
+    for (alias, restrictions) in mapped_dir_restrictions {
+        if let Some(fd) = fs.preopen_fd_by_name(&alias) {
+            fs.restrict_preopen(fd, restrictions)
+                .map_err(|e| format!("Could not restrict \"{}\": {}", alias, e))?;
+        }
+    }
+    for (guest_path, host_file, read_only) in mapped_file_vec {
+        fs.map_file(&guest_path, &host_file, read_only)?;
+    }
+    let wasi_state = wasi::state::WasiState {
+        fs,
+        args: arg_vec,
+        envs: env_vec,
+        clock: Default::default(),
+        random: Default::default(),
+        hook: None,
+        mode: Default::default(),
+        exit_code: None,
+        unimplemented_policy: Default::default(),
     };
 
-    let import_object = Box::new(wasi::generate_import_object_for_version(
-        version,
-        arg_vec,
-        env_vec,
-        po_file_vec,
-        mapped_dir_vec,
-    ));
+    let import_object = Box::new(match target {
+        ImportObjectTarget::Version(version) => {
+            let version = match version {
+                Version::Latest => wasi::WasiVersion::Latest,
+                Version::Snapshot0 => wasi::WasiVersion::Snapshot0,
+                Version::Snapshot1 => wasi::WasiVersion::Snapshot1,
+                _ => panic!("Version {:?} is invalid.", version),
+            };
+
+            wasi::generate_import_object_from_state(wasi_state, version)
+        }
+        ImportObjectTarget::Module(module) => {
+            wasi::generate_import_object_from_state_for_module(module, wasi_state)
+        }
+    });
     Ok(Box::into_raw(import_object) as *mut wasmer_import_object_t)
 }
 
@@ -184,9 +302,103 @@ fn wasmer_wasi_generate_import_object_inner(
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_wasi_generate_default_import_object() -> *mut wasmer_import_object_t
 {
-    let import_object = Box::new(wasi::generate_import_object(vec![], vec![], vec![], vec![]));
+    catch_panic(ptr::null_mut(), || {
+        let import_object = Box::new(wasi::generate_import_object(vec![], vec![], vec![], vec![]));
+
+        Box::into_raw(import_object) as *mut wasmer_import_object_t
+    })
+}
 
-    Box::into_raw(import_object) as *mut wasmer_import_object_t
+/// Reads the file the WASI module sees at `path` into `buffer`.
+///
+/// `instance` must have been instantiated with a WASI import object. The
+/// file is read through the instance's WASI filesystem, so files that only
+/// exist in the virtual filesystem can be read too.
+///
+/// Returns the size of the file in bytes. Nothing is copied when `buffer`
+/// is null or `buffer_len` is smaller than the file, so the function can be
+/// called again with a large enough buffer.
+/// Returns `-1` if an error occurs.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_read_file(
+    instance: *mut wasmer_instance_t,
+    path: *const c_char,
+    buffer: *mut u8,
+    buffer_len: u32,
+) -> i64 {
+    catch_panic(-1, || {
+        if instance.is_null() || path.is_null() {
+            update_last_error(CApiError {
+                msg: "instance and path must not be null".to_string(),
+            });
+            return -1;
+        }
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(_) => {
+                update_last_error(CApiError {
+                    msg: "path is not valid UTF-8".to_string(),
+                });
+                return -1;
+            }
+        };
+
+        let instance = &mut *(instance as *mut Instance);
+        let state = wasi::state::get_wasi_state(instance.context_mut());
+        let contents = match state.fs.read_file(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                update_last_error(CApiError {
+                    msg: format!("could not read \"{}\": {:?}", path, e),
+                });
+                return -1;
+            }
+        };
+
+        if !buffer.is_null() && contents.len() <= buffer_len as usize {
+            ptr::copy_nonoverlapping(contents.as_ptr(), buffer, contents.len());
+        }
+        contents.len() as i64
+    })
+}
+
+/// Gets the code the WASI module passed to `proc_exit`.
+///
+/// When a call into the module fails because the module called `proc_exit`,
+/// the code it exited with is written to `exit_code`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` if the module has exited.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` if it hasn't, or upon failure.
+/// Use `wasmer_last_error_length` and `wasmer_last_error_message` to get an
+/// error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_wasi_get_exit_code(
+    instance: *mut wasmer_instance_t,
+    exit_code: *mut u32,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if instance.is_null() || exit_code.is_null() {
+            update_last_error(CApiError {
+                msg: "instance and exit_code must not be null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let instance = &mut *(instance as *mut Instance);
+        match wasi::state::get_wasi_state(instance.context_mut()).exit_code {
+            Some(code) => {
+                *exit_code = code;
+                wasmer_result_t::WASMER_OK
+            }
+            None => {
+                update_last_error(CApiError {
+                    msg: "the WASI module has not called `proc_exit`".to_string(),
+                });
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
 }
 
 #[cfg(test)]
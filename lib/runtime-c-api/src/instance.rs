@@ -1,17 +1,20 @@
 //! Instantiate a module, call functions, and read exports.
 
 use crate::{
-    error::{update_last_error, CApiError},
-    export::{wasmer_exports_t, wasmer_import_export_kind, NamedExport, NamedExports},
+    error::{catch_panic, update_last_error, CApiError},
+    export::{
+        wasmer_export_func_handle_t, wasmer_exports_t, wasmer_import_export_kind, ExportFuncHandle,
+        NamedExport, NamedExports,
+    },
     import::{wasmer_import_object_t, wasmer_import_t},
     memory::wasmer_memory_t,
     module::wasmer_module_t,
-    value::{wasmer_value, wasmer_value_t, wasmer_value_tag},
+    value::wasmer_value_t,
     wasmer_result_t,
 };
 use libc::{c_char, c_int, c_void};
-use std::{collections::HashMap, ffi::CStr, slice};
-use wasmer_runtime::{Ctx, Global, Instance, Memory, Module, Table, Value};
+use std::{collections::HashMap, ffi::CStr, mem, ptr, slice};
+use wasmer_runtime::{Ctx, Global, Instance, Memory, Module, ResolvedFunc, Table, Value};
 use wasmer_runtime_core::{
     export::Export,
     import::{ImportObject, Namespace},
@@ -23,6 +26,19 @@ pub struct wasmer_instance_t;
 #[repr(C)]
 pub struct wasmer_instance_context_t;
 
+/// The resources used by an instance, see `wasmer_instance_stats`.
+///
+/// `points_used` is only set when `has_points_used` is true, which is when the module was
+/// compiled with the metering middleware.
+#[repr(C)]
+pub struct wasmer_instance_stats_t {
+    pub memory_bytes: u64,
+    pub table_elements: u64,
+    pub host_calls: u64,
+    pub has_points_used: bool,
+    pub points_used: u64,
+}
+
 /// Creates a new Instance from the given wasm bytes and imports.
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
@@ -38,79 +54,81 @@ pub unsafe extern "C" fn wasmer_instantiate(
     imports: *mut wasmer_import_t,
     imports_len: c_int,
 ) -> wasmer_result_t {
-    if wasm_bytes.is_null() {
-        update_last_error(CApiError {
-            msg: "wasm bytes ptr is null".to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    }
-    let imports: &[wasmer_import_t] = slice::from_raw_parts(imports, imports_len as usize);
-    let mut import_object = ImportObject::new();
-    let mut namespaces = HashMap::new();
-    for import in imports {
-        let module_name = slice::from_raw_parts(
-            import.module_name.bytes,
-            import.module_name.bytes_len as usize,
-        );
-        let module_name = if let Ok(s) = std::str::from_utf8(module_name) {
-            s
-        } else {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if wasm_bytes.is_null() {
             update_last_error(CApiError {
-                msg: "error converting module name to string".to_string(),
+                msg: "wasm bytes ptr is null".to_string(),
             });
             return wasmer_result_t::WASMER_ERROR;
-        };
-        let import_name = slice::from_raw_parts(
-            import.import_name.bytes,
-            import.import_name.bytes_len as usize,
-        );
-        let import_name = if let Ok(s) = std::str::from_utf8(import_name) {
-            s
-        } else {
-            update_last_error(CApiError {
-                msg: "error converting import_name to string".to_string(),
-            });
-            return wasmer_result_t::WASMER_ERROR;
-        };
+        }
+        let imports: &[wasmer_import_t] = slice::from_raw_parts(imports, imports_len as usize);
+        let mut import_object = ImportObject::new();
+        let mut namespaces = HashMap::new();
+        for import in imports {
+            let module_name = slice::from_raw_parts(
+                import.module_name.bytes,
+                import.module_name.bytes_len as usize,
+            );
+            let module_name = if let Ok(s) = std::str::from_utf8(module_name) {
+                s
+            } else {
+                update_last_error(CApiError {
+                    msg: "error converting module name to string".to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            };
+            let import_name = slice::from_raw_parts(
+                import.import_name.bytes,
+                import.import_name.bytes_len as usize,
+            );
+            let import_name = if let Ok(s) = std::str::from_utf8(import_name) {
+                s
+            } else {
+                update_last_error(CApiError {
+                    msg: "error converting import_name to string".to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            };
 
-        let namespace = namespaces.entry(module_name).or_insert_with(Namespace::new);
+            let namespace = namespaces.entry(module_name).or_insert_with(Namespace::new);
 
-        // TODO check that tag is actually in bounds here
-        let export = match import.tag {
-            wasmer_import_export_kind::WASM_MEMORY => {
-                let mem = import.value.memory as *mut Memory;
-                Export::Memory((&*mem).clone())
-            }
-            wasmer_import_export_kind::WASM_FUNCTION => {
-                let func_export = import.value.func as *mut Export;
-                (&*func_export).clone()
-            }
-            wasmer_import_export_kind::WASM_GLOBAL => {
-                let global = import.value.global as *mut Global;
-                Export::Global((&*global).clone())
-            }
-            wasmer_import_export_kind::WASM_TABLE => {
-                let table = import.value.table as *mut Table;
-                Export::Table((&*table).clone())
+            // TODO check that tag is actually in bounds here
+            let export = match import.tag {
+                wasmer_import_export_kind::WASM_MEMORY => {
+                    let mem = import.value.memory as *mut Memory;
+                    Export::Memory((&*mem).clone())
+                }
+                wasmer_import_export_kind::WASM_FUNCTION => {
+                    let func_export = import.value.func as *mut Export;
+                    (&*func_export).clone()
+                }
+                wasmer_import_export_kind::WASM_GLOBAL => {
+                    let global = import.value.global as *mut Global;
+                    Export::Global((&*global).clone())
+                }
+                wasmer_import_export_kind::WASM_TABLE => {
+                    let table = import.value.table as *mut Table;
+                    Export::Table((&*table).clone())
+                }
+            };
+            namespace.insert(import_name, export);
+        }
+        for (module_name, namespace) in namespaces.into_iter() {
+            import_object.register(module_name, namespace);
+        }
+
+        let bytes: &[u8] = slice::from_raw_parts_mut(wasm_bytes, wasm_bytes_len as usize);
+        let result = wasmer_runtime::instantiate(bytes, &import_object);
+        let new_instance = match result {
+            Ok(instance) => instance,
+            Err(error) => {
+                update_last_error(error);
+                return wasmer_result_t::WASMER_ERROR;
             }
         };
-        namespace.insert(import_name, export);
-    }
-    for (module_name, namespace) in namespaces.into_iter() {
-        import_object.register(module_name, namespace);
-    }
-
-    let bytes: &[u8] = slice::from_raw_parts_mut(wasm_bytes, wasm_bytes_len as usize);
-    let result = wasmer_runtime::instantiate(bytes, &import_object);
-    let new_instance = match result {
-        Ok(instance) => instance,
-        Err(error) => {
-            update_last_error(error);
-            return wasmer_result_t::WASMER_ERROR;
-        }
-    };
-    *instance = Box::into_raw(Box::new(new_instance)) as *mut wasmer_instance_t;
-    wasmer_result_t::WASMER_OK
+        *instance = Box::into_raw(Box::new(new_instance)) as *mut wasmer_instance_t;
+        wasmer_result_t::WASMER_OK
+    })
 }
 
 /// Given:
@@ -124,19 +142,21 @@ pub unsafe extern "C" fn wasmer_module_import_instantiate(
     module: *const wasmer_module_t,
     import_object: *const wasmer_import_object_t,
 ) -> wasmer_result_t {
-    let import_object: &ImportObject = &*(import_object as *const ImportObject);
-    let module: &Module = &*(module as *const Module);
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let import_object: &ImportObject = &*(import_object as *const ImportObject);
+        let module: &Module = &*(module as *const Module);
 
-    let new_instance: Instance = match module.instantiate(import_object) {
-        Ok(instance) => instance,
-        Err(error) => {
-            update_last_error(error);
-            return wasmer_result_t::WASMER_ERROR;
-        }
-    };
-    *instance = Box::into_raw(Box::new(new_instance)) as *mut wasmer_instance_t;
+        let new_instance: Instance = match module.instantiate(import_object) {
+            Ok(instance) => instance,
+            Err(error) => {
+                update_last_error(error);
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        *instance = Box::into_raw(Box::new(new_instance)) as *mut wasmer_instance_t;
 
-    return wasmer_result_t::WASMER_OK;
+        return wasmer_result_t::WASMER_OK;
+    })
 }
 
 /// Extracts the instance's context and returns it.
@@ -145,11 +165,86 @@ pub unsafe extern "C" fn wasmer_module_import_instantiate(
 pub unsafe extern "C" fn wasmer_instance_context_get(
     instance: *mut wasmer_instance_t,
 ) -> *const wasmer_instance_context_t {
-    let instance_ref = &*(instance as *const Instance);
+    catch_panic(ptr::null(), || {
+        let instance_ref = &*(instance as *const Instance);
 
-    let ctx: *const Ctx = instance_ref.context() as *const _;
+        let ctx: *const Ctx = instance_ref.context() as *const _;
 
-    ctx as *const wasmer_instance_context_t
+        ctx as *const wasmer_instance_context_t
+    })
+}
+
+/// Stores the resources used by an instance so far in `stats`: the size of the memories
+/// and the number of elements of the tables it defines, the number of calls it made to
+/// host functions, and the metering points it used.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_stats(
+    instance: *const wasmer_instance_t,
+    stats: *mut wasmer_instance_stats_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if instance.is_null() {
+            update_last_error(CApiError {
+                msg: "instance ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if stats.is_null() {
+            update_last_error(CApiError {
+                msg: "stats ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let instance = &*(instance as *const Instance);
+        let instance_stats = instance.stats();
+        *stats = wasmer_instance_stats_t {
+            memory_bytes: instance_stats.memory_bytes,
+            table_elements: instance_stats.table_elements,
+            host_calls: instance_stats.host_calls,
+            has_points_used: instance_stats.points_used.is_some(),
+            points_used: instance_stats.points_used.unwrap_or(0),
+        };
+        wasmer_result_t::WASMER_OK
+    })
+}
+
+/// Runs the start function of an instance created with
+/// `wasmer_module_instantiate_unstarted`, if the module has one. It runs only once, and
+/// not at all for instances created otherwise, which were started when created.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_run_start(
+    instance: *mut wasmer_instance_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if instance.is_null() {
+            update_last_error(CApiError {
+                msg: "instance ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let instance = &mut *(instance as *mut Instance);
+        match instance.run_start() {
+            Ok(()) => wasmer_result_t::WASMER_OK,
+            Err(error) => {
+                update_last_error(error);
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
 }
 
 /// Calls an instances exported function by `name` with the provided parameters.
@@ -169,65 +264,107 @@ pub unsafe extern "C" fn wasmer_instance_call(
     results: *mut wasmer_value_t,
     results_len: u32,
 ) -> wasmer_result_t {
-    if instance.is_null() {
-        update_last_error(CApiError {
-            msg: "instance ptr is null".to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    }
-    if name.is_null() {
-        update_last_error(CApiError {
-            msg: "name ptr is null".to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    }
-    if params.is_null() {
-        update_last_error(CApiError {
-            msg: "params ptr is null".to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    }
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if instance.is_null() {
+            update_last_error(CApiError {
+                msg: "instance ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if name.is_null() {
+            update_last_error(CApiError {
+                msg: "name ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if params.is_null() {
+            update_last_error(CApiError {
+                msg: "params ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let params: &[wasmer_value_t] = slice::from_raw_parts(params, params_len as usize);
+        let params: Vec<Value> = params.iter().cloned().map(|x| x.into()).collect();
 
-    let params: &[wasmer_value_t] = slice::from_raw_parts(params, params_len as usize);
-    let params: Vec<Value> = params.iter().cloned().map(|x| x.into()).collect();
-
-    let func_name_c = CStr::from_ptr(name);
-    let func_name_r = func_name_c.to_str().unwrap();
-
-    let results: &mut [wasmer_value_t] = slice::from_raw_parts_mut(results, results_len as usize);
-    let result = (&*(instance as *mut Instance)).call(func_name_r, &params[..]);
-
-    match result {
-        Ok(results_vec) => {
-            if !results_vec.is_empty() {
-                let ret = match results_vec[0] {
-                    Value::I32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I32,
-                        value: wasmer_value { I32: x },
-                    },
-                    Value::I64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I64,
-                        value: wasmer_value { I64: x },
-                    },
-                    Value::F32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F32,
-                        value: wasmer_value { F32: x },
-                    },
-                    Value::F64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F64,
-                        value: wasmer_value { F64: x },
-                    },
-                    Value::V128(_) => unimplemented!("calling function with V128 parameter"),
-                };
-                results[0] = ret;
+        let func_name_c = CStr::from_ptr(name);
+        let func_name_r = func_name_c.to_str().unwrap();
+
+        let results: &mut [wasmer_value_t] =
+            slice::from_raw_parts_mut(results, results_len as usize);
+        let result = (&*(instance as *mut Instance)).call(func_name_r, &params[..]);
+
+        match result {
+            Ok(results_vec) => {
+                if !results_vec.is_empty() {
+                    results[0] = results_vec[0].clone().into();
+                }
+                wasmer_result_t::WASMER_OK
             }
-            wasmer_result_t::WASMER_OK
+            Err(err) => {
+                update_last_error(err);
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
+}
+
+/// Resolves the exported function `name` of an instance once, returning a handle that
+/// `wasmer_export_func_call_fast` calls without looking the function up by name or
+/// checking the parameters against its signature again.
+///
+/// The handle must not outlive the instance. The caller owns the object and should call
+/// `wasmer_export_func_handle_destroy` to free it.
+///
+/// Returns `NULL` upon failure. Use `wasmer_last_error_length` and
+/// `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_get_export_func(
+    instance: *const wasmer_instance_t,
+    name: *const c_char,
+) -> *mut wasmer_export_func_handle_t {
+    catch_panic(ptr::null_mut(), || {
+        if instance.is_null() {
+            update_last_error(CApiError {
+                msg: "instance ptr is null".to_string(),
+            });
+            return ptr::null_mut();
         }
-        Err(err) => {
-            update_last_error(err);
-            wasmer_result_t::WASMER_ERROR
+        if name.is_null() {
+            update_last_error(CApiError {
+                msg: "name ptr is null".to_string(),
+            });
+            return ptr::null_mut();
         }
-    }
+
+        let name = match CStr::from_ptr(name).to_str() {
+            Ok(name) => name,
+            Err(_) => {
+                update_last_error(CApiError {
+                    msg: "name is not valid UTF-8".to_string(),
+                });
+                return ptr::null_mut();
+            }
+        };
+
+        let instance = &*(instance as *const Instance);
+        let func = match instance.dyn_func(name) {
+            Ok(func) => func.resolve(),
+            Err(err) => {
+                update_last_error(err);
+                return ptr::null_mut();
+            }
+        };
+
+        let handle = Box::new(ExportFuncHandle {
+            // The caller guarantees that the handle doesn't outlive the instance.
+            func: mem::transmute::<ResolvedFunc, ResolvedFunc<'static>>(func),
+            params: Vec::new(),
+            results: Vec::new(),
+        });
+        Box::into_raw(handle) as *mut wasmer_export_func_handle_t
+    })
 }
 
 /// Gets Exports for the given instance
@@ -239,17 +376,19 @@ pub unsafe extern "C" fn wasmer_instance_exports(
     instance: *mut wasmer_instance_t,
     exports: *mut *mut wasmer_exports_t,
 ) {
-    let instance_ref = &mut *(instance as *mut Instance);
-    let mut exports_vec: Vec<NamedExport> = Vec::with_capacity(instance_ref.exports().count());
-    for (name, export) in instance_ref.exports() {
-        exports_vec.push(NamedExport {
-            name: name.clone(),
-            export: export.clone(),
-            instance: instance as *mut Instance,
-        });
-    }
-    let named_exports: Box<NamedExports> = Box::new(NamedExports(exports_vec));
-    *exports = Box::into_raw(named_exports) as *mut wasmer_exports_t;
+    catch_panic((), || {
+        let instance_ref = &mut *(instance as *mut Instance);
+        let mut exports_vec: Vec<NamedExport> = Vec::with_capacity(instance_ref.exports().count());
+        for (name, export) in instance_ref.exports() {
+            exports_vec.push(NamedExport {
+                name: name.clone(),
+                export: export.clone(),
+                instance: instance as *mut Instance,
+            });
+        }
+        let named_exports: Box<NamedExports> = Box::new(NamedExports(exports_vec));
+        *exports = Box::into_raw(named_exports) as *mut wasmer_exports_t;
+    })
 }
 
 /// Sets the `data` field of the instance context. This context will be
@@ -260,21 +399,79 @@ pub extern "C" fn wasmer_instance_context_data_set(
     instance: *mut wasmer_instance_t,
     data_ptr: *mut c_void,
 ) {
-    let instance_ref = unsafe { &mut *(instance as *mut Instance) };
-    instance_ref.context_mut().data = data_ptr;
+    catch_panic((), || {
+        let instance_ref = unsafe { &mut *(instance as *mut Instance) };
+        instance_ref.context_mut().data = data_ptr;
+    })
+}
+
+/// Calls the destructor of the data of an instance when the instance is
+/// destroyed or its data is replaced.
+struct InstanceData {
+    data: *mut c_void,
+    destructor: extern "C" fn(*mut c_void),
+}
+
+// Manually implemented because InstanceData contains a raw pointer, which
+// the embedder hands over to the instance.
+unsafe impl Send for InstanceData {}
+
+impl Drop for InstanceData {
+    fn drop(&mut self) {
+        catch_panic((), || (self.destructor)(self.data));
+    }
+}
+
+/// Sets the `data` field of the instance context like
+/// `wasmer_instance_context_data_set`, and calls `destructor` with it
+/// when the instance is destroyed or its data set again.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_instance_context_data_set_with_destructor(
+    instance: *mut wasmer_instance_t,
+    data_ptr: *mut c_void,
+    destructor: extern "C" fn(*mut c_void),
+) {
+    catch_panic((), || {
+        let instance_ref = unsafe { &mut *(instance as *mut Instance) };
+        let ctx = instance_ref.context_mut();
+        ctx.data = data_ptr;
+        ctx.set_data(InstanceData {
+            data: data_ptr,
+            destructor,
+        });
+    })
 }
 
 /// Gets the memory within the context at the index `memory_idx`.
-/// The index is always 0 until multiple memories are supported.
+///
+/// Multiple memories aren't supported, so `memory_idx` must be 0 and the
+/// instance must have a memory.
+///
+/// Returns NULL if the instance has no memory at that index. Use
+/// `wasmer_last_error_length` and `wasmer_last_error_message` to get an
+/// error message.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_instance_context_memory(
     ctx: *const wasmer_instance_context_t,
-    _memory_idx: u32,
+    memory_idx: u32,
 ) -> *const wasmer_memory_t {
-    let ctx = unsafe { &*(ctx as *const Ctx) };
-    let memory = ctx.memory(0);
-    memory as *const Memory as *const wasmer_memory_t
+    catch_panic(ptr::null(), || {
+        let ctx = unsafe { &*(ctx as *const Ctx) };
+        if memory_idx >= ctx.memory_count() {
+            update_last_error(CApiError {
+                msg: format!(
+                    "the instance has no memory at index {} ({} memories)",
+                    memory_idx,
+                    ctx.memory_count()
+                ),
+            });
+            return std::ptr::null();
+        }
+        let memory = ctx.memory(memory_idx);
+        memory as *const Memory as *const wasmer_memory_t
+    })
 }
 
 /// Gets the `data` field within the context.
@@ -283,15 +480,19 @@ pub extern "C" fn wasmer_instance_context_memory(
 pub extern "C" fn wasmer_instance_context_data_get(
     ctx: *const wasmer_instance_context_t,
 ) -> *mut c_void {
-    let ctx = unsafe { &*(ctx as *const Ctx) };
-    ctx.data
+    catch_panic(ptr::null_mut(), || {
+        let ctx = unsafe { &*(ctx as *const Ctx) };
+        ctx.data
+    })
 }
 
 /// Frees memory for the given Instance
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_instance_destroy(instance: *mut wasmer_instance_t) {
-    if !instance.is_null() {
-        unsafe { Box::from_raw(instance as *mut Instance) };
-    }
+    catch_panic((), || {
+        if !instance.is_null() {
+            unsafe { Box::from_raw(instance as *mut Instance) };
+        }
+    })
 }
@@ -93,6 +93,8 @@
 extern crate wasmer_runtime;
 extern crate wasmer_runtime_core;
 
+pub mod cache;
+pub mod config;
 pub mod error;
 pub mod export;
 pub mod global;
@@ -106,6 +108,7 @@ pub mod table;
 #[cfg(all(not(target_family = "windows"), target_arch = "x86_64"))]
 pub mod trampoline;
 pub mod value;
+pub mod version;
 
 #[allow(non_camel_case_types)]
 #[repr(C)]
@@ -133,6 +136,14 @@ pub struct wasmer_byte_array {
 }
 
 impl wasmer_byte_array {
+    /// An empty byte array, with a null pointer.
+    pub(crate) fn empty() -> Self {
+        wasmer_byte_array {
+            bytes: std::ptr::null(),
+            bytes_len: 0,
+        }
+    }
+
     /// Get the data as a slice
     pub unsafe fn as_slice<'a>(&self) -> &'a [u8] {
         get_slice_checked(self.bytes, self.bytes_len as usize)
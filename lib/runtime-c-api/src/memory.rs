@@ -1,11 +1,17 @@
 //! Create, read, write, grow, destroy memory of an instance.
 
-use crate::{error::update_last_error, error::CApiError, wasmer_limits_t, wasmer_result_t};
-use std::cell::Cell;
+use crate::{
+    error::{catch_panic, update_last_error, CApiError},
+    wasmer_limit_option_t, wasmer_limits_t, wasmer_result_t,
+};
+use libc::c_void;
+use std::{cell::Cell, ptr};
 use wasmer_runtime::Memory;
 use wasmer_runtime_core::{
+    error::GrowError,
+    memory::MemoryBacking,
     types::MemoryDescriptor,
-    units::{Bytes, Pages},
+    units::{Bytes, Pages, WASM_PAGE_SIZE},
 };
 
 #[repr(C)]
@@ -26,31 +32,155 @@ pub unsafe extern "C" fn wasmer_memory_new(
     memory: *mut *mut wasmer_memory_t,
     limits: wasmer_limits_t,
 ) -> wasmer_result_t {
-    let max = if limits.max.has_some {
-        Some(Pages(limits.max.some))
-    } else {
-        None
-    };
-    let desc = MemoryDescriptor::new(Pages(limits.min), max, false);
-    let new_desc = match desc {
-        Ok(desc) => desc,
-        Err(error) => {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let max = if limits.max.has_some {
+            Some(Pages(limits.max.some))
+        } else {
+            None
+        };
+        let desc = MemoryDescriptor::new(Pages(limits.min), max, false);
+        let new_desc = match desc {
+            Ok(desc) => desc,
+            Err(error) => {
+                update_last_error(CApiError {
+                    msg: error.to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        let result = Memory::new(new_desc);
+        let new_memory = match result {
+            Ok(memory) => memory,
+            Err(error) => {
+                update_last_error(error);
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        *memory = Box::into_raw(Box::new(new_memory)) as *mut wasmer_memory_t;
+        wasmer_result_t::WASMER_OK
+    })
+}
+
+/// A buffer owned by the caller of `wasmer_memory_new_external`.
+struct ExternalBuffer {
+    data: *mut u8,
+    data_length: usize,
+    grow: Option<extern "C" fn(data: *mut u8, data_length: u32, user_data: *mut c_void) -> *mut u8>,
+    user_data: *mut c_void,
+}
+
+// Manually implemented because the buffer and the user data are owned by the
+// caller, who is responsible for making them safe to use from whichever thread
+// uses the memory.
+unsafe impl Send for ExternalBuffer {}
+
+unsafe impl MemoryBacking for ExternalBuffer {
+    fn base(&self) -> *mut u8 {
+        self.data
+    }
+
+    fn len(&self) -> usize {
+        self.data_length
+    }
+
+    fn grow(&mut self, len: usize) -> Result<(), GrowError> {
+        catch_panic(Err(GrowError::MemoryGrowError), || {
+            let grow = self.grow.ok_or(GrowError::MemoryGrowError)?;
+            if len > u32::max_value() as usize {
+                return Err(GrowError::MemoryGrowError);
+            }
+            let data = grow(self.data, len as u32, self.user_data);
+            if data.is_null() {
+                return Err(GrowError::MemoryGrowError);
+            }
+            self.data = data;
+            self.data_length = len;
+            Ok(())
+        })
+    }
+}
+
+/// Creates a new Memory over `buffer`, which the caller owns and must keep alive
+/// until the Memory is destroyed, e.g. to share it with another process.
+///
+/// `data_length` is the size of the memory in bytes, a multiple of the 64KiB wasm
+/// page size. The buffer must be 8-byte aligned, and extend 4096 bytes past
+/// `data_length`: guest accesses straddling the end of the memory may touch them.
+///
+/// The memory can't grow past `max_pages`, and can only grow through `grow`, which
+/// is called with the buffer, the new size in bytes and `user_data`. It returns a
+/// buffer of the new size plus 4096 bytes holding the current contents, e.g. by
+/// reallocating `data`, or `NULL` to refuse. A `NULL` `grow` disables growth. It
+/// must not call back into the Memory.
+///
+/// The caller owns the object and should call `wasmer_memory_destroy` to free it,
+/// which leaves the buffer untouched.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_memory_new_external(
+    memory: *mut *mut wasmer_memory_t,
+    buffer: *mut u8,
+    data_length: u32,
+    max_pages: wasmer_limit_option_t,
+    grow: Option<extern "C" fn(data: *mut u8, data_length: u32, user_data: *mut c_void) -> *mut u8>,
+    user_data: *mut c_void,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if buffer.is_null() {
             update_last_error(CApiError {
-                msg: error.to_string(),
+                msg: "buffer ptr is null".to_string(),
             });
             return wasmer_result_t::WASMER_ERROR;
         }
-    };
-    let result = Memory::new(new_desc);
-    let new_memory = match result {
-        Ok(memory) => memory,
-        Err(error) => {
-            update_last_error(error);
+        if buffer as usize % 8 != 0 {
+            update_last_error(CApiError {
+                msg: "buffer is not 8-byte aligned".to_string(),
+            });
             return wasmer_result_t::WASMER_ERROR;
         }
-    };
-    *memory = Box::into_raw(Box::new(new_memory)) as *mut wasmer_memory_t;
-    wasmer_result_t::WASMER_OK
+        let data_length = data_length as usize;
+        if data_length % WASM_PAGE_SIZE != 0 {
+            update_last_error(CApiError {
+                msg: "data_length is not a multiple of the wasm page size".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let max = if max_pages.has_some {
+            Some(Pages(max_pages.some))
+        } else {
+            None
+        };
+        let desc = match MemoryDescriptor::new(Bytes(data_length).into(), max, false) {
+            Ok(desc) => desc,
+            Err(error) => {
+                update_last_error(CApiError {
+                    msg: error.to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        let backing = ExternalBuffer {
+            data: buffer,
+            data_length,
+            grow,
+            user_data,
+        };
+        match Memory::with_backing(desc, backing) {
+            Ok(new_memory) => {
+                *memory = Box::into_raw(Box::new(new_memory)) as *mut wasmer_memory_t;
+                wasmer_result_t::WASMER_OK
+            }
+            Err(error) => {
+                update_last_error(error);
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
 }
 
 /// Grows a Memory by the given number of pages.
@@ -62,48 +192,108 @@ pub unsafe extern "C" fn wasmer_memory_new(
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_memory_grow(memory: *mut wasmer_memory_t, delta: u32) -> wasmer_result_t {
-    let memory = unsafe { &*(memory as *mut Memory) };
-    let delta_result = memory.grow(Pages(delta));
-    match delta_result {
-        Ok(_) => wasmer_result_t::WASMER_OK,
-        Err(grow_error) => {
-            update_last_error(grow_error);
-            wasmer_result_t::WASMER_ERROR
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let memory = unsafe { &*(memory as *mut Memory) };
+        let delta_result = memory.grow(Pages(delta));
+        match delta_result {
+            Ok(_) => wasmer_result_t::WASMER_OK,
+            Err(grow_error) => {
+                update_last_error(grow_error);
+                wasmer_result_t::WASMER_ERROR
+            }
         }
-    }
+    })
 }
 
 /// Returns the current length in pages of the given memory
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_memory_length(memory: *const wasmer_memory_t) -> u32 {
-    let memory = unsafe { &*(memory as *const Memory) };
-    let Pages(len) = memory.size();
-    len
+    catch_panic(0, || {
+        let memory = unsafe { &*(memory as *const Memory) };
+        let Pages(len) = memory.size();
+        len
+    })
 }
 
 /// Gets the start pointer to the bytes within a Memory
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_memory_data(mem: *const wasmer_memory_t) -> *mut u8 {
-    let memory = unsafe { &*(mem as *const Memory) };
-    memory.view::<u8>()[..].as_ptr() as *mut Cell<u8> as *mut u8
+    catch_panic(ptr::null_mut(), || {
+        let memory = unsafe { &*(mem as *const Memory) };
+        memory.view::<u8>()[..].as_ptr() as *mut Cell<u8> as *mut u8
+    })
 }
 
 /// Gets the size in bytes of a Memory
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_memory_data_length(mem: *mut wasmer_memory_t) -> u32 {
-    let memory = mem as *mut Memory;
-    let Bytes(len) = unsafe { (*memory).size().bytes() };
-    len as u32
+    catch_panic(0, || {
+        let memory = mem as *mut Memory;
+        let Bytes(len) = unsafe { (*memory).size().bytes() };
+        len as u32
+    })
+}
+
+/// Opaque user data passed back to a grow callback.
+struct GrowCallbackData(*mut c_void);
+
+// Manually implemented because the user data is owned by the caller, who is
+// responsible for making it safe to use from whichever thread grows the memory.
+unsafe impl Send for GrowCallbackData {}
+unsafe impl Sync for GrowCallbackData {}
+
+/// Registers a callback invoked after every successful grow of the given
+/// Memory, whether the grow was requested by the host or by the guest.
+///
+/// Growing may move the memory, making pointers obtained from
+/// `wasmer_memory_data` stale. The callback receives the new data pointer,
+/// the new size in bytes, which is 4GiB for a memory of 65536 pages, and the
+/// given `user_data`.
+///
+/// The callback must not call back into the same Memory.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_memory_set_grow_callback(
+    memory: *mut wasmer_memory_t,
+    callback: extern "C" fn(data: *mut u8, data_length: u64, user_data: *mut c_void),
+    user_data: *mut c_void,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if memory.is_null() {
+            update_last_error(CApiError {
+                msg: "memory ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let memory = &*(memory as *mut Memory);
+        let user_data = GrowCallbackData(user_data);
+        memory.subscribe_grow(move |base, pages| {
+            catch_panic((), || {
+                let Bytes(len) = pages.bytes();
+                callback(base, len as u64, user_data.0);
+            })
+        });
+
+        wasmer_result_t::WASMER_OK
+    })
 }
 
 /// Frees memory for the given Memory
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_memory_destroy(memory: *mut wasmer_memory_t) {
-    if !memory.is_null() {
-        unsafe { Box::from_raw(memory as *mut Memory) };
-    }
+    catch_panic((), || {
+        if !memory.is_null() {
+            unsafe { Box::from_raw(memory as *mut Memory) };
+        }
+    })
 }
@@ -1,16 +1,29 @@
 //! Compile, validate, instantiate, serialize, and destroy modules.
 
 use crate::{
-    error::{update_last_error, CApiError},
+    error::{catch_panic, update_last_error, CApiError},
     export::wasmer_import_export_kind,
     import::wasmer_import_t,
     instance::wasmer_instance_t,
     wasmer_byte_array, wasmer_result_t,
 };
-use libc::c_int;
-use std::{collections::HashMap, slice};
-use wasmer_runtime::{compile, default_compiler, Global, ImportObject, Memory, Module, Table};
-use wasmer_runtime_core::{cache::Artifact, export::Export, import::Namespace, load_cache_with};
+use libc::{c_char, c_int, c_void};
+use std::{
+    cmp,
+    collections::HashMap,
+    ffi::CStr,
+    fs::File,
+    io::{self, Write},
+    ptr, slice,
+};
+use wasmer_runtime::{
+    cache::{load_artifact, store_shared},
+    compile, compile_with_config, default_compiler, CompilerConfig, Global, ImportObject, Instance,
+    Memory, Module, Table,
+};
+use wasmer_runtime_core::{
+    backend::Features, cache::Artifact, export::Export, import::Namespace, load_cache_with,
+};
 
 #[repr(C)]
 pub struct wasmer_module_t;
@@ -18,6 +31,29 @@ pub struct wasmer_module_t;
 #[repr(C)]
 pub struct wasmer_serialized_module_t;
 
+/// The WebAssembly features a module is compiled or validated with, see
+/// `wasmer_features_new`.
+#[repr(C)]
+pub struct wasmer_features_t;
+
+/// A feature of a `wasmer_features_t`.
+#[allow(non_camel_case_types)]
+#[repr(u32)]
+#[derive(Clone)]
+pub enum wasmer_feature_t {
+    /// The SIMD proposal.
+    WASMER_FEATURE_SIMD,
+    /// The threads proposal.
+    WASMER_FEATURE_THREADS,
+    /// The bulk memory proposal. No backend compiles its instructions yet,
+    /// so modules using them only pass validation.
+    WASMER_FEATURE_BULK_MEMORY,
+    /// The multi-value proposal.
+    WASMER_FEATURE_MULTI_VALUE,
+    /// Rejects modules using floating point types or instructions.
+    WASMER_FEATURE_DENY_FLOATS,
+}
+
 /// Creates a new Module from the given wasm bytes.
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
@@ -31,29 +67,164 @@ pub unsafe extern "C" fn wasmer_compile(
     wasm_bytes: *mut u8,
     wasm_bytes_len: u32,
 ) -> wasmer_result_t {
-    let bytes: &[u8] = slice::from_raw_parts_mut(wasm_bytes, wasm_bytes_len as usize);
-    let result = compile(bytes);
-    let new_module = match result {
-        Ok(instance) => instance,
-        Err(error) => {
-            update_last_error(error);
-            return wasmer_result_t::WASMER_ERROR;
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let bytes: &[u8] = slice::from_raw_parts_mut(wasm_bytes, wasm_bytes_len as usize);
+        let result = compile(bytes);
+        let new_module = match result {
+            Ok(instance) => instance,
+            Err(error) => {
+                update_last_error(error);
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        *module = Box::into_raw(Box::new(new_module)) as *mut wasmer_module_t;
+        wasmer_result_t::WASMER_OK
+    })
+}
+
+/// Creates a new Module from the given wasm bytes, with the given features.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message, which names the feature
+/// the module needs when it isn't enabled.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_compile_with_features(
+    module: *mut *mut wasmer_module_t,
+    wasm_bytes: *mut u8,
+    wasm_bytes_len: u32,
+    features: *const wasmer_features_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let features = match (features as *const Features).as_ref() {
+            Some(features) => features.clone(),
+            None => {
+                update_last_error(CApiError {
+                    msg: "features is null".to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        let bytes: &[u8] = slice::from_raw_parts_mut(wasm_bytes, wasm_bytes_len as usize);
+        let result = compile_with_config(
+            bytes,
+            CompilerConfig {
+                features,
+                ..Default::default()
+            },
+        );
+        let new_module = match result {
+            Ok(instance) => instance,
+            Err(error) => {
+                update_last_error(error);
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        *module = Box::into_raw(Box::new(new_module)) as *mut wasmer_module_t;
+        wasmer_result_t::WASMER_OK
+    })
+}
+
+/// Creates a new set of features, with none of them enabled.
+///
+/// The caller owns the object and should call `wasmer_features_destroy` to free it.
+#[no_mangle]
+pub extern "C" fn wasmer_features_new() -> *mut wasmer_features_t {
+    catch_panic(ptr::null_mut(), || {
+        Box::into_raw(Box::new(Features::default())) as *mut wasmer_features_t
+    })
+}
+
+/// Enables or disables a feature.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_features_set(
+    features: *mut wasmer_features_t,
+    feature: wasmer_feature_t,
+    enabled: bool,
+) {
+    catch_panic((), || {
+        let features = match (features as *mut Features).as_mut() {
+            Some(features) => features,
+            None => return,
+        };
+        match feature {
+            wasmer_feature_t::WASMER_FEATURE_SIMD => features.simd = enabled,
+            wasmer_feature_t::WASMER_FEATURE_THREADS => features.threads = enabled,
+            wasmer_feature_t::WASMER_FEATURE_BULK_MEMORY => features.bulk_memory = enabled,
+            wasmer_feature_t::WASMER_FEATURE_MULTI_VALUE => features.multi_value = enabled,
+            wasmer_feature_t::WASMER_FEATURE_DENY_FLOATS => features.deny_floats = enabled,
         }
-    };
-    *module = Box::into_raw(Box::new(new_module)) as *mut wasmer_module_t;
-    wasmer_result_t::WASMER_OK
+    })
+}
+
+/// Frees memory for the given features
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_features_destroy(features: *mut wasmer_features_t) {
+    catch_panic((), || {
+        if !features.is_null() {
+            unsafe { Box::from_raw(features as *mut Features) };
+        }
+    })
 }
 
 /// Returns true for valid wasm bytes and false for invalid bytes
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_validate(wasm_bytes: *const u8, wasm_bytes_len: u32) -> bool {
-    if wasm_bytes.is_null() {
-        return false;
-    }
-    let bytes: &[u8] = slice::from_raw_parts(wasm_bytes, wasm_bytes_len as usize);
+    catch_panic(false, || {
+        if wasm_bytes.is_null() {
+            return false;
+        }
+        let bytes: &[u8] = slice::from_raw_parts(wasm_bytes, wasm_bytes_len as usize);
 
-    wasmer_runtime_core::validate(bytes)
+        wasmer_runtime_core::validate(bytes)
+    })
+}
+
+/// Validates the given wasm bytes with the given features.
+///
+/// Returns `wasmer_result_t::WASMER_OK` if the bytes are valid.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` otherwise. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message, which names the feature
+/// the module needs when it isn't enabled.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_validate_with_features(
+    wasm_bytes: *const u8,
+    wasm_bytes_len: u32,
+    features: *const wasmer_features_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let features = match (features as *const Features).as_ref() {
+            Some(features) => features.clone(),
+            None => {
+                update_last_error(CApiError {
+                    msg: "features is null".to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        if wasm_bytes.is_null() {
+            update_last_error(CApiError {
+                msg: "wasm_bytes is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        let bytes: &[u8] = slice::from_raw_parts(wasm_bytes, wasm_bytes_len as usize);
+
+        match wasmer_runtime_core::validate_and_report_errors_with_features(bytes, features) {
+            Ok(()) => wasmer_result_t::WASMER_OK,
+            Err(msg) => {
+                update_last_error(CApiError { msg });
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
 }
 
 /// Creates a new Instance from the given module and imports.
@@ -70,6 +241,68 @@ pub unsafe extern "C" fn wasmer_module_instantiate(
     imports: *mut wasmer_import_t,
     imports_len: c_int,
 ) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        instantiate(module, instance, imports, imports_len, Module::instantiate)
+    })
+}
+
+/// Creates a new Instance from the given module and imports, without running the
+/// start function of the module. Call `wasmer_instance_run_start` to run it, e.g.
+/// after setting the globals of the instance.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_instantiate_unstarted(
+    module: *const wasmer_module_t,
+    instance: *mut *mut wasmer_instance_t,
+    imports: *mut wasmer_import_t,
+    imports_len: c_int,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        instantiate(
+            module,
+            instance,
+            imports,
+            imports_len,
+            Module::instantiate_unstarted,
+        )
+    })
+}
+
+#[allow(clippy::cast_ptr_alignment)]
+unsafe fn instantiate(
+    module: *const wasmer_module_t,
+    instance: *mut *mut wasmer_instance_t,
+    imports: *mut wasmer_import_t,
+    imports_len: c_int,
+    instantiate_with: fn(&Module, &ImportObject) -> wasmer_runtime_core::error::Result<Instance>,
+) -> wasmer_result_t {
+    let import_object = match import_object(imports, imports_len) {
+        Some(import_object) => import_object,
+        None => return wasmer_result_t::WASMER_ERROR,
+    };
+
+    let module = &*(module as *const Module);
+    let new_instance = match instantiate_with(module, &import_object) {
+        Ok(instance) => instance,
+        Err(error) => {
+            update_last_error(error);
+            return wasmer_result_t::WASMER_ERROR;
+        }
+    };
+
+    *instance = Box::into_raw(Box::new(new_instance)) as *mut wasmer_instance_t;
+    wasmer_result_t::WASMER_OK
+}
+
+/// Builds an import object from the given imports, or updates the last error and
+/// returns `None` if a name isn't valid UTF-8.
+#[allow(clippy::cast_ptr_alignment)]
+unsafe fn import_object(imports: *mut wasmer_import_t, imports_len: c_int) -> Option<ImportObject> {
     let imports: &[wasmer_import_t] = slice::from_raw_parts(imports, imports_len as usize);
     let mut import_object = ImportObject::new();
     let mut namespaces = HashMap::new();
@@ -84,7 +317,7 @@ pub unsafe extern "C" fn wasmer_module_instantiate(
             update_last_error(CApiError {
                 msg: "error converting module name to string".to_string(),
             });
-            return wasmer_result_t::WASMER_ERROR;
+            return None;
         };
         let import_name = slice::from_raw_parts(
             import.import_name.bytes,
@@ -96,7 +329,7 @@ pub unsafe extern "C" fn wasmer_module_instantiate(
             update_last_error(CApiError {
                 msg: "error converting import_name to string".to_string(),
             });
-            return wasmer_result_t::WASMER_ERROR;
+            return None;
         };
 
         let namespace = namespaces.entry(module_name).or_insert_with(Namespace::new);
@@ -124,18 +357,7 @@ pub unsafe extern "C" fn wasmer_module_instantiate(
     for (module_name, namespace) in namespaces.into_iter() {
         import_object.register(module_name, namespace);
     }
-
-    let module = &*(module as *const Module);
-    let new_instance = match module.instantiate(&import_object) {
-        Ok(instance) => instance,
-        Err(error) => {
-            update_last_error(error);
-            return wasmer_result_t::WASMER_ERROR;
-        }
-    };
-
-    *instance = Box::into_raw(Box::new(new_instance)) as *mut wasmer_instance_t;
-    wasmer_result_t::WASMER_OK
+    Some(import_object)
 }
 
 /// Serialize the given Module.
@@ -152,43 +374,250 @@ pub unsafe extern "C" fn wasmer_module_serialize(
     serialized_module: *mut *mut wasmer_serialized_module_t,
     module: *const wasmer_module_t,
 ) -> wasmer_result_t {
-    let module = &*(module as *const Module);
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let module = &*(module as *const Module);
 
-    match module.cache() {
-        Ok(artifact) => match artifact.serialize() {
-            Ok(serialized_artifact) => {
-                *serialized_module = Box::into_raw(Box::new(serialized_artifact)) as _;
+        match module.cache() {
+            Ok(artifact) => match artifact.serialize() {
+                Ok(serialized_artifact) => {
+                    *serialized_module = Box::into_raw(Box::new(serialized_artifact)) as _;
 
-                wasmer_result_t::WASMER_OK
-            }
+                    wasmer_result_t::WASMER_OK
+                }
+                Err(_) => {
+                    update_last_error(CApiError {
+                        msg: "Failed to serialize the module artifact".to_string(),
+                    });
+                    wasmer_result_t::WASMER_ERROR
+                }
+            },
             Err(_) => {
                 update_last_error(CApiError {
-                    msg: "Failed to serialize the module artifact".to_string(),
+                    msg: "Failed to serialize the module".to_string(),
                 });
                 wasmer_result_t::WASMER_ERROR
             }
-        },
-        Err(_) => {
+        }
+    })
+}
+
+/// Writes to the callback given to `wasmer_module_serialize_to_callback`.
+struct CallbackWriter {
+    callback: extern "C" fn(data: *const u8, data_length: u32, user_data: *mut c_void) -> bool,
+    user_data: *mut c_void,
+}
+
+impl Write for CallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len(), u32::max_value() as usize);
+        if (self.callback)(buf.as_ptr(), len as u32, self.user_data) {
+            Ok(len)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the write callback failed",
+            ))
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes the given Module in the shared artifact layout, writing it to `writer`
+/// as it goes.
+unsafe fn serialize_shared<W: Write>(
+    module: *const wasmer_module_t,
+    writer: &mut W,
+) -> wasmer_result_t {
+    let module = &*(module as *const Module);
+
+    match store_shared(module, writer) {
+        Ok(()) => wasmer_result_t::WASMER_OK,
+        Err(e) => {
             update_last_error(CApiError {
-                msg: "Failed to serialize the module".to_string(),
+                msg: format!("Failed to serialize the module: {:?}", e),
             });
             wasmer_result_t::WASMER_ERROR
         }
     }
 }
 
+/// Serializes the given Module, passing the artifact to `callback` piece by piece
+/// instead of building it in memory like `wasmer_module_serialize` does.
+///
+/// The artifact is written in the shared layout, where the compiled code is stored
+/// verbatim, so only the module metadata is buffered. `callback` receives each piece,
+/// its length in bytes, and the given `user_data`, and returns `false` to abort.
+///
+/// The shared layout needs position independent code, which only the Cranelift backend
+/// compiles; modules compiled otherwise can't be serialized this way.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_serialize_to_callback(
+    module: *const wasmer_module_t,
+    callback: extern "C" fn(data: *const u8, data_length: u32, user_data: *mut c_void) -> bool,
+    user_data: *mut c_void,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if module.is_null() {
+            update_last_error(CApiError {
+                msg: "module ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        serialize_shared(
+            module,
+            &mut CallbackWriter {
+                callback,
+                user_data,
+            },
+        )
+    })
+}
+
+/// Serializes the given Module to the file at `path`, which is created or truncated,
+/// like `wasmer_module_serialize_to_callback` does.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_serialize_to_path(
+    module: *const wasmer_module_t,
+    path: *const c_char,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if module.is_null() {
+            update_last_error(CApiError {
+                msg: "module ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if path.is_null() {
+            update_last_error(CApiError {
+                msg: "path ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(_) => {
+                update_last_error(CApiError {
+                    msg: "path is not valid UTF-8".to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+
+        let mut file = match File::create(path) {
+            Ok(file) => file,
+            Err(e) => {
+                update_last_error(e);
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+
+        serialize_shared(module, &mut file)
+    })
+}
+
+/// Gets the contents of the `index`th custom section named `name` of the
+/// given Module, in the order the sections appear in the module.
+///
+/// The bytes are owned by the module and live as long as it does. Custom
+/// sections are kept when a module is serialized, so this works the same for
+/// a deserialized module.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure, e.g. if the module
+/// has no more than `index` sections named `name`. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_custom_section(
+    module: *const wasmer_module_t,
+    name: *const c_char,
+    index: u32,
+    section: *mut wasmer_byte_array,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if module.is_null() {
+            update_last_error(CApiError {
+                msg: "module ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if name.is_null() {
+            update_last_error(CApiError {
+                msg: "name ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if section.is_null() {
+            update_last_error(CApiError {
+                msg: "section ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let name = match CStr::from_ptr(name).to_str() {
+            Ok(name) => name,
+            Err(_) => {
+                update_last_error(CApiError {
+                    msg: "name is not valid UTF-8".to_string(),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+
+        let module = &*(module as *const Module);
+        match module.custom_sections(name).get(index as usize) {
+            Some(bytes) => {
+                *section = wasmer_byte_array {
+                    bytes: bytes.as_ptr(),
+                    bytes_len: bytes.len() as u32,
+                };
+                wasmer_result_t::WASMER_OK
+            }
+            None => {
+                update_last_error(CApiError {
+                    msg: format!(
+                        "the module has no custom section `{}` at index {}",
+                        name, index
+                    ),
+                });
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
+}
+
 /// Get bytes of the serialized module.
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_serialized_module_bytes(
     serialized_module: *const wasmer_serialized_module_t,
 ) -> wasmer_byte_array {
-    let serialized_module = &*(serialized_module as *const &[u8]);
+    catch_panic(wasmer_byte_array::empty(), || {
+        let serialized_module = &*(serialized_module as *const &[u8]);
 
-    wasmer_byte_array {
-        bytes: serialized_module.as_ptr(),
-        bytes_len: serialized_module.len() as u32,
-    }
+        wasmer_byte_array {
+            bytes: serialized_module.as_ptr(),
+            bytes_len: serialized_module.len() as u32,
+        }
+    })
 }
 
 /// Transform a sequence of bytes into a serialized module.
@@ -206,20 +635,22 @@ pub unsafe extern "C" fn wasmer_serialized_module_from_bytes(
     serialized_module_bytes: *const u8,
     serialized_module_bytes_length: u32,
 ) -> wasmer_result_t {
-    if serialized_module.is_null() {
-        update_last_error(CApiError {
-            msg: "`serialized_module_bytes` pointer is null".to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    }
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if serialized_module.is_null() {
+            update_last_error(CApiError {
+                msg: "`serialized_module_bytes` pointer is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
 
-    let serialized_module_bytes: &[u8] = slice::from_raw_parts(
-        serialized_module_bytes,
-        serialized_module_bytes_length as usize,
-    );
+        let serialized_module_bytes: &[u8] = slice::from_raw_parts(
+            serialized_module_bytes,
+            serialized_module_bytes_length as usize,
+        );
 
-    *serialized_module = Box::into_raw(Box::new(serialized_module_bytes)) as _;
-    wasmer_result_t::WASMER_OK
+        *serialized_module = Box::into_raw(Box::new(serialized_module_bytes)) as _;
+        wasmer_result_t::WASMER_OK
+    })
 }
 
 /// Deserialize the given serialized module.
@@ -234,35 +665,93 @@ pub unsafe extern "C" fn wasmer_module_deserialize(
     module: *mut *mut wasmer_module_t,
     serialized_module: *const wasmer_serialized_module_t,
 ) -> wasmer_result_t {
-    if serialized_module.is_null() {
-        update_last_error(CApiError {
-            msg: "`serialized_module` pointer is null".to_string(),
-        });
-        return wasmer_result_t::WASMER_ERROR;
-    }
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if serialized_module.is_null() {
+            update_last_error(CApiError {
+                msg: "`serialized_module` pointer is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let serialized_module: &[u8] = &*(serialized_module as *const &[u8]);
 
-    let serialized_module: &[u8] = &*(serialized_module as *const &[u8]);
+        match Artifact::deserialize(serialized_module) {
+            Ok(artifact) => match load_cache_with(artifact, &default_compiler()) {
+                Ok(deserialized_module) => {
+                    *module = Box::into_raw(Box::new(deserialized_module)) as _;
+                    wasmer_result_t::WASMER_OK
+                }
+                Err(_) => {
+                    update_last_error(CApiError {
+                        msg: "Failed to compile the serialized module".to_string(),
+                    });
+                    wasmer_result_t::WASMER_ERROR
+                }
+            },
+            Err(_) => {
+                update_last_error(CApiError {
+                    msg: "Failed to deserialize the module".to_string(),
+                });
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
+}
+
+/// Deserializes a module from an artifact written by `wasmer_module_serialize_to_callback`
+/// or `wasmer_module_serialize_to_path`, using the compiled code in `bytes` in place
+/// instead of copying it.
+///
+/// `bytes` must start at a page boundary, which they do when the artifact file is
+/// mapped into memory. The code must be position independent. The protection of the
+/// code pages is changed and the table of addresses that follows them is written to,
+/// so the mapping must be writable and should be private (e.g.
+/// `PROT_READ | PROT_WRITE` with `MAP_PRIVATE`). The
+/// caller must keep `bytes` mapped until the module and its instances are destroyed.
+///
+/// This is only supported on Unix.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_deserialize_borrowed(
+    module: *mut *mut wasmer_module_t,
+    bytes: *mut u8,
+    bytes_len: u32,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if bytes.is_null() {
+            update_last_error(CApiError {
+                msg: "bytes ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+
+        let bytes: &mut [u8] = slice::from_raw_parts_mut(bytes, bytes_len as usize);
 
-    match Artifact::deserialize(serialized_module) {
-        Ok(artifact) => match load_cache_with(artifact, &default_compiler()) {
+        #[cfg(unix)]
+        let artifact = Artifact::from_shared_bytes(bytes);
+        #[cfg(not(unix))]
+        let artifact: Result<Artifact, _> = Err(wasmer_runtime_core::cache::Error::Unknown(
+            "borrowed artifacts aren't supported on this platform".to_string(),
+        ));
+
+        match artifact.and_then(|artifact| load_artifact(artifact)) {
             Ok(deserialized_module) => {
                 *module = Box::into_raw(Box::new(deserialized_module)) as _;
                 wasmer_result_t::WASMER_OK
             }
-            Err(_) => {
+            Err(e) => {
                 update_last_error(CApiError {
-                    msg: "Failed to compile the serialized module".to_string(),
+                    msg: format!("Failed to deserialize the module: {:?}", e),
                 });
                 wasmer_result_t::WASMER_ERROR
             }
-        },
-        Err(_) => {
-            update_last_error(CApiError {
-                msg: "Failed to deserialize the module".to_string(),
-            });
-            wasmer_result_t::WASMER_ERROR
         }
-    }
+    })
 }
 
 /// Frees memory for the given serialized Module.
@@ -271,16 +760,20 @@ pub unsafe extern "C" fn wasmer_module_deserialize(
 pub extern "C" fn wasmer_serialized_module_destroy(
     serialized_module: *mut wasmer_serialized_module_t,
 ) {
-    if !serialized_module.is_null() {
-        unsafe { Box::from_raw(serialized_module as *mut &[u8]) };
-    }
+    catch_panic((), || {
+        if !serialized_module.is_null() {
+            unsafe { Box::from_raw(serialized_module as *mut &[u8]) };
+        }
+    })
 }
 
 /// Frees memory for the given Module
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_module_destroy(module: *mut wasmer_module_t) {
-    if !module.is_null() {
-        unsafe { Box::from_raw(module as *mut Module) };
-    }
+    catch_panic((), || {
+        if !module.is_null() {
+            unsafe { Box::from_raw(module as *mut Module) };
+        }
+    })
 }
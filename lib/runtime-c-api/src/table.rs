@@ -1,13 +1,28 @@
-//! Create, grow, destroy tables of an instance.
+//! Create, grow, read, write, destroy tables of an instance.
 
-use crate::{error::update_last_error, wasmer_limits_t, wasmer_result_t};
-use wasmer_runtime::Table;
-use wasmer_runtime_core::types::{ElementType, TableDescriptor};
+use crate::{
+    error::{catch_panic, update_last_error, CApiError},
+    export::{wasmer_export_func_t, NamedExport},
+    import::wasmer_import_func_t,
+    instance::wasmer_instance_t,
+    wasmer_limits_t, wasmer_result_t,
+};
+use std::ptr;
+use wasmer_runtime::{Instance, Table};
+use wasmer_runtime_core::{
+    export::Export,
+    types::{ElementType, FuncRef, TableDescriptor},
+};
 
 #[repr(C)]
 #[derive(Clone)]
 pub struct wasmer_table_t;
 
+/// Opaque pointer to a `FuncRef`, a reference to a function stored in a table.
+#[repr(C)]
+#[derive(Clone)]
+pub struct wasmer_funcref_t;
+
 /// Creates a new Table for the given descriptor and initializes the given
 /// pointer to pointer to a pointer to the new Table.
 ///
@@ -22,26 +37,28 @@ pub unsafe extern "C" fn wasmer_table_new(
     table: *mut *mut wasmer_table_t,
     limits: wasmer_limits_t,
 ) -> wasmer_result_t {
-    let max = if limits.max.has_some {
-        Some(limits.max.some)
-    } else {
-        None
-    };
-    let desc = TableDescriptor {
-        element: ElementType::Anyfunc,
-        minimum: limits.min,
-        maximum: max,
-    };
-    let result = Table::new(desc);
-    let new_table = match result {
-        Ok(table) => table,
-        Err(error) => {
-            update_last_error(error);
-            return wasmer_result_t::WASMER_ERROR;
-        }
-    };
-    *table = Box::into_raw(Box::new(new_table)) as *mut wasmer_table_t;
-    wasmer_result_t::WASMER_OK
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let max = if limits.max.has_some {
+            Some(limits.max.some)
+        } else {
+            None
+        };
+        let desc = TableDescriptor {
+            element: ElementType::Anyfunc,
+            minimum: limits.min,
+            maximum: max,
+        };
+        let result = Table::new(desc);
+        let new_table = match result {
+            Ok(table) => table,
+            Err(error) => {
+                update_last_error(error);
+                return wasmer_result_t::WASMER_ERROR;
+            }
+        };
+        *table = Box::into_raw(Box::new(new_table)) as *mut wasmer_table_t;
+        wasmer_result_t::WASMER_OK
+    })
 }
 
 /// Grows a Table by the given number of elements.
@@ -53,30 +70,187 @@ pub unsafe extern "C" fn wasmer_table_new(
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_table_grow(table: *mut wasmer_table_t, delta: u32) -> wasmer_result_t {
-    let table = unsafe { &*(table as *mut Table) };
-    let delta_result = table.grow(delta);
-    match delta_result {
-        Ok(_) => wasmer_result_t::WASMER_OK,
-        Err(grow_error) => {
-            update_last_error(grow_error);
-            wasmer_result_t::WASMER_ERROR
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        let table = unsafe { &*(table as *mut Table) };
+        let delta_result = table.grow(delta);
+        match delta_result {
+            Ok(_) => wasmer_result_t::WASMER_OK,
+            Err(grow_error) => {
+                update_last_error(grow_error);
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
+}
+
+/// Gets the element at `index` of the given Table, and stores a new function
+/// reference to it in `func_ref`. The reference is null if the element is empty.
+///
+/// The caller owns the object and should call `wasmer_funcref_destroy` to free it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_get(
+    table: *const wasmer_table_t,
+    index: u32,
+    func_ref: *mut *mut wasmer_funcref_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if table.is_null() {
+            update_last_error(CApiError {
+                msg: "table ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        let table = &*(table as *const Table);
+        match table.get(index) {
+            Some(element) => {
+                *func_ref = Box::into_raw(Box::new(element)) as *mut wasmer_funcref_t;
+                wasmer_result_t::WASMER_OK
+            }
+            None => {
+                update_last_error(CApiError {
+                    msg: format!("table index out of bounds: {}", index),
+                });
+                wasmer_result_t::WASMER_ERROR
+            }
+        }
+    })
+}
+
+/// Sets the element at `index` of the given Table to `func_ref`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_set(
+    table: *const wasmer_table_t,
+    index: u32,
+    func_ref: *const wasmer_funcref_t,
+) -> wasmer_result_t {
+    catch_panic(wasmer_result_t::WASMER_ERROR, || {
+        if table.is_null() {
+            update_last_error(CApiError {
+                msg: "table ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        if func_ref.is_null() {
+            update_last_error(CApiError {
+                msg: "func_ref ptr is null".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+        let table = &*(table as *const Table);
+        let func_ref = *(func_ref as *const FuncRef);
+        match table.set(index, func_ref) {
+            Ok(()) => wasmer_result_t::WASMER_OK,
+            Err(()) => {
+                update_last_error(CApiError {
+                    msg: format!("table index out of bounds: {}", index),
+                });
+                wasmer_result_t::WASMER_ERROR
+            }
         }
-    }
+    })
 }
 
 /// Returns the current length of the given Table
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_table_length(table: *mut wasmer_table_t) -> u32 {
-    let table = unsafe { &*(table as *mut Table) };
-    table.size()
+    catch_panic(0, || {
+        let table = unsafe { &*(table as *mut Table) };
+        table.size()
+    })
 }
 
 /// Frees memory for the given Table
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
 pub extern "C" fn wasmer_table_destroy(table: *mut wasmer_table_t) {
-    if !table.is_null() {
-        unsafe { Box::from_raw(table as *mut Table) };
-    }
+    catch_panic((), || {
+        if !table.is_null() {
+            unsafe { Box::from_raw(table as *mut Table) };
+        }
+    })
+}
+
+/// Creates a function reference to a host function, so it can be stored in a table
+/// with `wasmer_table_set`. When called through the table, the host function gets the
+/// context of `instance`, like the imports of that instance.
+///
+/// The caller owns the object and should call `wasmer_funcref_destroy` to free it.
+/// The host function and the instance must outlive any table it is stored in.
+///
+/// Returns a null pointer if `instance` or `func` is null.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_funcref_from_import_func(
+    instance: *mut wasmer_instance_t,
+    func: *const wasmer_import_func_t,
+) -> *mut wasmer_funcref_t {
+    catch_panic(ptr::null_mut(), || {
+        if instance.is_null() || func.is_null() {
+            return std::ptr::null_mut();
+        }
+        let instance = &mut *(instance as *mut Instance);
+        let export = &*(func as *const Export);
+        match FuncRef::from_host_func(export, instance.context_mut()) {
+            Some(func_ref) => Box::into_raw(Box::new(func_ref)) as *mut wasmer_funcref_t,
+            None => std::ptr::null_mut(),
+        }
+    })
+}
+
+/// Creates a function reference to an exported function, so it can be stored in a
+/// table with `wasmer_table_set`.
+///
+/// The caller owns the object and should call `wasmer_funcref_destroy` to free it.
+/// The instance exporting the function must outlive any table it is stored in.
+///
+/// Returns a null pointer if `func` is null.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_funcref_from_export_func(
+    func: *const wasmer_export_func_t,
+) -> *mut wasmer_funcref_t {
+    catch_panic(ptr::null_mut(), || {
+        if func.is_null() {
+            return std::ptr::null_mut();
+        }
+        let named_export = &*(func as *const NamedExport);
+        match FuncRef::from_export(&named_export.export) {
+            Some(func_ref) => Box::into_raw(Box::new(func_ref)) as *mut wasmer_funcref_t,
+            None => std::ptr::null_mut(),
+        }
+    })
+}
+
+/// Returns true if the given function reference is null, i.e. it refers to an
+/// empty table element.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_funcref_is_null(func_ref: *const wasmer_funcref_t) -> bool {
+    catch_panic(true, || {
+        func_ref.is_null() || (&*(func_ref as *const FuncRef)).is_null()
+    })
+}
+
+/// Frees memory for the given function reference
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub extern "C" fn wasmer_funcref_destroy(func_ref: *mut wasmer_funcref_t) {
+    catch_panic((), || {
+        if !func_ref.is_null() {
+            unsafe { Box::from_raw(func_ref as *mut FuncRef) };
+        }
+    })
 }
@@ -11,6 +11,7 @@ pub enum wasmer_value_tag {
     WASM_I64,
     WASM_F32,
     WASM_F64,
+    WASM_V128,
 }
 
 #[repr(C)]
@@ -21,6 +22,8 @@ pub union wasmer_value {
     pub I64: i64,
     pub F32: f32,
     pub F64: f64,
+    /// The bytes of a `v128`, in little-endian order.
+    pub V128: [u8; 16],
 }
 
 #[repr(C)]
@@ -51,6 +54,10 @@ impl From<wasmer_value_t> for Value {
                     tag: wasmer_value_tag::WASM_F64,
                     value: wasmer_value { F64 },
                 } => Value::F64(F64),
+                wasmer_value_t {
+                    tag: wasmer_value_tag::WASM_V128,
+                    value: wasmer_value { V128 },
+                } => Value::V128(u128::from_le_bytes(V128)),
                 _ => unreachable!("unknown WASM type"),
             }
         }
@@ -76,21 +83,19 @@ impl From<Value> for wasmer_value_t {
                 tag: wasmer_value_tag::WASM_F64,
                 value: wasmer_value { F64: x },
             },
-            Value::V128(_) => unimplemented!("V128 not supported in C API"),
+            Value::V128(x) => wasmer_value_t {
+                tag: wasmer_value_tag::WASM_V128,
+                value: wasmer_value {
+                    V128: x.to_le_bytes(),
+                },
+            },
         }
     }
 }
 
 impl From<Type> for wasmer_value_tag {
     fn from(ty: Type) -> Self {
-        #[allow(unreachable_patterns)]
-        match ty {
-            Type::I32 => wasmer_value_tag::WASM_I32,
-            Type::I64 => wasmer_value_tag::WASM_I64,
-            Type::F32 => wasmer_value_tag::WASM_F32,
-            Type::F64 => wasmer_value_tag::WASM_F64,
-            Type::V128 => unreachable!("V128 not supported in C API"),
-        }
+        wasmer_value_tag::from(&ty)
     }
 }
 
@@ -102,6 +107,7 @@ impl From<wasmer_value_tag> for Type {
             wasmer_value_tag::WASM_I64 => Type::I64,
             wasmer_value_tag::WASM_F32 => Type::F32,
             wasmer_value_tag::WASM_F64 => Type::F64,
+            wasmer_value_tag::WASM_V128 => Type::V128,
             _ => unreachable!("unknown WASM type"),
         }
     }
@@ -114,7 +120,7 @@ impl From<&wasmer_runtime::wasm::Type> for wasmer_value_tag {
             Type::I64 => wasmer_value_tag::WASM_I64,
             Type::F32 => wasmer_value_tag::WASM_F32,
             Type::F64 => wasmer_value_tag::WASM_F64,
-            Type::V128 => unimplemented!("V128 not supported in C API"),
+            Type::V128 => wasmer_value_tag::WASM_V128,
         }
     }
 }
@@ -0,0 +1,64 @@
+//! Query the version of this library, and the optional features it was built with.
+
+use crate::error::catch_panic;
+use libc::c_char;
+use std::{ffi::CStr, ptr};
+
+/// The version of this library, with a trailing null.
+const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// Gets the major version of this library, e.g. `0` for `0.12.0`.
+#[no_mangle]
+pub extern "C" fn wasmer_version_major() -> u8 {
+    catch_panic(0, || env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0))
+}
+
+/// Gets the minor version of this library, e.g. `12` for `0.12.0`.
+#[no_mangle]
+pub extern "C" fn wasmer_version_minor() -> u8 {
+    catch_panic(0, || env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0))
+}
+
+/// Gets the patch version of this library, e.g. `0` for `0.12.0`.
+#[no_mangle]
+pub extern "C" fn wasmer_version_patch() -> u8 {
+    catch_panic(0, || env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0))
+}
+
+/// Gets the version of this library as a null-terminated string, e.g. `"0.12.0"`.
+///
+/// The string is static, and must not be freed.
+#[no_mangle]
+pub extern "C" fn wasmer_version_string() -> *const c_char {
+    catch_panic(ptr::null(), || VERSION.as_ptr() as *const c_char)
+}
+
+/// Returns true if this library was built with the optional feature `name`, which is
+/// one of:
+///
+/// * `"cranelift-backend"`, `"singlepass-backend"` and `"llvm-backend"`, the backends
+///   compiling modules, see `wasmer_config_set_backend`,
+/// * `"wasi"` and `"emscripten"`, the import objects of the `wasmer_wasi_*` and
+///   `wasmer_emscripten_*` functions,
+/// * `"simd"` and `"threads"`, the WebAssembly proposals only the LLVM backend compiles,
+/// * `"trampolines"`, the `wasmer_trampoline_*` functions.
+///
+/// Returns false for any other name, or if `name` is null.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_has_feature(name: *const c_char) -> bool {
+    catch_panic(false, || {
+        if name.is_null() {
+            return false;
+        }
+        match CStr::from_ptr(name).to_bytes() {
+            b"cranelift-backend" => cfg!(feature = "cranelift-backend"),
+            b"singlepass-backend" => cfg!(feature = "singlepass-backend"),
+            b"llvm-backend" => cfg!(feature = "llvm-backend"),
+            b"wasi" => cfg!(feature = "wasi"),
+            b"emscripten" => cfg!(feature = "emscripten"),
+            b"simd" | b"threads" => cfg!(feature = "llvm-backend"),
+            b"trampolines" => cfg!(all(not(target_family = "windows"), target_arch = "x86_64")),
+            _ => false,
+        }
+    })
+}
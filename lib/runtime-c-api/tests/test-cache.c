@@ -0,0 +1,77 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+
+int call_sum(wasmer_module_t *module, int32_t a, int32_t b)
+{
+    wasmer_import_t imports[] = {};
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t instantiate_result = wasmer_module_instantiate(module, &instance, imports, 0);
+    assert(instantiate_result == WASMER_OK);
+
+    wasmer_value_t params[2];
+    params[0].tag = WASM_I32;
+    params[0].value.I32 = a;
+    params[1].tag = WASM_I32;
+    params[1].value.I32 = b;
+
+    wasmer_value_t results[1];
+
+    wasmer_result_t call_result = wasmer_instance_call(instance, "sum", params, 2, results, 1);
+    assert(call_result == WASMER_OK);
+
+    wasmer_instance_destroy(instance);
+    return results[0].value.I32;
+}
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/sum.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_cache_t *cache = wasmer_cache_new("test-cache-dir");
+    assert(cache != NULL);
+
+    printf("Compile the module, storing it in the cache\n");
+    wasmer_module_t *module_one = NULL;
+    wasmer_result_t compile_result = wasmer_cache_load_or_compile(cache, bytes, len, &module_one);
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+    assert(call_sum(module_one, 7, 8) == 15);
+
+    printf("Load the module from the cache\n");
+    wasmer_module_t *module_two = NULL;
+    wasmer_result_t load_result = wasmer_cache_load_or_compile(cache, bytes, len, &module_two);
+    printf("Load result: %d\n", load_result);
+    assert(load_result == WASMER_OK);
+    assert(call_sum(module_two, 1, 2) == 3);
+
+    printf("Store the module under another key\n");
+    const uint8_t key[] = "sum";
+    wasmer_result_t store_result = wasmer_cache_store(cache, key, sizeof(key) - 1, module_one);
+    printf("Store result: %d\n", store_result);
+    assert(store_result == WASMER_OK);
+
+    printf("Fail to compile invalid bytes\n");
+    uint8_t invalid_bytes[] = {0, 1, 2, 3};
+    wasmer_module_t *module_three = NULL;
+    wasmer_result_t invalid_result = wasmer_cache_load_or_compile(cache, invalid_bytes, sizeof(invalid_bytes), &module_three);
+    assert(invalid_result == WASMER_ERROR);
+    assert(wasmer_last_error_length() > 0);
+
+    printf("Destroy modules\n");
+    wasmer_module_destroy(module_one);
+    wasmer_module_destroy(module_two);
+
+    printf("Destroy cache\n");
+    wasmer_cache_destroy(cache);
+    free(bytes);
+    return 0;
+}
@@ -0,0 +1,77 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+int call_sum(wasmer_module_t *module, int32_t a, int32_t b)
+{
+    wasmer_import_t imports[] = {};
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t instantiate_result = wasmer_module_instantiate(module, &instance, imports, 0);
+    assert(instantiate_result == WASMER_OK);
+
+    wasmer_value_t params[2];
+    params[0].tag = WASM_I32;
+    params[0].value.I32 = a;
+    params[1].tag = WASM_I32;
+    params[1].value.I32 = b;
+
+    wasmer_value_t results[1];
+
+    wasmer_result_t call_result = wasmer_instance_call(instance, "sum", params, 2, results, 1);
+    assert(call_result == WASMER_OK);
+
+    wasmer_instance_destroy(instance);
+    return results[0].value.I32;
+}
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/sum.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    printf("Compile with the default configuration\n");
+    wasmer_config_t *config = wasmer_config_new();
+    wasmer_module_t *module_one = NULL;
+    wasmer_result_t compile_result = wasmer_compile_with_config(&module_one, bytes, len, config);
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+    assert(call_sum(module_one, 7, 8) == 15);
+
+    printf("Compile with dynamic memories and NaN canonicalization\n");
+    wasmer_config_set_memory_style(config, WASMER_MEMORY_STYLE_DYNAMIC);
+    wasmer_config_set_nan_canonicalization(config, true);
+    wasmer_module_t *module_two = NULL;
+    compile_result = wasmer_compile_with_config(&module_two, bytes, len, config);
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+    assert(call_sum(module_two, 1, 2) == 3);
+
+    printf("Fail to compile without a configuration\n");
+    wasmer_module_t *module_three = NULL;
+    compile_result = wasmer_compile_with_config(&module_three, bytes, len, NULL);
+    assert(compile_result == WASMER_ERROR);
+
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(0 == strcmp(error_str, "config is null"));
+    free(error_str);
+
+    printf("Destroy modules\n");
+    wasmer_module_destroy(module_one);
+    wasmer_module_destroy(module_two);
+
+    printf("Destroy config\n");
+    wasmer_config_destroy(config);
+    free(bytes);
+    return 0;
+}
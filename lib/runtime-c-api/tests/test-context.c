@@ -44,6 +44,13 @@ int32_t get_counter(wasmer_instance_context_t *ctx) {
     return data->value;
 }
 
+int destroyed_counters = 0;
+
+void destroy_counter(void *data) {
+    free(data);
+    destroyed_counters++;
+}
+
 counter_data *init_counter(int32_t value, int32_t amount) {
     counter_data* counter = malloc(sizeof(counter_data));
     counter->value = value;
@@ -126,10 +133,17 @@ int main()
     assert_counter(instance, 12);
     assert_counter(instance, 17);
 
+    // Replace the counter with one the instance owns
+    counter_data *owned_counter = init_counter(1, 3);
+    wasmer_instance_context_data_set_with_destructor(instance, owned_counter, destroy_counter);
+    assert_counter(instance, 4);
+    assert(destroyed_counters == 0);
+
     // Clear resources
     wasmer_import_func_destroy(inc_func);
     wasmer_import_func_destroy(get_func);
     wasmer_instance_destroy(instance);
+    assert(destroyed_counters == 1);
     free(counter);
     free(wasm_file.bytes);
 
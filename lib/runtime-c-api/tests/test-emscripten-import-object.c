@@ -6,6 +6,19 @@
 
 static bool host_print_called = false;
 
+// What the guest writes to its stdout
+static char captured_stdout[4096];
+static uint32_t captured_stdout_len = 0;
+
+void capture_stdout(const uint8_t *data, uint32_t data_length, void *user_data)
+{
+    assert(user_data == &captured_stdout);
+    uint32_t space = sizeof(captured_stdout) - 1 - captured_stdout_len;
+    uint32_t copied = data_length < space ? data_length : space;
+    memcpy(captured_stdout + captured_stdout_len, data, copied);
+    captured_stdout_len += copied;
+}
+
 // Host function that will be imported into the Web Assembly Instance
 void host_print(const wasmer_instance_context_t *ctx, int32_t ptr, int32_t len)
 {
@@ -66,6 +79,11 @@ int main()
     }
     assert(emscripten_globals);
 
+    // Capture what the guest prints instead of inheriting our stdout
+    wasmer_result_t capture_result =
+            wasmer_emscripten_set_stdout_callback(emscripten_globals, capture_stdout, &captured_stdout);
+    assert(capture_result == WASMER_OK);
+
     // Create the Emscripten import object
     wasmer_import_object_t *import_object =
             wasmer_emscripten_generate_import_object(emscripten_globals);
@@ -108,6 +126,9 @@ int main()
     printf("Main result:  %d\n", main_result);
     assert(main_result == WASMER_OK);
 
+    printf("Captured stdout: `%.*s`\n", captured_stdout_len, captured_stdout);
+    assert(captured_stdout_len > 0);
+
     wasmer_import_object_iter_t *func_iter = wasmer_import_object_iterate_functions(import_object);
 
     puts("Functions in import object:");
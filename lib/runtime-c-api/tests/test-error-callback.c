@@ -0,0 +1,54 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+typedef struct {
+    int count;
+    wasmer_error_code_t code;
+    char message[256];
+} errors_t;
+
+void on_error(wasmer_error_code_t code, const char *message, void *user_data)
+{
+    errors_t *errors = (errors_t *) user_data;
+    errors->count++;
+    errors->code = code;
+    strncpy(errors->message, message, sizeof(errors->message) - 1);
+    printf("Error callback: %d `%s`\n", code, message);
+}
+
+int main()
+{
+    errors_t errors = {0};
+    wasmer_set_error_callback(on_error, &errors);
+
+    printf("Fail to compile invalid bytes\n");
+    uint8_t invalid_bytes[] = {0, 1, 2, 3};
+    wasmer_module_t *module = NULL;
+    wasmer_result_t compile_result = wasmer_compile(&module, invalid_bytes, sizeof(invalid_bytes));
+    assert(compile_result == WASMER_ERROR);
+    assert(errors.count == 1);
+    assert(errors.code == WASMER_ERROR_CODE_COMPILE);
+
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    assert(0 == strcmp(error_str, errors.message));
+    free(error_str);
+
+    printf("Fail to call a function without an instance\n");
+    wasmer_result_t call_result = wasmer_instance_call(NULL, "sum", NULL, 0, NULL, 0);
+    assert(call_result == WASMER_ERROR);
+    assert(errors.count == 2);
+    assert(errors.code == WASMER_ERROR_CODE_OTHER);
+
+    printf("Remove the callback\n");
+    wasmer_set_error_callback(NULL, NULL);
+    compile_result = wasmer_compile(&module, invalid_bytes, sizeof(invalid_bytes));
+    assert(compile_result == WASMER_ERROR);
+    assert(errors.count == 2);
+
+    return 0;
+}
@@ -433,6 +433,33 @@ int main()
         }
     }
 
+    {
+        printf("\nCall the `sum` exported function through a handle\n");
+
+        wasmer_export_func_handle_t *handle = wasmer_instance_get_export_func(instance, "sum");
+        assert(handle != NULL);
+
+        for (int32_t i = 0; i < 1000; ++i) {
+            wasmer_value_t inputs[2];
+            inputs[0].tag = WASM_I32;
+            inputs[0].value.I32 = i;
+            inputs[1].tag = WASM_I32;
+            inputs[1].value.I32 = 1;
+
+            wasmer_value_t outputs[1];
+
+            wasmer_result_t call_result = wasmer_export_func_call_fast(handle, inputs, outputs);
+            assert(call_result == WASMER_OK);
+            assert(outputs[0].tag == WASM_I32);
+            assert(outputs[0].value.I32 == i + 1);
+        }
+
+        wasmer_export_func_handle_destroy(handle);
+
+        assert(wasmer_instance_get_export_func(instance, "does_not_exist") == NULL);
+        assert(wasmer_last_error_length() > 0);
+    }
+
     printf("\nDestroy instance\n");
 
     wasmer_instance_destroy(instance);
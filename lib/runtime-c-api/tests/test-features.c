@@ -0,0 +1,58 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+// (module (func (param f32)))
+uint8_t FLOAT_MODULE[] = {
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    0x01, 0x05, 0x01, 0x60, 0x01, 0x7d, 0x00,
+    0x03, 0x02, 0x01, 0x00,
+    0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+};
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/sum.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_features_t *features = wasmer_features_new();
+    wasmer_features_set(features, WASMER_FEATURE_DENY_FLOATS, true);
+
+    wasmer_result_t validate_result = wasmer_validate_with_features(bytes, len, features);
+    printf("Validate result: %d\n", validate_result);
+    assert(validate_result == WASMER_OK);
+
+    wasmer_module_t *module = NULL;
+    wasmer_result_t compile_result = wasmer_compile_with_features(&module, bytes, len, features);
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+    wasmer_module_destroy(module);
+
+    validate_result = wasmer_validate_with_features(FLOAT_MODULE, sizeof(FLOAT_MODULE), features);
+    printf("Validate result: %d\n", validate_result);
+    assert(validate_result == WASMER_ERROR);
+
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(strstr(error_str, "deny_floats") != NULL);
+    free(error_str);
+
+    wasmer_features_set(features, WASMER_FEATURE_DENY_FLOATS, false);
+    validate_result = wasmer_validate_with_features(FLOAT_MODULE, sizeof(FLOAT_MODULE), features);
+    printf("Validate result: %d\n", validate_result);
+    assert(validate_result == WASMER_OK);
+
+    wasmer_features_destroy(features);
+    free(bytes);
+    return 0;
+}
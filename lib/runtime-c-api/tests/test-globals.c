@@ -16,7 +16,8 @@ int main()
     wasmer_value_t val2;
     val2.tag = WASM_I32;
     val2.value.I32 = 14;
-    wasmer_global_set(global, val2);
+    wasmer_result_t set_result = wasmer_global_set(global, val2);
+    assert(set_result == WASMER_OK);
 
     wasmer_value_t new_get_val = wasmer_global_get(global);
     assert( new_get_val.value.I32 == 14);
@@ -25,6 +26,19 @@ int main()
     assert(desc.mutable_);
     assert(desc.kind == WASM_I32);
 
+    // Setting a value of the wrong type fails
+    wasmer_value_t val3;
+    val3.tag = WASM_I64;
+    val3.value.I64 = 21;
+    assert(wasmer_global_set(global, val3) == WASMER_ERROR);
+
+    // Setting an immutable global fails
+    wasmer_global_t *immutable_global = wasmer_global_new(val, false);
+    assert(wasmer_global_set(immutable_global, val2) == WASMER_ERROR);
+    wasmer_value_t immutable_get_val = wasmer_global_get(immutable_global);
+    assert(immutable_get_val.value.I32 == 7);
+
+    wasmer_global_destroy(immutable_global);
     wasmer_global_destroy(global);
     return 0;
 }
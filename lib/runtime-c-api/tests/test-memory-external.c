@@ -0,0 +1,70 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+#define PAGE_SIZE 65536
+#define GUARD_SIZE 4096
+
+uint8_t *grow_buffer(uint8_t *data, uint32_t data_length, void *user_data)
+{
+    int *grow_count = (int *) user_data;
+    (*grow_count)++;
+    printf("Grow the buffer to %d bytes\n", data_length);
+    return realloc(data, data_length + GUARD_SIZE);
+}
+
+int main()
+{
+    wasmer_limit_option_t max = { .has_some = true, .some = 2 };
+
+    printf("Create a memory over a buffer\n");
+    uint8_t *buffer = malloc(PAGE_SIZE + GUARD_SIZE);
+    memset(buffer, 0, PAGE_SIZE + GUARD_SIZE);
+    buffer[42] = 7;
+    int grow_count = 0;
+    wasmer_memory_t *memory = NULL;
+    wasmer_result_t memory_result = wasmer_memory_new_external(&memory, buffer, PAGE_SIZE, max, grow_buffer, &grow_count);
+    printf("Memory result: %d\n", memory_result);
+    assert(memory_result == WASMER_OK);
+    assert(wasmer_memory_length(memory) == 1);
+    assert(wasmer_memory_data(memory) == buffer);
+    assert(wasmer_memory_data(memory)[42] == 7);
+
+    printf("Grow the memory through the callback\n");
+    wasmer_result_t grow_result = wasmer_memory_grow(memory, 1);
+    assert(grow_result == WASMER_OK);
+    assert(grow_count == 1);
+    assert(wasmer_memory_length(memory) == 2);
+    assert(wasmer_memory_data_length(memory) == 2 * PAGE_SIZE);
+    assert(wasmer_memory_data(memory)[42] == 7);
+
+    printf("Fail to grow past the maximum\n");
+    grow_result = wasmer_memory_grow(memory, 1);
+    assert(grow_result == WASMER_ERROR);
+    assert(grow_count == 1);
+
+    buffer = wasmer_memory_data(memory);
+    wasmer_memory_destroy(memory);
+    free(buffer);
+
+    printf("Fail to grow a memory without a callback\n");
+    buffer = malloc(PAGE_SIZE + GUARD_SIZE);
+    wasmer_limit_option_t no_max = { .has_some = false };
+    memory_result = wasmer_memory_new_external(&memory, buffer, PAGE_SIZE, no_max, NULL, NULL);
+    assert(memory_result == WASMER_OK);
+    grow_result = wasmer_memory_grow(memory, 1);
+    assert(grow_result == WASMER_ERROR);
+    assert(wasmer_memory_length(memory) == 1);
+    wasmer_memory_destroy(memory);
+
+    printf("Fail to create a memory over a partial page\n");
+    wasmer_memory_t *partial_memory = NULL;
+    memory_result = wasmer_memory_new_external(&partial_memory, buffer, PAGE_SIZE - 1, no_max, NULL, NULL);
+    assert(memory_result == WASMER_ERROR);
+    assert(wasmer_last_error_length() > 0);
+    free(buffer);
+
+    return 0;
+}
@@ -4,6 +4,15 @@
 #include <stdint.h>
 #include <string.h>
 
+static uint64_t grown_data_length = 0;
+
+void on_grow(uint8_t *data, uint64_t data_length, void *user_data)
+{
+    assert(data != NULL);
+    assert(*(int *)user_data == 42);
+    grown_data_length = data_length;
+}
+
 int main()
 {
     wasmer_memory_t *memory = NULL;
@@ -21,8 +30,13 @@ int main()
     printf("Memory pages length:  %d\n", len);
     assert(len == 10);
 
+    int user_data = 42;
+    wasmer_result_t callback_result = wasmer_memory_set_grow_callback(memory, on_grow, &user_data);
+    assert(callback_result == WASMER_OK);
+
     wasmer_result_t grow_result = wasmer_memory_grow(memory, 2);
     assert(grow_result == WASMER_OK);
+    assert(grown_data_length == 12 * 65536);
 
     uint32_t new_len = wasmer_memory_length(memory);
     printf("Memory pages length:  %d\n", new_len);
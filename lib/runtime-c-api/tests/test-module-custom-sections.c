@@ -0,0 +1,77 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+// An empty module with the custom sections `notes` ("one"), `other` ("x")
+// and `notes` ("two!").
+uint8_t CUSTOM_SECTIONS_MODULE[] = {
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    0x00, 0x09, 0x05, 'n', 'o', 't', 'e', 's', 'o', 'n', 'e',
+    0x00, 0x07, 0x05, 'o', 't', 'h', 'e', 'r', 'x',
+    0x00, 0x0a, 0x05, 'n', 'o', 't', 'e', 's', 't', 'w', 'o', '!',
+};
+
+void check_custom_sections(wasmer_module_t *module)
+{
+    wasmer_byte_array section;
+
+    wasmer_result_t first_result = wasmer_module_custom_section(module, "notes", 0, &section);
+    assert(first_result == WASMER_OK);
+    assert(section.bytes_len == 3);
+    assert(memcmp(section.bytes, "one", 3) == 0);
+
+    wasmer_result_t second_result = wasmer_module_custom_section(module, "notes", 1, &section);
+    assert(second_result == WASMER_OK);
+    assert(section.bytes_len == 4);
+    assert(memcmp(section.bytes, "two!", 4) == 0);
+
+    wasmer_result_t other_result = wasmer_module_custom_section(module, "other", 0, &section);
+    assert(other_result == WASMER_OK);
+    assert(section.bytes_len == 1);
+    assert(section.bytes[0] == 'x');
+
+    wasmer_result_t past_end_result = wasmer_module_custom_section(module, "notes", 2, &section);
+    assert(past_end_result == WASMER_ERROR);
+
+    wasmer_result_t missing_result = wasmer_module_custom_section(module, "missing", 0, &section);
+    assert(missing_result == WASMER_ERROR);
+
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(0 == strcmp(error_str, "the module has no custom section `missing` at index 0"));
+    free(error_str);
+}
+
+int main()
+{
+    wasmer_module_t *module = NULL;
+    wasmer_result_t compile_result = wasmer_compile(&module, CUSTOM_SECTIONS_MODULE, sizeof(CUSTOM_SECTIONS_MODULE));
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    check_custom_sections(module);
+
+    wasmer_serialized_module_t *serialized_module = NULL;
+    wasmer_result_t serialize_result = wasmer_module_serialize(&serialized_module, module);
+    printf("Serialize result: %d\n", serialize_result);
+    assert(serialize_result == WASMER_OK);
+
+    wasmer_module_t *deserialized_module = NULL;
+    wasmer_result_t deserialize_result = wasmer_module_deserialize(&deserialized_module, serialized_module);
+    printf("Deserialize result: %d\n", deserialize_result);
+    assert(deserialize_result == WASMER_OK);
+
+    check_custom_sections(deserialized_module);
+
+    printf("Destroy the serialized module\n");
+    wasmer_serialized_module_destroy(serialized_module);
+
+    printf("Destroy modules\n");
+    wasmer_module_destroy(module);
+    wasmer_module_destroy(deserialized_module);
+    return 0;
+}
@@ -0,0 +1,85 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+int main()
+{
+    // Read the wasm file bytes
+    FILE *file = fopen("assets/sum.wasm", "r");
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    uint8_t *bytes = malloc(len);
+    fseek(file, 0, SEEK_SET);
+    fread(bytes, 1, len, file);
+    fclose(file);
+
+    wasmer_module_t *module_one = NULL;
+    wasmer_result_t compile_result = wasmer_compile(&module_one, bytes, len);
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    wasmer_result_t serialize_result = wasmer_module_serialize_to_path(module_one, "borrowed-sum.wasmer");
+    printf("Serialize result: %d\n", serialize_result);
+    assert(serialize_result == WASMER_OK);
+
+    // Map the artifact privately, as a language runtime shipping it would.
+    int fd = open("borrowed-sum.wasmer", O_RDONLY);
+    assert(fd >= 0);
+    struct stat artifact_stat;
+    assert(fstat(fd, &artifact_stat) == 0);
+    uint8_t *artifact = mmap(NULL, artifact_stat.st_size, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+    assert(artifact != MAP_FAILED);
+    close(fd);
+
+    wasmer_module_t *module_two = NULL;
+    wasmer_result_t deserialize_result = wasmer_module_deserialize_borrowed(&module_two, artifact, artifact_stat.st_size);
+    printf("Deserialize result: %d\n", deserialize_result);
+    assert(deserialize_result == WASMER_OK);
+
+    wasmer_import_t imports[] = {};
+    wasmer_instance_t *instance = NULL;
+    wasmer_result_t instantiate_result = wasmer_module_instantiate(module_two, &instance, imports, 0);
+    printf("Instantiate result: %d\n", instantiate_result);
+    assert(instantiate_result == WASMER_OK);
+
+    wasmer_value_t param_one;
+    param_one.tag = WASM_I32;
+    param_one.value.I32 = 7;
+    wasmer_value_t param_two;
+    param_two.tag = WASM_I32;
+    param_two.value.I32 = 8;
+    wasmer_value_t params[] = {param_one, param_two};
+
+    wasmer_value_t result_one;
+    wasmer_value_t results[] = {result_one};
+
+    wasmer_result_t call_result = wasmer_instance_call(instance, "sum", params, 2, results, 1);
+    printf("Call result:  %d\n", call_result);
+    printf("Result: %d\n", results[0].value.I32);
+    assert(results[0].value.I32 == 15);
+    assert(call_result == WASMER_OK);
+
+    // Artifacts in the regular layout can't be borrowed.
+    wasmer_module_t *module_three = NULL;
+    wasmer_result_t invalid_result = wasmer_module_deserialize_borrowed(&module_three, bytes, len);
+    assert(invalid_result == WASMER_ERROR);
+    assert(wasmer_last_error_length() > 0);
+
+    printf("Destroy instance\n");
+    wasmer_instance_destroy(instance);
+
+    printf("Destroy modules\n");
+    wasmer_module_destroy(module_two);
+    wasmer_module_destroy(module_one);
+
+    // The mapping must outlive the module borrowing it.
+    munmap(artifact, artifact_stat.st_size);
+    remove("borrowed-sum.wasmer");
+    free(bytes);
+    return 0;
+}
@@ -45,6 +45,11 @@ int main()
         assert(name_bytes.bytes[idx] == expected[idx]);
     }
 
+    printf("Get an export descriptor out of bounds\n");
+    wasmer_export_descriptor_t *missing_export = wasmer_export_descriptors_get(exports, 1);
+    assert(missing_export == NULL);
+    assert(wasmer_last_error_length() > 0);
+
     printf("Destroy module\n");
     wasmer_module_destroy(module);
     printf("Destroy exports\n");
@@ -0,0 +1,77 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+// A module whose start function increments a global, and which exports `get`
+// returning the global.
+uint8_t START_MODULE[] = {
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    0x01, 0x08, 0x02, 0x60, 0x00, 0x00, 0x60, 0x00, 0x01, 0x7f,
+    0x03, 0x03, 0x02, 0x00, 0x01,
+    0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x00, 0x0b,
+    0x07, 0x07, 0x01, 0x03, 'g', 'e', 't', 0x00, 0x01,
+    0x08, 0x01, 0x00,
+    0x0a, 0x10, 0x02,
+    0x09, 0x00, 0x23, 0x00, 0x41, 0x01, 0x6a, 0x24, 0x00, 0x0b,
+    0x04, 0x00, 0x23, 0x00, 0x0b,
+};
+
+int get(wasmer_instance_t *instance)
+{
+    wasmer_value_t result_one;
+    wasmer_value_t results[] = {result_one};
+    wasmer_result_t call_result = wasmer_instance_call(instance, "get", NULL, 0, results, 1);
+    assert(call_result == WASMER_OK);
+    return results[0].value.I32;
+}
+
+int main()
+{
+    wasmer_module_t *module = NULL;
+    wasmer_result_t compile_result = wasmer_compile(&module, START_MODULE, sizeof(START_MODULE));
+    printf("Compile result: %d\n", compile_result);
+    assert(compile_result == WASMER_OK);
+
+    wasmer_import_t imports[] = {};
+
+    wasmer_instance_t *unstarted = NULL;
+    wasmer_result_t unstarted_result = wasmer_module_instantiate_unstarted(module, &unstarted, imports, 0);
+    printf("Instantiate unstarted result: %d\n", unstarted_result);
+    assert(unstarted_result == WASMER_OK);
+    assert(get(unstarted) == 0);
+
+    wasmer_result_t start_result = wasmer_instance_run_start(unstarted);
+    printf("Run start result: %d\n", start_result);
+    assert(start_result == WASMER_OK);
+    assert(get(unstarted) == 1);
+
+    // The start function runs only once.
+    assert(wasmer_instance_run_start(unstarted) == WASMER_OK);
+    assert(get(unstarted) == 1);
+
+    wasmer_instance_t *started = NULL;
+    wasmer_result_t started_result = wasmer_module_instantiate(module, &started, imports, 0);
+    printf("Instantiate result: %d\n", started_result);
+    assert(started_result == WASMER_OK);
+    assert(get(started) == 1);
+    assert(wasmer_instance_run_start(started) == WASMER_OK);
+    assert(get(started) == 1);
+
+    assert(wasmer_instance_run_start(NULL) == WASMER_ERROR);
+
+    int error_len = wasmer_last_error_length();
+    char *error_str = malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    printf("Error str: `%s`\n", error_str);
+    assert(0 == strcmp(error_str, "instance ptr is null"));
+    free(error_str);
+
+    printf("Destroy instances\n");
+    wasmer_instance_destroy(unstarted);
+    wasmer_instance_destroy(started);
+    printf("Destroy module\n");
+    wasmer_module_destroy(module);
+    return 0;
+}
@@ -2,6 +2,26 @@
 #include "../wasmer.h"
 #include <assert.h>
 #include <stdint.h>
+#include <string.h>
+
+typedef struct {
+    uint8_t *bytes;
+    uint32_t len;
+} buffer_t;
+
+bool append_to_buffer(const uint8_t *data, uint32_t data_length, void *user_data)
+{
+    buffer_t *buffer = (buffer_t *) user_data;
+    buffer->bytes = realloc(buffer->bytes, buffer->len + data_length);
+    memcpy(buffer->bytes + buffer->len, data, data_length);
+    buffer->len += data_length;
+    return true;
+}
+
+bool fail_to_write(const uint8_t *data, uint32_t data_length, void *user_data)
+{
+    return false;
+}
 
 int main()
 {
@@ -79,6 +99,34 @@ int main()
     wasmer_result_t instantiate_result_two = wasmer_module_instantiate(module_three, &instance, imports, 0);
     assert(instantiate_result_two == WASMER_OK);
 
+    buffer_t streamed = { NULL, 0 };
+    wasmer_result_t stream_result = wasmer_module_serialize_to_callback(module_one, append_to_buffer, &streamed);
+    printf("Stream result: %d\n", stream_result);
+    assert(stream_result == WASMER_OK);
+    assert(streamed.len > 8);
+    assert(memcmp(streamed.bytes, "WASMERSH", 8) == 0);
+
+    wasmer_result_t path_result = wasmer_module_serialize_to_path(module_one, "serialized-sum.wasmer");
+    assert(path_result == WASMER_OK);
+
+    FILE *serialized_file = fopen("serialized-sum.wasmer", "r");
+    fseek(serialized_file, 0, SEEK_END);
+    long serialized_file_len = ftell(serialized_file);
+    uint8_t *serialized_file_bytes = malloc(serialized_file_len);
+    fseek(serialized_file, 0, SEEK_SET);
+    fread(serialized_file_bytes, 1, serialized_file_len, serialized_file);
+    fclose(serialized_file);
+    remove("serialized-sum.wasmer");
+
+    assert(serialized_file_len == streamed.len);
+    assert(memcmp(serialized_file_bytes, streamed.bytes, streamed.len) == 0);
+    free(serialized_file_bytes);
+    free(streamed.bytes);
+
+    wasmer_result_t failed_stream_result = wasmer_module_serialize_to_callback(module_one, fail_to_write, NULL);
+    assert(failed_stream_result == WASMER_ERROR);
+    assert(wasmer_last_error_length() > 0);
+
     printf("Destroy the serialized module\n");
     wasmer_serialized_module_destroy(serialized_module);
     wasmer_serialized_module_destroy(serialized_module_two);
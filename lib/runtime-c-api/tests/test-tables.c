@@ -34,6 +34,20 @@ int main()
     printf("Table length:  %d\n", len_grow2);
     assert(len_grow2 == 15);
 
+    wasmer_funcref_t *func_ref = NULL;
+    wasmer_result_t get_result = wasmer_table_get(table, 14, &func_ref);
+    assert(get_result == WASMER_OK);
+    assert(wasmer_funcref_is_null(func_ref));
+
+    wasmer_result_t set_result = wasmer_table_set(table, 0, func_ref);
+    assert(set_result == WASMER_OK);
+
+    // Out of bounds accesses fail
+    assert(wasmer_table_set(table, 15, func_ref) == WASMER_ERROR);
+    wasmer_funcref_t *func_ref_bad = NULL;
+    assert(wasmer_table_get(table, 15, &func_ref_bad) == WASMER_ERROR);
+    wasmer_funcref_destroy(func_ref);
+
     wasmer_table_t *table_bad = NULL;
     wasmer_limits_t bad_descriptor;
     bad_descriptor.min = 15;
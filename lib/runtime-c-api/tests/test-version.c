@@ -0,0 +1,28 @@
+#include <stdio.h>
+#include "../wasmer.h"
+#include <assert.h>
+#include <stdint.h>
+#include <string.h>
+
+int main()
+{
+    const char *version = wasmer_version_string();
+    printf("Version: %s\n", version);
+
+    char expected[32];
+    snprintf(expected, sizeof(expected), "%d.%d.%d", wasmer_version_major(), wasmer_version_minor(), wasmer_version_patch());
+    assert(0 == strcmp(version, expected));
+
+    printf("Query features\n");
+#if defined(WASMER_WASI_ENABLED)
+    assert(wasmer_has_feature("wasi"));
+#endif
+#if defined(WASMER_EMSCRIPTEN_ENABLED)
+    assert(wasmer_has_feature("emscripten"));
+#endif
+    assert(wasmer_has_feature("simd") == wasmer_has_feature("llvm-backend"));
+    assert(!wasmer_has_feature("unknown-feature"));
+    assert(!wasmer_has_feature(NULL));
+
+    return 0;
+}
@@ -49,6 +49,84 @@ enum Version {
 typedef uint8_t Version;
 #endif
 
+/**
+ * The backend compiling modules, see `wasmer_config_set_backend`.
+ */
+enum wasmer_backend_t {
+  /**
+   * Picks a backend for each module, from its size and whether it's metered.
+   */
+  WASMER_BACKEND_AUTO,
+  WASMER_BACKEND_CRANELIFT,
+  WASMER_BACKEND_SINGLEPASS,
+  WASMER_BACKEND_LLVM,
+  WASMER_BACKEND_INTERPRETER,
+};
+typedef uint32_t wasmer_backend_t;
+
+/**
+ * The kind of an error, passed to the callback set with `wasmer_set_error_callback`.
+ */
+enum wasmer_error_code_t {
+  /**
+   * Any error not listed below, e.g. a null pointer argument.
+   */
+  WASMER_ERROR_CODE_OTHER,
+  /**
+   * The module failed to compile or validate.
+   */
+  WASMER_ERROR_CODE_COMPILE,
+  /**
+   * An import of the module is missing, or has the wrong type.
+   */
+  WASMER_ERROR_CODE_LINK,
+  /**
+   * The instance trapped, or a host function failed.
+   */
+  WASMER_ERROR_CODE_RUNTIME,
+  /**
+   * The called function doesn't exist, or got arguments of the wrong type.
+   */
+  WASMER_ERROR_CODE_RESOLVE,
+  /**
+   * A memory, table or global couldn't be created.
+   */
+  WASMER_ERROR_CODE_CREATION,
+  /**
+   * The library panicked, which is a bug.
+   */
+  WASMER_ERROR_CODE_PANIC,
+};
+typedef uint32_t wasmer_error_code_t;
+
+/**
+ * A feature of a `wasmer_features_t`.
+ */
+enum wasmer_feature_t {
+  /**
+   * The SIMD proposal.
+   */
+  WASMER_FEATURE_SIMD,
+  /**
+   * The threads proposal.
+   */
+  WASMER_FEATURE_THREADS,
+  /**
+   * The bulk memory proposal. No backend compiles its instructions yet,
+   * so modules using them only pass validation.
+   */
+  WASMER_FEATURE_BULK_MEMORY,
+  /**
+   * The multi-value proposal.
+   */
+  WASMER_FEATURE_MULTI_VALUE,
+  /**
+   * Rejects modules using floating point types or instructions.
+   */
+  WASMER_FEATURE_DENY_FLOATS,
+};
+typedef uint32_t wasmer_feature_t;
+
 /**
  * List of export/import kinds.
  */
@@ -60,6 +138,25 @@ enum wasmer_import_export_kind {
 };
 typedef uint32_t wasmer_import_export_kind;
 
+/**
+ * The style of the memories of compiled modules, see `wasmer_config_set_memory_style`.
+ */
+enum wasmer_memory_style_t {
+  /**
+   * Memories with a maximum size are static, and the others dynamic.
+   */
+  WASMER_MEMORY_STYLE_DEFAULT,
+  /**
+   * Every memory reserves 6GiB of virtual memory, so accesses aren't bounds-checked.
+   */
+  WASMER_MEMORY_STYLE_STATIC,
+  /**
+   * Every memory only maps its current size, and accesses are bounds-checked.
+   */
+  WASMER_MEMORY_STYLE_DYNAMIC,
+};
+typedef uint32_t wasmer_memory_style_t;
+
 typedef enum {
   WASMER_OK = 1,
   WASMER_ERROR = 2,
@@ -70,6 +167,7 @@ enum wasmer_value_tag {
   WASM_I64,
   WASM_F32,
   WASM_F64,
+  WASM_V128,
 };
 typedef uint32_t wasmer_value_tag;
 
@@ -77,6 +175,28 @@ typedef struct {
 
 } wasmer_module_t;
 
+/**
+ * Opaque pointer to a `FileSystemCache`.
+ */
+typedef struct {
+
+} wasmer_cache_t;
+
+/**
+ * Opaque pointer to a `CompileConfig`.
+ */
+typedef struct {
+
+} wasmer_config_t;
+
+/**
+ * The WebAssembly features a module is compiled or validated with, see
+ * `wasmer_features_new`.
+ */
+typedef struct {
+
+} wasmer_features_t;
+
 typedef struct {
 
 } wasmer_instance_t;
@@ -120,11 +240,22 @@ typedef struct {
 
 } wasmer_export_func_t;
 
+/**
+ * Opaque pointer to `ExportFuncHandle`.
+ */
+typedef struct {
+
+} wasmer_export_func_handle_t;
+
 typedef union {
   int32_t I32;
   int64_t I64;
   float F32;
   double F64;
+  /**
+   * The bytes of a `v128`, in little-endian order.
+   */
+  uint8_t V128[16];
 } wasmer_value;
 
 typedef struct {
@@ -150,6 +281,13 @@ typedef struct {
 
 } wasmer_exports_t;
 
+/**
+ * Opaque pointer to a `FuncRef`, a reference to a function stored in a table.
+ */
+typedef struct {
+
+} wasmer_funcref_t;
+
 typedef struct {
 
 } wasmer_global_t;
@@ -200,6 +338,20 @@ typedef struct {
 
 } wasmer_instance_context_t;
 
+/**
+ * The resources used by an instance, see `wasmer_instance_stats`.
+ *
+ * `points_used` is only set when `has_points_used` is true, which is when the module was
+ * compiled with the metering middleware.
+ */
+typedef struct {
+  uint64_t memory_bytes;
+  uint64_t table_elements;
+  uint64_t host_calls;
+  bool has_points_used;
+  uint64_t points_used;
+} wasmer_instance_stats_t;
+
 typedef struct {
   bool has_some;
   uint32_t some;
@@ -236,6 +388,10 @@ typedef struct {
 /**
  * Opens a directory that's visible to the WASI module as `alias` but
  * is backed by the host file at `host_file_path`
+ *
+ * If `host_file_path` is a regular file rather than a directory, only
+ * that file is exposed, and `alias` is the full path the WASI module
+ * sees it at, e.g. `/etc/hosts`.
  */
 typedef struct {
   /**
@@ -246,9 +402,73 @@ typedef struct {
    * The backing file that the WASI module will interact with via the alias
    */
   wasmer_byte_array host_file_path;
+  /**
+   * Don't allow the WASI module to write to anything under the mapping.
+   */
+  bool read_only;
+  /**
+   * Don't allow the WASI module to create anything in a mapped directory.
+   */
+  bool no_create;
+  /**
+   * Don't allow the WASI module to remove anything from a mapped directory.
+   */
+  bool no_delete;
 } wasmer_wasi_map_dir_entry_t;
 #endif
 
+/**
+ * Frees memory for the given cache. The modules stored in it stay on disk.
+ */
+void wasmer_cache_destroy(wasmer_cache_t *cache);
+
+/**
+ * Loads the module compiled from the given wasm bytes from the cache, or compiles
+ * them and stores the module in the cache if it isn't there yet.
+ *
+ * Modules are keyed by a hash of their wasm bytes. The module is still returned if it
+ * can't be stored.
+ *
+ * The caller owns the module and should call `wasmer_module_destroy` to free it.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_cache_load_or_compile(wasmer_cache_t *cache,
+                                             const uint8_t *wasm_bytes,
+                                             uint32_t wasm_bytes_len,
+                                             wasmer_module_t **module);
+
+/**
+ * Creates a cache storing compiled modules in the directory at `dir_path`, which is
+ * created if it doesn't exist yet.
+ *
+ * The artifacts in the directory are loaded without being checked, so it must not be
+ * writable by anyone untrusted.
+ *
+ * The caller owns the object and should call `wasmer_cache_destroy` to free it.
+ *
+ * Returns `NULL` upon failure. Use `wasmer_last_error_length` and
+ * `wasmer_last_error_message` to get an error message.
+ */
+wasmer_cache_t *wasmer_cache_new(const char *dir_path);
+
+/**
+ * Stores the given module in the cache under `key`, usually the wasm bytes it was
+ * compiled from, so that `wasmer_cache_load_or_compile` finds it.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_cache_store(wasmer_cache_t *cache,
+                                   const uint8_t *key,
+                                   uint32_t key_len,
+                                   const wasmer_module_t *module);
+
 /**
  * Creates a new Module from the given wasm bytes.
  *
@@ -261,6 +481,80 @@ wasmer_result_t wasmer_compile(wasmer_module_t **module,
                                uint8_t *wasm_bytes,
                                uint32_t wasm_bytes_len);
 
+/**
+ * Creates a new Module from the given wasm bytes, compiled as `config` says.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_compile_with_config(wasmer_module_t **module,
+                                           uint8_t *wasm_bytes,
+                                           uint32_t wasm_bytes_len,
+                                           const wasmer_config_t *config);
+
+/**
+ * Creates a new Module from the given wasm bytes, with the given features.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message, which names the feature
+ * the module needs when it isn't enabled.
+ */
+wasmer_result_t wasmer_compile_with_features(wasmer_module_t **module,
+                                             uint8_t *wasm_bytes,
+                                             uint32_t wasm_bytes_len,
+                                             const wasmer_features_t *features);
+
+/**
+ * Frees memory for the given compile configuration.
+ */
+void wasmer_config_destroy(wasmer_config_t *config);
+
+/**
+ * Creates a new compile configuration, which compiles modules like `wasmer_compile`
+ * until it's changed with the `wasmer_config_set_*` functions.
+ *
+ * The caller owns the object and should call `wasmer_config_destroy` to free it.
+ */
+wasmer_config_t *wasmer_config_new(void);
+
+/**
+ * Sets the backend compiling modules. Whether it's enabled in this library is only
+ * checked when compiling.
+ */
+void wasmer_config_set_backend(wasmer_config_t *config, wasmer_backend_t backend);
+
+/**
+ * Sets the WebAssembly features modules are compiled with, which are copied.
+ */
+void wasmer_config_set_features(wasmer_config_t *config, const wasmer_features_t *features);
+
+/**
+ * Sets the style of the memories of the compiled modules.
+ */
+void wasmer_config_set_memory_style(wasmer_config_t *config, wasmer_memory_style_t memory_style);
+
+/**
+ * Meters the compiled modules, so that their instances trap once they've used `limit`
+ * points, as reported by `wasmer_instance_stats`. A `limit` of 0 disables metering.
+ *
+ * The Cranelift backend can't meter modules.
+ */
+void wasmer_config_set_metering_limit(wasmer_config_t *config, uint64_t limit);
+
+/**
+ * Enables or disables NaN canonicalization: every NaN produced by a floating point
+ * instruction is replaced by the canonical NaN, so the results don't depend on the
+ * hardware.
+ *
+ * This is the deterministic execution profile, which also rejects modules using
+ * threads, and SIMD unless compiled by the LLVM backend.
+ */
+void wasmer_config_set_nan_canonicalization(wasmer_config_t *config, bool enabled);
+
 #if defined(WASMER_EMSCRIPTEN_ENABLED)
 /**
  * Convenience function for setting up arguments and calling the Emscripten
@@ -307,6 +601,42 @@ wasmer_import_object_t *wasmer_emscripten_generate_import_object(wasmer_emscript
 wasmer_emscripten_globals_t *wasmer_emscripten_get_globals(const wasmer_module_t *module);
 #endif
 
+#if defined(WASMER_EMSCRIPTEN_ENABLED)
+/**
+ * Sends what the guest writes to its standard error to `callback` instead
+ * of the host's stderr. The callback receives the written bytes, their
+ * length, and the given `user_data`.
+ *
+ * Call it before `wasmer_emscripten_set_up`.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_emscripten_set_stderr_callback(wasmer_emscripten_globals_t *globals,
+                                                      void (*callback)(const uint8_t *data, uint32_t data_length, void *user_data),
+                                                      void *user_data);
+#endif
+
+#if defined(WASMER_EMSCRIPTEN_ENABLED)
+/**
+ * Sends what the guest writes to its standard output to `callback` instead
+ * of the host's stdout. The callback receives the written bytes, their
+ * length, and the given `user_data`.
+ *
+ * Call it before `wasmer_emscripten_set_up`.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_emscripten_set_stdout_callback(wasmer_emscripten_globals_t *globals,
+                                                      void (*callback)(const uint8_t *data, uint32_t data_length, void *user_data),
+                                                      void *user_data);
+#endif
+
 #if defined(WASMER_EMSCRIPTEN_ENABLED)
 /**
  * Execute global constructors (required if the module is compiled from C++)
@@ -368,6 +698,30 @@ wasmer_result_t wasmer_export_func_call(const wasmer_export_func_t *func,
                                         wasmer_value_t *results,
                                         unsigned int results_len);
 
+/**
+ * Calls a function handle returned by `wasmer_instance_get_export_func` with the
+ * provided parameters. Results are set using the provided `results` pointer.
+ *
+ * `params` must hold as many values as the function has parameters, and `results` room
+ * for as many values as it has results. Unlike `wasmer_export_func_call`, the tags of
+ * the parameters aren't checked: each value is read as the type of its parameter.
+ *
+ * A handle must not be called from multiple threads at once.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_export_func_call_fast(wasmer_export_func_handle_t *handle,
+                                             const wasmer_value_t *params,
+                                             wasmer_value_t *results);
+
+/**
+ * Frees the memory for the given function handle.
+ */
+void wasmer_export_func_handle_destroy(wasmer_export_func_handle_t *handle);
+
 /**
  * Sets the params buffer to the parameter types of the given wasmer_export_func_t
  *
@@ -453,6 +807,58 @@ wasmer_export_t *wasmer_exports_get(wasmer_exports_t *exports, int idx);
  */
 int wasmer_exports_len(wasmer_exports_t *exports);
 
+/**
+ * Frees memory for the given features
+ */
+void wasmer_features_destroy(wasmer_features_t *features);
+
+/**
+ * Creates a new set of features, with none of them enabled.
+ *
+ * The caller owns the object and should call `wasmer_features_destroy` to free it.
+ */
+wasmer_features_t *wasmer_features_new(void);
+
+/**
+ * Enables or disables a feature.
+ */
+void wasmer_features_set(wasmer_features_t *features, wasmer_feature_t feature, bool enabled);
+
+/**
+ * Frees memory for the given function reference
+ */
+void wasmer_funcref_destroy(wasmer_funcref_t *func_ref);
+
+/**
+ * Creates a function reference to an exported function, so it can be stored in a
+ * table with `wasmer_table_set`.
+ *
+ * The caller owns the object and should call `wasmer_funcref_destroy` to free it.
+ * The instance exporting the function must outlive any table it is stored in.
+ *
+ * Returns a null pointer if `func` is null.
+ */
+wasmer_funcref_t *wasmer_funcref_from_export_func(const wasmer_export_func_t *func);
+
+/**
+ * Creates a function reference to a host function, so it can be stored in a table
+ * with `wasmer_table_set`. When called through the table, the host function gets the
+ * context of `instance`, like the imports of that instance.
+ *
+ * The caller owns the object and should call `wasmer_funcref_destroy` to free it.
+ * The host function and the instance must outlive any table it is stored in.
+ *
+ * Returns a null pointer if `instance` or `func` is null.
+ */
+wasmer_funcref_t *wasmer_funcref_from_import_func(wasmer_instance_t *instance,
+                                                  const wasmer_import_func_t *func);
+
+/**
+ * Returns true if the given function reference is null, i.e. it refers to an
+ * empty table element.
+ */
+bool wasmer_funcref_is_null(const wasmer_funcref_t *func_ref);
+
 /**
  * Frees memory for the given Global
  */
@@ -476,8 +882,29 @@ wasmer_global_t *wasmer_global_new(wasmer_value_t value, bool mutable_);
 
 /**
  * Sets the value stored by the given Global
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure, e.g. if the Global is
+ * immutable or the value has the wrong type. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
  */
-void wasmer_global_set(wasmer_global_t *global, wasmer_value_t value);
+wasmer_result_t wasmer_global_set(wasmer_global_t *global, wasmer_value_t value);
+
+/**
+ * Returns true if this library was built with the optional feature `name`, which is
+ * one of:
+ *
+ * * `"cranelift-backend"`, `"singlepass-backend"` and `"llvm-backend"`, the backends
+ *   compiling modules, see `wasmer_config_set_backend`,
+ * * `"wasi"` and `"emscripten"`, the import objects of the `wasmer_wasi_*` and
+ *   `wasmer_emscripten_*` functions,
+ * * `"simd"` and `"threads"`, the WebAssembly proposals only the LLVM backend compiles,
+ * * `"trampolines"`, the `wasmer_trampoline_*` functions.
+ *
+ * Returns false for any other name, or if `name` is null.
+ */
+bool wasmer_has_feature(const char *name);
 
 /**
  * Gets export descriptor kind
@@ -675,6 +1102,15 @@ void *wasmer_instance_context_data_get(const wasmer_instance_context_t *ctx);
  */
 void wasmer_instance_context_data_set(wasmer_instance_t *instance, void *data_ptr);
 
+/**
+ * Sets the `data` field of the instance context like
+ * `wasmer_instance_context_data_set`, and calls `destructor` with it
+ * when the instance is destroyed or its data set again.
+ */
+void wasmer_instance_context_data_set_with_destructor(wasmer_instance_t *instance,
+                                                      void *data_ptr,
+                                                      void (*destructor)(void*));
+
 /**
  * Extracts the instance's context and returns it.
  */
@@ -682,10 +1118,16 @@ const wasmer_instance_context_t *wasmer_instance_context_get(wasmer_instance_t *
 
 /**
  * Gets the memory within the context at the index `memory_idx`.
- * The index is always 0 until multiple memories are supported.
+ *
+ * Multiple memories aren't supported, so `memory_idx` must be 0 and the
+ * instance must have a memory.
+ *
+ * Returns NULL if the instance has no memory at that index. Use
+ * `wasmer_last_error_length` and `wasmer_last_error_message` to get an
+ * error message.
  */
 const wasmer_memory_t *wasmer_instance_context_memory(const wasmer_instance_context_t *ctx,
-                                                      uint32_t _memory_idx);
+                                                      uint32_t memory_idx);
 
 /**
  * Frees memory for the given Instance
@@ -699,6 +1141,45 @@ void wasmer_instance_destroy(wasmer_instance_t *instance);
  */
 void wasmer_instance_exports(wasmer_instance_t *instance, wasmer_exports_t **exports);
 
+/**
+ * Resolves the exported function `name` of an instance once, returning a handle that
+ * `wasmer_export_func_call_fast` calls without looking the function up by name or
+ * checking the parameters against its signature again.
+ *
+ * The handle must not outlive the instance. The caller owns the object and should call
+ * `wasmer_export_func_handle_destroy` to free it.
+ *
+ * Returns `NULL` upon failure. Use `wasmer_last_error_length` and
+ * `wasmer_last_error_message` to get an error message.
+ */
+wasmer_export_func_handle_t *wasmer_instance_get_export_func(const wasmer_instance_t *instance,
+                                                             const char *name);
+
+/**
+ * Runs the start function of an instance created with
+ * `wasmer_module_instantiate_unstarted`, if the module has one. It runs only once, and
+ * not at all for instances created otherwise, which were started when created.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_instance_run_start(wasmer_instance_t *instance);
+
+/**
+ * Stores the resources used by an instance so far in `stats`: the size of the memories
+ * and the number of elements of the tables it defines, the number of calls it made to
+ * host functions, and the metering points it used.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_instance_stats(const wasmer_instance_t *instance,
+                                      wasmer_instance_stats_t *stats);
+
 /**
  * Creates a new Instance from the given wasm bytes and imports.
  *
@@ -788,6 +1269,74 @@ uint32_t wasmer_memory_length(const wasmer_memory_t *memory);
  */
 wasmer_result_t wasmer_memory_new(wasmer_memory_t **memory, wasmer_limits_t limits);
 
+/**
+ * Creates a new Memory over `buffer`, which the caller owns and must keep alive
+ * until the Memory is destroyed, e.g. to share it with another process.
+ *
+ * `data_length` is the size of the memory in bytes, a multiple of the 64KiB wasm
+ * page size. The buffer must be 8-byte aligned, and extend 4096 bytes past
+ * `data_length`: guest accesses straddling the end of the memory may touch them.
+ *
+ * The memory can't grow past `max_pages`, and can only grow through `grow`, which
+ * is called with the buffer, the new size in bytes and `user_data`. It returns a
+ * buffer of the new size plus 4096 bytes holding the current contents, e.g. by
+ * reallocating `data`, or `NULL` to refuse. A `NULL` `grow` disables growth. It
+ * must not call back into the Memory.
+ *
+ * The caller owns the object and should call `wasmer_memory_destroy` to free it,
+ * which leaves the buffer untouched.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_memory_new_external(wasmer_memory_t **memory,
+                                           uint8_t *buffer,
+                                           uint32_t data_length,
+                                           wasmer_limit_option_t max_pages,
+                                           uint8_t *(*grow)(uint8_t *data, uint32_t data_length, void *user_data),
+                                           void *user_data);
+
+/**
+ * Registers a callback invoked after every successful grow of the given
+ * Memory, whether the grow was requested by the host or by the guest.
+ *
+ * Growing may move the memory, making pointers obtained from
+ * `wasmer_memory_data` stale. The callback receives the new data pointer,
+ * the new size in bytes, which is 4GiB for a memory of 65536 pages, and the
+ * given `user_data`.
+ *
+ * The callback must not call back into the same Memory.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_memory_set_grow_callback(wasmer_memory_t *memory,
+                                                void (*callback)(uint8_t *data, uint64_t data_length, void *user_data),
+                                                void *user_data);
+
+/**
+ * Gets the contents of the `index`th custom section named `name` of the
+ * given Module, in the order the sections appear in the module.
+ *
+ * The bytes are owned by the module and live as long as it does. Custom
+ * sections are kept when a module is serialized, so this works the same for
+ * a deserialized module.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure, e.g. if the module
+ * has no more than `index` sections named `name`. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_module_custom_section(const wasmer_module_t *module,
+                                             const char *name,
+                                             uint32_t index,
+                                             wasmer_byte_array *section);
+
 /**
  * Deserialize the given serialized module.
  *
@@ -799,6 +1348,29 @@ wasmer_result_t wasmer_memory_new(wasmer_memory_t **memory, wasmer_limits_t limi
 wasmer_result_t wasmer_module_deserialize(wasmer_module_t **module,
                                           const wasmer_serialized_module_t *serialized_module);
 
+/**
+ * Deserializes a module from an artifact written by `wasmer_module_serialize_to_callback`
+ * or `wasmer_module_serialize_to_path`, using the compiled code in `bytes` in place
+ * instead of copying it.
+ *
+ * `bytes` must start at a page boundary, which they do when the artifact file is
+ * mapped into memory. The code must be position independent. The protection of the
+ * code pages is changed and the table of addresses that follows them is written to,
+ * so the mapping must be writable and should be private (e.g.
+ * `PROT_READ | PROT_WRITE` with `MAP_PRIVATE`). The
+ * caller must keep `bytes` mapped until the module and its instances are destroyed.
+ *
+ * This is only supported on Unix.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_module_deserialize_borrowed(wasmer_module_t **module,
+                                                   uint8_t *bytes,
+                                                   uint32_t bytes_len);
+
 /**
  * Frees memory for the given Module
  */
@@ -828,6 +1400,21 @@ wasmer_result_t wasmer_module_instantiate(const wasmer_module_t *module,
                                           wasmer_import_t *imports,
                                           int imports_len);
 
+/**
+ * Creates a new Instance from the given module and imports, without running the
+ * start function of the module. Call `wasmer_instance_run_start` to run it, e.g.
+ * after setting the globals of the instance.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_module_instantiate_unstarted(const wasmer_module_t *module,
+                                                    wasmer_instance_t **instance,
+                                                    wasmer_import_t *imports,
+                                                    int imports_len);
+
 /**
  * Serialize the given Module.
  *
@@ -841,6 +1428,37 @@ wasmer_result_t wasmer_module_instantiate(const wasmer_module_t *module,
 wasmer_result_t wasmer_module_serialize(wasmer_serialized_module_t **serialized_module,
                                         const wasmer_module_t *module);
 
+/**
+ * Serializes the given Module, passing the artifact to `callback` piece by piece
+ * instead of building it in memory like `wasmer_module_serialize` does.
+ *
+ * The artifact is written in the shared layout, where the compiled code is stored
+ * verbatim, so only the module metadata is buffered. `callback` receives each piece,
+ * its length in bytes, and the given `user_data`, and returns `false` to abort.
+ *
+ * The shared layout needs position independent code, which only the Cranelift backend
+ * compiles; modules compiled otherwise can't be serialized this way.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_module_serialize_to_callback(const wasmer_module_t *module,
+                                                    bool (*callback)(const uint8_t *data, uint32_t data_length, void *user_data),
+                                                    void *user_data);
+
+/**
+ * Serializes the given Module to the file at `path`, which is created or truncated,
+ * like `wasmer_module_serialize_to_callback` does.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_module_serialize_to_path(const wasmer_module_t *module, const char *path);
+
 /**
  * Get bytes of the serialized module.
  */
@@ -865,11 +1483,37 @@ wasmer_result_t wasmer_serialized_module_from_bytes(wasmer_serialized_module_t *
                                                     const uint8_t *serialized_module_bytes,
                                                     uint32_t serialized_module_bytes_length);
 
+/**
+ * Sets a callback called with the code and message of every error, when it becomes
+ * the last error of the thread it happened in. The message is only valid during the
+ * call, and the errors can still be read with `wasmer_last_error_message`.
+ *
+ * The callback is global, so it may be called from any thread, and replaces the one
+ * set before. A `NULL` callback removes it.
+ */
+void wasmer_set_error_callback(void (*callback)(wasmer_error_code_t code, const char *message, void *user_data),
+                               void *user_data);
+
 /**
  * Frees memory for the given Table
  */
 void wasmer_table_destroy(wasmer_table_t *table);
 
+/**
+ * Gets the element at `index` of the given Table, and stores a new function
+ * reference to it in `func_ref`. The reference is null if the element is empty.
+ *
+ * The caller owns the object and should call `wasmer_funcref_destroy` to free it.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_get(const wasmer_table_t *table,
+                                 uint32_t index,
+                                 wasmer_funcref_t **func_ref);
+
 /**
  * Grows a Table by the given number of elements.
  *
@@ -898,6 +1542,18 @@ uint32_t wasmer_table_length(wasmer_table_t *table);
  */
 wasmer_result_t wasmer_table_new(wasmer_table_t **table, wasmer_limits_t limits);
 
+/**
+ * Sets the element at `index` of the given Table to `func_ref`.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_set(const wasmer_table_t *table,
+                                 uint32_t index,
+                                 const wasmer_funcref_t *func_ref);
+
 #if (!defined(_WIN32) && defined(ARCH_X86_64))
 /**
  * Adds a callinfo trampoline to the builder.
@@ -958,6 +1614,41 @@ void *wasmer_trampoline_get_context(void);
  */
 bool wasmer_validate(const uint8_t *wasm_bytes, uint32_t wasm_bytes_len);
 
+/**
+ * Validates the given wasm bytes with the given features.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` if the bytes are valid.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` otherwise. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message, which names the feature
+ * the module needs when it isn't enabled.
+ */
+wasmer_result_t wasmer_validate_with_features(const uint8_t *wasm_bytes,
+                                              uint32_t wasm_bytes_len,
+                                              const wasmer_features_t *features);
+
+/**
+ * Gets the major version of this library, e.g. `0` for `0.12.0`.
+ */
+uint8_t wasmer_version_major(void);
+
+/**
+ * Gets the minor version of this library, e.g. `12` for `0.12.0`.
+ */
+uint8_t wasmer_version_minor(void);
+
+/**
+ * Gets the patch version of this library, e.g. `0` for `0.12.0`.
+ */
+uint8_t wasmer_version_patch(void);
+
+/**
+ * Gets the version of this library as a null-terminated string, e.g. `"0.12.0"`.
+ *
+ * The string is static, and must not be freed.
+ */
+const char *wasmer_version_string(void);
+
 #if defined(WASMER_WASI_ENABLED)
 /**
  * Convenience function that creates a WASI import object with no arguments,
@@ -987,6 +1678,31 @@ wasmer_import_object_t *wasmer_wasi_generate_import_object(const wasmer_byte_arr
                                                            unsigned int mapped_dirs_len);
 #endif
 
+#if defined(WASMER_WASI_ENABLED)
+/**
+ * Creates a WASI import object satisfying every version of WASI
+ * imported by the module.
+ *
+ * This function is similar to `wasmer_wasi_generate_import_object`
+ * except that modules importing both `wasi_unstable` and
+ * `wasi_snapshot_preview1` get both namespaces, sharing the same WASI
+ * state.
+ *
+ * Returns null if `module` is null or if the WASI state can't be
+ * created; use `wasmer_last_error_length` and
+ * `wasmer_last_error_message` to get an error message.
+ */
+wasmer_import_object_t *wasmer_wasi_generate_import_object_for_module(const wasmer_module_t *module,
+                                                                      const wasmer_byte_array *args,
+                                                                      unsigned int args_len,
+                                                                      const wasmer_byte_array *envs,
+                                                                      unsigned int envs_len,
+                                                                      const wasmer_byte_array *preopened_files,
+                                                                      unsigned int preopened_files_len,
+                                                                      const wasmer_wasi_map_dir_entry_t *mapped_dirs,
+                                                                      unsigned int mapped_dirs_len);
+#endif
+
 #if defined(WASMER_WASI_ENABLED)
 /**
  * Creates a WASI import object for a specific version.
@@ -1007,6 +1723,22 @@ wasmer_import_object_t *wasmer_wasi_generate_import_object_for_version(unsigned
                                                                        unsigned int mapped_dirs_len);
 #endif
 
+#if defined(WASMER_WASI_ENABLED)
+/**
+ * Gets the code the WASI module passed to `proc_exit`.
+ *
+ * When a call into the module fails because the module called `proc_exit`,
+ * the code it exited with is written to `exit_code`.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` if the module has exited.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` if it hasn't, or upon failure.
+ * Use `wasmer_last_error_length` and `wasmer_last_error_message` to get an
+ * error message.
+ */
+wasmer_result_t wasmer_wasi_get_exit_code(wasmer_instance_t *instance, uint32_t *exit_code);
+#endif
+
 #if defined(WASMER_WASI_ENABLED)
 /**
  * Find the version of WASI used by the module.
@@ -1016,4 +1748,23 @@ wasmer_import_object_t *wasmer_wasi_generate_import_object_for_version(unsigned
 Version wasmer_wasi_get_version(const wasmer_module_t *module);
 #endif
 
+#if defined(WASMER_WASI_ENABLED)
+/**
+ * Reads the file the WASI module sees at `path` into `buffer`.
+ *
+ * `instance` must have been instantiated with a WASI import object. The
+ * file is read through the instance's WASI filesystem, so files that only
+ * exist in the virtual filesystem can be read too.
+ *
+ * Returns the size of the file in bytes. Nothing is copied when `buffer`
+ * is null or `buffer_len` is smaller than the file, so the function can be
+ * called again with a large enough buffer.
+ * Returns `-1` if an error occurs.
+ */
+int64_t wasmer_wasi_read_file(wasmer_instance_t *instance,
+                              const char *path,
+                              uint8_t *buffer,
+                              uint32_t buffer_len);
+#endif
+
 #endif /* WASMER_H */
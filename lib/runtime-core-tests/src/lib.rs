@@ -19,3 +19,9 @@ pub fn get_compiler() -> impl Compiler {
     use wasmer_llvm_backend::LLVMCompiler;
     LLVMCompiler::new()
 }
+
+#[cfg(feature = "backend-interpreter")]
+pub fn get_compiler() -> impl Compiler {
+    use wasmer_interpreter_backend::InterpreterCompiler;
+    InterpreterCompiler::new()
+}
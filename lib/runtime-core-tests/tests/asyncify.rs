@@ -0,0 +1,253 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+use wasmer_runtime_core::{
+    asyncify::{AsyncifyBuffer, AsyncifyCall, AsyncifyError, AsyncifyState, Suspend},
+    compile_with,
+    error::{CallError, RuntimeError},
+    imports,
+    typed_func::Func,
+    types::Value,
+    vm::Ctx,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+// What `wasm-opt --asyncify` makes of a function calling `env.sleep`, written by hand:
+// `$x` is pushed to the buffer while unwinding, and popped while rewinding.
+const MODULE: &str = r#"
+(module
+  (import "env" "sleep" (func $sleep))
+  (memory (export "memory") 1)
+  (global $state (mut i32) (i32.const 0))
+  (global $data (mut i32) (i32.const 0))
+
+  (func (export "asyncify_start_unwind") (param i32)
+    i32.const 1
+    set_global $state
+    get_local 0
+    set_global $data)
+  (func (export "asyncify_stop_unwind")
+    i32.const 0
+    set_global $state)
+  (func (export "asyncify_start_rewind") (param i32)
+    i32.const 2
+    set_global $state
+    get_local 0
+    set_global $data)
+  (func (export "asyncify_stop_rewind")
+    i32.const 0
+    set_global $state)
+  (func (export "asyncify_get_state") (result i32)
+    get_global $state)
+
+  (func (export "run") (param $x i32) (result i32)
+    (if (i32.eq (get_global $state) (i32.const 2))
+      (then
+        (i32.store (get_global $data) (i32.sub (i32.load (get_global $data)) (i32.const 4)))
+        (set_local $x (i32.load (i32.load (get_global $data))))))
+    (call $sleep)
+    (if (i32.eq (get_global $state) (i32.const 1))
+      (then
+        (i32.store (i32.load (get_global $data)) (get_local $x))
+        (i32.store (get_global $data) (i32.add (i32.load (get_global $data)) (i32.const 4)))
+        (return (i32.const 0))))
+    (i32.add (get_local $x) (i32.const 1))))
+"#;
+
+const BUFFER: AsyncifyBuffer = AsyncifyBuffer {
+    addr: 1024,
+    len: 1024,
+};
+
+#[test]
+fn suspend_and_resume() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let resumed = Arc::new(AtomicU32::new(0));
+    let sleep_resumed = Arc::clone(&resumed);
+    let import_object = imports! {
+        "env" => {
+            "sleep" => Func::new(move |ctx: &mut Ctx| -> Result<(), AsyncifyError> {
+                if ctx.suspend()? == Suspend::Resumed {
+                    sleep_resumed.fetch_add(1, Ordering::SeqCst);
+                }
+                Ok(())
+            }),
+        },
+    };
+    let mut instance = module.instantiate(&import_object).unwrap();
+
+    let suspended = match instance.call_asyncify("run", &[Value::I32(41)], BUFFER) {
+        Ok(AsyncifyCall::Suspended(suspended)) => suspended,
+        result => panic!("the call wasn't suspended: {:?}", result),
+    };
+    assert_eq!(suspended.name(), "run");
+    assert_eq!(suspended.buffer(), BUFFER);
+    assert_eq!(instance.asyncify_state().unwrap(), AsyncifyState::Normal);
+    assert_eq!(resumed.load(Ordering::SeqCst), 0);
+
+    match instance.resume(suspended) {
+        Ok(AsyncifyCall::Returned(values)) => assert_eq!(values, vec![Value::I32(42)]),
+        result => panic!("the call didn't return: {:?}", result),
+    }
+    assert_eq!(resumed.load(Ordering::SeqCst), 1);
+    assert_eq!(instance.asyncify_state().unwrap(), AsyncifyState::Normal);
+}
+
+#[test]
+fn suspend_outside_of_an_asyncify_call() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "sleep" => Func::new(|ctx: &mut Ctx| ctx.suspend().map(|_| ())),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let run: Func<i32, i32> = instance.func("run").unwrap();
+    match run.call(1) {
+        Err(RuntimeError::Error { data }) => match data.downcast_ref::<AsyncifyError>() {
+            Some(AsyncifyError::NoBuffer) => (),
+            other => panic!("unexpected error: {:?}", other),
+        },
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn async_host_function_resumed_without_awaiting() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "sleep" => Func::new_async(|_ctx: &mut Ctx| async {}),
+        },
+    };
+    let mut instance = module.instantiate(&import_object).unwrap();
+
+    let suspended = match instance.call_asyncify("run", &[Value::I32(41)], BUFFER) {
+        Ok(AsyncifyCall::Suspended(suspended)) => suspended,
+        result => panic!("the call wasn't suspended: {:?}", result),
+    };
+    match instance.resume(suspended) {
+        Err(AsyncifyError::Call(CallError::Runtime(RuntimeError::Error { data }))) => {
+            match data.downcast_ref::<AsyncifyError>() {
+                Some(AsyncifyError::NotAwaited) => (),
+                other => panic!("unexpected error: {:?}", other),
+            }
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn async_host_function_awaited() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let awaited = Arc::new(AtomicU32::new(0));
+    let sleep_awaited = Arc::clone(&awaited);
+    let import_object = imports! {
+        "env" => {
+            "sleep" => Func::new_async(move |_ctx: &mut Ctx| {
+                let sleep_awaited = Arc::clone(&sleep_awaited);
+                async move {
+                    sleep_awaited.fetch_add(1, Ordering::SeqCst);
+                }
+            }),
+        },
+    };
+    let mut instance = module.instantiate(&import_object).unwrap();
+
+    let values = block_on(instance.call_async("run", &[Value::I32(41)], BUFFER)).unwrap();
+    assert_eq!(values, vec![Value::I32(42)]);
+    assert_eq!(awaited.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn pending_future_dropped_with_the_instance() {
+    struct DropCounter(Arc<AtomicU32>);
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    let dropped = Arc::new(AtomicU32::new(0));
+    let sleep_dropped = Arc::clone(&dropped);
+    let import_object = imports! {
+        "env" => {
+            "sleep" => Func::new_async(move |_ctx: &mut Ctx| {
+                let counter = DropCounter(Arc::clone(&sleep_dropped));
+                async move {
+                    drop(counter);
+                }
+            }),
+        },
+    };
+    let mut instance = module.instantiate(&import_object).unwrap();
+
+    match instance.call_asyncify("run", &[Value::I32(41)], BUFFER) {
+        Ok(AsyncifyCall::Suspended(_)) => (),
+        result => panic!("the call wasn't suspended: {:?}", result),
+    }
+    assert_eq!(dropped.load(Ordering::SeqCst), 0);
+    drop(instance);
+    assert_eq!(dropped.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn call_asyncify_without_a_memory() {
+    let wasm_binary = wat2wasm(
+        r#"
+        (module
+          (func (export "asyncify_start_unwind") (param i32))
+          (func (export "asyncify_stop_unwind"))
+          (func (export "asyncify_start_rewind") (param i32))
+          (func (export "asyncify_stop_rewind"))
+          (func (export "asyncify_get_state") (result i32)
+            i32.const 0)
+          (func (export "run")))
+        "#
+        .as_bytes(),
+    )
+    .expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    match instance.call_asyncify("run", &[], BUFFER) {
+        Err(AsyncifyError::NoMemory) => (),
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+/// Polls `future` until it completes, for futures which never wait on a waker.
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
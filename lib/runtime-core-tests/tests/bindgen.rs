@@ -0,0 +1,98 @@
+use wasmer_bindgen::wasmer_bindgen;
+use wasmer_runtime_core::{compile_with, typed_func::Func, Instance};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+wasmer_bindgen!("tests/key-value.wit");
+
+struct Host;
+
+impl KeyValue for Host {
+    fn add(&self, a: u32, b: u32) -> u32 {
+        a + b
+    }
+
+    fn greet(&self, name: String) -> String {
+        format!("hello {}", name)
+    }
+
+    fn lookup(&self, key: String) -> Result<u32, Error> {
+        match key.as_str() {
+            "answer" => Ok(42),
+            _ => Err(Error::NotFound),
+        }
+    }
+}
+
+const MODULE: &str = r#"
+(module
+  (import "key-value" "add" (func $add (param i32 i32) (result i32)))
+  (import "key-value" "greet" (func $greet (param i32 i32 i32 i32) (result i32)))
+  (import "key-value" "lookup" (func $lookup (param i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "world")
+  (data (i32.const 16) "answer")
+  (data (i32.const 32) "missing")
+
+  (func (export "add") (result i32)
+    (call $add (i32.const 2) (i32.const 3)))
+  (func (export "greet") (param $cap i32) (result i32)
+    (call $greet (i32.const 0) (i32.const 5) (i32.const 64) (get_local $cap)))
+  (func (export "greet_out_of_bounds") (result i32)
+    (call $greet (i32.const 65530) (i32.const 100) (i32.const 64) (i32.const 32)))
+  (func (export "lookup_answer") (result i32)
+    (call $lookup (i32.const 16) (i32.const 6) (i32.const 128)))
+  (func (export "lookup_missing") (result i32)
+    (call $lookup (i32.const 32) (i32.const 7) (i32.const 128))))
+"#;
+
+fn instantiate() -> Instance {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = KeyValueImports::new(Host).build();
+    module.instantiate(&import_object).unwrap()
+}
+
+fn read_bytes(instance: &Instance, offset: usize, len: usize) -> Vec<u8> {
+    instance.context().memory(0).view::<u8>()[offset..offset + len]
+        .iter()
+        .map(|byte| byte.get())
+        .collect()
+}
+
+#[test]
+fn bindgen_passes_scalars() {
+    let instance = instantiate();
+    let add: Func<(), i32> = instance.func("add").unwrap();
+    assert_eq!(add.call(), Ok(5));
+}
+
+#[test]
+fn bindgen_lifts_and_lowers_strings() {
+    let instance = instantiate();
+    let greet: Func<i32, i32> = instance.func("greet").unwrap();
+
+    // The buffer is too small, so only the length is returned
+    assert_eq!(greet.call(4), Ok(11));
+    assert_eq!(read_bytes(&instance, 64, 4), vec![0; 4]);
+
+    assert_eq!(greet.call(32), Ok(11));
+    assert_eq!(read_bytes(&instance, 64, 11), b"hello world".to_vec());
+}
+
+#[test]
+fn bindgen_traps_on_strings_out_of_bounds() {
+    let instance = instantiate();
+    let greet_out_of_bounds: Func<(), i32> = instance.func("greet_out_of_bounds").unwrap();
+    assert!(greet_out_of_bounds.call().is_err());
+}
+
+#[test]
+fn bindgen_returns_expected_values_and_errors() {
+    let instance = instantiate();
+    let lookup_answer: Func<(), i32> = instance.func("lookup_answer").unwrap();
+    assert_eq!(lookup_answer.call(), Ok(0));
+    assert_eq!(read_bytes(&instance, 128, 4), 42u32.to_le_bytes().to_vec());
+
+    let lookup_missing: Func<(), i32> = instance.func("lookup_missing").unwrap();
+    assert_eq!(lookup_missing.call(), Ok(Error::NotFound as i32 + 1));
+}
@@ -0,0 +1,50 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config, imports,
+    typed_func::Func,
+};
+use wasmer_runtime_core_tests::get_compiler;
+
+const MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "fill") (param i32 i32 i32)
+    (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy") (param i32 i32 i32)
+    (memory.copy (local.get 0) (local.get 1) (local.get 2))))
+"#;
+
+#[test]
+fn fill_and_copy() {
+    let mut wabt_features = wabt::Features::new();
+    wabt_features.enable_bulk_memory();
+    let wasm_binary = wabt::wat2wasm_with_features(MODULE.as_bytes(), wabt_features)
+        .expect("WAST not valid or malformed");
+    let config = CompilerConfig {
+        features: Features {
+            bulk_memory: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let module = compile_with_config(&wasm_binary, &get_compiler(), config).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let fill: Func<(u32, u32, u32)> = instance.func("fill").unwrap();
+    let copy: Func<(u32, u32, u32)> = instance.func("copy").unwrap();
+    let memory = instance.context().memory(0);
+    let bytes = |range: std::ops::Range<usize>| -> Vec<u8> {
+        memory.view::<u8>()[range].iter().map(|b| b.get()).collect()
+    };
+
+    fill.call(10, 0xab, 4).unwrap();
+    assert_eq!(bytes(9..15), [0, 0xab, 0xab, 0xab, 0xab, 0]);
+
+    // Overlapping ranges are copied as if through a temporary buffer.
+    copy.call(12, 10, 4).unwrap();
+    assert_eq!(bytes(9..17), [0, 0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0]);
+
+    // Filling or copying out of bounds traps without writing anything.
+    assert!(fill.call(65534, 1, 4).is_err());
+    assert!(copy.call(0, 65535, 2).is_err());
+    assert_eq!(bytes(65530..65536), [0; 6]);
+}
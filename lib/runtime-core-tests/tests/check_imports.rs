@@ -0,0 +1,50 @@
+use wasmer_runtime_core::{
+    compile_with, error::LinkError, func, imports, memory::Memory, types::MemoryDescriptor,
+    units::Pages,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (import "env" "double" (func (param i32) (result i32)))
+  (import "env" "memory" (memory 1)))
+"#;
+
+fn double(n: i32) -> i32 {
+    n * 2
+}
+
+fn negate(n: i64) -> i64 {
+    -n
+}
+
+#[test]
+fn check_imports_reports_each_unresolved_import() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+
+    let import_object = imports! {
+        "env" => {
+            "double" => func!(double),
+            "memory" => memory,
+        },
+    };
+    module.check_imports(&import_object).unwrap();
+
+    let import_object = imports! {
+        "env" => {
+            "double" => func!(negate),
+        },
+    };
+    let errors = module.check_imports(&import_object).unwrap_err();
+    assert_eq!(errors.len(), 2, "unexpected errors: {:?}", errors);
+    match &errors[0] {
+        LinkError::IncorrectImportSignature { name, .. } => assert_eq!(name, "double"),
+        error => panic!("unexpected error: {:?}", error),
+    }
+    match &errors[1] {
+        LinkError::ImportNotFound { name, .. } => assert_eq!(name, "memory"),
+        error => panic!("unexpected error: {:?}", error),
+    }
+}
@@ -0,0 +1,29 @@
+use wasmer_runtime_core::{compile_with, load_cache_with};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (import "env" "host" (func $host))
+  (func (export "one") (result i32)
+    i32.const 1)
+  (func (export "two") (result i32)
+    i32.const 2))
+"#;
+
+#[test]
+fn compile_info_describes_the_compilation() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let compiler = get_compiler();
+    let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+    let info = module.compile_info().unwrap();
+    assert_eq!(info.backend, module.info().backend);
+    assert_eq!(info.functions, 2);
+    if let Some(code_size) = info.code_size {
+        assert!(code_size > 0);
+    }
+
+    let artifact = module.cache().unwrap();
+    let cached = unsafe { load_cache_with(artifact, &compiler) }.unwrap();
+    assert!(cached.compile_info().is_none());
+}
@@ -0,0 +1,85 @@
+use wasmer_runtime_core::{
+    backend::{CompileLimits, CompilerConfig},
+    compile_with_config,
+    error::CompileError,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (table 16 anyfunc)
+  (memory 1)
+  (data (i32.const 0) "0123456789abcdef")
+  (func (export "sum") (param i32) (result i32)
+    (local i32 i32 i32)
+    local.get 0
+    local.set 1
+    local.get 1)
+  (func (export "zero") (result i32)
+    i32.const 0))
+"#;
+
+fn compile_with_limits(limits: CompileLimits) -> Result<(), CompileError> {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let config = CompilerConfig {
+        limits,
+        ..Default::default()
+    };
+    compile_with_config(&wasm_binary, &get_compiler(), config).map(|_| ())
+}
+
+fn assert_limit_exceeded(limits: CompileLimits, expected: &str) {
+    match compile_with_limits(limits) {
+        Err(CompileError::LimitExceeded { msg }) => {
+            assert!(msg.contains(expected), "unexpected error: {}", msg)
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(()) => panic!("the module was compiled despite exceeding {:?}", limits),
+    }
+}
+
+#[test]
+fn default_limits_accept_ordinary_modules() {
+    compile_with_limits(CompileLimits::default()).unwrap();
+    compile_with_limits(CompileLimits::unlimited()).unwrap();
+}
+
+#[test]
+fn modules_exceeding_limits_are_rejected() {
+    let unlimited = CompileLimits::unlimited();
+    assert_limit_exceeded(
+        CompileLimits {
+            max_functions: Some(1),
+            ..unlimited
+        },
+        "more functions than the limit of 1",
+    );
+    assert_limit_exceeded(
+        CompileLimits {
+            max_function_body_size: Some(4),
+            ..unlimited
+        },
+        "function body 0",
+    );
+    assert_limit_exceeded(
+        CompileLimits {
+            max_locals: Some(2),
+            ..unlimited
+        },
+        "declares 3 locals",
+    );
+    assert_limit_exceeded(
+        CompileLimits {
+            max_table_size: Some(8),
+            ..unlimited
+        },
+        "16 initial elements",
+    );
+    assert_limit_exceeded(
+        CompileLimits {
+            max_data_segment_size: Some(8),
+            ..unlimited
+        },
+        "a data segment is 16 bytes",
+    );
+}
@@ -0,0 +1,74 @@
+use wasmer_runtime_core::{
+    cache::Artifact, compile_with, load_cache_with, structures::TypedIndex, types::FuncIndex,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+/// Append a custom section named `name` with the given contents.
+fn push_custom_section(wasm: &mut Vec<u8>, name: &str, contents: &[u8]) {
+    let size = 1 + name.len() + contents.len();
+    assert!(name.len() < 0x80 && size < 0x80);
+    wasm.push(0);
+    wasm.push(size as u8);
+    wasm.push(name.len() as u8);
+    wasm.extend_from_slice(name.as_bytes());
+    wasm.extend_from_slice(contents);
+}
+
+#[test]
+fn custom_sections_are_kept_in_order_through_the_cache() {
+    let mut wasm_binary = wat2wasm(b"(module)").expect("WAST not valid or malformed");
+    push_custom_section(&mut wasm_binary, "notes", b"one");
+    push_custom_section(&mut wasm_binary, "other", b"x");
+    push_custom_section(&mut wasm_binary, "notes", b"two");
+
+    let compiler = get_compiler();
+    let module = compile_with(&wasm_binary, &compiler).unwrap();
+    assert_eq!(
+        module.custom_sections("notes"),
+        vec![&b"one"[..], &b"two"[..]]
+    );
+    assert_eq!(module.custom_sections("other"), vec![&b"x"[..]]);
+    assert!(module.custom_sections("missing").is_empty());
+
+    let bytes = module.cache().unwrap().serialize().unwrap();
+    let artifact = Artifact::deserialize(&bytes).unwrap();
+    let cached = unsafe { load_cache_with(artifact, &compiler) }.unwrap();
+    assert_eq!(
+        cached.custom_sections("notes"),
+        vec![&b"one"[..], &b"two"[..]]
+    );
+    assert_eq!(cached.custom_sections("other"), vec![&b"x"[..]]);
+}
+
+#[test]
+fn name_section_names_are_kept_through_the_cache() {
+    let mut wasm_binary =
+        wat2wasm(b"(module (func (param i32 i32)) (func))").expect("WAST not valid or malformed");
+    #[rustfmt::skip]
+    let name_section = [
+        // module name: "demo"
+        0, 5, 4, b'd', b'e', b'm', b'o',
+        // function names: 0 => "add"
+        1, 6, 1, 0, 3, b'a', b'd', b'd',
+        // local names of function 0: 0 => "lhs", 1 => "rhs"
+        2, 13, 1, 0, 2, 0, 3, b'l', b'h', b's', 1, 3, b'r', b'h', b's',
+    ];
+    push_custom_section(&mut wasm_binary, "name", &name_section);
+
+    let compiler = get_compiler();
+    let module = compile_with(&wasm_binary, &compiler).unwrap();
+    let bytes = module.cache().unwrap().serialize().unwrap();
+    let artifact = Artifact::deserialize(&bytes).unwrap();
+    let cached = unsafe { load_cache_with(artifact, &compiler) }.unwrap();
+
+    for module in &[module, cached] {
+        assert_eq!(module.module_name(), Some("demo"));
+        assert_eq!(module.function_name(FuncIndex::new(0)), Some("add"));
+        assert_eq!(module.function_name(FuncIndex::new(1)), None);
+        assert_eq!(
+            module.local_names(FuncIndex::new(0)),
+            vec![(0, "lhs"), (1, "rhs")]
+        );
+        assert!(module.local_names(FuncIndex::new(1)).is_empty());
+    }
+}
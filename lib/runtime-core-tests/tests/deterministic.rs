@@ -0,0 +1,51 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config, imports,
+    typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (func (export "f32_div") (param f32 f32) (result i32)
+    get_local 0
+    get_local 1
+    f32.div
+    i32.reinterpret/f32)
+  (func (export "f64_sqrt") (param f64) (result i64)
+    get_local 0
+    f64.sqrt
+    i64.reinterpret/f64))
+"#;
+
+#[test]
+fn nans_are_canonical() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let config = CompilerConfig {
+        features: Features::deterministic(),
+        ..Default::default()
+    };
+    let module = compile_with_config(&wasm_binary, &get_compiler(), config).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let f32_div: Func<(f32, f32), i32> = instance.func("f32_div").unwrap();
+    assert_eq!(f32_div.call(0.0, 0.0), Ok(0x7FC0_0000));
+    assert_eq!(f32_div.call(1.0, 2.0), Ok(0.5f32.to_bits() as i32));
+
+    let f64_sqrt: Func<f64, i64> = instance.func("f64_sqrt").unwrap();
+    assert_eq!(f64_sqrt.call(-1.0), Ok(0x7FF8_0000_0000_0000));
+    assert_eq!(f64_sqrt.call(4.0), Ok(2.0f64.to_bits() as i64));
+}
+
+#[test]
+fn threads_are_rejected() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let config = CompilerConfig {
+        features: Features {
+            threads: true,
+            ..Features::deterministic()
+        },
+        ..Default::default()
+    };
+    assert!(compile_with_config(&wasm_binary, &get_compiler(), config).is_err());
+}
@@ -0,0 +1,82 @@
+use wasmer_runtime_core::{
+    compile_with, error::ResolveError, imports, typed_func::Func, types::ExternType, types::Type,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (memory (export "memory") 1 2)
+  (global (export "counter") (mut i32) (i32.const 0))
+  (table (export "table") 3 anyfunc)
+  (func (export "add") (param i32 i32) (result i32)
+    get_local 0
+    get_local 1
+    i32.add))
+"#;
+
+#[test]
+fn exports_iterate_with_their_types() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let exports: Vec<_> = instance.exports.iter().collect();
+    let names: Vec<_> = exports.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, vec!["memory", "counter", "table", "add"]);
+
+    match &exports[0].1 {
+        ExternType::Memory(desc) => {
+            assert_eq!(desc.minimum.0, 1);
+            assert_eq!(desc.maximum.map(|pages| pages.0), Some(2));
+        }
+        other => panic!("unexpected type {:?}", other),
+    }
+    match &exports[1].1 {
+        ExternType::Global(desc) => {
+            assert!(desc.mutable);
+            assert_eq!(desc.ty, Type::I32);
+        }
+        other => panic!("unexpected type {:?}", other),
+    }
+    match &exports[2].1 {
+        ExternType::Table(desc) => assert_eq!(desc.minimum, 3),
+        other => panic!("unexpected type {:?}", other),
+    }
+    match &exports[3].1 {
+        ExternType::Function(sig) => {
+            assert_eq!(sig.params(), &[Type::I32, Type::I32]);
+            assert_eq!(sig.returns(), &[Type::I32]);
+        }
+        other => panic!("unexpected type {:?}", other),
+    }
+    assert!(instance.exports.get_type("missing").is_none());
+}
+
+#[test]
+fn typed_funcs_report_signature_mismatches() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let add: Func<(i32, i32), i32> = instance.exports.get_typed_func("add").unwrap();
+    assert_eq!(add.call(2, 3).unwrap(), 5);
+
+    let error = instance
+        .exports
+        .get_typed_func::<(i64, i64), i64>("add")
+        .err()
+        .unwrap();
+    match &error {
+        ResolveError::ExportSignature { name, .. } => assert_eq!(name, "add"),
+        other => panic!("unexpected error {:?}", other),
+    }
+    assert_eq!(
+        error.to_string(),
+        "Export `add` has signature [I32, I32] -> [I32] but was requested as [I64, I64] -> [I64]"
+    );
+
+    match instance.exports.get_typed_func::<(), ()>("memory") {
+        Err(ResolveError::ExportWrongType { name }) => assert_eq!(name, "memory"),
+        _ => panic!("expected a wrong type error"),
+    }
+}
@@ -0,0 +1,57 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config, validate_and_report_errors_with_features,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const FLOAT_MODULE: &str = r#"
+(module
+  (func (export "half") (param i32) (result i32)
+    local.get 0
+    f32.convert_i32_s
+    f32.const 0.5
+    f32.mul
+    i32.trunc_f32_s))
+"#;
+
+const INTEGER_MODULE: &str = r#"
+(module
+  (func (export "half") (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    i32.shr_s))
+"#;
+
+fn deny_floats() -> Features {
+    Features {
+        deny_floats: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn deny_floats_rejects_float_instructions() {
+    let wasm_binary = wat2wasm(FLOAT_MODULE.as_bytes()).expect("WAST not valid or malformed");
+    validate_and_report_errors_with_features(&wasm_binary, Default::default()).unwrap();
+
+    let error = validate_and_report_errors_with_features(&wasm_binary, deny_floats()).unwrap_err();
+    assert!(error.contains("deny_floats"), "unexpected error: {}", error);
+
+    let config = CompilerConfig {
+        features: deny_floats(),
+        ..Default::default()
+    };
+    assert!(compile_with_config(&wasm_binary, &get_compiler(), config).is_err());
+}
+
+#[test]
+fn deny_floats_accepts_integer_modules() {
+    let wasm_binary = wat2wasm(INTEGER_MODULE.as_bytes()).expect("WAST not valid or malformed");
+    validate_and_report_errors_with_features(&wasm_binary, deny_floats()).unwrap();
+
+    let config = CompilerConfig {
+        features: deny_floats(),
+        ..Default::default()
+    };
+    compile_with_config(&wasm_binary, &get_compiler(), config).unwrap();
+}
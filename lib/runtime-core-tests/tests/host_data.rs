@@ -0,0 +1,74 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use wasmer_runtime_core::{compile_with, func, imports, vm, InstanceConfig};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (import "env" "bump" (func $bump))
+  (func $start
+    call $bump)
+  (func (export "run")
+    call $bump
+    call $bump)
+  (start $start))
+"#;
+
+struct Counter {
+    calls: u32,
+    dropped: Arc<AtomicBool>,
+}
+
+impl Drop for Counter {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+fn bump(ctx: &mut vm::Ctx) {
+    ctx.data::<Counter>().calls += 1;
+}
+
+#[test]
+fn typed_host_data_is_there_from_the_start_and_dropped_with_the_instance() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "bump" => func!(bump),
+        },
+    };
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let config = InstanceConfig::default().with_data(Counter {
+        calls: 0,
+        dropped: Arc::clone(&dropped),
+    });
+    let mut instance = module
+        .instantiate_with_config(&import_object, config)
+        .unwrap();
+    assert_eq!(instance.context_mut().data::<Counter>().calls, 1);
+
+    instance.call("run", &[]).unwrap();
+    let ctx = instance.context_mut();
+    assert_eq!(ctx.data::<Counter>().calls, 3);
+    assert!(ctx.try_data::<String>().is_none());
+
+    assert!(!dropped.load(Ordering::SeqCst));
+    drop(instance);
+    assert!(dropped.load(Ordering::SeqCst));
+}
+
+#[test]
+fn instances_without_typed_host_data_have_none() {
+    let wasm_binary = wat2wasm(b"(module)").expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    let ctx = instance.context_mut();
+    assert!(ctx.try_data::<u32>().is_none());
+    ctx.set_data(7u32);
+    assert_eq!(*ctx.data::<u32>(), 7);
+}
@@ -0,0 +1,75 @@
+use std::sync::Mutex;
+use wasmer_bindgen::wasmer_host_module;
+use wasmer_runtime_core::{
+    compile_with,
+    memory::ptr::{Array, WasmPtr},
+    typed_func::Func,
+    vm::Ctx,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+struct Logger {
+    lines: Mutex<Vec<String>>,
+}
+
+#[wasmer_host_module(namespace = "logger")]
+impl Logger {
+    fn log(&self, ctx: &mut Ctx, message: WasmPtr<u8, Array>, len: u32) -> Result<(), String> {
+        let message = message
+            .get_utf8_string(ctx.memory(0), len)
+            .ok_or_else(|| "the message is out of bounds".to_string())?;
+        self.lines.lock().unwrap().push(message.to_string());
+        Ok(())
+    }
+
+    #[host_function(name = "line_count")]
+    fn count(&self) -> u32 {
+        self.lines.lock().unwrap().len() as u32
+    }
+
+    fn double(value: i64) -> i64 {
+        value * 2
+    }
+
+    #[host_function(skip)]
+    fn new() -> Self {
+        Logger {
+            lines: Mutex::new(vec![]),
+        }
+    }
+}
+
+#[test]
+fn host_module_methods_are_host_functions() {
+    const MODULE: &str = r#"
+(module
+  (import "logger" "log" (func $log (param i32 i32)))
+  (import "logger" "line_count" (func $line_count (result i32)))
+  (import "logger" "double" (func $double (param i64) (result i64)))
+  (memory 1)
+  (data (i32.const 0) "hello")
+
+  (func (export "run") (result i32)
+    (call $log (i32.const 0) (i32.const 5))
+    (call $log (i32.const 1) (i32.const 4))
+    (call $line_count))
+  (func (export "log_out_of_bounds")
+    (call $log (i32.const 65535) (i32.const 2)))
+  (func (export "double") (param i64) (result i64)
+    (call $double (get_local 0))))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = Logger::new().into_import_object();
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let run: Func<(), i32> = instance.func("run").unwrap();
+    assert_eq!(run.call(), Ok(2));
+
+    let double: Func<i64, i64> = instance.func("double").unwrap();
+    assert_eq!(double.call(21), Ok(42));
+
+    let log_out_of_bounds: Func<(), ()> = instance.func("log_out_of_bounds").unwrap();
+    assert!(log_out_of_bounds.call().is_err());
+}
@@ -0,0 +1,52 @@
+use wasmer_runtime_core::{compile_with, func, import::ImportRewriter, imports, types::Value};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (import "host" "double" (func $double (param i32) (result i32)))
+  (import "host" "add_one" (func $add_one (param i32) (result i32)))
+  (func (export "run") (param i32) (result i32)
+    local.get 0
+    call $double
+    call $add_one))
+"#;
+
+fn double(n: i32) -> i32 {
+    n * 2
+}
+
+fn increment(n: i32) -> i32 {
+    n + 1
+}
+
+#[test]
+fn imports_are_resolved_with_their_new_names() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut import_object = imports! {
+        "env" => {
+            "double" => func!(double),
+            "increment" => func!(increment),
+        },
+    };
+
+    assert!(module.instantiate(&import_object).is_err());
+
+    import_object.set_rewriter(
+        ImportRewriter::new()
+            .rename_namespace("host", "env")
+            .rename_import("host", "add_one", "env", "increment"),
+    );
+    let instance = module.instantiate(&import_object).unwrap();
+    assert_eq!(
+        instance.call("run", &[Value::I32(20)]).unwrap(),
+        [Value::I32(41)]
+    );
+
+    let frozen = import_object.freeze();
+    let instance = module.instantiate(&frozen).unwrap();
+    assert_eq!(
+        instance.call("run", &[Value::I32(1)]).unwrap(),
+        [Value::I32(3)]
+    );
+}
@@ -0,0 +1,159 @@
+#![cfg(feature = "backend-interpreter")]
+
+use wasmer_runtime_core::{compile_with, error::RuntimeError, func, imports, typed_func::Func, vm};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (import "env" "mix" (func $mix (param i32 f64 i64 f32) (result f64)))
+  (type $binary (func (param i32 i32) (result i32)))
+  (table 2 anyfunc)
+  (elem (i32.const 0) $add $sub)
+  (func $add (type $binary)
+    get_local 0
+    get_local 1
+    i32.add)
+  (func $sub (type $binary)
+    get_local 0
+    get_local 1
+    i32.sub)
+  (func (export "mix") (result f64)
+    i32.const 1
+    f64.const 2.5
+    i64.const 3
+    f32.const 4.5
+    call $mix)
+  (func (export "dispatch") (param i32 i32 i32) (result i32)
+    get_local 1
+    get_local 2
+    get_local 0
+    call_indirect (type $binary))
+  (func (export "factorial") (param i64) (result i64)
+    (local i64)
+    i64.const 1
+    set_local 1
+    (block $done
+      (loop $continue
+        get_local 0
+        i64.eqz
+        br_if $done
+        get_local 1
+        get_local 0
+        i64.mul
+        set_local 1
+        get_local 0
+        i64.const 1
+        i64.sub
+        set_local 0
+        br $continue))
+    get_local 1)
+  (func (export "divide") (param i32 i32) (result i32)
+    get_local 0
+    get_local 1
+    i32.div_s))
+"#;
+
+fn mix(_ctx: &mut vm::Ctx, a: i32, b: f64, c: i64, d: f32) -> f64 {
+    a as f64 + b + c as f64 + d as f64
+}
+
+#[test]
+fn runs_functions_and_host_calls() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module
+        .instantiate(&imports! {
+            "env" => {
+                "mix" => func!(mix),
+            },
+        })
+        .unwrap();
+
+    let mix: Func<(), f64> = instance.func("mix").unwrap();
+    assert_eq!(mix.call().unwrap(), 11.0);
+
+    let dispatch: Func<(i32, i32, i32), i32> = instance.func("dispatch").unwrap();
+    assert_eq!(dispatch.call(0, 7, 2).unwrap(), 9);
+    assert_eq!(dispatch.call(1, 7, 2).unwrap(), 5);
+    assert!(dispatch.call(2, 7, 2).is_err());
+
+    let factorial: Func<i64, i64> = instance.func("factorial").unwrap();
+    assert_eq!(factorial.call(10).unwrap(), 3_628_800);
+
+    let divide: Func<(i32, i32), i32> = instance.func("divide").unwrap();
+    assert_eq!(divide.call(7, 2).unwrap(), 3);
+    match divide.call(1, 0) {
+        Err(RuntimeError::Trap { .. }) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn host_function_errors_are_returned() {
+    let wasm_binary = wat2wasm(
+        r#"
+        (module
+          (import "env" "check" (func $check (param i32) (result i32)))
+          (import "env" "panic" (func $panic))
+          (import "env" "sum" (func $sum (param i32 i64 i32 i64 i32 i64 i32 i64) (result i64)))
+          (func (export "check") (param i32) (result i32)
+            get_local 0
+            call $check)
+          (func (export "panic")
+            call $panic)
+          (func (export "sum") (result i64)
+            i32.const 1
+            i64.const 2
+            i32.const 3
+            i64.const 4
+            i32.const 5
+            i64.const 6
+            i32.const 7
+            i64.const 8
+            call $sum))
+        "#
+        .as_bytes(),
+    )
+    .expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module
+        .instantiate(&imports! {
+            "env" => {
+                "check" => func!(|x: i32| -> Result<i32, String> {
+                    if x < 0 {
+                        Err(format!("{} is negative", x))
+                    } else {
+                        Ok(x + 1)
+                    }
+                }),
+                "panic" => func!(|| panic!("the host function panicked")),
+                "sum" => func!(|a: i32, b: i64, c: i32, d: i64, e: i32, f: i64, g: i32, h: i64| {
+                    (a + c + e + g) as i64 + b + d + f + h
+                }),
+            },
+        })
+        .unwrap();
+
+    let check: Func<i32, i32> = instance.func("check").unwrap();
+    assert_eq!(check.call(1).unwrap(), 2);
+    match check.call(-1) {
+        Err(RuntimeError::Error { data }) => {
+            assert_eq!(data.downcast_ref::<String>().unwrap(), "-1 is negative")
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let panic: Func<(), ()> = instance.func("panic").unwrap();
+    match panic.call() {
+        Err(RuntimeError::Error { data }) => {
+            assert_eq!(
+                *data.downcast_ref::<&str>().unwrap(),
+                "the host function panicked"
+            )
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let sum: Func<(), i64> = instance.func("sum").unwrap();
+    assert_eq!(sum.call().unwrap(), 36);
+}
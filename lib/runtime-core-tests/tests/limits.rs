@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use wasmer_runtime_core::{
+    compile_with,
+    error::{CreationError, Error},
+    imports,
+    limits::{ResourceLimits, StaticLimiter},
+    typed_func::Func,
+    units::{Bytes, Pages},
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (memory 1)
+  (table 2 anyfunc)
+  (func (export "grow") (param i32) (result i32)
+    get_local 0
+    memory.grow))
+"#;
+
+#[test]
+fn limits_memory_growth_and_instances() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let limiter = Arc::new(StaticLimiter::new(ResourceLimits {
+        max_memory: Some(Pages(3).bytes()),
+        max_table_elements: Some(2),
+        max_instances: Some(1),
+    }));
+    let mut import_object = imports! {};
+    import_object.set_limiter(limiter.clone());
+
+    let instance = module.instantiate(&import_object).unwrap();
+    assert_eq!(limiter.instances(), 1);
+    match module.instantiate(&import_object) {
+        Err(Error::CreationError(CreationError::LimitExceeded(_))) => (),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    let grow: Func<i32, i32> = instance.func("grow").unwrap();
+    assert_eq!(grow.call(2).unwrap(), 1);
+    assert_eq!(grow.call(1).unwrap(), -1);
+
+    drop(instance);
+    assert_eq!(limiter.instances(), 0);
+    module.instantiate(&import_object).unwrap();
+}
+
+#[test]
+fn limits_initial_sizes() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+
+    for limits in &[
+        ResourceLimits {
+            max_memory: Some(Bytes(1000)),
+            ..Default::default()
+        },
+        ResourceLimits {
+            max_table_elements: Some(1),
+            ..Default::default()
+        },
+    ] {
+        let mut import_object = imports! {};
+        import_object.set_limiter(Arc::new(StaticLimiter::new(*limits)));
+        match module.instantiate(&import_object) {
+            Err(Error::CreationError(CreationError::LimitExceeded(_))) => (),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use wasmer_runtime_core::{
+    compile_with, imports, memory::Memory, types::MemoryDescriptor, units::Pages,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+// Written by hand, since `wat2wasm` doesn't accept more than one memory or 64-bit memories.
+const HEADER: &[u8] = &[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+/// Two memories of one page.
+const MEMORY_SECTION: &[u8] = &[0x05, 0x05, 0x02, 0x00, 0x01, 0x00, 0x01];
+/// `(import "env" "memory" (memory 1))`
+const IMPORT_SECTION: &[u8] = &[
+    0x02, 0x0f, 0x01, 0x03, b'e', b'n', b'v', 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+    0x01,
+];
+/// One memory of one page.
+const ONE_MEMORY_SECTION: &[u8] = &[0x05, 0x03, 0x01, 0x00, 0x01];
+/// A 64-bit memory of one page, with the memory64 limits flag.
+const MEMORY64_SECTION: &[u8] = &[0x05, 0x03, 0x01, 0x04, 0x01];
+/// `(import "env" "memory" (memory i64 1))`
+const MEMORY64_IMPORT_SECTION: &[u8] = &[
+    0x02, 0x0f, 0x01, 0x03, b'e', b'n', b'v', 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x04,
+    0x01,
+];
+
+fn assert_rejected(wasm: &[u8], reason: &str) {
+    match compile_with(wasm, &get_compiler()) {
+        Err(e) => {
+            let msg = format!("{:?}", e);
+            assert!(msg.contains(reason), "unexpected error: {}", msg);
+        }
+        Ok(_) => panic!("the module was compiled"),
+    }
+}
+
+#[test]
+fn modules_with_multiple_memories_are_rejected() {
+    assert_rejected(&[HEADER, MEMORY_SECTION].concat(), "memories");
+    assert_rejected(
+        &[HEADER, IMPORT_SECTION, ONE_MEMORY_SECTION].concat(),
+        "memories",
+    );
+}
+
+#[test]
+fn modules_with_64_bit_memories_are_rejected() {
+    // `wasmparser` rejects the memory64 limits flag itself.
+    assert!(compile_with(&[HEADER, MEMORY64_SECTION].concat(), &get_compiler()).is_err());
+    assert!(compile_with(&[HEADER, MEMORY64_IMPORT_SECTION].concat(), &get_compiler()).is_err());
+}
+
+#[test]
+fn memory_count() {
+    let wasm_binary = wat2wasm(
+        r#"
+        (module
+          (import "env" "memory" (memory 1))
+          (func (export "noop")))
+        "#
+        .as_bytes(),
+    )
+    .expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "memory" => memory,
+        },
+    };
+    let mut instance = module.instantiate(&import_object).unwrap();
+    let ctx = instance.context_mut();
+    assert_eq!(ctx.memory_count(), 1);
+    assert_eq!(ctx.memory(0).size(), Pages(1));
+}
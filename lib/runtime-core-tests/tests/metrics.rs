@@ -0,0 +1,76 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use wasmer_runtime_core::{
+    compile_with, func, imports,
+    metrics::RuntimeMetrics,
+    typed_func::{Func, WasmTrapInfo},
+    units::Pages,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[derive(Default)]
+struct Recorder {
+    instantiations: Mutex<u32>,
+    grows: Mutex<Vec<(u32, u32)>>,
+    traps: Mutex<Vec<WasmTrapInfo>>,
+    host_calls: Mutex<u32>,
+}
+
+impl RuntimeMetrics for Recorder {
+    fn instantiated(&self, _latency: Duration) {
+        *self.instantiations.lock().unwrap() += 1;
+    }
+
+    fn memory_grown(&self, old: Pages, new: Pages) {
+        self.grows.lock().unwrap().push((old.0, new.0));
+    }
+
+    fn trapped(&self, trap: &WasmTrapInfo) {
+        self.traps.lock().unwrap().push(*trap);
+    }
+
+    fn host_call(&self) {
+        *self.host_calls.lock().unwrap() += 1;
+    }
+}
+
+#[test]
+fn metrics_are_recorded_per_import_object() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "host" (func $host))
+  (memory 1)
+
+  (func (export "run")
+    call $host
+    i32.const 2
+    memory.grow
+    drop)
+  (func (export "trap")
+    unreachable))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let recorder = Arc::new(Recorder::default());
+    let mut import_object = imports! {
+        "env" => {
+            "host" => func!(|| {}),
+        },
+    };
+    import_object.set_metrics(recorder.clone());
+
+    let instance = module.instantiate(&import_object).unwrap();
+    assert_eq!(*recorder.instantiations.lock().unwrap(), 1);
+
+    let run: Func<(), ()> = instance.func("run").unwrap();
+    run.call().unwrap();
+    assert_eq!(*recorder.host_calls.lock().unwrap(), 1);
+    assert_eq!(*recorder.grows.lock().unwrap(), vec![(1, 3)]);
+
+    let trap: Func<(), ()> = instance.func("trap").unwrap();
+    assert!(trap.call().is_err());
+    assert_eq!(recorder.traps.lock().unwrap().len(), 1);
+}
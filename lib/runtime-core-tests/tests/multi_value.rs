@@ -0,0 +1,81 @@
+#![cfg(feature = "backend-cranelift")]
+
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config, func, imports,
+    typed_func::Func,
+};
+use wasmer_runtime_core_tests::get_compiler;
+
+const MODULE: &str = r#"
+(module
+  (import "env" "divmod" (func $divmod (param i32 i32) (result i32 i32)))
+  (import "env" "split" (func $split (param f64) (result f32 f32)))
+  (func (export "divmod") (param i32 i32) (result i32 i32)
+    local.get 0
+    local.get 1
+    call $divmod)
+  (func (export "split") (param f64) (result f32 f32)
+    local.get 0
+    call $split)
+  (func (export "sum16")
+    (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)
+    (result i64 i32)
+    (i64.extend_i32_s
+      (i32.add (local.get 0) (i32.add (local.get 1) (i32.add (local.get 2)
+      (i32.add (local.get 3) (i32.add (local.get 4) (i32.add (local.get 5)
+      (i32.add (local.get 6) (i32.add (local.get 7) (i32.add (local.get 8)
+      (i32.add (local.get 9) (i32.add (local.get 10) (i32.add (local.get 11)
+      (i32.add (local.get 12) (i32.add (local.get 13) (i32.add (local.get 14)
+        (local.get 15)))))))))))))))))
+    i32.const 16))
+"#;
+
+#[rustfmt::skip]
+type SixteenI32s = (i32, i32, i32, i32, i32, i32, i32, i32, i32, i32, i32, i32, i32, i32, i32, i32);
+
+fn divmod(a: i32, b: i32) -> (i32, i32) {
+    (a / b, a % b)
+}
+
+fn split(x: f64) -> (f32, f32) {
+    let high = x as f32;
+    (high, (x - f64::from(high)) as f32)
+}
+
+#[test]
+fn tuple_returns_through_host_and_wasm_functions() {
+    let mut wabt_features = wabt::Features::new();
+    wabt_features.enable_multi_value();
+    let wasm_binary = wabt::wat2wasm_with_features(MODULE.as_bytes(), wabt_features)
+        .expect("WAST not valid or malformed");
+    let config = CompilerConfig {
+        features: Features {
+            multi_value: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let module = compile_with_config(&wasm_binary, &get_compiler(), config).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "divmod" => func!(divmod),
+            "split" => func!(split),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+
+    let divmod: Func<(i32, i32), (i32, i32)> = instance.func("divmod").unwrap();
+    assert_eq!(divmod.call(17, 5).unwrap(), (3, 2));
+
+    let split: Func<f64, (f32, f32)> = instance.func("split").unwrap();
+    assert_eq!(split.call(1.5).unwrap(), (1.5, 0.0));
+
+    let sum16: Func<SixteenI32s, (i64, i32)> = instance.func("sum16").unwrap();
+    assert_eq!(
+        sum16
+            .call(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16)
+            .unwrap(),
+        (136, 16)
+    );
+}
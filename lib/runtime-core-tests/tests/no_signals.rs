@@ -0,0 +1,88 @@
+use wasmer_runtime_core::{
+    backend::CompilerConfig, compile_with_config, error::RuntimeError, imports, typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (memory 1)
+  (table 1 anyfunc)
+  (type $t (func (result i32)))
+
+  (func (export "unreachable")
+    unreachable)
+  (func (export "div_s") (param i32 i32) (result i32)
+    get_local 0
+    get_local 1
+    i32.div_s)
+  (func (export "load") (param i32) (result i32)
+    get_local 0
+    i32.load)
+  (func (export "call_indirect") (param i32) (result i32)
+    get_local 0
+    call_indirect (type $t))
+  (func $recurse (export "recurse")
+    call $recurse))
+"#;
+
+#[cfg(feature = "backend-singlepass")]
+#[test]
+fn traps_without_signal_handlers() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let config = CompilerConfig::default().require_no_signals();
+    let module = compile_with_config(&wasm_binary, &get_compiler(), config).unwrap();
+    assert!(module.info().no_signals);
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    fn trap_message<T: std::fmt::Debug>(result: Result<T, RuntimeError>) -> String {
+        match result {
+            Err(RuntimeError::Trap { msg }) => msg.into(),
+            other => panic!("expected a trap, got {:?}", other),
+        }
+    }
+
+    let unreachable: Func<(), ()> = instance.func("unreachable").unwrap();
+    assert_eq!(trap_message(unreachable.call()), "unreachable");
+
+    let div_s: Func<(i32, i32), i32> = instance.func("div_s").unwrap();
+    assert_eq!(div_s.call(7, 2), Ok(3));
+    assert_eq!(
+        trap_message(div_s.call(7, 0)),
+        "illegal arithmetic operation"
+    );
+    assert_eq!(
+        trap_message(div_s.call(std::i32::MIN, -1)),
+        "illegal arithmetic operation"
+    );
+
+    let load: Func<i32, i32> = instance.func("load").unwrap();
+    assert_eq!(load.call(65_532), Ok(0));
+    assert_eq!(
+        trap_message(load.call(65_533)),
+        "memory out-of-bounds access"
+    );
+
+    let call_indirect: Func<i32, i32> = instance.func("call_indirect").unwrap();
+    assert_eq!(
+        trap_message(call_indirect.call(1)),
+        "`call_indirect` out-of-bounds"
+    );
+    assert_eq!(
+        trap_message(call_indirect.call(0)),
+        "incorrect `call_indirect` signature"
+    );
+
+    let recurse: Func<(), ()> = instance.func("recurse").unwrap();
+    assert_eq!(trap_message(recurse.call()), "unknown");
+
+    // the instance is still usable after its traps
+    assert_eq!(div_s.call(9, 3), Ok(3));
+}
+
+#[cfg(not(feature = "backend-singlepass"))]
+#[test]
+fn no_signals_is_refused() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let config = CompilerConfig::default().require_no_signals();
+    assert!(compile_with_config(&wasm_binary, &get_compiler(), config).is_err());
+}
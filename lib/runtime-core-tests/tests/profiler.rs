@@ -0,0 +1,66 @@
+#![cfg(unix)]
+
+use std::time::Duration;
+use wasmer_runtime_core::{compile_with, imports, profiler::ProfilingError};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (func $spin (param i32)
+    (loop $continue
+      get_local 0
+      i32.const 1
+      i32.sub
+      tee_local 0
+      br_if $continue))
+  (func (export "run") (param i32)
+    get_local 0
+    call $spin))
+"#;
+
+#[cfg(feature = "backend-singlepass")]
+#[test]
+fn samples_the_wasm_call_stack() {
+    use wasmer_runtime_core::{backend::CompilerConfig, compile_with_config, typed_func::Func};
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let config = CompilerConfig {
+        track_state: true,
+        ..Default::default()
+    };
+    let module = compile_with_config(&wasm_binary, &get_compiler(), config).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    instance.start_profiling(Duration::from_millis(1)).unwrap();
+    assert_eq!(
+        instance.start_profiling(Duration::from_millis(1)),
+        Err(ProfilingError::AlreadyProfiling)
+    );
+    let run: Func<i32, ()> = instance.func("run").unwrap();
+    run.call(200_000_000).unwrap();
+    let profile = instance.stop_profiling().unwrap();
+    assert!(instance.stop_profiling().is_none());
+
+    // the loop of `$spin` is where nearly all the samples are taken
+    assert!(profile.samples() > 0);
+    assert_eq!(profile.self_samples()[0].0, "func[0]");
+    for (stack, _) in profile.stacks() {
+        assert_eq!(stack[0], "run");
+    }
+
+    let mut folded = Vec::new();
+    profile.write_folded(&mut folded).unwrap();
+    let folded = String::from_utf8(folded).unwrap();
+    assert!(folded.lines().any(|line| line.starts_with("run;func[0] ")));
+}
+
+#[test]
+fn untracked_modules_cant_be_profiled() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    assert_eq!(
+        instance.start_profiling(Duration::from_millis(1)),
+        Err(ProfilingError::Unsupported)
+    );
+}
@@ -0,0 +1,83 @@
+use wasmer_runtime_core::{compile_with, export::Export, imports, typed_func::Func, units::Pages};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn reset_restores_the_initial_state() {
+    const MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 0) "\2a")
+  (global $counter (mut i32) (i32.const 0))
+
+  (func $start
+    i32.const 4
+    i32.const 7
+    i32.store8)
+  (start $start)
+
+  (func (export "bump") (result i32)
+    i32.const 0
+    i32.const 99
+    i32.store8
+    i32.const 1
+    memory.grow
+    drop
+    get_global $counter
+    i32.const 1
+    i32.add
+    set_global $counter
+    get_global $counter))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    let bump: Func<(), i32> = instance.func("bump").unwrap();
+    assert_eq!(bump.call(), Ok(1));
+    assert_eq!(bump.call(), Ok(2));
+    assert_eq!(instance.context().memory(0).size(), Pages(3));
+
+    instance.reset().unwrap();
+
+    let memory = instance.context().memory(0);
+    assert_eq!(memory.size(), Pages(1));
+    assert_eq!(memory.view::<u8>()[0].get(), 0x2a);
+    assert_eq!(memory.view::<u8>()[4].get(), 7);
+
+    let bump: Func<(), i32> = instance.func("bump").unwrap();
+    assert_eq!(bump.call(), Ok(1));
+}
+
+#[test]
+fn reset_shrinks_grown_memories_and_tables() {
+    const MODULE: &str = r#"
+(module
+  (memory (export "memory") 1 4)
+  (table (export "table") 1 4 anyfunc)
+  (func $f)
+  (elem (i32.const 0) $f))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    let table = match instance.get_export("table") {
+        Some(Export::Table(table)) => table,
+        _ => panic!("missing table"),
+    };
+    let element = table.get(0).unwrap();
+    instance.context().memory(0).grow(Pages(2)).unwrap();
+    table.grow(2).unwrap();
+    table.set(2, element).unwrap();
+    assert_eq!(instance.context().memory(0).size(), Pages(3));
+    assert_eq!(table.size(), 3);
+
+    instance.reset().unwrap();
+
+    assert_eq!(instance.context().memory(0).size(), Pages(1));
+    assert_eq!(table.size(), 1);
+    assert_eq!(table.get(0), Some(element));
+    assert_eq!(table.get(2), None);
+}
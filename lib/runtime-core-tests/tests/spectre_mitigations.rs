@@ -0,0 +1,85 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, MemoryStyle, SpectreMitigations},
+    compile_with_config, imports,
+    typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (memory 1)
+  (table 2 anyfunc)
+  (type $t (func (result i32)))
+  (elem (i32.const 0) $one)
+
+  (func $one (result i32)
+    i32.const 1)
+  (func (export "load") (param i32) (result i32)
+    get_local 0
+    i32.load)
+  (func (export "call_indirect") (param i32) (result i32)
+    get_local 0
+    call_indirect (type $t)))
+"#;
+
+#[cfg(not(feature = "backend-cranelift"))]
+fn config() -> CompilerConfig {
+    CompilerConfig {
+        memory_style: MemoryStyle::Dynamic,
+        spectre_mitigations: SpectreMitigations::all(),
+        ..Default::default()
+    }
+}
+
+#[cfg(feature = "backend-cranelift")]
+fn config() -> CompilerConfig {
+    CompilerConfig {
+        memory_style: MemoryStyle::Static,
+        spectre_mitigations: SpectreMitigations {
+            fence_indirect_calls: false,
+            ..SpectreMitigations::all()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn mitigations_keep_semantics() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with_config(&wasm_binary, &get_compiler(), config()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let load: Func<i32, i32> = instance.func("load").unwrap();
+    assert_eq!(load.call(0), Ok(0));
+    assert_eq!(load.call(65_532), Ok(0));
+    assert!(load.call(65_533).is_err());
+
+    let call_indirect: Func<i32, i32> = instance.func("call_indirect").unwrap();
+    assert_eq!(call_indirect.call(0), Ok(1));
+    assert!(call_indirect.call(1).is_err());
+    assert!(call_indirect.call(2).is_err());
+}
+
+#[cfg(feature = "backend-cranelift")]
+#[test]
+fn unsupported_mitigations_are_refused() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let fenced = CompilerConfig {
+        spectre_mitigations: SpectreMitigations {
+            fence_indirect_calls: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(compile_with_config(&wasm_binary, &get_compiler(), fenced).is_err());
+
+    let masked_dynamic = CompilerConfig {
+        memory_style: MemoryStyle::Dynamic,
+        spectre_mitigations: SpectreMitigations {
+            mask_memory_accesses: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(compile_with_config(&wasm_binary, &get_compiler(), masked_dynamic).is_err());
+}
@@ -0,0 +1,55 @@
+use wasmer_runtime_core::{compile_with, imports, Instance};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (memory 1)
+  (func $start
+    i32.const 4
+    i32.const 0
+    i32.load
+    i32.const 1
+    i32.add
+    i32.store)
+  (start $start))
+"#;
+
+fn memory_i32(instance: &Instance, index: usize) -> i32 {
+    instance.context().memory(0).view::<i32>()[index].get()
+}
+
+#[test]
+fn unstarted_instances_run_the_start_function_once_when_asked() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {};
+
+    let mut instance = module.instantiate_unstarted(&import_object).unwrap();
+    assert!(!instance.is_started());
+    assert_eq!(memory_i32(&instance, 1), 0);
+
+    // The host prepares the input of the start function.
+    instance.context().memory(0).view::<i32>()[0].set(41);
+    instance.run_start().unwrap();
+    assert!(instance.is_started());
+    assert_eq!(memory_i32(&instance, 1), 42);
+
+    instance.context().memory(0).view::<i32>()[0].set(99);
+    instance.run_start().unwrap();
+    assert_eq!(memory_i32(&instance, 1), 42);
+}
+
+#[test]
+fn instances_are_started_when_created() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {};
+
+    let mut instance = module.instantiate(&import_object).unwrap();
+    assert!(instance.is_started());
+    assert_eq!(memory_i32(&instance, 1), 1);
+
+    instance.context().memory(0).view::<i32>()[0].set(41);
+    instance.run_start().unwrap();
+    assert_eq!(memory_i32(&instance, 1), 1);
+}
@@ -0,0 +1,49 @@
+use wasmer_runtime_core::{compile_with, func, imports, typed_func::Func, InstanceStats};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn stats_account_for_the_instance_resources() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "host" (func $host))
+  (memory 1)
+  (table 3 anyfunc)
+
+  (func (export "run")
+    call $host
+    call $host
+    i32.const 1
+    memory.grow
+    drop))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "host" => func!(|| {}),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+    assert_eq!(
+        instance.stats(),
+        InstanceStats {
+            memory_bytes: 65_536,
+            table_elements: 3,
+            host_calls: 0,
+            points_used: None,
+        }
+    );
+
+    let run: Func<(), ()> = instance.func("run").unwrap();
+    run.call().unwrap();
+    assert_eq!(
+        instance.stats(),
+        InstanceStats {
+            memory_bytes: 2 * 65_536,
+            table_elements: 3,
+            host_calls: 2,
+            points_used: None,
+        }
+    );
+}
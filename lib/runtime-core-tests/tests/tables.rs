@@ -0,0 +1,60 @@
+use wasmer_runtime_core::{
+    compile_with,
+    export::Export,
+    import::{IsExport, LikeNamespace},
+    imports,
+    typed_func::Func,
+    types::FuncRef,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (type $type (func (param i32) (result i32)))
+  (import "env" "add_shift" (func $add_shift (type $type)))
+  (table (export "table") 1 anyfunc)
+  (export "add_shift" (func $add_shift))
+  (func (export "call") (param i32) (result i32)
+    get_local 0
+    i32.const 0
+    call_indirect (type $type)))
+"#;
+
+#[test]
+fn host_function_called_through_a_table() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let shift = 100;
+    let import_object = imports! {
+        "env" => {
+            "add_shift" => Func::new(move |n: i32| -> i32 { n + shift }),
+        },
+    };
+    let mut instance = module.instantiate(&import_object).unwrap();
+
+    // The import, bound to the instance.
+    let import = import_object.get_export("env", "add_shift").unwrap();
+    assert!(FuncRef::from_export(&import).is_none());
+    let bound = FuncRef::from_host_func(&import, instance.context_mut()).unwrap();
+
+    // A host function the instance doesn't import can't find its environment.
+    let other = Func::new(move |n: i32| -> i32 { n * shift }).to_export();
+    assert!(FuncRef::from_host_func(&other, instance.context_mut()).is_none());
+
+    let table = match instance.get_export("table") {
+        Some(Export::Table(table)) => table,
+        _ => panic!("missing table"),
+    };
+    let call: Func<i32, i32> = instance.func("call").unwrap();
+
+    // The import, taken from the exports of the instance.
+    let export = instance.get_export("add_shift").unwrap();
+    table
+        .set(0, FuncRef::from_export(&export).unwrap())
+        .unwrap();
+    assert_eq!(call.call(1).unwrap(), 101);
+
+    table.set(0, bound).unwrap();
+    assert_eq!(table.get(0), Some(bound));
+    assert_eq!(call.call(2).unwrap(), 102);
+}
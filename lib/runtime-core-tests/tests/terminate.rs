@@ -0,0 +1,37 @@
+#![cfg(all(unix, feature = "backend-singlepass"))]
+
+use std::{thread, time::Duration};
+use wasmer_runtime_core::{
+    compile_with,
+    error::RuntimeError,
+    fault::{terminate_wasm, WasmTerminated},
+    imports,
+    typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn terminates_a_running_loop() {
+    const MODULE: &str = r#"
+(module
+  (func (export "spin")
+    (loop $continue
+      br $continue)))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    thread::spawn(|| {
+        thread::sleep(Duration::from_millis(50));
+        terminate_wasm();
+    });
+    let spin: Func<(), ()> = instance.func("spin").unwrap();
+    match spin.call() {
+        Err(RuntimeError::Error { data }) => {
+            assert!(data.downcast_ref::<WasmTerminated>().is_some())
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,89 @@
+use wasmer_runtime_core::{compile_with, func, imports, vm::Ctx};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+use wasmer_values::{read_value, write_value, Json, TypedCaller, ValueError};
+
+const MODULE: &str = r#"
+(module
+  (import "env" "shout" (func $shout (param i32) (result i32)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (data (i32.const 0) "hello ")
+
+  (func $allocate (export "allocate") (param $size i32) (result i32)
+    (local $ptr i32)
+    (set_local $ptr (get_global $next))
+    (set_global $next (i32.add (get_global $next) (get_local $size)))
+    (get_local $ptr))
+  (func (export "deallocate") (param i32 i32))
+
+  (func $copy (param $dst i32) (param $src i32) (param $len i32)
+    (block $done
+      (loop $bytes
+        (br_if $done (i32.eqz (get_local $len)))
+        (i32.store8 (get_local $dst) (i32.load8_u (get_local $src)))
+        (set_local $dst (i32.add (get_local $dst) (i32.const 1)))
+        (set_local $src (i32.add (get_local $src) (i32.const 1)))
+        (set_local $len (i32.sub (get_local $len) (i32.const 1)))
+        (br $bytes))))
+
+  (func (export "greet") (param $name i32) (result i32)
+    (local $len i32)
+    (local $greeting i32)
+    (set_local $len (i32.load (get_local $name)))
+    (set_local $greeting (call $allocate (i32.add (get_local $len) (i32.const 10))))
+    (i32.store (get_local $greeting) (i32.add (get_local $len) (i32.const 6)))
+    (call $copy (i32.add (get_local $greeting) (i32.const 4)) (i32.const 0) (i32.const 6))
+    (call $copy
+      (i32.add (get_local $greeting) (i32.const 10))
+      (i32.add (get_local $name) (i32.const 4))
+      (get_local $len))
+    (get_local $greeting))
+
+  (func (export "echo") (param $value i32) (result i32)
+    (local $size i32)
+    (local $copy i32)
+    (set_local $size (i32.add (i32.load (get_local $value)) (i32.const 4)))
+    (set_local $copy (call $allocate (get_local $size)))
+    (call $copy (get_local $copy) (get_local $value) (get_local $size))
+    (get_local $copy))
+
+  (func (export "shout") (param i32) (result i32)
+    (call $shout (get_local 0))))
+"#;
+
+fn shout(ctx: &mut Ctx, message: u32) -> Result<u32, ValueError> {
+    let message: String = read_value(ctx, message)?;
+    write_value(ctx, &message.to_uppercase())
+}
+
+#[test]
+fn values_are_passed_to_and_from_the_guest() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let import_object = imports! {
+        "env" => {
+            "shout" => func!(shout),
+        },
+    };
+    let instance = module.instantiate(&import_object).unwrap();
+    let mut caller = TypedCaller::new(&instance);
+
+    let greeting: String = caller.call("greet", &[&"world"]).unwrap();
+    assert_eq!(greeting, "hello world");
+
+    let bytes: Vec<u8> = caller.call("echo", &[&vec![0u8, 1, 255]]).unwrap();
+    assert_eq!(bytes, [0, 1, 255]);
+
+    let Json(pair): Json<(u32, String)> = caller
+        .call("echo", &[&Json((7, "seven".to_string()))])
+        .unwrap();
+    assert_eq!(pair, (7, "seven".to_string()));
+
+    let shouted: String = caller.call("shout", &[&"quiet"]).unwrap();
+    assert_eq!(shouted, "QUIET");
+
+    match caller.call::<Json<u32>>("greet", &[&"world"]) {
+        Err(ValueError::Json(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
@@ -24,11 +24,25 @@ fn main() {
         .write_all(hash_string.as_bytes())
         .expect("Could not write to file for wasmer hash value");
 
+    let version_meta = rustc_version::version_meta().unwrap();
+
     // Enable "nightly" cfg if the current compiler is nightly.
-    if rustc_version::version_meta().unwrap().channel == rustc_version::Channel::Nightly {
+    if version_meta.channel == rustc_version::Channel::Nightly {
         println!("cargo:rustc-cfg=nightly");
     }
 
+    // Backend plugins must be built with the same compiler, see `plugin`.
+    println!(
+        "cargo:rustc-env=WASMER_RUSTC_VERSION={}",
+        version_meta.semver
+    );
+
+    // The functions traps unwind through get the "C-unwind" ABI once rustc
+    // has it, see `plugin`.
+    if (version_meta.semver.major, version_meta.semver.minor) >= (1, 71) {
+        println!("cargo:rustc-cfg=c_unwind");
+    }
+
     let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
     let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
 
@@ -0,0 +1,475 @@
+//! Suspending and resuming guest execution with Binaryen's Asyncify.
+//!
+//! A module transformed with `wasm-opt --asyncify` exports functions that
+//! unwind its call stack into a buffer in linear memory and rewind it from
+//! there later.  A host function yields back to the embedder with
+//! [`Ctx::suspend`]: the call started with [`Instance::call_asyncify`] then
+//! returns a [`SuspendedCall`], which [`Instance::resume`] continues where it
+//! left off.
+//!
+//! Every suspended call needs its own [`AsyncifyBuffer`], so many guest
+//! "green threads" can be scheduled cooperatively on one host thread.  Calls
+//! suspended in the same instance also share the guest's own data, e.g. its
+//! shadow stack, so green threads usually get an instance each.
+//!
+//! Async host functions, created with [`Func::new_async`], suspend the call
+//! the same way while their future runs.  [`Instance::call_async`] awaits
+//! those futures on the embedder's executor and resumes the call once they
+//! complete.
+//!
+//! [`Func::new_async`]: crate::typed_func::Func
+
+use crate::{
+    error::CallError,
+    instance::{call_func_with_index, Instance},
+    module::{ExportIndex, ModuleInfo},
+    typed_func::WasmTypeList,
+    types::Value,
+    vm::{Ctx, InternalField},
+};
+use std::{any::Any, fmt, future::Future, pin::Pin};
+
+const START_UNWIND: &str = "asyncify_start_unwind";
+const STOP_UNWIND: &str = "asyncify_stop_unwind";
+const START_REWIND: &str = "asyncify_start_rewind";
+const STOP_REWIND: &str = "asyncify_stop_rewind";
+const GET_STATE: &str = "asyncify_get_state";
+
+/// The buffer of the call currently running, see `AsyncifyBuffer::to_internal`.
+static CURRENT_BUFFER: InternalField = InternalField::allocate();
+
+/// A `Box<Pending>` handed between an async host function and
+/// `Instance::call_async`, or 0.
+static PENDING: InternalField = InternalField::allocate();
+
+/// Size of the header Asyncify keeps at the start of a buffer: the current
+/// and the end address of the unwound stack.
+const BUFFER_HEADER_SIZE: u32 = 8;
+
+/// Returns true if the module was transformed by Asyncify, i.e. it exports
+/// the functions that drive unwinding and rewinding.
+pub fn is_asyncified(info: &ModuleInfo) -> bool {
+    [
+        START_UNWIND,
+        STOP_UNWIND,
+        START_REWIND,
+        STOP_REWIND,
+        GET_STATE,
+    ]
+    .iter()
+    .all(|name| match info.exports.get(*name) {
+        Some(ExportIndex::Func(_)) => true,
+        _ => false,
+    })
+}
+
+/// The state of an asyncified instance, as reported by `asyncify_get_state`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AsyncifyState {
+    /// Running normally.
+    Normal,
+    /// Unwinding the call stack into the buffer.
+    Unwinding,
+    /// Rewinding the call stack from the buffer.
+    Rewinding,
+}
+
+/// The region of linear memory a call's stack is unwound into.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AsyncifyBuffer {
+    /// The address of the buffer; it must be aligned to 4 bytes.
+    pub addr: u32,
+    /// The size of the buffer in bytes, including Asyncify's 8 byte header.
+    pub len: u32,
+}
+
+impl AsyncifyBuffer {
+    fn validate(self, memory_size: usize) -> Result<Self, AsyncifyError> {
+        let end = u64::from(self.addr) + u64::from(self.len);
+        if self.addr % 4 != 0 || self.len <= BUFFER_HEADER_SIZE || end > memory_size as u64 {
+            return Err(AsyncifyError::InvalidBuffer(self));
+        }
+        Ok(self)
+    }
+
+    /// Buffers are stored in an internal field as `len << 32 | addr`; a
+    /// valid buffer is never 0, which means there is none.
+    fn to_internal(self) -> u64 {
+        u64::from(self.len) << 32 | u64::from(self.addr)
+    }
+
+    fn from_internal(value: u64) -> Option<Self> {
+        if value == 0 {
+            return None;
+        }
+        Some(Self {
+            addr: value as u32,
+            len: (value >> 32) as u32,
+        })
+    }
+}
+
+/// What a host function that called [`Ctx::suspend`] has to do next.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Suspend {
+    /// The guest is being unwound: return right away, the return value is
+    /// ignored.  The host function is called again on resume.
+    Unwinding,
+    /// The call was resumed: carry on and return the real result.
+    Resumed,
+}
+
+/// The outcome of a call that may be suspended.
+#[derive(Debug)]
+pub enum AsyncifyCall {
+    /// The call returned these values.
+    Returned(Vec<Value>),
+    /// A host function suspended the call.
+    Suspended(SuspendedCall),
+}
+
+/// A call suspended by a host function, to be continued with
+/// [`Instance::resume`].
+#[derive(Debug)]
+pub struct SuspendedCall {
+    name: String,
+    args: Vec<Value>,
+    buffer: AsyncifyBuffer,
+}
+
+impl SuspendedCall {
+    /// The name of the export that was called.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The buffer the call's stack is unwound into.
+    pub fn buffer(&self) -> AsyncifyBuffer {
+        self.buffer
+    }
+}
+
+/// Error returned when a call can't be suspended or resumed.
+#[derive(Debug)]
+pub enum AsyncifyError {
+    /// The module wasn't transformed by Asyncify.
+    NotAsyncified,
+    /// The buffer isn't aligned, is too small or is out of bounds.
+    InvalidBuffer(AsyncifyBuffer),
+    /// `Ctx::suspend` was called outside of `Instance::call_asyncify` or
+    /// `Instance::resume`.
+    NoBuffer,
+    /// The instance has no memory to unwind the call's stack into.
+    NoMemory,
+    /// The instance isn't in the state the operation requires.
+    UnexpectedState(AsyncifyState),
+    /// An async host function was resumed before its future completed, e.g.
+    /// by `Instance::resume` instead of `Instance::call_async`.
+    NotAwaited,
+    /// The output an async host function was resumed with isn't of its
+    /// return type.
+    OutputMismatch,
+    /// Calling the guest failed.
+    Call(CallError),
+}
+
+impl fmt::Display for AsyncifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsyncifyError::NotAsyncified => write!(f, "the module wasn't transformed by Asyncify"),
+            AsyncifyError::InvalidBuffer(buffer) => write!(
+                f,
+                "invalid Asyncify buffer of {} bytes at {:#x}",
+                buffer.len, buffer.addr
+            ),
+            AsyncifyError::NoBuffer => write!(f, "the call can't be suspended"),
+            AsyncifyError::NoMemory => write!(f, "the instance has no memory"),
+            AsyncifyError::UnexpectedState(state) => {
+                write!(f, "unexpected Asyncify state {:?}", state)
+            }
+            AsyncifyError::NotAwaited => write!(
+                f,
+                "an async host function was resumed before its future completed"
+            ),
+            AsyncifyError::OutputMismatch => {
+                write!(f, "an async host function was resumed with another output")
+            }
+            AsyncifyError::Call(e) => write!(f, "call failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AsyncifyError {}
+
+impl From<CallError> for AsyncifyError {
+    fn from(e: CallError) -> Self {
+        AsyncifyError::Call(e)
+    }
+}
+
+fn get_state(values: Vec<Value>) -> Result<AsyncifyState, AsyncifyError> {
+    match values.as_slice() {
+        [Value::I32(0)] => Ok(AsyncifyState::Normal),
+        [Value::I32(1)] => Ok(AsyncifyState::Unwinding),
+        [Value::I32(2)] => Ok(AsyncifyState::Rewinding),
+        _ => Err(AsyncifyError::NotAsyncified),
+    }
+}
+
+/// The future of an async host function while the guest is parked, then its
+/// output while the call is resumed.
+enum Pending {
+    Future(Pin<Box<dyn Future<Output = Box<dyn Any>>>>),
+    Output(Box<dyn Any>),
+}
+
+fn take_pending(ctx: &mut Ctx) -> Option<Box<Pending>> {
+    let pending = ctx.get_internal(&PENDING) as usize as *mut Pending;
+    if pending.is_null() {
+        return None;
+    }
+    ctx.set_internal(&PENDING, 0);
+    Some(unsafe { Box::from_raw(pending) })
+}
+
+fn set_pending(ctx: &mut Ctx, pending: Pending) {
+    drop(take_pending(ctx));
+    ctx.set_internal(&PENDING, Box::into_raw(Box::new(pending)) as usize as u64);
+}
+
+/// Drops the future an async host function left pending, when its instance
+/// is dropped while the call is suspended.
+pub(crate) fn discard_pending(ctx: *mut Ctx) {
+    drop(take_pending(unsafe { &mut *ctx }));
+}
+
+/// The body of the host functions created by `Func::new_async`: `start`
+/// creates the future the guest is suspended on, and the future's output is
+/// returned once the call is resumed.
+pub(crate) fn call_async_host_func<Rets, Fut, F>(
+    ctx: &mut Ctx,
+    start: F,
+) -> Result<Rets, AsyncifyError>
+where
+    Rets: WasmTypeList + 'static,
+    Fut: Future<Output = Rets> + 'static,
+    F: FnOnce(&mut Ctx) -> Fut,
+{
+    match take_pending(ctx).map(|pending| *pending) {
+        Some(Pending::Output(output)) => {
+            ctx.suspend()?;
+            output
+                .downcast::<Rets>()
+                .map(|output| *output)
+                .map_err(|_| AsyncifyError::OutputMismatch)
+        }
+        // Only `Instance::call_async` awaits the future before resuming.
+        Some(Pending::Future(_)) => Err(AsyncifyError::NotAwaited),
+        None => {
+            let future = start(ctx);
+            ctx.suspend()?;
+            set_pending(
+                ctx,
+                Pending::Future(Box::pin(
+                    async move { Box::new(future.await) as Box<dyn Any> },
+                )),
+            );
+            // ignored, the guest is unwinding
+            Ok(Rets::from_ret_array(Rets::empty_ret_array()))
+        }
+    }
+}
+
+fn memory_size(ctx: &Ctx) -> Result<usize, AsyncifyError> {
+    if ctx.memory_count() == 0 {
+        return Err(AsyncifyError::NoMemory);
+    }
+    Ok(ctx.memory(0).size().bytes().0)
+}
+
+impl Instance {
+    /// The Asyncify state of the instance.
+    pub fn asyncify_state(&self) -> Result<AsyncifyState, AsyncifyError> {
+        if !is_asyncified(&self.module.info) {
+            return Err(AsyncifyError::NotAsyncified);
+        }
+        get_state(self.call(GET_STATE, &[])?)
+    }
+
+    /// Call the export `name` with `args`, allowing host functions to suspend
+    /// it with [`Ctx::suspend`], in which case its stack is unwound into
+    /// `buffer`.
+    pub fn call_asyncify(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        buffer: AsyncifyBuffer,
+    ) -> Result<AsyncifyCall, AsyncifyError> {
+        match self.asyncify_state()? {
+            AsyncifyState::Normal => (),
+            state => return Err(AsyncifyError::UnexpectedState(state)),
+        }
+        buffer.validate(memory_size(self.context())?)?;
+
+        self.run_asyncify(name, args, buffer)
+    }
+
+    /// Continue a call suspended by a host function.  The host function is
+    /// called again, and [`Ctx::suspend`] returns [`Suspend::Resumed`].
+    pub fn resume(&mut self, call: SuspendedCall) -> Result<AsyncifyCall, AsyncifyError> {
+        match self.asyncify_state()? {
+            AsyncifyState::Normal => (),
+            state => return Err(AsyncifyError::UnexpectedState(state)),
+        }
+
+        self.call(START_REWIND, &[Value::I32(call.buffer.addr as i32)])?;
+        self.run_asyncify(&call.name, &call.args, call.buffer)
+    }
+
+    /// Call the export `name` with `args`, awaiting the futures of the async
+    /// host functions it calls; see [`Func::new_async`].
+    ///
+    /// The guest is parked in `buffer` while a future runs.  Calls suspended
+    /// with [`Ctx::suspend`] instead are resumed right away.
+    ///
+    /// [`Func::new_async`]: crate::typed_func::Func
+    pub async fn call_async(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        buffer: AsyncifyBuffer,
+    ) -> Result<Vec<Value>, AsyncifyError> {
+        let mut call = self.call_asyncify(name, args, buffer);
+        loop {
+            let suspended = match call {
+                Ok(AsyncifyCall::Returned(values)) => return Ok(values),
+                Ok(AsyncifyCall::Suspended(suspended)) => suspended,
+                Err(e) => {
+                    drop(take_pending(self.context_mut()));
+                    return Err(e);
+                }
+            };
+            if let Some(Pending::Future(future)) = take_pending(self.context_mut()).map(|p| *p) {
+                let output = future.await;
+                set_pending(self.context_mut(), Pending::Output(output));
+            }
+            call = self.resume(suspended);
+        }
+    }
+
+    fn run_asyncify(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        buffer: AsyncifyBuffer,
+    ) -> Result<AsyncifyCall, AsyncifyError> {
+        self.context_mut()
+            .set_internal(&CURRENT_BUFFER, buffer.to_internal());
+        let result = self.call(name, args);
+        self.context_mut().set_internal(&CURRENT_BUFFER, 0);
+
+        let values = result?;
+        match self.asyncify_state()? {
+            AsyncifyState::Normal => Ok(AsyncifyCall::Returned(values)),
+            AsyncifyState::Unwinding => {
+                self.call(STOP_UNWIND, &[])?;
+                Ok(AsyncifyCall::Suspended(SuspendedCall {
+                    name: name.to_string(),
+                    args: args.to_vec(),
+                    buffer,
+                }))
+            }
+            state => Err(AsyncifyError::UnexpectedState(state)),
+        }
+    }
+}
+
+impl Ctx {
+    /// Suspend the call into the instance, from a host function.
+    ///
+    /// The first time, the guest starts unwinding and the host function must
+    /// return right away.  When the call is resumed, the host function is
+    /// called again with the same arguments, and this returns
+    /// [`Suspend::Resumed`].
+    pub fn suspend(&mut self) -> Result<Suspend, AsyncifyError> {
+        if !is_asyncified(unsafe { &(*self.module).info }) {
+            return Err(AsyncifyError::NotAsyncified);
+        }
+
+        match get_state(self.call_export(GET_STATE, &[])?)? {
+            AsyncifyState::Rewinding => {
+                self.call_export(STOP_REWIND, &[])?;
+                Ok(Suspend::Resumed)
+            }
+            AsyncifyState::Normal => {
+                let buffer = AsyncifyBuffer::from_internal(self.get_internal(&CURRENT_BUFFER))
+                    .ok_or(AsyncifyError::NoBuffer)?
+                    .validate(memory_size(self)?)?;
+                let view = self.memory(0).view::<u32>();
+                let header = (buffer.addr / 4) as usize;
+                view[header].set(buffer.addr + BUFFER_HEADER_SIZE);
+                view[header + 1].set(buffer.addr + buffer.len);
+
+                self.call_export(START_UNWIND, &[Value::I32(buffer.addr as i32)])?;
+                Ok(Suspend::Unwinding)
+            }
+            state => Err(AsyncifyError::UnexpectedState(state)),
+        }
+    }
+
+    fn call_export(&mut self, name: &str, args: &[Value]) -> Result<Vec<Value>, CallError> {
+        let module = unsafe { &*self.module };
+        let func_index = match module.info.exports.get(name) {
+            Some(ExportIndex::Func(func_index)) => *func_index,
+            _ => unreachable!("asyncified modules export {}", name),
+        };
+
+        let mut results = Vec::new();
+        call_func_with_index(
+            &module.info,
+            &**module.runnable_module,
+            unsafe { &*self.import_backing },
+            self,
+            func_index,
+            args,
+            &mut results,
+        )?;
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn asyncify_buffer() {
+        let buffer = AsyncifyBuffer {
+            addr: 1024,
+            len: 4096,
+        };
+        assert_eq!(buffer.validate(65536).ok(), Some(buffer));
+        assert_eq!(
+            AsyncifyBuffer::from_internal(buffer.to_internal()),
+            Some(buffer)
+        );
+        assert_eq!(AsyncifyBuffer::from_internal(0), None);
+
+        for invalid in &[
+            AsyncifyBuffer {
+                addr: 1022,
+                len: 4096,
+            },
+            AsyncifyBuffer { addr: 1024, len: 8 },
+            AsyncifyBuffer {
+                addr: 65532,
+                len: 16,
+            },
+        ] {
+            match invalid.validate(65536) {
+                Err(AsyncifyError::InvalidBuffer(b)) => assert_eq!(&b, invalid),
+                _ => panic!("{:?} is invalid", invalid),
+            }
+        }
+    }
+}
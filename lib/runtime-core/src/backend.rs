@@ -15,7 +15,10 @@ use crate::{
 };
 use std::{any::Any, ptr::NonNull};
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::{fmt, str::FromStr};
+
+pub use target_lexicon::Triple;
 
 pub mod sys {
     pub use crate::sys::*;
@@ -28,6 +31,7 @@ pub enum Backend {
     Cranelift,
     Singlepass,
     LLVM,
+    Interpreter,
     Auto,
 }
 
@@ -41,6 +45,8 @@ impl Backend {
             "singlepass",
             #[cfg(feature = "backend-llvm")]
             "llvm",
+            #[cfg(feature = "backend-interpreter")]
+            "interpreter",
             "auto",
         ]
     }
@@ -52,6 +58,7 @@ impl Backend {
             Backend::Cranelift => "cranelift",
             Backend::Singlepass => "singlepass",
             Backend::LLVM => "llvm",
+            Backend::Interpreter => "interpreter",
             Backend::Auto => "auto",
         }
     }
@@ -70,12 +77,34 @@ impl std::str::FromStr for Backend {
             "singlepass" => Ok(Backend::Singlepass),
             "cranelift" => Ok(Backend::Cranelift),
             "llvm" => Ok(Backend::LLVM),
+            "interpreter" => Ok(Backend::Interpreter),
             "auto" => Ok(Backend::Auto),
             _ => Err(format!("The backend {} doesn't exist", s)),
         }
     }
 }
 
+/// Why [`Backend::Auto`] picked the backend that compiled a module, see
+/// [`CompileInfo::auto_selection`].
+///
+/// [`Backend::Auto`]: enum.Backend.html#variant.Auto
+/// [`CompileInfo::auto_selection`]: ../module/struct.CompileInfo.html#structfield.auto_selection
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AutoSelection {
+    /// Optimized code was asked for, so LLVM compiled the module.
+    Optimize,
+    /// The module is metered and small, so singlepass compiled it quickly.
+    SmallMetered,
+    /// The module is metered but large, so a backend generating faster code
+    /// than singlepass compiled it, while still supporting metering.
+    LargeMetered,
+    /// The module was compiled by the default backend, Cranelift.
+    Default,
+    /// The preferred backend isn't available on this platform or in this build,
+    /// so the module was compiled by the first one that is.
+    Fallback,
+}
+
 /// The target architecture for code generation.
 #[derive(Copy, Clone, Debug)]
 pub enum Architecture {
@@ -86,6 +115,163 @@ pub enum Architecture {
     Aarch64,
 }
 
+/// Returns whether code compiled for `triple` can run on this host.
+///
+/// Only the architecture and the operating system are compared, so e.g. the
+/// vendor of the triple doesn't matter.
+pub fn is_host_triple(triple: &str) -> bool {
+    let host = Triple::host();
+    match Triple::from_str(triple) {
+        Ok(triple) => {
+            triple.architecture == host.architecture
+                && triple.operating_system == host.operating_system
+        }
+        Err(_) => false,
+    }
+}
+
+/// A set of CPU features that generated code may use, named like LLVM's
+/// target features, e.g. `"sse4.2"`, `"avx2"` or `"neon"`.
+///
+/// It's displayed and parsed in LLVM's syntax, e.g. `+sse4.2,+avx2`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuFeatures {
+    features: BTreeSet<String>,
+}
+
+impl CpuFeatures {
+    /// The baseline features of the target, without any extension.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The features supported by the host CPU.
+    pub fn host() -> Self {
+        #[allow(unused_mut)]
+        let mut features = Self::new();
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            macro_rules! detect {
+                ($($feature:tt => $name:expr,)*) => {
+                    $(
+                        if is_x86_feature_detected!($feature) {
+                            features.insert($name);
+                        }
+                    )*
+                };
+            }
+            detect! {
+                "sse3" => "sse3",
+                "ssse3" => "ssse3",
+                "sse4.1" => "sse4.1",
+                "sse4.2" => "sse4.2",
+                "popcnt" => "popcnt",
+                "avx" => "avx",
+                "avx2" => "avx2",
+                "bmi1" => "bmi",
+                "bmi2" => "bmi2",
+                "lzcnt" => "lzcnt",
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        features.insert("neon");
+
+        features
+    }
+
+    /// Add `feature` to the set.
+    pub fn with(mut self, feature: &str) -> Self {
+        self.insert(feature);
+        self
+    }
+
+    /// Add `feature` to the set.
+    pub fn insert(&mut self, feature: &str) {
+        self.features.insert(feature.to_string());
+    }
+
+    /// Whether `feature` is in the set.
+    pub fn contains(&self, feature: &str) -> bool {
+        self.features.contains(feature)
+    }
+
+    /// The features in the set, in alphabetical order.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.features.iter().map(String::as_str)
+    }
+
+    /// The features in both sets, e.g. the features every machine of a fleet
+    /// has, to compile artifacts which run on all of them:
+    ///
+    /// ```
+    /// # use wasmer_runtime_core::backend::CpuFeatures;
+    /// let old = CpuFeatures::new().with("sse4.2").with("popcnt");
+    /// let new = CpuFeatures::new().with("sse4.2").with("popcnt").with("avx2");
+    /// assert_eq!(old.intersection(&new), old);
+    /// ```
+    pub fn intersection(&self, other: &CpuFeatures) -> Self {
+        Self {
+            features: self
+                .features
+                .intersection(&other.features)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The features of this set which aren't in `other`, e.g. the features
+    /// an artifact requires which the host doesn't have.
+    pub fn difference(&self, other: &CpuFeatures) -> Self {
+        Self {
+            features: self.features.difference(&other.features).cloned().collect(),
+        }
+    }
+
+    /// Whether every feature of this set is in `other`.
+    pub fn is_subset(&self, other: &CpuFeatures) -> bool {
+        self.features.is_subset(&other.features)
+    }
+
+    /// Whether the set has no features, i.e. it's the baseline of the target.
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+}
+
+impl fmt::Display for CpuFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, feature) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "+{}", feature)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CpuFeatures {
+    type Err = String;
+
+    /// Parse a comma-separated list of features, each optionally prefixed
+    /// with `+`; features prefixed with `-` are left out.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut features = Self::new();
+        for feature in s.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            if feature.starts_with('-') {
+                continue;
+            }
+            let name = feature.trim_start_matches('+');
+            if name.is_empty() {
+                return Err(format!("invalid CPU feature `{}`", feature));
+            }
+            features.insert(name);
+        }
+        Ok(features)
+    }
+}
+
 /// The type of an inline breakpoint.
 #[repr(u8)]
 #[derive(Copy, Clone, Debug)]
@@ -114,10 +300,62 @@ mod backend_test {
         // if this test breaks, think hard about why it's breaking
         // can we avoid having these be different?
 
-        for &backend in &[Backend::Cranelift, Backend::LLVM, Backend::Singlepass] {
+        for &backend in &[
+            Backend::Cranelift,
+            Backend::LLVM,
+            Backend::Singlepass,
+            Backend::Interpreter,
+        ] {
             assert_eq!(backend, Backend::from_str(backend.to_string()).unwrap());
         }
     }
+
+    #[test]
+    fn cpu_features_round_trip() {
+        let features = CpuFeatures::new().with("sse4.2").with("avx");
+        assert_eq!(features.to_string(), "+avx,+sse4.2");
+        assert_eq!(CpuFeatures::from_str("+avx,-avx2, sse4.2"), Ok(features));
+        assert!(CpuFeatures::from_str("+").is_err());
+    }
+
+    #[test]
+    fn cpu_features_set_operations() {
+        let old = CpuFeatures::new().with("sse4.2").with("popcnt");
+        let new = CpuFeatures::new().with("sse4.2").with("avx2");
+        assert_eq!(old.intersection(&new), CpuFeatures::new().with("sse4.2"));
+        assert_eq!(old.difference(&new), CpuFeatures::new().with("popcnt"));
+        assert!(old.intersection(&new).is_subset(&old));
+        assert!(!old.is_subset(&new));
+        assert!(old.difference(&old).is_empty());
+    }
+
+    #[test]
+    fn required_cpu_features() {
+        let config = CompilerConfig::default();
+        assert!(config.required_cpu_features(Backend::Cranelift).is_empty());
+        assert_eq!(
+            config.required_cpu_features(Backend::LLVM),
+            CpuFeatures::host()
+        );
+
+        let config = config.with_cpu_features(CpuFeatures::new().with("sse4.2"));
+        assert_eq!(
+            config.required_cpu_features(Backend::LLVM),
+            CpuFeatures::new().with("sse4.2")
+        );
+    }
+
+    #[test]
+    fn host_triple() {
+        assert!(is_host_triple(&Triple::host().to_string()));
+        assert!(!is_host_triple("not-a-triple"));
+        let other = if cfg!(target_arch = "aarch64") {
+            "x86_64-unknown-linux-gnu"
+        } else {
+            "aarch64-unknown-linux-gnu"
+        };
+        assert!(!is_host_triple(other));
+    }
 }
 
 /// This type cannot be constructed from
@@ -145,11 +383,184 @@ impl Default for MemoryBoundCheckMode {
     }
 }
 
+/// How the memories of a module are allocated, which decides whether the
+/// code compiled for it relies on guard pages or on explicit bounds checks.
+///
+/// Shared memories are always static.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryStyle {
+    /// Memories with a maximum size are static, and the others dynamic.
+    Default,
+    /// Every memory is static: it reserves 6GiB of virtual memory, and
+    /// out-of-bounds accesses fault on its guard pages, so the compiled code
+    /// doesn't bounds-check them.
+    Static,
+    /// Every memory is dynamic: it only maps its current size, and the
+    /// compiled code bounds-checks each access.  For hosts that can't afford
+    /// the reservations of static memories, e.g. without overcommit or with
+    /// a strict `RLIMIT_AS`.
+    Dynamic,
+}
+
+impl MemoryStyle {
+    /// The names of the memory styles, as parsed by `from_str`.
+    pub fn variants() -> &'static [&'static str] {
+        &["default", "static", "dynamic"]
+    }
+}
+
+impl Default for MemoryStyle {
+    fn default() -> MemoryStyle {
+        MemoryStyle::Default
+    }
+}
+
+impl std::str::FromStr for MemoryStyle {
+    type Err = String;
+    fn from_str(s: &str) -> Result<MemoryStyle, String> {
+        match s.to_lowercase().as_str() {
+            "default" => Ok(MemoryStyle::Default),
+            "static" => Ok(MemoryStyle::Static),
+            "dynamic" => Ok(MemoryStyle::Dynamic),
+            _ => Err(format!("The memory style {} doesn't exist", s)),
+        }
+    }
+}
+
+/// Opt-in mitigations against Spectre-style speculative execution attacks,
+/// for hosts that run untrusted modules next to each other or next to
+/// sensitive data.
+///
+/// None of these are enabled by default, as each of them costs performance:
+/// masking adds a compare and a conditional move to every checked access, and
+/// fencing stalls the pipeline on every `call_indirect`, which can slow down
+/// code that makes a lot of indirect calls by tens of percent.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpectreMitigations {
+    /// Clamp the address of a bounds-checked memory access, so that it can't
+    /// reach past the memory even when the bounds check is mispredicted.
+    ///
+    /// Only dynamic memories are bounds-checked; static memories rely on
+    /// their guard region, which a 32-bit index can't reach past.
+    pub mask_memory_accesses: bool,
+    /// Clamp the table index of `call_indirect` in the same way, so that
+    /// a mispredicted bounds check can't load an entry past the table.
+    pub mask_table_indices: bool,
+    /// Serialize execution with a fence before the call of `call_indirect`,
+    /// once its bounds and signature checks are resolved.
+    pub fence_indirect_calls: bool,
+}
+
+impl SpectreMitigations {
+    /// Enables all mitigations.
+    pub fn all() -> SpectreMitigations {
+        SpectreMitigations {
+            mask_memory_accesses: true,
+            mask_table_indices: true,
+            fence_indirect_calls: true,
+        }
+    }
+
+    /// Returns whether any mitigation is enabled.
+    pub fn any(&self) -> bool {
+        self.mask_memory_accesses || self.mask_table_indices || self.fence_indirect_calls
+    }
+}
+
 /// Controls which experimental features will be enabled.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct Features {
     pub simd: bool,
     pub threads: bool,
+    /// Enable the bulk memory proposal.  Only Cranelift compiles its
+    /// instructions yet, and only `memory.copy` and `memory.fill`; with
+    /// the other backends this only lets modules using them validate.
+    pub bulk_memory: bool,
+    /// Enable the multi-value proposal, for blocks and functions returning
+    /// several values.  Only Cranelift compiles them yet; typed `Func`s
+    /// return them as tuples, and host functions may return tuples of up
+    /// to two values.
+    pub multi_value: bool,
+    /// Reject modules using floating point types or instructions, whose
+    /// results can depend on the hardware.
+    pub deny_floats: bool,
+    /// Compile with the deterministic execution profile, see [`Features::deterministic`].
+    pub deterministic: bool,
+}
+
+impl Features {
+    /// The deterministic execution profile, for users like blockchains that
+    /// need every node to compute the same results from the same module,
+    /// whatever the backend and the host.
+    ///
+    /// With this profile:
+    ///
+    /// - every NaN produced by a floating point instruction is replaced by
+    ///   the canonical NaN, so NaN bits don't depend on the hardware;
+    /// - the threads proposal is rejected, since the order of shared memory
+    ///   accesses isn't deterministic;
+    /// - the SIMD proposal is only accepted by backends that canonicalize
+    ///   vector NaNs, which is the LLVM backend only for now.
+    ///
+    /// Execution must also be bounded and fed deterministic inputs, which
+    /// the runtime can't enforce by itself: push the `Metering` middleware of
+    /// `wasmer-middleware-common` (its costs are the same for all backends),
+    /// and give WASI modules a virtual clock and seeded randomness with
+    /// `WasiStateBuilder::deterministic`.
+    pub fn deterministic() -> Features {
+        Features {
+            deterministic: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Limits on the modules a compiler accepts, checked before compiling them, so
+/// that hostile modules with millions of functions or locals can't make the
+/// compiler use excessive time or memory.  `None` means no limit.
+///
+/// The defaults are the limits web browsers put on the untrusted modules they
+/// compile, which legitimate modules stay well within; relax them with
+/// [`CompileLimits::unlimited`] for trusted modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileLimits {
+    /// The number of functions a module may define, 1,000,000 by default.
+    pub max_functions: Option<u32>,
+    /// The size in bytes of each function body, 7,654,321 by default.
+    pub max_function_body_size: Option<u32>,
+    /// The number of locals each function may declare, besides its
+    /// parameters, 50,000 by default.
+    pub max_locals: Option<u32>,
+    /// The initial number of elements of each table a module defines,
+    /// 10,000,000 by default.
+    pub max_table_size: Option<u32>,
+    /// The size in bytes of each data segment, 64 MiB by default.
+    pub max_data_segment_size: Option<u32>,
+}
+
+impl CompileLimits {
+    /// No limits at all.
+    pub fn unlimited() -> Self {
+        Self {
+            max_functions: None,
+            max_function_body_size: None,
+            max_locals: None,
+            max_table_size: None,
+            max_data_segment_size: None,
+        }
+    }
+}
+
+impl Default for CompileLimits {
+    fn default() -> Self {
+        Self {
+            max_functions: Some(1_000_000),
+            max_function_body_size: Some(7_654_321),
+            max_locals: Some(50_000),
+            max_table_size: Some(10_000_000),
+            max_data_segment_size: Some(64 << 20),
+        }
+    }
 }
 
 /// Use this to point to a compiler config struct provided by the backend.
@@ -171,16 +582,103 @@ pub struct CompilerConfig {
     /// Symbol information generated from emscripten; used for more detailed debug messages
     pub symbol_map: Option<HashMap<u32, String>>,
     pub memory_bound_check_mode: MemoryBoundCheckMode,
+    /// The style of the module's memories, see [`MemoryStyle`].
+    pub memory_style: MemoryStyle,
     pub enforce_stack_check: bool,
     pub track_state: bool,
     pub features: Features,
+    /// The limits modules must stay within to be compiled, see [`CompileLimits`].
+    pub limits: CompileLimits,
+
+    /// Trap with explicit checks only, see [`CompilerConfig::require_no_signals`].
+    pub no_signals: bool,
+
+    /// Mitigations against speculative execution attacks, see [`SpectreMitigations`].
+    pub spectre_mitigations: SpectreMitigations,
 
-    // Target info. Presently only supported by LLVM.
+    // Target info, see `CompilerConfig::target`. Not supported by singlepass.
     pub triple: Option<String>,
     pub cpu_name: Option<String>,
     pub cpu_features: Option<String>,
 
     pub backend_specific_config: Option<BackendCompilerConfig>,
+
+    /// Why [`Backend::Auto`] picked the backend compiling with this
+    /// configuration, reported by [`CompileInfo::auto_selection`].
+    ///
+    /// [`Backend::Auto`]: enum.Backend.html#variant.Auto
+    /// [`CompileInfo::auto_selection`]: ../module/struct.CompileInfo.html#structfield.auto_selection
+    pub auto_selection: Option<AutoSelection>,
+}
+
+impl CompilerConfig {
+    /// Compile for `triple` instead of the host, e.g. to build AArch64
+    /// artifacts on an x86-64 machine.  The generated code only uses the
+    /// `cpu_features` on top of the baseline of the target's architecture.
+    ///
+    /// Modules compiled for another architecture or operating system can't
+    /// be instantiated; they can only be cached, and the resulting
+    /// [`Artifact`] loaded on a matching host.
+    ///
+    /// Supported by the LLVM and Cranelift backends.
+    pub fn target(mut self, triple: Triple, cpu_features: CpuFeatures) -> Self {
+        self.triple = Some(triple.to_string());
+        self.cpu_name = Some("generic".to_string());
+        self.cpu_features = Some(cpu_features.to_string());
+        self
+    }
+
+    /// Compile for the host, but only use the `cpu_features` on top of the
+    /// baseline of its architecture, e.g. the [intersection] of the features
+    /// of the machines of a fleet, so the artifacts run on all of them.
+    ///
+    /// Supported by the LLVM and Cranelift backends.  The singlepass backend
+    /// always uses AVX and POPCNT on x86-64.
+    ///
+    /// [intersection]: struct.CpuFeatures.html#method.intersection
+    pub fn with_cpu_features(mut self, cpu_features: CpuFeatures) -> Self {
+        self.cpu_name = Some("generic".to_string());
+        self.cpu_features = Some(cpu_features.to_string());
+        self
+    }
+
+    /// The CPU features the code `backend` generates with this configuration
+    /// may use on top of the baseline of the target's architecture, which
+    /// the machines running it must have.
+    ///
+    /// Without explicit features, LLVM compiles for the host's, while
+    /// Cranelift only uses the baseline.
+    pub fn required_cpu_features(&self, backend: Backend) -> CpuFeatures {
+        let mut features = match &self.cpu_features {
+            Some(features) => features.parse().unwrap_or_default(),
+            None if backend == Backend::LLVM => CpuFeatures::host(),
+            None => CpuFeatures::new(),
+        };
+        if backend == Backend::Singlepass && cfg!(target_arch = "x86_64") {
+            features.insert("avx");
+            features.insert("popcnt");
+        }
+        features
+    }
+
+    /// Compile code that traps with explicit checks only, so that running
+    /// it doesn't install process-wide signal handlers, e.g. when embedding
+    /// in a host that handles `SIGSEGV` itself.  Memories are dynamic and
+    /// bounds-checked, and the stack is checked on entry to each function.
+    ///
+    /// Middlewares that use breakpoints, like metering, can't be used.
+    ///
+    /// Only supported by the singlepass backend on x86-64. Cranelift and
+    /// LLVM compile traps to instructions that fault, and rely on guard
+    /// pages to catch stack overflows, so compiling with them fails with a
+    /// `CodegenError` instead.
+    pub fn require_no_signals(mut self) -> Self {
+        self.no_signals = true;
+        self.memory_style = MemoryStyle::Dynamic;
+        self.memory_bound_check_mode = MemoryBoundCheckMode::Enable;
+        self.enforce_stack_check = true;
+        self
+    }
 }
 
 pub trait Compiler {
@@ -260,4 +758,13 @@ pub trait RunnableModule: Send + Sync {
 
 pub trait CacheGen: Send + Sync {
     fn generate_cache(&self) -> Result<(Box<[u8]>, Memory), CacheError>;
+
+    /// The length of the start of the compiled code returned by `generate_cache` that
+    /// is position independent and never written to when the code is loaded, if any.
+    ///
+    /// Shared artifacts map this part shared and read-only, so any number of processes
+    /// can use the same pages.  It must be a multiple of the page size.
+    fn shared_code_len(&self) -> Option<usize> {
+        None
+    }
 }
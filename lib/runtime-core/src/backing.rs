@@ -1,15 +1,19 @@
 use crate::{
+    backend::Backend,
     error::{CreationError, LinkError, LinkResult},
     export::{Context, Export},
     global::Global,
     import::ImportObject,
+    limits::ResourceLimiter,
     memory::Memory,
+    metrics::RuntimeMetrics,
     module::{ImportName, ModuleInfo, ModuleInner},
+    pool::InstancePool,
     sig_registry::SigRegistry,
     structures::{BoxedMap, Map, SliceMap, TypedIndex},
     table::Table,
     types::{
-        ImportedFuncIndex, ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex,
+        FuncIndex, ImportedFuncIndex, ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex,
         Initializer, LocalFuncIndex, LocalGlobalIndex, LocalMemoryIndex, LocalOrImport,
         LocalTableIndex, SigIndex, Value,
     },
@@ -19,6 +23,7 @@ use std::{
     fmt::Debug,
     ptr::{self, NonNull},
     slice,
+    sync::Arc,
 };
 
 /// Size of the array for internal instance usage
@@ -57,6 +62,15 @@ pub struct LocalBacking {
     pub(crate) local_functions: BoxedMap<LocalFuncIndex, *const vm::Func>,
 
     pub(crate) internals: Internals,
+
+    /// The number of calls made to host functions, see `Instance::stats`.
+    pub(crate) host_calls: u64,
+
+    /// The recorder of the instance's metrics, see `metrics`.
+    pub(crate) metrics: Option<Arc<dyn RuntimeMetrics>>,
+
+    /// The limiter of the instance's resources, see `limits`.
+    pub(crate) limiter: Option<Arc<dyn ResourceLimiter>>,
 }
 
 // Manually implemented because LocalBacking contains raw pointers directly
@@ -67,8 +81,9 @@ impl LocalBacking {
         module: &ModuleInner,
         imports: &ImportBacking,
         vmctx: *mut vm::Ctx,
+        pool: Option<&InstancePool>,
     ) -> LinkResult<Self> {
-        let mut memories = match Self::generate_memories(module) {
+        let mut memories = match Self::generate_memories(module, pool) {
             Ok(m) => m,
             Err(e) => {
                 return Err(vec![LinkError::Generic {
@@ -76,7 +91,14 @@ impl LocalBacking {
                 }]);
             }
         };
-        let mut tables = Self::generate_tables(module);
+        let mut tables = match Self::generate_tables(module, pool) {
+            Ok(t) => t,
+            Err(e) => {
+                return Err(vec![LinkError::Generic {
+                    message: format!("unable to create table: {:?}", e),
+                }]);
+            }
+        };
         let mut globals = Self::generate_globals(module, imports)?;
 
         // Ensure all initializers are valid before running finalizers
@@ -103,9 +125,143 @@ impl LocalBacking {
             local_functions,
 
             internals: Internals([0; INTERNALS_SIZE]),
+
+            host_calls: 0,
+
+            metrics: None,
+
+            limiter: None,
         })
     }
 
+    /// Restore the locally-defined memories, tables and mutable globals to
+    /// the state they had before the start function ran.
+    ///
+    /// Memories and tables shrink back to their minimum size and hold
+    /// their data and element segments again, and globals hold their
+    /// initial values.  Imported memories, tables and globals are left as
+    /// they are.  Nothing is reset if a memory is shared.
+    pub(crate) fn reset(
+        &self,
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        vmctx: *mut vm::Ctx,
+    ) -> LinkResult<()> {
+        if self
+            .memories
+            .iter()
+            .any(|(_, memory)| memory.descriptor().shared)
+        {
+            return Err(vec![LinkError::Generic {
+                message: "instances with a shared memory can't be reset".to_string(),
+            }]);
+        }
+
+        for (_, memory) in self.memories.iter() {
+            memory.reset().map_err(|e| {
+                vec![LinkError::Generic {
+                    message: format!("unable to reset memory: {:?}", e),
+                }]
+            })?;
+        }
+        for init in module.info.data_initializers.iter() {
+            if let LocalOrImport::Local(local_memory_index) =
+                init.memory_index.local_or_import(&module.info)
+            {
+                let init_base = Self::initializer_base(&init.base, imports)?;
+                let mem = &self.memories[local_memory_index];
+                unsafe { init_memory(mem.vm_local_memory(), init_base, &init.data) };
+            }
+        }
+
+        for (_, table) in self.tables.iter() {
+            table.reset();
+        }
+        for init in &module.info.elem_initializers {
+            if let LocalOrImport::Local(local_table_index) =
+                init.table_index.local_or_import(&module.info)
+            {
+                let init_base = Self::initializer_base(&init.base, imports)?;
+                self.tables[local_table_index].anyfunc_direct_access_mut(|elements| {
+                    for (i, &func_index) in init.elements.iter().enumerate() {
+                        elements[init_base + i] = Self::anyfunc(module, imports, func_index, vmctx);
+                    }
+                });
+            }
+        }
+
+        let initial_globals = Self::generate_globals(module, imports)?;
+        for ((_, global), (_, initial)) in self.globals.iter().zip(initial_globals.iter()) {
+            if global.descriptor().mutable {
+                global.set(initial.get()).map_err(|e| {
+                    vec![LinkError::Generic {
+                        message: format!("unable to reset global: {:?}", e),
+                    }]
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The offset a data or element segment starts at.
+    fn initializer_base(base: &Initializer, imports: &ImportBacking) -> LinkResult<usize> {
+        let base = match base {
+            Initializer::Const(Value::I32(offset)) => *offset as u32,
+            Initializer::Const(_) => {
+                return Err(vec![LinkError::Generic {
+                    message: "a const initializer must be an i32".to_string(),
+                }]);
+            }
+            Initializer::GetGlobal(import_global_index) => {
+                match imports.globals.get(*import_global_index).map(Global::get) {
+                    Some(Value::I32(x)) => x as u32,
+                    Some(_) => {
+                        return Err(vec![LinkError::Generic {
+                            message: "unsupported global type for initializer".to_string(),
+                        }]);
+                    }
+                    None => {
+                        return Err(vec![LinkError::Generic {
+                            message: "incorrect global index for initializer".to_string(),
+                        }]);
+                    }
+                }
+            }
+        };
+
+        Ok(base as usize)
+    }
+
+    /// The table element for the function `func_index`.
+    fn anyfunc(
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        func_index: FuncIndex,
+        vmctx: *mut vm::Ctx,
+    ) -> vm::Anyfunc {
+        let sig_index = module.info.func_assoc[func_index];
+        let signature = SigRegistry.lookup_signature_ref(&module.info.signatures[sig_index]);
+        let sig_id = vm::SigId(SigRegistry.lookup_sig_index(signature).index() as u32);
+
+        let (func, ctx) = match func_index.local_or_import(&module.info) {
+            LocalOrImport::Local(local_func_index) => (
+                module
+                    .runnable_module
+                    .get_func(&module.info, local_func_index)
+                    .unwrap()
+                    .as_ptr() as *const vm::Func,
+                vmctx,
+            ),
+            LocalOrImport::Import(imported_func_index) => {
+                let vm::ImportedFunc { func, func_ctx } = imports.vm_functions[imported_func_index];
+                (func, unsafe { func_ctx.as_ref() }.vmctx.as_ptr())
+            }
+        };
+
+        vm::Anyfunc { func, ctx, sig_id }
+    }
+
     fn generate_local_functions(module: &ModuleInner) -> BoxedMap<LocalFuncIndex, *const vm::Func> {
         (0..module.info.func_assoc.len() - module.info.imported_functions.len())
             .map(|index| {
@@ -133,10 +289,14 @@ impl LocalBacking {
 
     fn generate_memories(
         module: &ModuleInner,
+        pool: Option<&InstancePool>,
     ) -> Result<BoxedMap<LocalMemoryIndex, Memory>, CreationError> {
         let mut memories = Map::with_capacity(module.info.memories.len());
         for (_, &desc) in &module.info.memories {
-            let memory = Memory::new(desc)?;
+            let memory = match pool {
+                Some(pool) => Memory::new_pooled(desc, pool)?,
+                None => Memory::new(desc)?,
+            };
             memories.push(memory);
         }
 
@@ -236,23 +396,18 @@ impl LocalBacking {
             match init.memory_index.local_or_import(&module.info) {
                 LocalOrImport::Local(local_memory_index) => {
                     let mem = &memories[local_memory_index];
-                    for (mem_byte, data_byte) in mem.view()[init_base..init_base + init.data.len()]
-                        .iter()
-                        .zip(init.data.iter())
-                    {
-                        mem_byte.set(*data_byte);
-                    }
+                    unsafe { init_memory(mem.vm_local_memory(), init_base, &init.data) };
                 }
                 LocalOrImport::Import(imported_memory_index) => {
                     // Write the initialization data to the memory that
                     // we think the imported memory is.
-                    let memory_slice = unsafe {
-                        let local_memory = &*imports.vm_memories[imported_memory_index];
-                        slice::from_raw_parts_mut(local_memory.base, local_memory.bound)
+                    unsafe {
+                        init_memory(
+                            imports.vm_memories[imported_memory_index],
+                            init_base,
+                            &init.data,
+                        )
                     };
-
-                    let mem_init_view = &mut memory_slice[init_base..init_base + init.data.len()];
-                    mem_init_view.copy_from_slice(&init.data);
                 }
             }
         }
@@ -264,15 +419,21 @@ impl LocalBacking {
             .into_boxed_map())
     }
 
-    fn generate_tables(module: &ModuleInner) -> BoxedMap<LocalTableIndex, Table> {
+    fn generate_tables(
+        module: &ModuleInner,
+        pool: Option<&InstancePool>,
+    ) -> Result<BoxedMap<LocalTableIndex, Table>, CreationError> {
         let mut tables = Map::with_capacity(module.info.tables.len());
 
         for (_, &table_desc) in module.info.tables.iter() {
-            let table = Table::new(table_desc).unwrap();
+            let table = match pool {
+                Some(pool) => Table::new_pooled(table_desc, pool)?,
+                None => Table::new(table_desc)?,
+            };
             tables.push(table);
         }
 
-        tables.into_boxed_map()
+        Ok(tables.into_boxed_map())
     }
 
     /// This validates all of the locally-defined tables in the Module.
@@ -568,6 +729,33 @@ impl Drop for ImportBacking {
     }
 }
 
+/// Copy a data segment into a memory at `offset`, which must be in bounds,
+/// with a single `memcpy` rather than byte by byte through a view.
+///
+/// Segments are always copied, never mapped: they are held in the module's
+/// `Vec<u8>`s rather than at page-aligned offsets of a file, so there are no
+/// pages to map them from.
+unsafe fn init_memory(local_memory: *mut vm::LocalMemory, offset: usize, data: &[u8]) {
+    let local_memory = &*local_memory;
+    let memory_slice = slice::from_raw_parts_mut(local_memory.base, local_memory.bound);
+    memory_slice[offset..offset + data.len()].copy_from_slice(data);
+}
+
+/// Whether a function exported with `ctx` comes from an instance of an
+/// interpreted module, while `module` isn't interpreted.
+///
+/// The interpreter has no machine code to point to, so the functions it
+/// exports point to data that native code can't call.
+fn interpreted_into_native(module: &ModuleInner, ctx: &Context) -> bool {
+    let exporter = match *ctx {
+        Context::External(vmctx) | Context::ExternalWithEnv(vmctx, _) if !vmctx.is_null() => unsafe {
+            &*(*vmctx).module
+        },
+        _ => return false,
+    };
+    exporter.info.backend == Backend::Interpreter && module.info.backend != Backend::Interpreter
+}
+
 fn import_functions(
     module: &ModuleInner,
     imports: &ImportObject,
@@ -589,8 +777,7 @@ fn import_functions(
         let namespace = module.info.namespace_table.get(*namespace_index);
         let name = module.info.name_table.get(*name_index);
 
-        let import =
-            imports.maybe_with_namespace(namespace, |namespace| namespace.get_export(name));
+        let import = imports.get_export(namespace, name);
 
         match import {
             Some(Export::Function {
@@ -598,7 +785,17 @@ fn import_functions(
                 ctx,
                 signature,
             }) => {
-                if *expected_sig == *signature {
+                if interpreted_into_native(module, &ctx) {
+                    link_errors.push(LinkError::Generic {
+                        message: format!(
+                            "{}.{} is exported by an interpreted instance, which can't be \
+                             imported by a module compiled by the {} backend",
+                            namespace,
+                            name,
+                            module.info.backend.to_string()
+                        ),
+                    });
+                } else if *expected_sig == *signature {
                     functions.push(vm::ImportedFunc {
                         func: func.inner(),
                         func_ctx: NonNull::new(Box::into_raw(Box::new(vm::FuncCtx {
@@ -695,8 +892,7 @@ fn import_memories(
         let namespace = module.info.namespace_table.get(*namespace_index);
         let name = module.info.name_table.get(*name_index);
 
-        let memory_import =
-            imports.maybe_with_namespace(namespace, |namespace| namespace.get_export(name));
+        let memory_import = imports.get_export(namespace, name);
         match memory_import {
             Some(Export::Memory(memory)) => {
                 if expected_memory_desc.fits_in_imported(memory.descriptor()) {
@@ -766,8 +962,7 @@ fn import_tables(
         let namespace = module.info.namespace_table.get(*namespace_index);
         let name = module.info.name_table.get(*name_index);
 
-        let table_import =
-            imports.maybe_with_namespace(namespace, |namespace| namespace.get_export(name));
+        let table_import = imports.get_export(namespace, name);
         match table_import {
             Some(Export::Table(mut table)) => {
                 if expected_table_desc.fits_in_imported(table.descriptor()) {
@@ -836,8 +1031,7 @@ fn import_globals(
     {
         let namespace = module.info.namespace_table.get(*namespace_index);
         let name = module.info.name_table.get(*name_index);
-        let import =
-            imports.maybe_with_namespace(namespace, |namespace| namespace.get_export(name));
+        let import = imports.get_export(namespace, name);
         match import {
             Some(Export::Global(mut global)) => {
                 if global.descriptor() == *imported_global_desc {
@@ -882,3 +1076,136 @@ fn import_globals(
         Ok((globals.into_boxed_map(), vm_globals.into_boxed_map()))
     }
 }
+
+fn export_type_name(export: &Export) -> &'static str {
+    match export {
+        Export::Function { .. } => "function",
+        Export::Memory { .. } => "memory",
+        Export::Table { .. } => "table",
+        Export::Global { .. } => "global",
+    }
+}
+
+/// Checks that `imports` provides each import of `module` with the type
+/// `ImportBacking::new` requires, without creating anything.
+pub(crate) fn check_imports(module: &ModuleInner, imports: &ImportObject) -> LinkResult<()> {
+    let info = &module.info;
+    let mut link_errors = vec![];
+
+    for (index, import_name) in &info.imported_functions {
+        let expected = &info.signatures[info.func_assoc[index.convert_up(info)]];
+        link_errors.extend(check_import(
+            info,
+            imports,
+            import_name,
+            "function",
+            |export, namespace, name| match export {
+                Export::Function { signature, .. } if *expected != *signature => {
+                    Some(LinkError::IncorrectImportSignature {
+                        namespace,
+                        name,
+                        expected: expected.clone(),
+                        found: (*signature).clone(),
+                    })
+                }
+                _ => None,
+            },
+        ));
+    }
+    for (_, (import_name, expected)) in &info.imported_memories {
+        link_errors.extend(check_import(
+            info,
+            imports,
+            import_name,
+            "memory",
+            |export, namespace, name| match export {
+                Export::Memory(memory) if !expected.fits_in_imported(memory.descriptor()) => {
+                    Some(LinkError::IncorrectMemoryDescriptor {
+                        namespace,
+                        name,
+                        expected: *expected,
+                        found: memory.descriptor(),
+                    })
+                }
+                _ => None,
+            },
+        ));
+    }
+    for (_, (import_name, expected)) in &info.imported_tables {
+        link_errors.extend(check_import(
+            info,
+            imports,
+            import_name,
+            "table",
+            |export, namespace, name| match export {
+                Export::Table(table) if !expected.fits_in_imported(table.descriptor()) => {
+                    Some(LinkError::IncorrectTableDescriptor {
+                        namespace,
+                        name,
+                        expected: *expected,
+                        found: table.descriptor(),
+                    })
+                }
+                _ => None,
+            },
+        ));
+    }
+    for (_, (import_name, expected)) in &info.imported_globals {
+        link_errors.extend(check_import(
+            info,
+            imports,
+            import_name,
+            "global",
+            |export, namespace, name| match export {
+                Export::Global(global) if global.descriptor() != *expected => {
+                    Some(LinkError::IncorrectGlobalDescriptor {
+                        namespace,
+                        name,
+                        expected: *expected,
+                        found: global.descriptor(),
+                    })
+                }
+                _ => None,
+            },
+        ));
+    }
+
+    if link_errors.is_empty() {
+        Ok(())
+    } else {
+        Err(link_errors)
+    }
+}
+
+/// Checks one import of type `expected`, with `mismatch` checking the
+/// signature or descriptor of an export of that type.
+fn check_import(
+    info: &ModuleInfo,
+    imports: &ImportObject,
+    import_name: &ImportName,
+    expected: &str,
+    mismatch: impl Fn(Export, String, String) -> Option<LinkError>,
+) -> Option<LinkError> {
+    let namespace = info.namespace_table.get(import_name.namespace_index);
+    let name = info.name_table.get(import_name.name_index);
+    match imports.get_export(namespace, name) {
+        Some(export) => {
+            let found = export_type_name(&export);
+            if found == expected {
+                mismatch(export, namespace.to_string(), name.to_string())
+            } else {
+                Some(LinkError::IncorrectImportType {
+                    namespace: namespace.to_string(),
+                    name: name.to_string(),
+                    expected: expected.to_string(),
+                    found: found.to_string(),
+                })
+            }
+        }
+        None if expected == "function" && imports.allow_missing_functions => None,
+        None => Some(LinkError::ImportNotFound {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }),
+    }
+}
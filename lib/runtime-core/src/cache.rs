@@ -3,12 +3,16 @@
 //! and loaded to allow skipping compilation and fast startup.
 
 use crate::{
-    backend::Backend,
+    backend::{Backend, CpuFeatures},
     module::{Module, ModuleInfo},
     sys::Memory,
 };
 use blake2b_simd::blake2bp;
-use std::{fmt, io, mem, slice};
+use std::{
+    fmt,
+    io::{self, Write},
+    mem, slice,
+};
 
 /// Indicates the invalid type of invalid cache file
 #[derive(Debug)]
@@ -17,6 +21,28 @@ pub enum InvalidFileType {
     InvalidSize,
     /// Given cache header slice does not contain the expected magic bytes
     InvalidMagic,
+    /// The compiled code section of a shared artifact is not aligned to the page size
+    /// of this machine
+    InvalidAlignment,
+    /// The contents of the cache file don't match the hash in its header
+    InvalidHash,
+}
+
+/// Why a cache artifact can't be loaded by this process, which should compile the
+/// module again instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incompatibility {
+    /// The artifact was written by another version of Wasmer.
+    WasmerVersion,
+    /// The artifact was compiled by another backend than the one loading it.
+    Backend {
+        /// The backend loading the artifact.
+        expected: Backend,
+        /// The backend which compiled the artifact.
+        found: Backend,
+    },
+    /// The code of the artifact uses these CPU features, which the host doesn't have.
+    CpuFeatures(Vec<String>),
 }
 
 /// Kinds of caching errors
@@ -32,10 +58,18 @@ pub enum Error {
     Unknown(String),
     /// An invalid cache binary given.
     InvalidFile(InvalidFileType),
+    /// The compiled code isn't position independent, so it can't be mapped shared
+    /// from a shared artifact. Only the Cranelift backend compiles position
+    /// independent code.
+    NotPositionIndependent,
     /// The cached binary has been invalidated.
     InvalidatedCache,
     /// The current backend does not support caching.
     UnsupportedBackend(Backend),
+    /// The cached module was compiled for this target triple, not for the host.
+    IncompatibleTarget(String),
+    /// The cached module can't be loaded by this process, and must be compiled again.
+    Incompatible(Incompatibility),
 }
 
 impl From<io::Error> for Error {
@@ -114,28 +148,56 @@ impl WasmHash {
     }
 }
 
-const CURRENT_CACHE_VERSION: u64 = 0;
+const CURRENT_CACHE_VERSION: u64 = 9;
 static WASMER_CACHE_MAGIC: [u8; 8] = *b"WASMER\0\0";
 
+/// The first bytes of `WASMER_VERSION_HASH`, identifying the version of Wasmer which
+/// wrote an artifact.
+fn wasmer_version() -> [u8; 32] {
+    let mut version = [0; 32];
+    version.copy_from_slice(&WASMER_VERSION_HASH.as_bytes()[..32]);
+    version
+}
+
+/// The name of `backend`, padded with zeros.
+fn backend_name(backend: Backend) -> [u8; 16] {
+    let mut name = [0; 16];
+    let backend = backend.to_string().as_bytes();
+    name[..backend.len()].copy_from_slice(backend);
+    name
+}
+
 /// The header of a cache file.
 #[repr(C, packed)]
 struct ArtifactHeader {
     magic: [u8; 8], // [W, A, S, M, E, R, \0, \0]
     version: u64,
     data_len: u64,
+    wasmer_version: [u8; 32],
+    backend: [u8; 16],
+    /// The hash of the body, as computed by `WasmHash::generate`.
+    body_hash: [u8; 64],
 }
 
 impl ArtifactHeader {
+    /// Checks the magic bytes and the versions in the header of `buffer`, and that the
+    /// body matches the hash in the header.
     pub fn read_from_slice(buffer: &[u8]) -> Result<(&Self, &[u8]), Error> {
         if buffer.len() >= mem::size_of::<ArtifactHeader>() {
             if &buffer[..8] == &WASMER_CACHE_MAGIC {
                 let (header_slice, body_slice) = buffer.split_at(mem::size_of::<ArtifactHeader>());
                 let header = unsafe { &*(header_slice.as_ptr() as *const ArtifactHeader) };
 
-                if header.version == CURRENT_CACHE_VERSION {
-                    Ok((header, body_slice))
-                } else {
+                if header.version != CURRENT_CACHE_VERSION {
                     Err(Error::InvalidatedCache)
+                } else if header.wasmer_version != wasmer_version() {
+                    Err(Error::Incompatible(Incompatibility::WasmerVersion))
+                } else if header.data_len != body_slice.len() as u64
+                    || WasmHash::generate(body_slice).into_array()[..] != header.body_hash[..]
+                {
+                    Err(Error::InvalidFile(InvalidFileType::InvalidHash))
+                } else {
+                    Ok((header, body_slice))
                 }
             } else {
                 Err(Error::InvalidFile(InvalidFileType::InvalidMagic))
@@ -171,12 +233,114 @@ impl ArtifactHeader {
     }
 }
 
+static WASMER_SHARED_CACHE_MAGIC: [u8; 8] = *b"WASMERSH";
+
+/// The header of a shared cache file.
+///
+/// Unlike a regular cache file, the compiled code is not part of the serialized
+/// body but is stored verbatim at a page aligned offset, so that it can be mapped
+/// directly instead of being copied into every process.
+#[repr(C, packed)]
+struct SharedArtifactHeader {
+    magic: [u8; 8], // [W, A, S, M, E, R, S, H]
+    version: u64,
+    metadata_len: u64,
+    code_offset: u64,
+    code_len: u64,
+    /// The length of the start of the code that is mapped shared and read-only,
+    /// followed by the private table of the addresses it refers to.
+    shared_code_len: u64,
+    wasmer_version: [u8; 32],
+    backend: [u8; 16],
+    /// The hash of the metadata, as computed by `WasmHash::generate`.  The compiled
+    /// code isn't hashed, since it's mapped rather than read.
+    metadata_hash: [u8; 64],
+}
+
+impl SharedArtifactHeader {
+    fn read_from_slice(buffer: &[u8]) -> Result<Self, Error> {
+        if buffer.len() < mem::size_of::<SharedArtifactHeader>() {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidSize));
+        }
+        if buffer[..8] != WASMER_SHARED_CACHE_MAGIC {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidMagic));
+        }
+        let header = unsafe { (buffer.as_ptr() as *const SharedArtifactHeader).read_unaligned() };
+        if header.version != CURRENT_CACHE_VERSION {
+            return Err(Error::InvalidatedCache);
+        }
+        if header.wasmer_version != wasmer_version() {
+            return Err(Error::Incompatible(Incompatibility::WasmerVersion));
+        }
+        Ok(header)
+    }
+
+    /// Checks that the compiled code is position independent, so that it can be
+    /// mapped without being patched.
+    fn check_position_independent(&self) -> Result<(), Error> {
+        if self.shared_code_len == 0 || self.shared_code_len > self.code_len {
+            return Err(Error::NotPositionIndependent);
+        }
+        Ok(())
+    }
+
+    /// Checks that the compiled code is aligned to the page size of this machine, so
+    /// that it can be mapped.
+    #[cfg(unix)]
+    fn check_alignment(&self) -> Result<(), Error> {
+        let page_size = page_size::get();
+        if self.code_offset as usize % page_size != 0
+            || self.code_len as usize % page_size != 0
+            || self.shared_code_len as usize % page_size != 0
+            || self.shared_code_len > self.code_len
+        {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidAlignment));
+        }
+        Ok(())
+    }
+
+    /// Deserializes the metadata following this header, checking it against the hash
+    /// and the backend in this header.
+    #[cfg(unix)]
+    fn read_metadata(&self, metadata_bytes: &[u8]) -> Result<SharedArtifactMetadata, Error> {
+        if WasmHash::generate(metadata_bytes).into_array()[..] != self.metadata_hash[..] {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidHash));
+        }
+        let metadata: SharedArtifactMetadata = serde_bench::deserialize(metadata_bytes)
+            .map_err(|e| Error::DeserializeError(format!("{:#?}", e)))?;
+        if self.backend != backend_name(metadata.info.backend) {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidHash));
+        }
+        Ok(metadata)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        let ptr = self as *const SharedArtifactHeader as *const u8;
+        unsafe { slice::from_raw_parts(ptr, mem::size_of::<SharedArtifactHeader>()) }
+    }
+}
+
+#[derive(Serialize)]
+struct SharedArtifactMetadataRef<'a> {
+    info: &'a ModuleInfo,
+    #[serde(with = "serde_bytes")]
+    backend_metadata: &'a [u8],
+}
+
+#[derive(Deserialize)]
+struct SharedArtifactMetadata {
+    info: Box<ModuleInfo>,
+    #[serde(with = "serde_bytes")]
+    backend_metadata: Box<[u8]>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct ArtifactInner {
     info: Box<ModuleInfo>,
     #[serde(with = "serde_bytes")]
     backend_metadata: Box<[u8]>,
     compiled_code: Memory,
+    shared_code_len: Option<u64>,
 }
 
 /// Artifact are produced by caching, are serialized/deserialized to binaries, and contain
@@ -190,31 +354,97 @@ impl Artifact {
         info: Box<ModuleInfo>,
         backend_metadata: Box<[u8]>,
         compiled_code: Memory,
+        shared_code_len: Option<usize>,
     ) -> Self {
         Self {
             inner: ArtifactInner {
                 info,
                 backend_metadata,
                 compiled_code,
+                shared_code_len: shared_code_len.map(|len| len as u64),
             },
         }
     }
 
     /// Deserializes an `Artifact` from the given byte slice.
+    ///
+    /// Fails with [`Error::Incompatible`] if the artifact was written by another version
+    /// of Wasmer, and with [`InvalidFileType::InvalidHash`] if it was corrupted.
+    ///
+    /// [`Error::Incompatible`]: enum.Error.html#variant.Incompatible
+    /// [`InvalidFileType::InvalidHash`]: enum.InvalidFileType.html#variant.InvalidHash
     pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
-        let (_, body_slice) = ArtifactHeader::read_from_slice(bytes)?;
+        let (header, body_slice) = ArtifactHeader::read_from_slice(bytes)?;
 
-        let inner = serde_bench::deserialize(body_slice)
+        let inner: ArtifactInner = serde_bench::deserialize(body_slice)
             .map_err(|e| Error::DeserializeError(format!("{:#?}", e)))?;
+        if header.backend != backend_name(inner.info.backend) {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidHash));
+        }
 
         Ok(Artifact { inner })
     }
 
+    /// Checks that the artifact was compiled by `backend`, and that it can run on this
+    /// host, so it can be loaded by this process.
+    ///
+    /// Artifacts compiled for another target fail with [`Error::IncompatibleTarget`],
+    /// and ones using CPU features the host doesn't have with [`Error::Incompatible`].
+    ///
+    /// [`Error::IncompatibleTarget`]: enum.Error.html#variant.IncompatibleTarget
+    /// [`Error::Incompatible`]: enum.Error.html#variant.Incompatible
+    pub fn check_compatible(&self, backend: Backend) -> Result<(), Error> {
+        let found = self.inner.info.backend;
+        if found != backend {
+            return Err(Error::Incompatible(Incompatibility::Backend {
+                expected: backend,
+                found,
+            }));
+        }
+        self.check_runs_on_host()
+    }
+
+    /// Checks that the artifact was compiled for the host's target, and that its code
+    /// only uses CPU features the host has.
+    pub(crate) fn check_runs_on_host(&self) -> Result<(), Error> {
+        let info = &self.inner.info;
+        if !info.runs_on_host() {
+            let triple = info.triple.clone().unwrap_or_default();
+            return Err(Error::IncompatibleTarget(triple));
+        }
+        let missing = self
+            .required_cpu_features()?
+            .difference(&CpuFeatures::host());
+        if !missing.is_empty() {
+            let missing = missing.iter().map(str::to_string).collect();
+            return Err(Error::Incompatible(Incompatibility::CpuFeatures(missing)));
+        }
+        Ok(())
+    }
+
+    /// The CPU features the code of the artifact may use on top of the baseline of its
+    /// target, which the machines loading it must have.
+    pub fn required_cpu_features(&self) -> Result<CpuFeatures, Error> {
+        match &self.inner.info.cpu_features {
+            Some(cpu_features) => cpu_features.parse().map_err(Error::Unknown),
+            None => Ok(CpuFeatures::new()),
+        }
+    }
+
     /// A reference to the `Artifact`'s stored `ModuleInfo`
     pub fn info(&self) -> &ModuleInfo {
         &self.inner.info
     }
 
+    /// The length of the start of the compiled code that is position independent, and
+    /// that [`Artifact::from_shared_file`] maps shared and read-only, or `None` if the
+    /// backend patches the code when it's loaded.
+    ///
+    /// [`Artifact::from_shared_file`]: struct.Artifact.html#method.from_shared_file
+    pub fn shared_code_len(&self) -> Option<usize> {
+        self.inner.shared_code_len.map(|len| len as usize)
+    }
+
     #[doc(hidden)]
     pub fn consume(self) -> (ModuleInfo, Box<[u8]>, Memory) {
         (
@@ -230,6 +460,9 @@ impl Artifact {
             magic: WASMER_CACHE_MAGIC,
             version: CURRENT_CACHE_VERSION,
             data_len: 0,
+            wasmer_version: wasmer_version(),
+            backend: backend_name(self.inner.info.backend),
+            body_hash: [0; 64],
         };
 
         let mut buffer = cache_header.as_slice().to_vec();
@@ -237,13 +470,190 @@ impl Artifact {
         serde_bench::serialize(&mut buffer, &self.inner)
             .map_err(|e| Error::SerializeError(e.to_string()))?;
 
-        let data_len = (buffer.len() - mem::size_of::<ArtifactHeader>()) as u64;
-
-        let (header, _) = ArtifactHeader::read_from_slice_mut(&mut buffer)?;
-        header.data_len = data_len;
+        let (header, body) = ArtifactHeader::read_from_slice_mut(&mut buffer)?;
+        header.data_len = body.len() as u64;
+        header.body_hash = WasmHash::generate(body).into_array();
 
         Ok(buffer)
     }
+
+    /// Writes the `Artifact` in the shared layout, where the compiled code is stored
+    /// verbatim at a page aligned offset.
+    ///
+    /// Artifacts written this way can be placed in a shared memory segment (e.g. a file
+    /// in `/dev/shm`) and loaded with [`Artifact::from_shared_file`] by any number of
+    /// processes, which then all share a single copy of the compiled code pages.
+    ///
+    /// Only position independent code can be shared, which only the Cranelift backend
+    /// compiles (see [`Artifact::shared_code_len`]); other artifacts are rejected with
+    /// [`Error::NotPositionIndependent`] rather than written in a layout that would
+    /// have to be patched when it's loaded.
+    ///
+    /// [`Artifact::from_shared_file`]: struct.Artifact.html#method.from_shared_file
+    /// [`Artifact::shared_code_len`]: struct.Artifact.html#method.shared_code_len
+    /// [`Error::NotPositionIndependent`]: enum.Error.html#variant.NotPositionIndependent
+    pub fn write_shared<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let page_size = page_size::get();
+        let shared_code_len = self
+            .inner
+            .shared_code_len
+            .ok_or(Error::NotPositionIndependent)?;
+
+        let mut metadata = Vec::new();
+        serde_bench::serialize(
+            &mut metadata,
+            &SharedArtifactMetadataRef {
+                info: &self.inner.info,
+                backend_metadata: &self.inner.backend_metadata,
+            },
+        )
+        .map_err(|e| Error::SerializeError(e.to_string()))?;
+
+        let code = unsafe { self.inner.compiled_code.as_slice() };
+        let code_offset = round_up(
+            mem::size_of::<SharedArtifactHeader>() + metadata.len(),
+            page_size,
+        );
+        let code_len = round_up(code.len(), page_size);
+
+        let header = SharedArtifactHeader {
+            magic: WASMER_SHARED_CACHE_MAGIC,
+            version: CURRENT_CACHE_VERSION,
+            metadata_len: metadata.len() as u64,
+            code_offset: code_offset as u64,
+            code_len: code_len as u64,
+            shared_code_len,
+            wasmer_version: wasmer_version(),
+            backend: backend_name(self.inner.info.backend),
+            metadata_hash: WasmHash::generate(&metadata).into_array(),
+        };
+
+        let padding = code_offset - mem::size_of::<SharedArtifactHeader>() - metadata.len();
+
+        writer.write_all(header.as_slice())?;
+        writer.write_all(&metadata)?;
+        writer.write_all(&vec![0; padding])?;
+        writer.write_all(code)?;
+        writer.write_all(&vec![0; code_len - code.len()])?;
+
+        Ok(())
+    }
+
+    /// Loads an `Artifact` written by [`Artifact::write_shared`] from `file`.
+    ///
+    /// The module info and backend metadata are deserialized into this process, but
+    /// the compiled code is mapped directly from `file`.
+    ///
+    /// The code is mapped shared and read-only, so every process uses the same pages,
+    /// and the backend never patches it: its relocations go through a table of
+    /// addresses which is private to this process and lies outside the mapping of
+    /// the file (see [`Artifact::shared_code_len`]). Code which isn't position
+    /// independent is rejected with [`Error::NotPositionIndependent`]. The file itself
+    /// is never written to and may be opened read-only.
+    ///
+    /// # Note:
+    /// The file must live on a filesystem that allows executable mappings; some
+    /// systems mount `/dev/shm` with `noexec`.
+    ///
+    /// [`Artifact::shared_code_len`]: struct.Artifact.html#method.shared_code_len
+    /// [`Artifact::write_shared`]: struct.Artifact.html#method.write_shared
+    /// [`Error::NotPositionIndependent`]: enum.Error.html#variant.NotPositionIndependent
+    #[cfg(unix)]
+    pub fn from_shared_file(file: &std::fs::File) -> Result<Self, Error> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let mut file = file;
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut header_bytes = [0u8; mem::size_of::<SharedArtifactHeader>()];
+        file.read_exact(&mut header_bytes)?;
+        let header = SharedArtifactHeader::read_from_slice(&header_bytes)?;
+
+        header.check_position_independent()?;
+        header.check_alignment()?;
+
+        let code_offset = header.code_offset as usize;
+        let code_len = header.code_len as usize;
+        let shared_code_len = header.shared_code_len as usize;
+
+        let mut metadata_bytes = vec![0u8; header.metadata_len as usize];
+        file.read_exact(&mut metadata_bytes)?;
+        let metadata = header.read_metadata(&metadata_bytes)?;
+
+        let compiled_code = Memory::from_file_region_shared(
+            file,
+            code_offset,
+            shared_code_len,
+            code_len - shared_code_len,
+        )
+        .map_err(|e| Error::Unknown(e.to_string()))?;
+
+        Ok(Self::from_parts(
+            metadata.info,
+            metadata.backend_metadata,
+            compiled_code,
+            Some(shared_code_len),
+        ))
+    }
+
+    /// Loads an `Artifact` written by [`Artifact::write_shared`] from `bytes` without
+    /// copying the compiled code, which the artifact uses in place.
+    ///
+    /// `bytes` must start at a page boundary, as they do when the artifact file is
+    /// mapped into memory. The code must be position independent, as for
+    /// [`Artifact::from_shared_file`]. The backend changes the protection of the code
+    /// pages and writes the table of addresses that follows them, which is why they're
+    /// borrowed mutably; a file mapping should be private.
+    ///
+    /// # Safety
+    /// `bytes` must stay mapped, and mustn't be used otherwise, as long as the
+    /// artifact or a module loaded from it is alive.
+    ///
+    /// [`Artifact::from_shared_file`]: struct.Artifact.html#method.from_shared_file
+    /// [`Artifact::write_shared`]: struct.Artifact.html#method.write_shared
+    #[cfg(unix)]
+    pub unsafe fn from_shared_bytes(bytes: &mut [u8]) -> Result<Self, Error> {
+        use crate::sys::Protect;
+
+        let header = SharedArtifactHeader::read_from_slice(bytes)?;
+        header.check_position_independent()?;
+        header.check_alignment()?;
+        if bytes.as_ptr() as usize % page_size::get() != 0 {
+            return Err(Error::InvalidFile(InvalidFileType::InvalidAlignment));
+        }
+
+        let metadata_offset = mem::size_of::<SharedArtifactHeader>();
+        let code_offset = header.code_offset as usize;
+        let code_len = header.code_len as usize;
+        let shared_code_len = header.shared_code_len as usize;
+        let (metadata_end, code_end) = match (
+            metadata_offset.checked_add(header.metadata_len as usize),
+            code_offset.checked_add(code_len),
+        ) {
+            (Some(metadata_end), Some(code_end))
+                if metadata_end <= code_offset && code_end <= bytes.len() =>
+            {
+                (metadata_end, code_end)
+            }
+            _ => return Err(Error::InvalidFile(InvalidFileType::InvalidSize)),
+        };
+        let metadata = header.read_metadata(&bytes[metadata_offset..metadata_end])?;
+
+        let code = &mut bytes[code_offset..code_end];
+        let compiled_code = Memory::from_raw_parts(code.as_mut_ptr(), code_len, Protect::Read);
+
+        Ok(Self::from_parts(
+            metadata.info,
+            metadata.backend_metadata,
+            compiled_code,
+            Some(shared_code_len),
+        ))
+    }
+}
+
+/// Round `size` up to the nearest multiple of `page_size`.
+fn round_up(size: usize, page_size: usize) -> usize {
+    (size + (page_size - 1)) & !(page_size - 1)
 }
 
 /// A generic cache for storing and loading compiled wasm modules.
@@ -4,12 +4,15 @@
 use crate::fault::FaultInfo;
 use crate::{
     backend::RunnableModule,
-    backend::{Backend, CacheGen, Compiler, CompilerConfig, Features, Token},
+    backend::{
+        is_host_triple, Backend, CacheGen, CompileLimits, Compiler, CompilerConfig, Features, Token,
+    },
     cache::{Artifact, Error as CacheError},
-    error::{CompileError, CompileResult},
-    module::{ModuleInfo, ModuleInner},
+    error::{CompileError, CompileResult, ValidationFailure},
+    module::{CompileInfo, ModuleInfo, ModuleInner},
     structures::Map,
     types::{FuncIndex, FuncSig, SigIndex},
+    vm::METERING_POINTS_USED,
 };
 use smallvec::SmallVec;
 use std::any::Any;
@@ -18,6 +21,7 @@ use std::fmt;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
 use wasmparser::{self, WasmDecoder};
 use wasmparser::{Operator, Type as WpType};
 
@@ -184,8 +188,8 @@ pub fn validating_parser_config(features: &Features) -> wasmparser::ValidatingPa
             enable_threads: features.threads,
             enable_reference_types: false,
             enable_simd: features.simd,
-            enable_bulk_memory: false,
-            enable_multi_value: false,
+            enable_bulk_memory: features.bulk_memory,
+            enable_multi_value: features.multi_value,
 
             #[cfg(feature = "deterministic-execution")]
             deterministic_only: true,
@@ -193,16 +197,196 @@ pub fn validating_parser_config(features: &Features) -> wasmparser::ValidatingPa
     }
 }
 
-fn validate_with_features(bytes: &[u8], features: &Features) -> CompileResult<()> {
+/// Validates `bytes` with the given features, and checks the module uses
+/// no floats if `features.deny_floats` is set.
+pub(crate) fn validate_with_features(
+    bytes: &[u8],
+    features: &Features,
+) -> Result<(), ValidationFailure> {
     let mut parser =
         wasmparser::ValidatingParser::new(bytes, Some(validating_parser_config(features)));
     loop {
         let state = parser.read();
         match *state {
-            wasmparser::ParserState::EndWasm => break Ok(()),
-            wasmparser::ParserState::Error(err) => Err(CompileError::ValidationError {
-                msg: err.message.to_string(),
-            })?,
+            wasmparser::ParserState::EndWasm => break,
+            wasmparser::ParserState::Error(err) => {
+                return Err(ValidationFailure {
+                    message: describe_validation_error(err.message),
+                    offset: Some(err.offset),
+                })
+            }
+            _ => {}
+        }
+    }
+    if features.deny_floats {
+        check_no_floats(bytes)?;
+    }
+    Ok(())
+}
+
+/// Names the feature a module needs when it fails to validate because that
+/// feature isn't enabled.
+fn describe_validation_error(message: &str) -> String {
+    let lowercase = message.to_lowercase();
+    let feature = if lowercase.contains("simd") {
+        "simd"
+    } else if lowercase.contains("thread") {
+        "threads"
+    } else if lowercase.contains("bulk memory") {
+        "bulk_memory"
+    } else if lowercase.contains("multi-value") || lowercase.contains("multiple values") {
+        "multi_value"
+    } else {
+        return message.to_string();
+    };
+    format!(
+        "{} (the module needs the `{}` feature, which isn't enabled)",
+        message, feature
+    )
+}
+
+/// Rejects modules using floating point types or instructions, see
+/// [`Features::deny_floats`].
+fn check_no_floats(bytes: &[u8]) -> Result<(), ValidationFailure> {
+    use wasmparser::{ImportSectionEntryType, ParserState};
+
+    let is_float = |ty: &WpType| *ty == WpType::F32 || *ty == WpType::F64;
+    let mut parser = wasmparser::Parser::new(bytes);
+    let mut function = 0;
+    loop {
+        let offset = parser.current_position();
+        let error = |what: String| {
+            Err(ValidationFailure {
+                message: format!(
+                    "{} uses floats, which the `deny_floats` feature rejects",
+                    what
+                ),
+                offset: Some(offset),
+            })
+        };
+        match *parser.read() {
+            ParserState::EndWasm => return Ok(()),
+            ParserState::Error(err) => {
+                return Err(ValidationFailure {
+                    message: err.message.to_string(),
+                    offset: Some(err.offset),
+                })
+            }
+            ParserState::TypeSectionEntry(ref ty) => {
+                if ty.params.iter().chain(ty.returns.iter()).any(is_float) {
+                    return error("a function type".to_string());
+                }
+            }
+            ParserState::ImportSectionEntry {
+                module,
+                field,
+                ty: ImportSectionEntryType::Global(ty),
+            } => {
+                if is_float(&ty.content_type) {
+                    return error(format!("the imported global {}.{}", module, field));
+                }
+            }
+            ParserState::BeginGlobalSectionEntry(ty) => {
+                if is_float(&ty.content_type) {
+                    return error("a global".to_string());
+                }
+            }
+            ParserState::BeginFunctionBody { .. } => function += 1,
+            ParserState::FunctionBodyLocals { ref locals } => {
+                if locals.iter().any(|(_, ty)| is_float(ty)) {
+                    return error(format!("the locals of function body {}", function - 1));
+                }
+            }
+            // Float instructions, conversions and block types all have
+            // `F32` or `F64` in their names.
+            ParserState::CodeOperator(ref op) | ParserState::InitExpressionOperator(ref op) => {
+                let name = format!("{:?}", op);
+                if name.contains("F32") || name.contains("F64") {
+                    return error(format!("the instruction {}", name));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Rejects modules exceeding `limits`, see [`CompileLimits`].
+fn check_limits(bytes: &[u8], limits: &CompileLimits) -> Result<(), ValidationFailure> {
+    use wasmparser::ParserState;
+
+    let exceeds = |value: u64, limit: Option<u32>| match limit {
+        Some(limit) => value > u64::from(limit),
+        None => false,
+    };
+    let mut parser = wasmparser::Parser::new(bytes);
+    let mut functions = 0;
+    let mut function_bodies = 0;
+    loop {
+        let offset = parser.current_position();
+        let error = |message: String| {
+            Err(ValidationFailure {
+                message,
+                offset: Some(offset),
+            })
+        };
+        match *parser.read() {
+            ParserState::EndWasm => return Ok(()),
+            ParserState::Error(err) => {
+                return Err(ValidationFailure {
+                    message: err.message.to_string(),
+                    offset: Some(err.offset),
+                })
+            }
+            ParserState::FunctionSectionEntry(_) => {
+                functions += 1;
+                if exceeds(functions, limits.max_functions) {
+                    return error(format!(
+                        "the module defines more functions than the limit of {}",
+                        limits.max_functions.unwrap()
+                    ));
+                }
+            }
+            ParserState::TableSectionEntry(ref ty) => {
+                if exceeds(ty.limits.initial.into(), limits.max_table_size) {
+                    return error(format!(
+                        "a table has {} initial elements, more than the limit of {}",
+                        ty.limits.initial,
+                        limits.max_table_size.unwrap()
+                    ));
+                }
+            }
+            ParserState::BeginFunctionBody { ref range } => {
+                function_bodies += 1;
+                let size = (range.end - range.start) as u64;
+                if exceeds(size, limits.max_function_body_size) {
+                    return error(format!(
+                        "function body {} is {} bytes, more than the limit of {}",
+                        function_bodies - 1,
+                        size,
+                        limits.max_function_body_size.unwrap()
+                    ));
+                }
+            }
+            ParserState::FunctionBodyLocals { ref locals } => {
+                let count: u64 = locals.iter().map(|&(count, _)| u64::from(count)).sum();
+                if exceeds(count, limits.max_locals) {
+                    return error(format!(
+                        "function body {} declares {} locals, more than the limit of {}",
+                        function_bodies - 1,
+                        count,
+                        limits.max_locals.unwrap()
+                    ));
+                }
+            }
+            ParserState::BeginDataSectionEntryBody(size) => {
+                if exceeds(size.into(), limits.max_data_segment_size) {
+                    return error(format!(
+                        "a data segment is {} bytes, more than the limit of {}",
+                        size,
+                        limits.max_data_segment_size.unwrap()
+                    ));
+                }
+            }
             _ => {}
         }
     }
@@ -222,9 +406,37 @@ impl<
         compiler_config: CompilerConfig,
         _: Token,
     ) -> CompileResult<ModuleInner> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "compile",
+            backend = MCG::backend_id().to_string(),
+            wasm_len = wasm.len()
+        );
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        if compiler_config.features.deterministic {
+            check_deterministic(&compiler_config.features, MCG::backend_id())?;
+        }
+        let start = Instant::now();
+        // Checked first, since validating a hostile module can be expensive too.
+        check_limits(wasm, &compiler_config.limits).map_err(|failure| {
+            CompileError::LimitExceeded {
+                msg: failure.to_string(),
+            }
+        })?;
         if requires_pre_validation(MCG::backend_id()) {
-            validate_with_features(wasm, &compiler_config.features)?;
+            validate_with_features(wasm, &compiler_config.features).map_err(|failure| {
+                CompileError::ValidationError {
+                    msg: failure.message,
+                }
+            })?;
+        } else if compiler_config.features.deny_floats {
+            check_no_floats(wasm).map_err(|failure| CompileError::ValidationError {
+                msg: failure.message,
+            })?;
         }
+        let validate = start.elapsed();
 
         let mut mcg = match MCG::backend_id() {
             Backend::LLVM => MCG::new_with_target(
@@ -232,25 +444,65 @@ impl<
                 compiler_config.cpu_name.clone(),
                 compiler_config.cpu_features.clone(),
             ),
+            // Cranelift picks its target in `feed_compiler_config`.
+            Backend::Singlepass
+                if !compiler_config
+                    .triple
+                    .as_ref()
+                    .map_or(true, |triple| is_host_triple(triple)) =>
+            {
+                return Err(CompileError::InternalError {
+                    msg: "cross compilation is not available for the singlepass backend"
+                        .to_string(),
+                });
+            }
             _ => MCG::new(),
         };
         let mut chain = (self.middleware_chain_generator)();
-        let info = crate::parse::read_module(
+        let start = Instant::now();
+        let (info, codegen) = crate::parse::read_module(
             wasm,
             MCG::backend_id(),
             &mut mcg,
             &mut chain,
             &compiler_config,
         )?;
+        let parse = start.elapsed() - codegen;
+
+        let start = Instant::now();
         let (exec_context, cache_gen) =
             mcg.finalize(&info.read().unwrap())
                 .map_err(|x| CompileError::InternalError {
                     msg: format!("{:?}", x),
                 })?;
+        let link = start.elapsed();
+
+        let mut info = Arc::try_unwrap(info).unwrap().into_inner().unwrap();
+        info.shrink_to_fit();
+        let compile_info = CompileInfo {
+            backend: MCG::backend_id(),
+            parse,
+            validate,
+            codegen,
+            link,
+            functions: info.func_assoc.len() - info.imported_functions.len(),
+            code_size: exec_context.get_code().map(|code| code.len()),
+            auto_selection: compiler_config.auto_selection,
+        };
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            functions = compile_info.functions,
+            parse = ?compile_info.parse,
+            validate = ?compile_info.validate,
+            codegen = ?compile_info.codegen,
+            link = ?compile_info.link,
+            "compiled"
+        );
         Ok(ModuleInner {
             cache_gen,
             runnable_module: Arc::new(Box::new(exec_context)),
-            info: Arc::try_unwrap(info).unwrap().into_inner().unwrap(),
+            info,
+            compile_info: Some(compile_info),
         })
     }
 
@@ -263,11 +515,32 @@ impl<
     }
 }
 
+/// Rejects the proposals that can't run deterministically on `backend`, see
+/// [`Features::deterministic`].
+fn check_deterministic(features: &Features, backend: Backend) -> CompileResult<()> {
+    if features.threads {
+        return Err(CompileError::InternalError {
+            msg: "the threads proposal isn't deterministic".to_string(),
+        });
+    }
+    // Only the LLVM backend canonicalizes the NaNs of vector instructions.
+    if features.simd && backend != Backend::LLVM {
+        return Err(CompileError::InternalError {
+            msg: format!(
+                "the SIMD proposal isn't deterministic with the {} backend",
+                backend.to_string()
+            ),
+        });
+    }
+    Ok(())
+}
+
 fn requires_pre_validation(backend: Backend) -> bool {
     match backend {
         Backend::Cranelift => true,
         Backend::LLVM => true,
         Backend::Singlepass => false,
+        Backend::Interpreter => true,
         Backend::Auto => false,
     }
 }
@@ -287,12 +560,24 @@ impl<'a, 'b> EventSink<'a, 'b> {
 /// A container for a chain of middlewares.
 pub struct MiddlewareChain {
     chain: Vec<Box<dyn GenericFunctionMiddleware>>,
+    metered: bool,
 }
 
 impl MiddlewareChain {
     /// Create a new empty `MiddlewareChain`.
     pub fn new() -> MiddlewareChain {
-        MiddlewareChain { chain: vec![] }
+        MiddlewareChain {
+            chain: vec![],
+            metered: false,
+        }
+    }
+
+    /// Whether a middleware of this chain stored metering points in
+    /// [`vm::METERING_POINTS_USED`].
+    ///
+    /// [`vm::METERING_POINTS_USED`]: ../vm/static.METERING_POINTS_USED.html
+    pub(crate) fn is_metered(&self) -> bool {
+        self.metered
     }
 
     /// Push a new `FunctionMiddleware` to this `MiddlewareChain`.
@@ -317,6 +602,14 @@ impl MiddlewareChain {
                 m.feed_event(ev, module_info, &mut sink)?;
             }
         }
+        if !self.metered {
+            self.metered = sink.buffer.iter().any(|ev| match ev {
+                Event::Internal(InternalEvent::SetInternal(idx)) => {
+                    *idx as usize == METERING_POINTS_USED.index()
+                }
+                _ => false,
+            });
+        }
         if let Some(fcg) = fcg {
             for ev in sink.buffer {
                 fcg.feed_event(ev, module_info)
@@ -42,6 +42,16 @@ pub enum CompileError {
         /// An error message.
         msg: String,
     },
+    /// The signature of the module couldn't be verified.
+    VerificationError {
+        /// An error message.
+        msg: String,
+    },
+    /// The module exceeds the `CompileLimits` it was compiled with.
+    LimitExceeded {
+        /// An error message.
+        msg: String,
+    },
 }
 
 impl PartialEq for CompileError {
@@ -57,12 +67,36 @@ impl std::fmt::Display for CompileError {
                 write!(f, "Internal compiler error: \"{}\"", msg)
             }
             CompileError::ValidationError { msg } => write!(f, "Validation error \"{}\"", msg),
+            CompileError::VerificationError { msg } => {
+                write!(f, "Verification error \"{}\"", msg)
+            }
+            CompileError::LimitExceeded { msg } => write!(f, "Limit exceeded \"{}\"", msg),
         }
     }
 }
 
 impl std::error::Error for CompileError {}
 
+/// This is returned by `validate_with_features` when a module isn't valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationFailure {
+    /// An error message.
+    pub message: String,
+    /// The offset in the module where validation failed, if known.
+    pub offset: Option<usize>,
+}
+
+impl std::fmt::Display for ValidationFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{} (at offset {})", self.message, offset),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ValidationFailure {}
+
 /// This is returned when the runtime is unable to
 /// correctly link the module with the provided imports.
 ///
@@ -247,6 +281,15 @@ pub enum ResolveError {
         /// Name.
         name: String,
     },
+    /// Exported function found with another signature than the requested one.
+    ExportSignature {
+        /// Name.
+        name: String,
+        /// The signature of the exported function.
+        expected: FuncSig,
+        /// The requested signature.
+        found: FuncSig,
+    },
 }
 
 impl PartialEq for ResolveError {
@@ -260,6 +303,15 @@ impl std::fmt::Display for ResolveError {
         match self {
             ResolveError::ExportNotFound { name } => write!(f, "Export not found: {}", name),
             ResolveError::ExportWrongType { name } => write!(f, "Export wrong type: {}", name),
+            ResolveError::ExportSignature {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Export `{}` has signature {} but was requested as {}",
+                name, expected, found
+            ),
             ResolveError::Signature { expected, found } => {
                 let found = found
                     .as_slice()
@@ -330,6 +382,12 @@ pub enum CreationError {
     UnableToCreateTable,
     /// Invalid descriptor error with message.
     InvalidDescriptor(String),
+    /// The `InstancePool` has no free slot of this kind.
+    PoolExhausted(&'static str),
+    /// The module was compiled for this target triple, not for the host.
+    IncompatibleTarget(String),
+    /// The `ResourceLimiter` refused this resource.
+    LimitExceeded(String),
 }
 
 impl PartialEq for CreationError {
@@ -348,6 +406,17 @@ impl std::fmt::Display for CreationError {
                 "Unable to create because the supplied descriptor is invalid: \"{}\"",
                 msg
             ),
+            CreationError::PoolExhausted(kind) => {
+                write!(f, "The instance pool has no free {} slot", kind)
+            }
+            CreationError::IncompatibleTarget(triple) => write!(
+                f,
+                "The module was compiled for {}, which is not the host",
+                triple
+            ),
+            CreationError::LimitExceeded(resource) => {
+                write!(f, "The resource limits don't allow {}", resource)
+            }
         }
     }
 }
@@ -570,6 +639,85 @@ impl Into<GrowError> for MemoryProtectionError {
     }
 }
 
+/// An error occurred while accessing a global.
+#[derive(Debug)]
+pub enum GlobalError {
+    /// Attempted to set an immutable global.
+    ImmutableGlobal,
+    /// The value does not have the type of the global.
+    TypeMismatch {
+        /// The type of the global.
+        expected: Type,
+        /// The type of the value.
+        found: Type,
+    },
+}
+
+impl PartialEq for GlobalError {
+    fn eq(&self, _other: &GlobalError) -> bool {
+        false
+    }
+}
+
+impl std::fmt::Display for GlobalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GlobalError::ImmutableGlobal => write!(f, "Cannot set an immutable global"),
+            GlobalError::TypeMismatch { expected, found } => write!(
+                f,
+                "Global type mismatch: expected {}, found {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GlobalError {}
+
+/// An error occurred while reading or writing the memory of an instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryAccessError {
+    /// The instance has no memory.
+    NoMemory,
+    /// The accessed bytes don't all lie within the memory.
+    OutOfBounds {
+        /// The offset of the first byte.
+        offset: u32,
+        /// The number of bytes.
+        len: usize,
+    },
+    /// The offset of an atomic access isn't aligned to its size.
+    Unaligned {
+        /// The offset of the access.
+        offset: u32,
+        /// The required alignment.
+        align: u32,
+    },
+    /// Only shared memories can be waited on.
+    NotShared,
+}
+
+impl std::fmt::Display for MemoryAccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MemoryAccessError::NoMemory => write!(f, "The instance has no memory"),
+            MemoryAccessError::OutOfBounds { offset, len } => write!(
+                f,
+                "Memory access of {} bytes at offset {:#x} is out of bounds",
+                len, offset
+            ),
+            MemoryAccessError::Unaligned { offset, align } => write!(
+                f,
+                "Atomic access at offset {:#x} is not aligned to {} bytes",
+                offset, align
+            ),
+            MemoryAccessError::NotShared => write!(f, "The memory is not shared"),
+        }
+    }
+}
+
+impl std::error::Error for MemoryAccessError {}
+
 /// Parse Error.
 #[derive(Debug)]
 pub enum ParseError {
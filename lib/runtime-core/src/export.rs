@@ -2,8 +2,15 @@
 //! manipulate and access a wasm module's exports including memories, tables, globals, and
 //! functions.
 use crate::{
-    global::Global, instance::InstanceInner, memory::Memory, module::ExportIndex,
-    module::ModuleInner, table::Table, types::FuncSig, vm,
+    error::ResolveResult,
+    global::Global,
+    instance::{self, InstanceInner},
+    memory::Memory,
+    module::{ExportIndex, ModuleInfo, ModuleInner},
+    table::Table,
+    typed_func::{Func, Wasm, WasmTypeList},
+    types::{ExternType, FuncSig, LocalOrImport},
+    vm,
 };
 use indexmap::map::Iter as IndexMapIter;
 use std::{ptr::NonNull, sync::Arc};
@@ -92,3 +99,73 @@ impl<'a> Iterator for ExportIter<'a> {
         ))
     }
 }
+
+/// The exports of an instance, see [`Instance::exports`].
+///
+/// [`Instance::exports`]: ../struct.Instance.html#structfield.exports
+pub struct Exports {
+    module: Arc<ModuleInner>,
+    /// The instance, which owns this and is pinned, so it outlives it.
+    inner: *const InstanceInner,
+}
+
+// Manually implemented because Exports contains a raw pointer to InstanceInner
+unsafe impl Send for Exports {}
+
+impl Exports {
+    pub(crate) fn new(module: Arc<ModuleInner>, inner: &InstanceInner) -> Self {
+        Self {
+            module,
+            inner: inner as *const InstanceInner,
+        }
+    }
+
+    /// Iterate over the names and types of the exports, in the order of the
+    /// module.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, ExternType)> {
+        let info = &self.module.info;
+        info.exports
+            .iter()
+            .map(move |(name, index)| (name.as_str(), extern_type(info, index)))
+    }
+
+    /// The type of the export `name`, if there's one.
+    pub fn get_type(&self, name: &str) -> Option<ExternType> {
+        let info = &self.module.info;
+        info.exports.get(name).map(|index| extern_type(info, index))
+    }
+
+    /// The exported function `name` as a `Func<Args, Rets>`.
+    ///
+    /// Fails with [`ResolveError::ExportSignature`], naming both signatures,
+    /// if the function doesn't take `Args` and return `Rets`.
+    ///
+    /// [`ResolveError::ExportSignature`]: ../error/enum.ResolveError.html#variant.ExportSignature
+    pub fn get_typed_func<Args, Rets>(&self, name: &str) -> ResolveResult<Func<Args, Rets, Wasm>>
+    where
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        instance::typed_func(&self.module, unsafe { &*self.inner }, name)
+    }
+}
+
+fn extern_type(info: &ModuleInfo, index: &ExportIndex) -> ExternType {
+    match *index {
+        ExportIndex::Func(index) => {
+            ExternType::Function(info.signatures[info.func_assoc[index]].clone())
+        }
+        ExportIndex::Memory(index) => ExternType::Memory(match index.local_or_import(info) {
+            LocalOrImport::Local(index) => info.memories[index],
+            LocalOrImport::Import(index) => info.imported_memories[index].1,
+        }),
+        ExportIndex::Table(index) => ExternType::Table(match index.local_or_import(info) {
+            LocalOrImport::Local(index) => info.tables[index],
+            LocalOrImport::Import(index) => info.imported_tables[index].1,
+        }),
+        ExportIndex::Global(index) => ExternType::Global(match index.local_or_import(info) {
+            LocalOrImport::Local(index) => info.globals[index].desc,
+            LocalOrImport::Import(index) => info.imported_globals[index].1,
+        }),
+    }
+}
@@ -85,6 +85,7 @@ thread_local! {
     static CURRENT_CTX: UnsafeCell<*mut vm::Ctx> = UnsafeCell::new(::std::ptr::null_mut());
     static CURRENT_CODE_VERSIONS: RefCell<Vec<CodeVersion>> = RefCell::new(vec![]);
     static WAS_SIGINT_TRIGGERED: Cell<bool> = Cell::new(false);
+    static LAST_BACKTRACE: RefCell<Option<ExecutionStateImage>> = RefCell::new(None);
     static BOUNDARY_REGISTER_PRESERVATION: UnsafeCell<BoundaryRegisterPreservation> = UnsafeCell::new(BoundaryRegisterPreservation::default());
 }
 
@@ -119,12 +120,32 @@ lazy_static! {
     };
 }
 static INTERRUPT_SIGNAL_DELIVERED: AtomicBool = AtomicBool::new(false);
+static TERMINATION_REQUESTED: AtomicBool = AtomicBool::new(false);
+static CAPTURE_BACKTRACES: AtomicBool = AtomicBool::new(false);
+
+/// The error a call into wasm fails with when [`terminate_wasm`] stopped it.
+///
+/// This is returned in the `Box<dyn Any>` `RuntimeError::Error` variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmTerminated;
 
 /// Returns a boolean indicating if SIGINT triggered the fault.
 pub fn was_sigint_triggered_fault() -> bool {
     WAS_SIGINT_TRIGGERED.with(|x| x.get())
 }
 
+/// Makes faults in wasm code keep their backtrace for `take_backtrace`,
+/// instead of printing it to stderr.
+pub fn set_capture_backtraces(capture: bool) {
+    CAPTURE_BACKTRACES.store(capture, Ordering::SeqCst);
+}
+
+/// Takes the backtrace of the last fault in wasm code on this thread, if
+/// backtraces are captured, see `set_capture_backtraces`.
+pub fn take_backtrace() -> Option<ExecutionStateImage> {
+    LAST_BACKTRACE.with(|x| x.borrow_mut().take())
+}
+
 /// Runs a callback function with the given `Ctx`.
 pub unsafe fn with_ctx<R, F: FnOnce() -> R>(ctx: *mut vm::Ctx, cb: F) -> R {
     let addr = CURRENT_CTX.with(|x| x.get());
@@ -178,6 +199,17 @@ pub unsafe fn clear_wasm_interrupt() {
     }
 }
 
+/// Stops the wasm code that reaches an interrupt check next, on any thread,
+/// making its call fail with [`WasmTerminated`].
+///
+/// Interrupt checks are only emitted by the singlepass backend, at function
+/// entries and loop headers, so code compiled by other backends, and host
+/// functions, keep running.  A single call is stopped per request.
+pub fn terminate_wasm() {
+    TERMINATION_REQUESTED.store(true, Ordering::SeqCst);
+    unsafe { set_wasm_interrupt() };
+}
+
 /// Catches an unsafe unwind with the given functions and breakpoints.
 pub unsafe fn catch_unsafe_unwind<R, F: FnOnce() -> R>(
     f: F,
@@ -365,8 +397,22 @@ extern "C" fn signal_trap_handler(
                 }
                 Ok(SIGSEGV) | Ok(SIGBUS) => {
                     if fault.faulting_addr as usize == get_wasm_interrupt_signal_mem() as usize {
-                        is_suspend_signal = true;
                         clear_wasm_interrupt();
+                        if TERMINATION_REQUESTED.swap(false, Ordering::SeqCst) {
+                            unwind_result = Box::new(WasmTerminated);
+                            return true;
+                        }
+                        if !INTERRUPT_SIGNAL_DELIVERED.load(Ordering::SeqCst)
+                            && crate::profiler::take_sample_request()
+                        {
+                            // Record the stack and resume at the interrupt check.
+                            if let Some(es_image) = fault.read_stack(None) {
+                                let ctx = *CURRENT_CTX.with(|x| x.get());
+                                crate::profiler::record_sample(ctx, &es_image);
+                            }
+                            return false;
+                        }
+                        is_suspend_signal = true;
                         if INTERRUPT_SIGNAL_DELIVERED.swap(false, Ordering::SeqCst) {
                             WAS_SIGINT_TRIGGERED.with(|x| x.set(true));
                         }
@@ -383,13 +429,15 @@ extern "C" fn signal_trap_handler(
             if is_suspend_signal {
                 let image = build_instance_image(ctx, es_image);
                 unwind_result = Box::new(image);
+            } else if CAPTURE_BACKTRACES.load(Ordering::SeqCst) {
+                LAST_BACKTRACE.with(|x| *x.borrow_mut() = Some(es_image));
             } else {
                 if es_image.frames.len() > 0 {
                     eprintln!(
                         "\n{}",
                         "Wasmer encountered an error while running your WebAssembly program."
                     );
-                    es_image.print_backtrace_if_needed();
+                    es_image.print_backtrace_with_names_if_needed(Some(&(*ctx.module).info));
                 }
                 // Just let the error propagate otherwise
             }
@@ -418,14 +466,21 @@ extern "C" fn sigint_handler(
 }
 
 /// Ensure the signal handler is installed.
+///
+/// Without the `signal-handlers` feature this does nothing, and faults in
+/// compiled code kill the process instead of trapping: only backends
+/// checking bounds themselves, like the interpreter, can be used.
 pub fn ensure_sighandler() {
+    #[cfg(feature = "signal-handlers")]
     INSTALL_SIGHANDLER.call_once(|| unsafe {
         install_sighandler();
     });
 }
 
+#[cfg_attr(not(feature = "signal-handlers"), allow(dead_code))]
 static INSTALL_SIGHANDLER: Once = Once::new();
 
+#[cfg_attr(not(feature = "signal-handlers"), allow(dead_code))]
 unsafe fn install_sighandler() {
     let sa_trap = SigAction::new(
         SigHandler::SigAction(signal_trap_handler),
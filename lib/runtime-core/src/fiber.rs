@@ -0,0 +1,252 @@
+//! Running calls into WebAssembly on dedicated stacks, or fibers.
+//!
+//! Wasm called from the host normally runs on the host thread's stack, which
+//! is shared by every call of a host → wasm → host → wasm chain and may
+//! overflow unpredictably.  An instance given a [`FiberStack`] with
+//! [`Instance::set_stack_size`] runs the calls entering it from the host on
+//! that stack instead; calls made while it already runs there, e.g. from one
+//! of its host functions, stay on it.
+//!
+//! The lowest page of a fiber stack is a guard page, so running out of stack
+//! faults and traps instead of overwriting other memory.  Backends that check
+//! the stack pointer against the `Ctx`'s `stack_lower_bound`, like singlepass
+//! with `enforce_stack_check`, trap before that and leave
+//! [`HOST_STACK_RESERVE`] bytes to the host functions the guest calls.
+//!
+//! Fiber stacks are only supported on unix x86-64.
+//!
+//! [`Instance::set_stack_size`]: crate::instance::Instance::set_stack_size
+
+use crate::vm::{Ctx, InternalField};
+use std::{io, panic, ptr};
+
+/// Bytes at the bottom of a fiber stack that are kept for host functions when
+/// the backend checks the stack pointer.
+pub const HOST_STACK_RESERVE: usize = 64 * 1024;
+
+/// The `FiberStack` of the instance, or 0.
+static FIBER_STACK: InternalField = InternalField::allocate();
+
+/// A stack calls into an instance run on.
+pub struct FiberStack {
+    /// The guard page, followed by the stack.
+    base: *mut u8,
+    len: usize,
+    guard_len: usize,
+    /// Whether a call into the instance runs on the stack.
+    active: bool,
+}
+
+// Manually implemented because FiberStack contains a raw pointer to memory it owns
+unsafe impl Send for FiberStack {}
+
+impl FiberStack {
+    /// Allocate a stack of at least `size` bytes, plus its guard page.
+    pub fn new(size: usize) -> io::Result<Self> {
+        if size < 2 * HOST_STACK_RESERVE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "fiber stacks must be at least {} bytes",
+                    2 * HOST_STACK_RESERVE
+                ),
+            ));
+        }
+
+        let page_size = page_size::get();
+        let len = page_size + (size + page_size - 1) / page_size * page_size;
+        let base = unsafe { map_stack(len, page_size)? };
+
+        Ok(Self {
+            base,
+            len,
+            guard_len: page_size,
+            active: false,
+        })
+    }
+
+    /// The size of the stack in bytes, without the guard page.
+    pub fn size(&self) -> usize {
+        self.len - self.guard_len
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        let base = self.base as usize;
+        addr >= base && addr < base + self.len
+    }
+
+    fn lower_bound(&self) -> *mut u8 {
+        unsafe { self.base.add(self.guard_len + HOST_STACK_RESERVE) }
+    }
+}
+
+impl Drop for FiberStack {
+    fn drop(&mut self) {
+        unsafe { unmap_stack(self.base, self.len) }
+    }
+}
+
+/// Make `stack` the stack the instance of `ctx` runs on.
+///
+/// The caller keeps `stack` alive, and in place, as long as the instance.
+pub(crate) unsafe fn set_instance_stack(ctx: *mut Ctx, stack: &mut FiberStack) {
+    (*ctx).set_internal(&FIBER_STACK, stack as *mut FiberStack as usize as u64);
+}
+
+/// Run `f`, which calls into the instance of `ctx`, on the instance's fiber
+/// stack if it has one.
+pub(crate) fn on_instance_stack<R, F: FnOnce() -> R>(ctx: *mut Ctx, f: F) -> R {
+    #[cfg(unix)]
+    let f = move || crate::profiler::with_profiling(ctx, f);
+
+    let stack = unsafe { (*ctx).get_internal(&FIBER_STACK) } as usize as *mut FiberStack;
+    if stack.is_null() {
+        return f();
+    }
+    // nested calls reach the stack and the bound through `ctx` too, so they
+    // are only accessed through raw pointers
+    let lower_bound: *mut *mut u8 = unsafe { &mut (*ctx).internal.stack_lower_bound };
+    let set_lower_bound = |bound| unsafe { *lower_bound = bound };
+    let set_active = |active| unsafe { (*stack).active = active };
+
+    let (active, base, len, stack_lower_bound) = unsafe {
+        (
+            (*stack).active,
+            (*stack).base,
+            (*stack).len,
+            (*stack).lower_bound(),
+        )
+    };
+    if !active {
+        set_active(true);
+        set_lower_bound(stack_lower_bound);
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| unsafe {
+            run_on_stack(base, len, f)
+        }));
+        set_lower_bound(ptr::null_mut());
+        set_active(false);
+
+        return result.unwrap_or_else(|e| panic::resume_unwind(e));
+    }
+
+    let sp_marker = 0u8;
+    if unsafe { (*stack).contains(&sp_marker as *const u8 as usize) } {
+        return f();
+    }
+
+    // The instance was entered again from another stack, while its own stack
+    // is in use further up the chain: run here, without a bound that belongs
+    // to the other stack.
+    let saved_lower_bound = unsafe { *lower_bound };
+    set_lower_bound(ptr::null_mut());
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
+    set_lower_bound(saved_lower_bound);
+    result.unwrap_or_else(|e| panic::resume_unwind(e))
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+unsafe fn map_stack(len: usize, guard_len: usize) -> io::Result<*mut u8> {
+    use libc::{
+        mmap, mprotect, MAP_ANON, MAP_FAILED, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE,
+    };
+
+    let base = mmap(
+        ptr::null_mut(),
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANON,
+        -1,
+        0,
+    );
+    if base == MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    if mprotect(base, guard_len, PROT_NONE) != 0 {
+        let error = io::Error::last_os_error();
+        libc::munmap(base, len);
+        return Err(error);
+    }
+    Ok(base as *mut u8)
+}
+
+#[cfg(not(all(unix, target_arch = "x86_64")))]
+unsafe fn map_stack(_len: usize, _guard_len: usize) -> io::Result<*mut u8> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "fiber stacks are not supported on this platform",
+    ))
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+unsafe fn unmap_stack(base: *mut u8, len: usize) {
+    libc::munmap(base as *mut _, len);
+}
+
+#[cfg(not(all(unix, target_arch = "x86_64")))]
+unsafe fn unmap_stack(_base: *mut u8, _len: usize) {}
+
+/// Run `f` on the stack `[base..base + len]`, like `fault::allocate_and_run`.
+#[cfg(all(unix, target_arch = "x86_64"))]
+unsafe fn run_on_stack<R, F: FnOnce() -> R>(base: *mut u8, len: usize, f: F) -> R {
+    struct Context<F: FnOnce() -> R, R> {
+        f: Option<F>,
+        ret: Option<std::thread::Result<R>>,
+    }
+
+    extern "C" fn invoke<F: FnOnce() -> R, R>(ctx: &mut Context<F, R>) {
+        let f = ctx.f.take().unwrap();
+        // unwinding must not cross the switch back to the caller's stack
+        ctx.ret = Some(panic::catch_unwind(panic::AssertUnwindSafe(f)));
+    }
+
+    let mut ctx = Context {
+        f: Some(f),
+        ret: None,
+    };
+
+    let stack_end = base.add(len) as *mut u64;
+    *stack_end.sub(4) = invoke::<F, R> as usize as u64;
+    // NOTE: Keep this consistent with `image-loading-*.s`.
+    *stack_end.sub(4 + 10) = &mut ctx as *mut Context<F, R> as usize as u64; // rdi
+    const NUM_SAVED_REGISTERS: usize = 31;
+    let stack_begin = stack_end.sub(4 + NUM_SAVED_REGISTERS);
+
+    crate::fault::run_on_alternative_stack(stack_end, stack_begin);
+    match ctx.ret.take().unwrap() {
+        Ok(ret) => ret,
+        Err(e) => panic::resume_unwind(e),
+    }
+}
+
+#[cfg(not(all(unix, target_arch = "x86_64")))]
+unsafe fn run_on_stack<R, F: FnOnce() -> R>(_base: *mut u8, _len: usize, f: F) -> R {
+    f()
+}
+
+#[cfg(all(test, unix, target_arch = "x86_64"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn run_on_fiber_stack() {
+        assert!(FiberStack::new(HOST_STACK_RESERVE).is_err());
+
+        let stack = FiberStack::new(256 * 1024).unwrap();
+        assert!(stack.size() >= 256 * 1024);
+
+        let on_stack = unsafe {
+            run_on_stack(stack.base, stack.len, || {
+                let marker = 0u8;
+                stack.contains(&marker as *const u8 as usize)
+            })
+        };
+        assert!(on_stack);
+
+        let panicked = panic::catch_unwind(|| unsafe {
+            run_on_stack(stack.base, stack.len, || {
+                panic::resume_unwind(Box::new(42u32))
+            })
+        });
+        assert_eq!(panicked.unwrap_err().downcast_ref::<u32>(), Some(&42));
+    }
+}
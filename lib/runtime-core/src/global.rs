@@ -1,9 +1,10 @@
 //! The global module contains the implementation data structures and helper functions used to
 //! manipulate and access a wasm globals.
 use crate::{
+    error::GlobalError,
     export::Export,
     import::IsExport,
-    types::{GlobalDescriptor, Type, Value},
+    types::{GlobalDescriptor, NativeWasmType, Type, Value},
     vm,
 };
 use std::{
@@ -73,30 +74,33 @@ impl Global {
         self.desc
     }
 
-    /// Set the value help by this global.
+    /// Set the value held by this global.
     ///
-    /// This method will panic if the value is
-    /// the wrong type.
-    pub fn set(&self, value: Value) {
-        if self.desc.mutable {
-            if self.desc.ty == value.ty() {
-                let local_global = vm::LocalGlobal {
-                    data: match value {
-                        Value::I32(x) => x as u128,
-                        Value::I64(x) => x as u128,
-                        Value::F32(x) => x.to_bits() as u128,
-                        Value::F64(x) => x.to_bits() as u128,
-                        Value::V128(x) => x,
-                    },
-                };
-                let mut storage = self.storage.lock().unwrap();
-                *storage = local_global;
-            } else {
-                panic!("Wrong type for setting this global")
-            }
-        } else {
-            panic!("Cannot modify global immutable by default")
+    /// Returns an error if the global is immutable or
+    /// the value is the wrong type.
+    pub fn set(&self, value: Value) -> Result<(), GlobalError> {
+        if !self.desc.mutable {
+            return Err(GlobalError::ImmutableGlobal);
+        }
+        if self.desc.ty != value.ty() {
+            return Err(GlobalError::TypeMismatch {
+                expected: self.desc.ty,
+                found: value.ty(),
+            });
         }
+
+        let local_global = vm::LocalGlobal {
+            data: match value {
+                Value::I32(x) => x as u128,
+                Value::I64(x) => x as u128,
+                Value::F32(x) => x.to_bits() as u128,
+                Value::F64(x) => x.to_bits() as u128,
+                Value::V128(x) => x,
+            },
+        };
+        let mut storage = self.storage.lock().unwrap();
+        *storage = local_global;
+        Ok(())
     }
 
     /// Get the value held by this global.
@@ -113,6 +117,38 @@ impl Global {
         }
     }
 
+    /// Get the value held by this global as a native type.
+    ///
+    /// Returns an error if `T` is not the type of the global.
+    ///
+    /// Usage:
+    ///
+    /// ```
+    /// # use wasmer_runtime_core::global::Global;
+    /// # use wasmer_runtime_core::types::Value;
+    /// let global = Global::new(Value::I32(42));
+    /// assert_eq!(global.get_as::<i32>().unwrap(), 42);
+    /// assert!(global.get_as::<i64>().is_err());
+    /// ```
+    pub fn get_as<T: NativeWasmType>(&self) -> Result<T, GlobalError> {
+        if self.desc.ty != T::TYPE {
+            return Err(GlobalError::TypeMismatch {
+                expected: self.desc.ty,
+                found: T::TYPE,
+            });
+        }
+        let storage = self.storage.lock().unwrap();
+        Ok(T::from_binary(storage.data as u64))
+    }
+
+    /// Set the value held by this global from a native type.
+    ///
+    /// Returns an error if the global is immutable or
+    /// `T` is not the type of the global.
+    pub fn set_as<T: NativeWasmType>(&self, value: T) -> Result<(), GlobalError> {
+        self.set(value.into())
+    }
+
     // TODO: think about this and if this should now be unsafe
     pub(crate) fn vm_local_global(&mut self) -> *mut vm::LocalGlobal {
         let mut storage = self.storage.lock().unwrap();
@@ -143,3 +179,32 @@ impl fmt::Debug for Global {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod global_tests {
+    use super::Global;
+    use crate::error::GlobalError;
+    use crate::types::{Type, Value};
+
+    #[test]
+    fn test_global_set_checks_mutability_and_type() {
+        let immutable = Global::new(Value::I32(1));
+        match immutable.set(Value::I32(2)) {
+            Err(GlobalError::ImmutableGlobal) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mutable = Global::new_mutable(Value::I32(1));
+        match mutable.set(Value::I64(2)) {
+            Err(GlobalError::TypeMismatch { expected, found }) => {
+                assert_eq!(expected, Type::I32);
+                assert_eq!(found, Type::I64);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        mutable.set_as(3i32).unwrap();
+        assert_eq!(mutable.get(), Value::I32(3));
+        assert_eq!(mutable.get_as::<i32>().unwrap(), 3);
+    }
+}
@@ -1,12 +1,18 @@
 //! The import module contains the implementation data structures and helper functions used to
 //! manipulate and access a wasm module's imports including memories, tables, globals, and
 //! functions.
-use crate::export::Export;
+use crate::{export::Export, limits::ResourceLimiter, metrics::RuntimeMetrics};
+use smallvec::SmallVec;
 use std::collections::VecDeque;
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{
+    hash_map::{DefaultHasher, Entry},
+    HashMap,
+};
 use std::{
     borrow::{Borrow, BorrowMut},
     ffi::c_void,
+    hash::{BuildHasherDefault, Hash, Hasher},
+    ops::Range,
     sync::{Arc, Mutex},
 };
 
@@ -54,22 +60,35 @@ impl IsExport for Export {
 ///     n
 /// }
 /// ```
+///
+/// An `ImportObject` reused for many instantiations, especially one
+/// providing thousands of host functions, should be [frozen] once it's
+/// complete: a frozen `ImportObject` resolves imports without locking and
+/// with a single hash lookup per import.
+///
+/// [frozen]: ImportObject::freeze
 pub struct ImportObject {
-    map: Arc<Mutex<HashMap<String, Box<dyn LikeNamespace + Send>>>>,
+    map: ImportMap,
     pub(crate) state_creator:
         Option<Arc<dyn Fn() -> (*mut c_void, fn(*mut c_void)) + Send + Sync + 'static>>,
     /// Allow missing functions to be generated and instantiation to continue when required
     /// functions are not provided.
     pub allow_missing_functions: bool,
+    pub(crate) metrics: Option<Arc<dyn RuntimeMetrics>>,
+    pub(crate) limiter: Option<Arc<dyn ResourceLimiter>>,
+    rewriter: Option<Arc<ImportRewriter>>,
 }
 
 impl ImportObject {
     /// Create a new `ImportObject`.
     pub fn new() -> Self {
         Self {
-            map: Arc::new(Mutex::new(HashMap::new())),
+            map: ImportMap::Mutable(Arc::new(Mutex::new(HashMap::new()))),
             state_creator: None,
             allow_missing_functions: false,
+            metrics: None,
+            limiter: None,
+            rewriter: None,
         }
     }
 
@@ -79,12 +98,47 @@ impl ImportObject {
         F: Fn() -> (*mut c_void, fn(*mut c_void)) + 'static + Send + Sync,
     {
         Self {
-            map: Arc::new(Mutex::new(HashMap::new())),
+            map: ImportMap::Mutable(Arc::new(Mutex::new(HashMap::new()))),
             state_creator: Some(Arc::new(state_creator)),
             allow_missing_functions: false,
+            metrics: None,
+            limiter: None,
+            rewriter: None,
         }
     }
 
+    /// Record the metrics of the instances created from this `ImportObject`
+    /// with `metrics` instead of the global recorder.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn RuntimeMetrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Limit the resources of the instances created from this `ImportObject`
+    /// with `limiter` instead of the global limiter.
+    pub fn set_limiter(&mut self, limiter: Arc<dyn ResourceLimiter>) {
+        self.limiter = Some(limiter);
+    }
+
+    /// Rename the imports of the modules instantiated with this
+    /// `ImportObject` with `rewriter` before resolving them.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::{imports, global::Global, types::Value};
+    /// # use wasmer_runtime_core::import::ImportRewriter;
+    /// let mut import_object = imports! {
+    ///     "env" => {
+    ///         "answer" => Global::new(Value::I32(42)),
+    ///     },
+    /// };
+    /// import_object.set_rewriter(ImportRewriter::new().rename_namespace("host", "env"));
+    ///
+    /// assert!(import_object.get_export("host", "answer").is_some());
+    /// ```
+    pub fn set_rewriter(&mut self, rewriter: ImportRewriter) {
+        self.rewriter = Some(Arc::new(rewriter));
+    }
+
     pub(crate) fn call_state_creator(&self) -> Option<(*mut c_void, fn(*mut c_void))> {
         self.state_creator.as_ref().map(|state_gen| state_gen())
     }
@@ -108,7 +162,7 @@ impl ImportObject {
         S: Into<String>,
         N: LikeNamespace + Send + 'static,
     {
-        let mut guard = self.map.lock().unwrap();
+        let mut guard = self.map.thaw().lock().unwrap();
         let map = guard.borrow_mut();
 
         match map.entry(name.into()) {
@@ -127,12 +181,17 @@ impl ImportObject {
         Func: FnOnce(&(dyn LikeNamespace + Send)) -> InnerRet,
         InnerRet: Sized,
     {
-        let guard = self.map.lock().unwrap();
-        let map_ref = guard.borrow();
-        if map_ref.contains_key(namespace) {
-            Some(f(map_ref[namespace].as_ref()))
-        } else {
-            None
+        match &self.map {
+            ImportMap::Mutable(map) => {
+                let guard = map.lock().unwrap();
+                let map_ref = guard.borrow();
+                if map_ref.contains_key(namespace) {
+                    Some(f(map_ref[namespace].as_ref()))
+                } else {
+                    None
+                }
+            }
+            ImportMap::Frozen(frozen) => frozen.namespace(namespace).map(|ns| f(&ns)),
         }
     }
 
@@ -150,33 +209,326 @@ impl ImportObject {
         Func: FnOnce(&(dyn LikeNamespace + Send)) -> Option<InnerRet>,
         InnerRet: Sized,
     {
-        let guard = self.map.lock().unwrap();
-        let map_ref = guard.borrow();
-        map_ref
-            .get(namespace)
-            .map(|ns| ns.as_ref())
-            .and_then(|ns| f(ns))
+        self.with_namespace(namespace, f).and_then(|ret| ret)
+    }
+
+    /// Gets the export `name` of the namespace `namespace`, after renaming
+    /// them with the [rewriter], if any.
+    ///
+    /// This is how instantiation resolves imports, and doesn't lock once the
+    /// `ImportObject` is frozen.
+    ///
+    /// [rewriter]: ImportObject::set_rewriter
+    pub fn get_export(&self, namespace: &str, name: &str) -> Option<Export> {
+        let (namespace, name) = match &self.rewriter {
+            Some(rewriter) => rewriter.rewrite(namespace, name),
+            None => (namespace, name),
+        };
+        match &self.map {
+            ImportMap::Mutable(_) => self.maybe_with_namespace(namespace, |ns| ns.get_export(name)),
+            ImportMap::Frozen(frozen) => frozen.get(namespace, name).cloned(),
+        }
+    }
+
+    /// Create a frozen copy of this `ImportObject`, with the same state
+    /// creator, metrics and limiter.
+    ///
+    /// The exports of every namespace are collected once and indexed by a
+    /// hash of their namespace and name, so resolving imports doesn't lock
+    /// the namespaces or ask them for their exports anymore.  The frozen
+    /// imports are shared by the objects created with [`clone_ref`], and
+    /// can be used from several threads at once.
+    ///
+    /// Registering or extending a frozen `ImportObject` copies its imports
+    /// first, so the other objects sharing them don't see the change.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::{imports, global::Global, types::Value};
+    /// let import_object = imports! {
+    ///     "env" => {
+    ///         "answer" => Global::new(Value::I32(42)),
+    ///     },
+    /// };
+    /// let import_object = import_object.freeze();
+    ///
+    /// assert!(import_object.is_frozen());
+    /// assert!(import_object.get_export("env", "answer").is_some());
+    /// ```
+    ///
+    /// [`clone_ref`]: ImportObject::clone_ref
+    pub fn freeze(&self) -> Self {
+        let frozen = match &self.map {
+            ImportMap::Mutable(_) => Arc::new(FrozenImports::new(self.get_objects())),
+            ImportMap::Frozen(frozen) => Arc::clone(frozen),
+        };
+        Self {
+            map: ImportMap::Frozen(frozen),
+            state_creator: self.state_creator.clone(),
+            allow_missing_functions: self.allow_missing_functions,
+            metrics: self.metrics.clone(),
+            limiter: self.limiter.clone(),
+            rewriter: self.rewriter.clone(),
+        }
+    }
+
+    /// Returns true if this `ImportObject` was created by [`freeze`] and
+    /// hasn't been modified since.
+    ///
+    /// [`freeze`]: ImportObject::freeze
+    pub fn is_frozen(&self) -> bool {
+        match self.map {
+            ImportMap::Mutable(_) => false,
+            ImportMap::Frozen(_) => true,
+        }
     }
 
     /// Create a clone ref of this namespace.
     pub fn clone_ref(&self) -> Self {
         Self {
-            map: Arc::clone(&self.map),
+            map: self.map.clone(),
             state_creator: self.state_creator.clone(),
             allow_missing_functions: false,
+            metrics: self.metrics.clone(),
+            limiter: self.limiter.clone(),
+            rewriter: self.rewriter.clone(),
         }
     }
 
     fn get_objects(&self) -> VecDeque<(String, String, Export)> {
-        let mut out = VecDeque::new();
-        let guard = self.map.lock().unwrap();
-        let map = guard.borrow();
-        for (name, ns) in map.iter() {
-            for (id, exp) in ns.get_exports() {
-                out.push_back((name.clone(), id, exp));
+        match &self.map {
+            ImportMap::Mutable(map) => {
+                let mut out = VecDeque::new();
+                let guard = map.lock().unwrap();
+                let map = guard.borrow();
+                for (name, ns) in map.iter() {
+                    for (id, exp) in ns.get_exports() {
+                        out.push_back((name.clone(), id, exp));
+                    }
+                }
+                out
+            }
+            ImportMap::Frozen(frozen) => frozen.entries.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Rules renaming the imports of a module before they're resolved, so one
+/// `ImportObject` serves modules importing the same functions from
+/// different namespaces, e.g. `env` and `host`.
+///
+/// A rule renaming a single import takes precedence over one renaming its
+/// namespace.  Renamed imports are only looked up by their new name.
+///
+/// # Usage:
+/// ```
+/// # use wasmer_runtime_core::import::ImportRewriter;
+/// let rewriter = ImportRewriter::new()
+///     .rename_namespace("host", "env")
+///     .rename_import("host", "print_str", "env", "print");
+///
+/// assert_eq!(rewriter.rewrite("host", "abort"), ("env", "abort"));
+/// assert_eq!(rewriter.rewrite("host", "print_str"), ("env", "print"));
+/// assert_eq!(rewriter.rewrite("wasi_unstable", "fd_write"), ("wasi_unstable", "fd_write"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ImportRewriter {
+    /// The new name of each renamed namespace.
+    namespaces: HashMap<String, String>,
+    /// The new namespace and name of the renamed imports of each namespace.
+    imports: HashMap<String, HashMap<String, (String, String)>>,
+}
+
+impl ImportRewriter {
+    /// Create an `ImportRewriter` without rules, which renames nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve the imports of the namespace `from` in the namespace `to`.
+    pub fn rename_namespace<S, T>(mut self, from: S, to: T) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.namespaces.insert(from.into(), to.into());
+        self
+    }
+
+    /// Resolve the import `name` of the namespace `namespace` as the import
+    /// `to_name` of the namespace `to_namespace`.
+    pub fn rename_import<S1, S2, T1, T2>(
+        mut self,
+        namespace: S1,
+        name: S2,
+        to_namespace: T1,
+        to_name: T2,
+    ) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        T1: Into<String>,
+        T2: Into<String>,
+    {
+        self.imports
+            .entry(namespace.into())
+            .or_default()
+            .insert(name.into(), (to_namespace.into(), to_name.into()));
+        self
+    }
+
+    /// The namespace and name the import `name` of `namespace` is resolved as.
+    pub fn rewrite<'a>(&'a self, namespace: &'a str, name: &'a str) -> (&'a str, &'a str) {
+        if let Some((to_namespace, to_name)) = self
+            .imports
+            .get(namespace)
+            .and_then(|imports| imports.get(name))
+        {
+            return (to_namespace, to_name);
+        }
+        match self.namespaces.get(namespace) {
+            Some(to_namespace) => (to_namespace, name),
+            None => (namespace, name),
+        }
+    }
+}
+
+/// The namespaces of an `ImportObject`.
+#[derive(Clone)]
+enum ImportMap {
+    /// Namespaces which can be registered and extended.
+    Mutable(Arc<Mutex<HashMap<String, Box<dyn LikeNamespace + Send>>>>),
+    /// The imports of a frozen `ImportObject`.
+    Frozen(Arc<FrozenImports>),
+}
+
+impl ImportMap {
+    /// Gets the mutable namespaces, copying the frozen imports into new
+    /// namespaces first.
+    fn thaw(&mut self) -> &Mutex<HashMap<String, Box<dyn LikeNamespace + Send>>> {
+        if let ImportMap::Frozen(frozen) = self {
+            let mut map: HashMap<String, Box<dyn LikeNamespace + Send>> = HashMap::new();
+            for (namespace, range) in &frozen.namespaces {
+                let mut new_ns = Namespace::new();
+                for (_, name, export) in &frozen.entries[range.clone()] {
+                    new_ns.insert(name.clone(), export.clone());
+                }
+                map.insert(namespace.clone(), Box::new(new_ns));
             }
+            *self = ImportMap::Mutable(Arc::new(Mutex::new(map)));
+        }
+        match self {
+            ImportMap::Mutable(map) => map,
+            ImportMap::Frozen(_) => unreachable!(),
+        }
+    }
+}
+
+/// Hashes the `u64` keys of `FrozenImports::index`, which are already hashes.
+#[derive(Default)]
+struct PrehashedHasher(u64);
+
+impl Hasher for PrehashedHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | u64::from(byte);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
+    }
+}
+
+/// The exports of a frozen `ImportObject`.
+struct FrozenImports {
+    /// The `(namespace, name, export)` entries, grouped by namespace.
+    entries: Vec<(String, String, Export)>,
+    /// The range of the entries of each namespace.
+    namespaces: HashMap<String, Range<usize>>,
+    /// The entries with each hash of a namespace and name.
+    index: HashMap<u64, SmallVec<[usize; 1]>, BuildHasherDefault<PrehashedHasher>>,
+}
+
+// Manually implemented because the exports contain raw pointers.  The
+// frozen exports are never modified, only cloned.
+unsafe impl Sync for FrozenImports {}
+
+impl FrozenImports {
+    fn new(objects: VecDeque<(String, String, Export)>) -> Self {
+        let mut entries: Vec<_> = objects.into_iter().collect();
+        entries.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+        let mut namespaces: HashMap<String, Range<usize>> = HashMap::new();
+        let mut index: HashMap<u64, SmallVec<[usize; 1]>, _> = HashMap::default();
+        index.reserve(entries.len());
+        for (i, (namespace, name, _)) in entries.iter().enumerate() {
+            namespaces.entry(namespace.clone()).or_insert(i..i).end = i + 1;
+            index
+                .entry(hash_import(namespace, name))
+                .or_default()
+                .push(i);
+        }
+
+        Self {
+            entries,
+            namespaces,
+            index,
         }
-        out
+    }
+
+    fn get(&self, namespace: &str, name: &str) -> Option<&Export> {
+        self.index
+            .get(&hash_import(namespace, name))?
+            .iter()
+            .map(|&i| &self.entries[i])
+            .find(|(ns, n, _)| ns == namespace && n == name)
+            .map(|(_, _, export)| export)
+    }
+
+    fn namespace<'a>(&'a self, namespace: &'a str) -> Option<FrozenNamespace<'a>> {
+        let range = self.namespaces.get(namespace)?.clone();
+        Some(FrozenNamespace {
+            imports: self,
+            namespace,
+            range,
+        })
+    }
+}
+
+/// Hash a namespace and a name, as the key of `FrozenImports::index`.
+fn hash_import(namespace: &str, name: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    namespace.hash(&mut hasher);
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A namespace of a frozen `ImportObject`, which can't be inserted into.
+struct FrozenNamespace<'a> {
+    imports: &'a FrozenImports,
+    namespace: &'a str,
+    range: Range<usize>,
+}
+
+impl<'a> LikeNamespace for FrozenNamespace<'a> {
+    fn get_export(&self, name: &str) -> Option<Export> {
+        self.imports.get(self.namespace, name).cloned()
+    }
+
+    fn get_exports(&self) -> Vec<(String, Export)> {
+        self.imports.entries[self.range.clone()]
+            .iter()
+            .map(|(_, name, export)| (name.clone(), export.clone()))
+            .collect()
+    }
+
+    fn maybe_insert(&mut self, _name: &str, _export: Export) -> Option<()> {
+        None
     }
 }
 
@@ -205,7 +557,7 @@ impl IntoIterator for ImportObject {
 
 impl Extend<(String, String, Export)> for ImportObject {
     fn extend<T: IntoIterator<Item = (String, String, Export)>>(&mut self, iter: T) {
-        let mut guard = self.map.lock().unwrap();
+        let mut guard = self.map.thaw().lock().unwrap();
         let map = guard.borrow_mut();
         for (ns, id, exp) in iter.into_iter() {
             if let Some(like_ns) = map.get_mut(&ns) {
@@ -270,6 +622,7 @@ impl LikeNamespace for Namespace {
 
 #[cfg(test)]
 mod test {
+    use super::ImportRewriter;
     use crate::export::Export;
     use crate::global::Global;
     use crate::types::Value;
@@ -328,4 +681,77 @@ mod test {
             false
         });
     }
+
+    #[test]
+    fn frozen_imports_resolve() {
+        let imports = imports! {
+            "dog" => {
+                "happy" => Global::new(Value::I32(0)),
+                "small" => Global::new(Value::I32(1)),
+            },
+            "cat" => {
+                "small" => Global::new(Value::I32(2)),
+            },
+        };
+        let frozen = imports.freeze();
+        assert!(frozen.is_frozen());
+
+        let small_cat = frozen.get_export("cat", "small").unwrap();
+        assert!(if let Export::Global(global) = small_cat {
+            global.get() == Value::I32(2)
+        } else {
+            false
+        });
+        assert!(frozen.get_export("cat", "happy").is_none());
+        assert!(frozen.get_export("bird", "small").is_none());
+
+        let dog_exports = frozen.with_namespace("dog", |dog_ns| dog_ns.get_exports().len());
+        assert_eq!(dog_exports, Some(2));
+        assert_eq!(frozen.clone_ref().into_iter().count(), 3);
+    }
+
+    #[test]
+    fn registering_thaws_a_copy() {
+        let frozen = imports! {
+            "dog" => {
+                "happy" => Global::new(Value::I32(0)),
+            },
+        }
+        .freeze();
+        let mut thawed = frozen.clone_ref();
+
+        thawed.extend(vec![(
+            "cat".to_string(),
+            "small".to_string(),
+            Export::Global(Global::new(Value::I32(3))),
+        )]);
+
+        assert!(!thawed.is_frozen());
+        assert!(thawed.get_export("dog", "happy").is_some());
+        assert!(thawed.get_export("cat", "small").is_some());
+        assert!(frozen.get_export("cat", "small").is_none());
+    }
+
+    #[test]
+    fn rewritten_imports_resolve() {
+        let mut imports = imports! {
+            "env" => {
+                "happy" => Global::new(Value::I32(0)),
+                "small" => Global::new(Value::I32(1)),
+            },
+        };
+        imports.set_rewriter(
+            ImportRewriter::new()
+                .rename_namespace("dog", "env")
+                .rename_import("cat", "tiny", "env", "small"),
+        );
+
+        assert!(imports.get_export("dog", "happy").is_some());
+        assert!(imports.get_export("cat", "tiny").is_some());
+        assert!(imports.get_export("cat", "happy").is_none());
+
+        let frozen = imports.freeze();
+        assert!(frozen.get_export("dog", "small").is_some());
+        assert!(frozen.clone_ref().get_export("cat", "tiny").is_some());
+    }
 }
@@ -1,28 +1,43 @@
 //! The instance module contains the implementation data structures and helper functions used to
 //! manipulate and access wasm instances.
+#[cfg(unix)]
+use crate::profiler::{Profile, ProfilingError};
 use crate::{
     backend::RunnableModule,
     backing::{ImportBacking, LocalBacking},
-    error::{CallError, CallResult, ResolveError, ResolveResult, Result, RuntimeError},
-    export::{Context, Export, ExportIter, FuncPointer},
+    error::{
+        CallError, CallResult, CreationError, MemoryAccessError, ResolveError, ResolveResult,
+        Result, RuntimeError,
+    },
+    export::{Context, Export, ExportIter, Exports, FuncPointer},
+    fiber::{self, FiberStack},
     global::Global,
     import::{ImportObject, LikeNamespace},
+    limits,
     loader::Loader,
-    memory::Memory,
+    memory::{
+        ptr::{Array, WasmPtr},
+        Memory,
+    },
+    metrics,
     module::{ExportIndex, Module, ModuleInfo, ModuleInner},
+    pool::{InstancePool, InstanceSlot},
     sig_registry::SigRegistry,
     structures::TypedIndex,
     table::Table,
     typed_func::{Func, Wasm, WasmTrapInfo, WasmTypeList},
     types::{FuncIndex, FuncSig, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex, Type, Value},
-    vm::{self, InternalField},
+    vm::{self, InternalField, METERING_POINTS_USED},
 };
 use smallvec::{smallvec, SmallVec};
 use std::{
+    any::Any,
+    marker::PhantomData,
     mem,
     pin::Pin,
-    ptr::NonNull,
+    ptr::{self, NonNull},
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 pub(crate) struct InstanceInner {
@@ -37,11 +52,63 @@ unsafe impl Send for InstanceInner {}
 
 impl Drop for InstanceInner {
     fn drop(&mut self) {
+        #[cfg(unix)]
+        crate::profiler::discard(self.vmctx);
+        crate::asyncify::discard_pending(self.vmctx);
+        if let Some(limiter) = &self.backing.limiter {
+            limiter.instance_dropped();
+        }
         // Drop the vmctx.
         unsafe { Box::from_raw(self.vmctx) };
     }
 }
 
+/// The resources used by an instance, see [`Instance::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceStats {
+    /// The size of the memories defined by the instance, in bytes.
+    pub memory_bytes: u64,
+    /// The number of elements of the tables defined by the instance.
+    pub table_elements: u64,
+    /// The number of calls the instance made to host functions.
+    pub host_calls: u64,
+    /// The metering points used, if the module was compiled with the
+    /// metering middleware.
+    pub points_used: Option<u64>,
+}
+
+/// How to instantiate a module, see [`Module::instantiate_with_config`].
+///
+/// [`Module::instantiate_with_config`]: struct.Module.html#method.instantiate_with_config
+#[derive(Debug, Default)]
+pub struct InstanceConfig {
+    data: Option<Box<dyn Any + Send>>,
+    defer_start: bool,
+}
+
+impl InstanceConfig {
+    /// Don't run the start function of the module when instantiating it, but
+    /// when [`Instance::run_start`] is called, e.g. to set globals first.
+    ///
+    /// [`Instance::run_start`]: struct.Instance.html#method.run_start
+    pub fn defer_start(mut self) -> Self {
+        self.defer_start = true;
+        self
+    }
+
+    /// Give the instance `data`, which host functions get back with
+    /// [`Ctx::data`], and which is dropped with the instance.
+    ///
+    /// Unlike [`Ctx::data`]'s raw pointer, the data is there when the start
+    /// function runs.
+    ///
+    /// [`Ctx::data`]: vm/struct.Ctx.html#method.data
+    pub fn with_data<T: Any + Send>(mut self, data: T) -> Self {
+        self.data = Some(Box::new(data));
+        self
+    }
+}
+
 /// An instantiated WebAssembly module.
 ///
 /// An `Instance` represents a WebAssembly module that
@@ -52,13 +119,47 @@ impl Drop for InstanceInner {
 pub struct Instance {
     /// Reference to the module used to instantiate this instance.
     pub module: Arc<ModuleInner>,
+    /// The exports of this instance.
+    pub exports: Exports,
     inner: Pin<Box<InstanceInner>>,
-    #[allow(dead_code)]
     import_object: ImportObject,
+    /// The stack calls into the instance run on, see `set_stack_size`.
+    stack: Option<Box<FiberStack>>,
+    /// The slot of a pooled instance, returned to its pool on drop.
+    #[allow(dead_code)]
+    pool_slot: Option<InstanceSlot>,
+    /// Whether the start function has run, or the module has none.
+    started: bool,
 }
 
 impl Instance {
-    pub(crate) fn new(module: Arc<ModuleInner>, imports: &ImportObject) -> Result<Instance> {
+    pub(crate) fn new(
+        module: Arc<ModuleInner>,
+        imports: &ImportObject,
+        pool: Option<&InstancePool>,
+        config: InstanceConfig,
+    ) -> Result<Instance> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("instantiate", pooled = pool.is_some());
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+        let start = Instant::now();
+
+        if !module.info.runs_on_host() {
+            let triple = module.info.triple.clone().unwrap_or_default();
+            return Err(CreationError::IncompatibleTarget(triple).into());
+        }
+
+        let limiter = imports.limiter.clone().or_else(limits::global);
+        if let Some(limiter) = &limiter {
+            limits::check_module(&**limiter, &module.info)?;
+        }
+
+        let pool_slot = match pool {
+            Some(pool) => Some(pool.take_instance()?),
+            None => None,
+        };
+
         // We need the backing and import_backing to create a vm::Ctx, but we need
         // a vm::Ctx to create a backing and an import_backing. The solution is to create an
         // uninitialized vm::Ctx and then initialize it in-place.
@@ -66,7 +167,14 @@ impl Instance {
             Box::new(mem::MaybeUninit::<vm::Ctx>::zeroed());
 
         let import_backing = ImportBacking::new(&module, &imports, vmctx.as_mut_ptr())?;
-        let backing = LocalBacking::new(&module, &import_backing, vmctx.as_mut_ptr())?;
+        let mut backing = LocalBacking::new(&module, &import_backing, vmctx.as_mut_ptr(), pool)?;
+        backing.metrics = imports.metrics.clone().or_else(metrics::global);
+        if let Some(limiter) = limiter {
+            if !limiter.instance_creating() {
+                return Err(CreationError::LimitExceeded("another instance".to_string()).into());
+            }
+            backing.limiter = Some(limiter);
+        }
 
         let mut inner = Box::pin(InstanceInner {
             backing,
@@ -86,35 +194,76 @@ impl Instance {
                 None => vm::Ctx::new(backing, import_backing, &module),
             };
             vmctx.as_mut_ptr().write(real_ctx);
+            if let Some(data) = config.data {
+                (*vmctx.as_mut_ptr()).typed_data = Some(data);
+            }
         };
         Box::leak(vmctx);
 
-        let instance = Instance {
+        let exports = Exports::new(Arc::clone(&module), &inner);
+        let mut instance = Instance {
             module,
+            exports,
             inner,
             import_object: imports.clone_ref(),
+            stack: None,
+            pool_slot,
+            started: false,
         };
 
-        if let Some(start_index) = instance.module.info.start_func {
+        if !config.defer_start {
+            instance.run_start()?;
+        }
+
+        if let Some(metrics) = &instance.inner.backing.metrics {
+            metrics.instantiated(start.elapsed());
+        }
+
+        Ok(instance)
+    }
+
+    /// Run the start function of the module, if it has one and it hasn't run
+    /// yet, for instances created with [`Module::instantiate_unstarted`].
+    ///
+    /// Instances are otherwise started when they're created, so this does
+    /// nothing for them.
+    ///
+    /// [`Module::instantiate_unstarted`]: struct.Module.html#method.instantiate_unstarted
+    pub fn run_start(&mut self) -> Result<()> {
+        if !self.started {
+            self.started = true;
+            self.call_start_func()?;
+        }
+        Ok(())
+    }
+
+    /// Whether the start function of the module has run, or it has none.
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Call the start function of the module, if it has one.
+    fn call_start_func(&self) -> Result<()> {
+        if let Some(start_index) = self.module.info.start_func {
             // We know that the start function takes no arguments and returns no values.
             // Therefore, we can call it without doing any signature checking, etc.
 
-            let func_ptr = match start_index.local_or_import(&instance.module.info) {
-                LocalOrImport::Local(local_func_index) => instance
+            let func_ptr = match start_index.local_or_import(&self.module.info) {
+                LocalOrImport::Local(local_func_index) => self
                     .module
                     .runnable_module
-                    .get_func(&instance.module.info, local_func_index)
+                    .get_func(&self.module.info, local_func_index)
                     .unwrap(),
                 LocalOrImport::Import(import_func_index) => NonNull::new(
-                    instance.inner.import_backing.vm_functions[import_func_index].func as *mut _,
+                    self.inner.import_backing.vm_functions[import_func_index].func as *mut _,
                 )
                 .unwrap(),
             };
 
-            let ctx_ptr = match start_index.local_or_import(&instance.module.info) {
-                LocalOrImport::Local(_) => instance.inner.vmctx,
+            let ctx_ptr = match start_index.local_or_import(&self.module.info) {
+                LocalOrImport::Local(_) => self.inner.vmctx,
                 LocalOrImport::Import(imported_func_index) => unsafe {
-                    instance.inner.import_backing.vm_functions[imported_func_index]
+                    self.inner.import_backing.vm_functions[imported_func_index]
                         .func_ctx
                         .as_ref()
                 }
@@ -122,17 +271,17 @@ impl Instance {
                 .as_ptr(),
             };
 
-            let sig_index = *instance
+            let sig_index = *self
                 .module
                 .info
                 .func_assoc
                 .get(start_index)
                 .expect("broken invariant, incorrect func index");
 
-            let wasm_trampoline = instance
+            let wasm_trampoline = self
                 .module
                 .runnable_module
-                .get_trampoline(&instance.module.info, sig_index)
+                .get_trampoline(&self.module.info, sig_index)
                 .expect("wasm trampoline");
 
             let start_func: Func<(), (), Wasm> =
@@ -141,7 +290,7 @@ impl Instance {
             start_func.call()?;
         }
 
-        Ok(instance)
+        Ok(())
     }
 
     /// Load an `Instance` using the given loader.
@@ -175,77 +324,7 @@ impl Instance {
         Args: WasmTypeList,
         Rets: WasmTypeList,
     {
-        let export_index =
-            self.module
-                .info
-                .exports
-                .get(name)
-                .ok_or_else(|| ResolveError::ExportNotFound {
-                    name: name.to_string(),
-                })?;
-
-        if let ExportIndex::Func(func_index) = export_index {
-            let sig_index = *self
-                .module
-                .info
-                .func_assoc
-                .get(*func_index)
-                .expect("broken invariant, incorrect func index");
-            let signature =
-                SigRegistry.lookup_signature_ref(&self.module.info.signatures[sig_index]);
-
-            if signature.params() != Args::types() || signature.returns() != Rets::types() {
-                Err(ResolveError::Signature {
-                    expected: (*signature).clone(),
-                    found: Args::types().to_vec(),
-                })?;
-            }
-
-            let ctx = match func_index.local_or_import(&self.module.info) {
-                LocalOrImport::Local(_) => self.inner.vmctx,
-                LocalOrImport::Import(imported_func_index) => unsafe {
-                    self.inner.import_backing.vm_functions[imported_func_index]
-                        .func_ctx
-                        .as_ref()
-                }
-                .vmctx
-                .as_ptr(),
-            };
-
-            let func_wasm_inner = self
-                .module
-                .runnable_module
-                .get_trampoline(&self.module.info, sig_index)
-                .unwrap();
-
-            let (func_ptr, func_env) = match func_index.local_or_import(&self.module.info) {
-                LocalOrImport::Local(local_func_index) => (
-                    self.module
-                        .runnable_module
-                        .get_func(&self.module.info, local_func_index)
-                        .unwrap(),
-                    None,
-                ),
-                LocalOrImport::Import(import_func_index) => {
-                    let imported_func = &self.inner.import_backing.vm_functions[import_func_index];
-
-                    (
-                        NonNull::new(imported_func.func as *mut _).unwrap(),
-                        unsafe { imported_func.func_ctx.as_ref() }.func_env,
-                    )
-                }
-            };
-
-            let typed_func: Func<Args, Rets, Wasm> =
-                unsafe { Func::from_raw_parts(func_wasm_inner, func_ptr, func_env, ctx) };
-
-            Ok(typed_func)
-        } else {
-            Err(ResolveError::ExportWrongType {
-                name: name.to_string(),
-            }
-            .into())
-        }
+        typed_func(&self.module, &self.inner, name)
     }
 
     /// Resolve a function by name.
@@ -373,6 +452,115 @@ impl Instance {
         Ok(results)
     }
 
+    /// Restore this instance to the state it had right after it was
+    /// instantiated, which is much cheaper than instantiating the module
+    /// again.
+    ///
+    /// The locally-defined memories and tables shrink back to their
+    /// minimum size and hold only their data and element segments, and
+    /// mutable globals get their initial values back.  The host data of the [`Ctx`] is finalized and created
+    /// again by the import object's state creator, or cleared if there is
+    /// none; its typed host data is kept.  Finally, the start function runs
+    /// again, even if the instance was created unstarted.
+    ///
+    /// Imported memories, tables and globals belong to the host and are
+    /// left as they are.  Locally-defined ones are reset even if they are
+    /// exported to and imported by other instances, which see the reset
+    /// too.  Instances with a locally-defined shared memory can't be reset:
+    /// other threads may be using it, so an error is returned before
+    /// anything is reset.
+    ///
+    /// [`Ctx`]: struct.Ctx.html
+    pub fn reset(&mut self) -> Result<()> {
+        self.inner
+            .backing
+            .reset(&self.module, &self.inner.import_backing, self.inner.vmctx)?;
+
+        let ctx = self.context_mut();
+        if let Some(finalizer) = ctx.data_finalizer.take() {
+            finalizer(ctx.data);
+        }
+        ctx.data = ptr::null_mut();
+        if let Some((data, finalizer)) = self.import_object.call_state_creator() {
+            let ctx = self.context_mut();
+            ctx.data = data;
+            ctx.data_finalizer = Some(finalizer);
+        }
+
+        self.started = true;
+        self.call_start_func()
+    }
+
+    /// Read `len` bytes at `ptr` from the first memory of this instance.
+    pub fn read_bytes(
+        &self,
+        ptr: WasmPtr<u8, Array>,
+        len: u32,
+    ) -> std::result::Result<Vec<u8>, MemoryAccessError> {
+        let cells =
+            ptr.deref(self.first_memory()?, 0, len)
+                .ok_or(MemoryAccessError::OutOfBounds {
+                    offset: ptr.offset(),
+                    len: len as usize,
+                })?;
+
+        Ok(cells.iter().map(|cell| cell.get()).collect())
+    }
+
+    /// Write `bytes` at `ptr` to the first memory of this instance.
+    ///
+    /// Nothing is written if the bytes don't all fit in the memory.
+    pub fn write_bytes(
+        &self,
+        ptr: WasmPtr<u8, Array>,
+        bytes: &[u8],
+    ) -> std::result::Result<(), MemoryAccessError> {
+        let out_of_bounds = MemoryAccessError::OutOfBounds {
+            offset: ptr.offset(),
+            len: bytes.len(),
+        };
+        if bytes.len() > u32::max_value() as usize {
+            return Err(out_of_bounds);
+        }
+        let cells = ptr
+            .deref(self.first_memory()?, 0, bytes.len() as u32)
+            .ok_or(out_of_bounds)?;
+
+        for (cell, &byte) in cells.iter().zip(bytes) {
+            cell.set(byte);
+        }
+        Ok(())
+    }
+
+    /// Write the UTF-8 bytes of `string`, without a nul terminator, at `ptr`
+    /// to the first memory of this instance.
+    pub fn write_str(
+        &self,
+        ptr: WasmPtr<u8, Array>,
+        string: &str,
+    ) -> std::result::Result<(), MemoryAccessError> {
+        self.write_bytes(ptr, string.as_bytes())
+    }
+
+    fn first_memory(&self) -> std::result::Result<&Memory, MemoryAccessError> {
+        let ctx = self.context();
+        if ctx.memory_count() == 0 {
+            return Err(MemoryAccessError::NoMemory);
+        }
+        Ok(ctx.memory(0))
+    }
+
+    /// Run the calls into this instance on a dedicated stack of `size`
+    /// bytes, instead of the caller's stack.
+    ///
+    /// See the [`fiber`](crate::fiber) module.
+    pub fn set_stack_size(&mut self, size: usize) -> std::io::Result<()> {
+        let mut stack = Box::new(FiberStack::new(size)?);
+        unsafe { fiber::set_instance_stack(self.inner.vmctx, &mut stack) };
+        self.stack = Some(stack);
+        Ok(())
+    }
+
     /// Returns an immutable reference to the
     /// [`Ctx`] used by this Instance.
     ///
@@ -409,6 +597,134 @@ impl Instance {
     pub fn set_internal(&mut self, field: &InternalField, value: u64) {
         self.inner.backing.internals.0[field.index()] = value;
     }
+
+    /// Returns the resources used by this instance so far, e.g. to account
+    /// for the usage of each tenant of a host.
+    ///
+    /// Memories and tables imported by the instance are accounted for by
+    /// the instance that defines them.
+    pub fn stats(&self) -> InstanceStats {
+        let backing = &self.inner.backing;
+        InstanceStats {
+            memory_bytes: backing
+                .memories
+                .iter()
+                .map(|(_, memory)| memory.size().bytes().0 as u64)
+                .sum(),
+            table_elements: backing
+                .tables
+                .iter()
+                .map(|(_, table)| u64::from(table.size()))
+                .sum(),
+            host_calls: backing.host_calls,
+            points_used: if self.module.info.metered {
+                Some(self.get_internal(&METERING_POINTS_USED))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Starts sampling the wasm call stack of this instance every `interval`,
+    /// until [`stop_profiling`] is called.
+    ///
+    /// The module must have been compiled by the singlepass backend with
+    /// [`CompilerConfig::track_state`]; see the [`profiler`] module.
+    ///
+    /// [`stop_profiling`]: #method.stop_profiling
+    /// [`CompilerConfig::track_state`]: ../backend/struct.CompilerConfig.html#structfield.track_state
+    /// [`profiler`]: ../profiler/index.html
+    #[cfg(unix)]
+    pub fn start_profiling(&self, interval: Duration) -> std::result::Result<(), ProfilingError> {
+        crate::profiler::start(self.inner.vmctx, &self.module, interval)
+    }
+
+    /// Stops profiling this instance and returns the samples taken, or
+    /// `None` if it wasn't being profiled.
+    #[cfg(unix)]
+    pub fn stop_profiling(&self) -> Option<Profile> {
+        crate::profiler::stop(self.inner.vmctx, &self.module.info)
+    }
+}
+
+/// The exported function `name` of an instance, as a `Func<Args, Rets>`.
+pub(crate) fn typed_func<'a, Args, Rets>(
+    module: &'a ModuleInner,
+    inner: &'a InstanceInner,
+    name: &str,
+) -> ResolveResult<Func<'a, Args, Rets, Wasm>>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    let export_index =
+        module
+            .info
+            .exports
+            .get(name)
+            .ok_or_else(|| ResolveError::ExportNotFound {
+                name: name.to_string(),
+            })?;
+
+    if let ExportIndex::Func(func_index) = export_index {
+        let sig_index = *module
+            .info
+            .func_assoc
+            .get(*func_index)
+            .expect("broken invariant, incorrect func index");
+        let signature = SigRegistry.lookup_signature_ref(&module.info.signatures[sig_index]);
+
+        if signature.params() != Args::types() || signature.returns() != Rets::types() {
+            Err(ResolveError::ExportSignature {
+                name: name.to_string(),
+                expected: (*signature).clone(),
+                found: FuncSig::new(Args::types(), Rets::types()),
+            })?;
+        }
+
+        let ctx = match func_index.local_or_import(&module.info) {
+            LocalOrImport::Local(_) => inner.vmctx,
+            LocalOrImport::Import(imported_func_index) => unsafe {
+                inner.import_backing.vm_functions[imported_func_index]
+                    .func_ctx
+                    .as_ref()
+            }
+            .vmctx
+            .as_ptr(),
+        };
+
+        let func_wasm_inner = module
+            .runnable_module
+            .get_trampoline(&module.info, sig_index)
+            .unwrap();
+
+        let (func_ptr, func_env) = match func_index.local_or_import(&module.info) {
+            LocalOrImport::Local(local_func_index) => (
+                module
+                    .runnable_module
+                    .get_func(&module.info, local_func_index)
+                    .unwrap(),
+                None,
+            ),
+            LocalOrImport::Import(import_func_index) => {
+                let imported_func = &inner.import_backing.vm_functions[import_func_index];
+
+                (
+                    NonNull::new(imported_func.func as *mut _).unwrap(),
+                    unsafe { imported_func.func_ctx.as_ref() }.func_env,
+                )
+            }
+        };
+
+        let typed_func: Func<Args, Rets, Wasm> =
+            unsafe { Func::from_raw_parts(func_wasm_inner, func_ptr, func_env, ctx) };
+
+        Ok(typed_func)
+    } else {
+        Err(ResolveError::ExportWrongType {
+            name: name.to_string(),
+        })
+    }
 }
 
 impl InstanceInner {
@@ -569,8 +885,31 @@ impl LikeNamespace for Arc<Mutex<Instance>> {
     }
 }
 
+/// Resolves the function pointer and context that `func_index` is called with.
+fn resolve_func(
+    info: &ModuleInfo,
+    runnable: &dyn RunnableModule,
+    import_backing: &ImportBacking,
+    local_ctx: *mut vm::Ctx,
+    func_index: FuncIndex,
+) -> (NonNull<vm::Func>, *mut vm::Ctx) {
+    match func_index.local_or_import(info) {
+        LocalOrImport::Local(local_func_index) => (
+            runnable.get_func(info, local_func_index).unwrap(),
+            local_ctx,
+        ),
+        LocalOrImport::Import(import_func_index) => {
+            let imported_func = &import_backing.vm_functions[import_func_index];
+            (
+                NonNull::new(imported_func.func as *mut _).unwrap(),
+                unsafe { imported_func.func_ctx.as_ref() }.vmctx.as_ptr(),
+            )
+        }
+    }
+}
+
 #[must_use]
-fn call_func_with_index(
+pub(crate) fn call_func_with_index(
     info: &ModuleInfo,
     runnable: &dyn RunnableModule,
     import_backing: &ImportBacking,
@@ -579,6 +918,11 @@ fn call_func_with_index(
     args: &[Value],
     rets: &mut Vec<Value>,
 ) -> CallResult<()> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("call", func_index = func_index.index());
+    #[cfg(feature = "tracing")]
+    let _enter = span.enter();
+
     let sig_index = *info
         .func_assoc
         .get(func_index)
@@ -586,25 +930,7 @@ fn call_func_with_index(
 
     let signature = &info.signatures[sig_index];
 
-    let func_ptr = match func_index.local_or_import(info) {
-        LocalOrImport::Local(local_func_index) => {
-            runnable.get_func(info, local_func_index).unwrap()
-        }
-        LocalOrImport::Import(import_func_index) => {
-            NonNull::new(import_backing.vm_functions[import_func_index].func as *mut _).unwrap()
-        }
-    };
-
-    let ctx_ptr = match func_index.local_or_import(info) {
-        LocalOrImport::Local(_) => local_ctx,
-        LocalOrImport::Import(imported_func_index) => unsafe {
-            import_backing.vm_functions[imported_func_index]
-                .func_ctx
-                .as_ref()
-        }
-        .vmctx
-        .as_ptr(),
-    };
+    let (func_ptr, ctx_ptr) = resolve_func(info, runnable, import_backing, local_ctx, func_index);
 
     let wasm = runnable
         .get_trampoline(info, sig_index)
@@ -620,6 +946,25 @@ pub(crate) fn call_func_with_index_inner(
     wasm: Wasm,
     args: &[Value],
     rets: &mut Vec<Value>,
+) -> CallResult<()> {
+    if !signature.check_param_value_types(args) {
+        Err(ResolveError::Signature {
+            expected: signature.clone(),
+            found: args.iter().map(|val| val.ty()).collect(),
+        })?
+    }
+
+    call_func_unchecked(ctx_ptr, func_ptr, signature, wasm, args, rets)
+}
+
+/// Calls `func_ptr` without checking that `args` match `signature`.
+fn call_func_unchecked(
+    ctx_ptr: *mut vm::Ctx,
+    func_ptr: NonNull<vm::Func>,
+    signature: &FuncSig,
+    wasm: Wasm,
+    args: &[Value],
+    rets: &mut Vec<Value>,
 ) -> CallResult<()> {
     rets.clear();
 
@@ -632,13 +977,6 @@ pub(crate) fn call_func_with_index_inner(
             .count();
     rets.reserve(num_results);
 
-    if !signature.check_param_value_types(args) {
-        Err(ResolveError::Signature {
-            expected: signature.clone(),
-            found: args.iter().map(|val| val.ty()).collect(),
-        })?
-    }
-
     let mut raw_args: SmallVec<[u64; 8]> = SmallVec::new();
     for v in args {
         match v {
@@ -676,23 +1014,30 @@ pub(crate) fn call_func_with_index_inner(
         let mut trap_info = WasmTrapInfo::Unknown;
         let mut user_error = None;
 
-        let success = invoke(
-            trampoline,
-            ctx_ptr,
-            func_ptr,
-            raw_args.as_ptr(),
-            result_space,
-            &mut trap_info,
-            &mut user_error,
-            invoke_env,
-        );
+        let success = fiber::on_instance_stack(ctx_ptr, || {
+            invoke(
+                trampoline,
+                ctx_ptr,
+                func_ptr,
+                raw_args.as_ptr(),
+                result_space,
+                &mut trap_info,
+                &mut user_error,
+                invoke_env,
+            )
+        });
 
         if success {
             Ok(())
         } else {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(trap = %trap_info, user_error = user_error.is_some(), "trapped");
             if let Some(data) = user_error {
                 Err(RuntimeError::Error { data })
             } else {
+                if let Some(metrics) = metrics::of_ctx(ctx_ptr) {
+                    metrics.trapped(&trap_info);
+                }
                 Err(RuntimeError::Trap {
                     msg: trap_info.to_string().into(),
                 })
@@ -801,6 +1146,40 @@ impl<'a> DynFunc<'a> {
         Ok(results)
     }
 
+    /// Resolves the function pointer, context and trampoline this function
+    /// is called with, so that calling it repeatedly through the returned
+    /// [`ResolvedFunc`] doesn't look them up again.
+    ///
+    /// [`ResolvedFunc`]: struct.ResolvedFunc.html
+    pub fn resolve(&self) -> ResolvedFunc<'a> {
+        let info = &self.module.info;
+        let runnable = &**self.module.runnable_module;
+        let sig_index = *info
+            .func_assoc
+            .get(self.func_index)
+            .expect("broken invariant, incorrect func index");
+
+        let (func_ptr, ctx_ptr) = resolve_func(
+            info,
+            runnable,
+            &self.instance_inner.import_backing,
+            self.instance_inner.vmctx,
+            self.func_index,
+        );
+
+        let wasm = runnable
+            .get_trampoline(info, sig_index)
+            .expect("wasm trampoline");
+
+        ResolvedFunc {
+            signature: Arc::clone(&self.signature),
+            func_ptr,
+            ctx_ptr,
+            wasm,
+            _instance: PhantomData,
+        }
+    }
+
     /// Gets the signature of this `Dynfunc`.
     pub fn signature(&self) -> &FuncSig {
         &*self.signature
@@ -822,6 +1201,61 @@ impl<'a> DynFunc<'a> {
     }
 }
 
+/// An exported WebAssembly function whose function pointer, context and
+/// trampoline are already resolved, see [`DynFunc::resolve`].
+///
+/// [`DynFunc::resolve`]: struct.DynFunc.html#method.resolve
+pub struct ResolvedFunc<'a> {
+    signature: Arc<FuncSig>,
+    func_ptr: NonNull<vm::Func>,
+    ctx_ptr: *mut vm::Ctx,
+    wasm: Wasm,
+    _instance: PhantomData<&'a InstanceInner>,
+}
+
+impl<'a> ResolvedFunc<'a> {
+    /// Call this function, writing the returned values to `results`, which
+    /// is cleared first so that its allocation can be reused across calls.
+    pub fn call(&self, params: &[Value], results: &mut Vec<Value>) -> CallResult<()> {
+        call_func_with_index_inner(
+            self.ctx_ptr,
+            self.func_ptr,
+            &self.signature,
+            self.wasm,
+            params,
+            results,
+        )
+    }
+
+    /// Call this function like [`ResolvedFunc::call`], without checking
+    /// that `params` match its signature.
+    ///
+    /// # Safety
+    /// The caller must make sure that `params` have the types of the
+    /// parameters of this function.
+    ///
+    /// [`ResolvedFunc::call`]: #method.call
+    pub unsafe fn call_unchecked(
+        &self,
+        params: &[Value],
+        results: &mut Vec<Value>,
+    ) -> CallResult<()> {
+        call_func_unchecked(
+            self.ctx_ptr,
+            self.func_ptr,
+            &self.signature,
+            self.wasm,
+            params,
+            results,
+        )
+    }
+
+    /// Gets the signature of this function.
+    pub fn signature(&self) -> &FuncSig {
+        &*self.signature
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -35,6 +35,7 @@ extern crate lazy_static;
 
 #[macro_use]
 mod macros;
+pub mod asyncify;
 #[doc(hidden)]
 pub mod backend;
 mod backing;
@@ -43,14 +44,20 @@ pub mod cache;
 pub mod codegen;
 pub mod error;
 pub mod export;
+pub mod fiber;
 pub mod global;
 pub mod import;
 pub mod instance;
+pub mod limits;
 pub mod loader;
 pub mod memory;
+pub mod metrics;
 pub mod module;
 pub mod parse;
+pub mod plugin;
+pub mod pool;
 mod sig_registry;
+pub mod signing;
 pub mod structures;
 mod sys;
 pub mod table;
@@ -66,6 +73,8 @@ pub mod vmcalls;
 pub use trampoline_x64 as trampoline;
 #[cfg(unix)]
 pub mod fault;
+#[cfg(unix)]
+pub mod profiler;
 pub mod state;
 #[cfg(feature = "managed")]
 pub mod tiering;
@@ -76,7 +85,7 @@ pub use self::error::Result;
 #[doc(inline)]
 pub use self::import::IsExport;
 #[doc(inline)]
-pub use self::instance::{DynFunc, Instance};
+pub use self::instance::{DynFunc, Instance, InstanceConfig, InstanceStats, ResolvedFunc};
 #[doc(inline)]
 pub use self::module::Module;
 #[doc(inline)]
@@ -85,6 +94,10 @@ use std::sync::Arc;
 
 pub use wasmparser;
 
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing;
+
 use self::cache::{Artifact, Error as CacheError};
 
 pub mod prelude {
@@ -129,7 +142,11 @@ pub fn compile_with_config(
     let token = backend::Token::generate();
     compiler
         .compile(wasm, compiler_config, token)
-        .map(|inner| module::Module::new(Arc::new(inner)))
+        .map(|mut inner| {
+            let inner_info: &mut crate::module::ModuleInfo = &mut inner.info;
+            inner_info.import_custom_sections(wasm).unwrap();
+            module::Module::new(Arc::new(inner))
+        })
 }
 
 /// Perform validation as defined by the
@@ -149,28 +166,16 @@ pub fn validate_and_report_errors_with_features(
     wasm: &[u8],
     features: backend::Features,
 ) -> ::std::result::Result<(), String> {
-    use wasmparser::WasmDecoder;
-    let config = wasmparser::ValidatingParserConfig {
-        operator_config: wasmparser::OperatorValidatorConfig {
-            enable_simd: features.simd,
-            enable_bulk_memory: false,
-            enable_multi_value: false,
-            enable_reference_types: false,
-            enable_threads: features.threads,
-
-            #[cfg(feature = "deterministic-execution")]
-            deterministic_only: true,
-        },
-    };
-    let mut parser = wasmparser::ValidatingParser::new(wasm, Some(config));
-    loop {
-        let state = parser.read();
-        match *state {
-            wasmparser::ParserState::EndWasm => break Ok(()),
-            wasmparser::ParserState::Error(e) => break Err(format!("{}", e)),
-            _ => {}
-        }
-    }
+    validate_with_features(wasm, features).map_err(|failure| failure.to_string())
+}
+
+/// The same as `validate_and_report_errors_with_features`, but the error
+/// also gives the offset where validation failed.
+pub fn validate_with_features(
+    wasm: &[u8],
+    features: backend::Features,
+) -> ::std::result::Result<(), error::ValidationFailure> {
+    codegen::validate_with_features(wasm, &features)
 }
 
 /// Creates a new module from the given cache `Artifact` for the specified compiler backend
@@ -178,6 +183,13 @@ pub unsafe fn load_cache_with(
     cache: Artifact,
     compiler: &dyn backend::Compiler,
 ) -> std::result::Result<module::Module, CacheError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("load_cache", backend = cache.info().backend.to_string());
+    #[cfg(feature = "tracing")]
+    let _enter = span.enter();
+
+    cache.check_runs_on_host()?;
+
     let token = backend::Token::generate();
     compiler
         .from_cache(cache, token)
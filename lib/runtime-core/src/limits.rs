@@ -0,0 +1,216 @@
+//! Limits on the resources instances use.
+//!
+//! A [`ResourceLimiter`] is installed either for the whole process with
+//! [`set_global`], or for the instances created from one [`ImportObject`]
+//! with [`ImportObject::set_limiter`], which takes precedence over the
+//! global limiter.  It's asked before the instances are created and before
+//! their memories grow, and can refuse.  The limiter is resolved when an
+//! instance is created.
+//!
+//! An instance that would exceed a limit fails to instantiate with
+//! [`CreationError::LimitExceeded`], and a `memory.grow` that would exceed
+//! one returns -1, as when a memory reaches its maximum.
+//!
+//! [`StaticLimiter`] enforces fixed [`ResourceLimits`].
+//!
+//! Tables only have their initial size checked: `table.grow` isn't
+//! supported yet.  Memories grown from the host with `Memory::grow` aren't
+//! checked either.
+//!
+//! [`ImportObject`]: crate::import::ImportObject
+//! [`ImportObject::set_limiter`]: crate::import::ImportObject::set_limiter
+//! [`CreationError::LimitExceeded`]: crate::error::CreationError::LimitExceeded
+
+use crate::{
+    error::CreationError,
+    module::ModuleInfo,
+    units::{Bytes, Pages},
+    vm,
+};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+lazy_static! {
+    static ref GLOBAL_LIMITER: RwLock<Option<Arc<dyn ResourceLimiter>>> = RwLock::new(None);
+}
+
+/// Decides whether instances may be created and their memories and tables
+/// may reach a size.
+///
+/// Every method has a default implementation allowing everything.  The
+/// methods are called on the thread creating the instance or growing the
+/// memory, from inside wasm calls for `memory_growing`.
+pub trait ResourceLimiter: Send + Sync {
+    /// Whether a memory may grow from `current` to `desired` pages.
+    /// Memories are created by growing them from 0 pages to their initial
+    /// size.
+    fn memory_growing(&self, _current: Pages, _desired: Pages) -> bool {
+        true
+    }
+
+    /// Whether a table may grow from `current` to `desired` elements.
+    /// Tables are created by growing them from 0 elements to their initial
+    /// size.
+    fn table_growing(&self, _current: u32, _desired: u32) -> bool {
+        true
+    }
+
+    /// Whether an instance may be created.  Each instance that was allowed
+    /// is matched by a call to `instance_dropped`.
+    fn instance_creating(&self) -> bool {
+        true
+    }
+
+    /// An instance allowed by `instance_creating` was dropped.
+    fn instance_dropped(&self) {}
+}
+
+impl fmt::Debug for dyn ResourceLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ResourceLimiter")
+    }
+}
+
+/// Install `limiter` as the limiter of instances whose `ImportObject`
+/// doesn't have one, or remove the global limiter with `None`.
+pub fn set_global(limiter: Option<Arc<dyn ResourceLimiter>>) {
+    *GLOBAL_LIMITER.write() = limiter;
+}
+
+/// The global limiter, if one is installed.
+pub fn global() -> Option<Arc<dyn ResourceLimiter>> {
+    GLOBAL_LIMITER.read().clone()
+}
+
+/// Fixed limits for a [`StaticLimiter`].  `None` doesn't limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The size each memory may reach.
+    pub max_memory: Option<Bytes>,
+    /// The number of elements each table may reach.
+    pub max_table_elements: Option<u32>,
+    /// The number of instances that may exist at once.
+    pub max_instances: Option<usize>,
+}
+
+/// A [`ResourceLimiter`] enforcing fixed [`ResourceLimits`].
+#[derive(Debug, Default)]
+pub struct StaticLimiter {
+    limits: ResourceLimits,
+    instances: AtomicUsize,
+}
+
+impl StaticLimiter {
+    /// A limiter enforcing `limits`.
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            instances: AtomicUsize::new(0),
+        }
+    }
+
+    /// The limits enforced.
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// The number of instances that exist.
+    pub fn instances(&self) -> usize {
+        self.instances.load(Ordering::SeqCst)
+    }
+}
+
+impl ResourceLimiter for StaticLimiter {
+    fn memory_growing(&self, _current: Pages, desired: Pages) -> bool {
+        self.limits
+            .max_memory
+            .map_or(true, |max| desired.bytes() <= max)
+    }
+
+    fn table_growing(&self, _current: u32, desired: u32) -> bool {
+        self.limits
+            .max_table_elements
+            .map_or(true, |max| desired <= max)
+    }
+
+    fn instance_creating(&self) -> bool {
+        let max = match self.limits.max_instances {
+            Some(max) => max,
+            None => {
+                self.instances.fetch_add(1, Ordering::SeqCst);
+                return true;
+            }
+        };
+        let mut instances = self.instances.load(Ordering::SeqCst);
+        loop {
+            if instances >= max {
+                return false;
+            }
+            match self.instances.compare_exchange(
+                instances,
+                instances + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return true,
+                Err(current) => instances = current,
+            }
+        }
+    }
+
+    fn instance_dropped(&self) {
+        self.instances.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Checks the initial sizes of the memories and tables `info` defines.
+pub(crate) fn check_module(
+    limiter: &dyn ResourceLimiter,
+    info: &ModuleInfo,
+) -> Result<(), CreationError> {
+    for (_, memory) in info.memories.iter() {
+        if !limiter.memory_growing(Pages(0), memory.minimum) {
+            return Err(CreationError::LimitExceeded(format!(
+                "a memory of {:?}",
+                memory.minimum
+            )));
+        }
+    }
+    for (_, table) in info.tables.iter() {
+        if !limiter.table_growing(0, table.minimum) {
+            return Err(CreationError::LimitExceeded(format!(
+                "a table of {} elements",
+                table.minimum
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the limiter of the instance that `ctx` belongs to lets a memory
+/// grow by `delta` from `current`.
+pub(crate) unsafe fn allows_memory_growth(
+    ctx: *const vm::Ctx,
+    current: Pages,
+    delta: Pages,
+) -> bool {
+    if ctx.is_null() || (*ctx).local_backing.is_null() {
+        return true;
+    }
+    let limiter = match &(*(*ctx).local_backing).limiter {
+        Some(limiter) => limiter,
+        None => return true,
+    };
+    match current.checked_add(delta) {
+        Ok(desired) => limiter.memory_growing(current, desired),
+        // The memory can't grow that much anyway.
+        Err(_) => true,
+    }
+}
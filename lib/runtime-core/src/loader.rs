@@ -1,11 +1,21 @@
 //! The loader module functions are used to load an instance.
 use crate::{backend::RunnableModule, module::ModuleInfo, types::Type, types::Value, vm::Ctx};
 #[cfg(unix)]
-use libc::{mmap, mprotect, munmap, MAP_ANON, MAP_PRIVATE, PROT_EXEC, PROT_READ, PROT_WRITE};
+use libc::{
+    c_int, mmap, munmap, MAP_ANON, MAP_FAILED, MAP_PRIVATE, PROT_EXEC, PROT_READ, PROT_WRITE,
+};
 use std::{
     fmt::Debug,
     ops::{Deref, DerefMut},
 };
+#[cfg(windows)]
+use winapi::{
+    shared::minwindef::DWORD,
+    um::{
+        memoryapi::{VirtualAlloc, VirtualFree, VirtualProtect},
+        winnt::{MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_EXECUTE_READ, PAGE_READWRITE},
+    },
+};
 
 /// The loader trait represents the functions used to load an instance.
 pub trait Loader {
@@ -123,32 +133,128 @@ impl Instance for LocalInstance {
 }
 
 /// A pointer to code in memory.
+///
+/// Code memory is never writable and executable at the same time. A single
+/// mapping is switched between read/write and read/exec by `make_writable` and
+/// `make_executable`; on macOS it is allocated with `MAP_JIT`, and on Apple
+/// Silicon the switch is done per thread with `pthread_jit_write_protect_np`.
+///
+/// A dual-mapped code memory (see `new_dual_mapped`) instead maps the same
+/// pages twice: writes go through a read/write view and execution happens
+/// from a read/exec view, so code can be patched while other threads run it.
 pub struct CodeMemory {
     ptr: *mut u8,
+    write_ptr: *mut u8,
     size: usize,
 }
 
 unsafe impl Send for CodeMemory {}
 unsafe impl Sync for CodeMemory {}
 
-#[cfg(not(unix))]
+fn round_up_to_page_size(size: usize) -> usize {
+    (size + (4096 - 1)) & !(4096 - 1)
+}
+
+impl CodeMemory {
+    /// Returns whether this code memory has separate writable and executable views.
+    pub fn is_dual_mapped(&self) -> bool {
+        self.ptr != self.write_ptr
+    }
+
+    /// Returns the address in the writable view that corresponds to `ptr`,
+    /// which must point into the executable view of this code memory.
+    pub fn writable_ptr(&self, ptr: *const u8) -> *mut u8 {
+        let offset = ptr as usize - self.ptr as usize;
+        assert!(offset < self.size, "pointer is not within this code memory");
+        unsafe { self.write_ptr.add(offset) }
+    }
+}
+
+#[cfg(windows)]
 impl CodeMemory {
     /// Creates a new code memory with the given size.
-    pub fn new(_size: usize) -> CodeMemory {
-        unimplemented!("CodeMemory::new");
+    pub fn new(size: usize) -> CodeMemory {
+        if size == 0 {
+            return CodeMemory {
+                ptr: std::ptr::null_mut(),
+                write_ptr: std::ptr::null_mut(),
+                size: 0,
+            };
+        }
+
+        let size = round_up_to_page_size(size);
+        let ptr = unsafe {
+            VirtualAlloc(
+                std::ptr::null_mut(),
+                size,
+                MEM_RESERVE | MEM_COMMIT,
+                PAGE_READWRITE,
+            )
+        };
+        if ptr.is_null() {
+            panic!("cannot allocate code memory");
+        }
+        CodeMemory {
+            ptr: ptr as _,
+            write_ptr: ptr as _,
+            size: size,
+        }
+    }
+
+    /// Creates a new code memory with separate writable and executable views.
+    ///
+    /// Dual mapping isn't implemented on Windows, so this is the same as `new`.
+    pub fn new_dual_mapped(size: usize) -> CodeMemory {
+        CodeMemory::new(size)
     }
 
     /// Makes this code memory executable.
     pub fn make_executable(&self) {
-        unimplemented!("CodeMemory::make_executable");
+        self.protect(PAGE_EXECUTE_READ, "executable");
     }
 
     /// Makes this code memory writable.
     pub fn make_writable(&self) {
-        unimplemented!("CodeMemory::make_writable");
+        self.protect(PAGE_READWRITE, "writable");
+    }
+
+    fn protect(&self, protection: DWORD, name: &str) {
+        if self.size == 0 {
+            return;
+        }
+        let mut old_protection: DWORD = 0;
+        if unsafe { VirtualProtect(self.ptr as _, self.size, protection, &mut old_protection) } == 0
+        {
+            panic!("cannot set code memory to {}", name);
+        }
     }
 }
 
+#[cfg(windows)]
+impl Drop for CodeMemory {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                VirtualFree(self.ptr as _, 0, MEM_RELEASE);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+use crate::sys::MAP_JIT;
+
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+extern "C" {
+    fn pthread_jit_write_protect_np(enabled: c_int);
+    fn sys_icache_invalidate(start: *mut libc::c_void, len: libc::size_t);
+}
+
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+extern "C" {
+    fn __clear_cache(start: *mut libc::c_char, end: *mut libc::c_char);
+}
+
 #[cfg(unix)]
 impl CodeMemory {
     /// Creates a new code memory with the given size.
@@ -156,44 +262,160 @@ impl CodeMemory {
         if size == 0 {
             return CodeMemory {
                 ptr: std::ptr::null_mut(),
+                write_ptr: std::ptr::null_mut(),
                 size: 0,
             };
         }
 
-        fn round_up_to_page_size(size: usize) -> usize {
-            (size + (4096 - 1)) & !(4096 - 1)
-        }
+        #[cfg(not(target_os = "macos"))]
+        let (protection, flags) = (PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANON);
+        #[cfg(all(target_os = "macos", not(target_arch = "aarch64")))]
+        let (protection, flags) = (PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANON | MAP_JIT);
+        // On Apple Silicon `MAP_JIT` pages must be mapped RWX; whether the current
+        // thread sees them as writable or executable is switched in `protect`.
+        #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+        let (protection, flags) = (
+            PROT_READ | PROT_WRITE | PROT_EXEC,
+            MAP_PRIVATE | MAP_ANON | MAP_JIT,
+        );
+
         let size = round_up_to_page_size(size);
-        let ptr = unsafe {
-            mmap(
-                std::ptr::null_mut(),
-                size,
-                PROT_READ | PROT_WRITE,
-                MAP_PRIVATE | MAP_ANON,
-                -1,
-                0,
-            )
-        };
-        if ptr as isize == -1 {
+        let ptr = unsafe { mmap(std::ptr::null_mut(), size, protection, flags, -1, 0) };
+        if ptr == MAP_FAILED {
             panic!("cannot allocate code memory");
         }
-        CodeMemory {
+        let code = CodeMemory {
             ptr: ptr as _,
+            write_ptr: ptr as _,
             size: size,
+        };
+        #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+        code.make_writable();
+        code
+    }
+
+    /// Creates a new code memory with separate writable and executable views.
+    ///
+    /// Falls back to a single mapping (as with `new`), logging a warning,
+    /// where dual mapping isn't available, e.g. when `memfd_create` is
+    /// denied by a seccomp filter.
+    #[cfg(target_os = "linux")]
+    pub fn new_dual_mapped(size: usize) -> CodeMemory {
+        if size == 0 {
+            return CodeMemory::new(0);
         }
+        let size = round_up_to_page_size(size);
+        match unsafe { Self::dual_map(size) } {
+            Ok(code) => code,
+            Err(error) => {
+                tracing::warn!(
+                    %error,
+                    "cannot dual-map code memory, switching its protection instead"
+                );
+                CodeMemory::new(size)
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn dual_map(size: usize) -> Result<CodeMemory, String> {
+        use libc::{close, ftruncate, syscall, SYS_memfd_create, MAP_SHARED, MFD_CLOEXEC};
+
+        let fd = syscall(SYS_memfd_create, b"wasmer-code\0".as_ptr(), MFD_CLOEXEC) as c_int;
+        if fd < 0 {
+            return Err(format!("memfd_create failed: {}", errno::errno()));
+        }
+        if ftruncate(fd, size as _) != 0 {
+            let error = format!("ftruncate failed: {}", errno::errno());
+            close(fd);
+            return Err(error);
+        }
+        let write_ptr = mmap(
+            std::ptr::null_mut(),
+            size,
+            PROT_READ | PROT_WRITE,
+            MAP_SHARED,
+            fd,
+            0,
+        );
+        if write_ptr == MAP_FAILED {
+            let error = format!("mapping the writable view failed: {}", errno::errno());
+            close(fd);
+            return Err(error);
+        }
+        let ptr = mmap(
+            std::ptr::null_mut(),
+            size,
+            PROT_READ | PROT_EXEC,
+            MAP_SHARED,
+            fd,
+            0,
+        );
+        let error = errno::errno();
+        close(fd);
+        if ptr == MAP_FAILED {
+            munmap(write_ptr, size);
+            return Err(format!("mapping the executable view failed: {}", error));
+        }
+        Ok(CodeMemory {
+            ptr: ptr as _,
+            write_ptr: write_ptr as _,
+            size: size,
+        })
+    }
+
+    /// Creates a new code memory with separate writable and executable views.
+    ///
+    /// Dual mapping is only implemented on Linux, so this is the same as `new`.
+    #[cfg(not(target_os = "linux"))]
+    pub fn new_dual_mapped(size: usize) -> CodeMemory {
+        CodeMemory::new(size)
     }
 
     /// Makes this code memory executable.
+    ///
+    /// For a dual-mapped code memory this only flushes the instruction cache.
     pub fn make_executable(&self) {
-        if unsafe { mprotect(self.ptr as _, self.size, PROT_READ | PROT_EXEC) } != 0 {
-            panic!("cannot set code memory to executable");
+        if self.size == 0 {
+            return;
         }
+        if !self.is_dual_mapped() {
+            self.protect(PROT_READ | PROT_EXEC, "executable");
+        }
+        self.flush_icache();
     }
 
     /// Makes this code memory writable.
+    ///
+    /// This is a no-op for a dual-mapped code memory, whose writable view
+    /// is always writable.
     pub fn make_writable(&self) {
-        if unsafe { mprotect(self.ptr as _, self.size, PROT_READ | PROT_WRITE) } != 0 {
-            panic!("cannot set code memory to writable");
+        if self.size == 0 || self.is_dual_mapped() {
+            return;
+        }
+        self.protect(PROT_READ | PROT_WRITE, "writable");
+    }
+
+    #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
+    fn protect(&self, protection: c_int, name: &str) {
+        if unsafe { libc::mprotect(self.ptr as _, self.size, protection) } != 0 {
+            panic!("cannot set code memory to {}", name);
+        }
+    }
+
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    fn protect(&self, protection: c_int, _name: &str) {
+        unsafe { pthread_jit_write_protect_np((protection & PROT_WRITE == 0) as c_int) }
+    }
+
+    fn flush_icache(&self) {
+        #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+        unsafe {
+            sys_icache_invalidate(self.ptr as _, self.size)
+        }
+        #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+        unsafe {
+            __clear_cache(self.ptr as _, self.ptr.add(self.size) as _)
         }
     }
 }
@@ -201,8 +423,14 @@ impl CodeMemory {
 #[cfg(unix)]
 impl Drop for CodeMemory {
     fn drop(&mut self) {
+        if self.size == 0 {
+            return;
+        }
         unsafe {
             munmap(self.ptr as _, self.size);
+            if self.is_dual_mapped() {
+                munmap(self.write_ptr as _, self.size);
+            }
         }
     }
 }
@@ -216,6 +444,32 @@ impl Deref for CodeMemory {
 
 impl DerefMut for CodeMemory {
     fn deref_mut(&mut self) -> &mut [u8] {
-        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.size) }
+        unsafe { std::slice::from_raw_parts_mut(self.write_ptr, self.size) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CodeMemory;
+
+    #[test]
+    fn dual_mapped_code_memory_shares_its_views() {
+        let mut code = CodeMemory::new_dual_mapped(100);
+        code.make_writable();
+        code[0..4].copy_from_slice(&[1, 2, 3, 4]);
+        code.make_executable();
+        assert_eq!(&code[0..4], &[1, 2, 3, 4]);
+
+        let ptr = code.as_ptr();
+        unsafe { *code.writable_ptr(ptr.add(2)) = 42 };
+        assert_eq!(code[2], 42);
+    }
+
+    #[test]
+    fn empty_code_memory() {
+        let code = CodeMemory::new_dual_mapped(0);
+        code.make_writable();
+        code.make_executable();
+        assert!(code.is_empty());
     }
 }
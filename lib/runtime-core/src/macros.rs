@@ -13,10 +13,20 @@ macro_rules! debug {
     }, line!(), $($arg)*));
 }
 
+/// Emits a `tracing` event at the debug level, when the tracing feature is enabled
+/// and the debug feature isn't.
+#[macro_export]
+#[cfg(all(feature = "tracing", not(feature = "debug")))]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::tracing::debug!($($arg)*)
+    };
+}
+
 /// Prints a log message with args, similar to println, when the debug feature is enabled.
 /// If the debug feature is disabled, arguments are not evaluated or printed.
 #[macro_export]
-#[cfg(not(feature = "debug"))]
+#[cfg(not(any(feature = "debug", feature = "tracing")))]
 macro_rules! debug {
     ($fmt:expr) => {};
     ($fmt:expr, $($arg:tt)*) => {};
@@ -35,10 +45,20 @@ macro_rules! trace {
     }
 }
 
+/// Emits a `tracing` event at the trace level, when the tracing feature is enabled
+/// and the trace feature isn't.
+#[macro_export]
+#[cfg(all(feature = "tracing", not(feature = "trace")))]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::tracing::trace!($($arg)*)
+    };
+}
+
 /// Prints a log message with args, similar to println, when the trace feature is enabled.
 /// If the trace feature is disabled, arguments are not evaluated or printed.
 #[macro_export]
-#[cfg(not(feature = "trace"))]
+#[cfg(not(any(feature = "trace", feature = "tracing")))]
 macro_rules! trace {
     ($fmt:expr) => {};
     ($fmt:expr, $($arg:tt)*) => {};
@@ -0,0 +1,114 @@
+//! Waiting and notifying on memory addresses, like the `memory.atomic.wait`
+//! and `memory.atomic.notify` instructions of the threads proposal.
+//!
+//! Waiters are parked in a table shared by all memories and keyed by the
+//! address they wait on.  Only host threads using [`Memory::atomic_wait32`],
+//! [`Memory::atomic_wait64`] and [`Memory::atomic_notify`] go through it.
+//!
+//! [`Memory::atomic_wait32`]: super::Memory::atomic_wait32
+//! [`Memory::atomic_wait64`]: super::Memory::atomic_wait64
+//! [`Memory::atomic_notify`]: super::Memory::atomic_notify
+
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The outcome of a wait, numbered like the result of `memory.atomic.wait`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitResult {
+    /// The waiter was woken by a notify.
+    Ok = 0,
+    /// The value in memory wasn't the expected one.
+    NotEqual = 1,
+    /// The timeout expired.
+    TimedOut = 2,
+}
+
+struct Waiter {
+    woken: Mutex<bool>,
+    condvar: Condvar,
+}
+
+lazy_static! {
+    static ref WAITERS: Mutex<HashMap<usize, VecDeque<Arc<Waiter>>>> = Mutex::new(HashMap::new());
+}
+
+/// Wait on `addr` until notified, if `is_expected` returns true.
+///
+/// `is_expected` is called while no notify for any address can run, so a
+/// notify following a store that makes it return false can't be missed.
+pub(super) fn wait(
+    addr: usize,
+    is_expected: impl FnOnce() -> bool,
+    timeout: Option<Duration>,
+) -> WaitResult {
+    let waiter = Arc::new(Waiter {
+        woken: Mutex::new(false),
+        condvar: Condvar::new(),
+    });
+    {
+        let mut waiters = WAITERS.lock();
+        if !is_expected() {
+            return WaitResult::NotEqual;
+        }
+        waiters
+            .entry(addr)
+            .or_insert_with(VecDeque::new)
+            .push_back(Arc::clone(&waiter));
+    }
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut woken = waiter.woken.lock();
+    while !*woken {
+        match deadline {
+            Some(deadline) => {
+                if waiter.condvar.wait_until(&mut woken, deadline).timed_out() {
+                    break;
+                }
+            }
+            None => waiter.condvar.wait(&mut woken),
+        }
+    }
+    if *woken {
+        return WaitResult::Ok;
+    }
+    drop(woken);
+
+    // `WAITERS` is always locked before `woken`
+    let mut waiters = WAITERS.lock();
+    if *waiter.woken.lock() {
+        return WaitResult::Ok;
+    }
+    if let Some(queue) = waiters.get_mut(&addr) {
+        queue.retain(|other| !Arc::ptr_eq(other, &waiter));
+        if queue.is_empty() {
+            waiters.remove(&addr);
+        }
+    }
+    WaitResult::TimedOut
+}
+
+/// Wake up to `count` of the waiters on `addr`, in the order they started
+/// waiting, and return how many were woken.
+pub(super) fn notify(addr: usize, count: u32) -> u32 {
+    let mut waiters = WAITERS.lock();
+    let mut woken = 0;
+    if let Some(queue) = waiters.get_mut(&addr) {
+        while woken < count {
+            let waiter = match queue.pop_front() {
+                Some(waiter) => waiter,
+                None => break,
+            };
+            *waiter.woken.lock() = true;
+            waiter.condvar.notify_one();
+            woken += 1;
+        }
+        if queue.is_empty() {
+            waiters.remove(&addr);
+        }
+    }
+    woken
+}
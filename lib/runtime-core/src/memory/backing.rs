@@ -0,0 +1,150 @@
+//! Storage provided by the embedder for a memory, see
+//! [`Memory::with_backing`](super::Memory::with_backing).
+
+use crate::{error::GrowError, memory::dynamic::DYNAMIC_GUARD_SIZE};
+
+/// The number of bytes past the end of a [`MemoryBacking`] that guest
+/// accesses straddling the end of the memory may touch.
+pub const MEMORY_BACKING_GUARD_SIZE: usize = DYNAMIC_GUARD_SIZE;
+
+/// Storage for a memory created with
+/// [`Memory::with_backing`](super::Memory::with_backing), e.g. a preallocated
+/// buffer, a file mapping or a shared-memory segment.
+///
+/// Memories over a backing are dynamic memories: the guest's accesses are
+/// checked against the size of the memory, but an access starting right
+/// before its end may run up to [`MEMORY_BACKING_GUARD_SIZE`] bytes past it.
+///
+/// # Safety
+///
+/// `base` must point to `len() + MEMORY_BACKING_GUARD_SIZE` bytes that nothing
+/// else uses and that are readable and writable, or fault when accessed, until
+/// the backing is grown or dropped.
+pub unsafe trait MemoryBacking: Send {
+    /// The start of the storage.
+    fn base(&self) -> *mut u8;
+
+    /// The number of bytes of the storage the memory may use.
+    fn len(&self) -> usize;
+
+    /// Make at least `len` bytes available to the memory; `base` may change.
+    ///
+    /// The default implementation doesn't let the memory grow.
+    fn grow(&mut self, len: usize) -> Result<(), GrowError> {
+        let _ = len;
+        Err(GrowError::MemoryGrowError)
+    }
+}
+
+/// A buffer holding the memory followed by [`MEMORY_BACKING_GUARD_SIZE`]
+/// bytes of guard.  Growing resizes the buffer, which may move it.
+unsafe impl MemoryBacking for Vec<u8> {
+    fn base(&self) -> *mut u8 {
+        self.as_ptr() as *mut u8
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self).saturating_sub(MEMORY_BACKING_GUARD_SIZE)
+    }
+
+    fn grow(&mut self, len: usize) -> Result<(), GrowError> {
+        self.resize(len + MEMORY_BACKING_GUARD_SIZE, 0);
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub use self::unix::MappedFile;
+
+#[cfg(unix)]
+mod unix {
+    use super::{MemoryBacking, MEMORY_BACKING_GUARD_SIZE};
+    use crate::error::{GrowError, MemoryCreationError};
+    use nix::libc;
+    use std::{fs::File, io, os::unix::io::AsRawFd, ptr};
+
+    /// A file mapped into memory, shared with every other process mapping it.
+    ///
+    /// The guest's writes go to the file, so its memory persists on disk or,
+    /// for a shared-memory segment, is seen by other processes.  Growing the
+    /// memory extends the file and maps it again.
+    pub struct MappedFile {
+        file: File,
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    // Manually implemented because MappedFile contains a raw pointer to its mapping
+    unsafe impl Send for MappedFile {}
+
+    impl MappedFile {
+        /// Map `file`, which must be opened for reading and writing.  The
+        /// memory uses the whole file.
+        pub fn new(file: File) -> Result<Self, MemoryCreationError> {
+            let len = file
+                .metadata()
+                .map_err(MemoryCreationError::CouldNotCreateMemoryFromFile)?
+                .len() as usize;
+            let ptr = map(&file, len)?;
+
+            Ok(Self { file, ptr, len })
+        }
+
+        /// The mapped file.
+        pub fn file(&self) -> &File {
+            &self.file
+        }
+    }
+
+    /// Map `len` bytes of `file` and the guard past them, which faults since
+    /// it lies beyond the end of the file.
+    fn map(file: &File, len: usize) -> Result<*mut u8, MemoryCreationError> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len + MEMORY_BACKING_GUARD_SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            Err(MemoryCreationError::VirtualMemoryAllocationFailed(
+                len,
+                io::Error::last_os_error().to_string(),
+            ))
+        } else {
+            Ok(ptr as *mut u8)
+        }
+    }
+
+    unsafe impl MemoryBacking for MappedFile {
+        fn base(&self) -> *mut u8 {
+            self.ptr
+        }
+
+        fn len(&self) -> usize {
+            self.len
+        }
+
+        fn grow(&mut self, len: usize) -> Result<(), GrowError> {
+            self.file
+                .set_len(len as u64)
+                .map_err(|e| GrowError::CouldNotCreateMemory(e.into()))?;
+            let ptr = map(&self.file, len).map_err(GrowError::CouldNotCreateMemory)?;
+
+            unsafe { libc::munmap(self.ptr as _, self.len + MEMORY_BACKING_GUARD_SIZE) };
+            self.ptr = ptr;
+            self.len = len;
+            Ok(())
+        }
+    }
+
+    impl Drop for MappedFile {
+        fn drop(&mut self) {
+            unsafe { libc::munmap(self.ptr as _, self.len + MEMORY_BACKING_GUARD_SIZE) };
+        }
+    }
+}
@@ -1,11 +1,13 @@
 use crate::error::GrowError;
 use crate::{
     error::CreationError,
+    memory::{GrowCallback, MemoryBacking},
     sys,
     types::MemoryDescriptor,
     units::{Bytes, Pages},
     vm,
 };
+use std::{ptr, slice};
 
 pub const DYNAMIC_GUARD_SIZE: usize = 4096;
 
@@ -22,10 +24,13 @@ pub const DYNAMIC_GUARD_SIZE: usize = 4096;
 /// While, a dynamic memory could use a vector of some sort as its
 /// backing memory, we use mmap (or the platform-equivalent) to allow
 /// us to add a guard-page at the end to help elide some bounds-checks.
+/// Memories created over a `MemoryBacking` use the embedder's storage
+/// instead.
 pub struct DynamicMemory {
-    memory: sys::Memory,
+    memory: Storage,
     current: Pages,
     max: Option<Pages>,
+    grow_callbacks: Vec<GrowCallback>,
 }
 
 impl DynamicMemory {
@@ -49,9 +54,10 @@ impl DynamicMemory {
         };
 
         let mut storage = Box::new(DynamicMemory {
-            memory,
+            memory: Storage::Owned(memory),
             current: desc.minimum,
             max: desc.maximum,
+            grow_callbacks: Vec::new(),
         });
         let storage_ptr: *mut DynamicMemory = &mut *storage;
 
@@ -62,6 +68,34 @@ impl DynamicMemory {
         Ok(storage)
     }
 
+    pub(super) fn with_backing(
+        desc: MemoryDescriptor,
+        backing: Box<dyn MemoryBacking>,
+        local: &mut vm::LocalMemory,
+    ) -> Result<Box<Self>, CreationError> {
+        let current: Pages = Bytes(backing.len()).into();
+        if current < desc.minimum || desc.maximum.map_or(false, |max| current > max) {
+            return Err(CreationError::InvalidDescriptor(format!(
+                "the backing holds {} pages, outside of the memory's limits",
+                current.0
+            )));
+        }
+
+        let mut storage = Box::new(DynamicMemory {
+            memory: Storage::Backing(backing),
+            current,
+            max: desc.maximum,
+            grow_callbacks: Vec::new(),
+        });
+        let storage_ptr: *mut DynamicMemory = &mut *storage;
+
+        local.base = storage.memory.as_ptr();
+        local.bound = current.bytes().0;
+        local.memory = storage_ptr as *mut ();
+
+        Ok(storage)
+    }
+
     /// The size of this memory in `Pages`.
     pub fn size(&self) -> Pages {
         self.current
@@ -84,35 +118,111 @@ impl DynamicMemory {
             }
         }
 
-        let mut new_memory = sys::Memory::with_size(new_pages.bytes().0 + DYNAMIC_GUARD_SIZE)
-            .map_err(|e| e.into())?;
+        match &mut self.memory {
+            Storage::Owned(memory) => {
+                let mut new_memory =
+                    sys::Memory::with_size(new_pages.bytes().0 + DYNAMIC_GUARD_SIZE)
+                        .map_err(|e| e.into())?;
 
-        unsafe {
-            new_memory
-                .protect(0..new_pages.bytes().0, sys::Protect::ReadWrite)
-                .map_err(|e| e.into())?;
+                unsafe {
+                    new_memory
+                        .protect(0..new_pages.bytes().0, sys::Protect::ReadWrite)
+                        .map_err(|e| e.into())?;
 
-            new_memory.as_slice_mut()[..self.current.bytes().0]
-                .copy_from_slice(&self.memory.as_slice()[..self.current.bytes().0]);
-        }
+                    new_memory.as_slice_mut()[..self.current.bytes().0]
+                        .copy_from_slice(&memory.as_slice()[..self.current.bytes().0]);
+                }
 
-        self.memory = new_memory; //The old memory gets dropped.
+                *memory = new_memory; //The old memory gets dropped.
+            }
+            Storage::Backing(backing) => {
+                backing.grow(new_pages.bytes().0)?;
+                if backing.len() < new_pages.bytes().0 {
+                    return Err(GrowError::MemoryGrowError);
+                }
+            }
+        }
 
         local.base = self.memory.as_ptr();
         local.bound = new_pages.bytes().0;
 
         let old_pages = self.current;
         self.current = new_pages;
+
+        for callback in &self.grow_callbacks {
+            callback(local.base, new_pages);
+        }
+
         Ok(old_pages)
     }
 
+    /// Shrink this memory back to `minimum` pages and zero it.
+    ///
+    /// A memory over a `MemoryBacking` can't shrink, it's only zeroed.
+    pub(super) fn reset(
+        &mut self,
+        minimum: Pages,
+        local: &mut vm::LocalMemory,
+    ) -> Result<(), CreationError> {
+        match &mut self.memory {
+            Storage::Owned(memory) => {
+                let mut new_memory = sys::Memory::with_size(minimum.bytes().0 + DYNAMIC_GUARD_SIZE)
+                    .map_err(|_| CreationError::UnableToCreateMemory)?;
+                if minimum != Pages(0) {
+                    unsafe {
+                        new_memory
+                            .protect(0..minimum.bytes().0, sys::Protect::ReadWrite)
+                            .map_err(|_| CreationError::UnableToCreateMemory)?;
+                    }
+                }
+
+                *memory = new_memory;
+                self.current = minimum;
+            }
+            Storage::Backing(backing) => unsafe {
+                ptr::write_bytes(backing.base(), 0, self.current.bytes().0);
+            },
+        }
+
+        local.base = self.memory.as_ptr();
+        local.bound = self.current.bytes().0;
+
+        for callback in &self.grow_callbacks {
+            callback(local.base, self.current);
+        }
+
+        Ok(())
+    }
+
+    /// Register a callback to be invoked after every successful grow.
+    pub(super) fn subscribe_grow(&mut self, callback: GrowCallback) {
+        self.grow_callbacks.push(callback);
+    }
+
     /// Get this memory represented as a slice of bytes.
     pub fn as_slice(&self) -> &[u8] {
-        unsafe { &self.memory.as_slice()[0..self.current.bytes().0] }
+        unsafe { slice::from_raw_parts(self.memory.as_ptr(), self.current.bytes().0) }
     }
 
     /// Get this memory represented as a mutable slice of bytes
     pub fn as_slice_mut(&mut self) -> &mut [u8] {
-        unsafe { &mut self.memory.as_slice_mut()[0..self.current.bytes().0] }
+        unsafe { slice::from_raw_parts_mut(self.memory.as_ptr(), self.current.bytes().0) }
+    }
+}
+
+/// The memory a `DynamicMemory` lives in.
+enum Storage {
+    /// Memory mapped by the runtime, followed by a guard page.
+    Owned(sys::Memory),
+    /// Storage provided by the embedder.
+    Backing(Box<dyn MemoryBacking>),
+}
+
+impl Storage {
+    fn as_ptr(&self) -> *mut u8 {
+        match self {
+            Storage::Owned(memory) => memory.as_ptr(),
+            Storage::Backing(backing) => backing.base(),
+        }
     }
 }
@@ -1,30 +1,49 @@
 //! The memory module contains the implementation data structures and helper functions used to
 //! manipulate and access wasm memory.
 use crate::{
-    error::{CreationError, GrowError},
+    error::{CreationError, GrowError, MemoryAccessError},
     export::Export,
     import::IsExport,
     memory::dynamic::DYNAMIC_GUARD_SIZE,
     memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
+    pool::InstancePool,
     types::{MemoryDescriptor, ValueType},
     units::Pages,
     vm,
 };
-use std::{cell::Cell, fmt, mem, sync::Arc};
+use std::{
+    cell::Cell,
+    fmt, mem,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use std::sync::Mutex as StdMutex;
 
+pub use self::atomics::WaitResult;
+#[cfg(unix)]
+pub use self::backing::MappedFile;
+pub use self::backing::{MemoryBacking, MEMORY_BACKING_GUARD_SIZE};
 pub use self::dynamic::DynamicMemory;
 pub use self::static_::StaticMemory;
-pub use self::view::{Atomically, MemoryView};
+pub use self::view::{Atomic, Atomically, MemoryView};
 
 use parking_lot::Mutex;
 
+mod atomics;
+mod backing;
 mod dynamic;
 pub mod ptr;
-mod static_;
+pub(crate) mod static_;
 mod view;
 
+/// A callback invoked after a memory has grown, receiving the new base
+/// pointer of the memory and its new size.
+pub type GrowCallback = Box<dyn Fn(*mut u8, Pages) + Send + Sync>;
+
 #[derive(Clone)]
 enum MemoryVariant {
     Unshared(UnsharedMemory),
@@ -84,6 +103,64 @@ impl Memory {
         Ok(Memory { desc, variant })
     }
 
+    /// Create a new `Memory` over storage provided by the embedder, like
+    /// a preallocated buffer or a mapped file.
+    ///
+    /// The memory starts out as large as `backing`, which must lie within
+    /// the limits of `desc`.  Growing the memory is delegated to
+    /// [`MemoryBacking::grow`].  Memories over a backing are always
+    /// dynamic, so they can only be imported by modules that don't
+    /// declare a maximum size, and can't be shared.
+    ///
+    /// Usage:
+    ///
+    /// ```
+    /// # use wasmer_runtime_core::types::MemoryDescriptor;
+    /// # use wasmer_runtime_core::memory::{Memory, MEMORY_BACKING_GUARD_SIZE};
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::units::{Bytes, Pages};
+    /// fn create_memory() -> Result<()> {
+    ///     let descriptor = MemoryDescriptor::new(Pages(1), None, false).unwrap();
+    ///     let buffer = vec![0; Bytes::from(Pages(1)).0 + MEMORY_BACKING_GUARD_SIZE];
+    ///
+    ///     let memory = Memory::with_backing(descriptor, buffer)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_backing<B>(mut desc: MemoryDescriptor, backing: B) -> Result<Self, CreationError>
+    where
+        B: MemoryBacking + 'static,
+    {
+        if desc.shared {
+            return Err(CreationError::InvalidDescriptor(
+                "memories over a backing can't be shared".to_string(),
+            ));
+        }
+        desc.memory_type = MemoryType::Dynamic;
+
+        let variant =
+            MemoryVariant::Unshared(UnsharedMemory::with_backing(desc, Box::new(backing))?);
+
+        Ok(Memory { desc, variant })
+    }
+
+    /// Create a new `Memory` for a pooled instance.  Unshared static
+    /// memories are created in a reservation taken from `pool`.
+    pub(crate) fn new_pooled(
+        desc: MemoryDescriptor,
+        pool: &InstancePool,
+    ) -> Result<Self, CreationError> {
+        if desc.memory_type() != MemoryType::Static
+            || desc.maximum.map_or(false, |max| max < desc.minimum)
+        {
+            return Self::new(desc);
+        }
+
+        let variant = MemoryVariant::Unshared(UnsharedMemory::new_pooled(desc, pool)?);
+
+        Ok(Memory { desc, variant })
+    }
+
     /// Return the [`MemoryDescriptor`] that this memory
     /// was created with.
     ///
@@ -100,6 +177,53 @@ impl Memory {
         }
     }
 
+    /// Shrink this memory back to its minimum size and zero it, for
+    /// `Instance::reset`.  Shared memories can't be reset.
+    pub(crate) fn reset(&self) -> Result<(), CreationError> {
+        match &self.variant {
+            MemoryVariant::Unshared(unshared_mem) => unshared_mem.reset(self.desc.minimum),
+            MemoryVariant::Shared(_) => Err(CreationError::InvalidDescriptor(
+                "shared memories can't be reset".to_string(),
+            )),
+        }
+    }
+
+    /// Register a callback that is invoked after every successful grow of
+    /// this memory, whether it was grown by the host or by the guest through
+    /// `memory.grow`.
+    ///
+    /// Growing may move the memory, so hosts holding raw pointers into it
+    /// can use the new base pointer to refresh them.  The callback is also
+    /// invoked when `Instance::reset` shrinks the memory back to its
+    /// minimum size.
+    ///
+    /// # Notes:
+    ///
+    /// The callback runs while the memory is locked, so it must not call
+    /// back into this `Memory` (e.g. `size` or `grow`).
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use wasmer_runtime_core::memory::Memory;
+    /// # fn subscribe(memory: &Memory) {
+    /// memory.subscribe_grow(|base, pages| {
+    ///     println!("memory is now {:?} at {:p}", pages, base);
+    /// });
+    /// # }
+    /// ```
+    pub fn subscribe_grow<F>(&self, callback: F)
+    where
+        F: Fn(*mut u8, Pages) + Send + Sync + 'static,
+    {
+        match &self.variant {
+            MemoryVariant::Unshared(unshared_mem) => {
+                unshared_mem.subscribe_grow(Box::new(callback))
+            }
+            MemoryVariant::Shared(shared_mem) => shared_mem.subscribe_grow(Box::new(callback)),
+        }
+    }
+
     /// The size, in wasm pages, of this memory.
     pub fn size(&self) -> Pages {
         match &self.variant {
@@ -149,6 +273,124 @@ impl Memory {
         unsafe { MemoryView::new(base as _, length as u32) }
     }
 
+    /// Return a view of the currently accessible memory that only allows
+    /// atomic accesses, e.g. an `AtomicU32` for each `u32` of the memory.
+    ///
+    /// This is the same as `memory.view::<T>().atomically()`.
+    ///
+    /// # Notes:
+    ///
+    /// Guest code and other threads may access a shared memory at any
+    /// time.  Host code reading or writing a shared memory concurrently
+    /// with them must use an atomic view: accesses through a [`view`] are
+    /// data races.  A wasm atomic instruction only synchronizes with host
+    /// accesses to the same location, of the same size, with `SeqCst`
+    /// ordering.
+    ///
+    /// [`view`]: #method.view
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use wasmer_runtime_core::memory::Memory;
+    /// # use std::sync::atomic::Ordering;
+    /// # fn increment(memory: &Memory) {
+    /// let counters = memory.atomic_view::<u32>();
+    /// counters[0x100].fetch_add(1, Ordering::SeqCst);
+    /// # }
+    /// ```
+    pub fn atomic_view<T: Atomic + ValueType>(&self) -> MemoryView<T::Output, Atomically> {
+        self.view::<T>().atomically()
+    }
+
+    /// Wait until the `u32` at `offset` is notified with [`atomic_notify`],
+    /// if it holds `expected`, or until `timeout` expires.
+    ///
+    /// Like `memory.atomic.wait32`, this fails if `offset` isn't aligned or
+    /// out of bounds, or if this memory isn't shared.
+    ///
+    /// [`atomic_notify`]: #method.atomic_notify
+    pub fn atomic_wait32(
+        &self,
+        offset: u32,
+        expected: u32,
+        timeout: Option<Duration>,
+    ) -> Result<WaitResult, MemoryAccessError> {
+        let addr = self.shared_atomic_addr(offset, 4)?;
+        let value = unsafe { &*(addr as *const AtomicU32) };
+
+        Ok(atomics::wait(
+            addr,
+            || value.load(Ordering::SeqCst) == expected,
+            timeout,
+        ))
+    }
+
+    /// Wait until the `u64` at `offset` is notified with [`atomic_notify`],
+    /// if it holds `expected`, or until `timeout` expires.
+    ///
+    /// Like `memory.atomic.wait64`, this fails if `offset` isn't aligned or
+    /// out of bounds, or if this memory isn't shared.
+    ///
+    /// [`atomic_notify`]: #method.atomic_notify
+    pub fn atomic_wait64(
+        &self,
+        offset: u32,
+        expected: u64,
+        timeout: Option<Duration>,
+    ) -> Result<WaitResult, MemoryAccessError> {
+        let addr = self.shared_atomic_addr(offset, 8)?;
+        let value = unsafe { &*(addr as *const AtomicU64) };
+
+        Ok(atomics::wait(
+            addr,
+            || value.load(Ordering::SeqCst) == expected,
+            timeout,
+        ))
+    }
+
+    /// Wake up to `count` of the threads waiting on `offset` with
+    /// [`atomic_wait32`] or [`atomic_wait64`], and return how many were
+    /// woken.
+    ///
+    /// Like `memory.atomic.notify`, this fails if `offset` isn't aligned
+    /// for a `u32` or out of bounds, and wakes no one if this memory isn't
+    /// shared.
+    ///
+    /// [`atomic_wait32`]: #method.atomic_wait32
+    /// [`atomic_wait64`]: #method.atomic_wait64
+    pub fn atomic_notify(&self, offset: u32, count: u32) -> Result<u32, MemoryAccessError> {
+        match self.shared_atomic_addr(offset, 4) {
+            Ok(addr) => Ok(atomics::notify(addr, count)),
+            Err(MemoryAccessError::NotShared) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The address of the `size` bytes at `offset`, checked for an atomic
+    /// access; only shared memories can be waited on.
+    fn shared_atomic_addr(&self, offset: u32, size: u32) -> Result<usize, MemoryAccessError> {
+        if offset % size != 0 {
+            return Err(MemoryAccessError::Unaligned {
+                offset,
+                align: size,
+            });
+        }
+        if offset as usize + size as usize > self.size().bytes().0 {
+            return Err(MemoryAccessError::OutOfBounds {
+                offset,
+                len: size as usize,
+            });
+        }
+        if !self.desc.shared {
+            return Err(MemoryAccessError::NotShared);
+        }
+
+        // shared memories are static, so the address doesn't change
+        let vm::LocalMemory { base, .. } = unsafe { *self.vm_local_memory() };
+        Ok(base as usize + offset as usize)
+    }
+
     pub(crate) fn vm_local_memory(&self) -> *mut vm::LocalMemory {
         match &self.variant {
             MemoryVariant::Unshared(unshared_mem) => unshared_mem.vm_local_memory(),
@@ -157,6 +399,12 @@ impl Memory {
     }
 }
 
+impl AsRef<Memory> for Memory {
+    fn as_ref(&self) -> &Memory {
+        self
+    }
+}
+
 impl IsExport for Memory {
     fn to_export(&self) -> Export {
         Export::Memory(self.clone())
@@ -251,6 +499,45 @@ impl UnsharedMemory {
         })
     }
 
+    fn new_pooled(desc: MemoryDescriptor, pool: &InstancePool) -> Result<Self, CreationError> {
+        let mut local = vm::LocalMemory {
+            base: std::ptr::null_mut(),
+            bound: 0,
+            memory: std::ptr::null_mut(),
+        };
+
+        let storage =
+            UnsharedMemoryStorage::Static(StaticMemory::new_pooled(desc, pool, &mut local)?);
+
+        Ok(Self {
+            internal: Arc::new(UnsharedMemoryInternal {
+                storage: StdMutex::new(storage),
+                local: Cell::new(local),
+            }),
+        })
+    }
+
+    fn with_backing(
+        desc: MemoryDescriptor,
+        backing: Box<dyn MemoryBacking>,
+    ) -> Result<Self, CreationError> {
+        let mut local = vm::LocalMemory {
+            base: std::ptr::null_mut(),
+            bound: 0,
+            memory: std::ptr::null_mut(),
+        };
+
+        let storage =
+            UnsharedMemoryStorage::Dynamic(DynamicMemory::with_backing(desc, backing, &mut local)?);
+
+        Ok(Self {
+            internal: Arc::new(UnsharedMemoryInternal {
+                storage: StdMutex::new(storage),
+                local: Cell::new(local),
+            }),
+        })
+    }
+
     /// Try to grow this memory by the given number of delta pages.
     pub fn grow(&self, delta: Pages) -> Result<Pages, GrowError> {
         let mut storage = self.internal.storage.lock().unwrap();
@@ -269,6 +556,38 @@ impl UnsharedMemory {
         pages
     }
 
+    /// Shrink this memory back to `minimum` pages and zero it.
+    pub(crate) fn reset(&self, minimum: Pages) -> Result<(), CreationError> {
+        let mut storage = self.internal.storage.lock().unwrap();
+
+        let mut local = self.internal.local.get();
+
+        let result = match &mut *storage {
+            UnsharedMemoryStorage::Dynamic(dynamic_memory) => {
+                dynamic_memory.reset(minimum, &mut local)
+            }
+            UnsharedMemoryStorage::Static(static_memory) => {
+                static_memory.reset(minimum, &mut local)
+            }
+        };
+
+        self.internal.local.set(local);
+
+        result
+    }
+
+    /// Register a callback to be invoked after every successful grow.
+    pub fn subscribe_grow(&self, callback: GrowCallback) {
+        let mut storage = self.internal.storage.lock().unwrap();
+
+        match &mut *storage {
+            UnsharedMemoryStorage::Dynamic(dynamic_memory) => {
+                dynamic_memory.subscribe_grow(callback)
+            }
+            UnsharedMemoryStorage::Static(static_memory) => static_memory.subscribe_grow(callback),
+        }
+    }
+
     /// Size of this memory in pages.
     pub fn size(&self) -> Pages {
         let storage = self.internal.storage.lock().unwrap();
@@ -336,6 +655,13 @@ impl SharedMemory {
         pages
     }
 
+    /// Register a callback to be invoked after every successful grow.
+    pub fn subscribe_grow(&self, callback: GrowCallback) {
+        let _guard = self.internal.lock.lock();
+        let mut memory = self.internal.memory.lock().unwrap();
+        memory.subscribe_grow(callback);
+    }
+
     /// Size of this memory in pages.
     pub fn size(&self) -> Pages {
         let _guard = self.internal.lock.lock();
@@ -361,7 +687,18 @@ impl Clone for SharedMemory {
 #[cfg(test)]
 mod memory_tests {
 
-    use super::{Memory, MemoryDescriptor, Pages};
+    use super::{
+        Memory, MemoryBacking, MemoryDescriptor, Pages, WaitResult, MEMORY_BACKING_GUARD_SIZE,
+    };
+    use crate::{error::MemoryAccessError, units::Bytes};
+    use std::{
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
 
     #[test]
     fn test_initial_memory_size() {
@@ -370,6 +707,106 @@ mod memory_tests {
         assert_eq!(unshared_memory.size(), Pages(10));
     }
 
+    #[test]
+    fn test_grow_callback_is_invoked() {
+        let memory_desc = MemoryDescriptor::new(Pages(1), Some(Pages(10)), false).unwrap();
+        let memory = Memory::new(memory_desc).unwrap();
+        let observed = Arc::new(AtomicU32::new(0));
+
+        let observed_clone = Arc::clone(&observed);
+        memory.subscribe_grow(move |base, pages| {
+            assert!(!base.is_null());
+            observed_clone.store(pages.0, Ordering::SeqCst);
+        });
+
+        memory.grow(Pages(2)).unwrap();
+        assert_eq!(observed.load(Ordering::SeqCst), 3);
+
+        assert!(memory.grow(Pages(100)).is_err());
+        assert_eq!(observed.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_memory_with_backing() {
+        let page = Bytes::from(Pages(1)).0;
+        let memory_desc = MemoryDescriptor::new(Pages(1), None, false).unwrap();
+
+        let too_small = vec![0; MEMORY_BACKING_GUARD_SIZE];
+        assert!(Memory::with_backing(memory_desc, too_small).is_err());
+
+        let memory =
+            Memory::with_backing(memory_desc, vec![0; page + MEMORY_BACKING_GUARD_SIZE]).unwrap();
+        assert_eq!(memory.size(), Pages(1));
+        memory.view::<u8>()[page - 1].set(42);
+
+        memory.grow(Pages(2)).unwrap();
+        assert_eq!(memory.size(), Pages(3));
+        assert_eq!(memory.view::<u8>()[page - 1].get(), 42);
+        assert_eq!(memory.view::<u8>().len(), 3 * page);
+
+        struct Fixed(Vec<u8>);
+        unsafe impl MemoryBacking for Fixed {
+            fn base(&self) -> *mut u8 {
+                self.0.as_ptr() as *mut u8
+            }
+
+            fn len(&self) -> usize {
+                self.0.len() - MEMORY_BACKING_GUARD_SIZE
+            }
+        }
+        let fixed = Fixed(vec![0; page + MEMORY_BACKING_GUARD_SIZE]);
+        let memory = Memory::with_backing(memory_desc, fixed).unwrap();
+        assert!(memory.grow(Pages(1)).is_err());
+        assert_eq!(memory.size(), Pages(1));
+    }
+
+    #[test]
+    fn test_atomic_wait_and_notify() {
+        let memory_desc = MemoryDescriptor::new(Pages(1), Some(Pages(1)), true).unwrap();
+        let memory = Memory::new(memory_desc).unwrap();
+
+        memory.atomic_view::<u32>()[1].store(7, Ordering::SeqCst);
+        assert_eq!(memory.view::<u32>()[1].get(), 7);
+
+        assert_eq!(memory.atomic_notify(4, 1), Ok(0));
+        assert_eq!(memory.atomic_wait32(4, 8, None), Ok(WaitResult::NotEqual));
+        assert_eq!(
+            memory.atomic_wait64(8, 0, Some(Duration::from_millis(10))),
+            Ok(WaitResult::TimedOut)
+        );
+        assert_eq!(
+            memory.atomic_wait32(2, 0, None),
+            Err(MemoryAccessError::Unaligned {
+                offset: 2,
+                align: 4
+            })
+        );
+        assert_eq!(
+            memory.atomic_notify(65536, 1),
+            Err(MemoryAccessError::OutOfBounds {
+                offset: 65536,
+                len: 4
+            })
+        );
+
+        let waiter = {
+            let memory = memory.clone();
+            thread::spawn(move || memory.atomic_wait32(4, 7, None))
+        };
+        while memory.atomic_notify(4, 1) == Ok(0) {
+            thread::yield_now();
+        }
+        assert_eq!(waiter.join().unwrap(), Ok(WaitResult::Ok));
+
+        let unshared_desc = MemoryDescriptor::new(Pages(1), Some(Pages(1)), false).unwrap();
+        let unshared = Memory::new(unshared_desc).unwrap();
+        assert_eq!(
+            unshared.atomic_wait32(0, 0, None),
+            Err(MemoryAccessError::NotShared)
+        );
+        assert_eq!(unshared.atomic_notify(0, 1), Ok(0));
+    }
+
     #[test]
     fn test_invalid_descriptor_returns_error() {
         let memory_desc = MemoryDescriptor::new(Pages(10), None, true);
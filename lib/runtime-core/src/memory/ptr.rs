@@ -5,12 +5,17 @@
 //!
 //! Therefore, you should use this abstraction whenever possible to avoid memory
 //! related bugs when implementing an ABI.
+//!
+//! The methods taking a memory accept anything that is `AsRef<Memory>`: a
+//! [`Memory`] or a [`Ctx`], whose first memory is used.
+//!
+//! [`Ctx`]: ../../vm/struct.Ctx.html
 
 use crate::{
     memory::Memory,
     types::{ValueType, WasmExternType},
 };
-use std::{cell::Cell, fmt, marker::PhantomData, mem};
+use std::{borrow::Cow, cell::Cell, fmt, marker::PhantomData, mem, ops::Deref};
 
 /// Array.
 pub struct Array;
@@ -39,6 +44,30 @@ impl<T: Copy, Ty> WasmPtr<T, Ty> {
     pub fn offset(self) -> u32 {
         self.offset
     }
+
+    /// The pointer `count` items of `T` past this one, or `None` if it
+    /// would overflow.
+    #[inline]
+    pub fn checked_add(self, count: u32) -> Option<Self> {
+        let bytes = count.checked_mul(mem::size_of::<T>() as u32)?;
+        Some(Self::new(self.offset.checked_add(bytes)?))
+    }
+
+    /// The pointer `count` items of `T` before this one, or `None` if it
+    /// would underflow.
+    #[inline]
+    pub fn checked_sub(self, count: u32) -> Option<Self> {
+        let bytes = count.checked_mul(mem::size_of::<T>() as u32)?;
+        Some(Self::new(self.offset.checked_sub(bytes)?))
+    }
+}
+
+/// Whether `len` bytes at `offset` lie within `memory`.
+#[inline(always)]
+fn in_bounds(memory: &Memory, offset: u32, len: usize) -> bool {
+    (offset as usize)
+        .checked_add(len)
+        .map_or(false, |end| end <= memory.size().bytes().0)
 }
 
 #[inline(always)]
@@ -51,8 +80,9 @@ fn align_pointer(ptr: usize, align: usize) -> usize {
 impl<T: Copy + ValueType> WasmPtr<T, Item> {
     /// Dereference this `WasmPtr`.
     #[inline]
-    pub fn deref<'a>(self, memory: &'a Memory) -> Option<&'a Cell<T>> {
-        if (self.offset as usize) + mem::size_of::<T>() >= memory.size().bytes().0 {
+    pub fn deref<'a, M: AsRef<Memory> + ?Sized>(self, memory: &'a M) -> Option<&'a Cell<T>> {
+        let memory = memory.as_ref();
+        if !in_bounds(memory, self.offset, mem::size_of::<T>()) {
             return None;
         }
         unsafe {
@@ -66,8 +96,12 @@ impl<T: Copy + ValueType> WasmPtr<T, Item> {
 
     /// Mutable dereference this `WasmPtr`.
     #[inline]
-    pub unsafe fn deref_mut<'a>(self, memory: &'a Memory) -> Option<&'a mut Cell<T>> {
-        if (self.offset as usize) + mem::size_of::<T>() >= memory.size().bytes().0 {
+    pub unsafe fn deref_mut<'a, M: AsRef<Memory> + ?Sized>(
+        self,
+        memory: &'a M,
+    ) -> Option<&'a mut Cell<T>> {
+        let memory = memory.as_ref();
+        if !in_bounds(memory, self.offset, mem::size_of::<T>()) {
             return None;
         }
         let cell_ptr = align_pointer(
@@ -81,13 +115,19 @@ impl<T: Copy + ValueType> WasmPtr<T, Item> {
 impl<T: Copy + ValueType> WasmPtr<T, Array> {
     /// Dereference this `WasmPtr`.
     #[inline]
-    pub fn deref<'a>(self, memory: &'a Memory, index: u32, length: u32) -> Option<&'a [Cell<T>]> {
+    pub fn deref<'a, M: AsRef<Memory> + ?Sized>(
+        self,
+        memory: &'a M,
+        index: u32,
+        length: u32,
+    ) -> Option<&'a [Cell<T>]> {
+        let memory = memory.as_ref();
         // gets the size of the item in the array with padding added such that
         // for any index, we will always result an aligned memory access
         let item_size = mem::size_of::<T>() + (mem::size_of::<T>() % mem::align_of::<T>());
         let slice_full_len = index as usize + length as usize;
 
-        if (self.offset as usize) + (item_size * slice_full_len) >= memory.size().bytes().0 {
+        if !in_bounds(memory, self.offset, item_size * slice_full_len) {
             return None;
         }
 
@@ -104,18 +144,19 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
 
     /// Mutable dereference this `WasmPtr`.
     #[inline]
-    pub unsafe fn deref_mut<'a>(
+    pub unsafe fn deref_mut<'a, M: AsRef<Memory> + ?Sized>(
         self,
-        memory: &'a Memory,
+        memory: &'a M,
         index: u32,
         length: u32,
     ) -> Option<&'a mut [Cell<T>]> {
+        let memory = memory.as_ref();
         // gets the size of the item in the array with padding added such that
         // for any index, we will always result an aligned memory access
         let item_size = mem::size_of::<T>() + (mem::size_of::<T>() % mem::align_of::<T>());
         let slice_full_len = index as usize + length as usize;
 
-        if (self.offset as usize) + (item_size * slice_full_len) >= memory.size().bytes().0 {
+        if !in_bounds(memory, self.offset, item_size * slice_full_len) {
             return None;
         }
 
@@ -128,20 +169,77 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
         Some(cell_ptrs)
     }
 
+    /// Get a checked view of the `length` items this `WasmPtr` points to.
+    ///
+    /// Returns `None` if the items don't all lie within the memory or if
+    /// this `WasmPtr` isn't aligned for `T`.
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use wasmer_runtime_core::memory::{Memory, ptr::{Array, WasmPtr}};
+    /// fn sum(memory: &Memory, ptr: WasmPtr<u32, Array>, len: u32) -> Option<u32> {
+    ///     let items = ptr.slice(memory, len)?;
+    ///     Some(items.iter().map(|item| item.get()).sum())
+    /// }
+    /// ```
+    #[inline]
+    pub fn slice<'a, M: AsRef<Memory> + ?Sized>(
+        self,
+        memory: &'a M,
+        length: u32,
+    ) -> Option<WasmSlice<'a, T>> {
+        let memory = memory.as_ref();
+        let len = (length as usize).checked_mul(mem::size_of::<T>())?;
+        if self.offset as usize % mem::align_of::<T>() != 0 || !in_bounds(memory, self.offset, len)
+        {
+            return None;
+        }
+
+        let cells = unsafe {
+            let ptr = memory.view::<u8>().as_ptr().add(self.offset as usize) as *const Cell<T>;
+            std::slice::from_raw_parts(ptr, length as usize)
+        };
+        Some(WasmSlice { cells })
+    }
+
     /// Get a UTF-8 string representation of this `WasmPtr` with the given length.
-    pub fn get_utf8_string<'a>(self, memory: &'a Memory, str_len: u32) -> Option<&'a str> {
-        if self.offset as usize + str_len as usize > memory.size().bytes().0 {
+    pub fn get_utf8_string<'a, M: AsRef<Memory> + ?Sized>(
+        self,
+        memory: &'a M,
+        str_len: u32,
+    ) -> Option<&'a str> {
+        std::str::from_utf8(self.get_bytes(memory.as_ref(), str_len)?).ok()
+    }
+
+    /// Get the string of the given length this `WasmPtr` points to, replacing
+    /// invalid UTF-8 sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn get_utf8_string_lossy<'a, M: AsRef<Memory> + ?Sized>(
+        self,
+        memory: &'a M,
+        str_len: u32,
+    ) -> Option<Cow<'a, str>> {
+        Some(String::from_utf8_lossy(
+            self.get_bytes(memory.as_ref(), str_len)?,
+        ))
+    }
+
+    fn get_bytes(self, memory: &Memory, len: u32) -> Option<&[u8]> {
+        if !in_bounds(memory, self.offset, len as usize) {
             return None;
         }
         let ptr = unsafe { memory.view::<u8>().as_ptr().add(self.offset as usize) as *const u8 };
-        let slice: &[u8] = unsafe { std::slice::from_raw_parts(ptr, str_len as usize) };
-        std::str::from_utf8(slice).ok()
+        Some(unsafe { std::slice::from_raw_parts(ptr, len as usize) })
     }
 
     /// Get a UTF-8 string representation of this `WasmPtr`, where the string is nul-terminated.
     /// Note that this does not account for UTF-8 strings that _contain_ nul themselves,
     /// [`get_utf8_string`] has to be used for those.
-    pub fn get_utf8_string_with_nul<'a>(self, memory: &'a Memory) -> Option<&'a str> {
+    pub fn get_utf8_string_with_nul<'a, M: AsRef<Memory> + ?Sized>(
+        self,
+        memory: &'a M,
+    ) -> Option<&'a str> {
+        let memory = memory.as_ref();
         memory.view::<u8>()[(self.offset as usize)..]
             .iter()
             .map(|cell| cell.get())
@@ -150,6 +248,45 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
     }
 }
 
+/// A checked view of an array of `T`s in the memory of an instance, see
+/// [`WasmPtr::slice`].
+///
+/// [`WasmPtr::slice`]: struct.WasmPtr.html#method.slice
+pub struct WasmSlice<'a, T: Copy> {
+    cells: &'a [Cell<T>],
+}
+
+impl<'a, T: Copy> WasmSlice<'a, T> {
+    /// Copy the items out of the memory.
+    pub fn to_vec(&self) -> Vec<T> {
+        self.cells.iter().map(Cell::get).collect()
+    }
+
+    /// Copy `items` into the memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `items` doesn't have the length of this slice.
+    pub fn copy_from_slice(&self, items: &[T]) {
+        assert_eq!(
+            self.cells.len(),
+            items.len(),
+            "slices have different lengths"
+        );
+        for (cell, &item) in self.cells.iter().zip(items) {
+            cell.set(item);
+        }
+    }
+}
+
+impl<'a, T: Copy> Deref for WasmSlice<'a, T> {
+    type Target = [Cell<T>];
+
+    fn deref(&self) -> &[Cell<T>] {
+        self.cells
+    }
+}
+
 unsafe impl<T: Copy, Ty> WasmExternType for WasmPtr<T, Ty> {
     type Native = i32;
 
@@ -190,3 +327,41 @@ impl<T: Copy, Ty> fmt::Debug for WasmPtr<T, Ty> {
         write!(f, "WasmPtr({:#x})", self.offset)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{types::MemoryDescriptor, units::Pages};
+
+    #[test]
+    fn wasm_ptr_bounds_and_slices() {
+        let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+        let end = memory.size().bytes().0 as u32;
+
+        let last: WasmPtr<u32> = WasmPtr::new(end - 4);
+        assert!(last.deref(&memory).is_some());
+        assert!(last.checked_add(1).unwrap().deref(&memory).is_none());
+        assert_eq!(last.checked_sub(1), Some(WasmPtr::new(end - 8)));
+        assert_eq!(WasmPtr::<u32>::new(u32::max_value()).checked_add(1), None);
+
+        let array: WasmPtr<u32, Array> = WasmPtr::new(8);
+        let items = array.slice(&memory, 3).unwrap();
+        items.copy_from_slice(&[1, 2, 3]);
+        assert_eq!(array.slice(&memory, 3).unwrap().to_vec(), vec![1, 2, 3]);
+        assert!(WasmPtr::<u32, Array>::new(6).slice(&memory, 1).is_none());
+        assert!(WasmPtr::<u32, Array>::new(end - 4)
+            .slice(&memory, 2)
+            .is_none());
+
+        let string: WasmPtr<u8, Array> = WasmPtr::new(16);
+        string
+            .slice(&memory, 4)
+            .unwrap()
+            .copy_from_slice(b"a\xffb\0");
+        assert_eq!(string.get_utf8_string(&memory, 3), None);
+        assert_eq!(
+            string.get_utf8_string_lossy(&memory, 3).unwrap(),
+            "a\u{fffd}b"
+        );
+    }
+}
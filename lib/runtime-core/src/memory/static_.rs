@@ -1,5 +1,9 @@
 use crate::error::GrowError;
-use crate::{error::CreationError, sys, types::MemoryDescriptor, units::Pages, vm};
+use crate::{
+    error::CreationError, memory::GrowCallback, pool::InstancePool, sys, types::MemoryDescriptor,
+    units::Pages, vm,
+};
+use std::mem;
 
 #[doc(hidden)]
 pub const SAFE_STATIC_HEAP_SIZE: usize = 1 << 32; // 4 GiB
@@ -15,12 +19,15 @@ pub const SAFE_STATIC_GUARD_SIZE: usize = 1 << 31; // 2 GiB
 /// to reload its address on each use.
 ///
 /// Static memories take a relatively long time to create, so if memories are short-lived,
-/// it's recommended that a dynamic memory is used. There is currently no user-facing api that
-/// allows them to select the type of memory used however.
+/// it's recommended that a dynamic memory is used, by compiling the module with
+/// `MemoryStyle::Dynamic`.
 pub struct StaticMemory {
     memory: sys::Memory,
     current: Pages,
     max: Option<Pages>,
+    grow_callbacks: Vec<GrowCallback>,
+    /// The pool `memory` is returned to when this memory is dropped.
+    pool: Option<InstancePool>,
 }
 
 impl StaticMemory {
@@ -28,25 +35,45 @@ impl StaticMemory {
         desc: MemoryDescriptor,
         local: &mut vm::LocalMemory,
     ) -> Result<Box<Self>, CreationError> {
-        let memory = {
-            let mut memory = sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE)
-                .map_err(|_| CreationError::UnableToCreateMemory)?;
-            if desc.minimum != Pages(0) {
-                unsafe {
-                    memory
-                        .protect(0..desc.minimum.bytes().0, sys::Protect::ReadWrite)
-                        .map_err(|_| CreationError::UnableToCreateMemory)?;
-                }
-            }
+        let memory = sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE)
+            .map_err(|_| CreationError::UnableToCreateMemory)?;
 
-            memory
-        };
+        Self::with_memory(desc, memory, None, local)
+    }
+
+    /// Create a memory in a reservation taken from `pool`.
+    pub(in crate::memory) fn new_pooled(
+        desc: MemoryDescriptor,
+        pool: &InstancePool,
+        local: &mut vm::LocalMemory,
+    ) -> Result<Box<Self>, CreationError> {
+        let memory = pool.take_memory()?;
+
+        Self::with_memory(desc, memory, Some(pool.clone()), local)
+    }
 
+    fn with_memory(
+        desc: MemoryDescriptor,
+        memory: sys::Memory,
+        pool: Option<InstancePool>,
+        local: &mut vm::LocalMemory,
+    ) -> Result<Box<Self>, CreationError> {
+        // constructed first, so that a pooled memory returns to its pool on failure
         let mut storage = Box::new(StaticMemory {
             memory,
             current: desc.minimum,
             max: desc.maximum,
+            grow_callbacks: Vec::new(),
+            pool,
         });
+        if desc.minimum != Pages(0) {
+            unsafe {
+                storage
+                    .memory
+                    .protect(0..desc.minimum.bytes().0, sys::Protect::ReadWrite)
+                    .map_err(|_| CreationError::UnableToCreateMemory)?;
+            }
+        }
         let storage_ptr: *mut StaticMemory = &mut *storage;
 
         local.base = storage.memory.as_ptr();
@@ -93,9 +120,45 @@ impl StaticMemory {
 
         self.current = new_pages;
 
+        for callback in &self.grow_callbacks {
+            callback(local.base, new_pages);
+        }
+
         Ok(old_pages)
     }
 
+    /// Shrink this memory back to `minimum` pages and zero it.
+    pub(in crate::memory) fn reset(
+        &mut self,
+        minimum: Pages,
+        local: &mut vm::LocalMemory,
+    ) -> Result<(), CreationError> {
+        unsafe {
+            self.memory
+                .reset(self.current.bytes().0)
+                .map_err(|_| CreationError::UnableToCreateMemory)?;
+            if minimum != Pages(0) {
+                self.memory
+                    .protect(0..minimum.bytes().0, sys::Protect::ReadWrite)
+                    .map_err(|_| CreationError::UnableToCreateMemory)?;
+            }
+        }
+
+        local.bound = minimum.bytes().0;
+        self.current = minimum;
+
+        for callback in &self.grow_callbacks {
+            callback(local.base, minimum);
+        }
+
+        Ok(())
+    }
+
+    /// Register a callback to be invoked after every successful grow.
+    pub(in crate::memory) fn subscribe_grow(&mut self, callback: GrowCallback) {
+        self.grow_callbacks.push(callback);
+    }
+
     /// Get this memory represented as a slice of bytes.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { &self.memory.as_slice()[0..self.current.bytes().0] }
@@ -106,3 +169,13 @@ impl StaticMemory {
         unsafe { &mut self.memory.as_slice_mut()[0..self.current.bytes().0] }
     }
 }
+
+impl Drop for StaticMemory {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            let empty = sys::Memory::with_size(0).unwrap();
+            let memory = mem::replace(&mut self.memory, empty);
+            pool.release_memory(memory, self.current.bytes().0);
+        }
+    }
+}
@@ -5,7 +5,9 @@ use std::sync::atomic::{
 };
 use std::{cell::Cell, marker::PhantomData, ops::Deref, slice};
 
+/// A type with an atomic counterpart, e.g. `AtomicU32` for `u32`.
 pub trait Atomic {
+    /// The atomic counterpart.
     type Output;
 }
 impl Atomic for i8 {
@@ -0,0 +1,77 @@
+//! Hooks for recording runtime metrics.
+//!
+//! A [`RuntimeMetrics`] recorder is told about instantiations, compile
+//! cache lookups, memory growth, traps and calls to host functions, and can
+//! forward them to whatever metrics system the embedder uses.
+//!
+//! A recorder is installed either for the whole process with [`set_global`],
+//! or for the instances created from one [`ImportObject`] with
+//! [`ImportObject::set_metrics`], which takes precedence over the global
+//! recorder.  The recorder is resolved when an instance is created, so
+//! installing one doesn't affect the instances that already exist.
+//!
+//! [`ImportObject`]: crate::import::ImportObject
+//! [`ImportObject::set_metrics`]: crate::import::ImportObject::set_metrics
+
+use crate::{typed_func::WasmTrapInfo, units::Pages, vm};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::{fmt, sync::Arc, time::Duration};
+
+lazy_static! {
+    static ref GLOBAL_METRICS: RwLock<Option<Arc<dyn RuntimeMetrics>>> = RwLock::new(None);
+}
+
+/// A recorder of runtime events.
+///
+/// Every method has an empty default implementation, so recorders only
+/// implement the events they're interested in.  The methods are called on
+/// the thread where the event happens, from inside wasm calls for
+/// `memory_grown` and `host_call`, and must be cheap.
+pub trait RuntimeMetrics: Send + Sync {
+    /// An instance was created, including running its start function, in
+    /// `latency`.
+    fn instantiated(&self, _latency: Duration) {}
+
+    /// A module was found in the compile cache.
+    fn cache_hit(&self) {}
+
+    /// A module wasn't found in the compile cache, or couldn't be loaded
+    /// from it.
+    fn cache_miss(&self) {}
+
+    /// A memory grew from `old` to `new` pages with `memory.grow`.
+    fn memory_grown(&self, _old: Pages, _new: Pages) {}
+
+    /// A call into wasm trapped with `trap`.  Errors returned by host
+    /// functions aren't traps and aren't recorded.
+    fn trapped(&self, _trap: &WasmTrapInfo) {}
+
+    /// Wasm called a host function.
+    fn host_call(&self) {}
+}
+
+impl fmt::Debug for dyn RuntimeMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RuntimeMetrics")
+    }
+}
+
+/// Install `metrics` as the recorder for instances whose `ImportObject`
+/// doesn't have one, or remove the global recorder with `None`.
+pub fn set_global(metrics: Option<Arc<dyn RuntimeMetrics>>) {
+    *GLOBAL_METRICS.write() = metrics;
+}
+
+/// The global recorder, if one is installed.
+pub fn global() -> Option<Arc<dyn RuntimeMetrics>> {
+    GLOBAL_METRICS.read().clone()
+}
+
+/// The recorder of the instance that `ctx` belongs to.
+pub(crate) unsafe fn of_ctx<'a>(ctx: *const vm::Ctx) -> Option<&'a dyn RuntimeMetrics> {
+    if ctx.is_null() || (*ctx).local_backing.is_null() {
+        return None;
+    }
+    (*(*ctx).local_backing).metrics.as_ref().map(|m| &**m)
+}
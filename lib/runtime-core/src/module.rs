@@ -1,10 +1,12 @@
 //! The module module contains the implementation data structures and helper functions used to
 //! manipulate and access wasm modules.
 use crate::{
-    backend::{Backend, RunnableModule},
+    backend::{AutoSelection, Backend, Compiler, RunnableModule},
     cache::{Artifact, Error as CacheError},
-    error,
+    error::{self, LinkResult},
     import::ImportObject,
+    instance::InstanceConfig,
+    signing::{self, PublicKey},
     structures::{Map, TypedIndex},
     types::{
         FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit, ImportedFuncIndex,
@@ -17,8 +19,14 @@ use crate::{
 
 use crate::backend::CacheGen;
 use indexmap::IndexMap;
-use std::collections::HashMap;
-use std::sync::Arc;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 /// This is used to instantiate a new WebAssembly module.
 #[doc(hidden)]
@@ -26,6 +34,37 @@ pub struct ModuleInner {
     pub runnable_module: Arc<Box<dyn RunnableModule>>,
     pub cache_gen: Box<dyn CacheGen>,
     pub info: ModuleInfo,
+    /// How the module was compiled, if it wasn't loaded from a cache.
+    pub compile_info: Option<CompileInfo>,
+}
+
+/// How a module was compiled, see [`Module::compile_info`].
+///
+/// Function bodies are translated as they are decoded, so their decoding
+/// is counted in `codegen` rather than `parse`.
+#[derive(Debug, Clone)]
+pub struct CompileInfo {
+    /// The backend that compiled the module.
+    pub backend: Backend,
+    /// Time spent decoding the module, except its function bodies.
+    pub parse: Duration,
+    /// Time spent validating the module before compiling it.  Zero for the
+    /// singlepass backend, which validates the module as it compiles it.
+    pub validate: Duration,
+    /// Time spent translating the function bodies: to machine code for the
+    /// singlepass backend, and to their IR for Cranelift and LLVM.
+    pub codegen: Duration,
+    /// Time spent finishing the module's code: generating machine code from
+    /// the IR of Cranelift and LLVM, resolving relocations and loading it into
+    /// executable memory.
+    pub link: Duration,
+    /// The number of functions defined by the module.
+    pub functions: usize,
+    /// The size of the generated machine code, if the backend exposes it.
+    pub code_size: Option<usize>,
+    /// Why `backend` was picked, if the module was compiled for `Backend::Auto`
+    /// rather than for an explicit backend.
+    pub auto_selection: Option<AutoSelection>,
 }
 
 /// Container for module data including memories, globals, tables, imports, and exports.
@@ -75,13 +114,77 @@ pub struct ModuleInfo {
     /// Symbol information from emscripten.
     pub em_symbol_map: Option<HashMap<u32, String>>,
 
-    /// Custom sections.
-    pub custom_sections: HashMap<String, Vec<u8>>,
+    /// The contents of the custom sections with each name, in the order
+    /// they appear in the module.
+    pub custom_sections: HashMap<String, Vec<Vec<u8>>>,
+
+    /// The names from the name section of the module.
+    pub names: Names,
+
+    /// The target triple the module was compiled for, if it isn't the host.
+    pub triple: Option<String>,
+
+    /// The CPU features the module's code may use on top of the baseline of
+    /// its target, if any, in LLVM's syntax; see
+    /// [`CompilerConfig::required_cpu_features`].
+    ///
+    /// [`CompilerConfig::required_cpu_features`]: ../backend/struct.CompilerConfig.html#method.required_cpu_features
+    pub cpu_features: Option<String>,
+
+    /// Whether the module's code traps without relying on signal handlers.
+    pub no_signals: bool,
+
+    /// Whether the module's code counts the points it uses in
+    /// [`vm::METERING_POINTS_USED`].
+    ///
+    /// [`vm::METERING_POINTS_USED`]: ../vm/static.METERING_POINTS_USED.html
+    pub metered: bool,
+
+    /// Whether the module was compiled with
+    /// [`CompilerConfig::track_state`], which the [`profiler`] needs to
+    /// walk its frames.
+    ///
+    /// [`CompilerConfig::track_state`]: ../backend/struct.CompilerConfig.html#structfield.track_state
+    /// [`profiler`]: ../profiler/index.html
+    pub track_state: bool,
 }
 
 impl ModuleInfo {
+    /// Whether the module's code can run on this host, i.e. it wasn't
+    /// compiled for another architecture or operating system.
+    pub fn runs_on_host(&self) -> bool {
+        match &self.triple {
+            Some(triple) => crate::backend::is_host_triple(triple),
+            None => true,
+        }
+    }
+
+    /// Drops the spare capacity left in the maps of the module by parsing
+    /// it, which can be a large part of the memory used by the `ModuleInfo`
+    /// of modules with many imports, exports or functions.
+    pub fn shrink_to_fit(&mut self) {
+        self.memories.shrink_to_fit();
+        self.globals.shrink_to_fit();
+        self.tables.shrink_to_fit();
+        self.imported_functions.shrink_to_fit();
+        self.imported_memories.shrink_to_fit();
+        self.imported_tables.shrink_to_fit();
+        self.imported_globals.shrink_to_fit();
+        self.exports.shrink_to_fit();
+        self.data_initializers.shrink_to_fit();
+        self.elem_initializers.shrink_to_fit();
+        self.func_assoc.shrink_to_fit();
+        self.signatures.shrink_to_fit();
+        self.namespace_table.table.shrink_to_fit();
+        self.name_table.table.shrink_to_fit();
+        self.custom_sections.shrink_to_fit();
+        self.names.functions.shrink_to_fit();
+        self.names.locals.shrink_to_fit();
+    }
+
     /// Creates custom section info from the given wasm file.
     pub fn import_custom_sections(&mut self, wasm: &[u8]) -> crate::error::ParseResult<()> {
+        self.custom_sections.clear();
         let mut parser = wasmparser::ModuleReader::new(wasm)?;
         while !parser.eof() {
             let section = parser.read()?;
@@ -91,13 +194,77 @@ impl ModuleInfo {
                 let bytes = reader.read_bytes(len)?;
                 let data = bytes.to_vec();
                 let name = name.to_string();
-                self.custom_sections.insert(name, data);
+                self.custom_sections
+                    .entry(name)
+                    .or_insert_with(Vec::new)
+                    .push(data);
             }
         }
+        // A malformed name section doesn't invalidate the module, its names
+        // are just ignored.
+        self.names = self
+            .custom_sections
+            .get("name")
+            .and_then(|sections| sections.first())
+            .and_then(|section| Names::parse(section).ok())
+            .unwrap_or_default();
         Ok(())
     }
 }
 
+/// The names given to a module, its functions and their locals by the
+/// name section of the module.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Names {
+    /// The name of the module.
+    pub module: Option<String>,
+    /// The names of the functions.
+    pub functions: HashMap<FuncIndex, String>,
+    /// The names of the locals of each function, by local index.
+    pub locals: HashMap<FuncIndex, HashMap<u32, String>>,
+}
+
+impl Names {
+    /// Parses the contents of a name section.
+    pub fn parse(section: &[u8]) -> wasmparser::Result<Self> {
+        let mut names = Names::default();
+        let mut reader = wasmparser::NameSectionReader::new(section, 0)?;
+        while !reader.eof() {
+            match reader.read()? {
+                wasmparser::Name::Module(module_name) => {
+                    names.module = Some(module_name.get_name()?.to_string());
+                }
+                wasmparser::Name::Function(function_names) => {
+                    let mut map = function_names.get_map()?;
+                    for _ in 0..map.get_count() {
+                        let naming = map.read()?;
+                        names.functions.insert(
+                            FuncIndex::new(naming.index as usize),
+                            naming.name.to_string(),
+                        );
+                    }
+                }
+                wasmparser::Name::Local(local_names) => {
+                    let mut functions = local_names.get_function_local_reader()?;
+                    for _ in 0..functions.get_count() {
+                        let function = functions.read()?;
+                        let mut map = function.get_map()?;
+                        let locals = names
+                            .locals
+                            .entry(FuncIndex::new(function.func_index as usize))
+                            .or_insert_with(HashMap::new);
+                        for _ in 0..map.get_count() {
+                            let naming = map.read()?;
+                            locals.insert(naming.index, naming.name.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(names)
+    }
+}
+
 /// A compiled WebAssembly module.
 ///
 /// `Module` is returned by the [`compile_with`][] function.
@@ -112,6 +279,21 @@ impl Module {
         Module { inner }
     }
 
+    /// Compile `wasm` with `compiler`, once its embedded signature is verified
+    /// to be made by one of `trusted_keys`.
+    ///
+    /// See the [`signing`] module for how modules are signed.
+    ///
+    /// [`signing`]: ../signing/index.html
+    pub fn new_verified(
+        wasm: &[u8],
+        trusted_keys: &[PublicKey],
+        compiler: &dyn Compiler,
+    ) -> error::CompileResult<Self> {
+        signing::verify(wasm, trusted_keys)?;
+        crate::compile_with(wasm, compiler)
+    }
+
     /// Instantiate a WebAssembly module with the provided [`ImportObject`].
     ///
     /// [`ImportObject`]: struct.ImportObject.html
@@ -135,16 +317,131 @@ impl Module {
     /// # }
     /// ```
     pub fn instantiate(&self, import_object: &ImportObject) -> error::Result<Instance> {
-        Instance::new(Arc::clone(&self.inner), import_object)
+        Instance::new(
+            Arc::clone(&self.inner),
+            import_object,
+            None,
+            InstanceConfig::default(),
+        )
+    }
+
+    /// Instantiate a WebAssembly module like [`instantiate`], as `config`
+    /// says, e.g. with typed host data.
+    ///
+    /// [`instantiate`]: #method.instantiate
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::{InstanceConfig, Module};
+    /// # use wasmer_runtime_core::imports;
+    /// # fn instantiate(module: &Module) -> Result<()> {
+    /// struct Counter(u32);
+    ///
+    /// let import_object = imports! {
+    ///     // ...
+    /// };
+    /// let config = InstanceConfig::default().with_data(Counter(0));
+    /// let mut instance = module.instantiate_with_config(&import_object, config)?;
+    /// instance.context_mut().data::<Counter>().0 += 1;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn instantiate_with_config(
+        &self,
+        import_object: &ImportObject,
+        config: InstanceConfig,
+    ) -> error::Result<Instance> {
+        Instance::new(Arc::clone(&self.inner), import_object, None, config)
+    }
+
+    /// Instantiate a WebAssembly module like [`instantiate`], without running
+    /// its start function, so the host can prepare the instance first, e.g.
+    /// by setting its globals or writing to its memory.
+    ///
+    /// The start function runs when [`Instance::run_start`] is called.
+    ///
+    /// [`instantiate`]: #method.instantiate
+    /// [`Instance::run_start`]: struct.Instance.html#method.run_start
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Module;
+    /// # use wasmer_runtime_core::imports;
+    /// # fn instantiate(module: &Module) -> Result<()> {
+    /// let import_object = imports! {
+    ///     // ...
+    /// };
+    /// let mut instance = module.instantiate_unstarted(&import_object)?;
+    /// // ...
+    /// instance.run_start()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn instantiate_unstarted(&self, import_object: &ImportObject) -> error::Result<Instance> {
+        self.instantiate_with_config(import_object, InstanceConfig::default().defer_start())
+    }
+
+    /// Instantiate a WebAssembly module like [`instantiate`], taking the
+    /// instance and its static memories and tables from `pool`.
+    ///
+    /// Fails when the pool has no free slot for the instance or one of its
+    /// memories or tables.  See the [`pool`] module for details.
+    ///
+    /// [`instantiate`]: #method.instantiate
+    /// [`pool`]: ../pool/index.html
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::error::Result;
+    /// # use wasmer_runtime_core::Module;
+    /// # use wasmer_runtime_core::imports;
+    /// # use wasmer_runtime_core::pool::{InstancePool, PoolConfig};
+    /// # fn instantiate(module: &Module) -> Result<()> {
+    /// let pool = InstancePool::new(PoolConfig::default())?;
+    /// let import_object = imports! {
+    ///     // ...
+    /// };
+    /// let instance = module.instantiate_pooled(&import_object, &pool)?;
+    /// // ...
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn instantiate_pooled(
+        &self,
+        import_object: &ImportObject,
+        pool: &InstancePool,
+    ) -> error::Result<Instance> {
+        Instance::new(
+            Arc::clone(&self.inner),
+            import_object,
+            Some(pool),
+            InstanceConfig::default(),
+        )
+    }
+
+    /// Checks that `import_object` provides every import of the module with
+    /// the type it's declared with, as [`instantiate`] does, without
+    /// instantiating the module or running anything.
+    ///
+    /// [`instantiate`]: #method.instantiate
+    pub fn check_imports(&self, import_object: &ImportObject) -> LinkResult<()> {
+        crate::backing::check_imports(&self.inner, import_object)
     }
 
     /// Create a cache artifact from this module.
     pub fn cache(&self) -> Result<Artifact, CacheError> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("cache");
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
         let (backend_metadata, code) = self.inner.cache_gen.generate_cache()?;
         Ok(Artifact::from_parts(
             Box::new(self.inner.info.clone()),
             backend_metadata,
             code,
+            self.inner.cache_gen.shared_code_len(),
         ))
     }
 
@@ -152,6 +449,74 @@ impl Module {
     pub fn info(&self) -> &ModuleInfo {
         &self.inner.info
     }
+
+    /// The contents of the custom sections named `name`, in the order they
+    /// appear in the module.
+    ///
+    /// Custom sections are kept when the module is cached, so this works
+    /// the same for a module loaded from a cache.
+    pub fn custom_sections(&self, name: &str) -> Vec<&[u8]> {
+        self.inner
+            .info
+            .custom_sections
+            .get(name)
+            .map(|sections| sections.iter().map(|section| &section[..]).collect())
+            .unwrap_or_default()
+    }
+
+    /// The name of this module, from its name section.
+    pub fn module_name(&self) -> Option<&str> {
+        self.inner.info.names.module.as_ref().map(String::as_str)
+    }
+
+    /// The name of the function at `index`, from the name section of this
+    /// module, e.g. to print `$fib` rather than `func[12]` in logs.
+    ///
+    /// Names are kept when the module is cached.
+    pub fn function_name(&self, index: FuncIndex) -> Option<&str> {
+        self.inner
+            .info
+            .names
+            .functions
+            .get(&index)
+            .map(String::as_str)
+    }
+
+    /// The names of the locals, parameters included, of the function at
+    /// `index` from the name section of this module, sorted by local index.
+    pub fn local_names(&self, index: FuncIndex) -> Vec<(u32, &str)> {
+        let mut names: Vec<_> = self
+            .inner
+            .info
+            .names
+            .locals
+            .get(&index)
+            .map(|locals| {
+                locals
+                    .iter()
+                    .map(|(local, name)| (*local, name.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// How long each phase of the compilation of this module took, and what
+    /// it produced, e.g. to compare backends on a workload.
+    ///
+    /// Returns `None` if the module was loaded from a cache.
+    pub fn compile_info(&self) -> Option<&CompileInfo> {
+        self.inner.compile_info.as_ref()
+    }
+
+    /// A reference to this module which doesn't keep its code alive, e.g. to
+    /// find it again while other clones of it are still in use.
+    pub fn downgrade(&self) -> WeakModule {
+        WeakModule {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
 }
 
 impl Clone for Module {
@@ -162,6 +527,20 @@ impl Clone for Module {
     }
 }
 
+/// A reference to a [`Module`] which doesn't keep it alive, created with
+/// [`Module::downgrade`].
+#[derive(Clone)]
+pub struct WeakModule {
+    inner: Weak<ModuleInner>,
+}
+
+impl WeakModule {
+    /// The module, if one of its clones is still alive.
+    pub fn upgrade(&self) -> Option<Module> {
+        self.inner.upgrade().map(Module::new)
+    }
+}
+
 impl ModuleInner {}
 
 #[doc(hidden)]
@@ -266,16 +645,101 @@ impl<K: TypedIndex> StringTableBuilder<K> {
 
         StringTable {
             table,
-            buffer: self.buffer,
+            buffer: intern(&self.buffer),
+        }
+    }
+}
+
+lazy_static! {
+    /// The live string table buffers, by hash.
+    static ref INTERNED_BUFFERS: Mutex<HashMap<u64, Vec<Weak<str>>>> = Mutex::new(HashMap::new());
+}
+
+/// Get a buffer with the contents of `buffer`, shared with the other string
+/// tables having the same contents.
+///
+/// Modules importing the same host functions, or loaded many times from a
+/// cache, usually have the same string tables, which are then only kept
+/// once in memory.
+fn intern(buffer: &str) -> InternedBuffer {
+    let mut hasher = DefaultHasher::new();
+    buffer.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    let mut interned = INTERNED_BUFFERS.lock();
+    let buffers = interned.entry(hash).or_insert_with(Vec::new);
+    if let Some(shared) = buffers
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|shared| &**shared == buffer)
+    {
+        return InternedBuffer { hash, shared };
+    }
+
+    let shared: Arc<str> = Arc::from(buffer);
+    buffers.push(Arc::downgrade(&shared));
+    InternedBuffer { hash, shared }
+}
+
+/// A string table buffer returned by [`intern`], which is forgotten once the
+/// last string table using it is dropped.
+#[derive(Debug, Clone)]
+struct InternedBuffer {
+    hash: u64,
+    shared: Arc<str>,
+}
+
+impl Deref for InternedBuffer {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.shared
+    }
+}
+
+impl Drop for InternedBuffer {
+    fn drop(&mut self) {
+        // New references are only handed out with the lock held, so the count
+        // can't grow past this check.
+        let mut interned = INTERNED_BUFFERS.lock();
+        if Arc::strong_count(&self.shared) > 1 {
+            return;
+        }
+        if let Some(buffers) = interned.get_mut(&self.hash) {
+            // A `Weak` keeps the allocation alive, so the entry is removed rather
+            // than left to expire.
+            buffers.retain(|weak| {
+                weak.upgrade()
+                    .map_or(false, |shared| !Arc::ptr_eq(&shared, &self.shared))
+            });
+            if buffers.is_empty() {
+                interned.remove(&self.hash);
+            }
         }
     }
 }
 
+impl Serialize for InternedBuffer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.shared)
+    }
+}
+
+impl<'de> Deserialize<'de> for InternedBuffer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let buffer = String::deserialize(deserializer)?;
+        Ok(intern(&buffer))
+    }
+}
+
 /// A map of index to string.
+///
+/// The strings are kept in one buffer, which is shared by the string tables
+/// of all the modules with the same strings.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StringTable<K: TypedIndex> {
     table: Map<K, (u32, u32)>,
-    buffer: String,
+    buffer: InternedBuffer,
 }
 
 impl<K: TypedIndex> StringTable<K> {
@@ -283,7 +747,7 @@ impl<K: TypedIndex> StringTable<K> {
     pub fn new() -> Self {
         Self {
             table: Map::new(),
-            buffer: String::new(),
+            buffer: intern(""),
         }
     }
 
@@ -328,3 +792,45 @@ impl TypedIndex for NameIndex {
         self.0 as usize
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn table(strings: &[&str]) -> StringTable<NameIndex> {
+        let mut builder = StringTableBuilder::new();
+        for s in strings {
+            builder.register(*s);
+        }
+        builder.finish()
+    }
+
+    #[test]
+    fn string_tables_share_buffers() {
+        let first = table(&["fd_write", "proc_exit", "fd_write"]);
+        let second = table(&["fd_write", "proc_exit"]);
+        let other = table(&["proc_exit", "fd_write"]);
+
+        assert!(Arc::ptr_eq(&first.buffer.shared, &second.buffer.shared));
+        assert!(!Arc::ptr_eq(&first.buffer.shared, &other.buffer.shared));
+        assert_eq!(second.get(NameIndex::new(1)), "proc_exit");
+        assert_eq!(other.get(NameIndex::new(1)), "fd_write");
+
+        let bytes = bincode::serialize(&first).unwrap();
+        let loaded: StringTable<NameIndex> = bincode::deserialize(&bytes).unwrap();
+        assert!(Arc::ptr_eq(&first.buffer.shared, &loaded.buffer.shared));
+        assert_eq!(loaded.get(NameIndex::new(0)), "fd_write");
+    }
+
+    #[test]
+    fn string_table_buffers_are_forgotten() {
+        let first = table(&["string_table_buffers_are_forgotten"]);
+        let second = first.clone();
+        let hash = first.buffer.hash;
+
+        drop(first);
+        assert!(INTERNED_BUFFERS.lock().contains_key(&hash));
+        drop(second);
+        assert!(!INTERNED_BUFFERS.lock().contains_key(&hash));
+    }
+}
@@ -20,6 +20,7 @@ use crate::{
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use wasmparser::{
     BinaryReaderError, ExternalKind, FuncType, ImportSectionEntryType, Operator, Type as WpType,
     WasmDecoder,
@@ -50,6 +51,9 @@ impl From<BinaryReaderError> for LoadError {
 
 /// Read wasm binary into module data using the given backend, module code generator, middlewares,
 /// and compiler configuration.
+///
+/// Also returns the time spent translating function bodies, which are fed to the function code
+/// generators as they are decoded.
 pub fn read_module<
     MCG: ModuleCodeGenerator<FCG, RM, E>,
     FCG: FunctionCodeGenerator<E>,
@@ -61,7 +65,7 @@ pub fn read_module<
     mcg: &mut MCG,
     middlewares: &mut MiddlewareChain,
     compiler_config: &CompilerConfig,
-) -> Result<Arc<RwLock<ModuleInfo>>, LoadError> {
+) -> Result<(Arc<RwLock<ModuleInfo>>, Duration), LoadError> {
     mcg.feed_compiler_config(compiler_config)
         .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
     let info = Arc::new(RwLock::new(ModuleInfo {
@@ -91,6 +95,15 @@ pub fn read_module<
         em_symbol_map: compiler_config.symbol_map.clone(),
 
         custom_sections: HashMap::new(),
+        names: Default::default(),
+
+        triple: compiler_config.triple.clone(),
+        cpu_features: Some(compiler_config.required_cpu_features(backend))
+            .filter(|features| !features.is_empty())
+            .map(|features| features.to_string()),
+        no_signals: compiler_config.no_signals,
+        metered: false,
+        track_state: compiler_config.track_state,
     }));
 
     let mut parser = wasmparser::ValidatingParser::new(
@@ -102,6 +115,7 @@ pub fn read_module<
     let mut name_builder = Some(StringTableBuilder::new());
     let mut func_count: usize = 0;
     let mut mcg_info_fed = false;
+    let mut codegen_time = Duration::new(0, 0);
 
     loop {
         use wasmparser::ParserState;
@@ -149,12 +163,12 @@ pub fn read_module<
                             memory_ty.limits.maximum.map(|max| Pages(max)),
                             memory_ty.shared,
                         )
-                        .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
+                        .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?
+                        .with_style(compiler_config.memory_style);
 
-                        info.write()
-                            .unwrap()
-                            .imported_memories
-                            .push((import_name, mem_desc));
+                        let mut info = info.write().unwrap();
+                        info.imported_memories.push((import_name, mem_desc));
+                        check_memory_count(&info)?;
                     }
                     ImportSectionEntryType::Global(global_ty) => {
                         let global_desc = GlobalDescriptor {
@@ -187,9 +201,12 @@ pub fn read_module<
                     memory_ty.limits.maximum.map(|max| Pages(max)),
                     memory_ty.shared,
                 )
-                .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
+                .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?
+                .with_style(compiler_config.memory_style);
 
-                info.write().unwrap().memories.push(mem_desc);
+                let mut info = info.write().unwrap();
+                info.memories.push(mem_desc);
+                check_memory_count(&info)?;
             }
             ParserState::ExportSectionEntry { field, kind, index } => {
                 let export_index = match kind {
@@ -221,6 +238,7 @@ pub fn read_module<
                     mcg.check_precondition(&info.read().unwrap())
                         .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
                 }
+                let body_start = Instant::now();
 
                 let fcg = mcg
                     .next_function(Arc::clone(&info))
@@ -292,6 +310,7 @@ pub fn read_module<
                 fcg.finalize()
                     .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
                 func_count = func_count.wrapping_add(1);
+                codegen_time += body_start.elapsed();
             }
             ParserState::BeginActiveElementSectionEntry(table_index) => {
                 let table_index = TableIndex::new(table_index as usize);
@@ -399,7 +418,8 @@ pub fn read_module<
             _ => {}
         }
     }
-    Ok(info)
+    info.write().unwrap().metered = middlewares.is_metered();
+    Ok((info, codegen_time))
 }
 
 /// Convert given `WpType` to `Type`.
@@ -430,6 +450,19 @@ pub fn type_to_wp_type(ty: Type) -> WpType {
     }
 }
 
+/// Rejects modules with more than one memory, imported or local: the backends only
+/// address memory 0.
+fn check_memory_count(info: &ModuleInfo) -> Result<(), LoadError> {
+    let count = info.imported_memories.len() + info.memories.len();
+    if count > 1 {
+        return Err(LoadError::Codegen(format!(
+            "the module has {} memories, but multiple memories aren't supported",
+            count
+        )));
+    }
+    Ok(())
+}
+
 fn func_type_to_func_sig(func_ty: &FuncType) -> Result<FuncSig, BinaryReaderError> {
     assert_eq!(func_ty.form, WpType::Func);
 
@@ -0,0 +1,823 @@
+//! Loading compiler backends from dynamic libraries.
+//!
+//! Backends are normally linked in at build time, behind cargo features.  A
+//! backend plugin is a dynamic library exporting a [`PluginDeclaration`],
+//! declared with [`declare_backend_plugin!`], and [`load_backend`] gets its
+//! compiler at run time, e.g. to give an installed `wasmer` binary the LLVM
+//! backend.
+//!
+//! The plugin links its own copy of `wasmer-runtime-core`, so only plain data
+//! and `extern "C"` functions cross the boundary: the plugin compiles a module
+//! and returns its `ModuleInfo`, serialized, and the entry points of its
+//! functions and trampolines.  The host's runtime builds the module from them
+//! and creates its instances, memories and tables, so none of them are ever
+//! seen by the plugin's copy of the runtime.  The trampolines handle traps the
+//! way their backend does when it's linked in, e.g. with LLVM's own signal
+//! handler.
+//!
+//! Errors cross the boundary as a [`PluginTrap`], a code and a message, never
+//! as Rust values.  The error a host function traps with stays on the host's
+//! side while the trap unwinds through the module's code, and the functions
+//! it unwinds through have the `"C-unwind"` ABI, on the rustc versions that
+//! have it.
+//!
+//! The declaration starts with the plugin ABI version, checked before
+//! anything else is read, followed by the versions of `wasmer-runtime-core`,
+//! whose `ModuleInfo` and `vm` structures are shared, and of rustc, which
+//! must all match the host's.
+//!
+//! Modules compiled by a plugin can't be cached.
+//!
+//! # Usage:
+//!
+//! In the plugin, a `cdylib` crate:
+//!
+//! ```ignore
+//! wasmer_runtime_core::declare_backend_plugin!("llvm", LLVMCompiler::new());
+//! ```
+//!
+//! [`declare_backend_plugin!`]: ../macro.declare_backend_plugin.html
+
+use crate::{
+    backend::{Backend, CacheGen, Compiler, CompilerConfig, Features, RunnableModule, Token},
+    cache::{Artifact, Error as CacheError},
+    error::{CompileError, CompileResult},
+    module::{ModuleInfo, ModuleInner},
+    structures::TypedIndex,
+    sys::Memory,
+    typed_func::{Invoke, Trampoline, Wasm, WasmTrapInfo},
+    types::{LocalFuncIndex, SigIndex},
+    vm,
+};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    ffi::CStr,
+    fmt,
+    mem::{self, MaybeUninit},
+    os::raw::{c_char, c_void},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr::{self, NonNull},
+    slice,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// The version of the layout of [`PluginDeclaration`] and of the types and
+/// functions it refers to.
+pub const PLUGIN_ABI_VERSION: u32 = 3;
+
+/// The code of a [`PluginTrap`] for the error a host function returned,
+/// which the host keeps.
+pub const PLUGIN_TRAP_HOST_ERROR: u32 = 0xffff_ffff;
+
+/// The code of a [`PluginTrap`] for any other error, described by its
+/// message.
+pub const PLUGIN_TRAP_ERROR: u32 = 0xffff_fffe;
+
+/// The name of the [`PluginDeclaration`] a plugin exports.
+pub const PLUGIN_SYMBOL: &str = "wasmer_backend_plugin";
+
+#[doc(hidden)]
+pub const CORE_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+#[doc(hidden)]
+pub const RUSTC_VERSION: &str = concat!(env!("WASMER_RUSTC_VERSION"), "\0");
+
+/// Declares the functions traps unwind through, and their types, with the
+/// `"C-unwind"` ABI, which rustc has since 1.71; see `build.rs`.
+#[cfg(c_unwind)]
+macro_rules! extern_unwind {
+    ($(#[$attr:meta])* pub type $name:ident = unsafe fn($($args:tt)*) -> !;) => {
+        $(#[$attr])* pub type $name = unsafe extern "C-unwind" fn($($args)*) -> !;
+    };
+    ($(#[$attr:meta])* pub unsafe fn $name:ident($($args:tt)*) -> ! $body:block) => {
+        $(#[$attr])* pub unsafe extern "C-unwind" fn $name($($args)*) -> ! $body
+    };
+}
+
+/// Declares the functions traps unwind through, and their types, with the
+/// `"C"` ABI, which older rustc versions let unwind.
+#[cfg(not(c_unwind))]
+macro_rules! extern_unwind {
+    ($(#[$attr:meta])* pub type $name:ident = unsafe fn($($args:tt)*) -> !;) => {
+        $(#[$attr])* pub type $name = unsafe extern "C" fn($($args)*) -> !;
+    };
+    ($(#[$attr:meta])* pub unsafe fn $name:ident($($args:tt)*) -> ! $body:block) => {
+        $(#[$attr])* pub unsafe extern "C" fn $name($($args)*) -> ! $body
+    };
+}
+
+extern_unwind! {
+    /// Trap with the error of `code` and the `message_len` bytes of
+    /// `message`, from a host function called by a module's code.  The trap
+    /// unwinds to the plugin's `invoke` the code was called by.
+    pub type EarlyTrap = unsafe fn(
+        module: *const c_void,
+        code: u32,
+        message: *const u8,
+        message_len: usize,
+    ) -> !;
+}
+
+/// The declaration a backend plugin exports as `wasmer_backend_plugin`.
+///
+/// The module handles are opaque to the host, and only passed back to the
+/// plugin's functions.
+#[repr(C)]
+pub struct PluginDeclaration {
+    /// The plugin's [`PLUGIN_ABI_VERSION`].
+    pub abi_version: u32,
+    /// The version of `wasmer-runtime-core` the plugin was built with, as a
+    /// nul-terminated string.
+    pub core_version: *const c_char,
+    /// The version of rustc the plugin was built with, as a nul-terminated
+    /// string.
+    pub rustc_version: *const c_char,
+    /// The name of the plugin's backend, e.g. `llvm`, as a nul-terminated
+    /// string.
+    pub backend: *const c_char,
+    /// Compile `wasm_len` bytes of wasm with `features`.  On success, returns
+    /// `true`, stores the module's handle in `module` and its `ModuleInfo`,
+    /// serialized with bincode, in `output`; on failure, returns `false` and
+    /// stores an error message in `output`.
+    pub compile: unsafe extern "C" fn(
+        wasm: *const u8,
+        wasm_len: usize,
+        features: *const PluginFeatures,
+        module: *mut *mut c_void,
+        output: *mut PluginBytes,
+    ) -> bool,
+    /// The code of a local function of a module, or null.
+    pub get_func:
+        unsafe extern "C" fn(module: *const c_void, local_func_index: u32) -> *const vm::Func,
+    /// Store the trampoline of a signature of a module in `trampoline`, or
+    /// return `false` if there isn't any.
+    pub get_trampoline: unsafe extern "C" fn(
+        module: *const c_void,
+        sig_index: u32,
+        trampoline: *mut PluginTrampoline,
+    ) -> bool,
+    /// Call `func` with `trampoline`, returned by `get_trampoline`.  On
+    /// failure, returns `false` and stores the error in `trap`.
+    pub invoke: unsafe extern "C" fn(
+        trampoline: *const PluginTrampoline,
+        vmctx: *mut vm::Ctx,
+        func: NonNull<vm::Func>,
+        args: *const u64,
+        rets: *mut u64,
+        trap: *mut PluginTrap,
+    ) -> bool,
+    /// See [`EarlyTrap`].
+    pub early_trap: EarlyTrap,
+    /// Free a module.
+    pub free_module: unsafe extern "C" fn(module: *mut c_void),
+    /// Free bytes returned by `compile`.
+    pub free_bytes: unsafe extern "C" fn(bytes: PluginBytes),
+}
+
+// Manually implemented because the declaration is a static holding pointers to static strings
+unsafe impl Sync for PluginDeclaration {}
+
+/// Bytes allocated by a plugin, which only the plugin frees.
+#[repr(C)]
+pub struct PluginBytes {
+    /// The start of the bytes.
+    pub ptr: *mut u8,
+    /// The number of bytes.
+    pub len: usize,
+    /// The capacity of the allocation.
+    pub capacity: usize,
+}
+
+impl PluginBytes {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let mut bytes = mem::ManuallyDrop::new(bytes);
+        Self {
+            ptr: bytes.as_mut_ptr(),
+            len: bytes.len(),
+            capacity: bytes.capacity(),
+        }
+    }
+
+    unsafe fn into_vec(self) -> Vec<u8> {
+        Vec::from_raw_parts(self.ptr, self.len, self.capacity)
+    }
+}
+
+/// An error a module's code failed with, as `invoke` returns it.
+#[repr(C)]
+pub struct PluginTrap {
+    /// The discriminant of a `WasmTrapInfo`, [`PLUGIN_TRAP_HOST_ERROR`] or
+    /// [`PLUGIN_TRAP_ERROR`].
+    pub code: u32,
+    /// A description of the error, in UTF-8.
+    pub message: PluginBytes,
+}
+
+/// The features a plugin compiles a module with, from [`Features`].
+#[repr(C)]
+pub struct PluginFeatures {
+    /// [`Features::simd`]
+    pub simd: bool,
+    /// [`Features::threads`]
+    pub threads: bool,
+    /// [`Features::bulk_memory`]
+    pub bulk_memory: bool,
+    /// [`Features::multi_value`]
+    pub multi_value: bool,
+    /// [`Features::deny_floats`]
+    pub deny_floats: bool,
+    /// [`Features::deterministic`]
+    pub deterministic: bool,
+}
+
+impl From<&Features> for PluginFeatures {
+    fn from(features: &Features) -> Self {
+        Self {
+            simd: features.simd,
+            threads: features.threads,
+            bulk_memory: features.bulk_memory,
+            multi_value: features.multi_value,
+            deny_floats: features.deny_floats,
+            deterministic: features.deterministic,
+        }
+    }
+}
+
+impl From<&PluginFeatures> for Features {
+    fn from(features: &PluginFeatures) -> Self {
+        Self {
+            simd: features.simd,
+            threads: features.threads,
+            bulk_memory: features.bulk_memory,
+            multi_value: features.multi_value,
+            deny_floats: features.deny_floats,
+            deterministic: features.deterministic,
+        }
+    }
+}
+
+/// The trampoline of a signature, the parts of a [`Wasm`], only called by
+/// the plugin's `invoke`.
+#[repr(C)]
+pub struct PluginTrampoline {
+    /// Calls a function of the signature.
+    pub trampoline: Trampoline,
+    /// Calls the trampoline, catching traps.
+    pub invoke: Invoke,
+    /// The extra argument of `invoke`, or null.
+    pub invoke_env: *mut c_void,
+}
+
+/// Declare the backend of a plugin: the name of its `Backend`, e.g. `"llvm"`,
+/// and an expression creating its `Compiler`.  See the [`plugin`] module.
+///
+/// [`plugin`]: plugin/index.html
+#[macro_export]
+macro_rules! declare_backend_plugin {
+    ($backend:literal, $compiler:expr) => {
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static wasmer_backend_plugin: $crate::plugin::PluginDeclaration =
+            $crate::plugin::PluginDeclaration {
+                abi_version: $crate::plugin::PLUGIN_ABI_VERSION,
+                core_version: $crate::plugin::CORE_VERSION.as_ptr() as *const _,
+                rustc_version: $crate::plugin::RUSTC_VERSION.as_ptr() as *const _,
+                backend: concat!($backend, "\0").as_ptr() as *const _,
+                compile: {
+                    unsafe extern "C" fn compile(
+                        wasm: *const u8,
+                        wasm_len: usize,
+                        features: *const $crate::plugin::PluginFeatures,
+                        module: *mut *mut ::std::os::raw::c_void,
+                        output: *mut $crate::plugin::PluginBytes,
+                    ) -> bool {
+                        $crate::plugin::export::compile(
+                            &$compiler, wasm, wasm_len, features, module, output,
+                        )
+                    }
+                    compile
+                },
+                get_func: $crate::plugin::export::get_func,
+                get_trampoline: $crate::plugin::export::get_trampoline,
+                invoke: $crate::plugin::export::invoke,
+                early_trap: $crate::plugin::export::early_trap,
+                free_module: $crate::plugin::export::free_module,
+                free_bytes: $crate::plugin::export::free_bytes,
+            };
+    };
+}
+
+/// The functions of the declaration, on the plugin's side.
+#[doc(hidden)]
+pub mod export {
+    use super::*;
+
+    pub unsafe fn compile(
+        compiler: &dyn Compiler,
+        wasm: *const u8,
+        wasm_len: usize,
+        features: *const PluginFeatures,
+        module: *mut *mut c_void,
+        output: *mut PluginBytes,
+    ) -> bool {
+        let wasm = slice::from_raw_parts(wasm, wasm_len);
+        let config = CompilerConfig {
+            features: Features::from(&*features),
+            ..Default::default()
+        };
+        let compiled = panic::catch_unwind(AssertUnwindSafe(|| {
+            let inner = compiler
+                .compile(wasm, config, Token::generate())
+                .map_err(|e| format!("{:?}", e))?;
+            let info = bincode::serialize(&inner.info).map_err(|e| e.to_string())?;
+            Ok::<_, String>((inner, info))
+        }))
+        .unwrap_or_else(|_| Err("the backend panicked".to_string()));
+
+        let (result, bytes) = match compiled {
+            Ok((inner, info)) => {
+                *module = Box::into_raw(Box::new(inner)) as *mut c_void;
+                (true, info)
+            }
+            Err(msg) => (false, msg.into_bytes()),
+        };
+        *output = PluginBytes::from_vec(bytes);
+        result
+    }
+
+    pub unsafe extern "C" fn get_func(
+        module: *const c_void,
+        local_func_index: u32,
+    ) -> *const vm::Func {
+        let module = &*(module as *const ModuleInner);
+        module
+            .runnable_module
+            .get_func(&module.info, LocalFuncIndex::new(local_func_index as usize))
+            .map_or(ptr::null(), |func| func.as_ptr() as *const vm::Func)
+    }
+
+    pub unsafe extern "C" fn get_trampoline(
+        module: *const c_void,
+        sig_index: u32,
+        trampoline: *mut PluginTrampoline,
+    ) -> bool {
+        let module = &*(module as *const ModuleInner);
+        match module
+            .runnable_module
+            .get_trampoline(&module.info, SigIndex::new(sig_index as usize))
+        {
+            Some(wasm) => {
+                *trampoline = PluginTrampoline {
+                    trampoline: wasm.trampoline,
+                    invoke: wasm.invoke,
+                    invoke_env: wasm.invoke_env.map_or(ptr::null_mut(), NonNull::as_ptr),
+                };
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The error a module's code traps with from `early_trap`.
+    struct EarlyTrapError {
+        code: u32,
+        message: String,
+    }
+
+    pub unsafe extern "C" fn invoke(
+        trampoline: *const PluginTrampoline,
+        vmctx: *mut vm::Ctx,
+        func: NonNull<vm::Func>,
+        args: *const u64,
+        rets: *mut u64,
+        trap: *mut PluginTrap,
+    ) -> bool {
+        let trampoline = &*trampoline;
+        let mut trap_info = WasmTrapInfo::Unknown;
+        let mut user_error = None;
+        if (trampoline.invoke)(
+            trampoline.trampoline,
+            vmctx,
+            func,
+            args,
+            rets,
+            &mut trap_info,
+            &mut user_error,
+            NonNull::new(trampoline.invoke_env),
+        ) {
+            return true;
+        }
+
+        let (code, message) = match user_error.map(|data| data.downcast::<EarlyTrapError>()) {
+            Some(Ok(error)) => (error.code, error.message),
+            Some(Err(data)) => (
+                PLUGIN_TRAP_ERROR,
+                if let Some(s) = data.downcast_ref::<String>() {
+                    s.clone()
+                } else if let Some(s) = data.downcast_ref::<&str>() {
+                    s.to_string()
+                } else {
+                    "unknown error".to_string()
+                },
+            ),
+            None => (trap_info as u32, trap_info.to_string()),
+        };
+        *trap = PluginTrap {
+            code,
+            message: PluginBytes::from_vec(message.into_bytes()),
+        };
+        false
+    }
+
+    extern_unwind! {
+        pub unsafe fn early_trap(
+            module: *const c_void,
+            code: u32,
+            message: *const u8,
+            message_len: usize,
+        ) -> ! {
+            let module = &*(module as *const ModuleInner);
+            let message = slice::from_raw_parts(message, message_len);
+            module.runnable_module.do_early_trap(Box::new(EarlyTrapError {
+                code,
+                message: String::from_utf8_lossy(message).into_owned(),
+            }))
+        }
+    }
+
+    pub unsafe extern "C" fn free_module(module: *mut c_void) {
+        drop(Box::from_raw(module as *mut ModuleInner));
+    }
+
+    pub unsafe extern "C" fn free_bytes(bytes: PluginBytes) {
+        drop(bytes.into_vec());
+    }
+}
+
+/// A backend loaded from a plugin.
+pub struct BackendPlugin {
+    /// The backend the compiler implements.
+    pub backend: Backend,
+    /// The backend's compiler.
+    pub compiler: Box<dyn Compiler>,
+}
+
+/// Compiles with a plugin.
+struct PluginCompiler {
+    declaration: &'static PluginDeclaration,
+}
+
+/// Copy bytes returned by a plugin, and free them.
+unsafe fn take_bytes(declaration: &PluginDeclaration, bytes: PluginBytes) -> Vec<u8> {
+    let copy = slice::from_raw_parts(bytes.ptr, bytes.len).to_vec();
+    (declaration.free_bytes)(bytes);
+    copy
+}
+
+const NOT_CACHEABLE: &str = "modules compiled by a backend plugin can't be cached";
+
+impl Compiler for PluginCompiler {
+    fn compile(
+        &self,
+        wasm: &[u8],
+        comp_conf: CompilerConfig,
+        _: Token,
+    ) -> CompileResult<ModuleInner> {
+        let features = PluginFeatures::from(&comp_conf.features);
+        let mut module = ptr::null_mut();
+        let mut output = MaybeUninit::uninit();
+        let (compiled, output) = unsafe {
+            let compiled = (self.declaration.compile)(
+                wasm.as_ptr(),
+                wasm.len(),
+                &features,
+                &mut module,
+                output.as_mut_ptr(),
+            );
+            (compiled, take_bytes(self.declaration, output.assume_init()))
+        };
+        if !compiled {
+            return Err(CompileError::InternalError {
+                msg: String::from_utf8_lossy(&output).into_owned(),
+            });
+        }
+
+        let runnable_module = PluginModule {
+            declaration: self.declaration,
+            module,
+            invoke_envs: Mutex::new(HashMap::new()),
+        };
+        let info: ModuleInfo =
+            bincode::deserialize(&output).map_err(|e| CompileError::InternalError {
+                msg: format!("the plugin returned an invalid module info: {}", e),
+            })?;
+        Ok(ModuleInner {
+            runnable_module: Arc::new(Box::new(runnable_module)),
+            cache_gen: Box::new(PluginCacheGen),
+            info,
+            compile_info: None,
+        })
+    }
+
+    unsafe fn from_cache(&self, _: Artifact, _: Token) -> Result<ModuleInner, CacheError> {
+        Err(CacheError::Unknown(NOT_CACHEABLE.to_string()))
+    }
+}
+
+/// A module compiled by a plugin.
+struct PluginModule {
+    declaration: &'static PluginDeclaration,
+    module: *mut c_void,
+    /// The `invoke_env` of the trampolines returned so far, by signature.
+    invoke_envs: Mutex<HashMap<SigIndex, Box<PluginInvokeEnv>>>,
+}
+
+/// What [`invoke`] calls a trampoline of a plugin with.
+struct PluginInvokeEnv {
+    declaration: &'static PluginDeclaration,
+    trampoline: PluginTrampoline,
+}
+
+thread_local! {
+    /// The error a host function trapped with, until the `invoke` it unwinds
+    /// to returns it.
+    static HOST_ERROR: RefCell<Option<Box<dyn Any + Send>>> = RefCell::new(None);
+}
+
+/// The `Invoke` of the trampolines of plugins: calls the plugin's `invoke`,
+/// and turns the `PluginTrap` it fails with back into a trap or an error.
+unsafe extern "C" fn invoke(
+    _: Trampoline,
+    vmctx: *mut vm::Ctx,
+    func: NonNull<vm::Func>,
+    args: *const u64,
+    rets: *mut u64,
+    trap_info: *mut WasmTrapInfo,
+    user_error: *mut Option<Box<dyn Any + Send>>,
+    invoke_env: Option<NonNull<c_void>>,
+) -> bool {
+    let env = match invoke_env {
+        Some(env) => &*(env.as_ptr() as *const PluginInvokeEnv),
+        None => return false,
+    };
+    let mut trap = MaybeUninit::<PluginTrap>::uninit();
+    if (env.declaration.invoke)(&env.trampoline, vmctx, func, args, rets, trap.as_mut_ptr()) {
+        return true;
+    }
+
+    let trap = trap.assume_init();
+    let message = take_bytes(env.declaration, trap.message);
+    let message = String::from_utf8_lossy(&message).into_owned();
+    match trap.code {
+        PLUGIN_TRAP_HOST_ERROR => {
+            let error = HOST_ERROR.with(|error| error.borrow_mut().take());
+            *user_error = Some(error.unwrap_or_else(|| Box::new(message)));
+        }
+        PLUGIN_TRAP_ERROR => *user_error = Some(Box::new(message)),
+        code => *trap_info = WasmTrapInfo::from_code(code),
+    }
+    false
+}
+
+// Manually implemented because the plugin's module, like the runnable modules
+// of the backends, isn't modified once compiled, and its trampolines are
+// behind a lock
+unsafe impl Send for PluginModule {}
+unsafe impl Sync for PluginModule {}
+
+impl Drop for PluginModule {
+    fn drop(&mut self) {
+        unsafe { (self.declaration.free_module)(self.module) }
+    }
+}
+
+impl RunnableModule for PluginModule {
+    fn get_func(
+        &self,
+        _: &ModuleInfo,
+        local_func_index: LocalFuncIndex,
+    ) -> Option<NonNull<vm::Func>> {
+        let func =
+            unsafe { (self.declaration.get_func)(self.module, local_func_index.index() as u32) };
+        NonNull::new(func as *mut vm::Func)
+    }
+
+    fn get_trampoline(&self, _: &ModuleInfo, sig_index: SigIndex) -> Option<Wasm> {
+        let mut invoke_envs = self.invoke_envs.lock().unwrap();
+        let env = match invoke_envs.entry(sig_index) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut trampoline = MaybeUninit::<PluginTrampoline>::uninit();
+                let trampoline = unsafe {
+                    if !(self.declaration.get_trampoline)(
+                        self.module,
+                        sig_index.index() as u32,
+                        trampoline.as_mut_ptr(),
+                    ) {
+                        return None;
+                    }
+                    trampoline.assume_init()
+                };
+                entry.insert(Box::new(PluginInvokeEnv {
+                    declaration: self.declaration,
+                    trampoline,
+                }))
+            }
+        };
+        // The environment is boxed, so it stays put for as long as the module.
+        let invoke_env = &mut **env as *mut PluginInvokeEnv as *mut c_void;
+        unsafe {
+            Some(Wasm::from_raw_parts(
+                env.trampoline.trampoline,
+                invoke,
+                NonNull::new(invoke_env),
+            ))
+        }
+    }
+
+    unsafe fn do_early_trap(&self, data: Box<dyn Any + Send>) -> ! {
+        HOST_ERROR.with(|error| *error.borrow_mut() = Some(data));
+        let message = "a host function returned an error";
+        (self.declaration.early_trap)(
+            self.module,
+            PLUGIN_TRAP_HOST_ERROR,
+            message.as_ptr(),
+            message.len(),
+        )
+    }
+}
+
+struct PluginCacheGen;
+
+impl CacheGen for PluginCacheGen {
+    fn generate_cache(&self) -> Result<(Box<[u8]>, Memory), CacheError> {
+        Err(CacheError::Unknown(NOT_CACHEABLE.to_string()))
+    }
+}
+
+/// Error returned by [`load_backend`].
+#[derive(Debug)]
+pub enum PluginError {
+    /// The dynamic library couldn't be loaded.
+    Load(String),
+    /// The library doesn't export `wasmer_backend_plugin`.
+    MissingDeclaration,
+    /// The plugin wasn't built with the same plugin ABI, `wasmer-runtime-core`
+    /// and rustc as the host.
+    Incompatible {
+        /// What the host was built with.
+        expected: String,
+        /// What the plugin was built with.
+        found: String,
+    },
+    /// The plugin declares a backend this runtime doesn't know.
+    UnknownBackend(String),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Load(msg) => write!(f, "could not load the plugin: {}", msg),
+            PluginError::MissingDeclaration => {
+                write!(f, "the library does not export `{}`", PLUGIN_SYMBOL)
+            }
+            PluginError::Incompatible { expected, found } => write!(
+                f,
+                "the plugin was built for {}, but this runtime is {}",
+                found, expected
+            ),
+            PluginError::UnknownBackend(backend) => {
+                write!(f, "the plugin's backend, \"{}\", is unknown", backend)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// A description of the ABI, e.g. `ABI 3, wasmer-runtime-core 0.12.0, rustc 1.39.0`.
+fn describe_abi(abi_version: u32, core_version: &str, rustc_version: &str) -> String {
+    format!(
+        "ABI {}, wasmer-runtime-core {}, rustc {}",
+        abi_version, core_version, rustc_version
+    )
+}
+
+/// Load the backend plugin at `path`.
+///
+/// The library is never unloaded, since the modules its compiler creates
+/// run its code.
+///
+/// # Safety
+///
+/// Loading a library runs its initializers, and the plugin's declaration
+/// and the code it compiles are trusted to be what they claim.
+pub unsafe fn load_backend(path: &Path) -> Result<BackendPlugin, PluginError> {
+    let library = sys::open(path).map_err(PluginError::Load)?;
+    let declaration = sys::symbol(library, PLUGIN_SYMBOL) as *const PluginDeclaration;
+    if declaration.is_null() {
+        return Err(PluginError::MissingDeclaration);
+    }
+    let declaration: &'static PluginDeclaration = &*declaration;
+
+    let expected = describe_abi(
+        PLUGIN_ABI_VERSION,
+        env!("CARGO_PKG_VERSION"),
+        env!("WASMER_RUSTC_VERSION"),
+    );
+    // The rest of the declaration can only be read once the ABI matches.
+    if declaration.abi_version != PLUGIN_ABI_VERSION {
+        return Err(PluginError::Incompatible {
+            expected,
+            found: format!("ABI {}", declaration.abi_version),
+        });
+    }
+    let found = describe_abi(
+        declaration.abi_version,
+        &CStr::from_ptr(declaration.core_version).to_string_lossy(),
+        &CStr::from_ptr(declaration.rustc_version).to_string_lossy(),
+    );
+    if expected != found {
+        return Err(PluginError::Incompatible { expected, found });
+    }
+
+    let backend = CStr::from_ptr(declaration.backend).to_string_lossy();
+    let backend = Backend::from_str(&backend)
+        .map_err(|_| PluginError::UnknownBackend(backend.into_owned()))?;
+    Ok(BackendPlugin {
+        backend,
+        compiler: Box::new(PluginCompiler { declaration }),
+    })
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::c_void;
+    use nix::libc;
+    use std::{ffi::CStr, ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+    pub unsafe fn open(path: &Path) -> Result<*mut c_void, String> {
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+        let library = libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if library.is_null() {
+            let error = libc::dlerror();
+            return Err(if error.is_null() {
+                "unknown error".to_string()
+            } else {
+                CStr::from_ptr(error).to_string_lossy().into_owned()
+            });
+        }
+        Ok(library)
+    }
+
+    pub unsafe fn symbol(library: *mut c_void, name: &str) -> *mut c_void {
+        match CString::new(name) {
+            Ok(name) => libc::dlsym(library, name.as_ptr()),
+            Err(_) => std::ptr::null_mut(),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::c_void;
+    use std::{ffi::CString, io, iter, os::windows::ffi::OsStrExt, path::Path};
+    use winapi::um::libloaderapi::{GetProcAddress, LoadLibraryW};
+
+    pub unsafe fn open(path: &Path) -> Result<*mut c_void, String> {
+        let path: Vec<u16> = path
+            .as_os_str()
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect();
+        let library = LoadLibraryW(path.as_ptr());
+        if library.is_null() {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(library as *mut c_void)
+    }
+
+    pub unsafe fn symbol(library: *mut c_void, name: &str) -> *mut c_void {
+        match CString::new(name) {
+            Ok(name) => GetProcAddress(library as _, name.as_ptr()) as *mut c_void,
+            Err(_) => std::ptr::null_mut(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missing_library() {
+        match unsafe { load_backend(Path::new("/nonexistent/libwasmer_backend.so")) } {
+            Err(PluginError::Load(_)) => {}
+            _ => panic!("a missing library was loaded"),
+        }
+    }
+}
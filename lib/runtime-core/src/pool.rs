@@ -0,0 +1,271 @@
+//! Pooling allocation of instances and of their memories and tables.
+//!
+//! Every static memory reserves 6GiB of address space when it's created and
+//! unmaps it when it's dropped.  When instances are created and dropped at a
+//! high rate, these system calls dominate the cost of instantiation.
+//!
+//! An [`InstancePool`] preallocates a fixed number of instance, memory and
+//! table slots.  Instances created with [`Module::instantiate_pooled`] take
+//! a slot for themselves, one for each of their static memories and one for
+//! each of their tables, and fail to instantiate when the pool has run out
+//! of slots.  Slots return to the pool when the instance, memory or table is
+//! dropped: memories are reset with `madvise(MADV_DONTNEED)` instead of being
+//! unmapped, and tables keep their allocation.
+//!
+//! Dynamic and shared memories are cheap to create or can outlive the
+//! instance's thread, so they aren't pooled.
+//!
+//! [`Module::instantiate_pooled`]: crate::module::Module::instantiate_pooled
+
+use crate::{
+    error::CreationError,
+    memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
+    sys, vm,
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The number of slots of each kind in an [`InstancePool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// The number of instances that can exist at once.
+    pub instances: usize,
+    /// The number of static memories that can exist at once.  Each one
+    /// reserves 6GiB of address space for as long as the pool exists.
+    pub memories: usize,
+    /// The number of tables that can exist at once.
+    pub tables: usize,
+    /// The number of elements allocated up front for each table.  Tables
+    /// can grow past it.
+    pub table_elements: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            instances: 16,
+            memories: 16,
+            tables: 16,
+            table_elements: 10_000,
+        }
+    }
+}
+
+/// The usage of the slots of one kind in an [`InstancePool`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SlotStats {
+    /// The number of slots.
+    pub capacity: usize,
+    /// The number of slots taken.
+    pub in_use: usize,
+    /// The highest number of slots that were taken at once.
+    pub peak: usize,
+    /// The number of times a slot was requested while all were taken.
+    pub exhausted: usize,
+}
+
+/// The usage of the slots of an [`InstancePool`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Instance slots.
+    pub instances: SlotStats,
+    /// Static memory slots.
+    pub memories: SlotStats,
+    /// Table slots.
+    pub tables: SlotStats,
+}
+
+struct Slots<T> {
+    free: Vec<T>,
+    stats: SlotStats,
+}
+
+impl<T> Slots<T> {
+    fn new(free: Vec<T>) -> Self {
+        let stats = SlotStats {
+            capacity: free.len(),
+            ..SlotStats::default()
+        };
+        Self { free, stats }
+    }
+
+    fn take(&mut self, kind: &'static str) -> Result<T, CreationError> {
+        match self.free.pop() {
+            Some(slot) => {
+                self.stats.in_use += 1;
+                self.stats.peak = self.stats.peak.max(self.stats.in_use);
+                Ok(slot)
+            }
+            None => {
+                self.stats.exhausted += 1;
+                Err(CreationError::PoolExhausted(kind))
+            }
+        }
+    }
+
+    fn release(&mut self, slot: Option<T>) {
+        self.stats.in_use -= 1;
+        match slot {
+            Some(slot) => self.free.push(slot),
+            // the slot couldn't be recycled
+            None => self.stats.capacity -= 1,
+        }
+    }
+}
+
+struct PoolInner {
+    instances: Mutex<Slots<()>>,
+    memories: Mutex<Slots<sys::Memory>>,
+    tables: Mutex<Slots<Vec<vm::Anyfunc>>>,
+    config: PoolConfig,
+}
+
+// Manually implemented because the free tables are `vm::Anyfunc`s, which contain raw pointers;
+// they are always cleared before being stored.
+unsafe impl Send for PoolInner {}
+unsafe impl Sync for PoolInner {}
+
+/// Preallocated slots for instances and their memories and tables, see the
+/// module documentation.
+///
+/// Clones of an `InstancePool` share its slots.
+#[derive(Clone)]
+pub struct InstancePool {
+    inner: Arc<PoolInner>,
+}
+
+impl InstancePool {
+    /// Create a pool, reserving all of its memories and allocating all of
+    /// its tables.
+    pub fn new(config: PoolConfig) -> Result<Self, CreationError> {
+        let memories = (0..config.memories)
+            .map(|_| sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| CreationError::UnableToCreateMemory)?;
+        let tables = (0..config.tables)
+            .map(|_| Vec::with_capacity(config.table_elements))
+            .collect();
+
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                instances: Mutex::new(Slots::new(vec![(); config.instances])),
+                memories: Mutex::new(Slots::new(memories)),
+                tables: Mutex::new(Slots::new(tables)),
+                config,
+            }),
+        })
+    }
+
+    /// The configuration the pool was created with.
+    pub fn config(&self) -> PoolConfig {
+        self.inner.config
+    }
+
+    /// The current usage of the pool's slots.
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            instances: self.inner.instances.lock().stats,
+            memories: self.inner.memories.lock().stats,
+            tables: self.inner.tables.lock().stats,
+        }
+    }
+
+    pub(crate) fn take_instance(&self) -> Result<InstanceSlot, CreationError> {
+        self.inner.instances.lock().take("instance")?;
+        Ok(InstanceSlot { pool: self.clone() })
+    }
+
+    /// Take an inaccessible static memory reservation.
+    pub(crate) fn take_memory(&self) -> Result<sys::Memory, CreationError> {
+        self.inner.memories.lock().take("memory")
+    }
+
+    /// Return a memory whose first `accessible` bytes may have been used.
+    pub(crate) fn release_memory(&self, mut memory: sys::Memory, accessible: usize) {
+        let memory = match unsafe { memory.reset(accessible) } {
+            Ok(()) => Some(memory),
+            Err(_) => None,
+        };
+        self.inner.memories.lock().release(memory);
+    }
+
+    /// Take an empty table allocation.
+    pub(crate) fn take_table(&self) -> Result<Vec<vm::Anyfunc>, CreationError> {
+        self.inner.tables.lock().take("table")
+    }
+
+    pub(crate) fn release_table(&self, mut table: Vec<vm::Anyfunc>) {
+        table.clear();
+        self.inner.tables.lock().release(Some(table));
+    }
+}
+
+/// The slot taken by a pooled instance, returned to the pool when dropped.
+pub(crate) struct InstanceSlot {
+    pool: InstancePool,
+}
+
+impl Drop for InstanceSlot {
+    fn drop(&mut self) {
+        self.pool.inner.instances.lock().release(Some(()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        memory::Memory,
+        table::Table,
+        types::{ElementType, MemoryDescriptor, TableDescriptor},
+        units::Pages,
+    };
+
+    #[test]
+    fn pooled_memories_and_tables_are_recycled() {
+        let pool = InstancePool::new(PoolConfig {
+            instances: 1,
+            memories: 1,
+            tables: 1,
+            table_elements: 16,
+        })
+        .unwrap();
+
+        let memory_desc = MemoryDescriptor::new(Pages(1), Some(Pages(2)), false).unwrap();
+        let memory = Memory::new_pooled(memory_desc, &pool).unwrap();
+        memory.view::<u8>()[0].set(42);
+        assert!(Memory::new_pooled(memory_desc, &pool).is_err());
+
+        let table_desc = TableDescriptor {
+            element: ElementType::Anyfunc,
+            minimum: 4,
+            maximum: None,
+        };
+        let table = Table::new_pooled(table_desc, &pool).unwrap();
+        assert_eq!(table.size(), 4);
+
+        let instance = pool.take_instance().unwrap();
+        assert!(pool.take_instance().is_err());
+
+        let stats = pool.stats();
+        for slots in &[stats.instances, stats.memories, stats.tables] {
+            assert_eq!(slots.in_use, 1);
+            assert_eq!(slots.peak, 1);
+        }
+        assert_eq!(stats.instances.exhausted, 1);
+        assert_eq!(stats.memories.exhausted, 1);
+
+        drop((memory, table, instance));
+        assert_eq!(pool.stats().memories.in_use, 0);
+
+        // the recycled memory reads as zero
+        let memory = Memory::new_pooled(memory_desc, &pool).unwrap();
+        assert_eq!(memory.view::<u8>()[0].get(), 0);
+        assert_eq!(pool.stats().memories.capacity, 1);
+
+        // dynamic memories aren't pooled
+        let dynamic_desc = MemoryDescriptor::new(Pages(1), None, false).unwrap();
+        let _dynamic = Memory::new_pooled(dynamic_desc, &pool).unwrap();
+        assert_eq!(pool.stats().memories.in_use, 1);
+    }
+}
@@ -0,0 +1,276 @@
+//! A sampling profiler for wasm code.
+//!
+//! While an instance is being profiled, a background thread periodically
+//! raises the same interrupt that suspends wasm code for tiering and on
+//! `SIGINT`.  When the wasm code of the profiled instance reaches its next
+//! interrupt check, the fault handler walks its wasm call stack with the
+//! frame metadata used for trap backtraces, records it, and lets the code
+//! continue.
+//!
+//! Interrupt checks are emitted at function entries and loop headers, so
+//! samples are taken there: time spent in a long straight-line function is
+//! attributed to it, but time spent in host functions is attributed to the
+//! wasm function that called them once it loops or calls again.
+//!
+//! Only modules compiled by the singlepass backend with
+//! [`CompilerConfig::track_state`] can be profiled.  The interrupt is shared
+//! by all the instances of the process, so profiling can't be combined with
+//! tiering, and other instances running at the same time are briefly
+//! stopped at each sample, without being recorded.
+//!
+//! [`CompilerConfig::track_state`]: ../backend/struct.CompilerConfig.html#structfield.track_state
+
+use crate::{
+    fault::{pop_code_version, push_code_version, set_wasm_interrupt, with_ctx},
+    module::{ExportIndex, ModuleInfo, ModuleInner},
+    state::{CodeVersion, ExecutionStateImage},
+    structures::TypedIndex,
+    types::FuncIndex,
+    vm,
+};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+lazy_static! {
+    /// The samplers of the instances being profiled, by the address of their `vm::Ctx`.
+    static ref SAMPLERS: Mutex<HashMap<usize, Arc<Sampler>>> = Mutex::new(HashMap::new());
+}
+
+/// The number of instances being profiled, to skip the lookup in
+/// `SAMPLERS` on every call otherwise.
+static PROFILED_INSTANCES: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the wasm interrupt was raised to take a sample.
+static SAMPLE_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// The reason an instance couldn't be profiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfilingError {
+    /// The module wasn't compiled by the singlepass backend with
+    /// `CompilerConfig::track_state`.
+    Unsupported,
+    /// The instance is already being profiled.
+    AlreadyProfiling,
+}
+
+impl fmt::Display for ProfilingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfilingError::Unsupported => write!(
+                f,
+                "only modules compiled by singlepass with `track_state` can be profiled"
+            ),
+            ProfilingError::AlreadyProfiling => write!(f, "the instance is already being profiled"),
+        }
+    }
+}
+
+impl std::error::Error for ProfilingError {}
+
+/// The samples taken while profiling an instance.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// The number of samples of each call stack, outermost function first.
+    stacks: HashMap<Vec<String>, u64>,
+}
+
+impl Profile {
+    /// The number of samples of each call stack, with the names of its
+    /// functions from the outermost to the innermost one.
+    pub fn stacks(&self) -> impl Iterator<Item = (&[String], u64)> {
+        self.stacks
+            .iter()
+            .map(|(stack, count)| (stack.as_slice(), *count))
+    }
+
+    /// The total number of samples.
+    pub fn samples(&self) -> u64 {
+        self.stacks.values().sum()
+    }
+
+    /// The number of samples taken in each function, not counting its
+    /// callees, from the most to the least sampled one.
+    pub fn self_samples(&self) -> Vec<(String, u64)> {
+        let mut functions: HashMap<&str, u64> = HashMap::new();
+        for (stack, count) in &self.stacks {
+            if let Some(innermost) = stack.last() {
+                *functions.entry(innermost).or_insert(0) += count;
+            }
+        }
+
+        let mut functions: Vec<(String, u64)> = functions
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        functions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        functions
+    }
+
+    /// Writes the profile in the folded stacks format read by `flamegraph.pl`
+    /// and `inferno`: one line per call stack, with the function names
+    /// separated by `;` and followed by the number of samples.
+    pub fn write_folded<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| format!("{} {}", stack.join(";"), count))
+            .collect();
+        lines.sort();
+        for line in lines {
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// The samples of one instance, by the local function indices of their
+/// frames, innermost first.
+pub(crate) struct Sampler {
+    stacks: Mutex<HashMap<Vec<usize>, u64>>,
+    running: AtomicBool,
+}
+
+/// Starts sampling the wasm call stack of the instance of `ctx` every `interval`.
+pub(crate) fn start(
+    ctx: *mut vm::Ctx,
+    module: &ModuleInner,
+    interval: Duration,
+) -> Result<(), ProfilingError> {
+    if !module.info.track_state || module.runnable_module.get_module_state_map().is_none() {
+        return Err(ProfilingError::Unsupported);
+    }
+
+    let sampler = Arc::new(Sampler {
+        stacks: Mutex::new(HashMap::new()),
+        running: AtomicBool::new(true),
+    });
+    {
+        let mut samplers = SAMPLERS.lock();
+        if samplers.contains_key(&(ctx as usize)) {
+            return Err(ProfilingError::AlreadyProfiling);
+        }
+        samplers.insert(ctx as usize, Arc::clone(&sampler));
+    }
+    PROFILED_INSTANCES.fetch_add(1, Ordering::SeqCst);
+
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        if !sampler.running.load(Ordering::SeqCst) {
+            break;
+        }
+        SAMPLE_REQUESTED.store(true, Ordering::SeqCst);
+        unsafe { set_wasm_interrupt() };
+    });
+
+    Ok(())
+}
+
+/// Stops sampling the instance of `ctx`, and returns its profile if it was
+/// being profiled.
+pub(crate) fn stop(ctx: *mut vm::Ctx, info: &ModuleInfo) -> Option<Profile> {
+    let sampler = SAMPLERS.lock().remove(&(ctx as usize))?;
+    PROFILED_INSTANCES.fetch_sub(1, Ordering::SeqCst);
+    sampler.running.store(false, Ordering::SeqCst);
+
+    let names = function_names(info);
+    let name_of = |local_index: usize| {
+        let index = FuncIndex::new(info.imported_functions.len() + local_index);
+        names
+            .get(&index)
+            .cloned()
+            .unwrap_or_else(|| format!("func[{}]", index.index()))
+    };
+
+    let mut profile = Profile::default();
+    for (frames, count) in sampler.stacks.lock().iter() {
+        let stack = frames.iter().rev().map(|&i| name_of(i)).collect();
+        *profile.stacks.entry(stack).or_insert(0) += count;
+    }
+    Some(profile)
+}
+
+/// Stops sampling the instance of `ctx` without building its profile, when
+/// the instance is dropped.
+pub(crate) fn discard(ctx: *mut vm::Ctx) {
+    if PROFILED_INSTANCES.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+    if let Some(sampler) = SAMPLERS.lock().remove(&(ctx as usize)) {
+        PROFILED_INSTANCES.fetch_sub(1, Ordering::SeqCst);
+        sampler.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Runs `f`, a call into the instance of `ctx`, with the frame metadata of
+/// its module available to the fault handler if the instance is being profiled.
+pub(crate) fn with_profiling<R, F: FnOnce() -> R>(ctx: *mut vm::Ctx, f: F) -> R {
+    if PROFILED_INSTANCES.load(Ordering::SeqCst) == 0
+        || !SAMPLERS.lock().contains_key(&(ctx as usize))
+    {
+        return f();
+    }
+
+    let module = unsafe { &*(*ctx).module };
+    push_code_version(CodeVersion {
+        baseline: true,
+        msm: module.runnable_module.get_module_state_map().unwrap(),
+        base: module.runnable_module.get_code().unwrap().as_ptr() as usize,
+        backend: module.info.backend,
+        runnable_module: module.runnable_module.clone(),
+    });
+    let result = unsafe { with_ctx(ctx, f) };
+    pop_code_version();
+    result
+}
+
+/// Whether the pending wasm interrupt was raised to take a sample, in which
+/// case it's now handled.
+pub(crate) fn take_sample_request() -> bool {
+    SAMPLE_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Records the call stack `image` of the instance of `ctx`, if it's being profiled.
+pub(crate) fn record_sample(ctx: *mut vm::Ctx, image: &ExecutionStateImage) {
+    if image.frames.is_empty() {
+        return;
+    }
+    // The instance can't stop being profiled while it runs, but another
+    // thread may hold the lock; losing this sample is better than waiting.
+    let sampler = match SAMPLERS.try_lock() {
+        Some(samplers) => samplers.get(&(ctx as usize)).cloned(),
+        None => None,
+    };
+    if let Some(sampler) = sampler {
+        let frames = image.frames.iter().map(|f| f.local_function_id).collect();
+        *sampler.stacks.lock().entry(frames).or_insert(0) += 1;
+    }
+}
+
+/// The names of the functions of a module: from its name section if it has
+/// one, or else the names they are exported under.
+fn function_names(info: &ModuleInfo) -> HashMap<FuncIndex, String> {
+    let mut names = HashMap::new();
+
+    for (name, index) in &info.exports {
+        if let ExportIndex::Func(func_index) = index {
+            names.entry(*func_index).or_insert_with(|| name.clone());
+        }
+    }
+
+    for (index, name) in &info.names.functions {
+        names.insert(*index, name.clone());
+    }
+
+    names
+}
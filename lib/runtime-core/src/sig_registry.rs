@@ -4,23 +4,66 @@ use crate::{
 };
 use lazy_static::lazy_static;
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
-lazy_static! {
-    static ref GLOBAL_SIG_REGISTRY: RwLock<GlobalSigRegistry> = {
-        let registry = GlobalSigRegistry {
-            func_table: HashMap::new(),
-            sig_assoc: Map::new(),
-        };
+/// The number of shards of the signature to index map, a power of two.
+const SHARDS: usize = 16;
 
-        RwLock::new(registry)
+lazy_static! {
+    static ref GLOBAL_SIG_REGISTRY: GlobalSigRegistry = GlobalSigRegistry {
+        func_tables: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+        sig_assoc: RwLock::new(Map::new()),
     };
 }
 
+/// The signatures registered with each index.  The interned signatures are
+/// kept as values as well, since `HashMap::get_key_value` needs Rust 1.40.
+type FuncTable = HashMap<Arc<FuncSig>, (SigIndex, Arc<FuncSig>)>;
+
+/// The signature to index map is sharded by the hash of the signatures, so
+/// threads registering different signatures don't contend, and looking up
+/// an already registered signature, by far the most common case when
+/// instantiating, only takes a read lock on one shard.
+///
+/// A shard is always locked before `sig_assoc`.
 struct GlobalSigRegistry {
-    func_table: HashMap<Arc<FuncSig>, SigIndex>,
-    sig_assoc: Map<SigIndex, Arc<FuncSig>>,
+    func_tables: Vec<RwLock<FuncTable>>,
+    sig_assoc: RwLock<Map<SigIndex, Arc<FuncSig>>>,
+}
+
+impl GlobalSigRegistry {
+    fn shard(&self, func_sig: &FuncSig) -> &RwLock<FuncTable> {
+        let mut hasher = DefaultHasher::new();
+        func_sig.hash(&mut hasher);
+        &self.func_tables[hasher.finish() as usize & (SHARDS - 1)]
+    }
+
+    /// The index and interned signature of `func_sig`, registering it with
+    /// `intern` if it isn't registered yet.
+    fn lookup(
+        &self,
+        func_sig: &FuncSig,
+        intern: impl FnOnce() -> Arc<FuncSig>,
+    ) -> (SigIndex, Arc<FuncSig>) {
+        let shard = self.shard(func_sig);
+
+        if let Some((sig_index, sig)) = shard.read().get(func_sig) {
+            return (*sig_index, Arc::clone(sig));
+        }
+
+        let mut func_table = shard.write();
+        // Another thread may have registered it between the locks.
+        if let Some((sig_index, sig)) = func_table.get(func_sig) {
+            return (*sig_index, Arc::clone(sig));
+        }
+
+        let sig = intern();
+        let sig_index = self.sig_assoc.write().push(Arc::clone(&sig));
+        func_table.insert(Arc::clone(&sig), (sig_index, Arc::clone(&sig)));
+        (sig_index, sig)
+    }
 }
 
 /// The `SigRegistry` represents a process-global map of function signatures
@@ -40,41 +83,56 @@ impl SigRegistry {
         Sig: Into<Arc<FuncSig>>,
     {
         let func_sig = func_sig.into();
-        let mut global = (*GLOBAL_SIG_REGISTRY).write();
-        let global = &mut *global;
-
-        let func_table = &mut global.func_table;
-        let sig_assoc = &mut global.sig_assoc;
-
-        let sig_index = *func_table
-            .entry(Arc::clone(&func_sig))
-            .or_insert_with(|| sig_assoc.push(func_sig));
-
-        sig_index
+        GLOBAL_SIG_REGISTRY
+            .lookup(&func_sig, || Arc::clone(&func_sig))
+            .0
     }
 
     /// Map a global `SigIndex` to an interned `FuncSig`.
     pub fn lookup_signature(&self, sig_index: SigIndex) -> Arc<FuncSig> {
-        let global = (*GLOBAL_SIG_REGISTRY).read();
-        Arc::clone(&global.sig_assoc[sig_index])
+        let sig_assoc = GLOBAL_SIG_REGISTRY.sig_assoc.read();
+        Arc::clone(&sig_assoc[sig_index])
     }
 
     /// Register a function signature with the global signature registry.
     ///
     /// This will return an interned `FuncSig`.
     pub fn lookup_signature_ref(&self, func_sig: &FuncSig) -> Arc<FuncSig> {
-        let mut global = (*GLOBAL_SIG_REGISTRY).write();
-        let global = &mut *global;
-
-        let func_table = &mut global.func_table;
-        let sig_assoc = &mut global.sig_assoc;
-
-        if func_table.contains_key(func_sig) {
-            Arc::clone(&sig_assoc[func_table[func_sig]])
-        } else {
-            let arc = Arc::new(func_sig.clone());
-            func_table.insert(Arc::clone(&arc), sig_assoc.push(Arc::clone(&arc)));
-            arc
+        GLOBAL_SIG_REGISTRY
+            .lookup(func_sig, || Arc::new(func_sig.clone()))
+            .1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Type;
+    use std::thread;
+
+    #[test]
+    fn concurrent_lookups_agree() {
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                thread::spawn(|| {
+                    (0..64)
+                        .map(|n| {
+                            let sig = FuncSig::new(vec![Type::I64; n], vec![Type::F32]);
+                            SigRegistry.lookup_sig_index(sig)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let indices: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+        for thread_indices in &indices[1..] {
+            assert_eq!(thread_indices, &indices[0]);
+        }
+        for (n, &sig_index) in indices[0].iter().enumerate() {
+            let sig = SigRegistry.lookup_signature(sig_index);
+            assert_eq!(sig.params().len(), n);
+            assert_eq!(SigRegistry.lookup_sig_index(sig), sig_index);
         }
     }
 }
@@ -0,0 +1,214 @@
+//! The signing module signs WebAssembly modules with Ed25519 keys, and verifies them
+//! before compiling, so that a host only runs modules from producers it trusts.
+//!
+//! A signature is embedded in a custom section named [`SIGNATURE_SECTION`], which must be
+//! the last section of the module, and covers all the bytes before that section. It can
+//! also be kept apart from the module, as a detached signature of all of its bytes.
+use crate::error::CompileError;
+use ed25519_dalek::Signature;
+use std::fmt;
+
+pub use ed25519_dalek::{Keypair, PublicKey, SIGNATURE_LENGTH};
+
+/// The name of the custom section holding the signature of a module.
+pub const SIGNATURE_SECTION: &str = "wasmer-signature";
+
+/// The reason a module couldn't be verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    /// The module doesn't end with a signature section.
+    MissingSignature,
+    /// The module or its signature is malformed.
+    Malformed(String),
+    /// None of the trusted keys made the signature.
+    Untrusted,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerificationError::MissingSignature => write!(f, "the module isn't signed"),
+            VerificationError::Malformed(msg) => write!(f, "malformed signature: {}", msg),
+            VerificationError::Untrusted => write!(f, "the module isn't signed by a trusted key"),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+impl From<VerificationError> for CompileError {
+    fn from(error: VerificationError) -> CompileError {
+        CompileError::VerificationError {
+            msg: error.to_string(),
+        }
+    }
+}
+
+/// Signs `wasm` with `keypair`, and returns it with the signature section appended.
+pub fn sign(wasm: &[u8], keypair: &Keypair) -> Vec<u8> {
+    let signature = sign_detached(wasm, keypair);
+
+    let mut name = Vec::new();
+    write_u32(&mut name, SIGNATURE_SECTION.len() as u32);
+    name.extend_from_slice(SIGNATURE_SECTION.as_bytes());
+
+    let mut signed = wasm.to_vec();
+    signed.push(0);
+    write_u32(&mut signed, (name.len() + signature.len()) as u32);
+    signed.extend_from_slice(&name);
+    signed.extend_from_slice(&signature);
+    signed
+}
+
+/// Signs `wasm` with `keypair`, and returns the signature.
+pub fn sign_detached(wasm: &[u8], keypair: &Keypair) -> [u8; SIGNATURE_LENGTH] {
+    keypair.sign(wasm).to_bytes()
+}
+
+/// Verifies that the signature section of `wasm` was made by one of `trusted_keys`.
+pub fn verify(wasm: &[u8], trusted_keys: &[PublicKey]) -> Result<(), VerificationError> {
+    let (signed, signature) = split_signature(wasm)?;
+    verify_detached(signed, signature, trusted_keys)
+}
+
+/// Verifies that `signature` of `wasm` was made by one of `trusted_keys`.
+pub fn verify_detached(
+    wasm: &[u8],
+    signature: &[u8],
+    trusted_keys: &[PublicKey],
+) -> Result<(), VerificationError> {
+    let signature = Signature::from_bytes(signature)
+        .map_err(|e| VerificationError::Malformed(e.to_string()))?;
+    if trusted_keys
+        .iter()
+        .any(|key| key.verify(wasm, &signature).is_ok())
+    {
+        Ok(())
+    } else {
+        Err(VerificationError::Untrusted)
+    }
+}
+
+/// Splits `wasm` into the bytes covered by its signature, and the signature.
+fn split_signature(wasm: &[u8]) -> Result<(&[u8], &[u8]), VerificationError> {
+    let malformed = |msg: &str| VerificationError::Malformed(msg.to_string());
+
+    if wasm.len() < 8 || &wasm[0..4] != b"\0asm" {
+        return Err(malformed("not a wasm binary"));
+    }
+    let mut pos = 8;
+    let mut signature = None;
+    while pos < wasm.len() {
+        let section_start = pos;
+        let id = wasm[pos];
+        pos += 1;
+        let size = read_u32(wasm, &mut pos).ok_or_else(|| malformed("bad section size"))? as usize;
+        let section_end = pos
+            .checked_add(size)
+            .filter(|&end| end <= wasm.len())
+            .ok_or_else(|| malformed("section out of bounds"))?;
+
+        signature = None;
+        if id == 0 {
+            let name_len = read_u32(wasm, &mut pos)
+                .ok_or_else(|| malformed("bad custom section name"))?
+                as usize;
+            if pos + name_len <= section_end
+                && &wasm[pos..pos + name_len] == SIGNATURE_SECTION.as_bytes()
+            {
+                signature = Some((section_start, &wasm[pos + name_len..section_end]));
+            }
+        }
+        pos = section_end;
+    }
+
+    match signature {
+        Some((section_start, signature)) => Ok((&wasm[..section_start], signature)),
+        None => Err(VerificationError::MissingSignature),
+    }
+}
+
+fn read_u32(bytes: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut result = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u32).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
+
+fn write_u32(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // `(module)` with a custom section named "name".
+    const WASM: &[u8] = b"\0asm\x01\0\0\0\0\x05\x04name";
+
+    fn keypair(seed: u8) -> Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        Keypair { secret, public }
+    }
+
+    #[test]
+    fn signed_module_verifies_with_its_key() {
+        let signer = keypair(1);
+        let signed = sign(WASM, &signer);
+        assert_eq!(&signed[..WASM.len()], WASM);
+        assert_eq!(verify(&signed, &[keypair(2).public, signer.public]), Ok(()));
+        assert_eq!(
+            verify(&signed, &[keypair(2).public]),
+            Err(VerificationError::Untrusted)
+        );
+    }
+
+    #[test]
+    fn tampered_module_is_rejected() {
+        let signer = keypair(1);
+        let mut signed = sign(WASM, &signer);
+        signed[12] = b'N';
+        assert_eq!(
+            verify(&signed, &[signer.public]),
+            Err(VerificationError::Untrusted)
+        );
+    }
+
+    #[test]
+    fn signature_must_be_the_last_section() {
+        let signer = keypair(1);
+        assert_eq!(
+            verify(WASM, &[signer.public]),
+            Err(VerificationError::MissingSignature)
+        );
+        let mut signed = sign(WASM, &signer);
+        signed.extend_from_slice(&WASM[8..]);
+        assert_eq!(
+            verify(&signed, &[signer.public]),
+            Err(VerificationError::MissingSignature)
+        );
+    }
+
+    #[test]
+    fn detached_signature() {
+        let signer = keypair(1);
+        let signature = sign_detached(WASM, &signer);
+        assert_eq!(verify_detached(WASM, &signature, &[signer.public]), Ok(()));
+        assert!(verify_detached(WASM, &signature[1..], &[signer.public]).is_err());
+    }
+}
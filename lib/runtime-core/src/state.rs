@@ -3,6 +3,9 @@
 //! generated code from one tier to another, or serializing state of a running instace.
 
 use crate::backend::{Backend, RunnableModule};
+use crate::module::ModuleInfo;
+use crate::structures::TypedIndex;
+use crate::types::FuncIndex;
 use std::collections::BTreeMap;
 use std::ops::Bound::{Included, Unbounded};
 use std::sync::Arc;
@@ -386,11 +389,17 @@ impl MachineStateDiff {
 impl ExecutionStateImage {
     /// Prints a backtrace if the `WASMER_BACKTRACE` environment variable is 1.
     pub fn print_backtrace_if_needed(&self) {
+        self.print_backtrace_with_names_if_needed(None);
+    }
+
+    /// Prints a backtrace if the `WASMER_BACKTRACE` environment variable is 1,
+    /// naming the functions of the frames from the name section of `info`.
+    pub fn print_backtrace_with_names_if_needed(&self, info: Option<&ModuleInfo>) {
         use std::env;
 
         if let Ok(x) = env::var("WASMER_BACKTRACE") {
             if x == "1" {
-                eprintln!("{}", self.output());
+                eprintln!("{}", self.output_with_names(info));
                 return;
             }
         }
@@ -400,6 +409,12 @@ impl ExecutionStateImage {
 
     /// Converts self into a `String`, used for display purposes.
     pub fn output(&self) -> String {
+        self.output_with_names(None)
+    }
+
+    /// Converts self into a `String` like `output`, naming the functions of
+    /// the frames from the name section of `info`.
+    pub fn output_with_names(&self, info: Option<&ModuleInfo>) -> String {
         fn join_strings(x: impl Iterator<Item = String>, sep: &str) -> String {
             let mut ret = String::new();
             let mut first = true;
@@ -444,6 +459,13 @@ impl ExecutionStateImage {
             ret += "\n";
             for (i, f) in self.frames.iter().enumerate() {
                 ret += &format!("* Frame {} @ Local function {}", i, f.local_function_id);
+                let name = info.and_then(|info| {
+                    let index = FuncIndex::new(info.imported_functions.len() + f.local_function_id);
+                    info.names.functions.get(&index)
+                });
+                if let Some(name) = name {
+                    ret += &format!(" (`{}`)", name);
+                }
                 ret += "\n";
                 ret += &format!("  {} {}\n", "Offset:", format!("{}", f.wasm_inst_offset),);
                 ret += &format!(
@@ -481,7 +503,6 @@ impl InstanceImage {
 }
 
 /// Declarations for x86-64 registers.
-#[cfg(unix)]
 pub mod x64_decl {
     use super::*;
 
@@ -613,20 +634,23 @@ pub mod x64_decl {
     }
 }
 
-#[cfg(unix)]
 pub mod x64 {
     //! The x64 state module contains functions to generate state and code for x64 targets.
     pub use super::x64_decl::*;
     use super::*;
+    #[cfg(unix)]
     use crate::codegen::BreakpointMap;
+    #[cfg(unix)]
     use crate::fault::{
         catch_unsafe_unwind, get_boundary_register_preservation, run_on_alternative_stack,
     };
     use crate::structures::TypedIndex;
     use crate::types::LocalGlobalIndex;
     use crate::vm::Ctx;
+    #[cfg(unix)]
     use std::any::Any;
 
+    #[cfg(unix)]
     unsafe fn compute_vmctx_deref(vmctx: *const Ctx, seq: &[usize]) -> u64 {
         let mut ptr = &vmctx as *const *const Ctx as *const u8;
         for x in seq {
@@ -649,6 +673,7 @@ pub mod x64 {
 
     /// Invokes a call return on the stack for the given module state map, code base, instance
     /// image and context.
+    #[cfg(unix)]
     #[warn(unused_variables)]
     pub unsafe fn invoke_call_return_on_stack(
         msm: &ModuleStateMap,
@@ -1038,6 +1063,7 @@ pub mod x64 {
 
     /// Returns a `ExecutionStateImage` for the given versions, stack, initial registers and
     /// initial address.
+    #[cfg(unix)]
     #[warn(unused_variables)]
     pub unsafe fn read_stack<'a, I: Iterator<Item = &'a CodeVersion>, F: Fn() -> I + 'a>(
         versions: F,
@@ -69,6 +69,11 @@ where
         self.elems.reserve_exact(size);
     }
 
+    /// Drops the spare capacity of the map.
+    pub fn shrink_to_fit(&mut self) {
+        self.elems.shrink_to_fit();
+    }
+
     /// Convert this into a `BoxedMap`.
     pub fn into_boxed_map(self) -> BoxedMap<K, V> {
         BoxedMap::new(self.elems.into_boxed_slice())
@@ -0,0 +1,318 @@
+use super::super::{page_allocator, PageAllocator};
+use crate::error::MemoryCreationError;
+use crate::error::MemoryProtectionError;
+use std::ops::{Bound, RangeBounds};
+use std::{ptr, ptr::NonNull, slice, sync::Arc};
+
+unsafe impl Send for Memory {}
+unsafe impl Sync for Memory {}
+
+/// Data for a sized and protected region of memory.
+///
+/// Without an MMU the protection is only recorded: accessing memory
+/// protected with `Protect::None` doesn't fault, so backends must check
+/// bounds themselves, as the interpreter does.
+#[derive(Debug)]
+pub struct Memory {
+    ptr: *mut u8,
+    size: usize,
+    protection: Protect,
+    /// The size of the allocation, which outlives `size` when the memory is
+    /// split.
+    allocated: usize,
+    /// The allocator owning the pages, or `None` for the second half of a
+    /// split memory, which is freed with the first.
+    allocator: Option<Arc<dyn PageAllocator>>,
+}
+
+impl Memory {
+    /// Create a new memory with the given size and protection.
+    pub fn with_size_protect(size: usize, protection: Protect) -> Result<Self, String> {
+        Self::with_size_protect_in(size, protection, page_allocator())
+    }
+
+    /// Create a new memory with the given size and protection, taking its
+    /// pages from `allocator` rather than from the installed one.
+    pub fn with_size_protect_in(
+        size: usize,
+        protection: Protect,
+        allocator: Arc<dyn PageAllocator>,
+    ) -> Result<Self, String> {
+        if size == 0 {
+            return Ok(Self {
+                ptr: ptr::null_mut(),
+                size: 0,
+                protection,
+                allocated: 0,
+                allocator: None,
+            });
+        }
+
+        let size = round_up_to_page_size(size, allocator.page_size());
+
+        match allocator.allocate(size) {
+            Some(ptr) => Ok(Self {
+                ptr: ptr.as_ptr(),
+                size,
+                protection,
+                allocated: size,
+                allocator: Some(allocator),
+            }),
+            None => Err(format!(
+                "the page allocator couldn't allocate {} bytes",
+                size
+            )),
+        }
+    }
+
+    /// Create a new memory with the given size.
+    pub fn with_size(size: usize) -> Result<Self, MemoryCreationError> {
+        Self::with_size_protect(size, Protect::None)
+            .map_err(|e| MemoryCreationError::VirtualMemoryAllocationFailed(size, e))
+    }
+
+    /// Create a new memory with the given size, for code that is written
+    /// while `ReadWrite` and then run once `ReadExec`.
+    pub fn with_size_for_code(size: usize) -> Result<Self, MemoryCreationError> {
+        Self::with_size(size)
+    }
+
+    /// Protect this memory with the given range bounds and protection.
+    pub unsafe fn protect(
+        &mut self,
+        range: impl RangeBounds<usize>,
+        protection: Protect,
+    ) -> Result<(), MemoryProtectionError> {
+        let range_end = match range.end_bound() {
+            Bound::Included(end) => *end,
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.size(),
+        };
+        assert!(range_end <= self.size);
+
+        self.protection = protection;
+        Ok(())
+    }
+
+    /// Discard the contents of the first `len` bytes of this memory and make
+    /// the whole memory inaccessible, keeping it allocated so it can be reused.
+    ///
+    /// The bytes read as zero once they're made accessible again.
+    pub unsafe fn reset(&mut self, len: usize) -> Result<(), MemoryProtectionError> {
+        assert!(len <= self.size);
+        ptr::write_bytes(self.ptr, 0, len);
+        self.protect(.., Protect::None)
+    }
+
+    /// Split this memory into multiple memories by the given offset.
+    ///
+    /// The first memory frees the whole allocation, so the second one must
+    /// be dropped first.
+    pub fn split_at(mut self, offset: usize) -> (Memory, Memory) {
+        let page_size = match &self.allocator {
+            Some(allocator) => allocator.page_size(),
+            None => 1,
+        };
+        if offset % page_size == 0 {
+            let second_ptr = unsafe { self.ptr.add(offset) };
+            let second_size = self.size - offset;
+
+            self.size = offset;
+
+            let second = Memory {
+                ptr: second_ptr,
+                size: second_size,
+                protection: self.protection,
+                allocated: 0,
+                allocator: None,
+            };
+
+            (self, second)
+        } else {
+            panic!("offset must be multiple of page size: {}", offset)
+        }
+    }
+
+    /// Gets the size of this memory.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Gets a slice for this memory.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        slice::from_raw_parts(self.ptr, self.size)
+    }
+
+    /// Gets a mutable slice for this memory.
+    pub unsafe fn as_slice_mut(&mut self) -> &mut [u8] {
+        slice::from_raw_parts_mut(self.ptr, self.size)
+    }
+
+    /// Gets the protect kind of this memory.
+    pub fn protection(&self) -> Protect {
+        self.protection
+    }
+
+    /// Gets mutable pointer to the memory.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        if let (Some(ptr), Some(allocator)) = (NonNull::new(self.ptr), self.allocator.as_ref()) {
+            unsafe { allocator.deallocate(ptr, self.allocated) };
+        }
+    }
+}
+
+impl Clone for Memory {
+    fn clone(&self) -> Self {
+        let allocator = self.allocator.clone().unwrap_or_else(page_allocator);
+        let mut new = Memory::with_size_protect_in(self.size, self.protection, allocator).unwrap();
+        unsafe {
+            new.as_slice_mut().copy_from_slice(self.as_slice());
+        }
+
+        new
+    }
+}
+
+/// Kinds of memory protection.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Protect {
+    /// Read/write/exec allowed.
+    None,
+    /// Read only.
+    Read,
+    /// Read/write only.
+    ReadWrite,
+    /// Read/exec only.
+    ReadExec,
+}
+
+impl Protect {
+    /// Returns true if this memory is readable.
+    pub fn is_readable(self) -> bool {
+        match self {
+            Protect::Read | Protect::ReadWrite | Protect::ReadExec => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if this memory is writable.
+    pub fn is_writable(self) -> bool {
+        match self {
+            Protect::ReadWrite => true,
+            _ => false,
+        }
+    }
+}
+
+/// Round `size` up to the nearest multiple of `page_size`.
+fn round_up_to_page_size(size: usize, page_size: usize) -> usize {
+    assert!(page_size.is_power_of_two());
+    (size + (page_size - 1)) & !(page_size - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::{set_page_allocator, HeapPageAllocator};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the bytes a `HeapPageAllocator` has allocated and not freed.
+    struct CountingAllocator {
+        heap: HeapPageAllocator,
+        allocated: AtomicUsize,
+    }
+
+    impl PageAllocator for CountingAllocator {
+        fn page_size(&self) -> usize {
+            self.heap.page_size()
+        }
+
+        fn allocate(&self, size: usize) -> Option<NonNull<u8>> {
+            self.allocated.fetch_add(size, Ordering::SeqCst);
+            self.heap.allocate(size)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, size: usize) {
+            self.allocated.fetch_sub(size, Ordering::SeqCst);
+            self.heap.deallocate(ptr, size)
+        }
+    }
+
+    /// Installs a page allocator until it's dropped, so that a failing test
+    /// doesn't leave it installed.
+    struct InstalledAllocator;
+
+    impl InstalledAllocator {
+        fn new(allocator: Arc<dyn PageAllocator>) -> Self {
+            set_page_allocator(Some(allocator));
+            InstalledAllocator
+        }
+    }
+
+    impl Drop for InstalledAllocator {
+        fn drop(&mut self) {
+            set_page_allocator(None);
+        }
+    }
+
+    fn counting_allocator() -> Arc<CountingAllocator> {
+        Arc::new(CountingAllocator {
+            heap: HeapPageAllocator::new(1024),
+            allocated: AtomicUsize::new(0),
+        })
+    }
+
+    #[test]
+    fn memories_take_their_pages_from_their_allocator() {
+        let allocator = counting_allocator();
+
+        let mut memory =
+            Memory::with_size_protect_in(1500, Protect::ReadWrite, allocator.clone()).unwrap();
+        assert_eq!(memory.size(), 2048);
+        assert_eq!(memory.as_ptr() as usize % 1024, 0);
+        assert_eq!(allocator.allocated.load(Ordering::SeqCst), 2048);
+        unsafe {
+            assert!(memory.as_slice().iter().all(|&b| b == 0));
+            memory.as_slice_mut()[0] = 1;
+            memory.as_slice_mut()[1500] = 2;
+        }
+
+        let clone = memory.clone();
+        assert_eq!(unsafe { clone.as_slice() }, unsafe { memory.as_slice() });
+        assert_eq!(allocator.allocated.load(Ordering::SeqCst), 4096);
+        drop(clone);
+
+        unsafe { memory.reset(1024).unwrap() };
+        assert_eq!(memory.protection(), Protect::None);
+        assert_eq!(unsafe { memory.as_slice() }[0], 0);
+        assert_eq!(unsafe { memory.as_slice() }[1500], 2);
+
+        // The first half frees the whole allocation.
+        let (first, second) = memory.split_at(1024);
+        assert_eq!((first.size(), second.size()), (1024, 1024));
+        drop(second);
+        assert_eq!(allocator.allocated.load(Ordering::SeqCst), 2048);
+        drop(first);
+        assert_eq!(allocator.allocated.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn memories_keep_the_allocator_installed_when_they_were_created() {
+        let allocator = counting_allocator();
+
+        let installed = InstalledAllocator::new(allocator.clone());
+        let memory = Memory::with_size(1).unwrap();
+        assert_eq!(allocator.allocated.load(Ordering::SeqCst), 1024);
+        drop(installed);
+
+        drop(memory);
+        assert_eq!(allocator.allocated.load(Ordering::SeqCst), 0);
+    }
+}
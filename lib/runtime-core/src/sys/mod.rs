@@ -4,12 +4,27 @@ mod unix;
 #[cfg(windows)]
 mod windows;
 
+// Also built for the tests on unix and windows, so that the memories taken
+// from the page allocator are tested there.
+#[cfg(any(not(any(unix, windows)), test))]
+#[cfg_attr(any(unix, windows), allow(dead_code))]
+mod embedded;
+
+mod page_allocator;
+
 #[cfg(unix)]
 pub use self::unix::*;
 
 #[cfg(windows)]
 pub use self::windows::*;
 
+#[cfg(not(any(unix, windows)))]
+pub use self::embedded::*;
+
+pub use self::page_allocator::{
+    page_allocator, set_page_allocator, HeapPageAllocator, PageAllocator,
+};
+
 use serde::{
     de::{self, SeqAccess, Visitor},
     ser::SerializeStruct,
@@ -0,0 +1,118 @@
+//! Pluggable page allocation for targets without virtual memory.
+//!
+//! On targets other than unix and windows there's no `mmap` or
+//! `VirtualAlloc`, so [`Memory`] asks the installed [`PageAllocator`] for its
+//! pages instead.  Embedders on an RTOS install one carving pages out of a
+//! dedicated RAM region with [`set_page_allocator`]; the default
+//! [`HeapPageAllocator`] takes them from the global allocator.
+//!
+//! Those targets still need `std`: there is no `alloc`-only build of the
+//! runtime. The loader and validator are `wasmparser` 0.45, which needs
+//! `std`, and the module cache, tiering, fibers and the locks shared
+//! between instances are built on `std::fs`, `std::thread` and
+//! `std::sync`.
+//!
+//! [`Memory`]: crate::backend::sys::Memory
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::{alloc, fmt, ptr::NonNull, sync::Arc};
+
+lazy_static! {
+    static ref PAGE_ALLOCATOR: RwLock<Option<Arc<dyn PageAllocator>>> = RwLock::new(None);
+}
+
+/// Allocates the pages backing memories, tables and compiled code.
+pub trait PageAllocator: Send + Sync {
+    /// The size of a page, a power of two.  Allocation sizes are rounded up
+    /// to a multiple of it.
+    fn page_size(&self) -> usize;
+
+    /// Allocate `size` zeroed bytes aligned to the page size, or return
+    /// `None` if they can't be allocated.
+    fn allocate(&self, size: usize) -> Option<NonNull<u8>>;
+
+    /// Free `size` bytes at `ptr`, as returned by `allocate(size)`.
+    ///
+    /// # Safety
+    /// `ptr` must have been returned by `allocate` on this allocator with
+    /// the same `size`, and not freed since.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, size: usize);
+}
+
+impl fmt::Debug for dyn PageAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("PageAllocator")
+    }
+}
+
+/// Install `allocator` to allocate the pages of memories created from now on,
+/// or go back to the [`HeapPageAllocator`] with `None`.
+///
+/// Memories keep the allocator they were created with, so it may be replaced
+/// while they're alive.
+pub fn set_page_allocator(allocator: Option<Arc<dyn PageAllocator>>) {
+    *PAGE_ALLOCATOR.write() = allocator;
+}
+
+/// The installed page allocator, or the [`HeapPageAllocator`].
+pub fn page_allocator() -> Arc<dyn PageAllocator> {
+    PAGE_ALLOCATOR
+        .read()
+        .clone()
+        .unwrap_or_else(|| Arc::new(HeapPageAllocator::default()))
+}
+
+/// A [`PageAllocator`] taking its pages from the global allocator.
+#[derive(Debug, Clone, Copy)]
+pub struct HeapPageAllocator {
+    page_size: usize,
+}
+
+impl HeapPageAllocator {
+    /// Allocate pages of `page_size` bytes, which must be a power of two.
+    pub fn new(page_size: usize) -> Self {
+        assert!(page_size.is_power_of_two());
+        Self { page_size }
+    }
+}
+
+impl Default for HeapPageAllocator {
+    /// Pages of 4 KiB.
+    fn default() -> Self {
+        Self::new(4096)
+    }
+}
+
+impl PageAllocator for HeapPageAllocator {
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn allocate(&self, size: usize) -> Option<NonNull<u8>> {
+        let layout = alloc::Layout::from_size_align(size, self.page_size).ok()?;
+        NonNull::new(unsafe { alloc::alloc_zeroed(layout) })
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, size: usize) {
+        let layout = alloc::Layout::from_size_align_unchecked(size, self.page_size);
+        alloc::dealloc(ptr.as_ptr(), layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heap_pages_are_aligned_and_zeroed() {
+        let allocator = HeapPageAllocator::new(1 << 16);
+        let ptr = allocator.allocate(3 << 16).unwrap();
+        assert_eq!(ptr.as_ptr() as usize % (1 << 16), 0);
+        unsafe {
+            let bytes = std::slice::from_raw_parts(ptr.as_ptr(), 3 << 16);
+            assert!(bytes.iter().all(|&b| b == 0));
+            allocator.deallocate(ptr, 3 << 16);
+        }
+    }
+}
@@ -4,11 +4,21 @@ use errno;
 use nix::libc;
 use page_size;
 use std::ops::{Bound, RangeBounds};
-use std::{fs::File, os::unix::io::IntoRawFd, path::Path, ptr, slice, sync::Arc};
+use std::{
+    fs::File,
+    os::unix::io::{AsRawFd, IntoRawFd},
+    path::Path,
+    ptr, slice,
+    sync::Arc,
+};
 
 unsafe impl Send for Memory {}
 unsafe impl Sync for Memory {}
 
+/// `MAP_JIT`, which `libc` doesn't define for macOS yet.
+#[cfg(target_os = "macos")]
+pub(crate) const MAP_JIT: libc::c_int = 0x0800;
+
 /// Data for a sized and protected region of memory.
 #[derive(Debug)]
 pub struct Memory {
@@ -16,6 +26,8 @@ pub struct Memory {
     size: usize,
     protection: Protect,
     fd: Option<Arc<RawFd>>,
+    /// Whether the pages belong to someone else, and mustn't be unmapped.
+    borrowed: bool,
 }
 
 impl Memory {
@@ -52,10 +64,88 @@ impl Memory {
                 size: file_len as usize,
                 protection,
                 fd: Some(Arc::new(raw_fd)),
+                borrowed: false,
             })
         }
     }
 
+    /// Create a new memory by mapping `shared_size` bytes of `file` starting at
+    /// `offset`, followed by `private_size` bytes of zeroed private memory.
+    ///
+    /// The file region is mapped shared and read-only: every process mapping it
+    /// uses the very same pages, and they can never be made writable, so the code
+    /// in it must not need patching. The private part is inaccessible until it is
+    /// protected otherwise.
+    /// `offset`, `shared_size` and `private_size` must be multiples of the page size.
+    pub fn from_file_region_shared(
+        file: &File,
+        offset: usize,
+        shared_size: usize,
+        private_size: usize,
+    ) -> Result<Self, MemoryCreationError> {
+        let page_size = page_size::get();
+        if offset % page_size != 0 || shared_size % page_size != 0 || private_size % page_size != 0
+        {
+            return Err(MemoryCreationError::VirtualMemoryAllocationFailed(
+                shared_size + private_size,
+                format!(
+                    "file region at offset {} with size {} is not page aligned",
+                    offset, shared_size
+                ),
+            ));
+        }
+
+        // Reserve the whole range first, so that the private part directly
+        // follows the file region.
+        let mut memory = Self::with_size(shared_size + private_size)?;
+        if shared_size == 0 {
+            return Ok(memory);
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                memory.ptr as _,
+                shared_size,
+                libc::PROT_READ,
+                libc::MAP_SHARED | libc::MAP_FIXED,
+                file.as_raw_fd(),
+                offset as libc::off_t,
+            )
+        };
+
+        if ptr == -1 as _ {
+            Err(MemoryCreationError::VirtualMemoryAllocationFailed(
+                shared_size,
+                errno::errno().to_string(),
+            ))
+        } else {
+            memory.protection = Protect::Read;
+            Ok(memory)
+        }
+    }
+
+    /// Create a memory over `size` bytes at `ptr` mapped by someone else, which are
+    /// left mapped when this memory is dropped.
+    ///
+    /// # Safety
+    /// `ptr` must be page aligned and point to `size` mapped bytes whose protection
+    /// can be changed, e.g. a private mapping, and which stay mapped as long as this
+    /// memory is alive. `protection` must be their current protection.
+    pub unsafe fn from_raw_parts(ptr: *mut u8, size: usize, protection: Protect) -> Self {
+        assert_eq!(
+            ptr as usize % page_size::get(),
+            0,
+            "borrowed memory must be page aligned"
+        );
+        Self {
+            ptr,
+            size,
+            protection,
+            fd: None,
+            borrowed: true,
+        }
+    }
+
     /// Create a new memory with the given size and protection.
     pub fn with_size_protect(size: usize, protection: Protect) -> Result<Self, String> {
         if size == 0 {
@@ -64,6 +154,7 @@ impl Memory {
                 size: 0,
                 protection,
                 fd: None,
+                borrowed: false,
             });
         }
 
@@ -88,33 +179,43 @@ impl Memory {
                 size,
                 protection,
                 fd: None,
+                borrowed: false,
             })
         }
     }
 
     /// Create a new memory with the given size.
     pub fn with_size(size: usize) -> Result<Self, MemoryCreationError> {
+        Self::with_size_and_flags(size, libc::MAP_PRIVATE | libc::MAP_ANON)
+    }
+
+    /// Create a new memory with the given size, for code that is written
+    /// while `ReadWrite` and then run once `ReadExec`.
+    ///
+    /// On macOS the memory is mapped with `MAP_JIT`, without which the
+    /// hardened runtime doesn't let it be made executable.
+    pub fn with_size_for_code(size: usize) -> Result<Self, MemoryCreationError> {
+        #[cfg(target_os = "macos")]
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANON | MAP_JIT;
+        #[cfg(not(target_os = "macos"))]
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANON;
+        Self::with_size_and_flags(size, flags)
+    }
+
+    fn with_size_and_flags(size: usize, flags: libc::c_int) -> Result<Self, MemoryCreationError> {
         if size == 0 {
             return Ok(Self {
                 ptr: ptr::null_mut(),
                 size: 0,
                 protection: Protect::None,
                 fd: None,
+                borrowed: false,
             });
         }
 
         let size = round_up_to_page_size(size, page_size::get());
 
-        let ptr = unsafe {
-            libc::mmap(
-                ptr::null_mut(),
-                size,
-                libc::PROT_NONE,
-                libc::MAP_PRIVATE | libc::MAP_ANON,
-                -1,
-                0,
-            )
-        };
+        let ptr = unsafe { libc::mmap(ptr::null_mut(), size, libc::PROT_NONE, flags, -1, 0) };
 
         if ptr == -1 as _ {
             Err(MemoryCreationError::VirtualMemoryAllocationFailed(
@@ -127,6 +228,7 @@ impl Memory {
                 size,
                 protection: Protect::None,
                 fd: None,
+                borrowed: false,
             })
         }
     }
@@ -171,6 +273,25 @@ impl Memory {
         }
     }
 
+    /// Discard the contents of the first `len` bytes of this memory and make
+    /// the whole memory inaccessible, keeping it mapped so it can be reused.
+    ///
+    /// The pages read as zero once they're made accessible again.
+    pub unsafe fn reset(&mut self, len: usize) -> Result<(), MemoryProtectionError> {
+        let size = round_up_to_page_size(len, page_size::get());
+        assert!(size <= self.size);
+
+        if size != 0 && libc::madvise(self.ptr as _, size, libc::MADV_DONTNEED) == -1 {
+            return Err(MemoryProtectionError::ProtectionFailed(
+                self.ptr as usize,
+                size,
+                errno::errno().to_string(),
+            ));
+        }
+
+        self.protect(..size, Protect::None)
+    }
+
     /// Split this memory into multiple memories by the given offset.
     pub fn split_at(mut self, offset: usize) -> (Memory, Memory) {
         let page_size = page_size::get();
@@ -185,6 +306,7 @@ impl Memory {
                 size: second_size,
                 protection: self.protection,
                 fd: self.fd.clone(),
+                borrowed: self.borrowed,
             };
 
             (self, second)
@@ -221,7 +343,7 @@ impl Memory {
 
 impl Drop for Memory {
     fn drop(&mut self) {
-        if !self.ptr.is_null() {
+        if !self.ptr.is_null() && !self.borrowed {
             let success = unsafe { libc::munmap(self.ptr as _, self.size) };
             assert_eq!(success, 0, "failed to unmap memory: {}", errno::errno());
         }
@@ -261,8 +383,6 @@ pub enum Protect {
     ReadWrite,
     /// Read/exec only.
     ReadExec,
-    /// Read/write/exec only.
-    ReadWriteExec,
 }
 
 impl Protect {
@@ -272,14 +392,13 @@ impl Protect {
             Protect::Read => 1,
             Protect::ReadWrite => 1 | 2,
             Protect::ReadExec => 1 | 4,
-            Protect::ReadWriteExec => 1 | 2 | 4,
         }
     }
 
     /// Returns true if this memory is readable.
     pub fn is_readable(self) -> bool {
         match self {
-            Protect::Read | Protect::ReadWrite | Protect::ReadExec | Protect::ReadWriteExec => true,
+            Protect::Read | Protect::ReadWrite | Protect::ReadExec => true,
             _ => false,
         }
     }
@@ -287,7 +406,7 @@ impl Protect {
     /// Returns true if this memory is writable.
     pub fn is_writable(self) -> bool {
         match self {
-            Protect::ReadWrite | Protect::ReadWriteExec => true,
+            Protect::ReadWrite => true,
             _ => false,
         }
     }
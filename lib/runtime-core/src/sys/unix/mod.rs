@@ -1,3 +1,5 @@
 mod memory;
 
 pub use self::memory::{Memory, Protect};
+#[cfg(target_os = "macos")]
+pub(crate) use self::memory::MAP_JIT;
@@ -82,6 +82,12 @@ impl Memory {
         }
     }
 
+    /// Create a new memory with the given size, for code that is written
+    /// while `ReadWrite` and then run once `ReadExec`.
+    pub fn with_size_for_code(size: usize) -> Result<Self, MemoryCreationError> {
+        Self::with_size(size)
+    }
+
     /// Protect this memory with the given range bounds and protection.
     pub unsafe fn protect(
         &mut self,
@@ -124,6 +130,27 @@ impl Memory {
         }
     }
 
+    /// Discard the contents of the first `len` bytes of this memory and make
+    /// the whole memory inaccessible, keeping it reserved so it can be reused.
+    ///
+    /// The pages read as zero once they're made accessible again.
+    pub unsafe fn reset(&mut self, len: usize) -> Result<(), MemoryProtectionError> {
+        let size = round_up_to_page_size(len, page_size::get());
+        assert!(size <= self.size);
+
+        // Decommitting makes the pages inaccessible, `protect` commits them again.
+        if size != 0 && VirtualFree(self.ptr as _, size, MEM_DECOMMIT) == 0 {
+            return Err(MemoryProtectionError::ProtectionFailed(
+                self.ptr as usize,
+                size,
+                "unable to decommit memory".to_string(),
+            ));
+        }
+
+        self.protection = Protect::None;
+        Ok(())
+    }
+
     /// Split this memory into multiple memories by the given offset.
     pub fn split_at(mut self, offset: usize) -> (Memory, Memory) {
         let page_size = page_size::get();
@@ -1,13 +1,14 @@
 use crate::{
     error::CreationError,
     instance::DynFunc,
+    pool::InstancePool,
     sig_registry::SigRegistry,
     structures::TypedIndex,
-    types::{FuncSig, TableDescriptor},
+    types::{FuncRef, FuncSig, TableDescriptor},
     vm,
 };
 
-use std::{ptr, sync::Arc};
+use std::{mem, ptr, sync::Arc};
 
 enum AnyfuncInner<'a> {
     Host {
@@ -15,6 +16,7 @@ enum AnyfuncInner<'a> {
         signature: Arc<FuncSig>,
     },
     Managed(DynFunc<'a>),
+    Raw(vm::Anyfunc),
 }
 
 /// Anyfunc data type.
@@ -45,9 +47,19 @@ impl<'a> From<DynFunc<'a>> for Anyfunc<'a> {
     }
 }
 
+impl<'a> From<FuncRef> for Anyfunc<'a> {
+    fn from(func_ref: FuncRef) -> Self {
+        Anyfunc {
+            inner: AnyfuncInner::Raw(func_ref.0),
+        }
+    }
+}
+
 pub struct AnyfuncTable {
     pub(crate) backing: Vec<vm::Anyfunc>,
     max: Option<u32>,
+    /// The pool `backing` is returned to when this table is dropped.
+    pool: Option<InstancePool>,
 }
 
 impl AnyfuncTable {
@@ -55,11 +67,33 @@ impl AnyfuncTable {
         desc: TableDescriptor,
         local: &mut vm::LocalTable,
     ) -> Result<Box<Self>, CreationError> {
+        Ok(Self::with_backing(desc, Vec::new(), None, local))
+    }
+
+    /// Create a table in an allocation taken from `pool`.
+    pub(crate) fn new_pooled(
+        desc: TableDescriptor,
+        pool: &InstancePool,
+        local: &mut vm::LocalTable,
+    ) -> Result<Box<Self>, CreationError> {
+        let backing = pool.take_table()?;
+
+        Ok(Self::with_backing(desc, backing, Some(pool.clone()), local))
+    }
+
+    fn with_backing(
+        desc: TableDescriptor,
+        mut backing: Vec<vm::Anyfunc>,
+        pool: Option<InstancePool>,
+        local: &mut vm::LocalTable,
+    ) -> Box<Self> {
         let initial_table_backing_len = desc.minimum as usize;
+        backing.resize(initial_table_backing_len, vm::Anyfunc::null());
 
         let mut storage = Box::new(AnyfuncTable {
-            backing: vec![vm::Anyfunc::null(); initial_table_backing_len],
+            backing,
             max: desc.maximum,
+            pool,
         });
 
         let storage_ptr: *mut AnyfuncTable = &mut *storage;
@@ -68,7 +102,7 @@ impl AnyfuncTable {
         local.count = storage.backing.len();
         local.table = storage_ptr as *mut ();
 
-        Ok(storage)
+        storage
     }
 
     pub fn current_size(&self) -> u32 {
@@ -98,6 +132,22 @@ impl AnyfuncTable {
         Some(starting_len)
     }
 
+    /// Shrink this table back to `minimum` elements, all null.
+    pub fn reset(&mut self, minimum: u32, local: &mut vm::LocalTable) {
+        self.backing.truncate(minimum as usize);
+        for element in self.backing.iter_mut() {
+            *element = vm::Anyfunc::null();
+        }
+        self.backing.resize(minimum as usize, vm::Anyfunc::null());
+
+        local.base = self.backing.as_mut_ptr() as *mut u8;
+        local.count = self.backing.len();
+    }
+
+    pub fn get(&self, index: u32) -> Option<vm::Anyfunc> {
+        self.backing.get(index as usize).cloned()
+    }
+
     pub fn set(&mut self, index: u32, element: Anyfunc) -> Result<(), ()> {
         if let Some(slot) = self.backing.get_mut(index as usize) {
             let anyfunc = match element.inner {
@@ -121,6 +171,7 @@ impl AnyfuncTable {
                         sig_id,
                     }
                 }
+                AnyfuncInner::Raw(anyfunc) => anyfunc,
             };
 
             *slot = anyfunc;
@@ -131,3 +182,11 @@ impl AnyfuncTable {
         }
     }
 }
+
+impl Drop for AnyfuncTable {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.release_table(mem::replace(&mut self.backing, Vec::new()));
+        }
+    }
+}
@@ -4,7 +4,8 @@ use crate::{
     error::CreationError,
     export::Export,
     import::IsExport,
-    types::{ElementType, TableDescriptor},
+    pool::InstancePool,
+    types::{ElementType, FuncRef, TableDescriptor},
     vm,
 };
 use std::{
@@ -24,6 +25,18 @@ pub enum Element<'a> {
     Anyfunc(Anyfunc<'a>),
 }
 
+impl<'a> From<Anyfunc<'a>> for Element<'a> {
+    fn from(anyfunc: Anyfunc<'a>) -> Self {
+        Element::Anyfunc(anyfunc)
+    }
+}
+
+impl<'a> From<FuncRef> for Element<'a> {
+    fn from(func_ref: FuncRef) -> Self {
+        Element::Anyfunc(func_ref.into())
+    }
+}
+
 /// Kind of table storage.
 // #[derive(Debug)]
 pub enum TableStorage {
@@ -60,6 +73,22 @@ impl Table {
     /// # }
     /// ```
     pub fn new(desc: TableDescriptor) -> Result<Self, CreationError> {
+        Self::with_pool(desc, None)
+    }
+
+    /// Create a new `Table` for a pooled instance, in an allocation taken
+    /// from `pool`.
+    pub(crate) fn new_pooled(
+        desc: TableDescriptor,
+        pool: &InstancePool,
+    ) -> Result<Self, CreationError> {
+        Self::with_pool(desc, Some(pool))
+    }
+
+    fn with_pool(
+        desc: TableDescriptor,
+        pool: Option<&InstancePool>,
+    ) -> Result<Self, CreationError> {
         if let Some(max) = desc.maximum {
             if max < desc.minimum {
                 return Err(CreationError::InvalidDescriptor(
@@ -75,7 +104,10 @@ impl Table {
         };
 
         let storage = match desc.element {
-            ElementType::Anyfunc => TableStorage::Anyfunc(AnyfuncTable::new(desc, &mut local)?),
+            ElementType::Anyfunc => TableStorage::Anyfunc(match pool {
+                Some(pool) => AnyfuncTable::new_pooled(desc, pool, &mut local)?,
+                None => AnyfuncTable::new(desc, &mut local)?,
+            }),
         };
 
         Ok(Self {
@@ -89,12 +121,30 @@ impl Table {
         self.desc
     }
 
+    /// Get the element at index, or `None` if the index is out of bounds.
+    pub fn get(&self, index: u32) -> Option<FuncRef> {
+        let storage = self.storage.lock().unwrap();
+        match &*storage {
+            (TableStorage::Anyfunc(ref anyfunc_table), _) => anyfunc_table.get(index).map(FuncRef),
+        }
+    }
+
     /// Set the element at index.
-    pub fn set(&self, index: u32, element: Element) -> Result<(), ()> {
+    ///
+    /// The element can be an [`Element`] or a [`FuncRef`], such as one obtained from
+    /// [`Table::get`].
+    ///
+    /// [`Element`]: enum.Element.html
+    /// [`FuncRef`]: ../types/struct.FuncRef.html
+    /// [`Table::get`]: struct.Table.html#method.get
+    pub fn set<'a, E>(&self, index: u32, element: E) -> Result<(), ()>
+    where
+        E: Into<Element<'a>>,
+    {
         let mut storage = self.storage.lock().unwrap();
         match &mut *storage {
             (TableStorage::Anyfunc(ref mut anyfunc_table), _) => {
-                match element {
+                match element.into() {
                     Element::Anyfunc(anyfunc) => anyfunc_table.set(index, anyfunc),
                     // _ => panic!("wrong element type for anyfunc table"),
                 }
@@ -134,6 +184,17 @@ impl Table {
         }
     }
 
+    /// Shrink this table back to its minimum size and clear it, for
+    /// `Instance::reset`.
+    pub(crate) fn reset(&self) {
+        let mut storage = self.storage.lock().unwrap();
+        match &mut *storage {
+            (TableStorage::Anyfunc(ref mut anyfunc_table), ref mut local) => {
+                anyfunc_table.reset(self.desc.minimum, local)
+            }
+        }
+    }
+
     /// Get a mutable pointer to underlying table storage.
     pub fn vm_local_table(&mut self) -> *mut vm::LocalTable {
         let mut storage = self.storage.lock().unwrap();
@@ -168,7 +229,7 @@ impl fmt::Debug for Table {
 #[cfg(test)]
 mod table_tests {
 
-    use super::{ElementType, Table, TableDescriptor};
+    use super::{ElementType, FuncRef, Table, TableDescriptor};
 
     #[test]
     fn test_initial_table_size() {
@@ -180,4 +241,23 @@ mod table_tests {
         .unwrap();
         assert_eq!(table.size(), 10);
     }
+
+    #[test]
+    fn test_table_get_set_grow() {
+        let table = Table::new(TableDescriptor {
+            element: ElementType::Anyfunc,
+            minimum: 1,
+            maximum: Some(2),
+        })
+        .unwrap();
+
+        assert_eq!(table.get(0), Some(FuncRef::null()));
+        assert_eq!(table.get(1), None);
+        assert!(table.set(1, FuncRef::null()).is_err());
+
+        assert_eq!(table.grow(1).unwrap(), 1);
+        assert_eq!(table.get(1), Some(FuncRef::null()));
+        assert!(table.set(1, FuncRef::null()).is_ok());
+        assert!(table.grow(1).is_err());
+    }
 }
@@ -1,24 +1,31 @@
 //! The typed func module implements a way of representing a wasm function
 //! with the correct types from rust. Function calls using a typed func have a low overhead.
 use crate::{
+    asyncify,
     error::RuntimeError,
     export::{Context, Export, FuncPointer},
+    fiber,
     import::IsExport,
+    metrics,
     types::{FuncSig, NativeWasmType, Type, WasmExternType},
     vm,
 };
+use lazy_static::lazy_static;
 use std::{
     any::Any,
+    collections::HashMap,
     convert::Infallible,
     ffi::c_void,
     fmt,
+    future::Future,
     marker::PhantomData,
     mem, panic,
     ptr::{self, NonNull},
-    sync::Arc,
+    sync::{Arc, RwLock},
 };
 
 /// Wasm trap info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(C)]
 pub enum WasmTrapInfo {
     /// Unreachable trap.
@@ -57,6 +64,50 @@ impl fmt::Display for WasmTrapInfo {
     }
 }
 
+impl WasmTrapInfo {
+    const ALL: [WasmTrapInfo; 7] = [
+        WasmTrapInfo::Unreachable,
+        WasmTrapInfo::IncorrectCallIndirectSignature,
+        WasmTrapInfo::MemoryOutOfBounds,
+        WasmTrapInfo::CallIndirectOOB,
+        WasmTrapInfo::IllegalArithmetic,
+        WasmTrapInfo::MisalignedAtomicAccess,
+        WasmTrapInfo::Unknown,
+    ];
+
+    /// A stable name of the trap for tools, e.g. `memory_out_of_bounds`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            WasmTrapInfo::Unreachable => "unreachable",
+            WasmTrapInfo::IncorrectCallIndirectSignature => "incorrect_call_indirect_signature",
+            WasmTrapInfo::MemoryOutOfBounds => "memory_out_of_bounds",
+            WasmTrapInfo::CallIndirectOOB => "call_indirect_out_of_bounds",
+            WasmTrapInfo::IllegalArithmetic => "illegal_arithmetic",
+            WasmTrapInfo::MisalignedAtomicAccess => "misaligned_atomic_access",
+            WasmTrapInfo::Unknown => "unknown",
+        }
+    }
+
+    /// The trap whose discriminant is `code`, or `WasmTrapInfo::Unknown`.
+    pub(crate) fn from_code(code: u32) -> WasmTrapInfo {
+        Self::ALL
+            .iter()
+            .cloned()
+            .find(|trap| *trap as u32 == code)
+            .unwrap_or(WasmTrapInfo::Unknown)
+    }
+
+    /// The trap a `RuntimeError::Trap` message describes, or
+    /// `WasmTrapInfo::Unknown`.
+    pub fn from_message(msg: &str) -> WasmTrapInfo {
+        Self::ALL
+            .iter()
+            .cloned()
+            .find(|trap| trap.to_string() == msg)
+            .unwrap_or(WasmTrapInfo::Unknown)
+    }
+}
+
 /// This is just an empty trait to constrict that types that
 /// can be put into the third/fourth (depending if you include lifetimes)
 /// of the `Func` struct.
@@ -105,12 +156,153 @@ impl Wasm {
             invoke_env,
         }
     }
+
+    /// Calls `func` of `vmctx`, whose signature is the one of this
+    /// trampoline, with `args`, writing its results to `rets`.
+    ///
+    /// Backends that don't generate machine code call the functions of
+    /// modules compiled by other backends this way.
+    pub unsafe fn call(
+        &self,
+        vmctx: *mut vm::Ctx,
+        func: NonNull<vm::Func>,
+        args: &[u64],
+        rets: &mut [u64],
+    ) -> Result<(), RuntimeError> {
+        let mut trap = WasmTrapInfo::Unknown;
+        let mut user_error = None;
+        let success = (self.invoke)(
+            self.trampoline,
+            vmctx,
+            func,
+            args.as_ptr(),
+            rets.as_mut_ptr(),
+            &mut trap,
+            &mut user_error,
+            self.invoke_env,
+        );
+        match user_error {
+            _ if success => Ok(()),
+            Some(data) => Err(RuntimeError::Error { data }),
+            None => Err(RuntimeError::Trap {
+                msg: trap.to_string().into(),
+            }),
+        }
+    }
 }
 
 /// This type, as part of the `Func` type signature, represents a function that is created
 /// by the host.
 pub struct Host(());
 
+/// Calls a host function created with [`Func::new`] from Rust rather than
+/// from native code: `vmctx` is the `vm::Ctx` the backends would pass it,
+/// `args` are its arguments and `rets` receives its results, each value being
+/// its bits zero-extended to a `u64`.
+///
+/// The error the function returns, or its panic, is returned instead of
+/// being trapped with, so nothing unwinds through native frames. Backends
+/// that don't generate machine code call host functions this way, rather
+/// than calling their native `wrap` function through a guessed signature.
+pub type HostCall = unsafe fn(
+    vmctx: *mut vm::Ctx,
+    args: &[u64],
+    rets: &mut [u64],
+) -> Result<(), Box<dyn Any + Send>>;
+
+lazy_static! {
+    /// The `HostCall` of each host function, by the address of its `wrap`
+    /// function, registered when the function is created.
+    static ref HOST_CALLS: RwLock<HashMap<usize, HostCall>> = RwLock::new(HashMap::new());
+}
+
+/// The `HostCall` of the host function `func` points to, if it was created
+/// with [`Func::new`] (or `func!`).
+pub fn host_call(func: *const vm::Func) -> Option<HostCall> {
+    HOST_CALLS.read().unwrap().get(&(func as usize)).copied()
+}
+
+fn register_host_call(func: *const vm::Func, host_call: HostCall) {
+    let address = func as usize;
+    if !HOST_CALLS.read().unwrap().contains_key(&address) {
+        HOST_CALLS.write().unwrap().insert(address, host_call);
+    }
+}
+
+/// Counts a call to a host function for `Instance::stats`, on the calling
+/// instance.
+unsafe fn count_host_call(vmctx: &vm::Ctx) {
+    (*vmctx.local_backing).host_calls += 1;
+    if let Some(metrics) = metrics::of_ctx(vmctx) {
+        metrics.host_call();
+    }
+}
+
+/// The `vm::Ctx` to pass to the host function `FN`, and the function, given
+/// the `vmctx` the backends call its `wrap` function with.
+unsafe fn host_func<'a, FN>(
+    vmctx: &'a vm::Ctx,
+    wrap: *const vm::Func,
+) -> (&'a mut vm::Ctx, &'a FN) {
+    if mem::size_of::<FN>() == 0 {
+        // Fast path: `FN` is a function pointer or a closure without a
+        // captured environment, so there's nothing to look up.  The
+        // backends pass the `vmctx` of the `vm::FuncCtx` of the import,
+        // and a zero-sized `FN` can be read from any aligned pointer.
+        return (
+            &mut *(vmctx as *const vm::Ctx as *mut vm::Ctx),
+            &*NonNull::<FN>::dangling().as_ptr(),
+        );
+    }
+
+    // Get the collection of imported functions.
+    let vm_imported_functions = &(*vmctx.import_backing).vm_functions;
+
+    // Retrieve the `vm::FuncCtx`.
+    let mut func_ctx: NonNull<vm::FuncCtx> = vm_imported_functions
+        .iter()
+        .find_map(|(_, imported_func)| {
+            if imported_func.func == wrap {
+                Some(imported_func.func_ctx)
+            } else {
+                None
+            }
+        })
+        .expect("Import backing is not well-formed, cannot find `func_ctx`.");
+    let func_ctx = func_ctx.as_mut();
+
+    // Extract `vm::Ctx` from `vm::FuncCtx`. The pointer is always
+    // non-null.
+    let vmctx = func_ctx.vmctx.as_mut();
+
+    // Extract `vm::FuncEnv` from `vm::FuncCtx`.
+    let func = match func_ctx.func_env {
+        // The imported function is a closure with a captured environment.
+        Some(func_env) => &*func_env.cast::<FN>().as_ptr(),
+
+        // This branch is supposed to be unreachable.
+        None => unreachable!(),
+    };
+
+    (vmctx, func)
+}
+
+/// Runs a host function, catching the error it returns or its panic.
+fn catch_host_error<Rets, Trap>(func: impl FnOnce() -> Trap) -> Result<Rets, Box<dyn Any + Send>>
+where
+    Rets: WasmTypeList,
+    Trap: TrapEarly<Rets>,
+{
+    match panic::catch_unwind(panic::AssertUnwindSafe(|| func().report())) {
+        Ok(Ok(returns)) => Ok(returns),
+        Ok(Err(err)) => {
+            let b: Box<_> = err.into();
+            Err(b as Box<dyn Any + Send>)
+        }
+        Err(err) => Err(err),
+    }
+}
+
 impl Kind for Wasm {}
 impl Kind for Host {}
 
@@ -125,6 +317,9 @@ pub trait WasmTypeList {
     /// Construct `Self` based on an array of returned values.
     fn from_ret_array(array: Self::RetArray) -> Self;
 
+    /// Transforms `self` into an array of returned values.
+    fn into_ret_array(self) -> Self::RetArray;
+
     /// Generates an empty array that will hold the returned values of
     /// the WebAssembly function.
     fn empty_ret_array() -> Self::RetArray;
@@ -313,6 +508,10 @@ impl WasmTypeList for Infallible {
         unreachable!()
     }
 
+    fn into_ret_array(self) -> Self::RetArray {
+        unreachable!()
+    }
+
     fn empty_ret_array() -> Self::RetArray {
         unreachable!()
     }
@@ -343,11 +542,48 @@ impl WasmTypeList for Infallible {
     }
 }
 
+/// A value of the `CStruct` of a tuple, aligned to 8 bytes so each value gets
+/// its own eightbyte.
+///
+/// Host functions return their `CStruct`. On System V targets, a struct of
+/// two `i32`s or two `f32`s would be packed into a single register, whereas
+/// the backends expect a function returning two values to return them in two
+/// registers, as they do with two `i64`s or `f64`s.
+#[repr(C, align(8))]
+struct RetSlot<T>(T);
+
+/// The type of a value in the `CStruct` of a typed func: a single value is
+/// returned as is, the values of larger tuples each get a `RetSlot`.
+macro_rules! c_struct_field {
+    (transparent, $x:ident) => { <$x as WasmExternType>::Native };
+    (C, $x:ident) => { RetSlot<<$x as WasmExternType>::Native> };
+}
+
+/// Reads a native value out of a `c_struct_field!`.
+macro_rules! from_c_struct_field {
+    (transparent, $value:expr) => {
+        $value
+    };
+    (C, $value:expr) => {
+        $value.0
+    };
+}
+
+/// Makes a `c_struct_field!` of a native value.
+macro_rules! into_c_struct_field {
+    (transparent, $value:expr) => {
+        $value
+    };
+    (C, $value:expr) => {
+        RetSlot($value)
+    };
+}
+
 macro_rules! impl_traits {
     ( [$repr:ident] $struct_name:ident, $( $x:ident ),* ) => {
         /// Struct for typed funcs.
         #[repr($repr)]
-        pub struct $struct_name< $( $x ),* > ( $( <$x as WasmExternType>::Native ),* )
+        pub struct $struct_name< $( $x ),* > ( $( c_struct_field!($repr, $x) ),* )
         where
             $( $x: WasmExternType ),*;
 
@@ -366,6 +602,13 @@ macro_rules! impl_traits {
                 ( $( WasmExternType::from_native(NativeWasmType::from_binary($x)) ),* )
             }
 
+            #[allow(unused_parens, non_snake_case)]
+            fn into_ret_array(self) -> Self::RetArray {
+                let ( $( $x ),* ) = self;
+
+                [ $( WasmExternType::to_native($x).to_binary() ),* ]
+            }
+
             fn empty_ret_array() -> Self::RetArray {
                 [0; count_idents!( $( $x ),* )]
             }
@@ -374,14 +617,14 @@ macro_rules! impl_traits {
                 #[allow(non_snake_case)]
                 let $struct_name ( $( $x ),* ) = c_struct;
 
-                ( $( WasmExternType::from_native($x) ),* )
+                ( $( WasmExternType::from_native(from_c_struct_field!($repr, $x)) ),* )
             }
 
             #[allow(unused_parens, non_snake_case)]
             fn into_c_struct(self) -> Self::CStruct {
                 let ( $( $x ),* ) = self;
 
-                $struct_name ( $( WasmExternType::to_native($x) ),* )
+                $struct_name ( $( into_c_struct_field!($repr, WasmExternType::to_native($x)) ),* )
             }
 
             fn types() -> &'static [Type] {
@@ -404,21 +647,30 @@ macro_rules! impl_traits {
                 let mut trap = WasmTrapInfo::Unknown;
                 let mut user_error = None;
 
-                if (wasm.invoke)(
-                    wasm.trampoline,
-                    ctx,
-                    f,
-                    args.as_ptr(),
-                    rets.as_mut().as_mut_ptr(),
-                    &mut trap,
-                    &mut user_error,
-                    wasm.invoke_env
-                ) {
+                let success = fiber::on_instance_stack(ctx, || {
+                    (wasm.invoke)(
+                        wasm.trampoline,
+                        ctx,
+                        f,
+                        args.as_ptr(),
+                        rets.as_mut().as_mut_ptr(),
+                        &mut trap,
+                        &mut user_error,
+                        wasm.invoke_env
+                    )
+                });
+
+                if success {
                     Ok(Rets::from_ret_array(rets))
                 } else {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(trap = %trap, user_error = user_error.is_some(), "trapped");
                     if let Some(data) = user_error {
                         Err(RuntimeError::Error { data })
                     } else {
+                        if let Some(metrics) = metrics::of_ctx(ctx) {
+                            metrics.trapped(&trap);
+                        }
                         Err(RuntimeError::Trap { msg: trap.to_string().into() })
                     }
                 }
@@ -430,7 +682,7 @@ macro_rules! impl_traits {
             $( $x: WasmExternType, )*
             Rets: WasmTypeList,
             Trap: TrapEarly<Rets>,
-            FN: Fn(&mut vm::Ctx $( , $x )*) -> Trap + 'static,
+            FN: Fn(&mut vm::Ctx, $( $x, )*) -> Trap + 'static,
         {
             #[allow(non_snake_case)]
             fn to_raw(self) -> (NonNull<vm::Func>, Option<NonNull<vm::FuncEnv>>) {
@@ -452,73 +704,57 @@ macro_rules! impl_traits {
                     Trap: TrapEarly<Rets>,
                     FN: Fn(&mut vm::Ctx, $( $x, )*) -> Trap,
                 {
-                    // Get the pointer to this `wrap` function.
-                    let self_pointer = wrap::<$( $x, )* Rets, Trap, FN> as *const vm::Func;
-
-                    // Get the collection of imported functions.
-                    let vm_imported_functions = unsafe { &(*vmctx.import_backing).vm_functions };
-
-                    // Retrieve the `vm::FuncCtx`.
-                    let mut func_ctx: NonNull<vm::FuncCtx> = vm_imported_functions
-                        .iter()
-                        .find_map(|(_, imported_func)| {
-                            if imported_func.func == self_pointer {
-                                Some(imported_func.func_ctx)
-                            } else {
-                                None
-                            }
-                        })
-                        .expect("Import backing is not well-formed, cannot find `func_ctx`.");
-                    let func_ctx = unsafe { func_ctx.as_mut() };
-
-                    // Extract `vm::Ctx` from `vm::FuncCtx`. The
-                    // pointer is always non-null.
-                    let vmctx = unsafe { func_ctx.vmctx.as_mut() };
-
-                    // Extract `vm::FuncEnv` from `vm::FuncCtx`.
-                    let func_env = func_ctx.func_env;
-
-                    let func: &FN = match func_env {
-                        // The imported function is a regular
-                        // function, a closure without a captured
-                        // environment, or a closure with a captured
-                        // environment.
-                        Some(func_env) => unsafe {
-                            let func: NonNull<FN> = func_env.cast();
-
-                            &*func.as_ptr()
-                        },
-
-                        // This branch is supposed to be unreachable.
-                        None => unreachable!()
+                    unsafe { count_host_call(vmctx) };
+                    let (vmctx, func) = unsafe {
+                        host_func::<FN>(vmctx, wrap::<$( $x, )* Rets, Trap, FN> as *const vm::Func)
                     };
 
-                    // Catch unwind in case of errors.
-                    let err = match panic::catch_unwind(
-                        panic::AssertUnwindSafe(
-                            || {
-                                func(vmctx $( , WasmExternType::from_native($x) )* ).report()
-                                //   ^^^^^ The imported function
-                                //         expects `vm::Ctx` as first
-                                //         argument; provide it.
-                            }
-                        )
-                    ) {
-                        Ok(Ok(returns)) => return returns.into_c_struct(),
-                        Ok(Err(err)) => {
-                            let b: Box<_> = err.into();
-                            b as Box<dyn Any + Send>
-                        },
-                        Err(err) => err,
-                    };
+                    let result: Result<Rets, _> =
+                        catch_host_error(|| func(vmctx, $( WasmExternType::from_native($x), )* ));
+                    match result {
+                        Ok(returns) => returns.into_c_struct(),
 
-                    // At this point, there is an error that needs to
-                    // be trapped.
-                    unsafe {
-                        (&*vmctx.module).runnable_module.do_early_trap(err)
+                        // At this point, there is an error that needs
+                        // to be trapped.
+                        Err(err) => unsafe {
+                            (&*vmctx.module).runnable_module.do_early_trap(err)
+                        },
                     }
                 }
 
+                // The `HostCall` of `wrap`, for the backends that
+                // don't call it natively.
+                #[allow(unused_parens, non_snake_case)]
+                unsafe fn call_host<$( $x, )* Rets, Trap, FN>(
+                    vmctx: *mut vm::Ctx,
+                    args: &[u64],
+                    rets: &mut [u64],
+                ) -> Result<(), Box<dyn Any + Send>>
+                where
+                    $( $x: WasmExternType, )*
+                    Rets: WasmTypeList,
+                    Trap: TrapEarly<Rets>,
+                    FN: Fn(&mut vm::Ctx, $( $x, )*) -> Trap,
+                {
+                    let vmctx = &*vmctx;
+                    count_host_call(vmctx);
+                    let (vmctx, func) =
+                        host_func::<FN>(vmctx, wrap::<$( $x, )* Rets, Trap, FN> as *const vm::Func);
+
+                    let mut array = <( $( $x ),* ) as WasmTypeList>::empty_ret_array();
+                    array.as_mut().copy_from_slice(args);
+                    let ( $( $x ),* ) = <( $( $x ),* ) as WasmTypeList>::from_ret_array(array);
+
+                    let returns: Rets = catch_host_error(|| func(vmctx, $( $x, )* ))?;
+                    rets.copy_from_slice(returns.into_ret_array().as_mut());
+                    Ok(())
+                }
+
+                register_host_call(
+                    wrap::<$( $x, )* Rets, Trap, Self> as *const vm::Func,
+                    call_host::<$( $x, )* Rets, Trap, Self>,
+                );
+
                 // Extract the captured environment of the imported
                 // function if any.
                 let func_env: Option<NonNull<vm::FuncEnv>> =
@@ -567,70 +803,57 @@ macro_rules! impl_traits {
                     Trap: TrapEarly<Rets>,
                     FN: Fn($( $x, )*) -> Trap,
                 {
-                    // Get the pointer to this `wrap` function.
-                    let self_pointer = wrap::<$( $x, )* Rets, Trap, FN> as *const vm::Func;
-
-                    // Get the collection of imported functions.
-                    let vm_imported_functions = unsafe { &(*vmctx.import_backing).vm_functions };
-
-                    // Retrieve the `vm::FuncCtx`.
-                    let mut func_ctx: NonNull<vm::FuncCtx> = vm_imported_functions
-                        .iter()
-                        .find_map(|(_, imported_func)| {
-                            if imported_func.func == self_pointer {
-                                Some(imported_func.func_ctx)
-                            } else {
-                                None
-                            }
-                        })
-                        .expect("Import backing is not well-formed, cannot find `func_ctx`.");
-                    let func_ctx = unsafe { func_ctx.as_mut() };
-
-                    // Extract `vm::Ctx` from `vm::FuncCtx`. The
-                    // pointer is always non-null.
-                    let vmctx = unsafe { func_ctx.vmctx.as_mut() };
-
-                    // Extract `vm::FuncEnv` from `vm::FuncCtx`.
-                    let func_env = func_ctx.func_env;
-
-                    let func: &FN = match func_env {
-                        // The imported function is a regular
-                        // function, a closure without a captured
-                        // environment, or a closure with a captured
-                        // environment.
-                        Some(func_env) => unsafe {
-                            let func: NonNull<FN> = func_env.cast();
-
-                            &*func.as_ptr()
-                        },
-
-                        // This branch is supposed to be unreachable.
-                        None => unreachable!()
+                    unsafe { count_host_call(vmctx) };
+                    let (vmctx, func) = unsafe {
+                        host_func::<FN>(vmctx, wrap::<$( $x, )* Rets, Trap, FN> as *const vm::Func)
                     };
 
-                    // Catch unwind in case of errors.
-                    let err = match panic::catch_unwind(
-                        panic::AssertUnwindSafe(
-                            || {
-                                func($( WasmExternType::from_native($x), )* ).report()
-                            }
-                        )
-                    ) {
-                        Ok(Ok(returns)) => return returns.into_c_struct(),
-                        Ok(Err(err)) => {
-                            let b: Box<_> = err.into();
-                            b as Box<dyn Any + Send>
-                        },
-                        Err(err) => err,
-                    };
+                    let result: Result<Rets, _> =
+                        catch_host_error(|| func($( WasmExternType::from_native($x), )* ));
+                    match result {
+                        Ok(returns) => returns.into_c_struct(),
 
-                    // At this point, there is an error that needs to
-                    // be trapped.
-                    unsafe {
-                        (&*vmctx.module).runnable_module.do_early_trap(err)
+                        // At this point, there is an error that needs
+                        // to be trapped.
+                        Err(err) => unsafe {
+                            (&*vmctx.module).runnable_module.do_early_trap(err)
+                        },
                     }
                 }
 
+                // The `HostCall` of `wrap`, for the backends that
+                // don't call it natively.
+                #[allow(unused_parens, non_snake_case)]
+                unsafe fn call_host<$( $x, )* Rets, Trap, FN>(
+                    vmctx: *mut vm::Ctx,
+                    args: &[u64],
+                    rets: &mut [u64],
+                ) -> Result<(), Box<dyn Any + Send>>
+                where
+                    $( $x: WasmExternType, )*
+                    Rets: WasmTypeList,
+                    Trap: TrapEarly<Rets>,
+                    FN: Fn($( $x, )*) -> Trap,
+                {
+                    let vmctx = &*vmctx;
+                    count_host_call(vmctx);
+                    let (_, func) =
+                        host_func::<FN>(vmctx, wrap::<$( $x, )* Rets, Trap, FN> as *const vm::Func);
+
+                    let mut array = <( $( $x ),* ) as WasmTypeList>::empty_ret_array();
+                    array.as_mut().copy_from_slice(args);
+                    let ( $( $x ),* ) = <( $( $x ),* ) as WasmTypeList>::from_ret_array(array);
+
+                    let returns: Rets = catch_host_error(|| func($( $x, )* ))?;
+                    rets.copy_from_slice(returns.into_ret_array().as_mut());
+                    Ok(())
+                }
+
+                register_host_call(
+                    wrap::<$( $x, )* Rets, Trap, Self> as *const vm::Func,
+                    call_host::<$( $x, )* Rets, Trap, Self>,
+                );
+
                 // Extract the captured environment of the imported
                 // function if any.
                 let func_env: Option<NonNull<vm::FuncEnv>> =
@@ -671,6 +894,31 @@ macro_rules! impl_traits {
                 }
             }
         }
+
+        impl<'a $( , $x )*, Rets> Func<'a, ( $( $x ),* ), Rets, Host>
+        where
+            $( $x: WasmExternType + 'static, )*
+            Rets: WasmTypeList + 'static,
+        {
+            /// Creates a new `Func` from an async host function.
+            ///
+            /// The guest is parked while the function's future runs, and
+            /// the future is awaited by [`Instance::call_async`]. The module
+            /// must have been transformed by Asyncify, see the
+            /// [`asyncify`](crate::asyncify) module.
+            ///
+            /// [`Instance::call_async`]: crate::instance::Instance::call_async
+            #[allow(non_snake_case)]
+            pub fn new_async<FN, Fut>(func: FN) -> Func<'a, ( $( $x ),* ), Rets, Host>
+            where
+                FN: Fn(&mut vm::Ctx $( , $x )*) -> Fut + 'static,
+                Fut: Future<Output = Rets> + 'static,
+            {
+                Func::new(move |ctx: &mut vm::Ctx $( , $x: $x )*| {
+                    asyncify::call_async_host_func(ctx, |ctx| func(ctx $( , $x )*))
+                })
+            }
+        }
     };
 }
 
@@ -815,6 +1063,24 @@ mod tests {
         let _f = Func::new(foo);
     }
 
+    #[test]
+    fn test_tuple_c_structs() {
+        // Each value of a tuple gets its own eightbyte, so that two values
+        // are returned in two registers.
+        assert_eq!(mem::size_of::<<(i32, i32) as WasmTypeList>::CStruct>(), 16);
+        assert_eq!(mem::size_of::<<(f32, i64) as WasmTypeList>::CStruct>(), 16);
+        assert_eq!(mem::size_of::<<f32 as WasmTypeList>::CStruct>(), 4);
+
+        let values = (1i32, -2i64, 3.5f32, 4.25f64);
+        assert_eq!(
+            <(i32, i64, f32, f64)>::from_c_struct(values.into_c_struct()),
+            values
+        );
+
+        let rets = <(i32, f32)>::from_ret_array([7, 1.5f32.to_bits() as u64]);
+        assert_eq!(rets, (7, 1.5));
+    }
+
     #[test]
     fn test_imports() {
         use crate::{func, imports};
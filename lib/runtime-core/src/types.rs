@@ -1,8 +1,17 @@
 //! The runtime types modules represent type used within the wasm runtime and helper functions to
 //! convert to other represenations.
 
-use crate::{memory::MemoryType, module::ModuleInfo, structures::TypedIndex, units::Pages};
-use std::borrow::Cow;
+use crate::{
+    backend::MemoryStyle,
+    export::{Context, Export},
+    memory::MemoryType,
+    module::ModuleInfo,
+    sig_registry::SigRegistry,
+    structures::TypedIndex,
+    units::Pages,
+    vm,
+};
+use std::{borrow::Cow, sync::Arc};
 
 /// Represents a WebAssembly type.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -97,6 +106,123 @@ impl From<u128> for Value {
     }
 }
 
+/// A reference to a function, as stored in an `anyfunc` table.
+///
+/// Table elements aren't values the functions of a module can take or return,
+/// so they're not a [`Value`] but are read and written with [`Table::get`] and
+/// [`Table::set`].
+///
+/// [`Value`]: enum.Value.html
+/// [`Table::get`]: ../table/struct.Table.html#method.get
+/// [`Table::set`]: ../table/struct.Table.html#method.set
+#[derive(Debug, Clone, Copy)]
+pub struct FuncRef(pub(crate) vm::Anyfunc);
+
+// Manually implemented because `vm::Anyfunc` contains raw pointers
+unsafe impl Send for FuncRef {}
+unsafe impl Sync for FuncRef {}
+
+impl FuncRef {
+    /// A null function reference.
+    pub fn null() -> Self {
+        FuncRef(vm::Anyfunc::null())
+    }
+
+    /// Returns true if this is a null function reference.
+    pub fn is_null(&self) -> bool {
+        self.0.func.is_null()
+    }
+
+    /// Creates a function reference from a function exported by an instance,
+    /// called with the `Ctx` of that instance.
+    ///
+    /// Returns `None` if `export` is not a function, or is a host function not
+    /// bound to an instance, see [`FuncRef::from_host_func`].
+    pub fn from_export(export: &Export) -> Option<Self> {
+        match export {
+            Export::Function {
+                func,
+                ctx: Context::External(ctx),
+                signature,
+            }
+            | Export::Function {
+                func,
+                ctx: Context::ExternalWithEnv(ctx, _),
+                signature,
+            } if !ctx.is_null() => Some(Self::bound(func.inner(), *ctx, signature)),
+            _ => None,
+        }
+    }
+
+    /// Creates a function reference from a host function, called with the `Ctx`
+    /// of the instance `ctx` like the imports of that instance.
+    ///
+    /// A `Func` created from Rust finds its captured environment through the
+    /// imports of the instance it's called with, so it must be one of them.
+    /// Functions exported by an instance are already bound to it, as with
+    /// [`FuncRef::from_export`].
+    ///
+    /// Returns `None` if `export` is not a function, or is a `Func` the instance
+    /// doesn't import.
+    pub fn from_host_func(export: &Export, ctx: &mut vm::Ctx) -> Option<Self> {
+        if let Some(func_ref) = Self::from_export(export) {
+            return Some(func_ref);
+        }
+
+        match export {
+            Export::Function {
+                func,
+                ctx: Context::Internal,
+                signature,
+            } => Some(Self::bound(func.inner(), ctx, signature)),
+            Export::Function {
+                func,
+                ctx: Context::ExternalWithEnv(..),
+                signature,
+            } => {
+                let imported_functions = unsafe { &(*ctx.import_backing).vm_functions };
+                if imported_functions
+                    .iter()
+                    .any(|(_, imported_func)| imported_func.func == func.inner())
+                {
+                    Some(Self::bound(func.inner(), ctx, signature))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn bound(func: *const vm::Func, ctx: *mut vm::Ctx, signature: &Arc<FuncSig>) -> Self {
+        let sig_index = SigRegistry.lookup_sig_index(Arc::clone(signature));
+
+        FuncRef(vm::Anyfunc {
+            func,
+            ctx,
+            sig_id: vm::SigId(sig_index.index() as u32),
+        })
+    }
+
+    /// Returns the signature of the referenced function, or `None` if this is a
+    /// null function reference.
+    pub fn signature(&self) -> Option<Arc<FuncSig>> {
+        if self.is_null() {
+            None
+        } else {
+            Some(SigRegistry.lookup_signature(SigIndex::new(self.0.sig_id.0 as usize)))
+        }
+    }
+}
+
+impl PartialEq for FuncRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.func == other.0.func
+            && self.0.ctx == other.0.ctx
+            && self.0.sig_id.0 == other.0.sig_id.0
+    }
+}
+
 /// Represents a native wasm type.
 pub unsafe trait NativeWasmType: Copy + Into<Value>
 where
@@ -384,13 +510,31 @@ impl MemoryDescriptor {
         self.memory_type
     }
 
+    /// Returns this descriptor with the `MemoryType` chosen by `style`.
+    /// Shared memories stay static.
+    pub fn with_style(mut self, style: MemoryStyle) -> Self {
+        if !self.shared {
+            match style {
+                MemoryStyle::Default => {}
+                MemoryStyle::Static => self.memory_type = MemoryType::Static,
+                MemoryStyle::Dynamic => self.memory_type = MemoryType::Dynamic,
+            }
+        }
+        self
+    }
+
     pub(crate) fn fits_in_imported(&self, imported: MemoryDescriptor) -> bool {
         let imported_max = imported.maximum.unwrap_or(Pages(65_536));
         let self_max = self.maximum.unwrap_or(Pages(65_536));
 
+        // code compiled for a static memory doesn't bounds-check its accesses
+        let fits_memory_type =
+            self.memory_type == MemoryType::Dynamic || imported.memory_type != MemoryType::Dynamic;
+
         self.shared == imported.shared
             && imported_max <= self_max
             && self.minimum <= imported.minimum
+            && fits_memory_type
     }
 }
 
@@ -454,6 +598,20 @@ impl std::fmt::Display for FuncSig {
     }
 }
 
+/// The type of an export: the signature of a function, or the descriptor
+/// of a memory, table or global.
+#[derive(Debug, Clone)]
+pub enum ExternType {
+    /// A function with this signature.
+    Function(FuncSig),
+    /// A memory.
+    Memory(MemoryDescriptor),
+    /// A table.
+    Table(TableDescriptor),
+    /// A global.
+    Global(GlobalDescriptor),
+}
+
 /// Trait that represents Local or Import.
 pub trait LocalImport {
     /// Local type.
@@ -2,16 +2,17 @@
 //! execute wasm instance functions.
 pub use crate::backing::{ImportBacking, LocalBacking, INTERNALS_SIZE};
 use crate::{
-    error::CallResult,
-    instance::call_func_with_index_inner,
+    error::{CallError, CallResult, ResolveError},
+    instance::{call_func_with_index, call_func_with_index_inner},
     memory::{Memory, MemoryType},
-    module::{ModuleInfo, ModuleInner},
+    module::{ExportIndex, ModuleInfo, ModuleInner},
     sig_registry::SigRegistry,
     structures::TypedIndex,
     types::{LocalOrImport, MemoryIndex, TableIndex, Value},
     vmcalls,
 };
 use std::{
+    any::Any,
     cell::UnsafeCell,
     ffi::c_void,
     mem,
@@ -65,6 +66,10 @@ pub struct Ctx {
     /// when the context is destructed, e.g. when an `Instance`
     /// is dropped.
     pub data_finalizer: Option<fn(data: *mut c_void)>,
+
+    /// The typed host data of the instance, see [`Ctx::data`].  It's
+    /// dropped with the context.
+    pub(crate) typed_data: Option<Box<dyn Any + Send>>,
 }
 
 /// When an instance context is destructed, we're calling its `data_finalizer`
@@ -81,6 +86,17 @@ impl Drop for Ctx {
     }
 }
 
+/// The first memory of the instance, for the methods of `WasmPtr`.
+///
+/// # Panics
+///
+/// Panics if the instance has no memory.
+impl AsRef<Memory> for Ctx {
+    fn as_ref(&self) -> &Memory {
+        self.memory(0)
+    }
+}
+
 /// The internal context of the currently running WebAssembly instance.
 ///
 ///
@@ -173,6 +189,12 @@ impl InternalField {
     }
 }
 
+/// The internal field holding the points used by the metering middleware,
+/// reported by [`Instance::stats`].
+///
+/// [`Instance::stats`]: ../instance/struct.Instance.html#method.stats
+pub static METERING_POINTS_USED: InternalField = InternalField::allocate();
+
 /// A container for VM instrinsic functions
 #[repr(C)]
 pub struct Intrinsics {
@@ -180,6 +202,10 @@ pub struct Intrinsics {
     pub memory_grow: *const Func,
     /// Const pointer to memory size `Func`.
     pub memory_size: *const Func,
+    /// Const pointer to memory copy `Func`.
+    pub memory_copy: *const Func,
+    /// Const pointer to memory fill `Func`.
+    pub memory_fill: *const Func,
     /*pub memory_grow: unsafe extern "C" fn(
         ctx: &mut Ctx,
         memory_index: usize,
@@ -204,27 +230,43 @@ impl Intrinsics {
     pub const fn offset_memory_size() -> u8 {
         (1 * ::std::mem::size_of::<usize>()) as u8
     }
+    /// Offset of the `memory_copy` field.
+    pub const fn offset_memory_copy() -> u8 {
+        (2 * ::std::mem::size_of::<usize>()) as u8
+    }
+    /// Offset of the `memory_fill` field.
+    pub const fn offset_memory_fill() -> u8 {
+        (3 * ::std::mem::size_of::<usize>()) as u8
+    }
 }
 
 /// Local static memory intrinsics
 pub static INTRINSICS_LOCAL_STATIC_MEMORY: Intrinsics = Intrinsics {
     memory_grow: vmcalls::local_static_memory_grow as _,
     memory_size: vmcalls::local_static_memory_size as _,
+    memory_copy: vmcalls::local_memory_copy as _,
+    memory_fill: vmcalls::local_memory_fill as _,
 };
 /// Local dynamic memory intrinsics
 pub static INTRINSICS_LOCAL_DYNAMIC_MEMORY: Intrinsics = Intrinsics {
     memory_grow: vmcalls::local_dynamic_memory_grow as _,
     memory_size: vmcalls::local_dynamic_memory_size as _,
+    memory_copy: vmcalls::local_memory_copy as _,
+    memory_fill: vmcalls::local_memory_fill as _,
 };
 /// Imported static memory intrinsics
 pub static INTRINSICS_IMPORTED_STATIC_MEMORY: Intrinsics = Intrinsics {
     memory_grow: vmcalls::imported_static_memory_grow as _,
     memory_size: vmcalls::imported_static_memory_size as _,
+    memory_copy: vmcalls::imported_memory_copy as _,
+    memory_fill: vmcalls::imported_memory_fill as _,
 };
 /// Imported dynamic memory intrinsics
 pub static INTRINSICS_IMPORTED_DYNAMIC_MEMORY: Intrinsics = Intrinsics {
     memory_grow: vmcalls::imported_dynamic_memory_grow as _,
     memory_size: vmcalls::imported_dynamic_memory_size as _,
+    memory_copy: vmcalls::imported_memory_copy as _,
+    memory_fill: vmcalls::imported_memory_fill as _,
 };
 
 fn get_intrinsics_for_module(m: &ModuleInfo) -> *const Intrinsics {
@@ -312,6 +354,7 @@ impl Ctx {
 
             data: ptr::null_mut(),
             data_finalizer: None,
+            typed_data: None,
         }
     }
 
@@ -365,6 +408,7 @@ impl Ctx {
 
             data,
             data_finalizer: Some(data_finalizer),
+            typed_data: None,
         }
     }
 
@@ -401,6 +445,15 @@ impl Ctx {
         }
     }
 
+    /// The number of memories of the WebAssembly instance, imported and
+    /// local; [`Ctx::memory`] panics for indices past this.
+    ///
+    /// This is at most 1, since modules with multiple memories are rejected.
+    pub fn memory_count(&self) -> u32 {
+        let info = unsafe { &(*self.module).info };
+        (info.imported_memories.len() + info.memories.len()) as u32
+    }
+
     /// Get access to [`Memory`] and mutable access to the user defined data
     /// field as the type, `T`.
     ///
@@ -416,6 +469,35 @@ impl Ctx {
         (self.memory(mem_index), &mut *(self.data as *mut T))
     }
 
+    /// The typed host data of the instance, given with
+    /// [`InstanceConfig::with_data`] or [`Ctx::set_data`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the instance has no data of type `T`; see [`Ctx::try_data`].
+    ///
+    /// [`InstanceConfig::with_data`]: ../struct.InstanceConfig.html#method.with_data
+    pub fn data<T: Any>(&mut self) -> &mut T {
+        match self.try_data::<T>() {
+            Some(data) => data,
+            None => panic!(
+                "the instance has no host data of type `{}`",
+                std::any::type_name::<T>()
+            ),
+        }
+    }
+
+    /// The typed host data of the instance, or `None` if it has no data of
+    /// type `T`.
+    pub fn try_data<T: Any>(&mut self) -> Option<&mut T> {
+        self.typed_data.as_mut()?.downcast_mut::<T>()
+    }
+
+    /// Sets the typed host data of the instance, dropping the previous one.
+    pub fn set_data<T: Any + Send>(&mut self, data: T) {
+        self.typed_data = Some(Box::new(data));
+    }
+
     /// Gives access to the emscripten symbol map, used for debugging
     pub unsafe fn borrow_symbol_map(&self) -> &Option<HashMap<u32, String>> {
         &(*self.module).info.em_symbol_map
@@ -438,6 +520,37 @@ impl Ctx {
         }
     }
 
+    /// Calls the function the instance exports as `name`, e.g. for a host
+    /// function to allocate in the guest.
+    pub fn call_export(&mut self, name: &str, args: &[Value]) -> CallResult<Vec<Value>> {
+        let module = unsafe { &*self.module };
+        let func_index = match module.info.exports.get(name) {
+            Some(ExportIndex::Func(func_index)) => *func_index,
+            Some(_) => {
+                return Err(CallError::Resolve(ResolveError::ExportWrongType {
+                    name: name.to_string(),
+                }))
+            }
+            None => {
+                return Err(CallError::Resolve(ResolveError::ExportNotFound {
+                    name: name.to_string(),
+                }))
+            }
+        };
+
+        let mut rets = vec![];
+        call_func_with_index(
+            &module.info,
+            &**module.runnable_module,
+            unsafe { &*self.import_backing },
+            self as *mut Ctx,
+            func_index,
+            args,
+            &mut rets,
+        )?;
+        Ok(rets)
+    }
+
     /// Calls a host or Wasm function at the given table index
     pub fn call_with_table_index(
         &mut self,
@@ -1024,6 +1137,11 @@ mod vm_ctx_tests {
             local_functions: Map::new().into_boxed_map(),
 
             internals: crate::backing::Internals([0; crate::backing::INTERNALS_SIZE]),
+
+            host_calls: 0,
+
+            metrics: None,
+            limiter: None,
         };
 
         let mut import_backing = ImportBacking {
@@ -1126,7 +1244,15 @@ mod vm_ctx_tests {
                 em_symbol_map: None,
 
                 custom_sections: HashMap::new(),
+                names: Default::default(),
+
+                triple: None,
+                cpu_features: None,
+                no_signals: false,
+                metered: false,
+                track_state: false,
             },
+            compile_info: None,
         }
     }
 }
@@ -1,12 +1,15 @@
 #![allow(clippy::cast_ptr_alignment)]
 
 use crate::{
+    limits,
     memory::{DynamicMemory, StaticMemory},
+    metrics,
     structures::TypedIndex,
     types::{ImportedMemoryIndex, LocalMemoryIndex, LocalTableIndex},
     units::Pages,
     vm,
 };
+use std::ptr;
 
 // +*****************************+
 // |       LOCAL MEMORIES        |
@@ -20,8 +23,16 @@ pub unsafe extern "C" fn local_static_memory_grow(
     let local_memory = *ctx.internal.memories.add(memory_index.index());
     let memory = (*local_memory).memory as *mut StaticMemory;
 
+    if !limits::allows_memory_growth(ctx, (*memory).size(), delta) {
+        return -1;
+    }
     let ret = match (*memory).grow(delta, &mut *local_memory) {
-        Ok(old) => old.0 as i32,
+        Ok(old) => {
+            if let Some(metrics) = metrics::of_ctx(ctx) {
+                metrics.memory_grown(old, old + delta);
+            }
+            old.0 as i32
+        }
         Err(_) => -1,
     };
 
@@ -49,8 +60,16 @@ pub unsafe extern "C" fn local_dynamic_memory_grow(
     let local_memory = *ctx.internal.memories.add(memory_index.index());
     let memory = (*local_memory).memory as *mut DynamicMemory;
 
+    if !limits::allows_memory_growth(ctx, (*memory).size(), delta) {
+        return -1;
+    }
     let ret = match (*memory).grow(delta, &mut *local_memory) {
-        Ok(old) => old.0 as i32,
+        Ok(old) => {
+            if let Some(metrics) = metrics::of_ctx(ctx) {
+                metrics.memory_grown(old, old + delta);
+            }
+            old.0 as i32
+        }
         Err(_) => -1,
     };
 
@@ -70,6 +89,28 @@ pub unsafe extern "C" fn local_dynamic_memory_size(
     (*memory).size()
 }
 
+pub unsafe extern "C" fn local_memory_copy(
+    ctx: &vm::Ctx,
+    memory_index: LocalMemoryIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) -> i32 {
+    let local_memory = *ctx.internal.memories.add(memory_index.index());
+    memory_copy(&*local_memory, dst, src, len)
+}
+
+pub unsafe extern "C" fn local_memory_fill(
+    ctx: &vm::Ctx,
+    memory_index: LocalMemoryIndex,
+    dst: u32,
+    value: u32,
+    len: u32,
+) -> i32 {
+    let local_memory = *ctx.internal.memories.add(memory_index.index());
+    memory_fill(&*local_memory, dst, value, len)
+}
+
 // +*****************************+
 // |      IMPORTED MEMORIES      |
 // +*****************************+
@@ -85,8 +126,16 @@ pub unsafe extern "C" fn imported_static_memory_grow(
         .add(import_memory_index.index());
     let memory = (*local_memory).memory as *mut StaticMemory;
 
+    if !limits::allows_memory_growth(ctx, (*memory).size(), delta) {
+        return -1;
+    }
     let ret = match (*memory).grow(delta, &mut *local_memory) {
-        Ok(old) => old.0 as i32,
+        Ok(old) => {
+            if let Some(metrics) = metrics::of_ctx(ctx) {
+                metrics.memory_grown(old, old + delta);
+            }
+            old.0 as i32
+        }
         Err(_) => -1,
     };
 
@@ -117,8 +166,16 @@ pub unsafe extern "C" fn imported_dynamic_memory_grow(
     let local_memory = *ctx.internal.imported_memories.add(memory_index.index());
     let memory = (*local_memory).memory as *mut DynamicMemory;
 
+    if !limits::allows_memory_growth(ctx, (*memory).size(), delta) {
+        return -1;
+    }
     let ret = match (*memory).grow(delta, &mut *local_memory) {
-        Ok(old) => old.0 as i32,
+        Ok(old) => {
+            if let Some(metrics) = metrics::of_ctx(ctx) {
+                metrics.memory_grown(old, old + delta);
+            }
+            old.0 as i32
+        }
         Err(_) => -1,
     };
 
@@ -138,6 +195,68 @@ pub unsafe extern "C" fn imported_dynamic_memory_size(
     (*memory).size()
 }
 
+pub unsafe extern "C" fn imported_memory_copy(
+    ctx: &vm::Ctx,
+    import_memory_index: ImportedMemoryIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) -> i32 {
+    let local_memory = *ctx
+        .internal
+        .imported_memories
+        .add(import_memory_index.index());
+    memory_copy(&*local_memory, dst, src, len)
+}
+
+pub unsafe extern "C" fn imported_memory_fill(
+    ctx: &vm::Ctx,
+    import_memory_index: ImportedMemoryIndex,
+    dst: u32,
+    value: u32,
+    len: u32,
+) -> i32 {
+    let local_memory = *ctx
+        .internal
+        .imported_memories
+        .add(import_memory_index.index());
+    memory_fill(&*local_memory, dst, value, len)
+}
+
+// +*****************************+
+// |         BULK MEMORY         |
+// +*****************************+
+
+/// Whether `len` bytes at `offset` are inside `memory`.
+fn in_bounds(memory: &vm::LocalMemory, offset: u32, len: u32) -> bool {
+    u64::from(offset) + u64::from(len) <= memory.bound as u64
+}
+
+/// `memory.copy`, with `memmove` so the ranges may overlap.  Returns 1
+/// without copying anything if either range is out of bounds, for the
+/// caller to trap, and 0 otherwise.
+unsafe fn memory_copy(memory: &vm::LocalMemory, dst: u32, src: u32, len: u32) -> i32 {
+    if !in_bounds(memory, src, len) || !in_bounds(memory, dst, len) {
+        return 1;
+    }
+    ptr::copy(
+        memory.base.add(src as usize),
+        memory.base.add(dst as usize),
+        len as usize,
+    );
+    0
+}
+
+/// `memory.fill`, with `memset`.  Returns 1 without writing anything if
+/// the range is out of bounds, for the caller to trap, and 0 otherwise.
+unsafe fn memory_fill(memory: &vm::LocalMemory, dst: u32, value: u32, len: u32) -> i32 {
+    if !in_bounds(memory, dst, len) {
+        return 1;
+    }
+    ptr::write_bytes(memory.base.add(dst as usize), value as u8, len as usize);
+    0
+}
+
 // +*****************************+
 // |        LOCAL TABLES         |
 // +*****************************+
@@ -0,0 +1,83 @@
+#[macro_use]
+extern crate criterion;
+use criterion::{Criterion, Throughput};
+use wasmer_runtime::{compile, compile_with_config, imports, CompilerConfig, Features, Func};
+
+/// The number of bytes initialized, filled and copied.
+const LEN: u32 = 1 << 20;
+
+fn bulk_memory_wasm() -> Vec<u8> {
+    let mut features = wabt::Features::new();
+    features.enable_bulk_memory();
+    wabt::wat2wasm_with_features(
+        r#"
+        (module
+          (memory 32)
+          (func (export "fill") (param i32 i32 i32)
+            (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+          (func (export "copy") (param i32 i32 i32)
+            (memory.copy (local.get 0) (local.get 1) (local.get 2))))
+        "#,
+        features,
+    )
+    .unwrap()
+}
+
+fn data_segment_wasm() -> Vec<u8> {
+    let data = "\\2a".repeat(LEN as usize);
+    let wat = format!(r#"(module (memory 32) (data (i32.const 0) "{}"))"#, data);
+    wabt::wat2wasm(wat).unwrap()
+}
+
+fn data_initialization_benchmark(c: &mut Criterion) {
+    let module = compile(&data_segment_wasm()).unwrap();
+    let imports = imports! {};
+    c.bench(
+        "bulk memory",
+        criterion::Benchmark::new("instantiate with a 1 MiB data segment", move |b| {
+            b.iter(|| module.instantiate(&imports).unwrap())
+        })
+        .throughput(Throughput::Bytes(LEN)),
+    );
+}
+
+fn fill_and_copy_benchmark(c: &mut Criterion) {
+    let module = compile_with_config(
+        &bulk_memory_wasm(),
+        CompilerConfig {
+            features: Features {
+                bulk_memory: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let fill_instance = module.instantiate(&imports! {}).unwrap();
+    let copy_instance = module.instantiate(&imports! {}).unwrap();
+
+    c.bench(
+        "bulk memory",
+        criterion::Benchmark::new("memory.fill 1 MiB", move |b| {
+            let fill: Func<(u32, u32, u32)> = fill_instance.func("fill").unwrap();
+            b.iter(|| fill.call(0, 0x2a, LEN).unwrap())
+        })
+        .throughput(Throughput::Bytes(LEN)),
+    );
+    // The ranges overlap, so this measures the `memmove` path.
+    c.bench(
+        "bulk memory",
+        criterion::Benchmark::new("overlapping memory.copy 1 MiB", move |b| {
+            let copy: Func<(u32, u32, u32)> = copy_instance.func("copy").unwrap();
+            b.iter(|| copy.call(4096, 0, LEN).unwrap())
+        })
+        .throughput(Throughput::Bytes(LEN)),
+    );
+}
+
+criterion_group! {
+    name = bulk_memory_bench;
+    config = Criterion::default().sample_size(20);
+    targets = data_initialization_benchmark, fill_and_copy_benchmark,
+}
+criterion_main!(bulk_memory_bench);
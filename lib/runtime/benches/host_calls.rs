@@ -0,0 +1,91 @@
+#[macro_use]
+extern crate criterion;
+use criterion::Criterion;
+use wasmer_runtime::{compile, func, Func, ImportObject};
+use wasmer_runtime_core::{import::Namespace, vm::Ctx};
+
+/// The number of host calls made by each call to `run`.
+const CALLS: i32 = 1000;
+
+/// A module importing `count` host functions, and calling the last one
+/// `CALLS` times from `run`.
+fn host_calls_wasm(count: usize) -> Vec<u8> {
+    let imports: String = (0..count)
+        .map(|i| format!(r#"(import "env" "f{}" (func (param i32) (result i32)))"#, i))
+        .collect();
+    let wat = format!(
+        r#"
+        (module
+          {}
+          (func (export "run") (result i32)
+            (local $i i32) (local $sum i32)
+            (block $done
+              (loop $loop
+                (br_if $done (i32.ge_s (local.get $i) (i32.const {})))
+                (local.set $sum (i32.add (local.get $sum) (call {} (local.get $i))))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $loop)))
+            (local.get $sum)))
+        "#,
+        imports,
+        CALLS,
+        count - 1
+    );
+    wabt::wat2wasm(wat).unwrap()
+}
+
+fn double(_ctx: &mut Ctx, n: i32) -> i32 {
+    n * 2
+}
+
+fn host_calls_benchmark(c: &mut Criterion) {
+    // Functions without a captured environment take the fast path, and
+    // closures capturing one look their environment up among the imports.
+    for &count in &[1, 100] {
+        let module = compile(&host_calls_wasm(count)).unwrap();
+
+        let mut functions = Namespace::new();
+        for i in 0..count {
+            functions.insert(format!("f{}", i), func!(double));
+        }
+        let mut import_object = ImportObject::new();
+        import_object.register("env", functions);
+        let instance = module.instantiate(&import_object).unwrap();
+        c.bench_function(
+            &format!("{} calls of a host fn, {} imports", CALLS, count),
+            move |b| {
+                let run: Func<(), i32> = instance.func("run").unwrap();
+                b.iter(|| run.call().unwrap())
+            },
+        );
+
+        // Only the last import is a closure, so looking it up goes
+        // through all the others.
+        let factor = 2;
+        let mut closures = Namespace::new();
+        for i in 0..count - 1 {
+            closures.insert(format!("f{}", i), func!(double));
+        }
+        closures.insert(
+            format!("f{}", count - 1),
+            func!(move |_ctx: &mut Ctx, n: i32| -> i32 { n * factor }),
+        );
+        let mut import_object = ImportObject::new();
+        import_object.register("env", closures);
+        let instance = module.instantiate(&import_object).unwrap();
+        c.bench_function(
+            &format!("{} calls of a capturing closure, {} imports", CALLS, count),
+            move |b| {
+                let run: Func<(), i32> = instance.func("run").unwrap();
+                b.iter(|| run.call().unwrap())
+            },
+        );
+    }
+}
+
+criterion_group! {
+    name = host_calls_bench;
+    config = Criterion::default().sample_size(20);
+    targets = host_calls_benchmark,
+}
+criterion_main!(host_calls_bench);
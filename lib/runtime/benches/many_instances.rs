@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate criterion;
 use criterion::Criterion;
+use std::{sync::Arc, thread};
 use tempfile::tempdir;
 use wasmer_runtime::{
     cache::{Cache, FileSystemCache, WasmHash},
@@ -63,6 +64,41 @@ fn create_instance_from_cache_benchmark(c: &mut Criterion) {
     });
 }
 
+fn concurrent_instantiation_benchmark(c: &mut Criterion) {
+    let imports = imports!(
+        "env" => {
+            "it_works" => func!(it_works),
+        },
+    )
+    .freeze();
+    let module = Arc::new(compile(SIMPLE_WASM).unwrap());
+    let imports = Arc::new(imports);
+    // Every thread instantiates the same number of times, so the time stays
+    // flat as threads are added while instantiation scales.
+    c.bench_function_over_inputs(
+        "instantiate concurrently",
+        move |b, &threads| {
+            b.iter(|| {
+                let handles: Vec<_> = (0..threads)
+                    .map(|_| {
+                        let module = Arc::clone(&module);
+                        let imports = Arc::clone(&imports);
+                        thread::spawn(move || {
+                            for _ in 0..16 {
+                                module.instantiate(&imports).unwrap();
+                            }
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.join().unwrap();
+                }
+            })
+        },
+        vec![1, 2, 4, 8],
+    );
+}
+
 fn calling_fn_benchmark(c: &mut Criterion) {
     let imports = imports!(
         "env" => {
@@ -79,6 +115,6 @@ fn calling_fn_benchmark(c: &mut Criterion) {
 criterion_group! {
     name = instance_bench;
     config = Criterion::default().sample_size(20);
-    targets = compile_benchmark, validate_benchmark, hashing_benchmark, create_instance_from_cache_benchmark, calling_fn_benchmark, create_instance_benchmark,
+    targets = compile_benchmark, validate_benchmark, hashing_benchmark, create_instance_from_cache_benchmark, calling_fn_benchmark, create_instance_benchmark, concurrent_instantiation_benchmark,
 }
 criterion_main!(instance_bench);
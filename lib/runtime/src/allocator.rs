@@ -0,0 +1,176 @@
+//! Allocating guest memory from the host, to pass buffers and strings to
+//! a module.
+//!
+//! A [`GuestAllocator`] calls the allocator the module exports: either
+//! `malloc` and `free`, as exported by C and Rust modules, or the canonical
+//! ABI's `cabi_realloc`.
+//!
+//! # Usage:
+//!
+//! ```
+//! # use wasmer_runtime::{allocator::{GuestAllocator, GuestAllocError}, Func, Instance};
+//! fn greet(instance: &Instance) -> Result<(), GuestAllocError> {
+//!     let allocator = GuestAllocator::new(instance)?;
+//!     let name = allocator.write_str("world")?;
+//!
+//!     let greet: Func<(u32, u32), ()> = instance.func("greet")?;
+//!     greet.call(name.offset(), 5)?;
+//!
+//!     allocator.free(name, 5)
+//! }
+//! ```
+
+use std::fmt;
+use wasmer_runtime_core::{
+    error::{MemoryAccessError, ResolveError, RuntimeError},
+    memory::ptr::{Array, WasmPtr},
+    typed_func::Func,
+    Instance,
+};
+
+/// The name of the `malloc` export.
+pub const MALLOC: &str = "malloc";
+/// The name of the `free` export.
+pub const FREE: &str = "free";
+/// The name of the canonical ABI's `cabi_realloc` export.
+pub const CABI_REALLOC: &str = "cabi_realloc";
+
+/// Error returned by a [`GuestAllocator`].
+#[derive(Debug)]
+pub enum GuestAllocError {
+    /// The module exports neither `malloc` and `free` nor `cabi_realloc`.
+    NoAllocator,
+    /// An allocator export doesn't have the expected signature.
+    Resolve(ResolveError),
+    /// The allocator trapped.
+    Call(RuntimeError),
+    /// The allocator returned a null pointer.
+    OutOfMemory {
+        /// The number of bytes that were requested.
+        size: u32,
+    },
+    /// The allocation doesn't lie within the memory.
+    Access(MemoryAccessError),
+}
+
+impl fmt::Display for GuestAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuestAllocError::NoAllocator => write!(
+                f,
+                "the module exports neither `{}` and `{}` nor `{}`",
+                MALLOC, FREE, CABI_REALLOC
+            ),
+            GuestAllocError::Resolve(e) => write!(f, "invalid allocator export: {}", e),
+            GuestAllocError::Call(e) => write!(f, "the allocator trapped: {}", e),
+            GuestAllocError::OutOfMemory { size } => {
+                write!(f, "the guest could not allocate {} bytes", size)
+            }
+            GuestAllocError::Access(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for GuestAllocError {}
+
+impl From<ResolveError> for GuestAllocError {
+    fn from(e: ResolveError) -> Self {
+        GuestAllocError::Resolve(e)
+    }
+}
+
+impl From<RuntimeError> for GuestAllocError {
+    fn from(e: RuntimeError) -> Self {
+        GuestAllocError::Call(e)
+    }
+}
+
+impl From<MemoryAccessError> for GuestAllocError {
+    fn from(e: MemoryAccessError) -> Self {
+        GuestAllocError::Access(e)
+    }
+}
+
+enum Exports<'a> {
+    Malloc {
+        malloc: Func<'a, u32, u32>,
+        free: Func<'a, u32, ()>,
+    },
+    CabiRealloc(Func<'a, (u32, u32, u32, u32), u32>),
+}
+
+/// Allocates memory in an instance with the allocator it exports.
+pub struct GuestAllocator<'a> {
+    instance: &'a Instance,
+    exports: Exports<'a>,
+}
+
+impl<'a> GuestAllocator<'a> {
+    /// Find the allocator exported by `instance`, preferring `malloc` and
+    /// `free` to `cabi_realloc`.
+    pub fn new(instance: &'a Instance) -> Result<Self, GuestAllocError> {
+        let exports = if instance.resolve_func(MALLOC).is_ok() {
+            Exports::Malloc {
+                malloc: instance.func(MALLOC)?,
+                free: instance.func(FREE)?,
+            }
+        } else if instance.resolve_func(CABI_REALLOC).is_ok() {
+            Exports::CabiRealloc(instance.func(CABI_REALLOC)?)
+        } else {
+            return Err(GuestAllocError::NoAllocator);
+        };
+
+        Ok(Self { instance, exports })
+    }
+
+    /// Allocate `size` bytes aligned to `align`, a power of two.
+    ///
+    /// `malloc` ignores `align`; it aligns its allocations for any type.
+    pub fn alloc(&self, size: u32, align: u32) -> Result<WasmPtr<u8, Array>, GuestAllocError> {
+        let offset = match &self.exports {
+            Exports::Malloc { malloc, .. } => malloc.call(size)?,
+            Exports::CabiRealloc(realloc) => realloc.call(0, 0, align, size)?,
+        };
+        if offset == 0 && size != 0 {
+            return Err(GuestAllocError::OutOfMemory { size });
+        }
+
+        Ok(WasmPtr::new(offset))
+    }
+
+    /// Free the `size` bytes at `ptr`, allocated by this allocator.
+    ///
+    /// The canonical ABI has no way to free memory, so this does nothing
+    /// with `cabi_realloc`.
+    pub fn free(&self, ptr: WasmPtr<u8, Array>, size: u32) -> Result<(), GuestAllocError> {
+        let _ = size;
+        match &self.exports {
+            Exports::Malloc { free, .. } => free.call(ptr.offset())?,
+            Exports::CabiRealloc(_) => (),
+        }
+        Ok(())
+    }
+
+    /// Copy `bytes` to a new allocation.
+    pub fn write_bytes(&self, bytes: &[u8]) -> Result<WasmPtr<u8, Array>, GuestAllocError> {
+        let size = bytes.len() as u32;
+        if size as usize != bytes.len() {
+            return Err(GuestAllocError::OutOfMemory {
+                size: u32::max_value(),
+            });
+        }
+        let ptr = self.alloc(size, 1)?;
+        if let Err(e) = self.instance.write_bytes(ptr, bytes) {
+            self.free(ptr, size)?;
+            return Err(e.into());
+        }
+
+        Ok(ptr)
+    }
+
+    /// Copy the UTF-8 bytes of `string`, without a nul terminator, to a new
+    /// allocation.
+    pub fn write_str(&self, string: &str) -> Result<WasmPtr<u8, Array>, GuestAllocError> {
+        self.write_bytes(string.as_bytes())
+    }
+}
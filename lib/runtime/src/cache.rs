@@ -2,18 +2,24 @@
 //! serializing compiled wasm code to a binary format.  The binary format can be persisted,
 //! and loaded to allow skipping compilation and fast startup.
 
-use crate::Module;
+use crate::{
+    error::{CompileError, CompileResult},
+    metrics, CompilerConfig, Module, WeakModule,
+};
 use memmap::Mmap;
 use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
     fs::{create_dir_all, File},
     io::{self, Write},
     path::PathBuf,
+    sync::Mutex,
 };
 
 use wasmer_runtime_core::cache::Error as CacheError;
 pub use wasmer_runtime_core::{
     backend::Backend,
-    cache::{Artifact, Cache, WasmHash},
+    cache::{Artifact, Cache, Incompatibility, WasmHash},
 };
 
 /// Representation of a directory that contains compiled wasm artifacts.
@@ -82,6 +88,22 @@ impl FileSystemCache {
             Ok(Self { path })
         }
     }
+
+    fn load_file(path: PathBuf, backend: Backend) -> Result<Module, CacheError> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let serialized_cache = Artifact::deserialize(&mmap[..])?;
+        serialized_cache.check_compatible(backend)?;
+        unsafe {
+            wasmer_runtime_core::load_cache_with(
+                serialized_cache,
+                crate::compiler_for_backend(backend)
+                    .ok_or_else(|| CacheError::UnsupportedBackend(backend))?
+                    .as_ref(),
+            )
+        }
+    }
 }
 
 impl Cache for FileSystemCache {
@@ -97,18 +119,16 @@ impl Cache for FileSystemCache {
         let mut new_path_buf = self.path.clone();
         new_path_buf.push(backend.to_string());
         new_path_buf.push(filename);
-        let file = File::open(new_path_buf)?;
-        let mmap = unsafe { Mmap::map(&file)? };
+        let result = Self::load_file(new_path_buf, backend);
 
-        let serialized_cache = Artifact::deserialize(&mmap[..])?;
-        unsafe {
-            wasmer_runtime_core::load_cache_with(
-                serialized_cache,
-                crate::compiler_for_backend(backend)
-                    .ok_or_else(|| CacheError::UnsupportedBackend(backend))?
-                    .as_ref(),
-            )
+        if let Some(metrics) = metrics::global() {
+            match result {
+                Ok(_) => metrics.cache_hit(),
+                Err(_) => metrics.cache_miss(),
+            }
         }
+
+        result
     }
 
     fn store(&mut self, key: WasmHash, module: Module) -> Result<(), CacheError> {
@@ -129,6 +149,286 @@ impl Cache for FileSystemCache {
     }
 }
 
+/// Writes `module` in the shared artifact layout to `writer`.
+///
+/// The written artifact can be placed in a shared memory segment and loaded with
+/// [`load_shared`] by many processes on the same machine, which then share a single
+/// copy of the compiled code pages.
+///
+/// Only the Cranelift backend compiles the position independent code this needs, and
+/// not for every module; other modules are rejected with
+/// `CacheError::NotPositionIndependent`.
+///
+/// [`load_shared`]: fn.load_shared.html
+pub fn store_shared<W: Write>(module: &Module, writer: &mut W) -> Result<(), CacheError> {
+    module.cache()?.write_shared(writer)
+}
+
+/// Loads a module from `artifact` with the backend that compiled it.
+///
+/// Unlike [`Cache::load`], no backend needs to be picked, so a headless
+/// engine (see the `headless` feature) can run artifacts compiled by any
+/// of its backends, including ones cross-compiled on another machine with
+/// [`CompilerConfig::target`].
+///
+/// # Note:
+/// This function is unsafe because there's no way to ensure the artifact
+/// hasn't been corrupted or tampered with.
+///
+/// [`Cache::load`]: trait.Cache.html#tymethod.load
+/// [`CompilerConfig::target`]: ../struct.CompilerConfig.html#method.target
+pub unsafe fn load_artifact(artifact: Artifact) -> Result<Module, CacheError> {
+    let backend = artifact.info().backend;
+    wasmer_runtime_core::load_cache_with(
+        artifact,
+        crate::compiler_for_backend(backend)
+            .ok_or_else(|| CacheError::UnsupportedBackend(backend))?
+            .as_ref(),
+    )
+}
+
+/// Loads a module from a shared artifact written by [`store_shared`].
+///
+/// The code is mapped shared and read-only from `file`, so every process uses the
+/// same pages; only the table of addresses it refers to is private. Artifacts whose
+/// code isn't position independent are rejected with
+/// `CacheError::NotPositionIndependent`.
+///
+/// # Note:
+/// This function is unsafe because there's no way to ensure the artifact
+/// hasn't been corrupted or tampered with.
+///
+/// [`store_shared`]: fn.store_shared.html
+#[cfg(unix)]
+pub unsafe fn load_shared(file: &File, backend: Backend) -> Result<Module, CacheError> {
+    let artifact = Artifact::from_shared_file(file)?;
+    artifact.check_compatible(backend)?;
+    wasmer_runtime_core::load_cache_with(
+        artifact,
+        crate::compiler_for_backend(backend)
+            .ok_or_else(|| CacheError::UnsupportedBackend(backend))?
+            .as_ref(),
+    )
+}
+
+/// An in-memory cache of compiled modules shared by the threads of a process, so the
+/// same wasm bytes aren't compiled again with the same configuration.
+///
+/// Modules are keyed by [`ModuleCache::key`]. The cache keeps the `capacity` most
+/// recently used modules alive, and only a weak reference to the others, so they're
+/// still found while they're used elsewhere.
+///
+/// With a [`FileSystemCache`] behind it, the modules missing from memory are loaded from
+/// disk if they're there, and stored on disk once they're compiled.
+///
+/// # Usage:
+///
+/// ```rust
+/// use wasmer_runtime::cache::{Backend, ModuleCache};
+/// # use wasmer_runtime::{error::CompileResult, CompilerConfig, Module};
+///
+/// fn compile(cache: &ModuleCache, wasm: &[u8]) -> CompileResult<Module> {
+///     // Compiled once, and then shared by every thread compiling the same bytes.
+///     cache.get_or_compile(wasm, CompilerConfig::default(), Backend::Cranelift)
+/// }
+/// ```
+pub struct ModuleCache {
+    capacity: usize,
+    entries: Mutex<ModuleCacheEntries>,
+    disk_cache: Option<Mutex<FileSystemCache>>,
+}
+
+#[derive(Default)]
+struct ModuleCacheEntries {
+    /// The modules kept alive, the least recently used first.
+    recent: VecDeque<(WasmHash, Module)>,
+    /// Every module of the cache which may still be alive.
+    modules: HashMap<WasmHash, WeakModule>,
+}
+
+impl ModuleCacheEntries {
+    /// Mark `module` as the most recently used, evicting the least recently used module
+    /// if there are more than `capacity`.
+    fn touch(&mut self, key: WasmHash, module: &Module, capacity: usize) {
+        self.recent.retain(|(recent_key, _)| *recent_key != key);
+        self.recent.push_back((key, module.clone()));
+        while self.recent.len() > capacity {
+            self.recent.pop_front();
+        }
+    }
+}
+
+impl ModuleCache {
+    /// Create an empty cache keeping at most `capacity` modules alive.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(ModuleCacheEntries::default()),
+            disk_cache: None,
+        }
+    }
+
+    /// Look the modules missing from memory up in `disk_cache`, and store the modules
+    /// compiled by this cache in it.
+    pub fn with_disk_cache(mut self, disk_cache: FileSystemCache) -> Self {
+        self.disk_cache = Some(Mutex::new(disk_cache));
+        self
+    }
+
+    /// The key of `wasm` compiled by `backend` with `compiler_config`: the hash of
+    /// `wasm`, the backend and every field of the configuration.
+    ///
+    /// Returns `None` if the configuration has a `backend_specific_config`, which can't
+    /// be hashed, so modules compiled with one aren't cached.
+    pub fn key(
+        wasm: &[u8],
+        backend: Backend,
+        compiler_config: &CompilerConfig,
+    ) -> Option<WasmHash> {
+        // Destructured so a new field can't be left out of the key.
+        let CompilerConfig {
+            symbol_map,
+            memory_bound_check_mode,
+            memory_style,
+            enforce_stack_check,
+            track_state,
+            features,
+            limits,
+            no_signals,
+            spectre_mitigations,
+            triple,
+            cpu_name,
+            cpu_features,
+            backend_specific_config,
+            auto_selection,
+        } = compiler_config;
+        if backend_specific_config.is_some() {
+            return None;
+        }
+
+        // The iteration order of a `HashMap` differs between maps with the same entries.
+        let symbol_map = symbol_map.as_ref().map(|symbol_map| {
+            let mut symbols: Vec<_> = symbol_map.iter().collect();
+            symbols.sort();
+            symbols
+        });
+
+        let fields: [&dyn fmt::Debug; 13] = [
+            &symbol_map,
+            memory_bound_check_mode,
+            memory_style,
+            enforce_stack_check,
+            track_state,
+            features,
+            limits,
+            no_signals,
+            spectre_mitigations,
+            triple,
+            cpu_name,
+            cpu_features,
+            auto_selection,
+        ];
+        let mut description = format!(
+            "{}\n{}\n",
+            WasmHash::generate(wasm).encode(),
+            backend.to_string()
+        );
+        for field in fields.iter() {
+            description.push_str(&format!("{:?}\n", field));
+        }
+        Some(WasmHash::generate(description.as_bytes()))
+    }
+
+    /// The module with `key`, if it's still alive.
+    pub fn get(&self, key: WasmHash) -> Option<Module> {
+        let mut entries = self.entries.lock().unwrap();
+        let module = entries.modules.get(&key)?.upgrade()?;
+        entries.touch(key, &module, self.capacity);
+        Some(module)
+    }
+
+    /// Add `module` to the cache with `key`, and return it, or the module another
+    /// thread added with `key` first.
+    pub fn insert(&self, key: WasmHash, module: Module) -> Module {
+        let mut entries = self.entries.lock().unwrap();
+        let module = match entries.modules.get(&key).and_then(WeakModule::upgrade) {
+            Some(existing) => existing,
+            None => {
+                entries.modules.retain(|_, weak| weak.upgrade().is_some());
+                entries.modules.insert(key, module.downgrade());
+                module
+            }
+        };
+        entries.touch(key, &module, self.capacity);
+        module
+    }
+
+    /// The number of modules in the cache which are still alive.
+    pub fn len(&self) -> usize {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .modules
+            .values()
+            .filter(|weak| weak.upgrade().is_some())
+            .count()
+    }
+
+    /// Returns true if no module of the cache is alive.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get `wasm` compiled by `backend` with `compiler_config` from the cache, from the
+    /// disk cache, or by compiling it.
+    ///
+    /// Threads compiling the same module at the same time may each compile it, but
+    /// they all get the module added to the cache first. Modules compiled with a
+    /// `backend_specific_config` are compiled every time, see [`ModuleCache::key`].
+    pub fn get_or_compile(
+        &self,
+        wasm: &[u8],
+        compiler_config: CompilerConfig,
+        backend: Backend,
+    ) -> CompileResult<Module> {
+        let key = match Self::key(wasm, backend, &compiler_config) {
+            Some(key) => key,
+            None => return compile(wasm, compiler_config, backend),
+        };
+        if let Some(module) = self.get(key) {
+            return Ok(module);
+        }
+
+        if let Some(disk_cache) = &self.disk_cache {
+            let disk_cache = disk_cache.lock().unwrap();
+            if let Ok(module) = disk_cache.load_with_backend(key, backend) {
+                return Ok(self.insert(key, module));
+            }
+        }
+
+        let module = compile(wasm, compiler_config, backend)?;
+
+        if let Some(disk_cache) = &self.disk_cache {
+            // The module is still cached in memory if it can't be stored on disk.
+            let _ = disk_cache.lock().unwrap().store(key, module.clone());
+        }
+
+        Ok(self.insert(key, module))
+    }
+}
+
+/// Compile `wasm` with `compiler_config` and `backend`, bypassing the cache.
+fn compile(
+    wasm: &[u8],
+    compiler_config: CompilerConfig,
+    backend: Backend,
+) -> CompileResult<Module> {
+    let compiler =
+        crate::compiler_for_backend(backend).ok_or_else(|| CompileError::InternalError {
+            msg: format!("the {} backend is not available", backend.to_string()),
+        })?;
+    wasmer_runtime_core::compile_with_config(wasm, compiler.as_ref(), compiler_config)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -177,4 +477,227 @@ mod tests {
         // verify it works
         assert_eq!(value, 43);
     }
+
+    #[cfg(all(unix, feature = "cranelift"))]
+    #[test]
+    fn test_shared_artifact_run() {
+        use crate::{compile_with_config_with, imports, Func};
+        use wabt::wat2wasm;
+
+        static WAT: &'static str = r#"
+            (module
+              (type $t0 (func (param i32) (result i32)))
+              (func $add_one (export "add_one") (type $t0) (param $p0 i32) (result i32)
+                get_local $p0
+                i32.const 1
+                i32.add))
+        "#;
+
+        let wasm = wat2wasm(WAT).unwrap();
+        let compiler = crate::compiler_for_backend(Backend::Cranelift).unwrap();
+        let module =
+            compile_with_config_with(&wasm, CompilerConfig::default(), compiler.as_ref()).unwrap();
+
+        let mut path = env::temp_dir();
+        path.push(format!(
+            "wasmer-shared-{}",
+            WasmHash::generate(&wasm).encode()
+        ));
+        {
+            let mut file = File::create(&path).unwrap();
+            store_shared(&module, &mut file).unwrap();
+        }
+
+        // load the same artifact twice, as two worker processes would
+        let file = File::open(&path).unwrap();
+        let first = unsafe { load_shared(&file, Backend::Cranelift).unwrap() };
+        let second = unsafe { load_shared(&file, Backend::Cranelift).unwrap() };
+
+        for module in &[first, second] {
+            let instance = module.instantiate(&imports! {}).unwrap();
+            let add_one: Func<i32, i32> = instance.func("add_one").unwrap();
+            assert_eq!(add_one.call(42).unwrap(), 43);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(all(unix, feature = "cranelift"))]
+    #[test]
+    fn test_shared_artifact_position_independent() {
+        use crate::{compile_with_config_with, imports, CompilerConfig, Func};
+        use wabt::wat2wasm;
+
+        // `memory.grow` calls into the runtime, and `call_indirect` checks the
+        // signature index, both of which are only known by the loading process.
+        static WAT: &'static str = r#"
+            (module
+              (type $t0 (func (param i32) (result i32)))
+              (memory 1)
+              (table anyfunc (elem $grow))
+              (func $grow (type $t0) (param $p0 i32) (result i32)
+                get_local $p0
+                memory.grow)
+              (func (export "grow_indirect") (type $t0) (param $p0 i32) (result i32)
+                get_local $p0
+                i32.const 0
+                call_indirect (type $t0)))
+        "#;
+
+        let wasm = wat2wasm(WAT).unwrap();
+        let compiler = crate::compiler_for_backend(Backend::Cranelift).unwrap();
+        let module =
+            compile_with_config_with(&wasm, CompilerConfig::default(), compiler.as_ref()).unwrap();
+        let shared_code_len = module.cache().unwrap().shared_code_len();
+        assert!(shared_code_len.is_some());
+
+        let mut path = env::temp_dir();
+        path.push(format!(
+            "wasmer-shared-pic-{}",
+            WasmHash::generate(&wasm).encode()
+        ));
+        {
+            let mut file = File::create(&path).unwrap();
+            store_shared(&module, &mut file).unwrap();
+        }
+
+        let file = File::open(&path).unwrap();
+        let first = unsafe { load_shared(&file, Backend::Cranelift).unwrap() };
+        let second = unsafe { load_shared(&file, Backend::Cranelift).unwrap() };
+
+        for module in &[first, second] {
+            let instance = module.instantiate(&imports! {}).unwrap();
+            let grow_indirect: Func<i32, i32> = instance.func("grow_indirect").unwrap();
+            assert_eq!(grow_indirect.call(1).unwrap(), 1);
+            assert_eq!(grow_indirect.call(1).unwrap(), 2);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(all(unix, feature = "cranelift"))]
+    #[test]
+    fn test_shared_artifact_rejects_code_to_patch() {
+        use crate::compile_with_config_with;
+        use std::io::{Seek, SeekFrom};
+        use wabt::wat2wasm;
+
+        let wasm = wat2wasm("(module (func (export \"nop\")))").unwrap();
+        let compiler = crate::compiler_for_backend(Backend::Cranelift).unwrap();
+        let module =
+            compile_with_config_with(&wasm, CompilerConfig::default(), compiler.as_ref()).unwrap();
+
+        let mut path = env::temp_dir();
+        path.push(format!(
+            "wasmer-shared-patch-{}",
+            WasmHash::generate(&wasm).encode()
+        ));
+        {
+            let mut file = File::create(&path).unwrap();
+            store_shared(&module, &mut file).unwrap();
+            // Claim that none of the code can be mapped shared, as the header of
+            // code which has to be patched in place would.
+            file.seek(SeekFrom::Start(40)).unwrap();
+            file.write_all(&0u64.to_ne_bytes()).unwrap();
+        }
+
+        let file = File::open(&path).unwrap();
+        match unsafe { load_shared(&file, Backend::Cranelift) } {
+            Err(CacheError::NotPositionIndependent) => {}
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "singlepass")]
+    #[test]
+    fn test_shared_artifact_needs_position_independent_code() {
+        use crate::compile_with_config_with;
+        use wabt::wat2wasm;
+
+        let wasm = wat2wasm("(module (func (export \"nop\")))").unwrap();
+        let compiler = crate::compiler_for_backend(Backend::Singlepass).unwrap();
+        let module =
+            compile_with_config_with(&wasm, CompilerConfig::default(), compiler.as_ref()).unwrap();
+
+        let mut artifact = Vec::new();
+        match store_shared(&module, &mut artifact) {
+            Err(CacheError::NotPositionIndependent) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert!(artifact.is_empty());
+    }
+
+    #[test]
+    fn test_module_cache_shares_modules() {
+        use wabt::wat2wasm;
+
+        let wasm = wat2wasm("(module (func (export \"nop\")))").unwrap();
+        let other_wasm = wat2wasm("(module (func (export \"other\")))").unwrap();
+        let cache = ModuleCache::new(1);
+
+        let first = cache
+            .get_or_compile(&wasm, CompilerConfig::default(), Backend::Cranelift)
+            .unwrap();
+        let second = cache
+            .get_or_compile(&wasm, CompilerConfig::default(), Backend::Cranelift)
+            .unwrap();
+        assert!(std::ptr::eq(first.info(), second.info()));
+
+        let tracked = CompilerConfig {
+            track_state: true,
+            ..Default::default()
+        };
+        let third = cache
+            .get_or_compile(&wasm, tracked, Backend::Cranelift)
+            .unwrap();
+        assert!(!std::ptr::eq(first.info(), third.info()));
+
+        // `third` evicted the module of `first`, which is still alive.
+        let key = ModuleCache::key(&wasm, Backend::Cranelift, &CompilerConfig::default()).unwrap();
+        assert!(std::ptr::eq(cache.get(key).unwrap().info(), first.info()));
+        assert_eq!(cache.len(), 2);
+
+        let other = cache
+            .get_or_compile(&other_wasm, CompilerConfig::default(), Backend::Cranelift)
+            .unwrap();
+        drop((first, second));
+        assert!(cache.get(key).is_none());
+        assert_eq!(cache.len(), 2);
+        drop((third, other));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_module_cache_key() {
+        use wasmer_runtime_core::backend::BackendCompilerConfig;
+
+        let wasm = [0, 97, 115, 109, 1, 0, 0, 0];
+        let with_symbols = |symbols: &[(u32, &str)]| CompilerConfig {
+            symbol_map: Some(
+                symbols
+                    .iter()
+                    .map(|&(index, name)| (index, name.to_string()))
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let symbols: Vec<_> = (0..64).map(|index| (index, "f")).collect();
+        let reversed: Vec<_> = symbols.iter().cloned().rev().collect();
+        assert_eq!(
+            ModuleCache::key(&wasm, Backend::Cranelift, &with_symbols(&symbols)),
+            ModuleCache::key(&wasm, Backend::Cranelift, &with_symbols(&reversed)),
+        );
+        assert_ne!(
+            ModuleCache::key(&wasm, Backend::Cranelift, &with_symbols(&symbols)),
+            ModuleCache::key(&wasm, Backend::Cranelift, &CompilerConfig::default()),
+        );
+
+        let backend_specific = CompilerConfig {
+            backend_specific_config: Some(BackendCompilerConfig(Box::new(0u32))),
+            ..Default::default()
+        };
+        assert!(ModuleCache::key(&wasm, Backend::Cranelift, &backend_specific).is_none());
+    }
 }
@@ -81,25 +81,37 @@
 //! The `wasmer-runtime` is build to support compiler multiple backends.
 //! Currently, we support the Singlepass, [Cranelift], and LLVM compilers
 //! with the [`wasmer-singlepass-backend`], [`wasmer-clif-backend`], and
-//! wasmer-llvm-backend crates, respectively.
+//! wasmer-llvm-backend crates, respectively. Where generating machine code
+//! at runtime isn't allowed, the interpreter of the
+//! [`wasmer-interpreter-backend`] crate runs modules instead, slower.
 //!
 //! You can specify the compiler you wish to use with the [`compile_with`]
 //! function or use the default with the [`compile`] function.
 //!
+//! With the `headless` feature, and no `default-backend-X` feature, the
+//! runtime is an engine that only runs precompiled modules: [`compile`],
+//! [`compile_with_config`], [`instantiate`] and [`default_compiler`] are
+//! left out, and artifacts are loaded with [`cache::load_artifact`].  The
+//! backends the artifacts were compiled by must still be enabled, to load
+//! their code.
+//!
 //! [Cranelift]: https://github.com/CraneStation/cranelift
 //! [LLVM]: https://llvm.org
 //! [`wasmer-singlepass-backend`]: https://crates.io/crates/wasmer-singlepass-backend
 //! [`wasmer-clif-backend`]: https://crates.io/crates/wasmer-clif-backend
+//! [`wasmer-interpreter-backend`]: https://crates.io/crates/wasmer-interpreter-backend
 
-pub use wasmer_runtime_core::backend::{Backend, Features};
+pub use wasmer_runtime_core::backend::{AutoSelection, Backend, Features};
 pub use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
 pub use wasmer_runtime_core::export::Export;
 pub use wasmer_runtime_core::global::Global;
 pub use wasmer_runtime_core::import::{ImportObject, LikeNamespace};
-pub use wasmer_runtime_core::instance::{DynFunc, Instance};
-pub use wasmer_runtime_core::memory::ptr::{Array, Item, WasmPtr};
+pub use wasmer_runtime_core::instance::{
+    DynFunc, Instance, InstanceConfig, InstanceStats, ResolvedFunc,
+};
+pub use wasmer_runtime_core::memory::ptr::{Array, Item, WasmPtr, WasmSlice};
 pub use wasmer_runtime_core::memory::Memory;
-pub use wasmer_runtime_core::module::Module;
+pub use wasmer_runtime_core::module::{CompileInfo, Module, WeakModule};
 pub use wasmer_runtime_core::table::Table;
 pub use wasmer_runtime_core::types::Value;
 pub use wasmer_runtime_core::vm::Ctx;
@@ -111,7 +123,7 @@ pub use wasmer_runtime_core::{func, imports};
 pub mod memory {
     //! The memory module contains the implementation data structures and helper functions used to
     //! manipulate and access wasm memory.
-    pub use wasmer_runtime_core::memory::{Atomically, Memory, MemoryView};
+    pub use wasmer_runtime_core::memory::{Atomically, Memory, MemoryType, MemoryView};
 }
 
 pub mod wasm {
@@ -130,6 +142,42 @@ pub mod error {
     pub use wasmer_runtime_core::error::*;
 }
 
+pub mod asyncify {
+    //! Suspending and resuming calls into modules transformed by Asyncify.
+    pub use wasmer_runtime_core::asyncify::*;
+}
+
+pub mod limits {
+    //! Limits on the resources instances use.
+    pub use wasmer_runtime_core::limits::*;
+}
+
+pub mod metrics {
+    //! Hooks for recording runtime metrics.
+    pub use wasmer_runtime_core::metrics::*;
+}
+
+pub mod plugin {
+    //! Loading compiler backends from dynamic libraries.
+    pub use wasmer_runtime_core::plugin::*;
+}
+
+pub mod pool {
+    //! Pooling allocation of instances and of their memories and tables.
+    pub use wasmer_runtime_core::pool::*;
+}
+
+#[cfg(unix)]
+pub mod profiler {
+    //! A sampling profiler for wasm code.
+    pub use wasmer_runtime_core::profiler::*;
+}
+
+pub mod signing {
+    //! Signing modules, and verifying their signatures before compiling them.
+    pub use wasmer_runtime_core::signing::*;
+}
+
 pub mod units {
     //! Various unit types.
     pub use wasmer_runtime_core::units::{Bytes, Pages};
@@ -140,9 +188,12 @@ pub mod types {
     pub use wasmer_runtime_core::types::*;
 }
 
+pub mod allocator;
 pub mod cache;
 
-pub use wasmer_runtime_core::backend::{Compiler, CompilerConfig};
+pub use wasmer_runtime_core::backend::{
+    CompileLimits, Compiler, CompilerConfig, CpuFeatures, MemoryStyle, SpectreMitigations, Triple,
+};
 
 /// Compile WebAssembly binary code into a [`Module`].
 /// This function is useful if it is necessary to
@@ -157,12 +208,26 @@ pub use wasmer_runtime_core::backend::{Compiler, CompilerConfig};
 ///   binary code of the wasm module you want to compile.
 /// # Errors:
 /// If the operation fails, the function returns `Err(error::CompileError::...)`.
+#[cfg(not(feature = "headless"))]
 pub fn compile(wasm: &[u8]) -> error::CompileResult<Module> {
     wasmer_runtime_core::compile_with(&wasm[..], &default_compiler())
 }
 
+/// The same as `compile`, once the embedded signature of `wasm` is verified
+/// to be made by one of `trusted_keys`, see [`signing`].
+///
+/// [`signing`]: signing/index.html
+#[cfg(not(feature = "headless"))]
+pub fn compile_verified(
+    wasm: &[u8],
+    trusted_keys: &[signing::PublicKey],
+) -> error::CompileResult<Module> {
+    Module::new_verified(wasm, trusted_keys, &default_compiler())
+}
+
 /// The same as `compile` but takes a `CompilerConfig` for the purpose of
 /// changing the compiler's behavior
+#[cfg(not(feature = "headless"))]
 pub fn compile_with_config(
     wasm: &[u8],
     compiler_config: CompilerConfig,
@@ -180,6 +245,118 @@ pub fn compile_with_config_with(
     wasmer_runtime_core::compile_with_config(&wasm[..], compiler, compiler_config)
 }
 
+/// The same as `compile_with_config`, but the module is taken from `cache`
+/// if the same bytes were already compiled with the same configuration.
+///
+/// See [`ModuleCache`] for how it composes with a [`FileSystemCache`].
+///
+/// [`ModuleCache`]: cache/struct.ModuleCache.html
+/// [`FileSystemCache`]: cache/struct.FileSystemCache.html
+#[cfg(not(feature = "headless"))]
+pub fn compile_cached(
+    wasm: &[u8],
+    compiler_config: CompilerConfig,
+    cache: &cache::ModuleCache,
+) -> error::CompileResult<Module> {
+    cache.get_or_compile(wasm, compiler_config, default_backend())
+}
+
+/// Modules smaller than this are compiled quickly enough by singlepass that it
+/// compiles them when they're metered.
+const SMALL_MODULE_SIZE: usize = 1 << 20;
+
+/// What [`select_backend`] takes into account, besides the size of the module.
+///
+/// [`select_backend`]: fn.select_backend.html
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BackendHints {
+    /// The module is compiled with a metering middleware, which the Cranelift
+    /// backend doesn't support.
+    pub metered: bool,
+    /// Faster code is worth a slower compilation.
+    pub optimize: bool,
+}
+
+/// Picks the backend `Backend::Auto` compiles a module of `module_size` bytes
+/// with, and why.
+///
+/// LLVM compiles the module when optimized code is asked for.  Metered modules
+/// are compiled by singlepass when they're smaller than 1 MiB, and by LLVM
+/// otherwise.  Cranelift compiles every other module.
+///
+/// When that backend isn't enabled in this build, or Cranelift isn't supported
+/// on this architecture, the first backend that is, in the order singlepass,
+/// Cranelift, LLVM and interpreter, compiles the module instead.
+pub fn select_backend(module_size: usize, hints: BackendHints) -> (Backend, AutoSelection) {
+    let preferred = if hints.optimize {
+        Backend::LLVM
+    } else if hints.metered && module_size < SMALL_MODULE_SIZE {
+        Backend::Singlepass
+    } else if hints.metered {
+        Backend::LLVM
+    } else {
+        Backend::Cranelift
+    };
+    let supported = |backend: Backend| {
+        let cranelift_supported = cfg!(target_arch = "x86_64") && !hints.metered;
+        (backend != Backend::Cranelift || cranelift_supported)
+            && compiler_for_backend(backend).is_some()
+    };
+
+    if supported(preferred) {
+        let selection = if hints.optimize {
+            AutoSelection::Optimize
+        } else if preferred == Backend::Singlepass {
+            AutoSelection::SmallMetered
+        } else if hints.metered {
+            AutoSelection::LargeMetered
+        } else {
+            AutoSelection::Default
+        };
+        return (preferred, selection);
+    }
+
+    let fallback = [
+        Backend::Singlepass,
+        Backend::Cranelift,
+        Backend::LLVM,
+        Backend::Interpreter,
+    ]
+    .iter()
+    .cloned()
+    .find(|backend| supported(*backend))
+    .unwrap_or(preferred);
+    (fallback, AutoSelection::Fallback)
+}
+
+/// The same as `compile_with_config`, but compiled by `backend`.
+///
+/// For `Backend::Auto`, the backend is picked by [`select_backend`] with
+/// `hints`, and the reason is reported by [`CompileInfo::auto_selection`].
+/// Any other backend overrides the selection.
+///
+/// [`select_backend`]: fn.select_backend.html
+/// [`CompileInfo::auto_selection`]: struct.CompileInfo.html#structfield.auto_selection
+pub fn compile_with_backend(
+    wasm: &[u8],
+    mut compiler_config: CompilerConfig,
+    backend: Backend,
+    hints: BackendHints,
+) -> error::CompileResult<Module> {
+    let backend = if backend == Backend::Auto {
+        let (backend, selection) = select_backend(wasm.len(), hints);
+        compiler_config.auto_selection = Some(selection);
+        backend
+    } else {
+        backend
+    };
+    let compiler =
+        compiler_for_backend(backend).ok_or_else(|| error::CompileError::InternalError {
+            msg: format!("the {} backend is not enabled", backend.to_string()),
+        })?;
+    compile_with_config_with(wasm, compiler_config, compiler.as_ref())
+}
+
 /// Compile and instantiate WebAssembly code without
 /// creating a [`Module`].
 ///
@@ -198,6 +375,7 @@ pub fn compile_with_config_with(
 /// `error::CompileError`, `error::LinkError`, or
 /// `error::RuntimeError` (all combined into an `error::Error`),
 /// depending on the cause of the failure.
+#[cfg(not(feature = "headless"))]
 pub fn instantiate(wasm: &[u8], import_object: &ImportObject) -> error::Result<Instance> {
     let module = compile(wasm)?;
     module.instantiate(import_object)
@@ -207,7 +385,9 @@ pub fn instantiate(wasm: &[u8], import_object: &ImportObject) -> error::Result<I
 ///
 /// The output of this function can be controlled by the mutually
 /// exclusive `default-backend-llvm`, `default-backend-singlepass`,
-/// and `default-backend-cranelift` feature flags.
+/// `default-backend-cranelift` and `default-backend-interpreter` feature
+/// flags.
+#[cfg(not(feature = "headless"))]
 pub fn default_compiler() -> impl Compiler {
     #[cfg(any(
         all(
@@ -222,6 +402,15 @@ pub fn default_compiler() -> impl Compiler {
             not(feature = "docs"),
             feature = "default-backend-cranelift",
             feature = "default-backend-singlepass"
+        ),
+        all(
+            feature = "default-backend-interpreter",
+            not(feature = "docs"),
+            any(
+                feature = "default-backend-cranelift",
+                feature = "default-backend-singlepass",
+                feature = "default-backend-llvm"
+            )
         )
     ))]
     compile_error!(
@@ -234,12 +423,35 @@ pub fn default_compiler() -> impl Compiler {
     #[cfg(all(feature = "default-backend-singlepass", not(feature = "docs")))]
     use wasmer_singlepass_backend::SinglePassCompiler as DefaultCompiler;
 
+    #[cfg(all(feature = "default-backend-interpreter", not(feature = "docs")))]
+    use wasmer_interpreter_backend::InterpreterCompiler as DefaultCompiler;
+
     #[cfg(any(feature = "default-backend-cranelift", feature = "docs"))]
     use wasmer_clif_backend::CraneliftCompiler as DefaultCompiler;
 
     DefaultCompiler::new()
 }
 
+/// The `Backend` of the `default_compiler`.
+#[cfg(not(feature = "headless"))]
+fn default_backend() -> Backend {
+    if cfg!(all(feature = "default-backend-llvm", not(feature = "docs"))) {
+        Backend::LLVM
+    } else if cfg!(all(
+        feature = "default-backend-singlepass",
+        not(feature = "docs")
+    )) {
+        Backend::Singlepass
+    } else if cfg!(all(
+        feature = "default-backend-interpreter",
+        not(feature = "docs")
+    )) {
+        Backend::Interpreter
+    } else {
+        Backend::Cranelift
+    }
+}
+
 /// Get the `Compiler` as a trait object for the given `Backend`.
 /// Returns `Option` because support for the requested `Compiler` may
 /// not be enabled by feature flags.
@@ -258,6 +470,11 @@ pub fn compiler_for_backend(backend: Backend) -> Option<Box<dyn Compiler>> {
         #[cfg(feature = "llvm")]
         Backend::LLVM => Some(Box::new(wasmer_llvm_backend::LLVMCompiler::new())),
 
+        #[cfg(feature = "interpreter")]
+        Backend::Interpreter => Some(Box::new(
+            wasmer_interpreter_backend::InterpreterCompiler::new(),
+        )),
+
         Backend::Auto => {
             #[cfg(feature = "default-backend-singlepass")]
             return Some(Box::new(
@@ -267,12 +484,68 @@ pub fn compiler_for_backend(backend: Backend) -> Option<Box<dyn Compiler>> {
             return Some(Box::new(wasmer_clif_backend::CraneliftCompiler::new()));
             #[cfg(feature = "default-backend-llvm")]
             return Some(Box::new(wasmer_llvm_backend::LLVMCompiler::new()));
+            #[cfg(feature = "default-backend-interpreter")]
+            return Some(Box::new(
+                wasmer_interpreter_backend::InterpreterCompiler::new(),
+            ));
         }
 
-        #[cfg(not(all(feature = "llvm", feature = "singlepass", feature = "cranelift")))]
+        #[cfg(not(all(
+            feature = "llvm",
+            feature = "singlepass",
+            feature = "cranelift",
+            feature = "interpreter"
+        )))]
         _ => None,
     }
 }
 
+/// Get a `Compiler` for the given `Backend` that meters the modules it
+/// compiles with the `Metering` middleware of `wasmer-middleware-common`,
+/// so their instances trap once they've used `limit` points.
+///
+/// Returns `None` if support for the backend isn't enabled by feature flags,
+/// or if the backend doesn't run middlewares, which Cranelift doesn't.
+#[cfg_attr(
+    not(any(feature = "singlepass", feature = "llvm", feature = "interpreter")),
+    allow(unused_variables)
+)]
+pub fn metered_compiler_for_backend(backend: Backend, limit: u64) -> Option<Box<dyn Compiler>> {
+    match backend {
+        #[cfg(feature = "singlepass")]
+        Backend::Singlepass => {
+            let c: StreamingCompiler<wasmer_singlepass_backend::ModuleCodeGenerator, _, _, _, _> =
+                StreamingCompiler::new(metering_chain(limit));
+            Some(Box::new(c))
+        }
+
+        #[cfg(feature = "llvm")]
+        Backend::LLVM => {
+            let c: StreamingCompiler<wasmer_llvm_backend::ModuleCodeGenerator, _, _, _, _> =
+                StreamingCompiler::new(metering_chain(limit));
+            Some(Box::new(c))
+        }
+
+        #[cfg(feature = "interpreter")]
+        Backend::Interpreter => {
+            let c: StreamingCompiler<wasmer_interpreter_backend::ModuleCodeGenerator, _, _, _, _> =
+                StreamingCompiler::new(metering_chain(limit));
+            Some(Box::new(c))
+        }
+
+        _ => None,
+    }
+}
+
+/// The middlewares of the compilers returned by `metered_compiler_for_backend`.
+#[cfg(any(feature = "singlepass", feature = "llvm", feature = "interpreter"))]
+fn metering_chain(limit: u64) -> impl Fn() -> MiddlewareChain {
+    move || {
+        let mut middlewares = MiddlewareChain::new();
+        middlewares.push(wasmer_middleware_common::metering::Metering::new(limit));
+        middlewares
+    }
+}
+
 /// The current version of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,65 @@
+#![cfg(target_arch = "x86_64")]
+
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    compile_with_backend, select_backend, AutoSelection, Backend, BackendHints, CompilerConfig,
+};
+
+static WAT: &'static str = r#"
+    (module
+      (func (export "add_one") (param i32) (result i32)
+        get_local 0
+        i32.const 1
+        i32.add))
+"#;
+
+#[cfg(feature = "cranelift")]
+#[test]
+fn auto_selection_is_reported() {
+    let wasm = wat2wasm(WAT).unwrap();
+    assert_eq!(
+        select_backend(wasm.len(), BackendHints::default()),
+        (Backend::Cranelift, AutoSelection::Default)
+    );
+
+    let module = compile_with_backend(
+        &wasm,
+        CompilerConfig::default(),
+        Backend::Auto,
+        BackendHints::default(),
+    )
+    .unwrap();
+    let compile_info = module.compile_info().unwrap();
+    assert_eq!(compile_info.backend, Backend::Cranelift);
+    assert_eq!(compile_info.auto_selection, Some(AutoSelection::Default));
+}
+
+#[cfg(feature = "cranelift")]
+#[test]
+fn explicit_backend_overrides_selection() {
+    let wasm = wat2wasm(WAT).unwrap();
+    let hints = BackendHints {
+        optimize: true,
+        ..Default::default()
+    };
+
+    let module =
+        compile_with_backend(&wasm, CompilerConfig::default(), Backend::Cranelift, hints).unwrap();
+    let compile_info = module.compile_info().unwrap();
+    assert_eq!(compile_info.backend, Backend::Cranelift);
+    assert_eq!(compile_info.auto_selection, None);
+}
+
+#[cfg(feature = "singlepass")]
+#[test]
+fn small_metered_modules_use_singlepass() {
+    let wasm = wat2wasm(WAT).unwrap();
+    let hints = BackendHints {
+        metered: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        select_backend(wasm.len(), hints),
+        (Backend::Singlepass, AutoSelection::SmallMetered)
+    );
+}
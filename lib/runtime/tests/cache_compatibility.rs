@@ -0,0 +1,101 @@
+#![cfg(target_arch = "x86_64")]
+
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    cache::{load_artifact, Artifact, Backend, Incompatibility},
+    compile, compile_with_config,
+    error::CacheError,
+    imports, CompilerConfig, CpuFeatures, Triple,
+};
+
+static WAT: &'static str = r#"
+    (module
+      (func (export "add_one") (param i32) (result i32)
+        get_local 0
+        i32.const 1
+        i32.add))
+"#;
+
+/// The offset of the Wasmer version in the header of an artifact.
+const WASMER_VERSION_OFFSET: usize = 24;
+
+fn serialized_module() -> Vec<u8> {
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile(&wasm).unwrap();
+    module.cache().unwrap().serialize().unwrap()
+}
+
+#[test]
+fn corrupted_artifacts_are_rejected() {
+    let mut bytes = serialized_module();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+
+    match Artifact::deserialize(&bytes) {
+        Err(CacheError::InvalidFile(_)) => {}
+        _ => panic!("a corrupted artifact was deserialized"),
+    }
+}
+
+#[test]
+fn artifacts_of_other_wasmer_versions_are_incompatible() {
+    let mut bytes = serialized_module();
+    bytes[WASMER_VERSION_OFFSET] ^= 0xff;
+
+    match Artifact::deserialize(&bytes) {
+        Err(CacheError::Incompatible(Incompatibility::WasmerVersion)) => {}
+        _ => panic!("an artifact of another version was deserialized"),
+    }
+}
+
+#[test]
+fn artifacts_of_other_backends_are_incompatible() {
+    let artifact = Artifact::deserialize(&serialized_module()).unwrap();
+    assert!(artifact.check_compatible(Backend::Cranelift).is_ok());
+
+    match artifact.check_compatible(Backend::LLVM) {
+        Err(CacheError::Incompatible(Incompatibility::Backend { expected, found })) => {
+            assert_eq!(expected, Backend::LLVM);
+            assert_eq!(found, Backend::Cranelift);
+        }
+        _ => panic!("an artifact of another backend was compatible"),
+    }
+}
+
+#[test]
+fn artifacts_using_missing_cpu_features_are_incompatible() {
+    // Never detected by `CpuFeatures::host`, and ignored by Cranelift.
+    let config =
+        CompilerConfig::default().target(Triple::host(), CpuFeatures::new().with("avx512f"));
+
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile_with_config(&wasm, config).unwrap();
+    let bytes = module.cache().unwrap().serialize().unwrap();
+    let artifact = Artifact::deserialize(&bytes).unwrap();
+
+    match unsafe { load_artifact(artifact) } {
+        Err(CacheError::Incompatible(Incompatibility::CpuFeatures(missing))) => {
+            assert_eq!(missing, ["avx512f"])
+        }
+        _ => panic!("an artifact using missing CPU features was loaded"),
+    }
+}
+
+#[test]
+fn artifacts_compiled_for_a_fleet_load_on_its_machines() {
+    // The features every machine of the fleet has.
+    let fleet = CpuFeatures::new().with("sse4.2").with("popcnt");
+    let features = CpuFeatures::host().intersection(&fleet);
+    let config = CompilerConfig::default().with_cpu_features(features.clone());
+
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile_with_config(&wasm, config).unwrap();
+    let bytes = module.cache().unwrap().serialize().unwrap();
+    let artifact = Artifact::deserialize(&bytes).unwrap();
+    assert_eq!(artifact.required_cpu_features().unwrap(), features);
+
+    let module = unsafe { load_artifact(artifact) }.unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let add_one = instance.func::<i32, i32>("add_one").unwrap();
+    assert_eq!(add_one.call(41), Ok(42));
+}
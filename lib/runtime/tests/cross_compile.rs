@@ -0,0 +1,64 @@
+#![cfg(target_arch = "x86_64")]
+
+use std::str::FromStr;
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    cache::{load_artifact, Artifact},
+    compile_with_config,
+    error::{CacheError, CreationError, Error},
+    imports, CompilerConfig, CpuFeatures, Triple,
+};
+
+static WAT: &'static str = r#"
+    (module
+      (func (export "add_one") (param i32) (result i32)
+        get_local 0
+        i32.const 1
+        i32.add))
+"#;
+
+#[test]
+fn modules_compiled_for_another_target_can_only_be_cached() {
+    let triple = if cfg!(target_os = "macos") {
+        "x86_64-unknown-linux-gnu"
+    } else {
+        "x86_64-apple-darwin"
+    };
+    let config = CompilerConfig::default().target(
+        Triple::from_str(triple).unwrap(),
+        CpuFeatures::new().with("sse4.2"),
+    );
+
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile_with_config(&wasm, config).unwrap();
+    assert_eq!(module.info().triple.as_ref().unwrap(), triple);
+
+    match module.instantiate(&imports! {}) {
+        Err(Error::CreationError(CreationError::IncompatibleTarget(target))) => {
+            assert_eq!(target, triple)
+        }
+        _ => panic!("a module compiled for {} was instantiated", triple),
+    }
+
+    let bytes = module.cache().unwrap().serialize().unwrap();
+    let artifact = Artifact::deserialize(&bytes).unwrap();
+    assert_eq!(artifact.info().triple.as_ref().unwrap(), triple);
+    match unsafe { load_artifact(artifact) } {
+        Err(CacheError::IncompatibleTarget(target)) => assert_eq!(target, triple),
+        _ => panic!("an artifact compiled for {} was loaded", triple),
+    }
+}
+
+#[test]
+fn modules_compiled_for_the_host_run() {
+    let config = CompilerConfig::default().target(Triple::host(), CpuFeatures::host());
+
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile_with_config(&wasm, config).unwrap();
+    let bytes = module.cache().unwrap().serialize().unwrap();
+    let module = unsafe { load_artifact(Artifact::deserialize(&bytes).unwrap()) }.unwrap();
+
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let add_one = instance.func::<i32, i32>("add_one").unwrap();
+    assert_eq!(add_one.call(41), Ok(42));
+}
@@ -0,0 +1,59 @@
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    allocator::{GuestAllocError, GuestAllocator},
+    error::MemoryAccessError,
+    imports, instantiate, WasmPtr,
+};
+
+static WAT: &'static str = r#"
+    (module
+      (memory (export "memory") 1)
+      (global $next (mut i32) (i32.const 1024))
+      (func (export "malloc") (param $size i32) (result i32)
+        (local $ptr i32)
+        get_global $next
+        set_local $ptr
+        get_global $next
+        get_local $size
+        i32.add
+        set_global $next
+        get_local $ptr)
+      (func (export "free") (param i32)))
+"#;
+
+#[test]
+fn guest_allocator() {
+    let wasm = wat2wasm(WAT).unwrap();
+    let instance = instantiate(&wasm, &imports! {}).unwrap();
+
+    let allocator = GuestAllocator::new(&instance).unwrap();
+    let hello = allocator.write_str("hello").unwrap();
+    let world = allocator.write_bytes(b"world").unwrap();
+    assert_eq!(hello.offset(), 1024);
+    assert_eq!(world.offset(), 1029);
+    assert_eq!(instance.read_bytes(hello, 10).unwrap(), b"helloworld");
+    allocator.free(hello, 5).unwrap();
+
+    assert_eq!(
+        instance.write_bytes(WasmPtr::new(65_534), b"abc"),
+        Err(MemoryAccessError::OutOfBounds {
+            offset: 65_534,
+            len: 3
+        })
+    );
+    match allocator.write_bytes(&vec![0; 70_000]) {
+        Err(GuestAllocError::Access(MemoryAccessError::OutOfBounds { offset: 1034, .. })) => (),
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn guest_allocator_requires_an_allocator() {
+    let wasm = wat2wasm(r#"(module (memory 1))"#).unwrap();
+    let instance = instantiate(&wasm, &imports! {}).unwrap();
+
+    match GuestAllocator::new(&instance) {
+        Err(GuestAllocError::NoAllocator) => (),
+        _ => panic!("found an allocator in a module that exports none"),
+    }
+}
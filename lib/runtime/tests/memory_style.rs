@@ -0,0 +1,44 @@
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    compile_with_config, imports, memory::MemoryType, CompilerConfig, MemoryStyle,
+};
+
+static WAT: &'static str = r#"
+    (module
+      (memory 1 1)
+      (func (export "load") (param i32) (result i32)
+        get_local 0
+        i32.load))
+"#;
+
+fn memory_type(style: MemoryStyle) -> MemoryType {
+    let config = CompilerConfig {
+        memory_style: style,
+        ..Default::default()
+    };
+    let module = compile_with_config(&wat2wasm(WAT).unwrap(), config).unwrap();
+    let (_, desc) = module.info().memories.iter().next().unwrap();
+    desc.memory_type()
+}
+
+#[test]
+fn memory_style_chooses_the_memory_type() {
+    assert_eq!(memory_type(MemoryStyle::Default), MemoryType::Static);
+    assert_eq!(memory_type(MemoryStyle::Static), MemoryType::Static);
+    assert_eq!(memory_type(MemoryStyle::Dynamic), MemoryType::Dynamic);
+}
+
+#[test]
+fn dynamic_memories_are_bounds_checked() {
+    let config = CompilerConfig {
+        memory_style: MemoryStyle::Dynamic,
+        ..Default::default()
+    };
+    let module = compile_with_config(&wat2wasm(WAT).unwrap(), config).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let load = instance.func::<i32, i32>("load").unwrap();
+
+    assert_eq!(load.call(65_532), Ok(0));
+    assert!(load.call(65_533).is_err());
+    assert!(load.call(1 << 20).is_err());
+}
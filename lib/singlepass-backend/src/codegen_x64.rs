@@ -3,7 +3,10 @@
 
 use crate::emitter_x64::*;
 use crate::machine::*;
-use crate::protect_unix;
+#[cfg(unix)]
+use crate::protect_unix as protect;
+#[cfg(windows)]
+use crate::protect_windows as protect;
 #[cfg(target_arch = "aarch64")]
 use dynasmrt::aarch64::Assembler;
 #[cfg(target_arch = "x86_64")]
@@ -20,15 +23,16 @@ use std::{
     sync::{Arc, RwLock},
     usize,
 };
+#[cfg(unix)]
+use wasmer_runtime_core::fault::raw::register_preservation_trampoline;
 use wasmer_runtime_core::{
     backend::{
         sys::{Memory, Protect},
         Architecture, Backend, CacheGen, CompilerConfig, InlineBreakpoint, InlineBreakpointType,
-        MemoryBoundCheckMode, RunnableModule, Token,
+        MemoryBoundCheckMode, RunnableModule, SpectreMitigations, Token,
     },
     cache::{Artifact, Error as CacheError},
     codegen::*,
-    fault::raw::register_preservation_trampoline,
     loader::CodeMemory,
     memory::MemoryType,
     module::{ModuleInfo, ModuleInner},
@@ -53,13 +57,70 @@ static ARCH: Architecture = Architecture::Aarch64;
 #[allow(dead_code)]
 static ARCH: Architecture = Architecture::X64;
 
+/// The stack that code compiled without signal handlers may use, below the
+/// frame calling into it, when the bounds of the thread's stack aren't known.
+const NO_SIGNALS_STACK_SIZE: usize = 1 << 20;
+
+/// The stack left to the host above the end of the thread's stack, e.g. for
+/// handling the trap raised when the code compiled without signal handlers
+/// reaches its bound.
+const NO_SIGNALS_STACK_RESERVE: usize = 64 * 1024;
+
+thread_local! {
+    /// The lowest address of the current thread's stack, see `stack_lower_bound`.
+    static THREAD_STACK_LOWER_BOUND: Option<usize> = thread_stack_lower_bound();
+}
+
+#[cfg(target_os = "linux")]
+fn thread_stack_lower_bound() -> Option<usize> {
+    unsafe {
+        let mut attr: libc::pthread_attr_t = std::mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let mut addr: *mut libc::c_void = std::ptr::null_mut();
+        let mut size = 0;
+        let result = libc::pthread_attr_getstack(&attr, &mut addr, &mut size);
+        libc::pthread_attr_destroy(&mut attr);
+        if result != 0 {
+            return None;
+        }
+        Some(addr as usize)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn thread_stack_lower_bound() -> Option<usize> {
+    unsafe {
+        let thread = libc::pthread_self();
+        let top = libc::pthread_get_stackaddr_np(thread) as usize;
+        top.checked_sub(libc::pthread_get_stacksize_np(thread))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn thread_stack_lower_bound() -> Option<usize> {
+    None
+}
+
+/// The lowest address the code compiled without signal handlers may use on
+/// the current thread, whose stack pointer is `stack_pointer`.
+fn stack_lower_bound(stack_pointer: usize) -> usize {
+    match THREAD_STACK_LOWER_BOUND.with(|bound| *bound) {
+        Some(bound) => bound
+            .saturating_add(NO_SIGNALS_STACK_RESERVE)
+            .min(stack_pointer),
+        None => stack_pointer.saturating_sub(NO_SIGNALS_STACK_SIZE),
+    }
+}
+
 /// Inline breakpoint size for x86-64.
 pub const INLINE_BREAKPOINT_SIZE_X86_SINGLEPASS: usize = 7;
 
 /// Inline breakpoint size for aarch64.
 pub const INLINE_BREAKPOINT_SIZE_AARCH64_SINGLEPASS: usize = 12;
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(all(target_arch = "x86_64", not(windows)))]
 lazy_static! {
     /// Performs a System V call to `target` with [stack_top..stack_base] as the argument list, from right to left.
     static ref CONSTRUCT_STACK_AND_CALL_WASM: unsafe extern "C" fn (stack_top: *const u64, stack_base: *const u64, ctx: *mut vm::Ctx, target: *const vm::Func) -> u64 = {
@@ -148,6 +209,113 @@ lazy_static! {
     };
 }
 
+#[cfg(all(target_arch = "x86_64", windows))]
+lazy_static! {
+    /// Performs a Windows x64 call to `target` with [stack_top..stack_base] as the argument list, from right to left.
+    ///
+    /// Generated code doesn't preserve RSI, RDI and XMM6-XMM15, which are callee-saved on Windows,
+    /// so they're saved here.
+    static ref CONSTRUCT_STACK_AND_CALL_WASM: unsafe extern "C" fn (stack_top: *const u64, stack_base: *const u64, ctx: *mut vm::Ctx, target: *const vm::Func) -> u64 = {
+        let mut assembler = Assembler::new().unwrap();
+        let offset = assembler.offset();
+        dynasm!(
+            assembler
+            ; push r15
+            ; push r14
+            ; push r13
+            ; push r12
+            ; push rsi
+            ; push rdi
+            ; push rbp
+            ; mov rbp, rsp
+            ; sub rsp, 160
+            ; movdqu [rsp], xmm6
+            ; movdqu [rsp + 16], xmm7
+            ; movdqu [rsp + 32], xmm8
+            ; movdqu [rsp + 48], xmm9
+            ; movdqu [rsp + 64], xmm10
+            ; movdqu [rsp + 80], xmm11
+            ; movdqu [rsp + 96], xmm12
+            ; movdqu [rsp + 112], xmm13
+            ; movdqu [rsp + 128], xmm14
+            ; movdqu [rsp + 144], xmm15
+
+            ; mov r15, rcx
+            ; mov r14, rdx
+            ; mov r13, r8
+            ; mov r12, r9
+
+            ; mov rcx, r13 // ctx
+
+            ; sub r14, 8
+            ; cmp r14, r15
+            ; jb >stack_ready
+
+            ; mov rdx, [r14]
+            ; sub r14, 8
+            ; cmp r14, r15
+            ; jb >stack_ready
+
+            ; mov r8, [r14]
+            ; sub r14, 8
+            ; cmp r14, r15
+            ; jb >stack_ready
+
+            ; mov r9, [r14]
+            ; sub r14, 8
+            ; cmp r14, r15
+            ; jb >stack_ready
+
+            ; mov rax, r14
+            ; sub rax, r15
+            ; sub rsp, rax
+            ; sub rsp, 8
+            ; mov rax, QWORD 0xfffffffffffffff0u64 as i64
+            ; and rsp, rax
+            ; mov rax, rsp
+            ; loop_begin:
+            ; mov r11, [r14]
+            ; mov [rax], r11
+            ; sub r14, 8
+            ; add rax, 8
+            ; cmp r14, r15
+            ; jb >stack_ready
+            ; jmp <loop_begin
+
+            ; stack_ready:
+            ; mov rax, QWORD 0xfffffffffffffff0u64 as i64
+            ; and rsp, rax
+            // Shadow space for the register parameters.
+            ; sub rsp, 32
+            ; call r12
+
+            ; movdqu xmm6, [rbp - 160]
+            ; movdqu xmm7, [rbp - 144]
+            ; movdqu xmm8, [rbp - 128]
+            ; movdqu xmm9, [rbp - 112]
+            ; movdqu xmm10, [rbp - 96]
+            ; movdqu xmm11, [rbp - 80]
+            ; movdqu xmm12, [rbp - 64]
+            ; movdqu xmm13, [rbp - 48]
+            ; movdqu xmm14, [rbp - 32]
+            ; movdqu xmm15, [rbp - 16]
+            ; mov rsp, rbp
+            ; pop rbp
+            ; pop rdi
+            ; pop rsi
+            ; pop r12
+            ; pop r13
+            ; pop r14
+            ; pop r15
+            ; ret
+        );
+        let buf = assembler.finalize().unwrap();
+        let ret = unsafe { mem::transmute(buf.ptr(offset)) };
+        mem::forget(buf);
+        ret
+    };
+}
+
 #[cfg(target_arch = "aarch64")]
 #[repr(C)]
 #[allow(dead_code)]
@@ -322,6 +490,14 @@ impl RunnableModule for X64ExecutionContext {
         Some(self.breakpoints.clone())
     }
 
+    #[cfg(windows)]
+    unsafe fn patch_local_function(&self, _idx: usize, _target_address: usize) -> bool {
+        // Patched functions go through `register_preservation_trampoline`, which is only
+        // available with the signal-based fault handling.
+        false
+    }
+
+    #[cfg(unix)]
     unsafe fn patch_local_function(&self, idx: usize, target_address: usize) -> bool {
         /*
         0:       48 b8 42 42 42 42 42 42 42 42   movabsq $4774451407313060418, %rax
@@ -337,10 +513,14 @@ impl RunnableModule for X64ExecutionContext {
             jmpq_r11: [u8; 3],
         }
 
+        // The code is dual-mapped where possible, so that making it writable
+        // doesn't take it away from threads that are running it.
         self.code.make_writable();
 
-        let trampoline = &mut *(self.function_pointers[self.func_import_count + idx].0
-            as *const LocalTrampoline as *mut LocalTrampoline);
+        let trampoline = &mut *(self
+            .code
+            .writable_ptr(self.function_pointers[self.func_import_count + idx].0 as *const u8)
+            as *mut LocalTrampoline);
         trampoline.movabsq_rax[0] = 0x48;
         trampoline.movabsq_rax[1] = 0xb8;
         trampoline.addr_rax = target_address as u64;
@@ -368,11 +548,20 @@ impl RunnableModule for X64ExecutionContext {
             num_params_plus_one: Option<NonNull<c_void>>,
         ) -> bool {
             let rm: &Box<dyn RunnableModule> = &(&*(*ctx).module).runnable_module;
+            let no_signals = (&*(*ctx).module).info.no_signals;
 
             let args =
                 slice::from_raw_parts(args, num_params_plus_one.unwrap().as_ptr() as usize - 1);
 
-            let ret = match protect_unix::call_protected(
+            // Without signal handlers, stack overflows are caught by the stack
+            // checks, which need a bound unless a caller already set one.
+            let outer_stack_lower_bound = (*ctx).internal.stack_lower_bound;
+            if no_signals && outer_stack_lower_bound.is_null() {
+                let stack_pointer = &outer_stack_lower_bound as *const _ as usize;
+                (*ctx).internal.stack_lower_bound = stack_lower_bound(stack_pointer) as *mut u8;
+            }
+
+            let ret = match protect::call_protected(
                 || {
                     #[cfg(target_arch = "x86_64")]
                     {
@@ -500,6 +689,7 @@ impl RunnableModule for X64ExecutionContext {
                     }
                 },
                 rm.get_breakpoints(),
+                no_signals,
             ) {
                 Ok(x) => {
                     if !rets.is_null() {
@@ -509,12 +699,18 @@ impl RunnableModule for X64ExecutionContext {
                 }
                 Err(err) => {
                     match err {
-                        protect_unix::CallProtError::Trap(info) => *trap_info = info,
-                        protect_unix::CallProtError::Error(data) => *user_error = Some(data),
+                        protect::CallProtError::Trap(info) => *trap_info = info,
+                        protect::CallProtError::Error(data) => {
+                            match data.downcast::<WasmTrapInfo>() {
+                                Ok(info) => *trap_info = *info,
+                                Err(data) => *user_error = Some(data),
+                            }
+                        }
                     }
                     false
                 }
             };
+            (*ctx).internal.stack_lower_bound = outer_stack_lower_bound;
             ret
         }
 
@@ -537,8 +733,8 @@ impl RunnableModule for X64ExecutionContext {
     }
 
     unsafe fn do_early_trap(&self, data: Box<dyn Any + Send>) -> ! {
-        protect_unix::TRAP_EARLY_DATA.with(|x| x.set(Some(data)));
-        protect_unix::trigger_trap();
+        protect::TRAP_EARLY_DATA.with(|x| x.set(Some(data)));
+        protect::trigger_trap();
     }
 
     fn get_code(&self) -> Option<&[u8]> {
@@ -627,6 +823,34 @@ struct CodegenConfig {
     memory_bound_check_mode: MemoryBoundCheckMode,
     enforce_stack_check: bool,
     track_state: bool,
+    /// The slot holding the address of `wasm_trap`, at the start of the
+    /// code, when compiling without signal handlers.
+    trap_slot: Option<DynamicLabel>,
+    spectre_mitigations: SpectreMitigations,
+    /// Replace the NaNs produced by floating point instructions with the
+    /// canonical NaN, for deterministic execution.
+    nan_canonicalization: bool,
+}
+
+/// Raises the trap `info`, a `WasmTrapInfo` discriminant, for code compiled
+/// without signal handlers.
+extern "C" fn wasm_trap(info: u32) -> ! {
+    let info = match info {
+        0 => WasmTrapInfo::Unreachable,
+        1 => WasmTrapInfo::IncorrectCallIndirectSignature,
+        2 => WasmTrapInfo::MemoryOutOfBounds,
+        3 => WasmTrapInfo::CallIndirectOOB,
+        4 => WasmTrapInfo::IllegalArithmetic,
+        5 => WasmTrapInfo::MisalignedAtomicAccess,
+        _ => WasmTrapInfo::Unknown,
+    };
+    unsafe { protect::throw(Box::new(info)) }
+}
+
+/// Stores the address of `wasm_trap` in the trap slot of `code`.
+fn patch_trap_slot(code: &mut CodeMemory) {
+    let address = wasm_trap as extern "C" fn(u32) -> ! as usize as u64;
+    code[0..8].copy_from_slice(&address.to_le_bytes());
 }
 
 impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
@@ -731,8 +955,11 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
 
         let total_size = assembler.get_offset().0;
         let _output = assembler.finalize().unwrap();
-        let mut output = CodeMemory::new(_output.len());
+        let mut output = CodeMemory::new_dual_mapped(_output.len());
         output[0.._output.len()].copy_from_slice(&_output);
+        if self.config.as_ref().unwrap().trap_slot.is_some() {
+            patch_trap_slot(&mut output);
+        }
         output.make_executable();
 
         let mut out_labels: Vec<FuncPtr> = vec![];
@@ -850,20 +1077,22 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
         let imported_func_ctx_addr = imported_func + vm::ImportedFunc::offset_func_ctx() as usize;
         let imported_func_ctx_vmctx_addr = vm::FuncCtx::offset_vmctx() as usize;
 
+        let vmctx = Machine::get_vmctx_param_reg();
+
         a.emit_mov(
             Size::S64,
-            Location::Memory(GPR::RDI, imported_funcs_addr as i32),
+            Location::Memory(vmctx, imported_funcs_addr as i32),
             Location::GPR(GPR::RAX),
         );
         a.emit_mov(
             Size::S64,
             Location::Memory(GPR::RAX, imported_func_ctx_addr as i32),
-            Location::GPR(GPR::RDI),
+            Location::GPR(vmctx),
         );
         a.emit_mov(
             Size::S64,
-            Location::Memory(GPR::RDI, imported_func_ctx_vmctx_addr as i32),
-            Location::GPR(GPR::RDI),
+            Location::Memory(vmctx, imported_func_ctx_vmctx_addr as i32),
+            Location::GPR(vmctx),
         );
         a.emit_mov(
             Size::S64,
@@ -878,10 +1107,64 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
     }
 
     fn feed_compiler_config(&mut self, config: &CompilerConfig) -> Result<(), CodegenError> {
+        if config.spectre_mitigations.any() && cfg!(not(target_arch = "x86_64")) {
+            return Err(CodegenError {
+                message: format!("spectre mitigations need x86-64"),
+            });
+        }
+
+        if config.features.deterministic
+            && !self
+                .assembler
+                .as_ref()
+                .unwrap()
+                .arch_supports_canonicalize_nan()
+        {
+            return Err(CodegenError {
+                message: format!("deterministic execution needs NaN canonicalization"),
+            });
+        }
+
+        let trap_slot = if config.no_signals {
+            if cfg!(not(target_arch = "x86_64")) {
+                return Err(CodegenError {
+                    message: format!("compiling without signal handlers needs x86-64"),
+                });
+            }
+            if let MemoryBoundCheckMode::Disable = config.memory_bound_check_mode {
+                return Err(CodegenError {
+                    message: format!("compiling without signal handlers needs bounds checks"),
+                });
+            }
+            if !config.enforce_stack_check {
+                return Err(CodegenError {
+                    message: format!("compiling without signal handlers needs stack checks"),
+                });
+            }
+
+            // Traps call `wasm_trap` through a slot at the start of the code,
+            // which is patched when the code is loaded.
+            let a = self.assembler.as_mut().unwrap();
+            if a.get_offset().0 != 0 {
+                return Err(CodegenError {
+                    message: format!("feed_compiler_config: code already emitted"),
+                });
+            }
+            let trap_slot = a.get_label();
+            a.emit_label(trap_slot);
+            a.emit_u64(0);
+            Some(trap_slot)
+        } else {
+            None
+        };
+
         self.config = Some(Arc::new(CodegenConfig {
             memory_bound_check_mode: config.memory_bound_check_mode,
             enforce_stack_check: config.enforce_stack_check,
             track_state: config.track_state,
+            trap_slot,
+            spectre_mitigations: config.spectre_mitigations,
+            nan_canonicalization: config.features.deterministic,
         }));
         Ok(())
     }
@@ -891,8 +1174,11 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
         let cache_image: CacheImage = bincode::deserialize(memory.as_slice())
             .map_err(|x| CacheError::DeserializeError(format!("{:?}", x)))?;
 
-        let mut code_mem = CodeMemory::new(cache_image.code.len());
+        let mut code_mem = CodeMemory::new_dual_mapped(cache_image.code.len());
         code_mem[0..cache_image.code.len()].copy_from_slice(&cache_image.code);
+        if info.no_signals {
+            patch_trap_slot(&mut code_mem);
+        }
         code_mem.make_executable();
 
         let function_pointers: Vec<FuncPtr> = cache_image
@@ -922,6 +1208,7 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
                 buffer: Arc::from(memory.as_slice().to_vec().into_boxed_slice()),
             }),
             info,
+            compile_info: None,
         })
     }
 }
@@ -947,10 +1234,52 @@ impl X64FunctionCode {
             .insert(m.state.wasm_inst_offset, SuspendOffset::Trappable(offset));
     }
 
+    /// Traps with `info` if `condition` holds.  Without signal handlers,
+    /// the trap calls `wasm_trap` instead of faulting.
+    fn emit_trap(
+        a: &mut Assembler,
+        config: &CodegenConfig,
+        condition: Condition,
+        info: WasmTrapInfo,
+    ) {
+        let trap_slot = match config.trap_slot {
+            Some(x) => x,
+            None => {
+                match condition {
+                    Condition::None => a.emit_ud2(),
+                    _ => a.emit_conditional_trap(condition),
+                }
+                return;
+            }
+        };
+
+        let trap = a.get_label();
+        let after = a.get_label();
+        if condition != Condition::None {
+            a.emit_jmp(condition, trap);
+            a.emit_jmp(Condition::None, after);
+        }
+        a.emit_label(trap);
+        // `wasm_trap` doesn't return, so the stack can just be realigned.
+        a.emit_and(Size::S64, Location::Imm32(!0xf), Location::GPR(GPR::RSP));
+        a.emit_mov(
+            Size::S32,
+            Location::Imm32(info as u32),
+            Location::GPR(Machine::get_vmctx_param_reg()), // the first parameter
+        );
+        if cfg!(windows) {
+            // shadow space
+            a.emit_sub(Size::S64, Location::Imm32(32), Location::GPR(GPR::RSP));
+        }
+        a.emit_call_indirect_label(trap_slot);
+        a.emit_label(after);
+    }
+
     /// Moves `loc` to a valid location for `div`/`idiv`.
     fn emit_relaxed_xdiv(
         a: &mut Assembler,
         m: &mut Machine,
+        config: &CodegenConfig,
         op: fn(&mut Assembler, Size, Location),
         sz: Size,
         loc: Location,
@@ -958,20 +1287,51 @@ impl X64FunctionCode {
         control_stack: &mut [ControlFrame],
     ) {
         m.state.wasm_stack_private_depth += 1;
-        match loc {
+        let divisor = match loc {
             Location::Imm64(_) | Location::Imm32(_) => {
                 a.emit_mov(sz, loc, Location::GPR(GPR::RCX)); // must not be used during div (rax, rdx)
-                Self::mark_trappable(a, m, fsm, control_stack);
-                op(a, sz, Location::GPR(GPR::RCX));
-            }
-            _ => {
-                Self::mark_trappable(a, m, fsm, control_stack);
-                op(a, sz, loc);
+                Location::GPR(GPR::RCX)
             }
+            _ => loc,
+        };
+        if config.trap_slot.is_some() {
+            // Division by zero faults.
+            a.emit_cmp(sz, Location::Imm32(0), divisor);
+            Self::emit_trap(a, config, Condition::Equal, WasmTrapInfo::IllegalArithmetic);
         }
+        Self::mark_trappable(a, m, fsm, control_stack);
+        op(a, sz, divisor);
         m.state.wasm_stack_private_depth -= 1;
     }
 
+    /// Traps if a signed division of `dividend` by `divisor` overflows,
+    /// which faults instead when compiling with signal handlers.
+    fn emit_idiv_overflow_check(
+        a: &mut Assembler,
+        m: &mut Machine,
+        config: &CodegenConfig,
+        sz: Size,
+        dividend: Location,
+        divisor: Location,
+    ) {
+        if config.trap_slot.is_none() {
+            return;
+        }
+        let (min, minus_one) = match sz {
+            Size::S32 => (Location::Imm32(0x80000000), Location::Imm32(0xffffffff)),
+            _ => (
+                Location::Imm64(0x8000000000000000u64),
+                Location::Imm64(0xffffffffffffffffu64),
+            ),
+        };
+        let no_overflow = a.get_label();
+        Self::emit_relaxed_binop(a, m, Assembler::emit_cmp, sz, min, dividend);
+        a.emit_jmp(Condition::NotEqual, no_overflow);
+        Self::emit_relaxed_binop(a, m, Assembler::emit_cmp, sz, minus_one, divisor);
+        Self::emit_trap(a, config, Condition::Equal, WasmTrapInfo::IllegalArithmetic);
+        a.emit_label(no_overflow);
+    }
+
     /// Moves `src` and `dst` to valid locations for `movzx`/`movsx`.
     fn emit_relaxed_zx_sx(
         a: &mut Assembler,
@@ -1601,7 +1961,46 @@ impl X64FunctionCode {
         Ok(())
     }
 
-    /// Emits a System V call sequence.
+    /// Replaces the value at `loc` with the canonical NaN if it's a NaN.
+    fn emit_canonicalize_nan(a: &mut Assembler, m: &mut Machine, sz: Size, loc: Location) {
+        let tmp_value = m.acquire_temp_xmm().unwrap();
+        let tmp_mask = m.acquire_temp_xmm().unwrap();
+        let tmp_nan = m.acquire_temp_xmm().unwrap();
+        let tmpg = m.acquire_temp_gpr().unwrap();
+
+        a.emit_mov(Size::S64, loc, Location::XMM(tmp_value));
+        match sz {
+            Size::S32 => {
+                a.emit_vcmpunordss(tmp_value, XMMOrMemory::XMM(tmp_value), tmp_mask);
+                a.emit_mov(
+                    Size::S64,
+                    Location::Imm32(0x7FC0_0000), // Canonical NaN
+                    Location::GPR(tmpg),
+                );
+                a.emit_mov(Size::S64, Location::GPR(tmpg), Location::XMM(tmp_nan));
+                a.emit_vblendvps(tmp_mask, XMMOrMemory::XMM(tmp_nan), tmp_value, tmp_value);
+            }
+            Size::S64 => {
+                a.emit_vcmpunordsd(tmp_value, XMMOrMemory::XMM(tmp_value), tmp_mask);
+                a.emit_mov(
+                    Size::S64,
+                    Location::Imm64(0x7FF8_0000_0000_0000), // Canonical NaN
+                    Location::GPR(tmpg),
+                );
+                a.emit_mov(Size::S64, Location::GPR(tmpg), Location::XMM(tmp_nan));
+                a.emit_vblendvpd(tmp_mask, XMMOrMemory::XMM(tmp_nan), tmp_value, tmp_value);
+            }
+            _ => unreachable!(),
+        }
+        a.emit_mov(Size::S64, Location::XMM(tmp_value), loc);
+
+        m.release_temp_gpr(tmpg);
+        m.release_temp_xmm(tmp_nan);
+        m.release_temp_xmm(tmp_mask);
+        m.release_temp_xmm(tmp_value);
+    }
+
+    /// Emits a System V call sequence, or a Windows x64 one on Windows.
     ///
     /// This function must not use RAX before `cb` is called.
     fn emit_call_sysv<I: Iterator<Item = Location>, F: FnOnce(&mut Assembler)>(
@@ -1765,6 +2164,16 @@ impl X64FunctionCode {
             }
         }
 
+        // Reserve the shadow space for the register parameters.
+        #[cfg(windows)]
+        {
+            a.emit_sub(Size::S64, Location::Imm32(32), Location::GPR(GPR::RSP));
+            stack_offset += 32;
+            for _ in 0..4 {
+                m.state.stack_values.push(MachineValue::Undefined);
+            }
+        }
+
         // Sort register moves so that register are not overwritten before read.
         sort_call_movs(&mut call_movs);
 
@@ -1911,7 +2320,7 @@ impl X64FunctionCode {
             Location::GPR(tmp_base),
         );
 
-        if need_check {
+        let tmp_bound = if need_check {
             let tmp_bound = m.acquire_temp_gpr().unwrap();
 
             a.emit_mov(
@@ -1949,10 +2358,17 @@ impl X64FunctionCode {
             // Trap if the end address of the requested area is above that of the linear memory.
             a.emit_add(Size::S64, Location::GPR(tmp_base), Location::GPR(tmp_addr));
             a.emit_cmp(Size::S64, Location::GPR(tmp_bound), Location::GPR(tmp_addr));
-            a.emit_conditional_trap(Condition::Above);
+            Self::emit_trap(a, config, Condition::Above, WasmTrapInfo::MemoryOutOfBounds);
 
-            m.release_temp_gpr(tmp_bound);
-        }
+            if config.spectre_mitigations.mask_memory_accesses {
+                Some(tmp_bound)
+            } else {
+                m.release_temp_gpr(tmp_bound);
+                None
+            }
+        } else {
+            None
+        };
 
         // Calculates the real address, and loads from it.
         a.emit_mov(Size::S32, addr, Location::GPR(tmp_addr));
@@ -1966,6 +2382,20 @@ impl X64FunctionCode {
         a.emit_add(Size::S64, Location::GPR(tmp_base), Location::GPR(tmp_addr));
         m.release_temp_gpr(tmp_base);
 
+        if let Some(tmp_bound) = tmp_bound {
+            // Clamps the address to zero if the access doesn't fit below the end of
+            // linear memory, so that a mispredicted bounds check can't read past it.
+            a.emit_sub(
+                Size::S64,
+                Location::Imm32(value_size as u32),
+                Location::GPR(tmp_bound),
+            );
+            a.emit_cmp(Size::S64, Location::GPR(tmp_bound), Location::GPR(tmp_addr));
+            a.emit_mov(Size::S64, Location::Imm32(0), Location::GPR(tmp_bound));
+            a.emit_cmov_gpr_64(Condition::Above, tmp_bound, tmp_addr);
+            m.release_temp_gpr(tmp_bound);
+        }
+
         let align = match memarg.flags & 3 {
             0 => 1,
             1 => 2,
@@ -1989,7 +2419,12 @@ impl X64FunctionCode {
                 Location::Imm32(align - 1),
                 Location::GPR(tmp_aligncheck),
             );
-            a.emit_conditional_trap(Condition::NotEqual);
+            Self::emit_trap(
+                a,
+                config,
+                Condition::NotEqual,
+                WasmTrapInfo::MisalignedAtomicAccess,
+            );
             m.release_temp_gpr(tmp_aligncheck);
         }
 
@@ -2108,6 +2543,7 @@ impl X64FunctionCode {
     fn emit_f32_int_conv_check_trap(
         a: &mut Assembler,
         m: &mut Machine,
+        config: &CodegenConfig,
         reg: XMM,
         lower_bound: f32,
         upper_bound: f32,
@@ -2117,7 +2553,7 @@ impl X64FunctionCode {
 
         Self::emit_f32_int_conv_check(a, m, reg, lower_bound, upper_bound, trap, trap, trap, end);
         a.emit_label(trap);
-        a.emit_ud2();
+        Self::emit_trap(a, config, Condition::None, WasmTrapInfo::IllegalArithmetic);
         a.emit_label(end);
     }
 
@@ -2232,6 +2668,7 @@ impl X64FunctionCode {
     fn emit_f64_int_conv_check_trap(
         a: &mut Assembler,
         m: &mut Machine,
+        config: &CodegenConfig,
         reg: XMM,
         lower_bound: f64,
         upper_bound: f64,
@@ -2241,7 +2678,7 @@ impl X64FunctionCode {
 
         Self::emit_f64_int_conv_check(a, m, reg, lower_bound, upper_bound, trap, trap, trap, end);
         a.emit_label(trap);
-        a.emit_ud2();
+        Self::emit_trap(a, config, Condition::None, WasmTrapInfo::IllegalArithmetic);
         a.emit_label(end);
     }
 
@@ -2360,12 +2797,12 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             a.emit_cmp(
                 Size::S64,
                 Location::Memory(
-                    GPR::RDI, // first parameter is vmctx
+                    Machine::get_vmctx_param_reg(), // first parameter is vmctx
                     vm::Ctx::offset_stack_lower_bound() as i32,
                 ),
                 Location::GPR(GPR::RSP),
             );
-            a.emit_conditional_trap(Condition::Below);
+            Self::emit_trap(a, &self.config, Condition::Below, WasmTrapInfo::Unknown);
         }
 
         self.locals = self
@@ -2495,6 +2932,13 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             Event::Internal(x) => {
                 match x {
                     InternalEvent::Breakpoint(callback) => {
+                        if self.config.trap_slot.is_some() {
+                            return Err(CodegenError {
+                                message: format!(
+                                    "breakpoints need signal handlers, which the module is compiled without"
+                                ),
+                            });
+                        }
                         self.breakpoints
                             .as_mut()
                             .unwrap()
@@ -2788,6 +3232,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 Self::emit_relaxed_xdiv(
                     a,
                     &mut self.machine,
+                    &self.config,
                     Assembler::emit_div,
                     Size::S32,
                     loc_b,
@@ -2808,11 +3253,20 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
                     false,
                 )[0];
+                Self::emit_idiv_overflow_check(
+                    a,
+                    &mut self.machine,
+                    &self.config,
+                    Size::S32,
+                    loc_a,
+                    loc_b,
+                );
                 a.emit_mov(Size::S32, loc_a, Location::GPR(GPR::RAX));
                 a.emit_cdq();
                 Self::emit_relaxed_xdiv(
                     a,
                     &mut self.machine,
+                    &self.config,
                     Assembler::emit_idiv,
                     Size::S32,
                     loc_b,
@@ -2838,6 +3292,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 Self::emit_relaxed_xdiv(
                     a,
                     &mut self.machine,
+                    &self.config,
                     Assembler::emit_div,
                     Size::S32,
                     loc_b,
@@ -2889,6 +3344,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 Self::emit_relaxed_xdiv(
                     a,
                     &mut self.machine,
+                    &self.config,
                     Assembler::emit_idiv,
                     Size::S32,
                     loc_b,
@@ -3187,6 +3643,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 Self::emit_relaxed_xdiv(
                     a,
                     &mut self.machine,
+                    &self.config,
                     Assembler::emit_div,
                     Size::S64,
                     loc_b,
@@ -3207,11 +3664,20 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
                     false,
                 )[0];
+                Self::emit_idiv_overflow_check(
+                    a,
+                    &mut self.machine,
+                    &self.config,
+                    Size::S64,
+                    loc_a,
+                    loc_b,
+                );
                 a.emit_mov(Size::S64, loc_a, Location::GPR(GPR::RAX));
                 a.emit_cqo();
                 Self::emit_relaxed_xdiv(
                     a,
                     &mut self.machine,
+                    &self.config,
                     Assembler::emit_idiv,
                     Size::S64,
                     loc_b,
@@ -3237,6 +3703,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 Self::emit_relaxed_xdiv(
                     a,
                     &mut self.machine,
+                    &self.config,
                     Assembler::emit_div,
                     Size::S64,
                     loc_b,
@@ -3296,6 +3763,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 Self::emit_relaxed_xdiv(
                     a,
                     &mut self.machine,
+                    &self.config,
                     Assembler::emit_idiv,
                     Size::S64,
                     loc_b,
@@ -4748,6 +5216,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Self::emit_f32_int_conv_check_trap(
                         a,
                         &mut self.machine,
+                        &self.config,
                         tmp_in,
                         GEF32_LT_U32_MIN,
                         LEF32_GT_U32_MAX,
@@ -4859,6 +5328,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Self::emit_f32_int_conv_check_trap(
                         a,
                         &mut self.machine,
+                        &self.config,
                         tmp_in,
                         GEF32_LT_I32_MIN,
                         LEF32_GT_I32_MAX,
@@ -4976,6 +5446,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Self::emit_f32_int_conv_check_trap(
                         a,
                         &mut self.machine,
+                        &self.config,
                         tmp_in,
                         GEF32_LT_I64_MIN,
                         LEF32_GT_I64_MAX,
@@ -5093,6 +5564,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Self::emit_f32_int_conv_check_trap(
                         a,
                         &mut self.machine,
+                        &self.config,
                         tmp_in,
                         GEF32_LT_U64_MIN,
                         LEF32_GT_U64_MAX,
@@ -5253,6 +5725,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Self::emit_f64_int_conv_check_trap(
                         a,
                         &mut self.machine,
+                        &self.config,
                         tmp_in,
                         GEF64_LT_U32_MIN,
                         LEF64_GT_U32_MAX,
@@ -5370,6 +5843,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Self::emit_f64_int_conv_check_trap(
                         a,
                         &mut self.machine,
+                        &self.config,
                         real_in,
                         GEF64_LT_I32_MIN,
                         LEF64_GT_I32_MAX,
@@ -5493,6 +5967,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Self::emit_f64_int_conv_check_trap(
                         a,
                         &mut self.machine,
+                        &self.config,
                         tmp_in,
                         GEF64_LT_I64_MIN,
                         LEF64_GT_I64_MAX,
@@ -5611,6 +6086,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Self::emit_f64_int_conv_check_trap(
                         a,
                         &mut self.machine,
+                        &self.config,
                         tmp_in,
                         GEF64_LT_U64_MIN,
                         LEF64_GT_U64_MAX,
@@ -6210,8 +6686,19 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Location::GPR(table_base),
                 );
                 a.emit_cmp(Size::S32, func_index, Location::GPR(table_count));
-                a.emit_conditional_trap(Condition::BelowEqual);
+                Self::emit_trap(
+                    a,
+                    &self.config,
+                    Condition::BelowEqual,
+                    WasmTrapInfo::CallIndirectOOB,
+                );
                 a.emit_mov(Size::S64, func_index, Location::GPR(table_count));
+                if self.config.spectre_mitigations.mask_table_indices {
+                    // Clamps the index to zero if it's out of bounds, so that a
+                    // mispredicted bounds check can't load an entry past the table.
+                    a.emit_mov(Size::S64, Location::Imm32(0), Location::GPR(sigidx));
+                    a.emit_cmov_gpr_64(Condition::BelowEqual, sigidx, table_count);
+                }
                 a.emit_imul_imm32_gpr64(vm::Anyfunc::size() as u32, table_count);
                 a.emit_add(
                     Size::S64,
@@ -6236,7 +6723,15 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     Location::GPR(sigidx),
                     Location::Memory(table_count, (vm::Anyfunc::offset_sig_id() as usize) as i32),
                 );
-                a.emit_conditional_trap(Condition::NotEqual);
+                Self::emit_trap(
+                    a,
+                    &self.config,
+                    Condition::NotEqual,
+                    WasmTrapInfo::IncorrectCallIndirectSignature,
+                );
+                if self.config.spectre_mitigations.fence_indirect_calls {
+                    a.emit_lfence();
+                }
 
                 self.machine.release_temp_gpr(sigidx);
                 self.machine.release_temp_gpr(table_count);
@@ -6572,6 +7067,62 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 self.value_stack.push(ret);
                 a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
             }
+            Operator::MemoryCopy | Operator::MemoryFill => {
+                let offset = match *op {
+                    Operator::MemoryCopy => vm::Intrinsics::offset_memory_copy(),
+                    _ => vm::Intrinsics::offset_memory_fill(),
+                };
+                // `src` is the byte value to fill with for `memory.fill`.
+                let len = self.value_stack.pop().unwrap();
+                let src = self.value_stack.pop().unwrap();
+                let dst = self.value_stack.pop().unwrap();
+                let params = [dst, src, len];
+
+                self.machine.release_locations_only_regs(&params);
+
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(
+                        Machine::get_vmctx_reg(),
+                        vm::Ctx::offset_intrinsics() as i32,
+                    ),
+                    Location::GPR(GPR::RAX),
+                );
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(GPR::RAX, offset as i32),
+                    Location::GPR(GPR::RAX),
+                );
+
+                self.machine.release_locations_only_osr_state(3);
+
+                // Memory 0 has index 0 whether it's local or imported.
+                Self::emit_call_sysv(
+                    a,
+                    &mut self.machine,
+                    |a| {
+                        let label = a.get_label();
+                        let after = a.get_label();
+                        a.emit_jmp(Condition::None, after);
+                        a.emit_label(label);
+                        a.emit_host_redirection(GPR::RAX);
+                        a.emit_label(after);
+                        a.emit_call_label(label);
+                    },
+                    iter::once(Location::Imm32(0)).chain(params.iter().cloned()),
+                    None,
+                )?;
+
+                self.machine.release_locations_only_stack(a, &params);
+
+                a.emit_cmp(Size::S32, Location::Imm32(0), Location::GPR(GPR::RAX));
+                Self::emit_trap(
+                    a,
+                    &self.config,
+                    Condition::NotEqual,
+                    WasmTrapInfo::MemoryOutOfBounds,
+                );
+            }
             Operator::I32Load { ref memarg } => {
                 let target =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
@@ -7298,7 +7849,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             }
             Operator::Unreachable => {
                 Self::mark_trappable(a, &self.machine, &mut self.fsm, &mut self.control_stack);
-                a.emit_ud2();
+                Self::emit_trap(a, &self.config, Condition::None, WasmTrapInfo::Unreachable);
                 self.unreachable_depth = 1;
             }
             Operator::Return => {
@@ -9614,6 +10165,37 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             }
         }
 
+        if self.config.nan_canonicalization {
+            // `min` and `max` already return the canonical NaN.
+            let size = match *op {
+                Operator::F32Add
+                | Operator::F32Sub
+                | Operator::F32Mul
+                | Operator::F32Div
+                | Operator::F32Sqrt
+                | Operator::F32Ceil
+                | Operator::F32Floor
+                | Operator::F32Trunc
+                | Operator::F32Nearest
+                | Operator::F32DemoteF64 => Some(Size::S32),
+                Operator::F64Add
+                | Operator::F64Sub
+                | Operator::F64Mul
+                | Operator::F64Div
+                | Operator::F64Sqrt
+                | Operator::F64Ceil
+                | Operator::F64Floor
+                | Operator::F64Trunc
+                | Operator::F64Nearest
+                | Operator::F64PromoteF32 => Some(Size::S64),
+                _ => None,
+            };
+            if let Some(size) = size {
+                let loc = *self.value_stack.last().unwrap();
+                Self::emit_canonicalize_nan(a, &mut self.machine, size, loc);
+            }
+        }
+
         Ok(())
     }
 }
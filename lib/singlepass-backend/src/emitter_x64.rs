@@ -113,6 +113,8 @@ pub trait Emitter {
 
     fn emit_cmovae_gpr_32(&mut self, src: GPR, dst: GPR);
     fn emit_cmovae_gpr_64(&mut self, src: GPR, dst: GPR);
+    fn emit_cmov_gpr_64(&mut self, condition: Condition, src: GPR, dst: GPR);
+    fn emit_lfence(&mut self);
 
     fn emit_vmovaps(&mut self, src: XMMOrMemory, dst: XMMOrMemory);
     fn emit_vmovapd(&mut self, src: XMMOrMemory, dst: XMMOrMemory);
@@ -193,6 +195,7 @@ pub trait Emitter {
     fn emit_ret(&mut self);
     fn emit_call_label(&mut self, label: Self::Label);
     fn emit_call_location(&mut self, loc: Location);
+    fn emit_call_indirect_label(&mut self, label: Self::Label);
 
     fn emit_bkpt(&mut self);
 
@@ -1113,6 +1116,23 @@ impl Emitter for Assembler {
     fn emit_cmovae_gpr_64(&mut self, src: GPR, dst: GPR) {
         dynasm!(self ; cmovae Rq(dst as u8), Rq(src as u8));
     }
+    fn emit_cmov_gpr_64(&mut self, condition: Condition, src: GPR, dst: GPR) {
+        match condition {
+            Condition::Above => dynasm!(self ; cmova Rq(dst as u8), Rq(src as u8)),
+            Condition::AboveEqual => dynasm!(self ; cmovae Rq(dst as u8), Rq(src as u8)),
+            Condition::Below => dynasm!(self ; cmovb Rq(dst as u8), Rq(src as u8)),
+            Condition::BelowEqual => dynasm!(self ; cmovbe Rq(dst as u8), Rq(src as u8)),
+            Condition::Equal => dynasm!(self ; cmove Rq(dst as u8), Rq(src as u8)),
+            Condition::NotEqual => dynasm!(self ; cmovne Rq(dst as u8), Rq(src as u8)),
+            _ => panic!(
+                "singlepass can't emit CMOV {:?} {:?} {:?}",
+                condition, src, dst
+            ),
+        }
+    }
+    fn emit_lfence(&mut self) {
+        dynasm!(self ; lfence);
+    }
 
     fn emit_vmovaps(&mut self, src: XMMOrMemory, dst: XMMOrMemory) {
         match (src, dst) {
@@ -1306,6 +1326,9 @@ impl Emitter for Assembler {
             _ => panic!("singlepass can't emit CALL {:?}", loc),
         }
     }
+    fn emit_call_indirect_label(&mut self, label: Self::Label) {
+        dynasm!(self ; call QWORD [=>label]);
+    }
 
     fn emit_bkpt(&mut self) {
         dynasm!(self ; int 0x3);
@@ -15,8 +15,9 @@
     all(target_os = "macos", target_arch = "x86_64"),
     all(target_os = "linux", target_arch = "x86_64"),
     all(target_os = "linux", target_arch = "aarch64"),
+    all(target_os = "windows", target_arch = "x86_64"),
 )))]
-compile_error!("This crate doesn't yet support compiling on operating systems other than linux and macos and architectures other than x86_64");
+compile_error!("This crate doesn't yet support compiling on operating systems other than linux, macos and windows and architectures other than x86_64");
 
 extern crate dynasmrt;
 
@@ -38,7 +39,10 @@ extern crate smallvec;
 mod codegen_x64;
 mod emitter_x64;
 mod machine;
+#[cfg(unix)]
 pub mod protect_unix;
+#[cfg(windows)]
+pub mod protect_windows;
 #[cfg(target_arch = "aarch64")]
 mod translator_aarch64;
 
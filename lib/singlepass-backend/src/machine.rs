@@ -478,6 +478,15 @@ impl Machine {
         }
     }
 
+    /// Returns the register holding vmctx, the first parameter, on function entry.
+    pub fn get_vmctx_param_reg() -> GPR {
+        match Self::get_param_location(0) {
+            Location::GPR(x) => x,
+            _ => unreachable!(),
+        }
+    }
+
+    #[cfg(not(windows))]
     pub fn get_param_location(idx: usize) -> Location {
         match idx {
             0 => Location::GPR(GPR::RDI),
@@ -489,6 +498,19 @@ impl Machine {
             _ => Location::Memory(GPR::RBP, (16 + (idx - 6) * 8) as i32),
         }
     }
+
+    /// On Windows, the caller reserves 32 bytes of shadow space for the register
+    /// parameters between the return address and the stack parameters.
+    #[cfg(windows)]
+    pub fn get_param_location(idx: usize) -> Location {
+        match idx {
+            0 => Location::GPR(GPR::RCX),
+            1 => Location::GPR(GPR::RDX),
+            2 => Location::GPR(GPR::R8),
+            3 => Location::GPR(GPR::R9),
+            _ => Location::Memory(GPR::RBP, (16 + 32 + (idx - 4) * 8) as i32),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -28,11 +28,17 @@ pub enum CallProtError {
     Error(Box<dyn Any + Send>),
 }
 
+/// Calls `f`, catching its traps.  Code compiled without signal handlers,
+/// which unwinds from its traps itself, is called with `no_signals`, so that
+/// the handlers aren't installed.
 pub fn call_protected<T>(
     f: impl FnOnce() -> T,
     breakpoints: Option<BreakpointMap>,
+    no_signals: bool,
 ) -> Result<T, CallProtError> {
-    ensure_sighandler();
+    if !no_signals {
+        ensure_sighandler();
+    }
     unsafe {
         let ret = catch_unsafe_unwind(|| f(), breakpoints);
         match ret {
@@ -0,0 +1,102 @@
+//! Catching traps and out-of-bounds memory accesses that occur when running
+//! WebAssembly on Windows.
+//!
+//! There are no signals on Windows: traps raise structured exceptions, e.g. an
+//! access violation or the illegal instruction of a `ud2`, instead.  The
+//! vectored exception handler of `wasmer-win-exception-handler` catches them
+//! and `longjmp`s back to `call_protected`, which doesn't need unwind
+//! information for the generated code.
+//!
+//! Traps triggered from host code, e.g. by an imported function returning an
+//! error, raise an exception of their own so they unwind the same way, with
+//! their payload in `TRAP_EARLY_DATA`.
+//!
+//! Inline breakpoints aren't supported: they trap like any other `ud2`.
+use std::any::Any;
+use std::cell::Cell;
+use std::ptr::{self, NonNull};
+use wasmer_runtime_core::codegen::BreakpointMap;
+use wasmer_runtime_core::typed_func::WasmTrapInfo;
+use wasmer_runtime_core::vm;
+use wasmer_win_exception_handler::{_call_protected, CallProtectedData};
+use winapi::{
+    shared::minwindef::DWORD,
+    um::{
+        errhandlingapi::RaiseException,
+        minwinbase::{
+            EXCEPTION_ACCESS_VIOLATION, EXCEPTION_INT_DIVIDE_BY_ZERO, EXCEPTION_INT_OVERFLOW,
+        },
+    },
+};
+
+/// The code of the exception raised by `trigger_trap`, with the customer bit set.
+const TRAP_EXCEPTION_CODE: DWORD = 0xE000_5741;
+
+thread_local! {
+    pub static TRAP_EARLY_DATA: Cell<Option<Box<dyn Any + Send>>> = Cell::new(None);
+}
+
+pub unsafe fn trigger_trap() -> ! {
+    RaiseException(TRAP_EXCEPTION_CODE, 0, 0, ptr::null());
+    unreachable!("the trap exception was not caught");
+}
+
+pub enum CallProtError {
+    Trap(WasmTrapInfo),
+    Error(Box<dyn Any + Send>),
+}
+
+/// Calls `f` from the C trampoline of `_call_protected`, which only takes
+/// plain pointers, by passing it as the `vm::Ctx` pointer.
+unsafe fn call_closure_protected<F: FnOnce()>(f: F) -> Result<(), CallProtectedData> {
+    unsafe extern "C" fn call<F: FnOnce()>(
+        f: *mut vm::Ctx,
+        _: NonNull<vm::Func>,
+        _: *const u64,
+        _: *mut u64,
+    ) {
+        let f = &mut *(f as *mut Option<F>);
+        f.take().unwrap()();
+    }
+
+    let mut f = Some(f);
+    _call_protected(
+        call::<F>,
+        &mut f as *mut Option<F> as *mut vm::Ctx,
+        NonNull::dangling(),
+        ptr::null(),
+        ptr::null_mut(),
+    )
+}
+
+/// Calls `f`, catching its traps.  The exception handler is only installed
+/// for the duration of the call, so code compiled without signal handlers
+/// is called the same way: its traps raise an exception of their own.
+pub fn call_protected<T>(
+    f: impl FnOnce() -> T,
+    _breakpoints: Option<BreakpointMap>,
+    _no_signals: bool,
+) -> Result<T, CallProtError> {
+    let mut ret = None;
+    let result = unsafe { call_closure_protected(|| ret = Some(f())) };
+    match result {
+        Ok(()) => Ok(ret.unwrap()),
+        Err(CallProtectedData { code, .. }) => {
+            if let Some(data) = TRAP_EARLY_DATA.with(|cell| cell.replace(None)) {
+                return Err(CallProtError::Error(data));
+            }
+            Err(CallProtError::Trap(match code as DWORD {
+                EXCEPTION_ACCESS_VIOLATION => WasmTrapInfo::MemoryOutOfBounds,
+                EXCEPTION_INT_DIVIDE_BY_ZERO | EXCEPTION_INT_OVERFLOW => {
+                    WasmTrapInfo::IllegalArithmetic
+                }
+                _ => WasmTrapInfo::Unknown,
+            }))
+        }
+    }
+}
+
+pub unsafe fn throw(payload: Box<dyn Any + Send>) -> ! {
+    TRAP_EARLY_DATA.with(|cell| cell.set(Some(payload)));
+    trigger_trap();
+}
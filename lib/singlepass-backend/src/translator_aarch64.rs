@@ -1511,6 +1511,12 @@ impl Emitter for Assembler {
     fn emit_cmovae_gpr_64(&mut self, _src: GPR, _dst: GPR) {
         unimplemented!();
     }
+    fn emit_cmov_gpr_64(&mut self, _condition: Condition, _src: GPR, _dst: GPR) {
+        unimplemented!("aarch64: cmov");
+    }
+    fn emit_lfence(&mut self) {
+        unimplemented!("aarch64: lfence");
+    }
     fn emit_ucomiss(&mut self, _src: XMMOrMemory, _dst: XMM) {
         unimplemented!();
     }
@@ -1612,6 +1618,9 @@ impl Emitter for Assembler {
             _ => unreachable!(),
         }
     }
+    fn emit_call_indirect_label(&mut self, _label: Self::Label) {
+        unimplemented!("aarch64: call indirect label");
+    }
 
     fn emit_bkpt(&mut self) {
         dynasm!(self ; .dword 0 ; .dword 1)
@@ -78,6 +78,22 @@ mod tests {
         "singlepass"
     }
 
+    #[cfg(feature = "interpreter")]
+    fn get_compiler_name() -> &'static str {
+        "interpreter"
+    }
+
+    /// The features modules are compiled with: all of them, except for the
+    /// interpreter, which supports neither SIMD nor threads.
+    fn get_features() -> Features {
+        let all = !cfg!(feature = "interpreter");
+        Features {
+            simd: all,
+            threads: all,
+            ..Default::default()
+        }
+    }
+
     #[cfg(unix)]
     fn get_target_family() -> &'static str {
         "unix"
@@ -153,6 +169,7 @@ mod tests {
                 "clif" => Some("clif".to_string()),
                 "singlepass" => Some("singlepass".to_string()),
                 "llvm" => Some("llvm".to_string()),
+                "interpreter" => Some("interpreter".to_string()),
                 _ => panic!("backend {:?} not recognized", backend),
             };
             let exclude_kind = match exclude_kind {
@@ -186,7 +203,12 @@ mod tests {
         }
     }
 
-    #[cfg(not(any(feature = "llvm", feature = "clif", feature = "singlepass")))]
+    #[cfg(not(any(
+        feature = "llvm",
+        feature = "clif",
+        feature = "singlepass",
+        feature = "interpreter"
+    )))]
     fn get_compiler_name() -> &'static str {
         panic!("compiler not specified, activate a compiler via features");
         "unknown"
@@ -293,10 +315,7 @@ mod tests {
                         let spectest_import_object =
                             get_spectest_import_object(&registered_modules);
                         let config = CompilerConfig {
-                            features: Features {
-                                simd: true,
-                                threads: true,
-                            },
+                            features: get_features(),
                             ..Default::default()
                         };
                         let module = compile_with_config(&module.into_vec(), config)
@@ -699,10 +718,7 @@ mod tests {
                     //                    println!("AssertInvalid");
                     let result = panic::catch_unwind(|| {
                         let config = CompilerConfig {
-                            features: Features {
-                                simd: true,
-                                threads: true,
-                            },
+                            features: get_features(),
                             ..Default::default()
                         };
                         compile_with_config(&module.into_vec(), config)
@@ -751,10 +767,7 @@ mod tests {
 
                     let result = panic::catch_unwind(|| {
                         let config = CompilerConfig {
-                            features: Features {
-                                simd: true,
-                                threads: true,
-                            },
+                            features: get_features(),
                             ..Default::default()
                         };
                         compile_with_config(&module.into_vec(), config)
@@ -802,10 +815,7 @@ mod tests {
                 CommandKind::AssertUninstantiable { module, message: _ } => {
                     let spectest_import_object = get_spectest_import_object(&registered_modules);
                     let config = CompilerConfig {
-                        features: Features {
-                            simd: true,
-                            threads: true,
-                        },
+                        features: get_features(),
                         ..Default::default()
                     };
                     let module = compile_with_config(&module.into_vec(), config)
@@ -897,10 +907,7 @@ mod tests {
                         let spectest_import_object =
                             get_spectest_import_object(&registered_modules);
                         let config = CompilerConfig {
-                            features: Features {
-                                simd: true,
-                                threads: true,
-                            },
+                            features: get_features(),
                             ..Default::default()
                         };
                         let module = compile_with_config(&module.into_vec(), config)
@@ -0,0 +1,371 @@
+#![deny(
+    dead_code,
+    nonstandard_style,
+    unused_imports,
+    unused_mut,
+    unused_variables,
+    unused_unsafe,
+    unreachable_patterns
+)]
+#![doc(html_favicon_url = "https://wasmer.io/static/icons/favicon.ico")]
+#![doc(html_logo_url = "https://avatars3.githubusercontent.com/u/44205449?s=200&v=4")]
+
+//! Passes strings, byte slices and `serde` values between the host and the
+//! guest, when calling guest functions with a [`TypedCaller`] and in host
+//! functions, with the conventions plugin systems otherwise reimplement.
+//!
+//! # Conventions
+//!
+//! - The guest exports its allocator as `allocate(size: i32) -> i32` and
+//!   `deallocate(ptr: i32, size: i32)`.
+//! - A value is passed as an `i32` pointer to a buffer holding its length, as
+//!   a little-endian `u32`, followed by its bytes. The `size` the allocator
+//!   sees includes the 4 bytes of the length.
+//! - Strings are UTF-8, byte slices are themselves, and [`Json`] values are
+//!   serialized to JSON, which guests in any language can read.
+//! - Whoever allocates a buffer frees it: the host frees the arguments it
+//!   passes to a guest function once it returns, and the values guest
+//!   functions return. Values the guest passes to host functions stay the
+//!   guest's, and the values host functions return become the guest's.
+//!
+//! ```ignore
+//! // Calling `greet(name) -> string` in the guest
+//! let mut caller = TypedCaller::new(&instance);
+//! let greeting: String = caller.call("greet", &[&"world"])?;
+//!
+//! // A host function taking and returning strings
+//! fn shout(ctx: &mut Ctx, message: u32) -> Result<u32, ValueError> {
+//!     let message: String = read_value(ctx, message)?;
+//!     write_value(ctx, &message.to_uppercase())
+//! }
+//! ```
+//!
+//! [`TypedCaller`]: struct.TypedCaller.html
+//! [`Json`]: struct.Json.html
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, fmt};
+use wasmer_runtime_core::{
+    error::{CallError, CallResult},
+    memory::{
+        ptr::{Array, WasmPtr},
+        Memory,
+    },
+    types::Value,
+    vm::Ctx,
+    Instance,
+};
+
+/// The name of the guest's allocation function
+pub const ALLOCATE: &str = "allocate";
+/// The name of the guest's deallocation function
+pub const DEALLOCATE: &str = "deallocate";
+
+/// The error of passing a value between the host and the guest
+#[derive(Debug)]
+pub enum ValueError {
+    /// Calling a guest function, the allocator included, failed
+    Call(CallError),
+    /// A guest function returned something other than the expected values
+    UnexpectedResults {
+        function: String,
+        results: Vec<Value>,
+    },
+    /// A buffer isn't within the guest's memory
+    OutOfBounds { ptr: u32, len: u32 },
+    /// A string isn't UTF-8
+    Utf8(std::string::FromUtf8Error),
+    /// A value couldn't be serialized or deserialized
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueError::Call(error) => write!(f, "calling the guest failed: {}", error),
+            ValueError::UnexpectedResults { function, results } => {
+                write!(f, "`{}` returned unexpected {:?}", function, results)
+            }
+            ValueError::OutOfBounds { ptr, len } => write!(
+                f,
+                "the buffer at {} of {} bytes is out of the guest's memory",
+                ptr, len
+            ),
+            ValueError::Utf8(error) => write!(f, "the string isn't UTF-8: {}", error),
+            ValueError::Json(error) => write!(f, "the JSON value is invalid: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+impl From<CallError> for ValueError {
+    fn from(error: CallError) -> Self {
+        ValueError::Call(error)
+    }
+}
+
+/// A value that can be written to the guest
+pub trait ToGuest {
+    /// The bytes the guest reads
+    fn to_bytes(&self) -> Result<Cow<[u8]>, ValueError>;
+}
+
+/// A value that can be read from the guest
+pub trait FromGuest: Sized {
+    /// The value of the bytes the guest wrote
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, ValueError>;
+}
+
+impl<'a, T: ToGuest + ?Sized> ToGuest for &'a T {
+    fn to_bytes(&self) -> Result<Cow<[u8]>, ValueError> {
+        (**self).to_bytes()
+    }
+}
+
+impl ToGuest for str {
+    fn to_bytes(&self) -> Result<Cow<[u8]>, ValueError> {
+        Ok(Cow::Borrowed(self.as_bytes()))
+    }
+}
+
+impl ToGuest for String {
+    fn to_bytes(&self) -> Result<Cow<[u8]>, ValueError> {
+        Ok(Cow::Borrowed(self.as_bytes()))
+    }
+}
+
+impl ToGuest for [u8] {
+    fn to_bytes(&self) -> Result<Cow<[u8]>, ValueError> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl ToGuest for Vec<u8> {
+    fn to_bytes(&self) -> Result<Cow<[u8]>, ValueError> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl FromGuest for String {
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, ValueError> {
+        String::from_utf8(bytes).map_err(ValueError::Utf8)
+    }
+}
+
+impl FromGuest for Vec<u8> {
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, ValueError> {
+        Ok(bytes)
+    }
+}
+
+/// A `serde` value, passed as JSON
+#[derive(Clone, Debug, PartialEq)]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> ToGuest for Json<T> {
+    fn to_bytes(&self) -> Result<Cow<[u8]>, ValueError> {
+        serde_json::to_vec(&self.0)
+            .map(Cow::Owned)
+            .map_err(ValueError::Json)
+    }
+}
+
+impl<T: DeserializeOwned> FromGuest for Json<T> {
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self, ValueError> {
+        serde_json::from_slice(&bytes)
+            .map(Json)
+            .map_err(ValueError::Json)
+    }
+}
+
+/// The memory and exports of a guest: an `Instance` for its callers, or the
+/// `Ctx` of a host function
+pub trait Guest {
+    /// The memory values are passed through
+    fn memory(&self) -> &Memory;
+
+    /// Calls the function the guest exports as `name`
+    fn call(&mut self, name: &str, args: &[Value]) -> CallResult<Vec<Value>>;
+}
+
+impl Guest for Instance {
+    fn memory(&self) -> &Memory {
+        self.context().memory(0)
+    }
+
+    fn call(&mut self, name: &str, args: &[Value]) -> CallResult<Vec<Value>> {
+        Instance::call(self, name, args)
+    }
+}
+
+impl Guest for Ctx {
+    fn memory(&self) -> &Memory {
+        Ctx::memory(self, 0)
+    }
+
+    fn call(&mut self, name: &str, args: &[Value]) -> CallResult<Vec<Value>> {
+        self.call_export(name, args)
+    }
+}
+
+fn bytes_at(memory: &Memory, ptr: u32, len: u32) -> Result<Vec<u8>, ValueError> {
+    WasmPtr::<u8, Array>::new(ptr)
+        .slice(memory, len)
+        .map(|bytes| bytes.to_vec())
+        .ok_or(ValueError::OutOfBounds { ptr, len })
+}
+
+/// The length of the value at `ptr`, without its prefix
+fn value_len(memory: &Memory, ptr: u32) -> Result<u32, ValueError> {
+    let prefix = bytes_at(memory, ptr, 4)?;
+    Ok(u32::from_le_bytes([
+        prefix[0], prefix[1], prefix[2], prefix[3],
+    ]))
+}
+
+/// Reads the value at `ptr`, leaving its buffer to the guest
+pub fn read_value<G, T>(guest: &G, ptr: u32) -> Result<T, ValueError>
+where
+    G: Guest + ?Sized,
+    T: FromGuest,
+{
+    let memory = guest.memory();
+    let len = value_len(memory, ptr)?;
+    let data_ptr = ptr
+        .checked_add(4)
+        .ok_or(ValueError::OutOfBounds { ptr, len })?;
+    T::from_bytes(bytes_at(memory, data_ptr, len)?)
+}
+
+/// Writes `value` to a buffer the guest allocates, returning its pointer
+pub fn write_value<G, T>(guest: &mut G, value: &T) -> Result<u32, ValueError>
+where
+    G: Guest + ?Sized,
+    T: ToGuest + ?Sized,
+{
+    let bytes = value.to_bytes()?;
+    let len = bytes.len() as u32;
+    let size = len
+        .checked_add(4)
+        .ok_or(ValueError::OutOfBounds { ptr: 0, len })?;
+    let ptr = match guest.call(ALLOCATE, &[Value::I32(size as i32)])?.as_slice() {
+        [Value::I32(ptr)] => *ptr as u32,
+        results => {
+            return Err(ValueError::UnexpectedResults {
+                function: ALLOCATE.to_string(),
+                results: results.to_vec(),
+            })
+        }
+    };
+
+    let buffer = WasmPtr::<u8, Array>::new(ptr)
+        .slice(guest.memory(), size)
+        .ok_or(ValueError::OutOfBounds { ptr, len: size })?;
+    buffer[..4]
+        .iter()
+        .zip(&len.to_le_bytes())
+        .for_each(|(cell, byte)| cell.set(*byte));
+    buffer[4..]
+        .iter()
+        .zip(bytes.iter())
+        .for_each(|(cell, byte)| cell.set(*byte));
+    Ok(ptr)
+}
+
+/// Gives the buffer of the value at `ptr` back to the guest's allocator
+pub fn free_value<G>(guest: &mut G, ptr: u32) -> Result<(), ValueError>
+where
+    G: Guest + ?Sized,
+{
+    let len = value_len(guest.memory(), ptr)?;
+    guest.call(
+        DEALLOCATE,
+        &[
+            Value::I32(ptr as i32),
+            Value::I32(len.wrapping_add(4) as i32),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Reads the value at `ptr` and frees its buffer, for the values guest
+/// functions return
+pub fn take_value<G, T>(guest: &mut G, ptr: u32) -> Result<T, ValueError>
+where
+    G: Guest + ?Sized,
+    T: FromGuest,
+{
+    let value = read_value(guest, ptr);
+    free_value(guest, ptr)?;
+    value
+}
+
+/// Calls guest functions whose parameters and result are values
+pub struct TypedCaller<'a> {
+    instance: &'a Instance,
+}
+
+impl<'a> TypedCaller<'a> {
+    /// A caller of the functions `instance` exports
+    pub fn new(instance: &'a Instance) -> Self {
+        Self { instance }
+    }
+
+    /// Calls `name` with a pointer to each of `args`, and reads the value
+    /// whose pointer it returns
+    pub fn call<R: FromGuest>(
+        &mut self,
+        name: &str,
+        args: &[&dyn ToGuest],
+    ) -> Result<R, ValueError> {
+        match self.call_with_values(name, args)?.as_slice() {
+            [Value::I32(ptr)] => take_value(self, *ptr as u32),
+            results => Err(ValueError::UnexpectedResults {
+                function: name.to_string(),
+                results: results.to_vec(),
+            }),
+        }
+    }
+
+    /// Calls `name` with a pointer to each of `args`, returning its results
+    /// as they are
+    pub fn call_with_values(
+        &mut self,
+        name: &str,
+        args: &[&dyn ToGuest],
+    ) -> Result<Vec<Value>, ValueError> {
+        let mut ptrs = Vec::with_capacity(args.len());
+        let mut result = Ok(vec![]);
+        for arg in args {
+            match write_value(self, arg) {
+                Ok(ptr) => ptrs.push(ptr),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+        if result.is_ok() {
+            let args = ptrs
+                .iter()
+                .map(|ptr| Value::I32(*ptr as i32))
+                .collect::<Vec<_>>();
+            result = self.instance.call(name, &args).map_err(ValueError::from);
+        }
+        for ptr in ptrs {
+            free_value(self, ptr)?;
+        }
+        result
+    }
+}
+
+impl<'a> Guest for TypedCaller<'a> {
+    fn memory(&self) -> &Memory {
+        self.instance.memory()
+    }
+
+    fn call(&mut self, name: &str, args: &[Value]) -> CallResult<Vec<Value>> {
+        self.instance.call(name, args)
+    }
+}
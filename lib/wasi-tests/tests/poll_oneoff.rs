@@ -0,0 +1,175 @@
+//! `poll_oneoff` as the async runtimes use it when compiled to WASI: tokio's
+//! current thread runtime (through mio) and async-std's reactor both poll their
+//! fd subscriptions together with a clock subscription for the next timer,
+//! and only look at the events reported.
+//!
+//! The subscriptions are written by the host, with the snapshot 1 layout, so
+//! no wasm32-wasi toolchain is needed to run the tests.
+//!
+//! There are no tests running async-std or tokio themselves: the programs in
+//! `wasitests` are single files compiled with `rustc`, which can't depend on
+//! crates, so these tests stand in for them.
+
+use std::time::{Duration, Instant};
+use wasmer_runtime::{compile, Func, Instance};
+use wasmer_wasi::{generate_import_object_from_state_for_module, state::WasiState};
+
+const MODULE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "poll_oneoff"
+    (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "clock_time_get"
+    (func $clock_time_get (param i32 i64 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "poll") (param $nsubscriptions i32) (result i32)
+    (call $poll_oneoff
+      (i32.const 0) (i32.const 1024) (get_local $nsubscriptions) (i32.const 2048)))
+  (func (export "now") (result i64)
+    (drop (call $clock_time_get (i32.const 1) (i64.const 0) (i32.const 2056)))
+    (i64.load (i32.const 2056))))
+"#;
+
+const SUBSCRIPTION_SIZE: usize = 48;
+const EVENTS: usize = 1024;
+const EVENT_SIZE: usize = 32;
+const NEVENTS: usize = 2048;
+
+const EVENTTYPE_CLOCK: u8 = 0;
+const EVENTTYPE_FD_WRITE: u8 = 2;
+const CLOCK_MONOTONIC: u32 = 1;
+const SUBSCRIPTION_CLOCK_ABSTIME: u16 = 1;
+const STDOUT: u32 = 1;
+
+enum Subscription {
+    Clock { timeout: u64, flags: u16 },
+    FdWrite { fd: u32 },
+}
+
+fn instantiate() -> Instance {
+    let module = compile(&wabt::wat2wasm(MODULE).unwrap()).unwrap();
+    let wasi_state = WasiState::new("poll").build().unwrap();
+    let import_object = generate_import_object_from_state_for_module(&module, wasi_state);
+    module.instantiate(&import_object).unwrap()
+}
+
+fn write(instance: &Instance, offset: usize, bytes: &[u8]) {
+    let view = instance.context().memory(0).view::<u8>();
+    for (cell, byte) in view[offset..offset + bytes.len()].iter().zip(bytes) {
+        cell.set(*byte);
+    }
+}
+
+fn read(instance: &Instance, offset: usize, len: usize) -> Vec<u8> {
+    let view = instance.context().memory(0).view::<u8>();
+    view[offset..offset + len]
+        .iter()
+        .map(|cell| cell.get())
+        .collect()
+}
+
+/// Polls `subscriptions`, the userdata of each being its index, and returns the
+/// userdata and type of the events.
+fn poll(instance: &Instance, subscriptions: &[Subscription]) -> Vec<(u64, u8)> {
+    for (i, subscription) in subscriptions.iter().enumerate() {
+        let offset = i * SUBSCRIPTION_SIZE;
+        write(instance, offset, &[0; SUBSCRIPTION_SIZE]);
+        write(instance, offset, &(i as u64).to_le_bytes());
+        match subscription {
+            Subscription::Clock { timeout, flags } => {
+                write(instance, offset + 8, &[EVENTTYPE_CLOCK]);
+                write(instance, offset + 16, &CLOCK_MONOTONIC.to_le_bytes());
+                write(instance, offset + 24, &timeout.to_le_bytes());
+                write(instance, offset + 40, &flags.to_le_bytes());
+            }
+            Subscription::FdWrite { fd } => {
+                write(instance, offset + 8, &[EVENTTYPE_FD_WRITE]);
+                write(instance, offset + 16, &fd.to_le_bytes());
+            }
+        }
+    }
+
+    let poll: Func<i32, i32> = instance.func("poll").unwrap();
+    assert_eq!(poll.call(subscriptions.len() as i32).unwrap(), 0);
+
+    let mut nevents = [0; 4];
+    nevents.copy_from_slice(&read(instance, NEVENTS, 4));
+    (0..u32::from_le_bytes(nevents) as usize)
+        .map(|i| {
+            let event = read(instance, EVENTS + i * EVENT_SIZE, EVENT_SIZE);
+            let mut userdata = [0; 8];
+            userdata.copy_from_slice(&event[0..8]);
+            assert_eq!(&event[8..10], &[0, 0], "the event has an error");
+            (u64::from_le_bytes(userdata), event[10])
+        })
+        .collect()
+}
+
+#[test]
+fn relative_timer() {
+    let instance = instantiate();
+    let start = Instant::now();
+    let timeout = Duration::from_millis(20);
+    let events = poll(
+        &instance,
+        &[Subscription::Clock {
+            timeout: timeout.as_nanos() as u64,
+            flags: 0,
+        }],
+    );
+    assert!(start.elapsed() >= timeout);
+    assert_eq!(events, vec![(0, EVENTTYPE_CLOCK)]);
+}
+
+#[test]
+fn absolute_timer() {
+    let instance = instantiate();
+    let now: Func<(), i64> = instance.func("now").unwrap();
+    let start = Instant::now();
+    let timeout = Duration::from_millis(20);
+    let events = poll(
+        &instance,
+        &[Subscription::Clock {
+            timeout: now.call().unwrap() as u64 + timeout.as_nanos() as u64,
+            flags: SUBSCRIPTION_CLOCK_ABSTIME,
+        }],
+    );
+    assert!(start.elapsed() >= timeout);
+    assert_eq!(events, vec![(0, EVENTTYPE_CLOCK)]);
+}
+
+#[test]
+fn the_earliest_timer_fires_first() {
+    let instance = instantiate();
+    let events = poll(
+        &instance,
+        &[
+            Subscription::Clock {
+                timeout: Duration::from_secs(60).as_nanos() as u64,
+                flags: 0,
+            },
+            Subscription::Clock {
+                timeout: Duration::from_millis(1).as_nanos() as u64,
+                flags: 0,
+            },
+        ],
+    );
+    assert_eq!(events, vec![(1, EVENTTYPE_CLOCK)]);
+}
+
+#[test]
+fn a_ready_fd_is_reported_before_the_timer() {
+    let instance = instantiate();
+    let start = Instant::now();
+    let events = poll(
+        &instance,
+        &[
+            Subscription::Clock {
+                timeout: Duration::from_secs(60).as_nanos() as u64,
+                flags: 0,
+            },
+            Subscription::FdWrite { fd: STDOUT },
+        ],
+    );
+    assert!(start.elapsed() < Duration::from_secs(60));
+    assert_eq!(events, vec![(1, EVENTTYPE_FD_WRITE)]);
+}
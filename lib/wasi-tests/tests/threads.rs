@@ -0,0 +1,137 @@
+use wasmer_runtime::{compile_with_config, CompilerConfig, Features, Func, Module};
+use wasmer_wasi::{
+    state::WasiState,
+    threads::{is_wasi_threads_module, WasiThreadError, WasiThreads, WasiThreadsError},
+    WasiExit,
+};
+
+// `_start` spawns a thread with `start_arg` 100 and another with 104, and
+// stores their IDs at 0 and 4. Each thread stores `1000 + tid` at its
+// `start_arg`, unless it's 0, where it traps.
+const MODULE: &str = r#"
+(module
+  (import "env" "memory" (memory 1 1 shared))
+  (import "wasi" "thread-spawn" (func $spawn (param i32) (result i32)))
+  (func (export "_start")
+    (i32.store (i32.const 0) (call $spawn (i32.const 100)))
+    (i32.store (i32.const 4) (call $spawn (i32.const 104))))
+  (func (export "spawn_trapping") (result i32)
+    (call $spawn (i32.const 0)))
+  (func (export "wasi_thread_start") (param $tid i32) (param $start_arg i32)
+    (if (i32.eqz (get_local $start_arg))
+      (then unreachable))
+    (i32.store (get_local $start_arg) (i32.add (get_local $tid) (i32.const 1000)))))
+"#;
+
+fn compile(wat: &str) -> Module {
+    let mut features = wabt::Features::new();
+    features.enable_threads();
+    let wasm = wabt::wat2wasm_with_features(wat, features).unwrap();
+    let config = CompilerConfig {
+        features: Features {
+            threads: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    compile_with_config(&wasm, config).unwrap()
+}
+
+fn wasi_state() -> WasiState {
+    WasiState::new("threads").build().unwrap()
+}
+
+#[test]
+fn spawn_and_join() {
+    let module = compile(MODULE);
+    assert!(is_wasi_threads_module(&module));
+    let threads = WasiThreads::new(&module, wasi_state()).unwrap();
+    let instance = module.instantiate(&threads.import_object()).unwrap();
+
+    let start: Func<(), ()> = instance.func("_start").unwrap();
+    start.call().unwrap();
+    assert_eq!(threads.join(), vec![(1, Ok(())), (2, Ok(()))]);
+
+    let view = threads.memory().view::<i32>();
+    assert_eq!(view[0].get(), 1);
+    assert_eq!(view[1].get(), 2);
+    assert_eq!(view[25].get(), 1001);
+    assert_eq!(view[26].get(), 1002);
+}
+
+#[test]
+fn join_reports_failed_threads() {
+    let module = compile(MODULE);
+    let threads = WasiThreads::new(&module, wasi_state()).unwrap();
+    let instance = module.instantiate(&threads.import_object()).unwrap();
+
+    let spawn_trapping: Func<(), i32> = instance.func("spawn_trapping").unwrap();
+    assert_eq!(spawn_trapping.call().unwrap(), 1);
+    match threads.join().as_slice() {
+        [(1, Err(WasiThreadError::Runtime(_)))] => (),
+        results => panic!("unexpected results: {:?}", results),
+    }
+}
+
+#[test]
+fn spawn_fails_once_the_threads_are_dropped() {
+    let module = compile(MODULE);
+    let threads = WasiThreads::new(&module, wasi_state()).unwrap();
+    let memory = threads.memory().clone();
+    let instance = module.instantiate(&threads.import_object()).unwrap();
+    drop(threads);
+
+    let start: Func<(), ()> = instance.func("_start").unwrap();
+    start.call().unwrap();
+    let view = memory.view::<i32>();
+    assert_eq!(view[0].get(), -1);
+    assert_eq!(view[1].get(), -1);
+}
+
+#[test]
+fn proc_exit_ends_every_thread() {
+    let module = compile(
+        r#"
+        (module
+          (import "env" "memory" (memory 1 1 shared))
+          (import "wasi" "thread-spawn" (func $spawn (param i32) (result i32)))
+          (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+          (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+          (func (export "spawn") (result i32)
+            (call $spawn (i32.const 0)))
+          (func (export "yield") (result i32)
+            (call $yield))
+          (func (export "wasi_thread_start") (param i32 i32)
+            (call $exit (i32.const 3))))
+        "#,
+    );
+    let threads = WasiThreads::new(&module, wasi_state()).unwrap();
+    let instance = module.instantiate(&threads.import_object()).unwrap();
+
+    let spawn: Func<(), i32> = instance.func("spawn").unwrap();
+    let yield_: Func<(), i32> = instance.func("yield").unwrap();
+    assert_eq!(yield_.call().unwrap(), 0);
+    assert_eq!(spawn.call().unwrap(), 1);
+    assert_eq!(threads.join(), vec![(1, Err(WasiThreadError::Exit(3)))]);
+
+    // the main instance shares the state the thread exited in
+    let exit = |error| WasiExit::from_runtime_error(&error).map(|exit| exit.code);
+    assert_eq!(yield_.call().map_err(exit), Err(Some(3)));
+    assert_eq!(spawn.call().map_err(exit), Err(Some(3)));
+}
+
+#[test]
+fn modules_without_a_shared_memory_are_rejected() {
+    let module = compile(
+        r#"
+        (module
+          (import "env" "memory" (memory 1))
+          (import "wasi" "thread-spawn" (func (param i32) (result i32)))
+          (func (export "wasi_thread_start") (param i32 i32)))
+        "#,
+    );
+    assert_eq!(
+        WasiThreads::new(&module, wasi_state()).err(),
+        Some(WasiThreadsError::MissingSharedMemory)
+    );
+}
@@ -0,0 +1,55 @@
+use wasmer_runtime::{compile, Func, ImportObject, Module};
+use wasmer_wasi::{
+    generate_import_object_for_module, generate_import_object_from_state_for_module,
+    get_wasi_versions, state::WasiState, WasiVersion,
+};
+
+// Reads `argc` through both versions of `args_sizes_get`.
+const MODULE: &str = r#"
+(module
+  (import "wasi_unstable" "args_sizes_get" (func $old (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "args_sizes_get" (func $new (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "argc_snapshot0") (result i32)
+    (drop (call $old (i32.const 0) (i32.const 4)))
+    (i32.load (i32.const 0)))
+  (func (export "argc_snapshot1") (result i32)
+    (drop (call $new (i32.const 8) (i32.const 12)))
+    (i32.load (i32.const 8))))
+"#;
+
+fn module() -> Module {
+    compile(&wabt::wat2wasm(MODULE).unwrap()).unwrap()
+}
+
+fn assert_argc(module: &Module, import_object: &ImportObject, argc: i32) {
+    let instance = module.instantiate(import_object).unwrap();
+    let argc_snapshot0: Func<(), i32> = instance.func("argc_snapshot0").unwrap();
+    let argc_snapshot1: Func<(), i32> = instance.func("argc_snapshot1").unwrap();
+    assert_eq!(argc_snapshot0.call().unwrap(), argc);
+    assert_eq!(argc_snapshot1.call().unwrap(), argc);
+}
+
+#[test]
+fn both_versions_are_detected() {
+    assert_eq!(
+        get_wasi_versions(&module()),
+        vec![WasiVersion::Snapshot0, WasiVersion::Snapshot1]
+    );
+}
+
+#[test]
+fn both_versions_share_the_state() {
+    let module = module();
+    let args = vec![b"prog".to_vec(), b"arg".to_vec()];
+    let import_object = generate_import_object_for_module(&module, args, vec![], vec![], vec![]);
+    assert_argc(&module, &import_object, 2);
+}
+
+#[test]
+fn both_versions_share_an_existing_state() {
+    let module = module();
+    let wasi_state = WasiState::new("prog").args(&["a", "b"]).build().unwrap();
+    let import_object = generate_import_object_from_state_for_module(&module, wasi_state);
+    assert_argc(&module, &import_object, 3);
+}
@@ -26,27 +26,72 @@ extern crate winapi;
 #[macro_use]
 mod macros;
 mod ptr;
+pub mod reactor;
 pub mod state;
 mod syscalls;
+pub mod threads;
 mod utils;
 
-use self::state::{WasiFs, WasiState};
+use self::state::{WasiFs, WasiState, WasiStateTemplate};
 pub use self::syscalls::types;
 use self::syscalls::*;
 
 use std::ffi::c_void;
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-pub use self::utils::{get_wasi_version, is_wasi_module, WasiVersion};
+use self::utils::is_wasi_namespace;
+pub use self::utils::{get_wasi_version, get_wasi_versions, is_wasi_module, WasiVersion};
 
-use wasmer_runtime_core::{func, import::ImportObject, imports};
+use wasmer_runtime_core::{
+    error::{CallError, RuntimeError},
+    import::{ImportObject, Namespace},
+    imports,
+    module::Module,
+};
 
+/// The error a call into a WASI module fails with when the module calls
+/// `proc_exit`.
+///
 /// This is returned in the Box<dyn Any> RuntimeError::Error variant.
-/// Use `downcast` or `downcast_ref` to retrieve the `ExitCode`.
-pub struct ExitCode {
+/// Use [`WasiExit::from_call_error`], or `downcast` or `downcast_ref`, to
+/// retrieve it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WasiExit {
     pub code: syscalls::types::__wasi_exitcode_t,
 }
 
+impl WasiExit {
+    /// The `WasiExit` that made a call fail, if it failed because the module
+    /// called `proc_exit`.
+    pub fn from_call_error(error: &CallError) -> Option<WasiExit> {
+        match error {
+            CallError::Runtime(error) => Self::from_runtime_error(error),
+            _ => None,
+        }
+    }
+
+    /// The `WasiExit` raised by `proc_exit`, if that's what `error` is.
+    pub fn from_runtime_error(error: &RuntimeError) -> Option<WasiExit> {
+        match error {
+            RuntimeError::Error { data } => data.downcast_ref::<WasiExit>().copied(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WasiExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WASI module exited with code {}", self.code)
+    }
+}
+
+impl std::error::Error for WasiExit {}
+
+#[deprecated(note = "renamed to `WasiExit`")]
+pub type ExitCode = WasiExit;
+
 /// Creates a Wasi [`ImportObject`] with [`WasiState`] with the latest snapshot
 /// of WASI.
 pub fn generate_import_object(
@@ -69,6 +114,13 @@ pub fn generate_import_object(
             fs: WasiFs::new(&preopened_files, &mapped_dirs).expect("Could not create WASI FS"),
             args: args.clone(),
             envs: envs.clone(),
+            clock: Default::default(),
+            random: Default::default(),
+            hook: None,
+            mode: Default::default(),
+            exit_code: None,
+            unimplemented_policy: Default::default(),
+            trace_mode: None,
         });
 
         (
@@ -82,6 +134,9 @@ pub fn generate_import_object(
 
 /// Create an [`ImportObject`] with an existing [`WasiState`]. [`WasiState`]
 /// can be constructed from a [`WasiStateBuilder`](state::WasiStateBuilder).
+///
+/// # Panics
+/// If the state can't be serialized, which every instance gets a copy of.
 pub fn generate_import_object_from_state(
     wasi_state: WasiState,
     version: WasiVersion,
@@ -89,7 +144,8 @@ pub fn generate_import_object_from_state(
     // HACK(mark): this is really quite nasty and inefficient, a proper fix will
     //             require substantial changes to the internals of the WasiFS
     // copy WasiState by serializing and deserializing
-    let wasi_state_bytes = wasi_state.freeze().unwrap();
+    let wasi_state_template =
+        WasiStateTemplate::new(&wasi_state).expect("the WASI state can't be copied");
     let state_gen = move || {
         fn state_destructor(data: *mut c_void) {
             unsafe {
@@ -97,7 +153,7 @@ pub fn generate_import_object_from_state(
             }
         }
 
-        let wasi_state = Box::new(WasiState::unfreeze(&wasi_state_bytes).unwrap());
+        let wasi_state = Box::new(wasi_state_template.instantiate());
 
         (
             Box::into_raw(wasi_state) as *mut c_void,
@@ -130,6 +186,114 @@ pub fn generate_import_object_for_version(
     }
 }
 
+/// Creates a Wasi [`ImportObject`] with [`WasiState`] that satisfies every
+/// WASI version imported by `module`.
+///
+/// See [`generate_import_object_from_state_for_module`].
+pub fn generate_import_object_for_module(
+    module: &Module,
+    args: Vec<Vec<u8>>,
+    envs: Vec<Vec<u8>>,
+    preopened_files: Vec<PathBuf>,
+    mapped_dirs: Vec<(String, PathBuf)>,
+) -> ImportObject {
+    let state_gen = move || {
+        fn state_destructor(data: *mut c_void) {
+            unsafe {
+                drop(Box::from_raw(data as *mut WasiState));
+            }
+        }
+
+        let state = Box::new(WasiState {
+            fs: WasiFs::new(&preopened_files, &mapped_dirs).expect("Could not create WASI FS"),
+            args: args.clone(),
+            envs: envs.clone(),
+            clock: Default::default(),
+            random: Default::default(),
+            hook: None,
+            mode: Default::default(),
+            exit_code: None,
+            unimplemented_policy: Default::default(),
+            trace_mode: None,
+        });
+
+        (
+            Box::into_raw(state) as *mut c_void,
+            state_destructor as fn(*mut c_void),
+        )
+    };
+
+    generate_import_object_for_versions_inner(&get_wasi_versions(module), state_gen)
+}
+
+/// Create an [`ImportObject`] with an existing [`WasiState`] that satisfies
+/// every WASI version imported by `module`.
+///
+/// Modules importing both `wasi_unstable` and `wasi_snapshot_preview1` get
+/// both namespaces, backed by the same [`WasiState`]. If the module imports
+/// neither, the latest version is provided.
+///
+/// # Panics
+/// If the state can't be serialized, see [`generate_import_object_from_state`].
+pub fn generate_import_object_from_state_for_module(
+    module: &Module,
+    wasi_state: WasiState,
+) -> ImportObject {
+    // see `generate_import_object_from_state` for why the state is copied this way
+    let wasi_state_template =
+        WasiStateTemplate::new(&wasi_state).expect("the WASI state can't be copied");
+    let state_gen = move || {
+        fn state_destructor(data: *mut c_void) {
+            unsafe {
+                drop(Box::from_raw(data as *mut WasiState));
+            }
+        }
+
+        let wasi_state = Box::new(wasi_state_template.instantiate());
+
+        (
+            Box::into_raw(wasi_state) as *mut c_void,
+            state_destructor as fn(*mut c_void),
+        )
+    };
+
+    generate_import_object_for_versions_inner(&get_wasi_versions(module), state_gen)
+}
+
+/// Create an [`ImportObject`] like [`generate_import_object_from_state_for_module`]
+/// that also provides the host `namespaces`, e.g. `my_host:v1`.
+///
+/// The functions of `namespaces` are called with the same [`Ctx`] as the WASI
+/// syscalls, so they can reach the instance's memory and [`WasiState`] with
+/// [`state::get_memory_and_wasi_state`].
+///
+/// # Panics
+///
+/// Panics if one of `namespaces` has the name of a WASI namespace.
+///
+/// [`Ctx`]: wasmer_runtime_core::vm::Ctx
+pub fn generate_import_object_from_state_with_namespaces<I, S>(
+    module: &Module,
+    wasi_state: WasiState,
+    namespaces: I,
+) -> ImportObject
+where
+    I: IntoIterator<Item = (S, Namespace)>,
+    S: Into<String>,
+{
+    let mut import_object = generate_import_object_from_state_for_module(module, wasi_state);
+    for (name, namespace) in namespaces {
+        let name = name.into();
+        assert!(
+            !is_wasi_namespace(&name),
+            "the WASI namespace `{}` can't be replaced by a host namespace",
+            name
+        );
+        import_object.register(name, namespace);
+    }
+    import_object
+}
+
 /// Creates a legacy Wasi [`ImportObject`] with [`WasiState`].
 fn generate_import_object_snapshot0(
     args: Vec<Vec<u8>>,
@@ -152,6 +316,13 @@ fn generate_import_object_snapshot0(
             fs: WasiFs::new(&preopened_files, &mapped_dirs).expect("Could not create WASI FS"),
             args: args.clone(),
             envs: envs.clone(),
+            clock: Default::default(),
+            random: Default::default(),
+            hook: None,
+            mode: Default::default(),
+            exit_code: None,
+            unimplemented_policy: Default::default(),
+            trace_mode: None,
         });
 
         (
@@ -162,6 +333,37 @@ fn generate_import_object_snapshot0(
     generate_import_object_snapshot0_inner(state_gen)
 }
 
+/// Combines a state generating function with the import lists of all the
+/// given versions; the namespaces share a single state per instance.
+fn generate_import_object_for_versions_inner<F>(
+    versions: &[WasiVersion],
+    state_gen: F,
+) -> ImportObject
+where
+    F: Fn() -> (*mut c_void, fn(*mut c_void)) + Send + Sync + 'static,
+{
+    let with_snapshot0 = versions.contains(&WasiVersion::Snapshot0);
+    let with_snapshot1 = versions.is_empty()
+        || versions.contains(&WasiVersion::Snapshot1)
+        || versions.contains(&WasiVersion::Latest);
+    let state_gen = Arc::new(state_gen);
+
+    if !with_snapshot1 {
+        return generate_import_object_snapshot0_inner(move || state_gen());
+    }
+
+    let mut import_object = {
+        let state_gen = Arc::clone(&state_gen);
+        generate_import_object_snapshot1_inner(move || state_gen())
+    };
+    if with_snapshot0 {
+        // only the state creator of the object used to instantiate is called,
+        // so the legacy namespace is moved into the snapshot 1 object
+        import_object.extend(generate_import_object_snapshot0_inner(move || state_gen()));
+    }
+    import_object
+}
+
 /// Combines a state generating function with the import list for legacy WASI
 fn generate_import_object_snapshot0_inner<F>(state_gen: F) -> ImportObject
 where
@@ -170,51 +372,78 @@ where
     imports! {
         state_gen,
         "wasi_unstable" => {
-            "args_get" => func!(args_get),
-            "args_sizes_get" => func!(args_sizes_get),
-            "clock_res_get" => func!(clock_res_get),
-            "clock_time_get" => func!(clock_time_get),
-            "environ_get" => func!(environ_get),
-            "environ_sizes_get" => func!(environ_sizes_get),
-            "fd_advise" => func!(fd_advise),
-            "fd_allocate" => func!(fd_allocate),
-            "fd_close" => func!(fd_close),
-            "fd_datasync" => func!(fd_datasync),
-            "fd_fdstat_get" => func!(fd_fdstat_get),
-            "fd_fdstat_set_flags" => func!(fd_fdstat_set_flags),
-            "fd_fdstat_set_rights" => func!(fd_fdstat_set_rights),
-            "fd_filestat_get" => func!(legacy::snapshot0::fd_filestat_get),
-            "fd_filestat_set_size" => func!(fd_filestat_set_size),
-            "fd_filestat_set_times" => func!(fd_filestat_set_times),
-            "fd_pread" => func!(fd_pread),
-            "fd_prestat_get" => func!(fd_prestat_get),
-            "fd_prestat_dir_name" => func!(fd_prestat_dir_name),
-            "fd_pwrite" => func!(fd_pwrite),
-            "fd_read" => func!(fd_read),
-            "fd_readdir" => func!(fd_readdir),
-            "fd_renumber" => func!(fd_renumber),
-            "fd_seek" => func!(legacy::snapshot0::fd_seek),
-            "fd_sync" => func!(fd_sync),
-            "fd_tell" => func!(fd_tell),
-            "fd_write" => func!(fd_write),
-            "path_create_directory" => func!(path_create_directory),
-            "path_filestat_get" => func!(legacy::snapshot0::path_filestat_get),
-            "path_filestat_set_times" => func!(path_filestat_set_times),
-            "path_link" => func!(path_link),
-            "path_open" => func!(path_open),
-            "path_readlink" => func!(path_readlink),
-            "path_remove_directory" => func!(path_remove_directory),
-            "path_rename" => func!(path_rename),
-            "path_symlink" => func!(path_symlink),
-            "path_unlink_file" => func!(path_unlink_file),
-            "poll_oneoff" => func!(legacy::snapshot0::poll_oneoff),
-            "proc_exit" => func!(proc_exit),
-            "proc_raise" => func!(proc_raise),
-            "random_get" => func!(random_get),
-            "sched_yield" => func!(sched_yield),
-            "sock_recv" => func!(sock_recv),
-            "sock_send" => func!(sock_send),
-            "sock_shutdown" => func!(sock_shutdown),
+            "args_get" => wasi_syscall!(args_get(argv, argv_buf)),
+            "args_sizes_get" => wasi_syscall!(args_sizes_get(argc, argv_buf_size)),
+            "clock_res_get" => wasi_syscall!(clock_res_get(clock_id, resolution)),
+            "clock_time_get" => wasi_syscall!(clock_time_get(clock_id, precision, time)),
+            "environ_get" => wasi_syscall!(environ_get(environ, environ_buf)),
+            "environ_sizes_get" => wasi_syscall!(environ_sizes_get(
+                environ_count, environ_buf_size
+            )),
+            "fd_advise" => wasi_syscall!(fd_advise(fd, offset, len, advice)),
+            "fd_allocate" => wasi_syscall!(fd_allocate(fd, offset, len)),
+            "fd_close" => wasi_syscall!(fd_close(fd)),
+            "fd_datasync" => wasi_syscall!(fd_datasync(fd)),
+            "fd_fdstat_get" => wasi_syscall!(fd_fdstat_get(fd, buf_ptr)),
+            "fd_fdstat_set_flags" => wasi_syscall!(fd_fdstat_set_flags(fd, flags)),
+            "fd_fdstat_set_rights" => wasi_syscall!(fd_fdstat_set_rights(
+                fd, fs_rights_base, fs_rights_inheriting
+            )),
+            "fd_filestat_get" => wasi_syscall!(legacy::snapshot0::fd_filestat_get(fd, buf)),
+            "fd_filestat_set_size" => wasi_syscall!(fd_filestat_set_size(fd, st_size)),
+            "fd_filestat_set_times" => wasi_syscall!(fd_filestat_set_times(
+                fd, st_atim, st_mtim, fst_flags
+            )),
+            "fd_pread" => wasi_syscall!(fd_pread(fd, iovs, iovs_len, offset, nread)),
+            "fd_prestat_get" => wasi_syscall!(fd_prestat_get(fd, buf)),
+            "fd_prestat_dir_name" => wasi_syscall!(fd_prestat_dir_name(fd, path, path_len)),
+            "fd_pwrite" => wasi_syscall!(fd_pwrite(fd, iovs, iovs_len, offset, nwritten)),
+            "fd_read" => wasi_syscall!(fd_read(fd, iovs, iovs_len, nread)),
+            "fd_readdir" => wasi_syscall!(fd_readdir(fd, buf, buf_len, cookie, bufused)),
+            "fd_renumber" => wasi_syscall!(fd_renumber(from, to)),
+            "fd_seek" => wasi_syscall!(legacy::snapshot0::fd_seek(fd, offset, whence, newoffset)),
+            "fd_sync" => wasi_syscall!(fd_sync(fd)),
+            "fd_tell" => wasi_syscall!(fd_tell(fd, offset)),
+            "fd_write" => wasi_syscall!(fd_write(fd, iovs, iovs_len, nwritten)),
+            "path_create_directory" => wasi_syscall!(path_create_directory(fd, path, path_len)),
+            "path_filestat_get" => wasi_syscall!(legacy::snapshot0::path_filestat_get(
+                fd, flags, path, path_len, buf
+            )),
+            "path_filestat_set_times" => wasi_syscall!(path_filestat_set_times(
+                fd, flags, path, path_len, st_atim, st_mtim, fst_flags
+            )),
+            "path_link" => wasi_syscall!(path_link(
+                old_fd, old_flags, old_path, old_path_len, new_fd, new_path, new_path_len
+            )),
+            "path_open" => wasi_syscall!(path_open(
+                dirfd, dirflags, path, path_len, o_flags, fs_rights_base, fs_rights_inheriting,
+                fs_flags, fd
+            )),
+            "path_readlink" => wasi_syscall!(path_readlink(
+                dir_fd, path, path_len, buf, buf_len, buf_used
+            )),
+            "path_remove_directory" => wasi_syscall!(path_remove_directory(fd, path, path_len)),
+            "path_rename" => wasi_syscall!(path_rename(
+                old_fd, old_path, old_path_len, new_fd, new_path, new_path_len
+            )),
+            "path_symlink" => wasi_syscall!(path_symlink(
+                old_path, old_path_len, fd, new_path, new_path_len
+            )),
+            "path_unlink_file" => wasi_syscall!(path_unlink_file(fd, path, path_len)),
+            "poll_oneoff" => wasi_syscall!(legacy::snapshot0::poll_oneoff(
+                in_, out_, nsubscriptions, nevents
+            )),
+            "proc_exit" => wasi_syscall!(proc_exit(code)),
+            "proc_raise" => wasi_syscall!(proc_raise(sig)),
+            "random_get" => wasi_syscall!(random_get(buf, buf_len)),
+            "sched_yield" => wasi_syscall!(sched_yield()),
+            "sock_recv" => wasi_syscall!(sock_recv(
+                sock, ri_data, ri_data_len, ri_flags, ro_datalen, ro_flags
+            )),
+            "sock_send" => wasi_syscall!(sock_send(
+                sock, si_data, si_data_len, si_flags, so_datalen
+            )),
+            "sock_shutdown" => wasi_syscall!(sock_shutdown(sock, how)),
         },
     }
 }
@@ -227,51 +456,76 @@ where
     imports! {
             state_gen,
             "wasi_snapshot_preview1" => {
-                "args_get" => func!(args_get),
-                "args_sizes_get" => func!(args_sizes_get),
-                "clock_res_get" => func!(clock_res_get),
-                "clock_time_get" => func!(clock_time_get),
-                "environ_get" => func!(environ_get),
-                "environ_sizes_get" => func!(environ_sizes_get),
-                "fd_advise" => func!(fd_advise),
-                "fd_allocate" => func!(fd_allocate),
-                "fd_close" => func!(fd_close),
-                "fd_datasync" => func!(fd_datasync),
-                "fd_fdstat_get" => func!(fd_fdstat_get),
-                "fd_fdstat_set_flags" => func!(fd_fdstat_set_flags),
-                "fd_fdstat_set_rights" => func!(fd_fdstat_set_rights),
-                "fd_filestat_get" => func!(fd_filestat_get),
-                "fd_filestat_set_size" => func!(fd_filestat_set_size),
-                "fd_filestat_set_times" => func!(fd_filestat_set_times),
-                "fd_pread" => func!(fd_pread),
-                "fd_prestat_get" => func!(fd_prestat_get),
-                "fd_prestat_dir_name" => func!(fd_prestat_dir_name),
-                "fd_pwrite" => func!(fd_pwrite),
-                "fd_read" => func!(fd_read),
-                "fd_readdir" => func!(fd_readdir),
-                "fd_renumber" => func!(fd_renumber),
-                "fd_seek" => func!(fd_seek),
-                "fd_sync" => func!(fd_sync),
-                "fd_tell" => func!(fd_tell),
-                "fd_write" => func!(fd_write),
-                "path_create_directory" => func!(path_create_directory),
-                "path_filestat_get" => func!(path_filestat_get),
-                "path_filestat_set_times" => func!(path_filestat_set_times),
-                "path_link" => func!(path_link),
-                "path_open" => func!(path_open),
-                "path_readlink" => func!(path_readlink),
-                "path_remove_directory" => func!(path_remove_directory),
-                "path_rename" => func!(path_rename),
-                "path_symlink" => func!(path_symlink),
-                "path_unlink_file" => func!(path_unlink_file),
-                "poll_oneoff" => func!(poll_oneoff),
-                "proc_exit" => func!(proc_exit),
-                "proc_raise" => func!(proc_raise),
-                "random_get" => func!(random_get),
-                "sched_yield" => func!(sched_yield),
-                "sock_recv" => func!(sock_recv),
-                "sock_send" => func!(sock_send),
-                "sock_shutdown" => func!(sock_shutdown),
+                "args_get" => wasi_syscall!(args_get(argv, argv_buf)),
+                "args_sizes_get" => wasi_syscall!(args_sizes_get(argc, argv_buf_size)),
+                "clock_res_get" => wasi_syscall!(clock_res_get(clock_id, resolution)),
+                "clock_time_get" => wasi_syscall!(clock_time_get(clock_id, precision, time)),
+                "environ_get" => wasi_syscall!(environ_get(environ, environ_buf)),
+                "environ_sizes_get" => wasi_syscall!(environ_sizes_get(
+                    environ_count, environ_buf_size
+                )),
+                "fd_advise" => wasi_syscall!(fd_advise(fd, offset, len, advice)),
+                "fd_allocate" => wasi_syscall!(fd_allocate(fd, offset, len)),
+                "fd_close" => wasi_syscall!(fd_close(fd)),
+                "fd_datasync" => wasi_syscall!(fd_datasync(fd)),
+                "fd_fdstat_get" => wasi_syscall!(fd_fdstat_get(fd, buf_ptr)),
+                "fd_fdstat_set_flags" => wasi_syscall!(fd_fdstat_set_flags(fd, flags)),
+                "fd_fdstat_set_rights" => wasi_syscall!(fd_fdstat_set_rights(
+                    fd, fs_rights_base, fs_rights_inheriting
+                )),
+                "fd_filestat_get" => wasi_syscall!(fd_filestat_get(fd, buf)),
+                "fd_filestat_set_size" => wasi_syscall!(fd_filestat_set_size(fd, st_size)),
+                "fd_filestat_set_times" => wasi_syscall!(fd_filestat_set_times(
+                    fd, st_atim, st_mtim, fst_flags
+                )),
+                "fd_pread" => wasi_syscall!(fd_pread(fd, iovs, iovs_len, offset, nread)),
+                "fd_prestat_get" => wasi_syscall!(fd_prestat_get(fd, buf)),
+                "fd_prestat_dir_name" => wasi_syscall!(fd_prestat_dir_name(fd, path, path_len)),
+                "fd_pwrite" => wasi_syscall!(fd_pwrite(fd, iovs, iovs_len, offset, nwritten)),
+                "fd_read" => wasi_syscall!(fd_read(fd, iovs, iovs_len, nread)),
+                "fd_readdir" => wasi_syscall!(fd_readdir(fd, buf, buf_len, cookie, bufused)),
+                "fd_renumber" => wasi_syscall!(fd_renumber(from, to)),
+                "fd_seek" => wasi_syscall!(fd_seek(fd, offset, whence, newoffset)),
+                "fd_sync" => wasi_syscall!(fd_sync(fd)),
+                "fd_tell" => wasi_syscall!(fd_tell(fd, offset)),
+                "fd_write" => wasi_syscall!(fd_write(fd, iovs, iovs_len, nwritten)),
+                "path_create_directory" => wasi_syscall!(path_create_directory(fd, path, path_len)),
+                "path_filestat_get" => wasi_syscall!(path_filestat_get(
+                    fd, flags, path, path_len, buf
+                )),
+                "path_filestat_set_times" => wasi_syscall!(path_filestat_set_times(
+                    fd, flags, path, path_len, st_atim, st_mtim, fst_flags
+                )),
+                "path_link" => wasi_syscall!(path_link(
+                    old_fd, old_flags, old_path, old_path_len, new_fd, new_path, new_path_len
+                )),
+                "path_open" => wasi_syscall!(path_open(
+                    dirfd, dirflags, path, path_len, o_flags, fs_rights_base, fs_rights_inheriting,
+                    fs_flags, fd
+                )),
+                "path_readlink" => wasi_syscall!(path_readlink(
+                    dir_fd, path, path_len, buf, buf_len, buf_used
+                )),
+                "path_remove_directory" => wasi_syscall!(path_remove_directory(fd, path, path_len)),
+                "path_rename" => wasi_syscall!(path_rename(
+                    old_fd, old_path, old_path_len, new_fd, new_path, new_path_len
+                )),
+                "path_symlink" => wasi_syscall!(path_symlink(
+                    old_path, old_path_len, fd, new_path, new_path_len
+                )),
+                "path_unlink_file" => wasi_syscall!(path_unlink_file(fd, path, path_len)),
+                "poll_oneoff" => wasi_syscall!(poll_oneoff(in_, out_, nsubscriptions, nevents)),
+                "proc_exit" => wasi_syscall!(proc_exit(code)),
+                "proc_raise" => wasi_syscall!(proc_raise(sig)),
+                "random_get" => wasi_syscall!(random_get(buf, buf_len)),
+                "sched_yield" => wasi_syscall!(sched_yield()),
+                "sock_recv" => wasi_syscall!(sock_recv(
+                    sock, ri_data, ri_data_len, ri_flags, ro_datalen, ro_flags
+                )),
+                "sock_send" => wasi_syscall!(sock_send(
+                    sock, si_data, si_data_len, si_flags, so_datalen
+                )),
+                "sock_shutdown" => wasi_syscall!(sock_shutdown(sock, how)),
             },
     }
 }
@@ -23,3 +23,38 @@ macro_rules! get_input_str {
         wasi_try!($data.get_utf8_string($memory, $len), __WASI_EINVAL)
     }};
 }
+
+/// Return what the instance's
+/// [`UnimplementedPolicy`](crate::state::UnimplementedPolicy) says `$syscall`
+/// returns when it's asked for `$feature`.
+macro_rules! wasi_unimplemented {
+    ($state:expr, $syscall:expr, $feature:expr) => {
+        return $state.unimplemented_policy.handle($syscall, $feature)
+    };
+}
+
+/// Create the import for a syscall, calling the instance's
+/// [`WasiSyscallHook`](crate::state::WasiSyscallHook) around it, and
+/// taking turns with the other threads the state is shared with.
+///
+/// The arguments are only named so the wrapper can forward them.
+macro_rules! wasi_syscall {
+    (@hook $name:expr, $func:path, $($arg:ident),*) => {
+        wasmer_runtime_core::func!(
+            move |ctx: &mut wasmer_runtime_core::vm::Ctx, $($arg),*| {
+                let args = [$(crate::state::hook::arg_value($arg)),*];
+                crate::threads::call_syscall(ctx, |ctx| {
+                    crate::state::hook::call_with_hook(ctx, $name, &args, |ctx| {
+                        $func(ctx, $($arg),*)
+                    })
+                })
+            }
+        )
+    };
+    (legacy::snapshot0::$func:ident($($arg:ident),*)) => {
+        wasi_syscall!(@hook stringify!($func), legacy::snapshot0::$func, $($arg),*)
+    };
+    ($func:ident($($arg:ident),*)) => {
+        wasi_syscall!(@hook stringify!($func), $func, $($arg),*)
+    };
+}
@@ -0,0 +1,185 @@
+//! Support for WASI reactors.
+//!
+//! A WASI *command* exports `_start`, which runs the whole program once.  A
+//! WASI *reactor* exports `_initialize` instead: the host calls it once to
+//! set the module up and may then call any of its exports, as many times as
+//! it wants.  Every call shares the same [`WasiState`], so files opened by one
+//! call stay open for the next.
+
+use crate::{generate_import_object_from_state_for_module, state::WasiState, WasiExit};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wasmer_runtime_core::{instance::Instance, module::Module, types::Value};
+
+/// Name of the function run by WASI commands.
+const COMMAND_START: &str = "_start";
+
+/// Name of the function WASI reactors must be initialized with.
+const REACTOR_INITIALIZE: &str = "_initialize";
+
+/// How a WASI module is meant to be run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WasiMode {
+    /// Use the mode the module was built for, see [`module_mode`].
+    Auto,
+    /// Run the module once through `_start`.
+    Command,
+    /// Initialize the module once through `_initialize`, then call its exports.
+    Reactor,
+}
+
+impl Default for WasiMode {
+    fn default() -> Self {
+        WasiMode::Auto
+    }
+}
+
+/// Error returned when a WASI reactor can't be set up or called.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WasiReactorError {
+    /// The module exports `_start`, so it must be run as a command.
+    CommandModule,
+    /// The module doesn't export `_start`, so it can't be run as a command.
+    ReactorModule,
+    /// The module exports both `_start` and `_initialize`.
+    AmbiguousModule,
+    /// `_initialize` was called again; reactors are initialized only once.
+    AlreadyInitialized,
+    /// The module has no function export with the given name.
+    MissingExport(String),
+    /// The module could not be instantiated.
+    Instantiation(String),
+    /// The call trapped or raised an error.
+    Runtime(String),
+    /// The module called `proc_exit` with the given code.
+    Exit(u32),
+}
+
+impl fmt::Display for WasiReactorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasiReactorError::CommandModule => write!(
+                f,
+                "the module is a WASI command (it exports `{}`) and can't be used as a reactor",
+                COMMAND_START
+            ),
+            WasiReactorError::ReactorModule => write!(
+                f,
+                "the module is a WASI reactor (it doesn't export `{}`) and can't be run as a command",
+                COMMAND_START
+            ),
+            WasiReactorError::AmbiguousModule => write!(
+                f,
+                "the module exports both `{}` and `{}`",
+                COMMAND_START, REACTOR_INITIALIZE
+            ),
+            WasiReactorError::AlreadyInitialized => {
+                write!(f, "the reactor has already been initialized")
+            }
+            WasiReactorError::MissingExport(name) => {
+                write!(f, "the module doesn't export a function named `{}`", name)
+            }
+            WasiReactorError::Instantiation(msg) => {
+                write!(f, "failed to instantiate the module: {}", msg)
+            }
+            WasiReactorError::Runtime(msg) => write!(f, "call failed: {}", msg),
+            WasiReactorError::Exit(code) => write!(f, "module exited with code {}", code),
+        }
+    }
+}
+
+impl std::error::Error for WasiReactorError {}
+
+fn exports_function(module: &Module, name: &str) -> bool {
+    module.info().exports.contains_key(name)
+}
+
+/// The mode `module` was built for.
+///
+/// Modules exporting `_start` are commands; every other module is a reactor,
+/// whether or not it exports `_initialize`.
+pub fn module_mode(module: &Module) -> Result<WasiMode, WasiReactorError> {
+    match (
+        exports_function(module, COMMAND_START),
+        exports_function(module, REACTOR_INITIALIZE),
+    ) {
+        (true, true) => Err(WasiReactorError::AmbiguousModule),
+        (true, false) => Ok(WasiMode::Command),
+        (false, _) => Ok(WasiMode::Reactor),
+    }
+}
+
+/// Check that `module` can be run in the `requested` mode and return the mode
+/// to run it in, resolving [`WasiMode::Auto`].
+pub fn resolve_mode(module: &Module, requested: WasiMode) -> Result<WasiMode, WasiReactorError> {
+    match (requested, module_mode(module)?) {
+        (WasiMode::Auto, mode) => Ok(mode),
+        (WasiMode::Reactor, WasiMode::Command) => Err(WasiReactorError::CommandModule),
+        (WasiMode::Command, WasiMode::Reactor) => Err(WasiReactorError::ReactorModule),
+        (_, mode) => Ok(mode),
+    }
+}
+
+/// An initialized WASI reactor.
+///
+/// Exports can be called any number of times with [`WasiReactor::call`];
+/// every call shares the instance's [`WasiState`].
+pub struct WasiReactor {
+    instance: Instance,
+}
+
+impl WasiReactor {
+    /// Instantiate `module` with `wasi_state` and run its `_initialize`
+    /// export, if it has one.
+    ///
+    /// Fails if the module is a command, or if `wasi_state` was built with
+    /// [`WasiMode::Command`].
+    pub fn new(module: &Module, wasi_state: WasiState) -> Result<Self, WasiReactorError> {
+        if resolve_mode(module, wasi_state.mode)? != WasiMode::Reactor {
+            return Err(WasiReactorError::CommandModule);
+        }
+
+        let import_object = generate_import_object_from_state_for_module(module, wasi_state);
+        let instance = module
+            .instantiate(&import_object)
+            .map_err(|e| WasiReactorError::Instantiation(format!("{:?}", e)))?;
+
+        if exports_function(module, REACTOR_INITIALIZE) {
+            call(&instance, REACTOR_INITIALIZE, &[])?;
+        }
+
+        Ok(Self { instance })
+    }
+
+    /// Call the export `name` with `args`.
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Vec<Value>, WasiReactorError> {
+        if name == REACTOR_INITIALIZE {
+            return Err(WasiReactorError::AlreadyInitialized);
+        }
+        call(&self.instance, name, args)
+    }
+
+    /// The instance the reactor runs in.
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    /// The instance the reactor runs in, e.g. to reach its [`WasiState`].
+    pub fn instance_mut(&mut self) -> &mut Instance {
+        &mut self.instance
+    }
+}
+
+fn call(instance: &Instance, name: &str, args: &[Value]) -> Result<Vec<Value>, WasiReactorError> {
+    let func = instance
+        .dyn_func(name)
+        .map_err(|_| WasiReactorError::MissingExport(name.to_string()))?;
+
+    match func.call(args) {
+        Ok(results) => Ok(results),
+        Err(e) => match WasiExit::from_call_error(&e) {
+            Some(exit) => Err(WasiReactorError::Exit(exit.code)),
+            None => Err(WasiReactorError::Runtime(format!("{:?}", e))),
+        },
+    }
+}
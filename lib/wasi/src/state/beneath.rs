@@ -0,0 +1,143 @@
+//! Opening host files one path component at a time, for [`WasiFs::sandbox_strict`].
+//!
+//! Every directory on the way is opened relative to the previous one with
+//! `O_NOFOLLOW`, as `openat2`'s `RESOLVE_BENEATH` would: a directory swapped
+//! for a symlink after the path was looked up makes the open fail instead of
+//! leaving the preopened directory.
+//!
+//! [`WasiFs::sandbox_strict`]: super::WasiFs::sandbox_strict
+
+use crate::syscalls::types::*;
+use std::{
+    ffi::{CString, OsStr},
+    fs, io,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, FromRawFd},
+    },
+    path::{Component, Path, PathBuf},
+};
+
+/// Opens `host_path` with the `open` flags `flags`, resolving it below the
+/// deepest of `roots` containing it.
+///
+/// Paths outside of every root, like mapped files, are opened as they are,
+/// only without following a symlink in their last component.
+pub(crate) fn open_beneath(
+    roots: &[PathBuf],
+    host_path: &Path,
+    flags: libc::c_int,
+) -> Result<fs::File, __wasi_errno_t> {
+    let (root, rest) = match roots
+        .iter()
+        .filter_map(|root| host_path.strip_prefix(root).ok().map(|rest| (root, rest)))
+        .max_by_key(|(root, _)| root.components().count())
+    {
+        Some(root_and_rest) => root_and_rest,
+        None => return open_at(None, host_path.as_os_str(), flags | libc::O_NOFOLLOW),
+    };
+
+    let mut dirs = vec![open_at(
+        None,
+        root.as_os_str(),
+        libc::O_RDONLY | libc::O_DIRECTORY,
+    )?];
+    let mut components = rest.components().peekable();
+    while let Some(component) = components.next() {
+        let dir = dirs.last();
+        match component {
+            Component::Normal(name) if components.peek().is_none() => {
+                return open_at(dir, name, flags | libc::O_NOFOLLOW);
+            }
+            Component::Normal(name) => {
+                let dir = open_at(
+                    dir,
+                    name,
+                    libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW,
+                )?;
+                dirs.push(dir);
+            }
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if dirs.len() == 1 {
+                    return Err(__WASI_ENOTCAPABLE);
+                }
+                dirs.pop();
+            }
+            Component::RootDir | Component::Prefix(_) => return Err(__WASI_ENOTCAPABLE),
+        }
+    }
+    // the path names a directory, which is opened again with `flags`
+    open_at(dirs.last(), OsStr::new("."), flags)
+}
+
+fn open_at(
+    dir: Option<&fs::File>,
+    name: &OsStr,
+    flags: libc::c_int,
+) -> Result<fs::File, __wasi_errno_t> {
+    let name = CString::new(name.as_bytes()).map_err(|_| __WASI_EINVAL)?;
+    let flags = flags | libc::O_CLOEXEC;
+    let mode: libc::c_uint = 0o666;
+    let fd = unsafe {
+        match dir {
+            Some(dir) => libc::openat(dir.as_raw_fd(), name.as_ptr(), flags, mode),
+            None => libc::open(name.as_ptr(), flags, mode),
+        }
+    };
+    if fd < 0 {
+        return Err(errno_from_io_error(&io::Error::last_os_error()));
+    }
+    Ok(unsafe { fs::File::from_raw_fd(fd) })
+}
+
+fn errno_from_io_error(error: &io::Error) -> __wasi_errno_t {
+    match error.raw_os_error() {
+        // a symlink where `O_NOFOLLOW` was given; FreeBSD reports `EMLINK`
+        Some(libc::ELOOP) | Some(libc::EMLINK) => __WASI_ENOTCAPABLE,
+        Some(libc::ENOENT) => __WASI_ENOENT,
+        Some(libc::ENOTDIR) => __WASI_ENOTDIR,
+        Some(libc::EEXIST) => __WASI_EEXIST,
+        Some(libc::EISDIR) => __WASI_EISDIR,
+        Some(libc::EACCES) | Some(libc::EPERM) => __WASI_EACCES,
+        _ => __WASI_EIO,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn symlinks_are_not_followed() {
+        let dir = std::env::temp_dir().join(format!("wasmer-wasi-beneath-{}", std::process::id()));
+        let root = dir.join("root");
+        let outside = dir.join("outside");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("sub/file"), b"inside").unwrap();
+        fs::write(outside.join("file"), b"outside").unwrap();
+        let roots = [root.clone()];
+
+        assert!(open_beneath(&roots, &root.join("sub/file"), libc::O_RDONLY).is_ok());
+        assert!(open_beneath(&roots, &root.join("sub/../sub/file"), libc::O_RDONLY).is_ok());
+        assert_eq!(
+            open_beneath(&roots, &root.join("../outside/file"), libc::O_RDONLY).err(),
+            Some(__WASI_ENOTCAPABLE)
+        );
+
+        // the directory is swapped for a symlink out of the root after being looked up
+        fs::rename(root.join("sub"), dir.join("sub")).unwrap();
+        symlink(&outside, root.join("sub")).unwrap();
+        assert!(open_beneath(&roots, &root.join("sub/file"), libc::O_RDONLY).is_err());
+
+        symlink(outside.join("file"), root.join("file")).unwrap();
+        assert_eq!(
+            open_beneath(&roots, &root.join("file"), libc::O_RDONLY).err(),
+            Some(__WASI_ENOTCAPABLE)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
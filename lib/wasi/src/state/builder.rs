@@ -1,8 +1,15 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
-use crate::state::{WasiFs, WasiState};
+use crate::reactor::WasiMode;
+use crate::state::{
+    PreopenRestrictions, UnimplementedPolicy, WasiClock, WasiFs, WasiRandom, WasiRecorder,
+    WasiReplayer, WasiState, WasiSyscallHook, WasiTraceMode,
+};
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpStream};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
 
 /// Creates an empty [`WasiStateBuilder`].
 pub(crate) fn create_wasi_state(program_name: &str) -> WasiStateBuilder {
@@ -19,6 +26,18 @@ pub struct WasiStateBuilder {
     envs: Vec<Vec<u8>>,
     preopened_files: Vec<PathBuf>,
     mapped_dirs: Vec<(String, PathBuf)>,
+    mapped_files: Vec<(String, PathBuf, bool)>,
+    /// restrictions keyed by the name of the preopened directory
+    preopen_restrictions: HashMap<String, PreopenRestrictions>,
+    sandbox_strict: bool,
+    clock: WasiClock,
+    random: WasiRandom,
+    sockets: Vec<Rc<TcpStream>>,
+    socket_addrs: Vec<SocketAddr>,
+    hook: Option<Arc<dyn WasiSyscallHook>>,
+    mode: WasiMode,
+    unimplemented_policy: UnimplementedPolicy,
+    trace_mode: Option<WasiTraceMode>,
     setup_fs_fn: Option<Rc<dyn Fn(&mut WasiFs) -> Result<(), String> + Send>>,
 }
 
@@ -29,6 +48,17 @@ impl std::fmt::Debug for WasiStateBuilder {
             .field("envs", &self.envs)
             .field("preopend_files", &self.preopened_files)
             .field("mapped_dirs", &self.mapped_dirs)
+            .field("mapped_files", &self.mapped_files)
+            .field("preopen_restrictions", &self.preopen_restrictions)
+            .field("sandbox_strict", &self.sandbox_strict)
+            .field("clock", &self.clock)
+            .field("random", &self.random)
+            .field("sockets", &self.sockets)
+            .field("socket_addrs", &self.socket_addrs)
+            .field("hook", &self.hook)
+            .field("mode", &self.mode)
+            .field("unimplemented_policy", &self.unimplemented_policy)
+            .field("trace_mode", &self.trace_mode)
             .field("setup_fs_fn exists", &self.setup_fs_fn.is_some())
             .finish()
     }
@@ -40,9 +70,13 @@ pub enum WasiStateCreationError {
     EnvironmentVariableFormatError(String),
     ArgumentContainsNulByte(String),
     PreopenedDirectoryNotFound(PathBuf),
+    MappedFileNotFound(PathBuf),
     MappedDirAliasFormattingError(String),
     WasiFsCreationError(String),
     WasiFsSetupError(String),
+    SocketConnectionError(SocketAddr, String),
+    /// The state can't be copied for the instances sharing it.
+    WasiStateCopyError(String),
 }
 
 fn validate_mapped_dir_alias(alias: &str) -> Result<(), WasiStateCreationError> {
@@ -150,7 +184,6 @@ impl WasiStateBuilder {
     /// Preopen a directory
     /// This opens the given directory at the virtual root, `/`, and allows
     /// the WASI module to read and write to the given directory.
-    // TODO: design a simple API for passing in permissions here (i.e. read-only)
     pub fn preopen_dir<FilePath>(&mut self, po_dir: FilePath) -> &mut Self
     where
         FilePath: AsRef<Path>,
@@ -177,6 +210,24 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Preopen a directory like [`WasiStateBuilder::preopen_dir`], but
+    /// withhold some capabilities from the WASI module, e.g. writing to it.
+    pub fn preopen_dir_restricted<FilePath>(
+        &mut self,
+        po_dir: FilePath,
+        restrictions: PreopenRestrictions,
+    ) -> &mut Self
+    where
+        FilePath: AsRef<Path>,
+    {
+        let path = po_dir.as_ref();
+        self.preopened_files.push(path.to_path_buf());
+        self.preopen_restrictions
+            .insert(path.to_string_lossy().into_owned(), restrictions);
+
+        self
+    }
+
     /// Preopen a directory with a different name exposed to the WASI.
     pub fn map_dir<FilePath>(&mut self, alias: &str, po_dir: FilePath) -> &mut Self
     where
@@ -189,6 +240,26 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Preopen a directory with a different name exposed to the WASI, and
+    /// withhold some capabilities from the WASI module, e.g. writing to it.
+    pub fn map_dir_restricted<FilePath>(
+        &mut self,
+        alias: &str,
+        po_dir: FilePath,
+        restrictions: PreopenRestrictions,
+    ) -> &mut Self
+    where
+        FilePath: AsRef<Path>,
+    {
+        let path = po_dir.as_ref();
+        self.mapped_dirs
+            .push((alias.to_string(), path.to_path_buf()));
+        self.preopen_restrictions
+            .insert(alias.to_string(), restrictions);
+
+        self
+    }
+
     /// Preopen directorys with a different names exposed to the WASI.
     pub fn map_dirs<I, FilePath>(&mut self, mapped_dirs: I) -> &mut Self
     where
@@ -203,6 +274,147 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Map a single host file to `guest_path`, e.g. `/etc/hosts`, without
+    /// exposing the directory containing it.
+    ///
+    /// The parent directory of `guest_path` is exposed to the WASI module as
+    /// a virtual directory holding only the files mapped into it.
+    pub fn map_file<FilePath>(&mut self, guest_path: &str, host_file: FilePath) -> &mut Self
+    where
+        FilePath: AsRef<Path>,
+    {
+        self.mapped_files.push((
+            guest_path.to_string(),
+            host_file.as_ref().to_path_buf(),
+            false,
+        ));
+
+        self
+    }
+
+    /// Map a single host file to `guest_path` like [`WasiStateBuilder::map_file`],
+    /// but don't allow the WASI module to write to it.
+    pub fn map_file_read_only<FilePath>(
+        &mut self,
+        guest_path: &str,
+        host_file: FilePath,
+    ) -> &mut Self
+    where
+        FilePath: AsRef<Path>,
+    {
+        self.mapped_files.push((
+            guest_path.to_string(),
+            host_file.as_ref().to_path_buf(),
+            true,
+        ));
+
+        self
+    }
+
+    /// Resolve paths strictly, see [`WasiFs::sandbox_strict`].
+    ///
+    /// This stops `..` from leaving the directory a path is resolved from,
+    /// rejects absolute symlinks, and refuses host entries that were swapped
+    /// for symlinks after being looked up.
+    pub fn sandbox_strict(&mut self, enabled: bool) -> &mut Self {
+        self.sandbox_strict = enabled;
+
+        self
+    }
+
+    /// Set the clock read by `clock_time_get`, e.g. a fixed or logical
+    /// clock for reproducible execution.  Defaults to the host clock.
+    pub fn clock(&mut self, clock: WasiClock) -> &mut Self {
+        self.clock = clock;
+
+        self
+    }
+
+    /// Make `random_get` return bytes from a deterministic PRNG started
+    /// from `seed` instead of the host's entropy source.
+    pub fn random_seed(&mut self, seed: u64) -> &mut Self {
+        self.random = WasiRandom::seeded(seed);
+
+        self
+    }
+
+    /// Give the module a logical clock starting at the epoch, and
+    /// randomness from a PRNG started from `seed`, so that its runs only
+    /// depend on its inputs.  To be used with the deterministic execution
+    /// profile of the compiler, `Features::deterministic`.
+    pub fn deterministic(&mut self, seed: u64) -> &mut Self {
+        self.clock(WasiClock::Logical { epoch: 0, step: 1 })
+            .random_seed(seed)
+    }
+
+    /// Give the WASI module an already connected socket.
+    ///
+    /// Sockets are given fds after every preopened directory, in the order
+    /// they were added.
+    pub fn preopen_socket(&mut self, stream: TcpStream) -> &mut Self {
+        self.sockets.push(Rc::new(stream));
+
+        self
+    }
+
+    /// Connect to `addr` when the [`WasiState`] is built and give the
+    /// connection to the WASI module like [`WasiStateBuilder::preopen_socket`].
+    ///
+    /// The module can only reach the addresses granted this way.
+    pub fn connect_socket(&mut self, addr: SocketAddr) -> &mut Self {
+        self.socket_addrs.push(addr);
+
+        self
+    }
+
+    /// Call `hook` before and after every syscall the WASI module makes,
+    /// e.g. a [`LoggingSyscallHook`] to audit the files it touches.
+    ///
+    /// [`LoggingSyscallHook`]: crate::state::LoggingSyscallHook
+    pub fn syscall_hook(&mut self, hook: Arc<dyn WasiSyscallHook>) -> &mut Self {
+        self.hook = Some(hook);
+
+        self
+    }
+
+    /// Record the results of the syscalls the WASI module makes with
+    /// `recorder`, to replay them later with [`WasiStateBuilder::replay`].
+    pub fn record(&mut self, recorder: Arc<WasiRecorder>) -> &mut Self {
+        self.trace_mode = Some(WasiTraceMode::Record(recorder));
+
+        self
+    }
+
+    /// Replay the syscall results recorded by a [`WasiRecorder`] with
+    /// `replayer` instead of running the syscalls.
+    ///
+    /// [`WasiRecorder`]: crate::state::WasiRecorder
+    pub fn replay(&mut self, replayer: Arc<WasiReplayer>) -> &mut Self {
+        self.trace_mode = Some(WasiTraceMode::Replay(replayer));
+
+        self
+    }
+
+    /// Set whether the module is run as a command or a reactor.  Defaults
+    /// to [`WasiMode::Auto`], which uses the mode the module was built for.
+    ///
+    /// [`WasiReactor`] refuses states built with [`WasiMode::Command`].
+    ///
+    /// [`WasiReactor`]: crate::reactor::WasiReactor
+    pub fn mode(&mut self, mode: WasiMode) -> &mut Self {
+        self.mode = mode;
+
+        self
+    }
+
+    /// Choose what syscalls do when the module asks for something that isn't
+    /// implemented, e.g. reading a symlink.  Defaults to returning `ENOSYS`.
+    pub fn unimplemented_policy(&mut self, policy: UnimplementedPolicy) -> &mut Self {
+        self.unimplemented_policy = policy;
+
+        self
+    }
+
     /// Setup the WASI filesystem before running
     // TODO: improve ergonomics on this function
     pub fn setup_fs(
@@ -279,15 +491,70 @@ impl WasiStateBuilder {
             }
             validate_mapped_dir_alias(&alias)?;
         }
+
+        for (_, host_file, _) in self.mapped_files.iter() {
+            if !host_file.is_file() {
+                return Err(WasiStateCreationError::MappedFileNotFound(
+                    host_file.clone(),
+                ));
+            }
+        }
         let mut wasi_fs = WasiFs::new(&self.preopened_files, &self.mapped_dirs)
             .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        wasi_fs.sandbox_strict = self.sandbox_strict;
+        for (name, restrictions) in self.preopen_restrictions.iter() {
+            if let Some(fd) = wasi_fs.preopen_fd_by_name(name) {
+                wasi_fs.restrict_preopen(fd, *restrictions).map_err(|e| {
+                    WasiStateCreationError::WasiFsCreationError(format!(
+                        "Could not restrict preopened directory \"{}\": {}",
+                        name, e
+                    ))
+                })?;
+            }
+        }
+        for (guest_path, host_file, read_only) in self.mapped_files.iter() {
+            wasi_fs
+                .map_file(guest_path, host_file, *read_only)
+                .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        }
         if let Some(f) = &self.setup_fs_fn {
             f(&mut wasi_fs).map_err(WasiStateCreationError::WasiFsSetupError)?;
         }
+        for stream in self.sockets.iter() {
+            let stream = stream.try_clone().map_err(|e| {
+                WasiStateCreationError::WasiFsCreationError(format!(
+                    "Could not preopen socket: {}",
+                    e
+                ))
+            })?;
+            wasi_fs.preopen_socket(stream).map_err(|e| {
+                WasiStateCreationError::WasiFsCreationError(format!(
+                    "Could not preopen socket: {}",
+                    e
+                ))
+            })?;
+        }
+        for addr in self.socket_addrs.iter() {
+            let stream = TcpStream::connect(addr)
+                .map_err(|e| WasiStateCreationError::SocketConnectionError(*addr, e.to_string()))?;
+            wasi_fs.preopen_socket(stream).map_err(|e| {
+                WasiStateCreationError::WasiFsCreationError(format!(
+                    "Could not preopen socket: {}",
+                    e
+                ))
+            })?;
+        }
         Ok(WasiState {
             fs: wasi_fs,
             args: self.args.clone(),
             envs: self.envs.clone(),
+            clock: self.clock.clone(),
+            random: self.random.clone(),
+            hook: self.hook.clone(),
+            mode: self.mode,
+            exit_code: None,
+            unimplemented_policy: self.unimplemented_policy.clone(),
+            trace_mode: self.trace_mode.clone(),
         })
     }
 }
@@ -330,4 +597,213 @@ mod test {
             _ => assert!(false),
         }
     }
+
+    #[test]
+    fn mapped_files() {
+        let output = create_wasi_state("test_prog")
+            .map_file("/etc/missing", "this-file-does-not-exist")
+            .build();
+        match output {
+            Err(WasiStateCreationError::MappedFileNotFound(_)) => assert!(true),
+            _ => assert!(false),
+        }
+
+        let output = create_wasi_state("test_prog")
+            .map_file("top-level", "Cargo.toml")
+            .build();
+        match output {
+            Err(WasiStateCreationError::WasiFsCreationError(_)) => assert!(true),
+            _ => assert!(false),
+        }
+
+        let mut state = create_wasi_state("test_prog")
+            .map_file_read_only("/etc/Cargo.toml", "Cargo.toml")
+            .map_file("/etc/lib.rs", "src/lib.rs")
+            .build()
+            .unwrap();
+        let etc_fd = *state.fs.preopen_fds.last().unwrap();
+        let manifest = state
+            .fs
+            .get_inode_at_path(etc_fd, "Cargo.toml", false)
+            .unwrap();
+        let lib = state.fs.get_inode_at_path(etc_fd, "lib.rs", false).unwrap();
+        assert!(state.fs.is_read_only(manifest));
+        assert!(!state.fs.is_read_only(lib));
+        assert!(state
+            .fs
+            .get_inode_at_path(etc_fd, "build.rs", false)
+            .is_err());
+    }
+
+    #[test]
+    fn restricted_preopens() {
+        use crate::syscalls::types::*;
+
+        let restrictions = PreopenRestrictions {
+            read_only: true,
+            ..PreopenRestrictions::default()
+        };
+        let state = create_wasi_state("test_prog")
+            .preopen_dir_restricted("src", restrictions)
+            .map_dir("dot", ".")
+            .build()
+            .unwrap();
+
+        let src_fd = state.fs.preopen_fd_by_name("src").unwrap();
+        let src = state.fs.get_fd(src_fd).unwrap();
+        assert_eq!(src.rights & __WASI_RIGHT_FD_WRITE, 0);
+        assert_eq!(src.rights_inheriting & __WASI_RIGHT_PATH_CREATE_FILE, 0);
+        assert_eq!(src.rights_inheriting & __WASI_RIGHT_PATH_UNLINK_FILE, 0);
+        assert_ne!(src.rights_inheriting & __WASI_RIGHT_FD_READ, 0);
+
+        let dot_fd = state.fs.preopen_fd_by_name("dot").unwrap();
+        let dot = state.fs.get_fd(dot_fd).unwrap();
+        assert_ne!(dot.rights_inheriting & __WASI_RIGHT_FD_WRITE, 0);
+    }
+
+    #[test]
+    fn strict_sandbox() {
+        use crate::syscalls::types::*;
+
+        let mut state = create_wasi_state("test_prog")
+            .preopen_dir("src")
+            .build()
+            .unwrap();
+        let src_fd = state.fs.preopen_fd_by_name("src").unwrap();
+        assert!(state
+            .fs
+            .get_inode_at_path(src_fd, "../src/lib.rs", false)
+            .is_ok());
+
+        let mut state = create_wasi_state("test_prog")
+            .preopen_dir("src")
+            .sandbox_strict(true)
+            .build()
+            .unwrap();
+        let src_fd = state.fs.preopen_fd_by_name("src").unwrap();
+        assert_eq!(
+            state.fs.get_inode_at_path(src_fd, "../src/lib.rs", false),
+            Err(__WASI_ENOTCAPABLE)
+        );
+        assert!(state
+            .fs
+            .get_inode_at_path(src_fd, "state/../lib.rs", false)
+            .is_ok());
+    }
+
+    #[test]
+    fn preopened_sockets() {
+        use crate::state::SOCKET_RIGHTS;
+        use crate::syscalls::types::*;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = TcpStream::connect(addr).unwrap();
+
+        let state = create_wasi_state("test_prog")
+            .preopen_dir("src")
+            .preopen_socket(stream)
+            .connect_socket(addr)
+            .build()
+            .unwrap();
+        let src_fd = state.fs.preopen_fd_by_name("src").unwrap();
+        for sock in &[src_fd + 1, src_fd + 2] {
+            let fdstat = state.fs.fdstat(*sock).unwrap();
+            assert_eq!(fdstat.fs_filetype, __WASI_FILETYPE_SOCKET_STREAM);
+            assert_eq!(fdstat.fs_rights_base, SOCKET_RIGHTS);
+            assert_eq!(state.fs.prestat_fd(*sock).err(), Some(__WASI_EBADF));
+        }
+
+        drop(listener);
+        let output = create_wasi_state("test_prog").connect_socket(addr).build();
+        match output {
+            Err(WasiStateCreationError::SocketConnectionError(a, _)) => assert_eq!(a, addr),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn filesystem_introspection() {
+        use std::path::PathBuf;
+
+        let mut state = create_wasi_state("test_prog")
+            .preopen_dir("src")
+            .build()
+            .unwrap();
+        let src_fd = state.fs.preopen_fd_by_name("src").unwrap();
+        let src = state
+            .fs
+            .open_fds()
+            .into_iter()
+            .find(|info| info.fd == src_fd)
+            .unwrap();
+        assert!(src.is_preopened);
+        assert_eq!(src.guest_path, Some(PathBuf::from("/src")));
+        assert!(state.fs.files().is_empty());
+
+        assert_eq!(
+            state.fs.read_file("src/lib.rs").unwrap(),
+            std::fs::read("src/lib.rs").unwrap()
+        );
+        assert_eq!(state.fs.files(), vec![PathBuf::from("/src/lib.rs")]);
+        assert!(state.fs.read_file("src").is_err());
+        assert!(state.fs.read_file("src/does_not_exist").is_err());
+    }
+
+    #[test]
+    fn wasi_mode() -> Result<(), WasiStateCreationError> {
+        use crate::state::WasiStateTemplate;
+
+        let state = create_wasi_state("test_prog").build()?;
+        assert_eq!(state.mode, WasiMode::Auto);
+
+        let state = create_wasi_state("test_prog")
+            .mode(WasiMode::Reactor)
+            .build()?;
+        assert_eq!(state.mode, WasiMode::Reactor);
+        let state = WasiStateTemplate::new(&state)?.instantiate();
+        assert_eq!(state.mode, WasiMode::Reactor);
+        Ok(())
+    }
+
+    #[test]
+    fn state_template_keeps_unserializable_parts() {
+        use crate::state::SOCKET_RIGHTS;
+        use crate::state::{Kind, LoggingSyscallHook, UnimplementedPolicy, WasiStateTemplate};
+        use std::io::Write;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+
+        let state = create_wasi_state("test_prog")
+            .preopen_socket(stream)
+            .syscall_hook(Arc::new(LoggingSyscallHook::default()))
+            .unimplemented_policy(UnimplementedPolicy::Trap)
+            .build()
+            .unwrap();
+        let mut copy = WasiStateTemplate::new(&state).unwrap().instantiate();
+        assert!(copy.hook.is_some());
+        match copy.unimplemented_policy {
+            UnimplementedPolicy::Trap => (),
+            _ => assert!(false),
+        }
+
+        server.write_all(b"ping").unwrap();
+        let socket = copy
+            .fs
+            .open_fds()
+            .into_iter()
+            .find(|info| info.rights == SOCKET_RIGHTS)
+            .unwrap();
+        let mut buf = [0; 4];
+        match &mut copy.fs.inodes[socket.inode].kind {
+            Kind::File {
+                handle: Some(handle),
+                ..
+            } => assert_eq!(handle.sock_peek(&mut buf).unwrap(), 4),
+            _ => assert!(false),
+        }
+        assert_eq!(&buf, b"ping");
+    }
 }
@@ -0,0 +1,266 @@
+//! Checkpointing a WASI instance to resume it later, possibly on another host.
+//!
+//! A [`WasiCheckpoint`] pairs an [`InstanceImage`], the snapshot of the
+//! instance's memory, globals and stack, with the instance's [`WasiState`]:
+//! its fd table, preopened directories, fd offsets and in-memory files.
+//!
+//! Host files are opened again when the checkpoint is restored.  A
+//! [`HostPathRemap`] maps the host paths they had when the checkpoint was
+//! taken to equivalent paths on the host restoring it.  Connected sockets
+//! can't be restored and come back disconnected.
+
+use crate::state::{Kind, WasiFs, WasiState};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    fmt,
+    path::{Path, PathBuf},
+};
+use wasmer_runtime_core::state::InstanceImage;
+
+/// Maps host path prefixes recorded in a checkpoint to the paths to use on
+/// the host restoring it.
+#[derive(Debug, Clone, Default)]
+pub struct HostPathRemap {
+    prefixes: Vec<(PathBuf, PathBuf)>,
+}
+
+impl HostPathRemap {
+    /// A remap that keeps every path as it is.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restore paths under `from` under `to` instead.
+    pub fn map<From, To>(&mut self, from: From, to: To) -> &mut Self
+    where
+        From: AsRef<Path>,
+        To: AsRef<Path>,
+    {
+        self.prefixes
+            .push((from.as_ref().to_path_buf(), to.as_ref().to_path_buf()));
+
+        self
+    }
+
+    /// The path `path` is restored at; the longest matching prefix wins.
+    pub fn remap(&self, path: &Path) -> PathBuf {
+        self.prefixes
+            .iter()
+            .filter_map(|(from, to)| Some((from, to, path.strip_prefix(from).ok()?)))
+            .max_by_key(|(from, _, _)| from.components().count())
+            .map(|(_, to, rest)| to.join(rest))
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+}
+
+/// Error returned when a [`WasiState`] can't be restored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WasiRestoreError {
+    /// The bytes aren't a serialized `WasiState`.
+    InvalidCheckpoint(String),
+    /// A file the module had open or mapped can't be opened at this path.
+    HostFileUnavailable(PathBuf),
+    /// A directory the module had open doesn't exist at this path.
+    HostDirectoryNotFound(PathBuf),
+}
+
+impl fmt::Display for WasiRestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasiRestoreError::InvalidCheckpoint(msg) => {
+                write!(f, "invalid WASI checkpoint: {}", msg)
+            }
+            WasiRestoreError::HostFileUnavailable(path) => {
+                write!(f, "could not open host file {:?}", path)
+            }
+            WasiRestoreError::HostDirectoryNotFound(path) => {
+                write!(f, "host directory {:?} does not exist", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WasiRestoreError {}
+
+struct Restoring {
+    remap: HostPathRemap,
+    unavailable: Option<PathBuf>,
+}
+
+thread_local! {
+    /// Set while `WasiState::restore` deserializes a state, for `HostFile`s
+    /// to find the path they should be opened at.
+    static RESTORING: RefCell<Option<Restoring>> = RefCell::new(None);
+}
+
+/// The path a `HostFile` that was serialized at `path` is opened at.
+pub(crate) fn remap_host_path(path: PathBuf) -> PathBuf {
+    RESTORING.with(|restoring| match &*restoring.borrow() {
+        Some(restoring) => restoring.remap.remap(&path),
+        None => path,
+    })
+}
+
+/// Record that the `HostFile` at `path` couldn't be opened.
+pub(crate) fn host_file_unavailable(path: &Path) {
+    RESTORING.with(|restoring| {
+        if let Some(restoring) = &mut *restoring.borrow_mut() {
+            restoring.unavailable = Some(path.to_path_buf());
+        }
+    })
+}
+
+impl WasiState {
+    /// Restore a `WasiState` serialized with [`WasiState::freeze`].
+    ///
+    /// Host files and directories are looked up at the paths `remap` maps
+    /// them to.  Files the module had open are opened again, and the
+    /// directories it had open and the files mapped into it must still
+    /// exist.
+    pub fn restore(bytes: &[u8], remap: &HostPathRemap) -> Result<Self, WasiRestoreError> {
+        RESTORING.with(|restoring| {
+            *restoring.borrow_mut() = Some(Restoring {
+                remap: remap.clone(),
+                unavailable: None,
+            })
+        });
+        let state = bincode::deserialize::<WasiState>(bytes);
+        let restoring = RESTORING.with(|restoring| restoring.borrow_mut().take());
+
+        let mut state = match (state, restoring.and_then(|r| r.unavailable)) {
+            (Ok(state), _) => state,
+            (Err(_), Some(path)) => return Err(WasiRestoreError::HostFileUnavailable(path)),
+            (Err(e), None) => return Err(WasiRestoreError::InvalidCheckpoint(e.to_string())),
+        };
+        remap_host_paths(&mut state.fs, remap);
+        validate_host_paths(&state.fs)?;
+
+        Ok(state)
+    }
+}
+
+fn remap_host_paths(fs: &mut WasiFs, remap: &HostPathRemap) {
+    let inodes = fs
+        .inodes
+        .iter_mut()
+        .map(|(_, inode)| inode)
+        .chain(fs.orphan_fds.values_mut());
+    for inode in inodes {
+        match &mut inode.kind {
+            Kind::File { path, .. } | Kind::Dir { path, .. } => *path = remap.remap(path),
+            _ => (),
+        }
+    }
+}
+
+fn validate_host_paths(fs: &WasiFs) -> Result<(), WasiRestoreError> {
+    // the inodes of open fds and of the files mapped into virtual directories
+    let open_inodes = fs.fd_map.values().map(|fd| fd.inode);
+    let mapped_inodes = fs
+        .inodes
+        .iter()
+        .filter_map(|(_, inode)| match &inode.kind {
+            Kind::Root { entries } => Some(entries.values().cloned()),
+            _ => None,
+        })
+        .flatten();
+
+    for inode in open_inodes.chain(mapped_inodes) {
+        match fs.inodes.get(inode).map(|inode| &inode.kind) {
+            Some(Kind::Dir { path, .. }) if !path.is_dir() => {
+                return Err(WasiRestoreError::HostDirectoryNotFound(path.clone()));
+            }
+            Some(Kind::File {
+                handle: None, path, ..
+            }) if !path.is_file() => {
+                return Err(WasiRestoreError::HostFileUnavailable(path.clone()));
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// A suspended WASI instance: the [`InstanceImage`] of the instance and its
+/// serialized [`WasiState`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasiCheckpoint {
+    pub image: InstanceImage,
+    pub wasi_state: Vec<u8>,
+}
+
+impl WasiCheckpoint {
+    /// Checkpoint an instance suspended as `image` whose state is `wasi_state`.
+    ///
+    /// Returns `None` if the state can't be serialized.
+    pub fn new(image: InstanceImage, wasi_state: &WasiState) -> Option<Self> {
+        Some(Self {
+            image,
+            wasi_state: wasi_state.freeze()?,
+        })
+    }
+
+    /// Restore the [`WasiState`], see [`WasiState::restore`].
+    pub fn restore_wasi_state(&self, remap: &HostPathRemap) -> Result<WasiState, WasiRestoreError> {
+        WasiState::restore(&self.wasi_state, remap)
+    }
+
+    /// Converts a slice of bytes into an `Option<WasiCheckpoint>`
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bincode::deserialize(bytes).ok()
+    }
+
+    /// Converts self into a vector of bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn host_path_remap() {
+        let mut remap = HostPathRemap::new();
+        remap.map("/old", "/new").map("/old/data", "/data");
+        assert_eq!(remap.remap(Path::new("/old/a")), PathBuf::from("/new/a"));
+        assert_eq!(
+            remap.remap(Path::new("/old/data/b")),
+            PathBuf::from("/data/b")
+        );
+        assert_eq!(remap.remap(Path::new("/other")), PathBuf::from("/other"));
+    }
+
+    #[test]
+    fn restore_preopened_dirs() {
+        let state = WasiState::new("test_prog")
+            .preopen_dir("src")
+            .build()
+            .unwrap();
+        let bytes = state.freeze().unwrap();
+
+        let mut remap = HostPathRemap::new();
+        remap.map("src", "src/state");
+        let restored = WasiState::restore(&bytes, &remap).unwrap();
+        let fd = restored.fs.preopen_fd_by_name("src").unwrap();
+        let inode = restored.fs.get_fd(fd).unwrap().inode;
+        match &restored.fs.inodes[inode].kind {
+            Kind::Dir { path, .. } => assert_eq!(path, &PathBuf::from("src/state")),
+            _ => panic!("preopened directory was not restored as a directory"),
+        }
+
+        remap.map("src", "does_not_exist");
+        assert_eq!(
+            WasiState::restore(&bytes, &remap).err(),
+            Some(WasiRestoreError::HostDirectoryNotFound(PathBuf::from(
+                "does_not_exist"
+            )))
+        );
+        match WasiState::restore(&bytes[..bytes.len() / 2], &HostPathRemap::new()) {
+            Err(WasiRestoreError::InvalidCheckpoint(_)) => (),
+            _ => panic!("a truncated checkpoint was restored"),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Virtual clocks for reproducible execution.
+
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+
+/// Where `clock_time_get` and `clock_res_get` read the time from.
+///
+/// Virtual clocks apply to every WASI clock id alike.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WasiClock {
+    /// Use the host's clocks.
+    Host,
+    /// Every read returns the same timestamp, in nanoseconds.
+    Fixed { epoch: __wasi_timestamp_t },
+    /// The first read returns `epoch`, and every read after that advances
+    /// the clock by `step` nanoseconds.
+    Logical {
+        epoch: __wasi_timestamp_t,
+        step: __wasi_timestamp_t,
+    },
+}
+
+impl Default for WasiClock {
+    fn default() -> Self {
+        WasiClock::Host
+    }
+}
+
+impl WasiClock {
+    /// Read a virtual clock, advancing it if it's a logical clock.
+    ///
+    /// Returns `None` for [`WasiClock::Host`].
+    pub fn read(&mut self) -> Option<__wasi_timestamp_t> {
+        match self {
+            WasiClock::Host => None,
+            WasiClock::Fixed { epoch } => Some(*epoch),
+            WasiClock::Logical { epoch, step } => {
+                let now = *epoch;
+                *epoch = epoch.wrapping_add(*step);
+                Some(now)
+            }
+        }
+    }
+
+    /// The resolution of a virtual clock, in nanoseconds.
+    ///
+    /// Returns `None` for [`WasiClock::Host`].
+    pub fn resolution(&self) -> Option<__wasi_timestamp_t> {
+        match self {
+            WasiClock::Host => None,
+            WasiClock::Fixed { .. } => Some(1),
+            WasiClock::Logical { step, .. } => Some((*step).max(1)),
+        }
+    }
+}
+
+/// Whether `clock_id` names a clock WASI knows about.
+pub(crate) fn is_valid_clock_id(clock_id: __wasi_clockid_t) -> bool {
+    match clock_id {
+        __WASI_CLOCK_REALTIME
+        | __WASI_CLOCK_MONOTONIC
+        | __WASI_CLOCK_PROCESS_CPUTIME_ID
+        | __WASI_CLOCK_THREAD_CPUTIME_ID => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn logical_clock_advances() {
+        let mut clock = WasiClock::Logical { epoch: 10, step: 5 };
+        assert_eq!(clock.read(), Some(10));
+        assert_eq!(clock.read(), Some(15));
+        assert_eq!(clock.read(), Some(20));
+        assert_eq!(clock.resolution(), Some(5));
+
+        let mut clock = WasiClock::Fixed { epoch: 42 };
+        assert_eq!(clock.read(), Some(42));
+        assert_eq!(clock.read(), Some(42));
+
+        assert_eq!(WasiClock::Host.read(), None);
+    }
+}
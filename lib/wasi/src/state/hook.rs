@@ -0,0 +1,262 @@
+//! Hooks for observing the syscalls a WASI module makes.
+//!
+//! A [`WasiSyscallHook`] set with [`WasiStateBuilder::syscall_hook`] is
+//! called before and after every WASI syscall of the instance.
+//! [`LoggingSyscallHook`] logs every call through the `log` crate.
+//!
+//! [`WasiStateBuilder::syscall_hook`]: super::WasiStateBuilder::syscall_hook
+
+use crate::{
+    ptr::{Array, WasmPtr},
+    state::{get_wasi_state, replay},
+    syscalls::types::*,
+    WasiExit,
+};
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, fmt, sync::Arc};
+use wasmer_runtime_core::{memory::Memory, types::Value, vm::Ctx};
+
+/// A WASI syscall made by the module.
+pub struct WasiSyscall<'a> {
+    /// The name of the syscall, e.g. `path_open`
+    pub name: &'a str,
+    /// The raw arguments, in the order of the syscall's signature
+    pub args: &'a [Value],
+    /// The memory that pointer arguments point into
+    pub memory: &'a Memory,
+}
+
+impl<'a> WasiSyscall<'a> {
+    /// The paths given to a `path_*` syscall, read from the module's memory.
+    pub fn paths(&self) -> Vec<String> {
+        // the (pointer, length) argument positions of each path
+        let positions: &[(usize, usize)] = match self.name {
+            "path_create_directory"
+            | "path_readlink"
+            | "path_remove_directory"
+            | "path_unlink_file" => &[(1, 2)],
+            "path_filestat_get" | "path_filestat_set_times" | "path_open" => &[(2, 3)],
+            "path_link" => &[(2, 3), (5, 6)],
+            "path_rename" => &[(1, 2), (4, 5)],
+            "path_symlink" => &[(0, 1), (3, 4)],
+            _ => &[],
+        };
+        positions
+            .iter()
+            .filter_map(
+                |&(ptr, len)| match (self.args.get(ptr)?, self.args.get(len)?) {
+                    (Value::I32(ptr), Value::I32(len)) => WasmPtr::<u8, Array>::new(*ptr as u32)
+                        .get_utf8_string(self.memory, *len as u32)
+                        .map(str::to_string),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+}
+
+/// How a syscall finished.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WasiSyscallOutcome {
+    /// The syscall returned an errno
+    Returned(__wasi_errno_t),
+    /// The syscall was `proc_exit` and the module is exiting with this code
+    Exited(__wasi_exitcode_t),
+}
+
+/// Observes the syscalls made by a WASI module.
+pub trait WasiSyscallHook: fmt::Debug + Send + Sync {
+    /// Called before `call` runs.
+    fn before_syscall(&self, _call: &WasiSyscall) {}
+
+    /// Called after `call` finished with `outcome`.
+    fn after_syscall(&self, _call: &WasiSyscall, _outcome: WasiSyscallOutcome) {}
+}
+
+/// A [`WasiSyscallHook`] that logs every syscall once it finished, with its
+/// arguments, the paths it was given and its outcome.
+///
+/// Calls are logged to the `wasi::syscall` target at `level`.
+#[derive(Debug, Clone)]
+pub struct LoggingSyscallHook {
+    pub level: log::Level,
+}
+
+impl Default for LoggingSyscallHook {
+    fn default() -> Self {
+        Self {
+            level: log::Level::Info,
+        }
+    }
+}
+
+impl WasiSyscallHook for LoggingSyscallHook {
+    fn after_syscall(&self, call: &WasiSyscall, outcome: WasiSyscallOutcome) {
+        let args = call
+            .args
+            .iter()
+            .map(|arg| match arg {
+                Value::I32(v) => v.to_string(),
+                Value::I64(v) => v.to_string(),
+                _ => format!("{:?}", arg),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let outcome = match outcome {
+            WasiSyscallOutcome::Returned(errno) => format!("errno={}", errno),
+            WasiSyscallOutcome::Exited(code) => format!("exit_code={}", code),
+        };
+        log::log!(
+            target: "wasi::syscall",
+            self.level,
+            "syscall={} args=[{}] paths={:?} {}",
+            call.name,
+            args,
+            call.paths(),
+            outcome
+        );
+    }
+}
+
+/// The return types of syscalls.
+pub(crate) trait SyscallResult {
+    /// What the syscall returns to the module.
+    type Value;
+
+    fn outcome(&self) -> WasiSyscallOutcome;
+
+    /// What the syscall returns to the module, or the exit it raises.
+    fn into_result(self) -> Result<Self::Value, WasiExit>;
+
+    /// The result of a syscall that finished with `outcome`, when it's
+    /// replayed.
+    fn from_outcome(outcome: WasiSyscallOutcome) -> Self;
+}
+
+impl SyscallResult for __wasi_errno_t {
+    type Value = __wasi_errno_t;
+
+    fn outcome(&self) -> WasiSyscallOutcome {
+        WasiSyscallOutcome::Returned(*self)
+    }
+
+    fn into_result(self) -> Result<__wasi_errno_t, WasiExit> {
+        Ok(self)
+    }
+
+    fn from_outcome(outcome: WasiSyscallOutcome) -> Self {
+        match outcome {
+            WasiSyscallOutcome::Returned(errno) => errno,
+            WasiSyscallOutcome::Exited(_) => unreachable!("only `proc_exit` exits"),
+        }
+    }
+}
+
+impl SyscallResult for Result<Infallible, WasiExit> {
+    type Value = Infallible;
+
+    fn outcome(&self) -> WasiSyscallOutcome {
+        match self {
+            Ok(never) => match *never {},
+            Err(exit_code) => WasiSyscallOutcome::Exited(exit_code.code),
+        }
+    }
+
+    fn into_result(self) -> Self {
+        self
+    }
+
+    fn from_outcome(outcome: WasiSyscallOutcome) -> Self {
+        match outcome {
+            WasiSyscallOutcome::Exited(code) => Err(WasiExit { code }),
+            WasiSyscallOutcome::Returned(_) => unreachable!("`proc_exit` doesn't return"),
+        }
+    }
+}
+
+/// Convert a syscall argument to a [`Value`] for [`WasiSyscall::args`].
+pub(crate) fn arg_value<T: wasmer_runtime_core::types::WasmExternType>(arg: T) -> Value {
+    arg.to_native().into()
+}
+
+/// Run `syscall`, calling the instance's hook around it if it has one, and
+/// recording or replaying its result if the instance is being traced.
+pub(crate) fn call_with_hook<R, F>(ctx: &mut Ctx, name: &str, args: &[Value], syscall: F) -> R
+where
+    R: SyscallResult,
+    F: FnOnce(&mut Ctx) -> R,
+{
+    let state = unsafe { get_wasi_state(ctx) };
+    let trace_mode = state.trace_mode.clone();
+    let hook = match state.hook.as_ref().map(Arc::clone) {
+        Some(hook) => hook,
+        None => {
+            return match trace_mode {
+                Some(mode) => replay::call_with_trace(&mode, ctx, name, syscall),
+                None => syscall(ctx),
+            }
+        }
+    };
+    hook.before_syscall(&WasiSyscall {
+        name,
+        args,
+        memory: ctx.memory(0),
+    });
+    let result = match trace_mode {
+        Some(mode) => replay::call_with_trace(&mode, ctx, name, syscall),
+        None => syscall(ctx),
+    };
+    hook.after_syscall(
+        &WasiSyscall {
+            name,
+            args,
+            memory: ctx.memory(0),
+        },
+        result.outcome(),
+    );
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmer_runtime_core::{types::MemoryDescriptor, units::Pages};
+
+    #[test]
+    fn syscall_paths() {
+        let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+        for (cell, byte) in memory.view::<u8>()[16..].iter().zip(b"from/to/dir".iter()) {
+            cell.set(*byte);
+        }
+
+        let args = [Value::I32(4), Value::I32(16), Value::I32(11)];
+        let call = WasiSyscall {
+            name: "path_create_directory",
+            args: &args,
+            memory: &memory,
+        };
+        assert_eq!(call.paths(), vec!["from/to/dir".to_string()]);
+
+        let args = [
+            Value::I32(4),
+            Value::I32(16),
+            Value::I32(4),
+            Value::I32(5),
+            Value::I32(21),
+            Value::I32(2),
+        ];
+        let call = WasiSyscall {
+            name: "path_rename",
+            args: &args,
+            memory: &memory,
+        };
+        assert_eq!(call.paths(), vec!["from".to_string(), "to".to_string()]);
+
+        let call = WasiSyscall {
+            name: "fd_close",
+            args: &args[..1],
+            memory: &memory,
+        };
+        assert!(call.paths().is_empty());
+    }
+}
@@ -13,30 +13,78 @@
 //! You can implement `WasiFile` for your own types to get custom behavior and extend WASI, see the
 //! [WASI plugin example](https://github.com/wasmerio/wasmer/blob/master/examples/plugin.rs).
 
+#[cfg(unix)]
+mod beneath;
 mod builder;
+mod checkpoint;
+mod clock;
+pub mod hook;
+mod random;
+pub mod replay;
+mod socket;
 mod types;
+mod unimplemented;
 
+#[cfg(unix)]
+pub(crate) use self::beneath::open_beneath;
 pub use self::builder::*;
+pub use self::checkpoint::{HostPathRemap, WasiCheckpoint, WasiRestoreError};
+pub(crate) use self::clock::is_valid_clock_id;
+pub use self::clock::WasiClock;
+pub use self::hook::{LoggingSyscallHook, WasiSyscallHook};
+pub use self::random::WasiRandom;
+pub use self::replay::{WasiRecorder, WasiReplayDiverged, WasiReplayer, WasiTrace, WasiTraceMode};
+pub use self::socket::WasiTcpStream;
 pub use self::types::*;
+pub use self::unimplemented::{UnimplementedPolicy, WasiUnimplemented};
+use crate::reactor::WasiMode;
 use crate::syscalls::types::*;
 use generational_arena::Arena;
 pub use generational_arena::Index as Inode;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::{
     borrow::Borrow,
     cell::Cell,
     fs,
-    io::Write,
-    path::{Path, PathBuf},
+    io::{self, Read, Seek, Write},
+    net::TcpStream,
+    path::{Component, Path, PathBuf},
     time::SystemTime,
 };
-use wasmer_runtime_core::{debug, vm::Ctx};
+use wasmer_runtime_core::{debug, memory::Memory, vm::Ctx};
 
 /// the fd value of the virtual root
 pub const VIRTUAL_ROOT_FD: __wasi_fd_t = 3;
 /// all the rights enabled
 pub const ALL_RIGHTS: __wasi_rights_t = 0x1FFFFFFF;
+/// the rights that allow modifying the contents or metadata of an open file
+pub const FILE_WRITE_RIGHTS: __wasi_rights_t = __WASI_RIGHT_FD_DATASYNC
+    | __WASI_RIGHT_FD_WRITE
+    | __WASI_RIGHT_FD_ALLOCATE
+    | __WASI_RIGHT_FD_FILESTAT_SET_SIZE
+    | __WASI_RIGHT_FD_FILESTAT_SET_TIMES;
+/// the rights that allow creating new entries in a directory
+const CREATE_RIGHTS: __wasi_rights_t = __WASI_RIGHT_PATH_CREATE_DIRECTORY
+    | __WASI_RIGHT_PATH_CREATE_FILE
+    | __WASI_RIGHT_PATH_LINK_TARGET
+    | __WASI_RIGHT_PATH_RENAME_TARGET
+    | __WASI_RIGHT_PATH_SYMLINK;
+/// the rights that allow removing entries from a directory
+const DELETE_RIGHTS: __wasi_rights_t = __WASI_RIGHT_PATH_REMOVE_DIRECTORY
+    | __WASI_RIGHT_PATH_UNLINK_FILE
+    | __WASI_RIGHT_PATH_RENAME_SOURCE;
+/// the rights that allow modifying existing entries through a directory
+const PATH_WRITE_RIGHTS: __wasi_rights_t =
+    __WASI_RIGHT_PATH_FILESTAT_SET_SIZE | __WASI_RIGHT_PATH_FILESTAT_SET_TIMES;
+/// the rights given to preopened sockets
+pub const SOCKET_RIGHTS: __wasi_rights_t = __WASI_RIGHT_FD_READ
+    | __WASI_RIGHT_FD_WRITE
+    | __WASI_RIGHT_FD_FDSTAT_SET_FLAGS
+    | __WASI_RIGHT_FD_FILESTAT_GET
+    | __WASI_RIGHT_POLL_FD_READWRITE
+    | __WASI_RIGHT_SOCK_SHUTDOWN;
 const STDIN_DEFAULT_RIGHTS: __wasi_rights_t = __WASI_RIGHT_FD_DATASYNC
     | __WASI_RIGHT_FD_READ
     | __WASI_RIGHT_FD_SYNC
@@ -57,10 +105,53 @@ pub unsafe fn get_wasi_state(ctx: &mut Ctx) -> &mut WasiState {
     &mut *(ctx.data as *mut WasiState)
 }
 
+/// Get the memory at `mem_index` and the WasiState from a Ctx, e.g. in a host
+/// function registered with
+/// [`generate_import_object_from_state_with_namespaces`].
+/// This function is unsafe because it must be called on a WASI Ctx
+///
+/// [`generate_import_object_from_state_with_namespaces`]: crate::generate_import_object_from_state_with_namespaces
+#[allow(clippy::mut_from_ref)]
+pub unsafe fn get_memory_and_wasi_state(
+    ctx: &mut Ctx,
+    mem_index: u32,
+) -> (&Memory, &mut WasiState) {
+    ctx.memory_and_data_mut(mem_index)
+}
+
 /// A completely aribtrary "big enough" number used as the upper limit for
 /// the number of symlinks that can be traversed when resolving a path
 pub const MAX_SYMLINKS: u32 = 128;
 
+/// Capabilities withheld from a preopened directory and from every fd opened
+/// through it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PreopenRestrictions {
+    /// Nothing under the directory can be created, modified, or removed.
+    pub read_only: bool,
+    /// No files, directories, or links can be created under the directory.
+    pub no_create: bool,
+    /// No files or directories can be removed from under the directory.
+    pub no_delete: bool,
+}
+
+impl PreopenRestrictions {
+    /// The rights left to fds under a directory with these restrictions.
+    pub fn allowed_rights(&self) -> __wasi_rights_t {
+        let mut rights = ALL_RIGHTS;
+        if self.read_only {
+            rights &= !(FILE_WRITE_RIGHTS | CREATE_RIGHTS | DELETE_RIGHTS | PATH_WRITE_RIGHTS);
+        }
+        if self.no_create {
+            rights &= !CREATE_RIGHTS;
+        }
+        if self.no_delete {
+            rights &= !DELETE_RIGHTS;
+        }
+        rights
+    }
+}
+
 /// A file that Wasi knows about that may or may not be open
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InodeVal {
@@ -154,6 +245,19 @@ impl Fd {
     pub const CREATE: u16 = 16;
 }
 
+/// An open file descriptor, see [`WasiFs::open_fds`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasiFdInfo {
+    pub fd: __wasi_fd_t,
+    pub inode: Inode,
+    /// Where the WASI module sees the file; `None` for stdio, sockets and
+    /// files that were removed while open
+    pub guest_path: Option<PathBuf>,
+    pub rights: __wasi_rights_t,
+    pub rights_inheriting: __wasi_rights_t,
+    pub is_preopened: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 /// Warning, modifying these fields directly may cause invariants to break and
 /// should be considered unsafe.  These fields may be made private in a future release
@@ -167,6 +271,16 @@ pub struct WasiFs {
     inode_counter: Cell<u64>,
     /// for fds still open after the file has been deleted
     pub orphan_fds: HashMap<Inode, InodeVal>,
+    /// files mapped with [`WasiFs::map_file`] that can't be opened for writing
+    read_only_inodes: HashSet<Inode>,
+    /// Resolve paths strictly: `..` may not leave the directory a path is
+    /// resolved from, absolute symlinks are rejected instead of followed,
+    /// and host entries swapped for symlinks after being looked up are
+    /// refused.
+    pub sandbox_strict: bool,
+    /// the connections of preopened sockets, to share with copies of the state
+    #[serde(skip)]
+    sockets: HashMap<Inode, TcpStream>,
 }
 
 impl WasiFs {
@@ -184,6 +298,9 @@ impl WasiFs {
             next_fd: Cell::new(3),
             inode_counter: Cell::new(1024),
             orphan_fds: HashMap::new(),
+            read_only_inodes: HashSet::new(),
+            sandbox_strict: false,
+            sockets: HashMap::new(),
         };
         wasi_fs.create_stdin();
         wasi_fs.create_stdout();
@@ -320,6 +437,262 @@ impl WasiFs {
         Ok(wasi_fs)
     }
 
+    /// Find the preopened directory the WASI module sees as `name`.
+    pub fn preopen_fd_by_name(&self, name: &str) -> Option<__wasi_fd_t> {
+        self.preopen_fds
+            .iter()
+            .cloned()
+            .find(|fd| self.inodes[self.fd_map[fd].inode].name == name)
+    }
+
+    /// Restrict what the WASI module can do through the preopened directory
+    /// `fd`; the restrictions are inherited by every fd opened through it.
+    pub fn restrict_preopen(
+        &mut self,
+        fd: __wasi_fd_t,
+        restrictions: PreopenRestrictions,
+    ) -> Result<(), __wasi_errno_t> {
+        if !self.preopen_fds.contains(&fd) {
+            return Err(__WASI_EBADF);
+        }
+        let allowed_rights = restrictions.allowed_rights();
+        let fd_entry = self.fd_map.get_mut(&fd).ok_or(__WASI_EBADF)?;
+        fd_entry.rights &= allowed_rights;
+        fd_entry.rights_inheriting &= allowed_rights;
+        Ok(())
+    }
+
+    /// Expose a single host file at `guest_path` without exposing the
+    /// directory containing it.
+    ///
+    /// The parent of `guest_path` becomes a preopened virtual directory that
+    /// only contains the files mapped into it; nothing can be created in or
+    /// removed from it.  Files mapped with `read_only` can't be opened for
+    /// writing.
+    pub fn map_file(
+        &mut self,
+        guest_path: &str,
+        host_file: &Path,
+        read_only: bool,
+    ) -> Result<(), String> {
+        let guest = Path::new(guest_path);
+        let file_name = guest
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| format!("Mapped file path \"{}\" does not name a file", guest_path))?;
+        let dir_name = match guest.parent() {
+            Some(dir) if dir != Path::new("") && dir != Path::new("/") => {
+                dir.to_string_lossy().into_owned()
+            }
+            _ => {
+                return Err(format!(
+                    "Mapped file path \"{}\" must be inside a directory",
+                    guest_path
+                ))
+            }
+        };
+
+        let metadata = host_file.metadata().map_err(|e| {
+            format!(
+                "Could not get metadata for file {:?}: {}",
+                host_file,
+                e.to_string()
+            )
+        })?;
+        if !metadata.is_file() {
+            return Err(format!("Mapped file {:?} is not a file", host_file));
+        }
+        // resolve host symlinks now, so that strict sandboxing doesn't reject the file
+        let host_file = host_file
+            .canonicalize()
+            .map_err(|e| format!("Could not resolve mapped file {:?}: {}", host_file, e))?;
+
+        let root_inode = self
+            .get_fd(VIRTUAL_ROOT_FD)
+            .map_err(|e| format!("Could not find the root fd: {}", e))?
+            .inode;
+        let existing_dir = match &self.inodes[root_inode].kind {
+            Kind::Root { entries } => entries.get(&dir_name).cloned(),
+            _ => unreachable!("The virtual root is not a root"),
+        };
+        let dir_inode = match existing_dir {
+            Some(inode) => match &self.inodes[inode].kind {
+                Kind::Root { entries } => {
+                    if entries.contains_key(&file_name) {
+                        return Err(format!("\"{}\" is mapped more than once", guest_path));
+                    }
+                    inode
+                }
+                _ => {
+                    return Err(format!(
+                        "Can't map files into \"{}\": it is a preopened host directory",
+                        dir_name
+                    ))
+                }
+            },
+            None => {
+                let inode = self.create_virtual_dir(dir_name.clone());
+                let fd = self
+                    .create_fd(ALL_RIGHTS, ALL_RIGHTS, 0, Fd::READ, inode)
+                    .map_err(|e| format!("Could not open fd for \"{}\": {}", dir_name, e))?;
+                if let Kind::Root { entries } = &mut self.inodes[root_inode].kind {
+                    entries.insert(dir_name, inode);
+                }
+                self.preopen_fds.push(fd);
+                inode
+            }
+        };
+
+        let kind = Kind::File {
+            handle: None,
+            path: host_file,
+            fd: None,
+        };
+        let file_inode = self
+            .create_inode(kind, false, file_name.clone())
+            .map_err(|e| {
+                format!(
+                    "Failed to create inode for mapped file: WASI error code: {}",
+                    e
+                )
+            })?;
+        if let Kind::Root { entries } = &mut self.inodes[dir_inode].kind {
+            entries.insert(file_name, file_inode);
+        }
+        if read_only {
+            self.read_only_inodes.insert(file_inode);
+        }
+
+        Ok(())
+    }
+
+    /// Give the WASI module a connected socket and return its fd.
+    ///
+    /// Preopened directories are found by the module by scanning fds until
+    /// the first one that isn't preopened, so sockets should be added after
+    /// every directory.
+    pub fn preopen_socket(&mut self, stream: TcpStream) -> Result<__wasi_fd_t, __wasi_errno_t> {
+        let connection = stream.try_clone().map_err(|_| __WASI_EIO)?;
+        let socket = WasiTcpStream::new(stream);
+        let name = match socket.peer_addr() {
+            Some(addr) => format!("socket:{}", addr),
+            None => "socket".to_string(),
+        };
+        let stat = __wasi_filestat_t {
+            st_filetype: __WASI_FILETYPE_SOCKET_STREAM,
+            st_ino: self.get_next_inode_index(),
+            ..__wasi_filestat_t::default()
+        };
+        let inode = self.inodes.insert(InodeVal {
+            stat,
+            is_preopened: false,
+            name: name.clone(),
+            kind: Kind::File {
+                handle: Some(Box::new(socket)),
+                path: PathBuf::from(name),
+                fd: None,
+            },
+        });
+        self.sockets.insert(inode, connection);
+        self.create_fd(SOCKET_RIGHTS, 0, 0, Fd::READ | Fd::WRITE, inode)
+    }
+
+    /// Whether the file at `inode` was mapped read-only with [`WasiFs::map_file`].
+    pub fn is_read_only(&self, inode: Inode) -> bool {
+        self.read_only_inodes.contains(&inode)
+    }
+
+    /// Every file descriptor the WASI module has open, ordered by fd.
+    pub fn open_fds(&self) -> Vec<WasiFdInfo> {
+        let guest_paths = self.guest_paths();
+        let mut fds = self
+            .fd_map
+            .iter()
+            .map(|(fd, fd_entry)| WasiFdInfo {
+                fd: *fd,
+                inode: fd_entry.inode,
+                guest_path: guest_paths.get(&fd_entry.inode).cloned(),
+                rights: fd_entry.rights,
+                rights_inheriting: fd_entry.rights_inheriting,
+                is_preopened: self.preopen_fds.contains(fd),
+            })
+            .collect::<Vec<_>>();
+        fds.sort_by_key(|info| info.fd);
+        fds
+    }
+
+    /// The paths of the files the WASI module has created or opened, as it
+    /// sees them.
+    ///
+    /// Directories are loaded lazily, so files the module never looked up
+    /// aren't listed.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = self
+            .guest_paths()
+            .into_iter()
+            .filter(|(inode, _)| match self.inodes[*inode].kind {
+                Kind::File { .. } | Kind::Buffer { .. } => true,
+                _ => false,
+            })
+            .map(|(_, path)| path)
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    /// Read the contents of the file the WASI module sees at `guest_path`.
+    ///
+    /// Open files and in-memory buffers are read through the `WasiFs`, so
+    /// this works for virtual files too.  Fd offsets are not affected.
+    pub fn read_file(&mut self, guest_path: &str) -> Result<Vec<u8>, WasiFsError> {
+        let inode = self
+            .get_inode_at_path(VIRTUAL_ROOT_FD, guest_path.trim_start_matches('/'), true)
+            .map_err(WasiFsError::from_wasi_err)?;
+        match &mut self.inodes[inode].kind {
+            Kind::File {
+                handle: Some(handle),
+                ..
+            } => {
+                let position = handle.seek(io::SeekFrom::Current(0))?;
+                handle.seek(io::SeekFrom::Start(0))?;
+                let mut contents = vec![];
+                let result = handle.read_to_end(&mut contents);
+                handle.seek(io::SeekFrom::Start(position))?;
+                result?;
+                Ok(contents)
+            }
+            Kind::File { path, .. } => Ok(fs::read(path)?),
+            Kind::Buffer { buffer } => Ok(buffer.clone()),
+            Kind::Dir { .. } | Kind::Root { .. } | Kind::Symlink { .. } => {
+                Err(WasiFsError::NotAFile)
+            }
+        }
+    }
+
+    /// The guest path of every inode reachable from the virtual root.
+    fn guest_paths(&self) -> HashMap<Inode, PathBuf> {
+        let mut paths = HashMap::new();
+        let root_inode = match self.fd_map.get(&VIRTUAL_ROOT_FD) {
+            Some(fd) => fd.inode,
+            None => return paths,
+        };
+        let mut to_visit = vec![(root_inode, PathBuf::from("/"))];
+        while let Some((inode, path)) = to_visit.pop() {
+            match &self.inodes[inode].kind {
+                Kind::Root { entries } | Kind::Dir { entries, .. } => {
+                    for (name, entry) in entries.iter() {
+                        if !paths.contains_key(entry) {
+                            to_visit.push((*entry, path.join(name)));
+                        }
+                    }
+                }
+                _ => (),
+            }
+            paths.insert(inode, path);
+        }
+        paths
+    }
+
     /// Get the `WasiFile` object at stdout
     pub fn stdout(&self) -> Result<&Option<Box<dyn WasiFile>>, WasiFsError> {
         self.std_dev_get(__WASI_STDOUT_FILENO)
@@ -592,10 +965,25 @@ impl WasiFs {
 
         let mut cur_inode = base_dir.inode;
         let n_components = path.components().count();
+        // how far below `base` the path currently is
+        let mut depth: usize = 0;
         // TODO: rights checks
         'path_iter: for (i, component) in path.components().enumerate() {
             // used to terminate symlink resolution properly
             let last_component = i + 1 == n_components;
+            if self.sandbox_strict {
+                match component {
+                    Component::ParentDir => {
+                        if depth == 0 {
+                            return Err(__WASI_ENOTCAPABLE);
+                        }
+                        depth -= 1;
+                    }
+                    Component::Normal(_) => depth += 1,
+                    Component::CurDir => (),
+                    Component::RootDir | Component::Prefix(_) => return Err(__WASI_ENOTCAPABLE),
+                }
+            }
             // for each component traverse file structure
             // loading inodes as necessary
             'symlink_resolution: while symlink_count < MAX_SYMLINKS {
@@ -659,6 +1047,8 @@ impl WasiFs {
 
                                 let (pre_open_dir_fd, relative_path) = if link_value.is_relative() {
                                     self.path_into_pre_open_and_relative_path(&file)?
+                                } else if self.sandbox_strict {
+                                    return Err(__WASI_ENOTCAPABLE);
                                 } else {
                                     unimplemented!("Absolute symlinks are not yet supported");
                                 };
@@ -753,11 +1143,47 @@ impl WasiFs {
                 }
                 break 'symlink_resolution;
             }
+            if self.sandbox_strict {
+                self.check_host_path(cur_inode)?;
+            }
         }
 
         Ok(cur_inode)
     }
 
+    /// Makes sure no directory on the way to a directory loaded earlier has
+    /// been replaced by a symlink on the host since.
+    fn check_host_path(&self, inode: Inode) -> Result<(), __wasi_errno_t> {
+        let inode_val = &self.inodes[inode];
+        if inode_val.is_preopened {
+            return Ok(());
+        }
+        if let Kind::Dir { path, .. } = &inode_val.kind {
+            #[cfg(unix)]
+            open_beneath(&self.host_roots(), path, libc::O_RDONLY | libc::O_DIRECTORY)
+                .map_err(|_| __WASI_ENOTCAPABLE)?;
+            #[cfg(not(unix))]
+            match path.symlink_metadata() {
+                Ok(metadata) if metadata.file_type().is_dir() => (),
+                _ => return Err(__WASI_ENOTCAPABLE),
+            }
+        }
+        Ok(())
+    }
+
+    /// The host directories of the preopened directories, which
+    /// [`open_beneath`] resolves paths from.
+    #[cfg(unix)]
+    pub(crate) fn host_roots(&self) -> Vec<PathBuf> {
+        self.preopen_fds
+            .iter()
+            .filter_map(|fd| match &self.inodes[self.fd_map[fd].inode].kind {
+                Kind::Dir { path, .. } => Some(path.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Splits a path into the first preopened directory that is a parent of it,
     /// if such a preopened directory exists, and the rest of the path.
     ///
@@ -924,6 +1350,10 @@ impl WasiFs {
 
         Ok(__wasi_fdstat_t {
             fs_filetype: match self.inodes[fd.inode].kind {
+                Kind::File {
+                    handle: Some(ref handle),
+                    ..
+                } if handle.is_socket() => __WASI_FILETYPE_SOCKET_STREAM,
                 Kind::File { .. } => __WASI_FILETYPE_REGULAR_FILE,
                 Kind::Dir { .. } => __WASI_FILETYPE_DIRECTORY,
                 Kind::Symlink { .. } => __WASI_FILETYPE_SYMBOLIC_LINK,
@@ -1066,6 +1496,12 @@ impl WasiFs {
     }
 
     fn create_virtual_root(&mut self) -> Inode {
+        self.create_virtual_dir("/".to_string())
+    }
+
+    /// Creates an immutable directory that only contains the entries added
+    /// to it by the host
+    fn create_virtual_dir(&mut self, name: String) -> Inode {
         let stat = __wasi_filestat_t {
             st_filetype: __WASI_FILETYPE_DIRECTORY,
             st_ino: self.get_next_inode_index(),
@@ -1078,7 +1514,7 @@ impl WasiFs {
         self.inodes.insert(InodeVal {
             stat,
             is_preopened: true,
-            name: "/".to_string(),
+            name,
             kind: root_kind,
         })
     }
@@ -1286,6 +1722,25 @@ pub struct WasiState {
     pub fs: WasiFs,
     pub args: Vec<Vec<u8>>,
     pub envs: Vec<Vec<u8>>,
+    /// The clock read by `clock_time_get`
+    pub clock: WasiClock,
+    /// The source of the bytes returned by `random_get`
+    pub random: WasiRandom,
+    /// Called around every syscall, see [`hook`]
+    #[serde(skip)]
+    pub hook: Option<Arc<dyn WasiSyscallHook>>,
+    /// Whether the module is run as a command or a reactor, see [`reactor`]
+    ///
+    /// [`reactor`]: crate::reactor
+    pub mode: WasiMode,
+    /// The code the module passed to `proc_exit`, once it has exited
+    pub exit_code: Option<__wasi_exitcode_t>,
+    /// What syscalls do when asked for something that isn't implemented
+    #[serde(skip)]
+    pub unimplemented_policy: UnimplementedPolicy,
+    /// Whether syscall results are recorded or replayed, see [`replay`]
+    #[serde(skip)]
+    pub trace_mode: Option<WasiTraceMode>,
 }
 
 impl WasiState {
@@ -1325,6 +1780,71 @@ impl WasiState {
     }
 }
 
+/// A [`WasiState`] every instance created from an import object gets a copy
+/// of.
+///
+/// Copies go through serialization, so what can't be serialized, the syscall
+/// hook, the unimplemented-syscall policy, the trace mode and socket
+/// connections, is carried next to it.  Every copy shares the connections of
+/// the sockets.
+///
+/// Creating the template checks that the serialized state can be read back,
+/// so making copies can't fail.
+pub(crate) struct WasiStateTemplate {
+    bytes: Vec<u8>,
+    hook: Option<Arc<dyn WasiSyscallHook>>,
+    unimplemented_policy: UnimplementedPolicy,
+    trace_mode: Option<WasiTraceMode>,
+    sockets: Vec<(Inode, TcpStream)>,
+}
+
+impl WasiStateTemplate {
+    pub(crate) fn new(wasi_state: &WasiState) -> Result<Self, WasiStateCreationError> {
+        let bytes = bincode::serialize(wasi_state)
+            .map_err(|e| WasiStateCreationError::WasiStateCopyError(e.to_string()))?;
+        bincode::deserialize::<WasiState>(&bytes)
+            .map_err(|e| WasiStateCreationError::WasiStateCopyError(e.to_string()))?;
+
+        Ok(Self {
+            bytes,
+            hook: wasi_state.hook.clone(),
+            unimplemented_policy: wasi_state.unimplemented_policy.clone(),
+            trace_mode: wasi_state.trace_mode.clone(),
+            sockets: wasi_state
+                .fs
+                .sockets
+                .iter()
+                .filter_map(|(inode, stream)| Some((*inode, stream.try_clone().ok()?)))
+                .collect(),
+        })
+    }
+
+    /// Make a new copy of the state.
+    pub(crate) fn instantiate(&self) -> WasiState {
+        let mut wasi_state =
+            WasiState::unfreeze(&self.bytes).expect("checked by `WasiStateTemplate::new`");
+        wasi_state.hook = self.hook.clone();
+        wasi_state.unimplemented_policy = self.unimplemented_policy.clone();
+        wasi_state.trace_mode = self.trace_mode.clone();
+        for (inode, stream) in self.sockets.iter() {
+            let (stream, connection) = match (stream.try_clone(), stream.try_clone()) {
+                (Ok(stream), Ok(connection)) => (stream, connection),
+                _ => continue,
+            };
+            if let Some(Kind::File { handle, .. }) = wasi_state
+                .fs
+                .inodes
+                .get_mut(*inode)
+                .map(|inode| &mut inode.kind)
+            {
+                *handle = Some(Box::new(WasiTcpStream::new(stream)));
+                wasi_state.fs.sockets.insert(*inode, connection);
+            }
+        }
+        wasi_state
+    }
+}
+
 pub fn host_file_type_to_wasi_file_type(file_type: fs::FileType) -> __wasi_filetype_t {
     // TODO: handle other file types
     if file_type.is_dir() {
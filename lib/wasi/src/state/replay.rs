@@ -0,0 +1,279 @@
+//! Recording the results of the syscalls a WASI module makes, and replaying
+//! them to re-run the module exactly as it ran.
+//!
+//! A [`WasiRecorder`] set with [`WasiStateBuilder::record`] runs every
+//! syscall and records in a [`WasiTrace`] what it returned and which bytes
+//! of the module's memory it wrote.  A [`WasiReplayer`] set with
+//! [`WasiStateBuilder::replay`] doesn't run the syscalls: it writes the
+//! recorded bytes to the memory and returns the recorded result instead, so
+//! a deterministically compiled module goes through the same execution as
+//! when it was recorded, whatever the clocks, the randomness, the files or
+//! the sockets return now.
+//!
+//! Syscalls are replayed in the order they were recorded.  If the module
+//! makes another syscall than the one recorded, the replay stops with a
+//! [`WasiReplayDiverged`] error.
+//!
+//! Only the WASI syscalls are recorded: the results of other host functions
+//! the module imports, like the host namespaces of
+//! [`generate_import_object_from_state_with_namespaces`], aren't in the
+//! trace, and they run again when replaying.
+//!
+//! Finding the bytes a syscall wrote compares the whole memory before and
+//! after it, which makes recording slow for modules with large memories.
+//!
+//! [`WasiStateBuilder::record`]: super::WasiStateBuilder::record
+//! [`WasiStateBuilder::replay`]: super::WasiStateBuilder::replay
+//! [`generate_import_object_from_state_with_namespaces`]: crate::generate_import_object_from_state_with_namespaces
+
+use crate::state::{get_wasi_state, hook::SyscallResult, hook::WasiSyscallOutcome};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::Cell,
+    fmt, panic,
+    sync::{Arc, Mutex},
+};
+use wasmer_runtime_core::{error::RuntimeError, memory::Memory, vm::Ctx};
+
+/// Whether the syscalls of a WASI module are recorded or replayed.
+#[derive(Debug, Clone)]
+pub enum WasiTraceMode {
+    /// Run the syscalls and record their results
+    Record(Arc<WasiRecorder>),
+    /// Replay the recorded results instead of running the syscalls
+    Replay(Arc<WasiReplayer>),
+}
+
+/// The results of the syscalls a WASI module made, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasiTrace {
+    syscalls: Vec<RecordedSyscall>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct RecordedSyscall {
+    name: String,
+    outcome: WasiSyscallOutcome,
+    /// The bytes the syscall wrote to memory, by offset
+    writes: Vec<(u32, Vec<u8>)>,
+}
+
+impl WasiTrace {
+    /// The number of syscalls recorded.
+    pub fn len(&self) -> usize {
+        self.syscalls.len()
+    }
+
+    /// Whether no syscall was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.syscalls.is_empty()
+    }
+
+    /// Deserializes a trace from bytes returned by [`WasiTrace::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bincode::deserialize(bytes).ok()
+    }
+
+    /// Converts self into a vector of bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+}
+
+/// Records the results of the syscalls of a WASI module in a [`WasiTrace`].
+#[derive(Debug, Default)]
+pub struct WasiRecorder {
+    trace: Mutex<WasiTrace>,
+}
+
+impl WasiRecorder {
+    /// A recorder that didn't record any syscall yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The syscalls recorded so far.
+    pub fn trace(&self) -> WasiTrace {
+        self.trace.lock().unwrap().clone()
+    }
+
+    fn record<R, F>(&self, ctx: &mut Ctx, name: &str, syscall: F) -> R
+    where
+        R: SyscallResult,
+        F: FnOnce(&mut Ctx) -> R,
+    {
+        let before = if ctx.memory_count() > 0 {
+            snapshot(ctx.memory(0))
+        } else {
+            vec![]
+        };
+        let result = syscall(ctx);
+        let writes = if ctx.memory_count() > 0 {
+            changes(&before, ctx.memory(0))
+        } else {
+            vec![]
+        };
+
+        self.trace.lock().unwrap().syscalls.push(RecordedSyscall {
+            name: name.to_string(),
+            outcome: result.outcome(),
+            writes,
+        });
+        result
+    }
+}
+
+/// Replays the results of the syscalls recorded in a [`WasiTrace`].
+#[derive(Debug)]
+pub struct WasiReplayer {
+    trace: WasiTrace,
+    next: Mutex<usize>,
+}
+
+impl WasiReplayer {
+    /// A replayer of the syscalls of `trace`, from the first one.
+    pub fn new(trace: WasiTrace) -> Self {
+        Self {
+            trace,
+            next: Mutex::new(0),
+        }
+    }
+
+    /// Whether every recorded syscall was replayed.
+    pub fn is_finished(&self) -> bool {
+        *self.next.lock().unwrap() == self.trace.len()
+    }
+
+    fn replay<R: SyscallResult>(&self, ctx: &mut Ctx, name: &str) -> R {
+        let index = {
+            let mut next = self.next.lock().unwrap();
+            let index = *next;
+            *next += 1;
+            index
+        };
+        let recorded = match self.trace.syscalls.get(index) {
+            Some(recorded) if recorded.name == name => recorded,
+            recorded => panic::resume_unwind(Box::new(WasiReplayDiverged {
+                index,
+                expected: recorded.map(|recorded| recorded.name.clone()),
+                found: name.to_string(),
+            })),
+        };
+
+        if !recorded.writes.is_empty() {
+            let view = ctx.memory(0).view::<u8>();
+            for (offset, bytes) in recorded.writes.iter() {
+                let start = *offset as usize;
+                for (cell, byte) in view[start..start + bytes.len()].iter().zip(bytes) {
+                    cell.set(*byte);
+                }
+            }
+        }
+        if let WasiSyscallOutcome::Exited(code) = recorded.outcome {
+            unsafe { get_wasi_state(ctx) }.exit_code = Some(code);
+        }
+        R::from_outcome(recorded.outcome)
+    }
+}
+
+/// The error a replayed module stops with when it makes another syscall
+/// than the one recorded.
+///
+/// This is returned in the Box<dyn Any> RuntimeError::Error variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasiReplayDiverged {
+    /// The position of the syscall in the trace
+    pub index: usize,
+    /// The syscall recorded at that position, if the trace is that long
+    pub expected: Option<String>,
+    /// The syscall the module made
+    pub found: String,
+}
+
+impl WasiReplayDiverged {
+    /// The `WasiReplayDiverged` that stopped a replay, if that's what `error` is.
+    pub fn from_runtime_error(error: &RuntimeError) -> Option<&WasiReplayDiverged> {
+        match error {
+            RuntimeError::Error { data } => data.downcast_ref::<WasiReplayDiverged>(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WasiReplayDiverged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expected {
+            Some(expected) => write!(
+                f,
+                "the replay diverged at syscall #{}: the module called `{}` instead of `{}`",
+                self.index, self.found, expected
+            ),
+            None => write!(
+                f,
+                "the replay diverged at syscall #{}: the module called `{}` after the end of the trace",
+                self.index, self.found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WasiReplayDiverged {}
+
+/// Run `syscall`, or replay its result, as `mode` says.
+pub(crate) fn call_with_trace<R, F>(
+    mode: &WasiTraceMode,
+    ctx: &mut Ctx,
+    name: &str,
+    syscall: F,
+) -> R
+where
+    R: SyscallResult,
+    F: FnOnce(&mut Ctx) -> R,
+{
+    match mode {
+        WasiTraceMode::Record(recorder) => recorder.record(ctx, name, syscall),
+        WasiTraceMode::Replay(replayer) => replayer.replay(ctx, name),
+    }
+}
+
+fn snapshot(memory: &Memory) -> Vec<u8> {
+    memory.view::<u8>().iter().map(Cell::get).collect()
+}
+
+/// The runs of bytes of `memory` that differ from `before`.
+fn changes(before: &[u8], memory: &Memory) -> Vec<(u32, Vec<u8>)> {
+    let mut writes: Vec<(u32, Vec<u8>)> = vec![];
+    let mut run: Option<(u32, Vec<u8>)> = None;
+    for (offset, cell) in memory.view::<u8>().iter().enumerate() {
+        let byte = cell.get();
+        if before.get(offset) != Some(&byte) {
+            let (_, bytes) = run.get_or_insert_with(|| (offset as u32, vec![]));
+            bytes.push(byte);
+        } else if let Some(run) = run.take() {
+            writes.push(run);
+        }
+    }
+    writes.extend(run);
+    writes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmer_runtime_core::{types::MemoryDescriptor, units::Pages};
+
+    #[test]
+    fn memory_changes() {
+        let memory = Memory::new(MemoryDescriptor::new(Pages(1), None, false).unwrap()).unwrap();
+        let before = snapshot(&memory);
+        let view = memory.view::<u8>();
+        view[3].set(1);
+        view[4].set(2);
+        view[10].set(3);
+        view[65_535].set(4);
+        assert_eq!(
+            changes(&before, &memory),
+            vec![(3, vec![1, 2]), (10, vec![3]), (65_535, vec![4])]
+        );
+    }
+}
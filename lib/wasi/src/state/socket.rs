@@ -0,0 +1,149 @@
+//! Connected sockets handed to the guest by the host.
+//!
+//! WASI has no way for a guest to open a socket itself, so networking is a
+//! capability: the host connects a socket (or accepts one) and preopens it
+//! with [`WasiFs::preopen_socket`].  The guest then uses it through
+//! `sock_recv`, `sock_send` and `sock_shutdown`, or `fd_read` and `fd_write`.
+//!
+//! [`WasiFs::preopen_socket`]: super::WasiFs::preopen_socket
+
+use crate::state::{WasiFile, WasiFsError};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Read, Seek, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+};
+
+/// A connected TCP stream that implements `WasiFile`.
+///
+/// Connections can't be serialized: a deserialized `WasiTcpStream` keeps the
+/// address of its peer but is no longer connected.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WasiTcpStream {
+    #[serde(skip)]
+    inner: Option<TcpStream>,
+    peer_addr: Option<SocketAddr>,
+}
+
+impl WasiTcpStream {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            peer_addr: stream.peer_addr().ok(),
+            inner: Some(stream),
+        }
+    }
+
+    /// The address this stream was connected to.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    fn stream(&self) -> io::Result<&TcpStream> {
+        self.inner
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "socket was not restored"))
+    }
+}
+
+impl Read for WasiTcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream()?.read(buf)
+    }
+}
+
+impl Write for WasiTcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream()?.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream()?.flush()
+    }
+}
+
+impl Seek for WasiTcpStream {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek a socket",
+        ))
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for WasiTcpStream {
+    fn last_accessed(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn last_modified(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn created_time(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        let stream = self.stream()?;
+        stream.set_nonblocking(true)?;
+        let mut buf = [0; 4096];
+        let result = stream.peek(&mut buf);
+        stream.set_nonblocking(false)?;
+        match result {
+            Ok(n) => Ok(n),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[cfg(unix)]
+    fn get_raw_fd(&self) -> Option<i32> {
+        use std::os::unix::io::AsRawFd;
+        self.inner.as_ref().map(|stream| stream.as_raw_fd())
+    }
+
+    fn is_socket(&self) -> bool {
+        true
+    }
+    fn sock_peek(&mut self, buf: &mut [u8]) -> Result<usize, WasiFsError> {
+        Ok(self.stream()?.peek(buf)?)
+    }
+    fn sock_shutdown(&mut self, how: Shutdown) -> Result<(), WasiFsError> {
+        Ok(self.stream()?.shutdown(how)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn tcp_stream_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        let mut socket = WasiTcpStream::new(client);
+        assert_eq!(socket.peer_addr(), Some(listener.local_addr().unwrap()));
+
+        server.write_all(b"ping").unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(socket.sock_peek(&mut buf).unwrap(), 4);
+        assert_eq!(socket.bytes_available().unwrap(), 4);
+        socket.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+
+        socket.write_all(b"pong").unwrap();
+        socket.sock_shutdown(Shutdown::Write).unwrap();
+        let mut reply = vec![];
+        server.read_to_end(&mut reply).unwrap();
+        assert_eq!(reply, b"pong");
+    }
+}
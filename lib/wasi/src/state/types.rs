@@ -1,4 +1,5 @@
 /// types for use in the WASI filesystem
+use crate::state::checkpoint;
 use crate::syscalls::types::*;
 use serde::{de, Deserialize, Serialize};
 #[cfg(unix)]
@@ -7,7 +8,7 @@ use std::{
     fs,
     io::{self, Read, Seek, Write},
     path::PathBuf,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 use wasmer_runtime_core::debug;
 
@@ -61,6 +62,8 @@ pub enum WasiFsError {
     WouldBlock,
     /// A call to write returned 0
     WriteZero,
+    /// The operation is only supported on sockets
+    NotASocket,
     /// A WASI error without an external name.  If you encounter this it means
     /// that there's probably a bug on our side (maybe as simple as forgetting to wrap
     /// this error, but perhaps something broke)
@@ -89,6 +92,7 @@ impl WasiFsError {
             __WASI_EPROTO => WasiFsError::UnexpectedEof,
             __WASI_EAGAIN => WasiFsError::WouldBlock,
             __WASI_ENOSPC => WasiFsError::WriteZero,
+            __WASI_ENOTSOCK => WasiFsError::NotASocket,
             _ => WasiFsError::UnknownError(err),
         }
     }
@@ -117,6 +121,7 @@ impl WasiFsError {
             WasiFsError::UnexpectedEof => __WASI_EPROTO,
             WasiFsError::WouldBlock => __WASI_EAGAIN,
             WasiFsError::WriteZero => __WASI_ENOSPC,
+            WasiFsError::NotASocket => __WASI_ENOTSOCK,
             WasiFsError::UnknownError(ec) => ec,
         }
     }
@@ -184,6 +189,22 @@ pub trait WasiFile: std::fmt::Debug + Write + Read + Seek {
     fn get_raw_fd(&self) -> Option<i32> {
         None
     }
+
+    /// Whether this file is a socket that `sock_recv`, `sock_send` and
+    /// `sock_shutdown` can be used with.  Default returns `false`
+    fn is_socket(&self) -> bool {
+        false
+    }
+
+    /// Read data into `buf` without removing it from the socket's queue
+    fn sock_peek(&mut self, _buf: &mut [u8]) -> Result<usize, WasiFsError> {
+        Err(WasiFsError::NotASocket)
+    }
+
+    /// Shut down the receiving and/or sending side of the socket
+    fn sock_shutdown(&mut self, _how: std::net::Shutdown) -> Result<(), WasiFsError> {
+        Err(WasiFsError::NotASocket)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -300,46 +321,102 @@ impl PollEventBuilder {
     }
 }
 
+/// Poll `selfs` for `events`, waiting at most `timeout` for one of them to
+/// become ready.  `None` waits until something is ready.
+///
+/// Files without a host fd, such as captured stdio, can't be waited on and
+/// are always reported ready for what was requested of them.
+///
+/// Returns the number of files with events in `seen_events`.
 #[cfg(unix)]
 pub(crate) fn poll(
     selfs: &[&dyn WasiFile],
     events: &[PollEventSet],
     seen_events: &mut [PollEventSet],
+    timeout: Option<Duration>,
 ) -> Result<u32, WasiFsError> {
     if !(selfs.len() == events.len() && events.len() == seen_events.len()) {
         return Err(WasiFsError::InvalidInput);
     }
-    let mut fds = selfs
-        .iter()
-        .enumerate()
-        .filter_map(|(i, s)| s.get_raw_fd().map(|rfd| (i, rfd)))
-        .map(|(i, host_fd)| libc::pollfd {
-            fd: host_fd,
-            events: poll_event_set_to_platform_poll_events(events[i]),
-            revents: 0,
-        })
-        .collect::<Vec<_>>();
-    let result = unsafe { libc::poll(fds.as_mut_ptr(), selfs.len() as _, 1) };
+    let mut ready = 0;
+    let mut host_fds = vec![];
+    let mut indices = vec![];
+    for (i, s) in selfs.iter().enumerate() {
+        seen_events[i] = 0;
+        match s.get_raw_fd() {
+            Some(host_fd) => {
+                host_fds.push(libc::pollfd {
+                    fd: host_fd,
+                    events: poll_event_set_to_platform_poll_events(events[i]),
+                    revents: 0,
+                });
+                indices.push(i);
+            }
+            None => {
+                seen_events[i] = events[i] & (PollEvent::PollIn as i16 | PollEvent::PollOut as i16);
+                if seen_events[i] != 0 {
+                    ready += 1;
+                }
+            }
+        }
+    }
+    let timeout_ms = if ready > 0 {
+        0
+    } else {
+        match timeout {
+            Some(duration) => {
+                let ms = (duration.as_nanos() + 999_999) / 1_000_000;
+                ms.min(libc::c_int::max_value() as u128) as libc::c_int
+            }
+            None => -1,
+        }
+    };
+    let result = unsafe {
+        libc::poll(
+            host_fds.as_mut_ptr(),
+            host_fds.len() as libc::nfds_t,
+            timeout_ms,
+        )
+    };
 
     if result < 0 {
-        // TODO: check errno and return value
-        return Err(WasiFsError::IOError);
+        return match io::Error::last_os_error().kind() {
+            // treat a signal like a spurious wakeup, callers poll again if
+            // nothing is ready
+            io::ErrorKind::Interrupted => Ok(ready),
+            _ => Err(WasiFsError::IOError),
+        };
     }
     // convert result and write back values
-    for (i, fd) in fds.into_iter().enumerate() {
+    for (i, fd) in indices.into_iter().zip(host_fds.into_iter()) {
         seen_events[i] = platform_poll_events_to_pollevent_set(fd.revents);
     }
-    // unwrap is safe because we check for negative values above
-    Ok(result.try_into().unwrap())
+    // the cast is safe because we check for negative values above
+    Ok(ready + result as u32)
 }
 
+/// Without host support for polling every file is reported ready for what
+/// was requested of it; with no files this sleeps for `timeout`.
 #[cfg(not(unix))]
 pub(crate) fn poll(
-    _selfs: &[&dyn WasiFile],
-    _events: &[PollEventSet],
-    _seen_events: &mut [PollEventSet],
-) -> Result<(), WasiFsError> {
-    unimplemented!("HostFile::poll in WasiFile is not implemented for non-Unix-like targets yet");
+    selfs: &[&dyn WasiFile],
+    events: &[PollEventSet],
+    seen_events: &mut [PollEventSet],
+    timeout: Option<Duration>,
+) -> Result<u32, WasiFsError> {
+    if !(selfs.len() == events.len() && events.len() == seen_events.len()) {
+        return Err(WasiFsError::InvalidInput);
+    }
+    if selfs.is_empty() {
+        if let Some(duration) = timeout {
+            std::thread::sleep(duration);
+        }
+        return Ok(0);
+    }
+    for (seen, requested) in seen_events.iter_mut().zip(events.iter()) {
+        *seen = requested & (PollEvent::PollIn as i16 | PollEvent::PollOut as i16);
+    }
+    Ok(selfs.len() as u32)
 }
 
 pub trait WasiPath {}
@@ -384,17 +461,7 @@ impl<'de> Deserialize<'de> for HostFile {
                 let flags = seq
                     .next_element()?
                     .ok_or_else(|| de::Error::invalid_length(1, &self))?;
-                let inner = std::fs::OpenOptions::new()
-                    .read(flags & HostFile::READ != 0)
-                    .write(flags & HostFile::WRITE != 0)
-                    .append(flags & HostFile::APPEND != 0)
-                    .open(&host_path)
-                    .map_err(|_| de::Error::custom("Could not open file on this system"))?;
-                Ok(HostFile {
-                    inner,
-                    host_path,
-                    flags,
-                })
+                HostFile::reopen(host_path, flags)
             }
 
             fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
@@ -421,17 +488,7 @@ impl<'de> Deserialize<'de> for HostFile {
                 }
                 let host_path = host_path.ok_or_else(|| de::Error::missing_field("host_path"))?;
                 let flags = flags.ok_or_else(|| de::Error::missing_field("flags"))?;
-                let inner = std::fs::OpenOptions::new()
-                    .read(flags & HostFile::READ != 0)
-                    .write(flags & HostFile::WRITE != 0)
-                    .append(flags & HostFile::APPEND != 0)
-                    .open(&host_path)
-                    .map_err(|_| de::Error::custom("Could not open file on this system"))?;
-                Ok(HostFile {
-                    inner,
-                    host_path,
-                    flags,
-                })
+                HostFile::reopen(host_path, flags)
             }
         }
 
@@ -467,6 +524,26 @@ impl HostFile {
     pub fn metadata(&self) -> fs::Metadata {
         self.inner.metadata().unwrap()
     }
+
+    /// Open a deserialized `HostFile` again, at the path it's remapped to if
+    /// a checkpoint is being restored.
+    fn reopen<E: de::Error>(host_path: PathBuf, flags: u16) -> Result<HostFile, E> {
+        let host_path = checkpoint::remap_host_path(host_path);
+        let inner = fs::OpenOptions::new()
+            .read(flags & HostFile::READ != 0)
+            .write(flags & HostFile::WRITE != 0)
+            .append(flags & HostFile::APPEND != 0)
+            .open(&host_path)
+            .map_err(|_| {
+                checkpoint::host_file_unavailable(&host_path);
+                de::Error::custom("Could not open file on this system")
+            })?;
+        Ok(HostFile {
+            inner,
+            host_path,
+            flags,
+        })
+    }
 }
 
 impl Read for HostFile {
@@ -919,3 +996,48 @@ trait WasiFdBacking: std::fmt::Debug {
     fn get_name(&self) -> &str;
 }
 */
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::os::unix::io::FromRawFd;
+    use std::time::Instant;
+
+    #[test]
+    fn poll_pipe_readiness() {
+        let mut pipe_fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(pipe_fds.as_mut_ptr()) }, 0);
+        let read_end = unsafe { fs::File::from_raw_fd(pipe_fds[0]) };
+        let mut write_end = unsafe { fs::File::from_raw_fd(pipe_fds[1]) };
+        let reader = HostFile::new(read_end, PathBuf::from("pipe"), true, false, false);
+        let files: [&dyn WasiFile; 1] = [&reader];
+        let pollin = PollEventBuilder::new().add(PollEvent::PollIn).build();
+        let mut seen = [0];
+
+        let start = Instant::now();
+        let ready = poll(
+            &files,
+            &[pollin],
+            &mut seen,
+            Some(Duration::from_millis(20)),
+        );
+        assert_eq!(ready, Ok(0));
+        assert_eq!(seen, [0]);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        write_end.write_all(b"abc").unwrap();
+        let ready = poll(&files, &[pollin], &mut seen, None);
+        assert_eq!(ready, Ok(1));
+        assert_eq!(seen, [pollin]);
+        assert_eq!(reader.bytes_available(), Ok(3));
+
+        drop(write_end);
+        let mut buf = [0; 3];
+        (&reader.inner).read_exact(&mut buf).unwrap();
+        poll(&files, &[pollin], &mut seen, None).unwrap();
+        assert!(iterate_poll_events(seen[0]).any(|e| match e {
+            PollEvent::PollHangUp => true,
+            _ => false,
+        }));
+    }
+}
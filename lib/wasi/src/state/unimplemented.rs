@@ -0,0 +1,106 @@
+//! What syscalls do when the module asks for something Wasmer doesn't
+//! implement, e.g. `fd_read` on a symlink or `proc_raise`.
+//!
+//! The [`UnimplementedPolicy`] is chosen with
+//! [`WasiStateBuilder::unimplemented_policy`].
+//!
+//! [`WasiStateBuilder::unimplemented_policy`]: super::WasiStateBuilder::unimplemented_policy
+
+use crate::syscalls::types::*;
+use std::{fmt, panic, sync::Arc};
+use wasmer_runtime_core::debug;
+
+/// Functionality a syscall was asked for but doesn't implement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasiUnimplemented {
+    /// The name of the syscall, e.g. `fd_read`
+    pub syscall: &'static str,
+    /// What isn't implemented, e.g. `symlinks`
+    pub feature: &'static str,
+}
+
+impl fmt::Display for WasiUnimplemented {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "wasi::{} is not implemented for {}",
+            self.syscall, self.feature
+        )
+    }
+}
+
+impl std::error::Error for WasiUnimplemented {}
+
+/// What a syscall does when it's asked for functionality that isn't
+/// implemented.
+#[derive(Clone)]
+pub enum UnimplementedPolicy {
+    /// Return `ENOSYS` to the module.
+    Enosys,
+    /// Trap.  The call into the module fails with a `RuntimeError::Error`
+    /// holding the [`WasiUnimplemented`].
+    Trap,
+    /// Return the errno the handler returns to the module.
+    Fallback(Arc<dyn Fn(&WasiUnimplemented) -> __wasi_errno_t + Send + Sync>),
+}
+
+impl Default for UnimplementedPolicy {
+    fn default() -> Self {
+        UnimplementedPolicy::Enosys
+    }
+}
+
+impl fmt::Debug for UnimplementedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnimplementedPolicy::Enosys => write!(f, "Enosys"),
+            UnimplementedPolicy::Trap => write!(f, "Trap"),
+            UnimplementedPolicy::Fallback(_) => write!(f, "Fallback"),
+        }
+    }
+}
+
+impl UnimplementedPolicy {
+    /// The errno `syscall` returns when it's asked for `feature`.
+    pub(crate) fn handle(&self, syscall: &'static str, feature: &'static str) -> __wasi_errno_t {
+        let unimplemented = WasiUnimplemented { syscall, feature };
+        debug!("{}", unimplemented);
+        match self {
+            UnimplementedPolicy::Enosys => __WASI_ENOSYS,
+            // `resume_unwind` skips the panic hook; the unwind is caught at
+            // the host function boundary and becomes a trap
+            UnimplementedPolicy::Trap => panic::resume_unwind(Box::new(unimplemented)),
+            UnimplementedPolicy::Fallback(handler) => handler(&unimplemented),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unimplemented_policies() {
+        assert_eq!(
+            UnimplementedPolicy::Enosys.handle("proc_raise", "signals"),
+            __WASI_ENOSYS
+        );
+
+        let fallback = UnimplementedPolicy::Fallback(Arc::new(|unimplemented| {
+            assert_eq!(unimplemented.syscall, "fd_read");
+            assert_eq!(unimplemented.feature, "symlinks");
+            __WASI_ENOTSUP
+        }));
+        assert_eq!(fallback.handle("fd_read", "symlinks"), __WASI_ENOTSUP);
+
+        let trap = panic::catch_unwind(|| UnimplementedPolicy::Trap.handle("fd_read", "symlinks"))
+            .unwrap_err();
+        assert_eq!(
+            trap.downcast_ref::<WasiUnimplemented>(),
+            Some(&WasiUnimplemented {
+                syscall: "fd_read",
+                feature: "symlinks",
+            })
+        );
+    }
+}
@@ -12,15 +12,17 @@ use crate::{
     ptr::{Array, WasmPtr},
     state::{
         self, host_file_type_to_wasi_file_type, iterate_poll_events, poll, Fd, HostFile, Inode,
-        InodeVal, Kind, PollEvent, PollEventBuilder, WasiFile, WasiFsError, WasiState,
-        MAX_SYMLINKS,
+        InodeVal, Kind, PollEvent, PollEventBuilder, WasiClock, WasiFile, WasiFsError, WasiState,
+        FILE_WRITE_RIGHTS, MAX_SYMLINKS,
     },
-    ExitCode,
+    WasiExit,
 };
 use std::borrow::Borrow;
 use std::cell::Cell;
 use std::convert::{Infallible, TryInto};
 use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use wasmer_runtime_core::{debug, memory::Memory, vm::Ctx};
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
@@ -201,9 +203,16 @@ pub fn clock_res_get(
     resolution: WasmPtr<__wasi_timestamp_t>,
 ) -> __wasi_errno_t {
     debug!("wasi::clock_res_get");
-    let memory = ctx.memory(0);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
 
     let out_addr = wasi_try!(resolution.deref(memory));
+    if let Some(virtual_resolution) = state.clock.resolution() {
+        if !state::is_valid_clock_id(clock_id) {
+            return __WASI_EINVAL;
+        }
+        out_addr.set(virtual_resolution);
+        return __WASI_ESUCCESS;
+    }
     platform_clock_res_get(clock_id, out_addr)
 }
 
@@ -227,10 +236,20 @@ pub fn clock_time_get(
         "wasi::clock_time_get clock_id: {}, precision: {}",
         clock_id, precision
     );
-    let memory = ctx.memory(0);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
 
     let out_addr = wasi_try!(time.deref(memory));
-    let result = platform_clock_time_get(clock_id, precision, out_addr);
+    let result = if !state::is_valid_clock_id(clock_id) {
+        __WASI_EINVAL
+    } else if let Some(virtual_time) = state.clock.read() {
+        out_addr.set(virtual_time);
+        __WASI_ESUCCESS
+    } else {
+        platform_clock_time_get(clock_id, precision, out_addr)
+    };
+    if result == __WASI_ENOSYS {
+        wasi_unimplemented!(state, "clock_time_get", "this clock on this platform");
+    }
     debug!(
         "time: {} => {}",
         wasi_try!(time.deref(memory)).get(),
@@ -690,7 +709,7 @@ pub fn fd_pread(
                     }
                 }
                 Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_pread"),
+                Kind::Symlink { .. } => wasi_unimplemented!(state, "fd_pread", "symlinks"),
                 Kind::Buffer { buffer } => {
                     wasi_try!(read_bytes(&buffer[(offset as usize)..], memory, iov_cells))
                 }
@@ -843,7 +862,7 @@ pub fn fd_pwrite(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_pwrite"),
+                Kind::Symlink { .. } => wasi_unimplemented!(state, "fd_pwrite", "symlinks"),
                 Kind::Buffer { buffer } => wasi_try!(write_bytes(
                     &mut buffer[(offset as usize)..],
                     memory,
@@ -920,7 +939,7 @@ pub fn fd_read(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_read"),
+                Kind::Symlink { .. } => wasi_unimplemented!(state, "fd_read", "symlinks"),
                 Kind::Buffer { buffer } => {
                     wasi_try!(read_bytes(&buffer[offset..], memory, iovs_arr_cell))
                 }
@@ -1114,9 +1133,7 @@ pub fn fd_seek(
                         return __WASI_EINVAL;
                     }
                 }
-                Kind::Symlink { .. } => {
-                    unimplemented!("wasi::fd_seek not implemented for symlinks")
-                }
+                Kind::Symlink { .. } => wasi_unimplemented!(state, "fd_seek", "symlinks"),
                 Kind::Dir { .. } | Kind::Root { .. } => {
                     // TODO: check this
                     return __WASI_EINVAL;
@@ -1269,7 +1286,7 @@ pub fn fd_write(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_write"),
+                Kind::Symlink { .. } => wasi_unimplemented!(state, "fd_write", "symlinks"),
                 Kind::Buffer { buffer } => {
                     wasi_try!(write_bytes(&mut buffer[offset..], memory, iovs_arr_cell))
                 }
@@ -1609,6 +1626,27 @@ pub fn path_link(
     __WASI_ESUCCESS
 }
 
+/// Opens a host file for `path_open` with `options`, or, with a strict sandbox,
+/// one path component at a time from the preopened directories `strict_roots`
+/// with the matching `open` flags `flags`.
+fn open_host_file(
+    path: &Path,
+    options: &std::fs::OpenOptions,
+    flags: libc::c_int,
+    strict_roots: Option<&[PathBuf]>,
+) -> Result<std::fs::File, __wasi_errno_t> {
+    #[cfg(unix)]
+    {
+        if let Some(roots) = strict_roots {
+            return state::open_beneath(roots, path, flags);
+        }
+    }
+    options.open(path).map_err(|e| {
+        debug!("Error opening file {}", e);
+        __WASI_EIO
+    })
+}
+
 /// ### `path_open()`
 /// Open file located at the given path
 /// Inputs:
@@ -1664,6 +1702,7 @@ pub fn path_open(
     // - __WASI_O_TRUNC (truncate size to 0)
 
     let working_dir = wasi_try!(state.fs.get_fd(dirfd));
+    let working_dir_rights = working_dir.rights;
     let working_dir_rights_inheriting = working_dir.rights_inheriting;
 
     // ASSUMPTION: open rights apply recursively
@@ -1685,9 +1724,21 @@ pub fn path_open(
     // TODO: traverse rights of dirs properly
     // COMMENTED OUT: WASI isn't giving appropriate rights here when opening
     //              TODO: look into this; file a bug report if this is a bug
-    let adjusted_rights = /*fs_rights_base &*/ working_dir_rights_inheriting;
+    let mut adjusted_rights = /*fs_rights_base &*/ working_dir_rights_inheriting;
+    // with a strict sandbox, host files are opened from the preopened directories
+    #[cfg(unix)]
+    let strict_roots = if state.fs.sandbox_strict {
+        Some(state.fs.host_roots())
+    } else {
+        None
+    };
+    #[cfg(not(unix))]
+    let strict_roots: Option<Vec<PathBuf>> = None;
     let inode = if let Ok(inode) = maybe_inode {
         // Happy path, we found the file we're trying to open
+        if state.fs.is_read_only(inode) {
+            adjusted_rights &= !FILE_WRITE_RIGHTS;
+        }
         match &mut state.fs.inodes[inode].kind {
             Kind::File {
                 ref mut handle,
@@ -1728,6 +1779,20 @@ pub fn path_open(
                     .create(create_permission)
                     .append(append_permission)
                     .truncate(truncate_permission);
+                let mut flags = if write_permission {
+                    libc::O_RDWR
+                } else {
+                    libc::O_RDONLY
+                };
+                if append_permission {
+                    flags |= libc::O_APPEND;
+                }
+                if truncate_permission {
+                    flags |= libc::O_TRUNC;
+                }
+                if create_permission {
+                    flags |= libc::O_CREAT;
+                }
                 open_flags |= Fd::READ;
                 if adjusted_rights & __WASI_RIGHT_FD_WRITE != 0 {
                     open_flags |= Fd::WRITE;
@@ -1739,14 +1804,19 @@ pub fn path_open(
                     open_flags |= Fd::TRUNCATE;
                 }
                 *handle = Some(Box::new(HostFile::new(
-                    wasi_try!(open_options.open(&path).map_err(|_| __WASI_EIO)),
+                    wasi_try!(open_host_file(
+                        path,
+                        open_options,
+                        flags,
+                        strict_roots.as_ref().map(Vec::as_slice)
+                    )),
                     path.to_path_buf(),
                     true,
                     adjusted_rights & __WASI_RIGHT_FD_WRITE != 0,
                     false,
                 )));
             }
-            Kind::Buffer { .. } => unimplemented!("wasi::path_open for Buffer type files"),
+            Kind::Buffer { .. } => wasi_unimplemented!(state, "path_open", "in-memory buffers"),
             Kind::Dir { .. } | Kind::Root { .. } => {
                 // TODO: adjust these to be correct
                 if o_flags & __WASI_O_EXCL != 0 {
@@ -1762,7 +1832,7 @@ pub fn path_open(
             } => {
                 // I think this should return an error (because symlinks should be resolved away by the path traversal)
                 // TODO: investigate this
-                unimplemented!("SYMLINKS IN PATH_OPEN");
+                wasi_unimplemented!(state, "path_open", "symlinks");
             }
         }
         inode
@@ -1773,6 +1843,9 @@ pub fn path_open(
             if o_flags & __WASI_O_DIRECTORY != 0 {
                 return __WASI_ENOTDIR;
             }
+            if !has_rights(working_dir_rights, __WASI_RIGHT_PATH_CREATE_FILE) {
+                return __WASI_EACCES;
+            }
             debug!("Creating file");
             // strip end file name
 
@@ -1801,13 +1874,19 @@ pub fn path_open(
                     // write access is required for creating a file
                     .write(true)
                     .create_new(true);
+                let mut flags = libc::O_RDWR | libc::O_CREAT | libc::O_EXCL;
+                if fs_flags & __WASI_FDFLAG_APPEND != 0 {
+                    flags |= libc::O_APPEND;
+                }
                 open_flags |= Fd::READ | Fd::WRITE | Fd::CREATE | Fd::TRUNCATE;
 
                 Some(Box::new(HostFile::new(
-                    wasi_try!(open_options.open(&new_file_host_path).map_err(|e| {
-                        debug!("Error opening file {}", e);
-                        __WASI_EIO
-                    })),
+                    wasi_try!(open_host_file(
+                        &new_file_host_path,
+                        open_options,
+                        flags,
+                        strict_roots.as_ref().map(Vec::as_slice)
+                    )),
                     new_file_host_path.clone(),
                     true,
                     true,
@@ -1844,9 +1923,11 @@ pub fn path_open(
 
     // TODO: check and reduce these
     // TODO: ensure a mutable fd to root can never be opened
+    // restrictions on the working directory are passed down to everything
+    // opened through it
     let out_fd = wasi_try!(state.fs.create_fd(
         adjusted_rights,
-        fs_rights_inheriting,
+        fs_rights_inheriting & working_dir_rights_inheriting,
         fs_flags,
         open_flags,
         inode
@@ -1930,6 +2011,9 @@ pub fn path_remove_directory(
     let (memory, state) = get_memory_and_wasi_state(ctx, 0);
 
     let base_dir = wasi_try!(state.fs.fd_map.get(&fd), __WASI_EBADF);
+    if !has_rights(base_dir.rights, __WASI_RIGHT_PATH_REMOVE_DIRECTORY) {
+        return __WASI_EACCES;
+    }
     let path_str = get_input_str!(memory, path, path_len);
 
     let inode = wasi_try!(state.fs.get_inode_at_path(fd, path_str, false));
@@ -2076,7 +2160,12 @@ pub fn path_rename(
                 }
             }
         }
-        Kind::Dir { path, .. } => unimplemented!("wasi::path_rename on Directories"),
+        Kind::Dir { .. } => {
+            if let Kind::Dir { entries, .. } = &mut state.fs.inodes[source_parent_inode].kind {
+                entries.insert(source_entry_name, source_entry);
+            }
+            wasi_unimplemented!(state, "path_rename", "directories");
+        }
         Kind::Buffer { .. } => {}
         Kind::Symlink { .. } => {}
         Kind::Root { .. } => unreachable!("The root can not be moved"),
@@ -2240,7 +2329,8 @@ pub fn path_unlink_file(
             Kind::Symlink { .. } => {
                 // TODO: actually delete real symlinks and do nothing for virtual symlinks
             }
-            _ => unimplemented!("wasi::path_unlink_file for Buffer"),
+            // in-memory buffers have nothing to delete on the host
+            Kind::Buffer { .. } => (),
         }
         // TODO: test this on Windows and actually make it portable
         // make the file an orphan fd if the fd is still open
@@ -2269,6 +2359,10 @@ pub fn path_unlink_file(
 
 /// ### `poll_oneoff()`
 /// Concurrently poll for a set of events
+///
+/// Blocks until at least one fd subscription is ready or the earliest clock
+/// subscription expires.  Only the subscriptions that fired are reported.
+/// With a virtual clock, clock subscriptions expire immediately.
 /// Inputs:
 /// - `const __wasi_subscription_t *in`
 ///     The events to subscribe to
@@ -2288,24 +2382,29 @@ pub fn poll_oneoff(
 ) -> __wasi_errno_t {
     debug!("wasi::poll_oneoff");
     debug!("  => nsubscriptions = {}", nsubscriptions);
+    if nsubscriptions == 0 {
+        return __WASI_EINVAL;
+    }
     let (memory, state) = get_memory_and_wasi_state(ctx, 0);
 
     let subscription_array = wasi_try!(in_.deref(memory, 0, nsubscriptions));
     let event_array = wasi_try!(out_.deref(memory, 0, nsubscriptions));
-    let mut events_seen = 0;
     let out_ptr = wasi_try!(nevents.deref(memory));
 
+    // the file being polled for each fd subscription, with its index
     let mut fds = vec![];
-    let mut clock_subs = vec![];
+    let mut fd_subs = vec![];
     let mut in_events = vec![];
-    let mut total_ns_slept = 0;
+    // when each clock subscription expires, `None` if it never does
+    let mut clock_subs = vec![];
+    // subscriptions that fire right away with the given error
+    let mut immediate = vec![];
 
-    for sub in subscription_array.iter() {
+    for (i, sub) in subscription_array.iter().enumerate() {
         let s: WasiSubscription = wasi_try!(sub.get().try_into());
-        let mut peb = PollEventBuilder::new();
-        let mut ns_to_sleep = 0;
+        let peb = PollEventBuilder::new();
 
-        let fd = match s.event_type {
+        let (fd, poll_event) = match s.event_type {
             EventType::Read(__wasi_subscription_fs_readwrite_t { fd }) => {
                 match fd {
                     __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
@@ -2316,8 +2415,7 @@ pub fn poll_oneoff(
                         }
                     }
                 }
-                in_events.push(peb.add(PollEvent::PollIn).build());
-                Some(fd)
+                (fd, peb.add(PollEvent::PollIn).build())
             }
             EventType::Write(__wasi_subscription_fs_readwrite_t { fd }) => {
                 match fd {
@@ -2330,135 +2428,148 @@ pub fn poll_oneoff(
                         }
                     }
                 }
-                in_events.push(peb.add(PollEvent::PollOut).build());
-                Some(fd)
+                (fd, peb.add(PollEvent::PollOut).build())
             }
             EventType::Clock(clock_info) => {
-                if clock_info.clock_id == __WASI_CLOCK_REALTIME {
-                    // this is a hack
-                    // TODO: do this properly
-                    ns_to_sleep = clock_info.timeout;
-                    clock_subs.push(clock_info);
-                    None
-                } else {
-                    unimplemented!("Polling not implemented for clocks yet");
+                if !state::is_valid_clock_id(clock_info.clock_id) {
+                    immediate.push((i, __WASI_EINVAL));
+                    continue;
                 }
+                let timeout = if state.clock != WasiClock::Host {
+                    0
+                } else if clock_info.flags & __WASI_SUBSCRIPTION_CLOCK_ABSTIME != 0 {
+                    let now = Cell::new(0);
+                    let result = platform_clock_time_get(clock_info.clock_id, 0, &now);
+                    if result != __WASI_ESUCCESS {
+                        immediate.push((i, result));
+                        continue;
+                    }
+                    clock_info.timeout.saturating_sub(now.get())
+                } else {
+                    clock_info.timeout
+                };
+                let deadline = Instant::now().checked_add(Duration::from_nanos(timeout));
+                clock_subs.push((i, deadline));
+                continue;
             }
         };
 
-        if let Some(fd) = fd {
-            let wasi_file_ref: &dyn WasiFile = match fd {
-                __WASI_STDERR_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stderr().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                __WASI_STDIN_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stdin().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                __WASI_STDOUT_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stdout().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                _ => {
-                    let fd_entry = wasi_try!(state.fs.get_fd(fd));
-                    let inode = fd_entry.inode;
-                    if !has_rights(fd_entry.rights, __WASI_RIGHT_POLL_FD_READWRITE) {
-                        return __WASI_EACCES;
-                    }
+        let wasi_file_ref: &dyn WasiFile = match fd {
+            __WASI_STDERR_FILENO => wasi_try!(
+                wasi_try!(state.fs.stderr().map_err(WasiFsError::into_wasi_err)).as_ref(),
+                __WASI_EBADF
+            )
+            .as_ref(),
+            __WASI_STDIN_FILENO => wasi_try!(
+                wasi_try!(state.fs.stdin().map_err(WasiFsError::into_wasi_err)).as_ref(),
+                __WASI_EBADF
+            )
+            .as_ref(),
+            __WASI_STDOUT_FILENO => wasi_try!(
+                wasi_try!(state.fs.stdout().map_err(WasiFsError::into_wasi_err)).as_ref(),
+                __WASI_EBADF
+            )
+            .as_ref(),
+            _ => {
+                let fd_entry = wasi_try!(state.fs.get_fd(fd));
+                let inode = fd_entry.inode;
+                if !has_rights(fd_entry.rights, __WASI_RIGHT_POLL_FD_READWRITE) {
+                    return __WASI_EACCES;
+                }
 
-                    match &state.fs.inodes[inode].kind {
-                        Kind::File { handle, .. } => {
-                            if let Some(h) = handle {
-                                h.as_ref()
-                            } else {
-                                return __WASI_EBADF;
-                            }
-                        }
-                        Kind::Dir { .. }
-                        | Kind::Root { .. }
-                        | Kind::Buffer { .. }
-                        | Kind::Symlink { .. } => {
-                            unimplemented!("polling read on non-files not yet supported")
+                match &state.fs.inodes[inode].kind {
+                    Kind::File { handle, .. } => {
+                        if let Some(h) = handle {
+                            h.as_ref()
+                        } else {
+                            return __WASI_EBADF;
                         }
                     }
+                    // in-memory buffers never block
+                    Kind::Buffer { .. } => {
+                        immediate.push((i, __WASI_ESUCCESS));
+                        continue;
+                    }
+                    Kind::Dir { .. } | Kind::Root { .. } | Kind::Symlink { .. } => {
+                        immediate.push((i, __WASI_EISDIR));
+                        continue;
+                    }
                 }
-            };
-            fds.push(wasi_file_ref);
+            }
+        };
+        fds.push(wasi_file_ref);
+        fd_subs.push(i);
+        in_events.push(poll_event);
+    }
+
+    let mut events: Vec<Option<__wasi_event_t>> = vec![None; subscription_array.len()];
+    let fd_event = |i: usize, error, nbytes, flags| __wasi_event_t {
+        userdata: subscription_array[i].get().userdata,
+        error,
+        type_: subscription_array[i].get().type_,
+        u: __wasi_event_u {
+            fd_readwrite: __wasi_event_fd_readwrite_t { nbytes, flags },
+        },
+    };
+    for &(i, error) in immediate.iter() {
+        events[i] = Some(fd_event(i, error, 0, 0));
+    }
+
+    let earliest_deadline = clock_subs
+        .iter()
+        .filter_map(|&(_, deadline)| deadline)
+        .min();
+    let mut seen_events = vec![Default::default(); in_events.len()];
+    loop {
+        let timeout = if immediate.is_empty() {
+            earliest_deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
         } else {
-            let remaining_ns = ns_to_sleep as i64 - total_ns_slept as i64;
-            if remaining_ns > 0 {
-                debug!("Sleeping for {} nanoseconds", remaining_ns);
-                let duration = std::time::Duration::from_nanos(remaining_ns as u64);
-                std::thread::sleep(duration);
-                total_ns_slept += remaining_ns;
+            Some(Duration::from_secs(0))
+        };
+        let ready = wasi_try!(poll(
+            fds.as_slice(),
+            in_events.as_slice(),
+            seen_events.as_mut_slice(),
+            timeout,
+        )
+        .map_err(|e| e.into_wasi_err()));
+
+        let now = Instant::now();
+        let mut clocks_fired = false;
+        for &(i, deadline) in clock_subs.iter() {
+            if deadline.map(|deadline| deadline <= now).unwrap_or(false) {
+                events[i] = Some(fd_event(i, __WASI_ESUCCESS, 0, 0));
+                clocks_fired = true;
             }
         }
+        if ready > 0 || clocks_fired || !immediate.is_empty() {
+            break;
+        }
     }
-    let mut seen_events = vec![Default::default(); in_events.len()];
-    wasi_try!(poll(
-        fds.as_slice(),
-        in_events.as_slice(),
-        seen_events.as_mut_slice()
-    )
-    .map_err(|e| e.into_wasi_err()));
-
-    for (i, seen_event) in seen_events.into_iter().enumerate() {
+
+    for (j, seen_event) in seen_events.into_iter().enumerate() {
+        if seen_event == 0 {
+            continue;
+        }
         let mut flags = 0;
-        let mut error = __WASI_EAGAIN;
+        let mut error = __WASI_ESUCCESS;
         let mut bytes_available = 0;
-        let event_iter = iterate_poll_events(seen_event);
-        for event in event_iter {
+        for event in iterate_poll_events(seen_event) {
             match event {
                 PollEvent::PollError => error = __WASI_EIO,
                 PollEvent::PollHangUp => flags = __WASI_EVENT_FD_READWRITE_HANGUP,
-                PollEvent::PollInvalid => error = __WASI_EINVAL,
-                PollEvent::PollIn => {
-                    bytes_available =
-                        wasi_try!(fds[i].bytes_available().map_err(|e| e.into_wasi_err()));
-                    error = __WASI_ESUCCESS;
-                }
-                PollEvent::PollOut => {
-                    bytes_available =
-                        wasi_try!(fds[i].bytes_available().map_err(|e| e.into_wasi_err()));
-                    error = __WASI_ESUCCESS;
+                PollEvent::PollInvalid => error = __WASI_EBADF,
+                PollEvent::PollIn | PollEvent::PollOut => {
+                    bytes_available = fds[j].bytes_available().unwrap_or(0);
                 }
             }
         }
-        let event = __wasi_event_t {
-            userdata: subscription_array[i].get().userdata,
-            error,
-            type_: subscription_array[i].get().type_,
-            u: unsafe {
-                __wasi_event_u {
-                    fd_readwrite: __wasi_event_fd_readwrite_t {
-                        nbytes: bytes_available as u64,
-                        flags,
-                    },
-                }
-            },
-        };
-        event_array[events_seen].set(event);
-        events_seen += 1;
+        let i = fd_subs[j];
+        events[i] = Some(fd_event(i, error, bytes_available as u64, flags));
     }
-    for clock_info in clock_subs {
-        let event = __wasi_event_t {
-            // TOOD: review userdata value
-            userdata: 0,
-            error: __WASI_ESUCCESS,
-            type_: __WASI_EVENTTYPE_CLOCK,
-            u: unsafe {
-                __wasi_event_u {
-                    fd_readwrite: __wasi_event_fd_readwrite_t {
-                        nbytes: 0,
-                        flags: 0,
-                    },
-                }
-            },
-        };
+
+    let mut events_seen = 0;
+    for event in events.into_iter().filter_map(|event| event) {
         event_array[events_seen].set(event);
         events_seen += 1;
     }
@@ -2466,13 +2577,16 @@ pub fn poll_oneoff(
     __WASI_ESUCCESS
 }
 
-pub fn proc_exit(ctx: &mut Ctx, code: __wasi_exitcode_t) -> Result<Infallible, ExitCode> {
+pub fn proc_exit(ctx: &mut Ctx, code: __wasi_exitcode_t) -> Result<Infallible, WasiExit> {
     debug!("wasi::proc_exit, {}", code);
-    Err(ExitCode { code })
+    let state = unsafe { state::get_wasi_state(ctx) };
+    state.exit_code = Some(code);
+    Err(WasiExit { code })
 }
 pub fn proc_raise(ctx: &mut Ctx, sig: __wasi_signal_t) -> __wasi_errno_t {
     debug!("wasi::proc_raise");
-    unimplemented!("wasi::proc_raise")
+    let state = unsafe { state::get_wasi_state(ctx) };
+    wasi_unimplemented!(state, "proc_raise", "signals")
 }
 
 /// ### `random_get()`
@@ -2484,13 +2598,13 @@ pub fn proc_raise(ctx: &mut Ctx, sig: __wasi_signal_t) -> __wasi_errno_t {
 ///     The number of bytes that will be written
 pub fn random_get(ctx: &mut Ctx, buf: WasmPtr<u8, Array>, buf_len: u32) -> __wasi_errno_t {
     debug!("wasi::random_get buf_len: {}", buf_len);
-    let memory = ctx.memory(0);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
 
     let buf = wasi_try!(buf.deref(memory, 0, buf_len));
 
     let res = unsafe {
         let u8_buffer = &mut *(buf as *const [_] as *mut [_] as *mut [u8]);
-        getrandom::getrandom(u8_buffer)
+        state.random.fill(u8_buffer)
     };
     match res {
         Ok(()) => __WASI_ESUCCESS,
@@ -2506,6 +2620,43 @@ pub fn sched_yield(ctx: &mut Ctx) -> __wasi_errno_t {
     __WASI_ESUCCESS
 }
 
+/// Get the socket at `sock`, checking that its fd has `rights`
+fn get_socket(
+    state: &mut WasiState,
+    sock: __wasi_fd_t,
+    rights: __wasi_rights_t,
+) -> Result<&mut Box<dyn WasiFile>, __wasi_errno_t> {
+    let fd_entry = state.fs.get_fd(sock)?;
+    if !has_rights(fd_entry.rights, rights) {
+        return Err(__WASI_EACCES);
+    }
+    let inode = fd_entry.inode;
+    match &mut state.fs.inodes[inode].kind {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } if handle.is_socket() => Ok(handle),
+        _ => Err(__WASI_ENOTSOCK),
+    }
+}
+
+/// ### `sock_recv()`
+/// Receive a message from a socket
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to receive from
+/// - `__wasi_iovec_t *ri_data`
+///     The buffers to store the data in
+/// - `u32 ri_data_len`
+///     The number of buffers in `ri_data`
+/// - `__wasi_riflags_t ri_flags`
+///     `__WASI_SOCK_RECV_PEEK` to leave the data in the socket,
+///     `__WASI_SOCK_RECV_WAITALL` to wait until every buffer is full
+/// Output:
+/// - `u32 *ro_datalen`
+///     The number of bytes received
+/// - `__wasi_roflags_t *ro_flags`
+///     Message flags
 pub fn sock_recv(
     ctx: &mut Ctx,
     sock: __wasi_fd_t,
@@ -2515,9 +2666,78 @@ pub fn sock_recv(
     ro_datalen: WasmPtr<u32>,
     ro_flags: WasmPtr<__wasi_roflags_t>,
 ) -> __wasi_errno_t {
-    debug!("wasi::sock_recv");
-    unimplemented!("wasi::sock_recv")
+    debug!("wasi::sock_recv: sock={}, ri_flags={}", sock, ri_flags);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
+
+    let iovs_arr_cell = wasi_try!(ri_data.deref(memory, 0, ri_data_len));
+    let ro_datalen_cell = wasi_try!(ro_datalen.deref(memory));
+    let ro_flags_cell = wasi_try!(ro_flags.deref(memory));
+    let socket = wasi_try!(get_socket(state, sock, __WASI_RIGHT_FD_READ));
+
+    let mut received = 0;
+    if ri_flags & __WASI_SOCK_RECV_PEEK != 0 {
+        // Every peek starts at the front of the queue, so the data is peeked into one
+        // buffer. The iovs may overlap, so its size is capped at the size of the memory
+        // rather than trusting their total length.
+        let capacity = iovs_arr_cell
+            .iter()
+            .map(|iov| iov.get().buf_len as usize)
+            .sum::<usize>()
+            .min(memory.size().bytes().0);
+        let mut data = vec![0; capacity];
+        received = wasi_try!(socket.sock_peek(&mut data).map_err(|e| e.into_wasi_err()));
+
+        let mut remaining = &data[..received];
+        for iov in iovs_arr_cell {
+            if remaining.is_empty() {
+                break;
+            }
+            let iov_inner = iov.get();
+            let len = remaining.len().min(iov_inner.buf_len as usize);
+            let cells = wasi_try!(iov_inner.buf.deref(memory, 0, len as u32));
+            for (cell, &byte) in cells.iter().zip(remaining[..len].iter()) {
+                cell.set(byte);
+            }
+            remaining = &remaining[len..];
+        }
+    } else {
+        'iovs: for iov in iovs_arr_cell {
+            let iov_inner = iov.get();
+            let cells = wasi_try!(iov_inner.buf.deref(memory, 0, iov_inner.buf_len));
+            let buf: &mut [u8] = unsafe { &mut *(cells as *const [_] as *mut [_] as *mut [u8]) };
+            let mut filled = 0;
+            while filled < buf.len() {
+                let n = wasi_try!(socket
+                    .read(&mut buf[filled..])
+                    .map_err(|e| WasiFsError::from(e).into_wasi_err()));
+                filled += n;
+                received += n;
+                if n == 0 || (filled < buf.len() && ri_flags & __WASI_SOCK_RECV_WAITALL == 0) {
+                    break 'iovs;
+                }
+            }
+        }
+    }
+
+    ro_datalen_cell.set(received as u32);
+    ro_flags_cell.set(0);
+    __WASI_ESUCCESS
 }
+
+/// ### `sock_send()`
+/// Send a message on a socket
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to send on
+/// - `const __wasi_ciovec_t *si_data`
+///     The buffers containing the data to send
+/// - `u32 si_data_len`
+///     The number of buffers in `si_data`
+/// - `__wasi_siflags_t si_flags`
+///     Message flags, none are currently defined
+/// Output:
+/// - `u32 *so_datalen`
+///     The number of bytes sent
 pub fn sock_send(
     ctx: &mut Ctx,
     sock: __wasi_fd_t,
@@ -2526,10 +2746,39 @@ pub fn sock_send(
     si_flags: __wasi_siflags_t,
     so_datalen: WasmPtr<u32>,
 ) -> __wasi_errno_t {
-    debug!("wasi::sock_send");
-    unimplemented!("wasi::sock_send")
+    debug!("wasi::sock_send: sock={}", sock);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
+
+    let iovs_arr_cell = wasi_try!(si_data.deref(memory, 0, si_data_len));
+    let so_datalen_cell = wasi_try!(so_datalen.deref(memory));
+    if si_flags != 0 {
+        return __WASI_EINVAL;
+    }
+    let socket = wasi_try!(get_socket(state, sock, __WASI_RIGHT_FD_WRITE));
+
+    let bytes_written = wasi_try!(write_bytes(socket, memory, iovs_arr_cell));
+    so_datalen_cell.set(bytes_written);
+    __WASI_ESUCCESS
 }
+
+/// ### `sock_shutdown()`
+/// Shut down the receiving and/or sending side of a socket
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to shut down
+/// - `__wasi_sdflags_t how`
+///     `__WASI_SHUT_RD` and/or `__WASI_SHUT_WR`
 pub fn sock_shutdown(ctx: &mut Ctx, sock: __wasi_fd_t, how: __wasi_sdflags_t) -> __wasi_errno_t {
-    debug!("wasi::sock_shutdown");
-    unimplemented!("wasi::sock_shutdown")
+    debug!("wasi::sock_shutdown: sock={}, how={}", sock, how);
+    let state = unsafe { state::get_wasi_state(ctx) };
+
+    let how = match how {
+        __WASI_SHUT_RD => std::net::Shutdown::Read,
+        __WASI_SHUT_WR => std::net::Shutdown::Write,
+        h if h == __WASI_SHUT_RD | __WASI_SHUT_WR => std::net::Shutdown::Both,
+        _ => return __WASI_EINVAL,
+    };
+    let socket = wasi_try!(get_socket(state, sock, __WASI_RIGHT_SOCK_SHUTDOWN));
+    wasi_try!(socket.sock_shutdown(how).map_err(|e| e.into_wasi_err()));
+    __WASI_ESUCCESS
 }
@@ -0,0 +1,345 @@
+//! Support for the [wasi-threads] proposal.
+//!
+//! A module built for wasi-threads imports a shared memory as `env.memory`,
+//! imports `wasi.thread-spawn` and exports `wasi_thread_start`. Every call to
+//! `thread-spawn` instantiates the module again on a new host thread, backed
+//! by the same shared memory, and runs `wasi_thread_start(tid, start_arg)`
+//! inside that instance.
+//!
+//! Every instance, the main one included, shares a single [`WasiState`], so
+//! file descriptors opened by one thread are visible to the others. Their
+//! syscalls take turns holding a lock on it: a thread blocked in a syscall,
+//! e.g. reading from stdin or sleeping in `poll_oneoff`, holds up the
+//! syscalls of the others until it returns.
+//!
+//! Once a thread calls `proc_exit`, the next syscall or `thread-spawn` of
+//! every other thread fails with the same exit code, which ends it. Threads
+//! that don't make syscalls anymore can't be stopped, and keep running until
+//! they return or trap.
+//!
+//! [wasi-threads]: https://github.com/WebAssembly/wasi-threads
+
+use crate::{
+    generate_import_object_for_versions_inner, get_wasi_versions,
+    state::{hook::SyscallResult, WasiState},
+    WasiExit,
+};
+use std::{
+    cell::UnsafeCell,
+    ffi::c_void,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+};
+use wasmer_runtime_core::{
+    func,
+    import::{ImportObject, Namespace},
+    memory::Memory,
+    module::Module,
+    types::{MemoryDescriptor, Value},
+    vm::Ctx,
+};
+
+/// Namespace of the `thread-spawn` import.
+const THREADS_NAMESPACE: &str = "wasi";
+
+/// Name of the function imported to spawn a new thread.
+const THREAD_SPAWN: &str = "thread-spawn";
+
+/// Name of the function every spawned thread starts in.
+const THREAD_START: &str = "wasi_thread_start";
+
+/// The highest thread ID allowed by the proposal; the top bits are reserved.
+const MAX_TID: u32 = 0x1FFF_FFFF;
+
+/// Check if a provided module imports `wasi.thread-spawn`.
+pub fn is_wasi_threads_module(module: &Module) -> bool {
+    let info = module.info();
+    info.imported_functions.iter().any(|(_, import_name)| {
+        info.namespace_table.get(import_name.namespace_index) == THREADS_NAMESPACE
+            && info.name_table.get(import_name.name_index) == THREAD_SPAWN
+    })
+}
+
+/// Error returned when a module cannot be set up to run with wasi-threads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WasiThreadsError {
+    /// The module does not import a shared memory as `env.memory`.
+    MissingSharedMemory,
+    /// The shared memory could not be created.
+    MemoryCreation(String),
+}
+
+impl fmt::Display for WasiThreadsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasiThreadsError::MissingSharedMemory => write!(
+                f,
+                "wasi-threads modules must import a shared memory as `env.memory`"
+            ),
+            WasiThreadsError::MemoryCreation(msg) => {
+                write!(f, "failed to create the shared memory: {}", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WasiThreadsError {}
+
+/// The outcome of a spawned thread that did not return normally.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WasiThreadError {
+    /// The module could not be instantiated on the new thread.
+    Instantiation(String),
+    /// `wasi_thread_start` trapped or raised an error.
+    Runtime(String),
+    /// The thread called `proc_exit` with the given code, or was ended by
+    /// another thread calling it.
+    Exit(u32),
+}
+
+impl fmt::Display for WasiThreadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasiThreadError::Instantiation(msg) => {
+                write!(f, "failed to instantiate thread: {}", msg)
+            }
+            WasiThreadError::Runtime(msg) => write!(f, "thread failed: {}", msg),
+            WasiThreadError::Exit(code) => write!(f, "thread exited with code {}", code),
+        }
+    }
+}
+
+impl std::error::Error for WasiThreadError {}
+
+/// The [`WasiState`] shared by the instances of a [`WasiThreads`], which their
+/// `ctx.data` points to.
+///
+/// `state` comes first, so that the syscalls find it at `ctx.data` as they do
+/// for other WASI instances.
+#[repr(C)]
+struct SharedWasiState {
+    state: UnsafeCell<WasiState>,
+    /// Held by the syscalls while they use `state`.
+    lock: Mutex<()>,
+}
+
+// `state` is only used while `lock` is held.
+unsafe impl Send for SharedWasiState {}
+unsafe impl Sync for SharedWasiState {}
+
+impl SharedWasiState {
+    /// Wait for the turn of the calling thread, failing with the exit of the
+    /// thread that called `proc_exit`, if one did.
+    fn turn(&self) -> Result<MutexGuard<()>, WasiExit> {
+        // a syscall that panicked didn't leave the state inconsistent enough
+        // to stop the other threads
+        let turn = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        match unsafe { (*self.state.get()).exit_code } {
+            Some(code) => Err(WasiExit { code }),
+            None => Ok(turn),
+        }
+    }
+}
+
+/// The `ctx.data` finalizer of the instances sharing a [`SharedWasiState`],
+/// which also tells them apart from other WASI instances.
+fn release_shared_state(data: *mut c_void) {
+    unsafe { drop(Arc::from_raw(data as *const SharedWasiState)) }
+}
+
+/// Run `syscall`, taking turns with the other threads if the instance of
+/// `ctx` shares its state with them.
+pub(crate) fn call_syscall<R, F>(ctx: &mut Ctx, syscall: F) -> Result<R::Value, WasiExit>
+where
+    R: SyscallResult,
+    F: FnOnce(&mut Ctx) -> R,
+{
+    if ctx.data_finalizer != Some(release_shared_state as fn(*mut c_void)) {
+        return syscall(ctx).into_result();
+    }
+    let shared = unsafe { &*(ctx.data as *const SharedWasiState) };
+    let _turn = shared.turn()?;
+    syscall(ctx).into_result()
+}
+
+struct ThreadsInner {
+    module: Module,
+    memory: Memory,
+    wasi_state: Arc<SharedWasiState>,
+    next_tid: AtomicU32,
+    threads: Mutex<Vec<(u32, JoinHandle<Result<(), WasiThreadError>>)>>,
+    /// Set when the `WasiThreads` is dropped, after which nothing is spawned.
+    dropped: AtomicBool,
+}
+
+/// Runs a wasi-threads module, spawning host threads on `thread-spawn`.
+///
+/// The main instance is created by the caller with [`WasiThreads::import_object`].
+/// Dropping a `WasiThreads` doesn't wait for the threads it spawned, which
+/// keep running in the background; after that, `thread-spawn` fails in the
+/// instances that outlive it.
+pub struct WasiThreads {
+    inner: Arc<ThreadsInner>,
+}
+
+impl WasiThreads {
+    /// Prepare `module` to run with wasi-threads.
+    ///
+    /// This creates the shared memory described by the module's `env.memory`
+    /// import. Every instance, including the main one, shares `wasi_state`.
+    pub fn new(module: &Module, wasi_state: WasiState) -> Result<Self, WasiThreadsError> {
+        let desc = shared_memory_descriptor(module).ok_or(WasiThreadsError::MissingSharedMemory)?;
+        let memory =
+            Memory::new(desc).map_err(|e| WasiThreadsError::MemoryCreation(format!("{}", e)))?;
+
+        Ok(Self {
+            inner: Arc::new(ThreadsInner {
+                module: module.clone(),
+                memory,
+                wasi_state: Arc::new(SharedWasiState {
+                    state: UnsafeCell::new(wasi_state),
+                    lock: Mutex::new(()),
+                }),
+                next_tid: AtomicU32::new(1),
+                threads: Mutex::new(Vec::new()),
+                dropped: AtomicBool::new(false),
+            }),
+        })
+    }
+
+    /// The memory shared by every thread.
+    pub fn memory(&self) -> &Memory {
+        &self.inner.memory
+    }
+
+    /// Create the [`ImportObject`] for the main instance.
+    pub fn import_object(&self) -> ImportObject {
+        import_object(&self.inner)
+    }
+
+    /// Wait for every spawned thread, including threads spawned while
+    /// waiting, and return their outcomes ordered by thread ID.
+    pub fn join(&self) -> Vec<(u32, Result<(), WasiThreadError>)> {
+        let mut results = Vec::new();
+        loop {
+            let handles: Vec<_> = self.inner.threads.lock().unwrap().drain(..).collect();
+            if handles.is_empty() {
+                break;
+            }
+            for (tid, handle) in handles {
+                let result = handle.join().unwrap_or_else(|_| {
+                    Err(WasiThreadError::Runtime("thread panicked".to_string()))
+                });
+                results.push((tid, result));
+            }
+        }
+        results.sort_by_key(|(tid, _)| *tid);
+        results
+    }
+}
+
+impl Drop for WasiThreads {
+    fn drop(&mut self) {
+        // joining could block forever on a thread that never returns, so
+        // the threads are detached instead
+        self.inner.dropped.store(true, Ordering::SeqCst);
+        self.inner.threads.lock().unwrap().clear();
+    }
+}
+
+fn shared_memory_descriptor(module: &Module) -> Option<MemoryDescriptor> {
+    let info = module.info();
+    info.imported_memories
+        .iter()
+        .find(|(_, (import_name, desc))| {
+            desc.shared
+                && info.namespace_table.get(import_name.namespace_index) == "env"
+                && info.name_table.get(import_name.name_index) == "memory"
+        })
+        .map(|(_, (_, desc))| *desc)
+}
+
+fn import_object(inner: &Arc<ThreadsInner>) -> ImportObject {
+    let wasi_state = Arc::clone(&inner.wasi_state);
+    let state_gen = move || {
+        let data = Arc::into_raw(Arc::clone(&wasi_state)) as *mut c_void;
+        (data, release_shared_state as fn(*mut c_void))
+    };
+    let mut import_object =
+        generate_import_object_for_versions_inner(&get_wasi_versions(&inner.module), state_gen);
+
+    let mut env = Namespace::new();
+    env.insert("memory", inner.memory.clone());
+    import_object.register("env", env);
+
+    // The instances only hold a weak reference, as `ThreadsInner` holds the
+    // threads running them
+    let spawn_inner = Arc::downgrade(inner);
+    let mut wasi = Namespace::new();
+    wasi.insert(
+        THREAD_SPAWN,
+        func!(
+            move |_ctx: &mut Ctx, start_arg: i32| -> Result<i32, WasiExit> {
+                match spawn_inner.upgrade() {
+                    Some(inner) => spawn(&inner, start_arg),
+                    None => Ok(-1),
+                }
+            }
+        ),
+    );
+    import_object.register(THREADS_NAMESPACE, wasi);
+
+    import_object
+}
+
+/// Start a new thread and return its ID, or a negative value on failure.
+///
+/// Fails with the exit of the thread that called `proc_exit`, if one did.
+fn spawn(inner: &Arc<ThreadsInner>, start_arg: i32) -> Result<i32, WasiExit> {
+    // holding the turn keeps threads from being spawned while exiting
+    let _turn = inner.wasi_state.turn()?;
+    if inner.dropped.load(Ordering::SeqCst) {
+        return Ok(-1);
+    }
+    let tid = inner.next_tid.fetch_add(1, Ordering::SeqCst);
+    if tid > MAX_TID {
+        return Ok(-1);
+    }
+
+    let thread_inner = Arc::clone(inner);
+    let handle = thread::Builder::new()
+        .name(format!("wasi-thread-{}", tid))
+        .spawn(move || run_thread(&thread_inner, tid, start_arg));
+
+    match handle {
+        Ok(handle) => {
+            inner.threads.lock().unwrap().push((tid, handle));
+            Ok(tid as i32)
+        }
+        Err(_) => Ok(-1),
+    }
+}
+
+fn run_thread(inner: &Arc<ThreadsInner>, tid: u32, start_arg: i32) -> Result<(), WasiThreadError> {
+    let instance = inner
+        .module
+        .instantiate(&import_object(inner))
+        .map_err(|e| WasiThreadError::Instantiation(format!("{:?}", e)))?;
+
+    let result = instance
+        .dyn_func(THREAD_START)
+        .map_err(|e| WasiThreadError::Instantiation(format!("{:?}", e)))?
+        .call(&[Value::I32(tid as i32), Value::I32(start_arg)]);
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => match WasiExit::from_call_error(&e) {
+            Some(exit) => Err(WasiThreadError::Exit(exit.code)),
+            None => Err(WasiThreadError::Runtime(format!("{:?}", e))),
+        },
+    }
+}
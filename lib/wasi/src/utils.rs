@@ -29,12 +29,43 @@ pub enum WasiVersion {
     Latest,
 }
 
+/// Detect every version of WASI imported by the module.
+///
+/// Unlike [`get_wasi_version`], this reports both versions when a module
+/// imports from `wasi_unstable` and `wasi_snapshot_preview1` side by
+/// side. Versions are returned in the order their namespaces first
+/// appear in the imports.
+pub fn get_wasi_versions(module: &Module) -> Vec<WasiVersion> {
+    let module_info = &module.info();
+    let namespace_table = &module_info.namespace_table;
+    let mut versions = Vec::new();
+
+    for (_, import_name) in module_info.imported_functions.iter() {
+        let version = match namespace_table.get(import_name.namespace_index) {
+            SNAPSHOT0_NAMESPACE => WasiVersion::Snapshot0,
+            SNAPSHOT1_NAMESPACE => WasiVersion::Snapshot1,
+            _ => continue,
+        };
+
+        if !versions.contains(&version) {
+            versions.push(version);
+        }
+    }
+
+    versions
+}
+
 /// Namespace for the `Snapshot0` version.
 const SNAPSHOT0_NAMESPACE: &'static str = "wasi_unstable";
 
 /// Namespace for the `Snapshot1` version.
 const SNAPSHOT1_NAMESPACE: &'static str = "wasi_snapshot_preview1";
 
+/// Whether `namespace` is the import namespace of a WASI version.
+pub(crate) fn is_wasi_namespace(namespace: &str) -> bool {
+    namespace == SNAPSHOT0_NAMESPACE || namespace == SNAPSHOT1_NAMESPACE
+}
+
 /// Detect the version of WASI being used based on the import
 /// namespaces.
 ///
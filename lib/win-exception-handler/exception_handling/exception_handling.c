@@ -1,5 +1,6 @@
 #include <windows.h>
 #include <setjmp.h>
+#include <string.h>
 #include "exception_handling.h"
 
 #define CALL_FIRST 1
@@ -56,8 +57,15 @@ uint8_t callProtected(trampoline_t trampoline,
         uint64_t* return_vec,
         struct call_protected_result_t* out_result) {
 
+    // Calls can nest, e.g. when an imported function calls back into wasm,
+    // so save the state of the outer call to restore it on the way out.
+    jmp_buf outerJmpBuf;
+    PVOID outerStackPointer = savedStackPointer;
+    BOOL outermost = exceptionHandlerInstalled == FALSE;
+    memcpy(outerJmpBuf, jmpBuf, sizeof(jmp_buf));
+
     // install exception handler
-    if (exceptionHandlerInstalled == FALSE) {
+    if (outermost) {
         exceptionHandlerInstalled = TRUE;
         handle = AddVectoredExceptionHandler(CALL_FIRST, exceptionHandler);
     }
@@ -73,7 +81,11 @@ uint8_t callProtected(trampoline_t trampoline,
         out_result->exception_address = 0;
         out_result->instruction_pointer = 0;
 
-        removeExceptionHandler();
+        memcpy(jmpBuf, outerJmpBuf, sizeof(jmp_buf));
+        savedStackPointer = outerStackPointer;
+        if (outermost) {
+            removeExceptionHandler();
+        }
         return TRUE;
     }
 
@@ -83,7 +95,12 @@ uint8_t callProtected(trampoline_t trampoline,
 
     caughtExceptionAddress = 0;
     caughtInstructionPointer = 0;
+    alreadyHandlingException = FALSE;
 
-    removeExceptionHandler();
+    memcpy(jmpBuf, outerJmpBuf, sizeof(jmp_buf));
+    savedStackPointer = outerStackPointer;
+    if (outermost) {
+        removeExceptionHandler();
+    }
     return FALSE;
-}
\ No newline at end of file
+}
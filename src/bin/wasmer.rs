@@ -11,33 +11,46 @@ extern crate structopt;
 
 use std::collections::HashMap;
 use std::env;
-use std::fs::{metadata, read_to_string, File};
+use std::fs::{self, metadata, read_to_string, File};
 use std::io;
 use std::io::Read;
 use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use structopt::{clap, StructOpt};
 
-use wasmer::*;
+use wasmer::{
+    diagnostic::{Diagnostic, OutputFormat},
+    *,
+};
 #[cfg(feature = "backend-cranelift")]
 use wasmer_clif_backend::CraneliftCompiler;
 #[cfg(feature = "backend-llvm")]
 use wasmer_llvm_backend::{
     InkwellMemoryBuffer, InkwellModule, LLVMBackendConfig, LLVMCallbacks, LLVMCompiler,
+    LLVMOptLevel,
 };
+use wasmer_middleware_common::metering::{get_points_used, ExecutionLimitExceededError};
 use wasmer_runtime::{
     cache::{Cache as BaseCache, FileSystemCache, WasmHash},
-    Value, VERSION,
+    error::{CallError, CompileError, RuntimeError},
+    limits::{self, ResourceLimits, StaticLimiter},
+    units::Bytes,
+    BackendHints, Value, VERSION,
 };
 #[cfg(feature = "managed")]
 use wasmer_runtime_core::tiering::{run_tiering, InteractiveShellContext, ShellExitOperation};
 use wasmer_runtime_core::{
     self,
-    backend::{Backend, Compiler, CompilerConfig, Features, MemoryBoundCheckMode},
+    backend::{
+        Backend, Compiler, CompilerConfig, CpuFeatures, Features, MemoryBoundCheckMode,
+        MemoryStyle, SpectreMitigations, Triple,
+    },
     debug,
     loader::{Instance as LoadedInstance, LocalLoader},
+    signing::{self, Keypair, PublicKey},
     Module,
 };
 #[cfg(feature = "wasi")]
@@ -51,7 +64,8 @@ use wasmer_runtime_core::backend::BackendCompilerConfig;
 #[cfg(not(any(
     feature = "backend-cranelift",
     feature = "backend-llvm",
-    feature = "backend-singlepass"
+    feature = "backend-singlepass",
+    feature = "backend-interpreter"
 )))]
 compile_error!("Please enable one or more of the compiler backends");
 
@@ -71,6 +85,26 @@ enum CLIOptions {
     #[structopt(name = "validate")]
     Validate(Validate),
 
+    /// List the imports, exports and other metadata of a WebAssembly file
+    #[structopt(name = "inspect")]
+    Inspect(Inspect),
+
+    /// Compile a WebAssembly file ahead of time to a cache artifact. Formats accepted: wasm, wat
+    #[structopt(name = "compile")]
+    Compile(Compile),
+
+    /// Sign a WebAssembly file with an Ed25519 key
+    #[structopt(name = "sign")]
+    Sign(Sign),
+
+    /// Instantiate a WebAssembly file and call its exports interactively
+    #[structopt(name = "repl")]
+    Repl(Repl),
+
+    /// Run spec-style .wast scripts
+    #[structopt(name = "wast")]
+    Wast(Wast),
+
     /// Update wasmer to the latest version
     #[structopt(name = "self-update")]
     SelfUpdate,
@@ -86,6 +120,19 @@ struct PrestandardFeatures {
     #[structopt(long = "enable-threads")]
     threads: bool,
 
+    /// Enable support for the bulk memory proposal. No backend compiles its
+    /// instructions yet, so modules using them only pass validation.
+    #[structopt(long = "enable-bulk-memory")]
+    bulk_memory: bool,
+
+    /// Enable support for the multi-value proposal.
+    #[structopt(long = "enable-multi-value")]
+    multi_value: bool,
+
+    /// Reject modules using floating point types or instructions.
+    #[structopt(long = "deny-floats")]
+    deny_floats: bool,
+
     /// Enable support for all pre-standard proposals.
     #[structopt(long = "enable-all")]
     all: bool,
@@ -101,6 +148,12 @@ impl PrestandardFeatures {
         if self.threads || self.all {
             features.enable_threads();
         }
+        if self.bulk_memory || self.all {
+            features.enable_bulk_memory();
+        }
+        if self.multi_value || self.all {
+            features.enable_multi_value();
+        }
         features.enable_sign_extension();
         features.enable_sat_float_to_int();
         features
@@ -111,6 +164,10 @@ impl PrestandardFeatures {
         Features {
             simd: self.simd || self.all,
             threads: self.threads || self.all,
+            bulk_memory: self.bulk_memory || self.all,
+            multi_value: self.multi_value || self.all,
+            deny_floats: self.deny_floats,
+            deterministic: false,
         }
     }
 }
@@ -130,18 +187,41 @@ pub struct LLVMCLIOptions {
     /// Emit LLVM generated native code object file.
     #[structopt(long = "llvm-object-file", parse(from_os_str))]
     obj_file: Option<PathBuf>,
+
+    /// LLVM optimization level: 0, 1, 2, 3 or s.
+    #[structopt(long = "llvm-opt-level", default_value = "3")]
+    opt_level: LLVMOptLevel,
+
+    /// Disable the LLVM loop and SLP vectorizers.
+    #[structopt(long = "llvm-disable-vectorize")]
+    disable_vectorize: bool,
+
+    /// Threshold of the LLVM inliner.
+    #[structopt(long = "llvm-inline-threshold")]
+    inline_threshold: Option<u32>,
+
+    /// Emit the LLVM IR of each function before and after the optimization pipeline to a directory.
+    #[structopt(long = "llvm-dump-ir-dir", parse(from_os_str))]
+    dump_ir_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, StructOpt, Clone)]
+#[structopt(setting = clap::AppSettings::AllowNegativeNumbers)]
 struct Run {
     /// Disable the cache
     #[structopt(long = "disable-cache")]
     disable_cache: bool,
 
-    /// Input file
+    /// Input file, in the wasm binary or text format, or an `http(s)://` URL or
+    /// `namespace/package@version` registry package to download
     #[structopt(parse(from_os_str))]
     path: PathBuf,
 
+    /// The checksum a downloaded module or package must have, as printed when it
+    /// is first downloaded
+    #[structopt(long = "checksum")]
+    checksum: Option<String>,
+
     /// Name of the backend to use. (x86_64)
     #[cfg(target_arch = "x86_64")]
     #[structopt(
@@ -162,9 +242,109 @@ struct Run {
     )]
     backend: Backend,
 
-    /// Invoke a specified function
-    #[structopt(long = "invoke", short = "i")]
-    invoke: Option<String>,
+    /// Load the compiler backend from a dynamic library instead of using --backend
+    #[structopt(long = "backend-path", parse(from_os_str))]
+    backend_path: Option<PathBuf>,
+
+    /// Only run the file if it's signed by one of these hex-encoded public
+    /// keys, as written by `wasmer sign --generate-key`
+    #[structopt(long = "trust-key", parse(from_os_str))]
+    trust_keys: Vec<PathBuf>,
+
+    /// Check this detached signature of the file instead of its signature section
+    #[structopt(long = "signature", parse(from_os_str), requires = "trust-key")]
+    signature: Option<PathBuf>,
+
+    /// Allocate memories with guard pages (`static`), or bounds-check each
+    /// access instead (`dynamic`), which takes much less virtual memory
+    #[structopt(
+        long = "memory-style",
+        default_value = "default",
+        case_insensitive = true,
+        possible_values = MemoryStyle::variants(),
+    )]
+    memory_style: MemoryStyle,
+
+    /// Mask bounds-checked memory and table accesses and fence indirect calls
+    /// against speculative execution attacks, at a cost in performance
+    #[structopt(long = "spectre-mitigations")]
+    spectre_mitigations: bool,
+
+    /// Run deterministically: canonicalize NaNs, reject the threads proposal,
+    /// meter execution, and give WASI modules a logical clock and seeded
+    /// randomness
+    #[structopt(long = "deterministic")]
+    deterministic: bool,
+
+    /// Trap once the module used this many metering points
+    #[structopt(long = "points-limit", requires = "deterministic")]
+    points_limit: Option<u64>,
+
+    /// Meter the module, and stop it once it used this many points
+    #[structopt(long = "gas-limit", conflicts_with = "points-limit")]
+    gas_limit: Option<u64>,
+
+    /// Meter the module, and print how many points it used once it stops
+    #[structopt(long = "print-gas-used")]
+    print_gas_used: bool,
+
+    /// The size each memory of the module may reach, e.g. `256MiB`
+    #[structopt(long = "max-memory", parse(try_from_str = utils::parse_size))]
+    max_memory: Option<Bytes>,
+
+    /// The number of elements each table of the module may reach
+    #[structopt(long = "max-table-elements")]
+    max_table_elements: Option<u32>,
+
+    /// The number of instances that may exist at once, including those WASI
+    /// threads run in
+    #[structopt(long = "max-instances")]
+    max_instances: Option<usize>,
+
+    /// Seed of the randomness given to WASI modules
+    #[structopt(long = "random-seed", requires = "deterministic")]
+    random_seed: Option<u64>,
+
+    /// Print how long each phase of compiling the module took
+    #[structopt(long = "verbose", short = "v")]
+    verbose: bool,
+
+    /// Let the `auto` backend pick LLVM, which generates faster code but
+    /// compiles more slowly
+    #[structopt(long = "optimize")]
+    optimize: bool,
+
+    /// Compile the module and check that the ABI it would run with (WASI,
+    /// Emscripten or none) provides all its imports, without running it
+    #[structopt(long = "check")]
+    check: bool,
+
+    /// Sample the wasm call stack while the module runs, and write the
+    /// samples to this file in the folded stacks format of flamegraphs
+    #[cfg(unix)]
+    #[structopt(long = "profile", parse(from_os_str))]
+    profile: Option<PathBuf>,
+
+    /// Interval between two samples of `--profile`, in microseconds
+    #[cfg(unix)]
+    #[structopt(
+        long = "profile-interval",
+        default_value = "1000",
+        requires = "profile"
+    )]
+    profile_interval: u64,
+
+    /// Stop the module if it's still running after this long, e.g. `5s` or
+    /// `500ms`, and exit with status 124
+    #[cfg(unix)]
+    #[structopt(long = "timeout", parse(try_from_str = utils::parse_duration))]
+    timeout: Option<std::time::Duration>,
+
+    /// Invoke a specified function, with its arguments after `--` or inline,
+    /// e.g. `--invoke "process 1 2"`.  Repeat it to call several functions in
+    /// sequence on the same instance, sharing its memory and WASI state
+    #[structopt(long = "invoke", short = "i", number_of_values = 1)]
+    invoke: Vec<String>,
 
     /// Emscripten symbol map
     #[structopt(long = "em-symbol-map", parse(from_os_str), group = "emscripten")]
@@ -174,6 +354,21 @@ struct Run {
     #[structopt(long = "em-entrypoint", group = "emscripten")]
     em_entrypoint: Option<String>,
 
+    /// Emscripten side module that `dlopen` can load by its file name, for
+    /// modules built with `-s MAIN_MODULE`
+    #[structopt(long = "em-side-module", parse(from_os_str), multiple = true)]
+    em_side_modules: Vec<PathBuf>,
+
+    /// Lets Emscripten sockets use the host's network, for the `host`,
+    /// `host:port`, `*` or `*:port` given. Networking is denied without it
+    #[structopt(long = "em-allow-net", multiple = true)]
+    em_allow_net: Vec<wasmer_emscripten::NetworkRule>,
+
+    /// WASI function to run instead of `_start`, or after `_initialize` for
+    /// WASI reactors
+    #[structopt(long = "entrypoint")]
+    entrypoint: Option<String>,
+
     /// WASI pre-opened directory
     #[structopt(long = "dir", multiple = true, group = "wasi")]
     pre_opened_directories: Vec<PathBuf>,
@@ -182,10 +377,49 @@ struct Run {
     #[structopt(long = "mapdir", multiple = true)]
     mapped_dirs: Vec<String>,
 
+    /// Allow WASI path resolution to follow `..` and symlinks out of the
+    /// directory a path is resolved from
+    #[structopt(long = "disable-strict-sandbox")]
+    disable_strict_sandbox: bool,
+
+    /// Record the results of the WASI syscalls the module makes to this
+    /// file, to replay them with `--replay`
+    #[structopt(long = "record", parse(from_os_str), conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay the WASI syscall results recorded with `--record` instead of
+    /// running the syscalls
+    #[structopt(long = "replay", parse(from_os_str))]
+    replay: Option<PathBuf>,
+
+    /// Read the standard input of a WASI module from this file, or from
+    /// wasmer's standard input with `-`
+    #[structopt(long = "stdin", parse(from_os_str))]
+    stdin: Option<PathBuf>,
+
+    /// Write the standard output of a WASI or Emscripten module to this file,
+    /// or to wasmer's standard output with `-`
+    #[structopt(long = "stdout", parse(from_os_str))]
+    stdout: Option<PathBuf>,
+
+    /// Write the standard error of a WASI or Emscripten module to this file,
+    /// or to wasmer's standard error with `-`
+    #[structopt(long = "stderr", parse(from_os_str))]
+    stderr: Option<PathBuf>,
+
     /// Pass custom environment variables
     #[structopt(long = "env", multiple = true)]
     env_vars: Vec<String>,
 
+    /// Pass the environment variables defined in a file of `NAME=value` lines
+    #[structopt(long = "env-file", parse(from_os_str), multiple = true)]
+    env_files: Vec<PathBuf>,
+
+    /// Pass the host environment variables whose names match a pattern, in
+    /// which `*` matches any characters, e.g. `AWS_*`
+    #[structopt(long = "inherit-env", multiple = true)]
+    inherit_env: Vec<String>,
+
     /// Custom code loader
     #[structopt(
         long = "loader",
@@ -199,6 +433,12 @@ struct Run {
     #[structopt(long = "resume")]
     resume: Option<String>,
 
+    /// Host path prefix a resumed WASI checkpoint should find its files under
+    /// instead of the one it was taken with, e.g. `/old/data:/new/data`
+    #[cfg(feature = "managed")]
+    #[structopt(long = "remap-host-path", multiple = true)]
+    remapped_host_paths: Vec<String>,
+
     /// Optimized backends for higher tiers.
     #[cfg(feature = "managed")]
     #[structopt(
@@ -241,6 +481,10 @@ struct Run {
     #[structopt(flatten)]
     features: PrestandardFeatures,
 
+    /// Print errors, such as traps with their backtrace, as `text` or `json`
+    #[structopt(long = "output", default_value = "text", possible_values = &["text", "json"])]
+    output: OutputFormat,
+
     /// Enable non-standard experimental IO devices
     #[cfg(feature = "experimental-io-devices")]
     #[structopt(long = "enable-experimental-io-devices", hidden = true)]
@@ -255,7 +499,90 @@ impl Run {
     /// Used with the `invoke` argument
     fn parse_args(&self, module: &Module, fn_name: &str) -> Result<Vec<Value>, String> {
         utils::parse_args(module, fn_name, &self.args)
-            .map_err(|e| format!("Invoke failed: {:?}", e))
+            .map_err(|e| format!("Can't invoke `{}`: {}", fn_name, e))
+    }
+
+    /// The functions to call with `--invoke`, in order, with their arguments:
+    /// those given inline, or the application arguments for a function given
+    /// without any
+    fn invocations(&self, module: &Module) -> Result<Vec<(String, Vec<Value>)>, String> {
+        self.invoke
+            .iter()
+            .map(|invocation| {
+                let mut words = invocation.split_whitespace();
+                let fn_name = words
+                    .next()
+                    .ok_or_else(|| "`--invoke` needs the name of a function".to_string())?;
+                let inline_args: Vec<String> = words.map(str::to_string).collect();
+                let args = if inline_args.is_empty() {
+                    self.parse_args(module, fn_name)?
+                } else {
+                    utils::parse_args(module, fn_name, &inline_args)
+                        .map_err(|e| format!("Can't invoke `{}`: {}", fn_name, e))?
+                };
+                Ok((fn_name.to_string(), args))
+            })
+            .collect()
+    }
+
+    /// The features to compile the module with
+    fn backend_features(&self) -> Features {
+        Features {
+            deterministic: self.deterministic,
+            ..self.features.into_backend_features()
+        }
+    }
+
+    /// The points limit to meter the module with, if it's metered
+    fn metering_limit(&self) -> Option<u64> {
+        if self.deterministic || self.gas_limit.is_some() || self.print_gas_used {
+            Some(
+                self.gas_limit
+                    .or(self.points_limit)
+                    .unwrap_or(std::u64::MAX),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// The limits given with `--max-memory`, `--max-table-elements` and
+    /// `--max-instances`, if any was
+    fn resource_limits(&self) -> Option<ResourceLimits> {
+        let limits = ResourceLimits {
+            max_memory: self.max_memory,
+            max_table_elements: self.max_table_elements,
+            max_instances: self.max_instances,
+        };
+        if limits == ResourceLimits::default() {
+            None
+        } else {
+            Some(limits)
+        }
+    }
+
+    /// Whether `--profile` was given
+    #[cfg(unix)]
+    fn is_profiling(&self) -> bool {
+        self.profile.is_some()
+    }
+
+    /// Whether `--profile` was given
+    #[cfg(not(unix))]
+    fn is_profiling(&self) -> bool {
+        false
+    }
+
+    /// Whether `--timeout` was given
+    #[cfg(unix)]
+    fn has_timeout(&self) -> bool {
+        self.timeout.is_some()
+    }
+
+    /// Whether `--timeout` was given
+    #[cfg(not(unix))]
+    fn has_timeout(&self) -> bool {
+        false
     }
 }
 
@@ -308,6 +635,156 @@ struct Validate {
 
     #[structopt(flatten)]
     features: PrestandardFeatures,
+
+    /// Print the result as `text` or `json`
+    #[structopt(long = "output", default_value = "text", possible_values = &["text", "json"])]
+    output: OutputFormat,
+}
+
+#[derive(Debug, StructOpt)]
+struct Inspect {
+    /// Input file, in the wasm binary or text format
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Name of the backend to use
+    #[structopt(
+        long = "backend",
+        default_value = "auto",
+        case_insensitive = true,
+        possible_values = Backend::variants(),
+    )]
+    backend: Backend,
+
+    #[structopt(flatten)]
+    features: PrestandardFeatures,
+
+    /// Print the metadata as `text` or `json`
+    #[structopt(long = "output", default_value = "text", possible_values = &["text", "json"])]
+    output: OutputFormat,
+}
+
+#[derive(Debug, StructOpt)]
+struct Repl {
+    /// Input file, in the wasm binary or text format
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Name of the backend to use
+    #[structopt(
+        long = "backend",
+        default_value = "auto",
+        case_insensitive = true,
+        possible_values = Backend::variants(),
+    )]
+    backend: Backend,
+
+    /// WASI pre-opened directory
+    #[cfg(feature = "wasi")]
+    #[structopt(long = "dir", multiple = true)]
+    pre_opened_directories: Vec<PathBuf>,
+
+    /// Map a host directory to a different location for the wasm module
+    #[cfg(feature = "wasi")]
+    #[structopt(long = "mapdir", multiple = true)]
+    mapped_dirs: Vec<String>,
+
+    #[structopt(flatten)]
+    features: PrestandardFeatures,
+}
+
+#[derive(Debug, StructOpt)]
+struct Wast {
+    /// The scripts to run
+    #[structopt(parse(from_os_str), required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Name of the backend to use
+    #[structopt(
+        long = "backend",
+        default_value = "auto",
+        case_insensitive = true,
+        possible_values = Backend::variants(),
+    )]
+    backend: Backend,
+
+    #[structopt(flatten)]
+    features: PrestandardFeatures,
+}
+
+#[derive(Debug, StructOpt)]
+struct Sign {
+    /// Input file, signed as is, so a wat file is signed as text
+    #[structopt(parse(from_os_str), required_unless = "generate-key")]
+    path: Option<PathBuf>,
+
+    /// Output file. Defaults to the input file with a `.signed.wasm`
+    /// extension, or a `.sig` extension with `--detached`
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Hex-encoded key pair to sign with
+    #[structopt(long = "key", parse(from_os_str))]
+    key: PathBuf,
+
+    /// Generate a new key pair into the `--key` file, and write its public
+    /// key next to it with a `.pub` extension
+    #[structopt(long = "generate-key")]
+    generate_key: bool,
+
+    /// Write a hex-encoded detached signature instead of appending a
+    /// signature section to the file
+    #[structopt(long = "detached")]
+    detached: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct Compile {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Output file
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: PathBuf,
+
+    /// Name of the backend to use
+    #[structopt(
+        long = "backend",
+        default_value = "auto",
+        case_insensitive = true,
+        possible_values = Backend::variants(),
+    )]
+    backend: Backend,
+
+    /// Target triple to compile for, e.g. `aarch64-unknown-linux-gnu`.
+    /// Defaults to the host
+    #[structopt(long = "target")]
+    target: Option<Triple>,
+
+    /// CPU features the code may use, e.g. `+sse4.2,+avx`. Defaults to the
+    /// host's when compiling for the host, and to none otherwise. Artifacts
+    /// can only be loaded on machines having all of them
+    #[structopt(long = "cpu-features")]
+    cpu_features: Option<CpuFeatures>,
+
+    /// Allocate memories with guard pages (`static`), or bounds-check each
+    /// access instead (`dynamic`), which takes much less virtual memory
+    #[structopt(
+        long = "memory-style",
+        default_value = "default",
+        case_insensitive = true,
+        possible_values = MemoryStyle::variants(),
+    )]
+    memory_style: MemoryStyle,
+
+    /// Mask bounds-checked memory and table accesses and fence indirect calls
+    /// against speculative execution attacks, at a cost in performance
+    #[structopt(long = "spectre-mitigations")]
+    spectre_mitigations: bool,
+
+    #[structopt(flatten)]
+    features: PrestandardFeatures,
 }
 
 /// Read the contents of a file
@@ -320,6 +797,67 @@ fn read_file_contents(path: &PathBuf) -> Result<Vec<u8>, io::Error> {
     Ok(buffer)
 }
 
+/// Assemble the contents of a file into a wasm binary if they are in the
+/// text format, so that wat files can be used wherever wasm files are
+fn wat_to_wasm(
+    contents: Vec<u8>,
+    path: &PathBuf,
+    features: &PrestandardFeatures,
+) -> Result<Vec<u8>, String> {
+    if utils::is_wasm_binary(&contents) {
+        return Ok(contents);
+    }
+    wabt::wat2wasm_with_features(contents, features.into_wabt_features()).map_err(|e| {
+        format!(
+            "Can't assemble the text file {}: {:?}",
+            path.as_os_str().to_string_lossy(),
+            e
+        )
+    })
+}
+
+/// Read a hex-encoded key or signature file
+fn read_hex_file(path: &PathBuf) -> Result<Vec<u8>, String> {
+    let contents = read_to_string(path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    hex::decode(contents.trim()).map_err(|err| {
+        format!(
+            "Can't decode the file {}: {}",
+            path.as_os_str().to_string_lossy(),
+            err
+        )
+    })
+}
+
+/// Verify that the file was signed by one of the keys trusted by `options`
+fn verify_wasm(wasm_binary: &[u8], options: &Run) -> Result<(), String> {
+    let trusted_keys = options
+        .trust_keys
+        .iter()
+        .map(|path| {
+            PublicKey::from_bytes(&read_hex_file(path)?).map_err(|err| {
+                format!(
+                    "Invalid public key {}: {}",
+                    path.as_os_str().to_string_lossy(),
+                    err
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    match options.signature {
+        Some(ref path) => {
+            signing::verify_detached(wasm_binary, &read_hex_file(path)?, &trusted_keys)
+        }
+        None => signing::verify(wasm_binary, &trusted_keys),
+    }
+    .map_err(|err| format!("Can't verify the file: {}", err))
+}
+
 fn get_cache_dir() -> PathBuf {
     match env::var("WASMER_CACHE_DIR") {
         Ok(dir) => {
@@ -363,12 +901,42 @@ fn get_mapped_dirs(input: &[String]) -> Result<Vec<(String, PathBuf)>, String> {
     Ok(md)
 }
 
+/// Collects the environment variables of a WASI module: the host variables
+/// matching `--inherit-env`, then those of the `--env-file`s, then those
+/// given with `--env`, each overriding the previous ones.
 #[cfg(feature = "wasi")]
-fn get_env_var_args(input: &[String]) -> Result<Vec<(&str, &str)>, String> {
+fn get_env_var_args(options: &Run) -> Result<Vec<(String, String)>, String> {
+    fn set(ev: &mut Vec<(String, String)>, name: String, value: String) {
+        match ev.iter_mut().find(|(existing, _)| *existing == name) {
+            Some(entry) => entry.1 = value,
+            None => ev.push((name, value)),
+        }
+    }
+
     let mut ev = vec![];
-    for entry in input.iter() {
+    if !options.inherit_env.is_empty() {
+        for (name, value) in env::vars() {
+            if options
+                .inherit_env
+                .iter()
+                .any(|pattern| utils::matches_pattern(pattern, &name))
+            {
+                set(&mut ev, name, value);
+            }
+        }
+    }
+    for path in options.env_files.iter() {
+        let contents = read_to_string(path)
+            .map_err(|e| format!("Can't read the env file {}: {}", path.display(), e))?;
+        let vars = utils::parse_env_file(&contents)
+            .map_err(|e| format!("Invalid env file {}: {}", path.display(), e))?;
+        for (name, value) in vars {
+            set(&mut ev, name, value);
+        }
+    }
+    for entry in options.env_vars.iter() {
         if let [env_var, value] = entry.split('=').collect::<Vec<&str>>()[..] {
-            ev.push((env_var, value));
+            set(&mut ev, env_var.to_string(), value.to_string());
         } else {
             return Err(format!(
                 "Env vars must be of the form <var_name>=<value>. Found {}",
@@ -382,13 +950,12 @@ fn get_env_var_args(input: &[String]) -> Result<Vec<(&str, &str)>, String> {
 /// Helper function for `execute_wasm` (the `Run` command)
 #[cfg(feature = "wasi")]
 fn execute_wasi(
-    wasi_version: wasmer_wasi::WasiVersion,
     options: &Run,
-    env_vars: Vec<(&str, &str)>,
+    env_vars: Vec<(String, String)>,
     module: wasmer_runtime_core::Module,
     mapped_dirs: Vec<(String, PathBuf)>,
     _wasm_binary: &[u8],
-) -> Result<(), String> {
+) -> Result<(), Diagnostic> {
     let name = if let Some(cn) = &options.command_name {
         cn.clone()
     } else {
@@ -402,7 +969,32 @@ fn execute_wasi(
         .args(args)
         .envs(env_vars)
         .preopen_dirs(preopened_files)
-        .map_dirs(mapped_dirs);
+        .map_dirs(mapped_dirs)
+        .sandbox_strict(!options.disable_strict_sandbox);
+    if options.deterministic {
+        wasi_state_builder.deterministic(options.random_seed.unwrap_or(0));
+    }
+    let recorder = options
+        .record
+        .as_ref()
+        .map(|_| Arc::new(wasmer_wasi::state::WasiRecorder::new()));
+    if let Some(recorder) = &recorder {
+        wasi_state_builder.record(recorder.clone());
+    }
+    let replayer = match &options.replay {
+        Some(path) => {
+            let bytes =
+                fs::read(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+            let trace = wasmer_wasi::state::WasiTrace::from_bytes(&bytes)
+                .ok_or_else(|| format!("{} isn't a WASI trace", path.display()))?;
+            Some(Arc::new(wasmer_wasi::state::WasiReplayer::new(trace)))
+        }
+        None => None,
+    };
+    if let Some(replayer) = &replayer {
+        wasi_state_builder.replay(replayer.clone());
+    }
+    let trace = WasiTraceFiles { recorder, replayer };
 
     #[cfg(feature = "experimental-io-devices")]
     {
@@ -414,36 +1006,62 @@ fn execute_wasi(
     }
     let wasi_state = wasi_state_builder.build().map_err(|e| format!("{:?}", e))?;
 
-    let import_object = wasmer_wasi::generate_import_object_from_state(wasi_state, wasi_version);
+    #[cfg(feature = "managed")]
+    let (mut wasi_state, resume_image) = read_resume_image(options, wasi_state)?;
+    #[cfg(not(feature = "managed"))]
+    let mut wasi_state = wasi_state;
+    redirect_wasi_stdio(options, &mut wasi_state)?;
+
+    let mode = wasmer_wasi::reactor::module_mode(&module).map_err(|e| format!("{}", e))?;
+    if mode == wasmer_wasi::reactor::WasiMode::Reactor {
+        return execute_wasi_reactor(options, &module, wasi_state, &trace)
+            .map_err(Diagnostic::from);
+    }
+
+    let wasi_threads;
+    let import_object = if wasmer_wasi::threads::is_wasi_threads_module(&module) {
+        if options.deterministic {
+            return Err("WASI threads can't run deterministically".into());
+        }
+        if options.record.is_some() || options.replay.is_some() {
+            return Err("WASI threads can't be recorded or replayed".into());
+        }
+        let threads = wasmer_wasi::threads::WasiThreads::new(&module, wasi_state)
+            .map_err(|e| format!("{}", e))?;
+        let import_object = threads.import_object();
+        wasi_threads = Some(threads);
+        import_object
+    } else {
+        wasi_threads = None;
+        wasmer_wasi::generate_import_object_from_state_for_module(&module, wasi_state)
+    };
 
     #[allow(unused_mut)] // mut used in feature
     let mut instance = module
         .instantiate(&import_object)
         .map_err(|e| format!("Can't instantiate WASI module: {:?}", e))?;
 
+    let entrypoint = options.entrypoint.as_ref().map_or("_start", String::as_str);
     let start: wasmer_runtime::Func<(), ()> =
-        instance.func("_start").map_err(|e| format!("{:?}", e))?;
+        instance.func(entrypoint).map_err(|e| format!("{:?}", e))?;
 
     #[cfg(feature = "managed")]
     {
         let start_raw: extern "C" fn(&mut wasmer_runtime_core::vm::Ctx) =
             unsafe { ::std::mem::transmute(start.get_vm_func()) };
+        // the guest is suspended while the shell runs, so its state can be read
+        let wasi_state = instance.context_mut().data as *const wasmer_wasi::state::WasiState;
+        let checkpoint = |image: &wasmer_runtime_core::state::InstanceImage| {
+            wasmer_wasi::state::WasiCheckpoint::new(image.clone(), unsafe { &*wasi_state })
+                .map(|checkpoint| checkpoint.to_bytes())
+                .ok_or_else(|| "The WASI state can not be serialized".to_string())
+        };
 
-        unsafe {
+        let result = unsafe {
             run_tiering(
                 module.info(),
                 &_wasm_binary,
-                if let Some(ref path) = options.resume {
-                    let mut f = File::open(path).unwrap();
-                    let mut out: Vec<u8> = vec![];
-                    f.read_to_end(&mut out).unwrap();
-                    Some(
-                        wasmer_runtime_core::state::InstanceImage::from_bytes(&out)
-                            .map_err(|_| format!("failed to decode image"))?,
-                    )
-                } else {
-                    None
-                },
+                resume_image,
                 &import_object,
                 start_raw,
                 &mut instance,
@@ -463,14 +1081,16 @@ fn execute_wasi(
                         },
                     )
                     .collect(),
-                interactive_shell,
-            )?
+                |ctx| interactive_shell(ctx, &checkpoint),
+            )
         };
+        print_gas_used(options, &instance);
+        trace.finish(options)?;
+        result?;
     }
 
     #[cfg(not(feature = "managed"))]
     {
-        use wasmer_runtime::error::RuntimeError;
         #[cfg(unix)]
         use wasmer_runtime_core::{
             fault::{pop_code_version, push_code_version},
@@ -493,15 +1113,28 @@ fn execute_wasi(
             false
         };
 
-        if let Some(invoke_fn) = options.invoke.as_ref() {
+        #[cfg(unix)]
+        start_profiling(options, &instance)?;
+
+        if !options.invoke.is_empty() {
             eprintln!("WARNING: Invoking aribtrary functions with WASI is not officially supported in the WASI standard yet.  Use this feature at your own risk!");
-            let args = options.parse_args(&module, invoke_fn)?;
-            let invoke_result = instance
-                .dyn_func(invoke_fn)
-                .map_err(|e| format!("Invoke failed: {:?}", e))?
-                .call(&args)
-                .map_err(|e| format!("Calling invoke fn failed: {:?}", e))?;
-            println!("{}({:?}) returned {:?}", invoke_fn, args, invoke_result);
+            let invocations = options.invocations(&module)?;
+            let invoke_result = call_invocations(&instance, &invocations);
+            #[cfg(unix)]
+            write_profile(options, &instance)?;
+            print_gas_used(options, &instance);
+            trace.finish(options)?;
+            invoke_result.map_err(|e| {
+                if let Some(exit) = wasmer_wasi::WasiExit::from_call_error(&e) {
+                    std::process::exit(exit.code as i32)
+                }
+                match e {
+                    CallError::Runtime(ref err) if ran_out_of_gas(err) => {
+                        out_of_gas_message(options).into()
+                    }
+                    e => Diagnostic::call(&e, format!("Calling invoke fn failed: {:?}", e)),
+                }
+            })?;
             return Ok(());
         } else {
             result = start.call();
@@ -512,56 +1145,439 @@ fn execute_wasi(
             if cv_pushed {
                 pop_code_version().unwrap();
             }
+            write_profile(options, &instance)?;
         }
+        print_gas_used(options, &instance);
+        trace.finish(options)?;
 
         if let Err(ref err) = result {
-            match err {
-                RuntimeError::Trap { msg } => return Err(format!("wasm trap occured: {}", msg)),
-                RuntimeError::Error { data } => {
-                    if let Some(error_code) = data.downcast_ref::<wasmer_wasi::ExitCode>() {
-                        std::process::exit(error_code.code as i32)
-                    }
+            if let Some(exit) = wasmer_wasi::WasiExit::from_runtime_error(err) {
+                std::process::exit(exit.code as i32)
+            }
+            if let Some(diverged) = wasmer_wasi::state::WasiReplayDiverged::from_runtime_error(err)
+            {
+                return Err(diverged.to_string().into());
+            }
+            if ran_out_of_gas(err) {
+                return Err(out_of_gas_message(options).into());
+            }
+            return Err(Diagnostic::trap(err).unwrap_or_else(|| format!("error: {:?}", err).into()));
+        }
+    }
+
+    if let Some(threads) = wasi_threads {
+        for (tid, result) in threads.join() {
+            match result {
+                Ok(()) => (),
+                Err(wasmer_wasi::threads::WasiThreadError::Exit(code)) => {
+                    std::process::exit(code as i32)
                 }
+                Err(e) => return Err(format!("wasi thread {}: {}", tid, e).into()),
             }
-            return Err(format!("error: {:?}", err));
         }
     }
     Ok(())
 }
 
-#[cfg(feature = "backend-llvm")]
-impl LLVMCallbacks for LLVMCLIOptions {
-    fn preopt_ir_callback(&mut self, module: &InkwellModule) {
-        if let Some(filename) = &self.pre_opt_ir {
-            module.print_to_file(filename).unwrap();
+/// The recorder of `--record` and the replayer of `--replay`
+#[cfg(feature = "wasi")]
+struct WasiTraceFiles {
+    recorder: Option<Arc<wasmer_wasi::state::WasiRecorder>>,
+    replayer: Option<Arc<wasmer_wasi::state::WasiReplayer>>,
+}
+
+#[cfg(feature = "wasi")]
+impl WasiTraceFiles {
+    /// Writes the syscalls recorded with `--record`, and warns when
+    /// `--replay` didn't replay all the recorded syscalls
+    fn finish(&self, options: &Run) -> Result<(), String> {
+        if let (Some(recorder), Some(path)) = (&self.recorder, &options.record) {
+            fs::write(path, recorder.trace().to_bytes())
+                .map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+        }
+        if let Some(replayer) = &self.replayer {
+            if !replayer.is_finished() {
+                eprintln!("WARNING: the module made fewer syscalls than were recorded");
+            }
         }
+        Ok(())
     }
+}
 
-    fn postopt_ir_callback(&mut self, module: &InkwellModule) {
-        if let Some(filename) = &self.post_opt_ir {
-            module.print_to_file(filename).unwrap();
+/// Helper function for `execute_wasm`: sends what an Emscripten module writes
+/// to its standard output and error to the files given by `--stdout` and
+/// `--stderr`.
+fn redirect_emscripten_stdio(
+    options: &Run,
+    emscripten_globals: &mut wasmer_emscripten::EmscriptenGlobals,
+) -> Result<(), String> {
+    use std::io::Write;
+    use wasmer_emscripten::StdioSink;
+
+    let streams = [(&options.stdout, true), (&options.stderr, false)];
+    for (path, stdout) in streams.iter() {
+        // `-` keeps the stream inherited from wasmer.
+        let path = match path {
+            Some(path) if path.as_os_str() != "-" => path,
+            _ => continue,
+        };
+        let mut file =
+            File::create(path).map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+        let sink = StdioSink::callback(move |bytes| {
+            let _ = file.write_all(bytes);
+        });
+        if *stdout {
+            emscripten_globals.set_stdout(sink);
+        } else {
+            emscripten_globals.set_stderr(sink);
         }
     }
+    Ok(())
+}
 
-    fn obj_memory_buffer_callback(&mut self, memory_buffer: &InkwellMemoryBuffer) {
-        if let Some(filename) = &self.obj_file {
-            let mem_buf_slice = memory_buffer.as_slice();
-            let mut file = File::create(filename).unwrap();
-            let mut pos = 0;
-            while pos < mem_buf_slice.len() {
-                pos += file.write(&mem_buf_slice[pos..]).unwrap();
-            }
+/// Helper function for `execute_wasi`: backs the standard streams of the
+/// module with the files given by `--stdin`, `--stdout` and `--stderr`.
+#[cfg(feature = "wasi")]
+fn redirect_wasi_stdio(
+    options: &Run,
+    wasi_state: &mut wasmer_wasi::state::WasiState,
+) -> Result<(), String> {
+    use wasmer_wasi::{
+        state::HostFile,
+        types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO},
+    };
+
+    let streams = [
+        (__WASI_STDIN_FILENO, &options.stdin, false),
+        (__WASI_STDOUT_FILENO, &options.stdout, true),
+        (__WASI_STDERR_FILENO, &options.stderr, true),
+    ];
+    for (fd, path, write) in streams.iter() {
+        // `-` keeps the stream inherited from wasmer.
+        let path = match path {
+            Some(path) if path.as_os_str() != "-" => path,
+            _ => continue,
+        };
+        let file = if *write {
+            File::create(path)
+        } else {
+            File::open(path)
         }
+        .map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+        let file = HostFile::new(file, path.clone(), !*write, *write, false);
+        wasi_state
+            .fs
+            .swap_file(*fd, Box::new(file))
+            .map_err(|e| format!("Can't redirect the stream {}: {:?}", fd, e))?;
     }
+    Ok(())
 }
 
-/// Execute a wasm/wat file
-fn execute_wasm(options: &Run) -> Result<(), String> {
-    let disable_cache = options.disable_cache;
+/// Helper function for `execute_wasi`: reads the image given with `--resume`.
+/// WASI checkpoints also replace `wasi_state` with the state they were taken
+/// with.
+#[cfg(all(feature = "wasi", feature = "managed"))]
+fn read_resume_image(
+    options: &Run,
+    wasi_state: wasmer_wasi::state::WasiState,
+) -> Result<
+    (
+        wasmer_wasi::state::WasiState,
+        Option<wasmer_runtime_core::state::InstanceImage>,
+    ),
+    String,
+> {
+    use wasmer_wasi::state::{HostPathRemap, WasiCheckpoint};
+
+    let path = match options.resume {
+        Some(ref path) => path,
+        None => return Ok((wasi_state, None)),
+    };
+    let mut f = File::open(path).map_err(|e| format!("Can't open {}: {}", path, e))?;
+    let mut out: Vec<u8> = vec![];
+    f.read_to_end(&mut out)
+        .map_err(|e| format!("Can't read {}: {}", path, e))?;
+
+    if let Some(checkpoint) = WasiCheckpoint::from_bytes(&out) {
+        let mut remap = HostPathRemap::new();
+        for entry in options.remapped_host_paths.iter() {
+            match entry.split(':').collect::<Vec<&str>>()[..] {
+                [from, to] => remap.map(from, to),
+                _ => {
+                    return Err(format!(
+                        "Host path remaps must be of the form <from>:<to>, got {}",
+                        entry
+                    ))
+                }
+            };
+        }
+        let wasi_state = checkpoint
+            .restore_wasi_state(&remap)
+            .map_err(|e| format!("Can't restore the WASI state: {}", e))?;
+        return Ok((wasi_state, Some(checkpoint.image)));
+    }
+
+    let image = wasmer_runtime_core::state::InstanceImage::from_bytes(&out)
+        .ok_or_else(|| format!("failed to decode image"))?;
+    Ok((wasi_state, Some(image)))
+}
+
+/// Helper function for `execute_wasi`: initializes a WASI reactor and calls
+/// the function given with `--invoke` or `--entrypoint`
+#[cfg(feature = "wasi")]
+fn execute_wasi_reactor(
+    options: &Run,
+    module: &wasmer_runtime_core::Module,
+    wasi_state: wasmer_wasi::state::WasiState,
+    trace: &WasiTraceFiles,
+) -> Result<(), String> {
+    use wasmer_wasi::reactor::{WasiReactor, WasiReactorError};
+
+    if options.invoke.is_empty() && options.entrypoint.is_none() {
+        return Err(
+            "The module is a WASI reactor: use `--invoke` or `--entrypoint` to call one of its functions"
+                .to_string(),
+        );
+    }
+
+    let result = WasiReactor::new(module, wasi_state).and_then(|reactor| {
+        let result = call_wasi_reactor(options, module, &reactor);
+        print_gas_used(options, reactor.instance());
+        result
+    });
+    trace.finish(options)?;
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(WasiReactorError::Exit(code)) => std::process::exit(code as i32),
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
+/// Helper function for `execute_wasi_reactor`: calls the function given
+/// with `--invoke` or `--entrypoint`
+#[cfg(feature = "wasi")]
+fn call_wasi_reactor(
+    options: &Run,
+    module: &wasmer_runtime_core::Module,
+    reactor: &wasmer_wasi::reactor::WasiReactor,
+) -> Result<(), wasmer_wasi::reactor::WasiReactorError> {
+    if !options.invoke.is_empty() {
+        let invocations = options
+            .invocations(module)
+            .map_err(wasmer_wasi::reactor::WasiReactorError::Runtime)?;
+        for (invoke_fn, args) in &invocations {
+            let invoke_result = reactor.call(invoke_fn, args)?;
+            print_invoke_result(invoke_fn, args, &invoke_result);
+        }
+    } else if let Some(entrypoint) = options.entrypoint.as_ref() {
+        reactor.call(entrypoint, &[])?;
+    }
+    Ok(())
+}
+
+/// Helper function for `execute_wasm` and `execute_wasi`: calls the functions
+/// given with `--invoke` in order on `instance`, printing what each returned,
+/// and stops at the first one that fails
+fn call_invocations(
+    instance: &wasmer_runtime::Instance,
+    invocations: &[(String, Vec<Value>)],
+) -> Result<(), CallError> {
+    for (invoke_fn, args) in invocations {
+        let invoke_result = instance.dyn_func(invoke_fn)?.call(args)?;
+        print_invoke_result(invoke_fn, args, &invoke_result);
+    }
+    Ok(())
+}
+
+/// Helper function for `execute_wasm` and `execute_wasi`: prints the values
+/// the function given with `--invoke` returned
+fn print_invoke_result(invoke_fn: &str, args: &[Value], results: &[Value]) {
+    let results = if results.is_empty() {
+        "nothing".to_string()
+    } else {
+        utils::format_values(results)
+    };
+    println!(
+        "{}({}) returned {}",
+        invoke_fn,
+        utils::format_values(args),
+        results
+    );
+}
+
+/// Helper function for `execute_wasm` and `execute_wasi`: prints the points
+/// `instance` used with `--print-gas-used`
+fn print_gas_used(options: &Run, instance: &wasmer_runtime::Instance) {
+    if options.print_gas_used {
+        eprintln!("Gas used: {}", get_points_used(instance));
+    }
+}
+
+/// Helper function for `execute_wasm` and `execute_wasi`: whether a call
+/// failed because the module used all the points it was metered with
+fn ran_out_of_gas(error: &RuntimeError) -> bool {
+    match error {
+        RuntimeError::Error { data } => {
+            data.downcast_ref::<ExecutionLimitExceededError>().is_some()
+        }
+        _ => false,
+    }
+}
+
+/// Helper function for `execute_wasm` and `execute_wasi`: the error reported
+/// when `ran_out_of_gas`
+fn out_of_gas_message(options: &Run) -> String {
+    format!(
+        "the module ran out of gas: it used all of its {} points",
+        options.metering_limit().unwrap_or(std::u64::MAX)
+    )
+}
+
+#[cfg(feature = "backend-llvm")]
+impl LLVMCallbacks for LLVMCLIOptions {
+    fn preopt_ir_callback(&mut self, module: &InkwellModule) {
+        if let Some(filename) = &self.pre_opt_ir {
+            module.print_to_file(filename).unwrap();
+        }
+    }
+
+    fn postopt_ir_callback(&mut self, module: &InkwellModule) {
+        if let Some(filename) = &self.post_opt_ir {
+            module.print_to_file(filename).unwrap();
+        }
+    }
+
+    fn obj_memory_buffer_callback(&mut self, memory_buffer: &InkwellMemoryBuffer) {
+        if let Some(filename) = &self.obj_file {
+            let mem_buf_slice = memory_buffer.as_slice();
+            let mut file = File::create(filename).unwrap();
+            let mut pos = 0;
+            while pos < mem_buf_slice.len() {
+                pos += file.write(&mem_buf_slice[pos..]).unwrap();
+            }
+        }
+    }
+}
+
+/// Helper function for `execute_wasm`: prints how the module was compiled
+/// with `--verbose`
+fn print_compile_info(module: &Module) {
+    let info = match module.compile_info() {
+        Some(info) => info,
+        None => {
+            eprintln!("The module was loaded from the cache");
+            return;
+        }
+    };
+    let code_size = match info.code_size {
+        Some(size) => format!("{} bytes of code", size),
+        None => "unknown code size".to_string(),
+    };
+    eprintln!(
+        "Compiled {} functions with the {} backend, {}",
+        info.functions,
+        info.backend.to_string(),
+        code_size
+    );
+    eprintln!(
+        "  parse: {:?}, validate: {:?}, codegen: {:?}, link: {:?}",
+        info.parse, info.validate, info.codegen, info.link
+    );
+}
+
+/// Helper function for `execute_wasm` and `execute_wasi`: starts sampling
+/// `instance` with `--profile`
+#[cfg(unix)]
+fn start_profiling(options: &Run, instance: &wasmer_runtime::Instance) -> Result<(), String> {
+    if options.profile.is_some() {
+        instance
+            .start_profiling(std::time::Duration::from_micros(options.profile_interval))
+            .map_err(|e| format!("Can't profile the module: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Helper function for `execute_wasm` and `execute_wasi`: writes the samples
+/// taken with `--profile`
+#[cfg(unix)]
+fn write_profile(options: &Run, instance: &wasmer_runtime::Instance) -> Result<(), String> {
+    if let (Some(path), Some(profile)) = (&options.profile, instance.stop_profiling()) {
+        let mut file =
+            File::create(path).map_err(|e| format!("Can't create {}: {}", path.display(), e))?;
+        profile
+            .write_folded(&mut file)
+            .map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// The exit status of `wasmer run` when the module ran past `--timeout`, as
+/// with the `timeout` command
+#[cfg(unix)]
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// How long the module may take to stop once `--timeout` elapsed, before
+/// the process exits anyway
+#[cfg(unix)]
+const TIMEOUT_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Set once `--timeout` elapsed, to report the error the module stops with
+/// as a timeout
+#[cfg(unix)]
+static TIMED_OUT: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Helper function for `execute_wasm`: stops the module once `--timeout`
+/// elapsed.  The module stops at its next loop iteration or function call;
+/// if it's blocked in a host function instead, the process exits after
+/// `TIMEOUT_GRACE_PERIOD`.
+#[cfg(unix)]
+fn start_timeout(options: &Run) {
+    if let Some(timeout) = options.timeout {
+        std::thread::spawn(move || {
+            std::thread::sleep(timeout);
+            TIMED_OUT.store(true, std::sync::atomic::Ordering::SeqCst);
+            wasmer_runtime_core::fault::terminate_wasm();
+            std::thread::sleep(TIMEOUT_GRACE_PERIOD);
+            exit_timed_out(timeout);
+        });
+    }
+}
+
+#[cfg(unix)]
+fn exit_timed_out(timeout: std::time::Duration) -> ! {
+    eprintln!("Error: the module timed out after {:?}", timeout);
+    exit(TIMEOUT_EXIT_CODE);
+}
+
+/// Execute a wasm/wat file
+fn execute_wasm(options: &Run) -> Result<(), Diagnostic> {
+    // The cache loads modules with the backends built into this binary, and
+    // doesn't key them on the memory style, the spectre mitigations, the
+    // deterministic profile, metering or the state tracking needed by
+    // `--profile`.
+    let disable_cache = options.disable_cache
+        || options.backend_path.is_some()
+        || options.memory_style != MemoryStyle::Default
+        || options.spectre_mitigations
+        || options.deterministic
+        || options.metering_limit().is_some()
+        || options.is_profiling();
+
+    let plugin = match options.backend_path {
+        Some(ref path) => Some(
+            unsafe { wasmer_runtime_core::plugin::load_backend(path) }
+                .map_err(|e| format!("Can't load the backend {}: {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+    let backend = plugin
+        .as_ref()
+        .map_or(options.backend, |plugin| plugin.backend);
 
     let mapped_dirs = get_mapped_dirs(&options.mapped_dirs[..])?;
     #[cfg(feature = "wasi")]
-    let env_vars = get_env_var_args(&options.env_vars[..])?;
+    let env_vars = get_env_var_args(options)?;
     let wasm_path = &options.path;
 
     let mut wasm_binary: Vec<u8> = read_file_contents(wasm_path).map_err(|err| {
@@ -572,6 +1588,10 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         )
     })?;
 
+    if !options.trust_keys.is_empty() {
+        verify_wasm(&wasm_binary, options)?;
+    }
+
     let em_symbol_map = if let Some(em_symbol_map_path) = options.em_symbol_map.clone() {
         let em_symbol_map_content: String = read_to_string(&em_symbol_map_path)
             .map_err(|err| {
@@ -588,10 +1608,10 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             let num_str = if let Some(ns) = split.next() {
                 ns
             } else {
-                return Err(
+                Err(
                     "Can't parse symbol map (expected each entry to be of the form: `0:func_name`)"
                         .to_string(),
-                );
+                )?
             };
             let num: u32 = num_str.parse::<u32>().map_err(|err| {
                 format!(
@@ -602,10 +1622,10 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             let name_str: String = if let Some(name_str) = split.next() {
                 name_str
             } else {
-                return Err(
+                Err(
                     "Can't parse symbol map (expected each entry to be of the form: `0:func_name`)"
                         .to_string(),
-                );
+                )?
             }
             .to_owned();
 
@@ -617,36 +1637,71 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
     };
 
     // Don't error on --enable-all for other backends.
-    if options.features.simd && options.backend != Backend::LLVM {
-        return Err("SIMD is only supported in the LLVM backend for now".to_string());
+    if options.features.simd && backend != Backend::LLVM {
+        return Err("SIMD is only supported in the LLVM backend for now".into());
     }
 
-    if !utils::is_wasm_binary(&wasm_binary) {
-        let features = options.features.into_wabt_features();
-        wasm_binary = wabt::wat2wasm_with_features(wasm_binary, features)
-            .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
+    if options.deterministic && (backend == Backend::Cranelift || plugin.is_some()) {
+        return Err(
+            "--deterministic needs the singlepass, LLVM or interpreter backend, for metering"
+                .into(),
+        );
+    }
+    if options.metering_limit().is_some() && (backend == Backend::Cranelift || plugin.is_some()) {
+        return Err(
+            "--gas-limit and --print-gas-used need the singlepass, LLVM or interpreter backend"
+                .into(),
+        );
+    }
+
+    if options.is_profiling() && (backend != Backend::Singlepass || plugin.is_some()) {
+        return Err("--profile needs the singlepass backend".into());
+    }
+    if options.has_timeout() && (backend != Backend::Singlepass || plugin.is_some()) {
+        return Err("--timeout needs the singlepass backend".into());
+    }
+    #[cfg(feature = "managed")]
+    {
+        if options.is_profiling() {
+            return Err("--profile can't be combined with tiering".into());
+        }
     }
 
-    let compiler: Box<dyn Compiler> = get_compiler_by_backend(options.backend, options)
-        .ok_or_else(|| {
+    wasm_binary = wat_to_wasm(wasm_binary, wasm_path, &options.features)?;
+
+    let compiler: Box<dyn Compiler> = match plugin {
+        Some(plugin) => plugin.compiler,
+        None => get_compiler_by_backend(options.backend, options).ok_or_else(|| {
             format!(
                 "the requested backend, \"{}\", is not enabled",
                 options.backend.to_string()
             )
-        })?;
+        })?,
+    };
 
     #[allow(unused_mut)]
     let mut backend_specific_config = None;
     #[cfg(feature = "backend-llvm")]
     {
-        if options.backend == Backend::LLVM {
+        if options.backend == Backend::LLVM && options.backend_path.is_none() {
+            let llvm_options = &options.backend_llvm_options;
             backend_specific_config = Some(BackendCompilerConfig(Box::new(LLVMBackendConfig {
-                callbacks: Some(Rc::new(RefCell::new(options.backend_llvm_options.clone()))),
+                callbacks: Some(Rc::new(RefCell::new(llvm_options.clone()))),
+                opt_level: llvm_options.opt_level,
+                vectorize: !llvm_options.disable_vectorize,
+                inline_threshold: llvm_options.inline_threshold,
+                dump_ir_dir: llvm_options.dump_ir_dir.clone(),
             })))
         }
     }
 
-    let track_state = options.track_state;
+    let track_state = options.track_state || options.is_profiling();
+
+    let spectre_mitigations = if options.spectre_mitigations {
+        SpectreMitigations::all()
+    } else {
+        SpectreMitigations::default()
+    };
 
     #[cfg(feature = "loader-kernel")]
     let is_kernel_loader = if let Some(LoaderName::Kernel) = options.loader {
@@ -665,27 +1720,31 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                 symbol_map: em_symbol_map.clone(),
                 memory_bound_check_mode: MemoryBoundCheckMode::Disable,
                 enforce_stack_check: true,
+                memory_style: options.memory_style,
+                spectre_mitigations,
                 track_state,
-                features: options.features.into_backend_features(),
+                features: options.backend_features(),
                 backend_specific_config,
                 ..Default::default()
             },
             &*compiler,
         )
-        .map_err(|e| format!("Can't compile module: {:?}", e))?
+        .map_err(|e| compile_error(options.backend_features(), &wasm_binary, e))?
     } else if disable_cache {
         webassembly::compile_with_config_with(
             &wasm_binary[..],
             CompilerConfig {
                 symbol_map: em_symbol_map.clone(),
+                memory_style: options.memory_style,
+                spectre_mitigations,
                 track_state,
-                features: options.features.into_backend_features(),
+                features: options.backend_features(),
                 backend_specific_config,
                 ..Default::default()
             },
             &*compiler,
         )
-        .map_err(|e| format!("Can't compile module: {:?}", e))?
+        .map_err(|e| compile_error(options.backend_features(), &wasm_binary, e))?
     } else {
         // If we have cache enabled
         let wasmer_cache_dir = get_cache_dir();
@@ -696,7 +1755,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         let mut cache = unsafe {
             FileSystemCache::new(wasmer_cache_dir).map_err(|e| format!("Cache error: {:?}", e))?
         };
-        let load_cache_key = || -> Result<_, String> {
+        let load_cache_key = || -> Result<_, Diagnostic> {
             if let Some(ref prehashed_cache_key) = options.cache_key {
                 if let Ok(module) =
                     WasmHash::decode(prehashed_cache_key).and_then(|prehashed_key| {
@@ -724,14 +1783,16 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                         &wasm_binary[..],
                         CompilerConfig {
                             symbol_map: em_symbol_map.clone(),
+                            memory_style: options.memory_style,
+                            spectre_mitigations,
                             track_state,
-                            features: options.features.into_backend_features(),
+                            features: options.backend_features(),
                             backend_specific_config,
                             ..Default::default()
                         },
                         &*compiler,
                     )
-                    .map_err(|e| format!("Can't compile module: {:?}", e))?;
+                    .map_err(|e| compile_error(options.backend_features(), &wasm_binary, e))?;
                     // We try to save the module into a cache file
                     cache.store(hash, module.clone()).unwrap_or_default();
 
@@ -743,6 +1804,21 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         load_cache_key()?
     };
 
+    if options.verbose {
+        print_compile_info(&module);
+    }
+
+    if options.check {
+        return check_imports(options, &module).map_err(Diagnostic::from);
+    }
+
+    if let Some(limits) = options.resource_limits() {
+        limits::set_global(Some(Arc::new(StaticLimiter::new(limits))));
+    }
+
+    #[cfg(unix)]
+    start_timeout(options);
+
     if let Some(loader) = options.loader {
         let mut import_object = wasmer_runtime_core::import::ImportObject::new();
         import_object.allow_missing_functions = true; // Import initialization might be left to the loader.
@@ -782,15 +1858,45 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         return Ok(());
     }
 
+    #[cfg(feature = "wasi")]
+    let is_wasi = wasmer_wasi::is_wasi_module(&module);
+    #[cfg(not(feature = "wasi"))]
+    let is_wasi = false;
+    // only the results of WASI syscalls are recorded; the other host
+    // functions aren't, so their modules couldn't be replayed
+    if !is_wasi && (options.record.is_some() || options.replay.is_some()) {
+        return Err("`--record` and `--replay` only support WASI modules".into());
+    }
+
     // TODO: refactor this
     if wasmer_emscripten::is_emscripten_module(&module) {
         let mut emscripten_globals = wasmer_emscripten::EmscriptenGlobals::new(&module)?;
+        redirect_emscripten_stdio(options, &mut emscripten_globals)?;
+        if !options.em_allow_net.is_empty() {
+            emscripten_globals.set_network_access(wasmer_emscripten::NetworkAccess::Allowlist(
+                options.em_allow_net.clone(),
+            ));
+        }
+        for side_module_path in &options.em_side_modules {
+            let side_module_binary = read_file_contents(side_module_path).map_err(|err| {
+                format!(
+                    "Can't read the side module {}: {}",
+                    side_module_path.as_os_str().to_string_lossy(),
+                    err
+                )
+            })?;
+            let name = side_module_path
+                .file_name()
+                .ok_or_else(|| "The side module path has no file name".to_string())?
+                .to_string_lossy();
+            emscripten_globals.add_side_module(&name, &side_module_binary, &*compiler)?;
+        }
         let import_object = wasmer_emscripten::generate_emscripten_env(&mut emscripten_globals);
         let mut instance = module
             .instantiate(&import_object)
             .map_err(|e| format!("Can't instantiate emscripten module: {:?}", e))?;
 
-        wasmer_emscripten::run_emscripten_instance(
+        let result = wasmer_emscripten::run_emscripten_instance(
             &module,
             &mut instance,
             &mut emscripten_globals,
@@ -802,52 +1908,120 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             options.args.iter().map(|arg| arg.as_str()).collect(),
             options.em_entrypoint.clone(),
             mapped_dirs,
-        )
-        .map_err(|e| format!("{:?}", e))?;
+        );
+        print_gas_used(options, &instance);
+        result.map_err(|e| format!("{:?}", e))?;
     } else {
-        #[cfg(feature = "wasi")]
-        let wasi_version = wasmer_wasi::get_wasi_version(&module, true);
-        #[cfg(feature = "wasi")]
-        let is_wasi = wasi_version.is_some();
-        #[cfg(not(feature = "wasi"))]
-        let is_wasi = false;
-
         if is_wasi {
             #[cfg(feature = "wasi")]
-            execute_wasi(
-                wasi_version.unwrap(),
-                options,
-                env_vars,
-                module,
-                mapped_dirs,
-                &wasm_binary,
-            )?;
+            execute_wasi(options, env_vars, module, mapped_dirs, &wasm_binary)?;
         } else {
             let import_object = wasmer_runtime_core::import::ImportObject::new();
             let instance = module
                 .instantiate(&import_object)
                 .map_err(|e| format!("Can't instantiate module: {:?}", e))?;
 
-            let invoke_fn = match options.invoke.as_ref() {
-                Some(fun) => fun,
-                _ => "main",
+            let invocations = if options.invoke.is_empty() {
+                vec![("main".to_string(), options.parse_args(&module, "main")?)]
+            } else {
+                options.invocations(&module)?
             };
-            let args = options.parse_args(&module, invoke_fn)?;
 
-            let result = instance
-                .dyn_func(&invoke_fn)
-                .map_err(|e| format!("{:?}", e))?
-                .call(&args)
-                .map_err(|e| format!("{:?}", e))?;
-            println!("{}({:?}) returned {:?}", invoke_fn, args, result);
+            #[cfg(unix)]
+            start_profiling(options, &instance)?;
+            let result = call_invocations(&instance, &invocations);
+            #[cfg(unix)]
+            write_profile(options, &instance)?;
+            print_gas_used(options, &instance);
+            result.map_err(|e| match e {
+                CallError::Runtime(ref err) if ran_out_of_gas(err) => {
+                    out_of_gas_message(options).into()
+                }
+                e => Diagnostic::call(&e, format!("{:?}", e)),
+            })?;
         }
     }
 
     Ok(())
 }
 
+/// Helper function for `execute_wasm` and `inspect_wasm`: the error of a
+/// module that failed to compile, with the offset of validation errors.
+fn compile_error(features: Features, wasm_binary: &[u8], error: CompileError) -> Diagnostic {
+    if let CompileError::ValidationError { msg } = error {
+        return match wasmer_runtime_core::validate_with_features(wasm_binary, features) {
+            Err(failure) => failure.into(),
+            Ok(()) => Diagnostic::Validation {
+                message: msg,
+                offset: None,
+            },
+        };
+    }
+    format!("Can't compile module: {:?}", error).into()
+}
+
+/// Helper function for `execute_wasm`: checks with `--check` that the ABI
+/// the module would run with provides all its imports, and lists those it
+/// doesn't.
+fn check_imports(options: &Run, module: &Module) -> Result<(), String> {
+    let (abi, result) = check_abi_imports(module)?;
+    match result {
+        Ok(()) => {
+            println!(
+                "{}: the {} ABI provides all the imports",
+                options.path.display(),
+                abi
+            );
+            Ok(())
+        }
+        Err(errors) => Err(format!(
+            "The {} ABI doesn't provide all the imports of {}:\n{}",
+            abi,
+            options.path.display(),
+            errors
+                .iter()
+                .map(|error| format!("  {}", error))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )),
+    }
+}
+
+/// Helper function for `check_imports`: the name of the ABI the module would
+/// run with, and whether it provides all the imports.
+fn check_abi_imports(
+    module: &Module,
+) -> Result<(&'static str, wasmer_runtime::error::LinkResult<()>), String> {
+    if wasmer_emscripten::is_emscripten_module(module) {
+        let mut emscripten_globals = wasmer_emscripten::EmscriptenGlobals::new(module)?;
+        let import_object = wasmer_emscripten::generate_emscripten_env(&mut emscripten_globals);
+        return Ok(("Emscripten", module.check_imports(&import_object)));
+    }
+    #[cfg(feature = "wasi")]
+    {
+        if wasmer_wasi::is_wasi_module(module) {
+            let wasi_state = wasmer_wasi::state::WasiState::new("")
+                .build()
+                .map_err(|e| format!("{:?}", e))?;
+            if wasmer_wasi::threads::is_wasi_threads_module(module) {
+                let threads = wasmer_wasi::threads::WasiThreads::new(module, wasi_state)
+                    .map_err(|e| format!("{}", e))?;
+                return Ok(("WASI", module.check_imports(&threads.import_object())));
+            }
+            let import_object =
+                wasmer_wasi::generate_import_object_from_state_for_module(module, wasi_state);
+            return Ok(("WASI", module.check_imports(&import_object)));
+        }
+    }
+    let import_object = wasmer_runtime_core::import::ImportObject::new();
+    Ok(("empty", module.check_imports(&import_object)))
+}
+
 #[cfg(feature = "managed")]
-fn interactive_shell(mut ctx: InteractiveShellContext) -> ShellExitOperation {
+fn interactive_shell(
+    mut ctx: InteractiveShellContext,
+    checkpoint: &dyn Fn(&wasmer_runtime_core::state::InstanceImage) -> Result<Vec<u8>, String>,
+) -> ShellExitOperation {
     use std::io::Write;
 
     let mut stdout = ::std::io::stdout();
@@ -877,7 +2051,13 @@ fn interactive_shell(mut ctx: InteractiveShellContext) -> ShellExitOperation {
                 let path = path.unwrap();
 
                 if let Some(ref image) = ctx.image {
-                    let buf = image.to_bytes();
+                    let buf = match checkpoint(image) {
+                        Ok(buf) => buf,
+                        Err(e) => {
+                            println!("Cannot take a snapshot: {}", e);
+                            continue;
+                        }
+                    };
                     let mut f = match File::create(path) {
                         Ok(x) => x,
                         Err(e) => {
@@ -921,38 +2101,80 @@ fn interactive_shell(mut ctx: InteractiveShellContext) -> ShellExitOperation {
 
 fn update_backend(options: &mut Run) {
     let binary_size = match metadata(&options.path) {
-        Ok(wasm_binary) => wasm_binary.len(),
+        Ok(wasm_binary) => wasm_binary.len() as usize,
         Err(_e) => 0,
     };
 
-    // Update backend when a backend flag is `auto`.
-    // Use the Singlepass backend if it's enabled and the file provided is larger
-    // than 10MiB (10485760 bytes), or it's enabled and the target architecture
-    // is AArch64. Otherwise, use the Cranelift backend.
+    // Update backend when a backend flag is `auto`, see `select_backend`.
     if options.backend == Backend::Auto {
-        if Backend::variants().contains(&Backend::Singlepass.to_string())
-            && (binary_size > 10485760 || cfg!(target_arch = "aarch64"))
-        {
-            options.backend = Backend::Singlepass;
-        } else {
-            options.backend = Backend::Cranelift;
+        let hints = BackendHints {
+            metered: options.metering_limit().is_some(),
+            optimize: options.optimize,
+        };
+        let (backend, selection) = wasmer_runtime::select_backend(binary_size, hints);
+        if options.verbose {
+            eprintln!(
+                "Picked the {} backend: {:?}",
+                backend.to_string(),
+                selection
+            );
         }
+        options.backend = backend;
     }
 }
 
+/// Replace a URL or registry package given as the path by the downloaded module
+fn fetch_source(options: &mut Run) -> Result<(), String> {
+    let source = match options.path.to_str().and_then(fetch::Source::parse) {
+        Some(source) if !options.path.exists() => source,
+        _ => {
+            if options.checksum.is_some() {
+                return Err("--checksum only applies to URLs and registry packages".to_string());
+            }
+            return Ok(());
+        }
+    };
+    let path = fetch::fetch(
+        &source,
+        options.checksum.as_ref().map(|s| s.as_str()),
+        &get_cache_dir(),
+    )?;
+    if options.command_name.is_none() {
+        options.command_name = Some(source.to_string());
+    }
+    options.path = path;
+    Ok(())
+}
+
 fn run(options: &mut Run) {
+    if let Err(message) = fetch_source(options) {
+        Diagnostic::from(message).report(options.output);
+        exit(1);
+    }
     update_backend(options);
+    #[cfg(unix)]
+    {
+        if options.output == OutputFormat::Json {
+            wasmer_runtime_core::fault::set_capture_backtraces(true);
+        }
+    }
     match execute_wasm(options) {
         Ok(()) => {}
-        Err(message) => {
-            eprintln!("Error: {}", message);
+        Err(diagnostic) => {
+            #[cfg(unix)]
+            {
+                if TIMED_OUT.load(std::sync::atomic::Ordering::SeqCst) {
+                    exit_timed_out(options.timeout.unwrap());
+                }
+            }
+            diagnostic.report(options.output);
             exit(1);
         }
     }
 }
 
-fn validate_wasm(validate: Validate) -> Result<(), String> {
-    let wasm_path = validate.path;
+fn validate_wasm(validate: &Validate) -> Result<(), Diagnostic> {
+    let wasm_path = &validate.path;
     let wasm_path_as_str = wasm_path.to_str().unwrap();
 
     let wasm_binary: Vec<u8> = read_file_contents(&wasm_path).map_err(|err| {
@@ -964,30 +2186,311 @@ fn validate_wasm(validate: Validate) -> Result<(), String> {
     })?;
 
     if !utils::is_wasm_binary(&wasm_binary) {
-        return Err(format!(
+        return Err(Diagnostic::from(format!(
             "Cannot recognize \"{}\" as a WASM binary",
             wasm_path_as_str,
-        ));
+        )));
     }
 
-    wasmer_runtime_core::validate_and_report_errors_with_features(
+    wasmer_runtime_core::validate_with_features(
         &wasm_binary,
         validate.features.into_backend_features(),
-    )
-    .map_err(|err| format!("Validation failed: {}", err))?;
+    )?;
 
     Ok(())
 }
 
 /// Runs logic for the `validate` subcommand
 fn validate(validate: Validate) {
-    match validate_wasm(validate) {
-        Err(message) => {
-            eprintln!("Error: {}", message);
+    match validate_wasm(&validate) {
+        Err(diagnostic) => {
+            diagnostic.report(validate.output);
             exit(-1);
         }
-        _ => (),
+        Ok(()) => {
+            if validate.output == OutputFormat::Json {
+                let path = validate.path.display().to_string();
+                println!("{}", serde_json::json!({ "path": path, "valid": true }));
+            }
+        }
+    }
+}
+
+/// Runs logic for the `inspect` subcommand
+fn inspect_wasm(options: Inspect) -> Result<(), Diagnostic> {
+    let wasm_binary = read_file_contents(&options.path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    let wasm_binary = wat_to_wasm(wasm_binary, &options.path, &options.features)?;
+
+    let compiler = compiler_for_subcommand(options.backend)?;
+    let features = options.features.into_backend_features();
+    let compiler_config = CompilerConfig {
+        features: features.clone(),
+        ..Default::default()
+    };
+    let module =
+        webassembly::compile_with_config_with(&wasm_binary[..], compiler_config, &*compiler)
+            .map_err(|e| compile_error(features, &wasm_binary, e))?;
+
+    let summary = inspect::ModuleSummary::new(&module);
+    match options.output {
+        OutputFormat::Text => println!("{}", summary),
+        OutputFormat::Json => {
+            let mut json = summary.to_json();
+            json["path"] = serde_json::json!(options.path.display().to_string());
+            println!("{}", json);
+        }
+    }
+    Ok(())
+}
+
+/// Runs logic for the `compile` subcommand
+fn compile_wasm(options: Compile) -> Result<(), String> {
+    let mut wasm_binary = read_file_contents(&options.path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    wasm_binary = wat_to_wasm(wasm_binary, &options.path, &options.features)?;
+
+    let backend = match options.backend {
+        Backend::Auto => Backend::Cranelift,
+        backend => backend,
+    };
+    let compiler = wasmer_runtime::compiler_for_backend(backend).ok_or_else(|| {
+        format!(
+            "the requested backend, \"{}\", is not enabled",
+            backend.to_string()
+        )
+    })?;
+
+    let mut compiler_config = CompilerConfig {
+        memory_style: options.memory_style,
+        spectre_mitigations: if options.spectre_mitigations {
+            SpectreMitigations::all()
+        } else {
+            SpectreMitigations::default()
+        },
+        features: options.features.into_backend_features(),
+        ..Default::default()
+    };
+    if let Some(triple) = options.target {
+        let cpu_features = options.cpu_features.unwrap_or_else(CpuFeatures::new);
+        compiler_config = compiler_config.target(triple, cpu_features);
+    } else if let Some(cpu_features) = options.cpu_features {
+        compiler_config = compiler_config.with_cpu_features(cpu_features);
+    }
+
+    let module =
+        webassembly::compile_with_config_with(&wasm_binary[..], compiler_config, &*compiler)
+            .map_err(|e| format!("Can't compile module: {:?}", e))?;
+
+    let output = module
+        .cache()
+        .and_then(|artifact| artifact.serialize())
+        .map_err(|e| format!("Can't serialize the module: {:?}", e))?;
+    fs::write(&options.output, output).map_err(|e| {
+        format!(
+            "Can't write {}: {}",
+            options.output.as_os_str().to_string_lossy(),
+            e
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Runs logic for the `compile` subcommand
+fn compile(options: Compile) {
+    if let Err(message) = compile_wasm(options) {
+        eprintln!("Error: {}", message);
+        exit(1);
+    }
+}
+
+/// Runs logic for the `sign` subcommand
+fn sign_wasm(options: Sign) -> Result<(), String> {
+    let keypair = if options.generate_key {
+        use std::io::Write;
+
+        let keypair = Keypair::generate(&mut rand::rngs::OsRng);
+        let mut public_key_path = options.key.clone().into_os_string();
+        public_key_path.push(".pub");
+
+        // The key pair holds the secret key, so it's only readable by its owner.
+        let mut open_options = fs::OpenOptions::new();
+        open_options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            open_options.mode(0o600);
+        }
+        open_options
+            .open(&options.key)
+            .and_then(|mut file| writeln!(file, "{}", hex::encode(&keypair.to_bytes()[..])))
+            .and_then(|()| {
+                fs::write(
+                    &public_key_path,
+                    format!("{}\n", hex::encode(&keypair.public.as_bytes()[..])),
+                )
+            })
+            .map_err(|err| {
+                format!(
+                    "Can't write the key {}: {}",
+                    options.key.as_os_str().to_string_lossy(),
+                    err
+                )
+            })?;
+        keypair
+    } else {
+        Keypair::from_bytes(&read_hex_file(&options.key)?).map_err(|err| {
+            format!(
+                "Invalid key pair {}: {}",
+                options.key.as_os_str().to_string_lossy(),
+                err
+            )
+        })?
+    };
+
+    let path = match options.path {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let wasm_binary = read_file_contents(&path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    let (output, contents) = if options.detached {
+        let signature = signing::sign_detached(&wasm_binary, &keypair);
+        (
+            options.output.unwrap_or_else(|| path.with_extension("sig")),
+            format!("{}\n", hex::encode(&signature[..])).into_bytes(),
+        )
+    } else {
+        (
+            options
+                .output
+                .unwrap_or_else(|| path.with_extension("signed.wasm")),
+            signing::sign(&wasm_binary, &keypair),
+        )
+    };
+    fs::write(&output, contents).map_err(|err| {
+        format!(
+            "Can't write the file {}: {}",
+            output.as_os_str().to_string_lossy(),
+            err
+        )
+    })
+}
+
+/// The compiler of `backend` for the `repl` and `wast` subcommands, which
+/// use the first backend enabled in this binary for `auto`
+fn compiler_for_subcommand(backend: Backend) -> Result<Box<dyn Compiler>, String> {
+    let backend = match backend {
+        Backend::Auto => [
+            Backend::Cranelift,
+            Backend::Singlepass,
+            Backend::LLVM,
+            Backend::Interpreter,
+        ]
+        .iter()
+        .cloned()
+        .find(|backend| wasmer_runtime::compiler_for_backend(*backend).is_some())
+        .unwrap_or(Backend::Auto),
+        backend => backend,
+    };
+    wasmer_runtime::compiler_for_backend(backend).ok_or_else(|| {
+        format!(
+            "the requested backend, \"{}\", is not enabled",
+            backend.to_string()
+        )
+    })
+}
+
+/// Runs logic for the `wast` subcommand.  Fails if a script can't be read or
+/// parsed, or one of its commands fails.
+fn run_wast(options: Wast) -> Result<(), String> {
+    let compiler = compiler_for_subcommand(options.backend)?;
+    let mut failed = 0;
+    for path in options.paths.iter() {
+        let source = read_file_contents(path).map_err(|err| {
+            format!(
+                "Can't read the file {}: {}",
+                path.as_os_str().to_string_lossy(),
+                err
+            )
+        })?;
+        let filename = path.to_string_lossy();
+        let report = wast::WastRunner::new(&*compiler, options.features.into_backend_features())
+            .run(&source, &filename, options.features.into_wabt_features())?;
+        for failure in report.failures.iter() {
+            println!(
+                "{}:{}: {} failed: {}",
+                filename, failure.line, failure.kind, failure.message
+            );
+        }
+        println!(
+            "{}: {} passed, {} failed",
+            filename,
+            report.passed,
+            report.failures.len()
+        );
+        failed += report.failures.len();
     }
+    if failed > 0 {
+        return Err(format!("{} command(s) failed", failed));
+    }
+    Ok(())
+}
+
+/// Runs logic for the `repl` subcommand
+fn repl_wasm(options: Repl) -> Result<(), String> {
+    let wasm_binary = read_file_contents(&options.path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    let wasm_binary = wat_to_wasm(wasm_binary, &options.path, &options.features)?;
+
+    let compiler = compiler_for_subcommand(options.backend)?;
+    let compiler_config = CompilerConfig {
+        features: options.features.into_backend_features(),
+        ..Default::default()
+    };
+    let module =
+        webassembly::compile_with_config_with(&wasm_binary[..], compiler_config, &*compiler)
+            .map_err(|e| format!("Can't compile module: {:?}", e))?;
+
+    #[cfg(feature = "wasi")]
+    let import_object = if wasmer_wasi::is_wasi_module(&module) {
+        let wasi_state = wasmer_wasi::state::WasiState::new(&options.path.to_string_lossy())
+            .preopen_dirs(options.pre_opened_directories.clone())
+            .map_dirs(get_mapped_dirs(&options.mapped_dirs[..])?)
+            .build()
+            .map_err(|e| format!("{:?}", e))?;
+        wasmer_wasi::generate_import_object_from_state_for_module(&module, wasi_state)
+    } else {
+        wasmer_runtime::ImportObject::new()
+    };
+    #[cfg(not(feature = "wasi"))]
+    let import_object = wasmer_runtime::ImportObject::new();
+
+    let instance = module
+        .instantiate(&import_object)
+        .map_err(|e| format!("Can't instantiate the module: {:?}", e))?;
+    repl::run(&module, &instance).map_err(|e| format!("Can't read the commands: {}", e))
 }
 
 fn get_compiler_by_backend(backend: Backend, _opts: &Run) -> Option<Box<dyn Compiler>> {
@@ -1009,6 +2512,10 @@ fn get_compiler_by_backend(backend: Backend, _opts: &Run) -> Option<Box<dyn Comp
                     use wasmer_middleware_common::block_trace::BlockTrace;
                     middlewares.push(BlockTrace::new());
                 }
+                if let Some(limit) = opts.metering_limit() {
+                    use wasmer_middleware_common::metering::Metering;
+                    middlewares.push(Metering::new(limit));
+                }
                 middlewares
             };
 
@@ -1023,9 +2530,50 @@ fn get_compiler_by_backend(backend: Backend, _opts: &Run) -> Option<Box<dyn Comp
         #[cfg(not(feature = "backend-cranelift"))]
         Backend::Cranelift => return None,
         #[cfg(feature = "backend-llvm")]
+        Backend::LLVM if _opts.metering_limit().is_some() => {
+            use wasmer_llvm_backend::ModuleCodeGenerator as LLVMMCG;
+            use wasmer_middleware_common::metering::Metering;
+            use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
+
+            let limit = _opts.metering_limit().unwrap();
+            let c: StreamingCompiler<LLVMMCG, _, _, _, _> = StreamingCompiler::new(move || {
+                let mut middlewares = MiddlewareChain::new();
+                middlewares.push(Metering::new(limit));
+                middlewares
+            });
+            Box::new(c)
+        }
+        #[cfg(feature = "backend-llvm")]
         Backend::LLVM => Box::new(LLVMCompiler::new()),
         #[cfg(not(feature = "backend-llvm"))]
         Backend::LLVM => return None,
+        #[cfg(feature = "backend-interpreter")]
+        Backend::Interpreter => {
+            use wasmer_interpreter_backend::ModuleCodeGenerator as InterpreterMCG;
+            use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
+
+            let opts = _opts.clone();
+            let c: StreamingCompiler<InterpreterMCG, _, _, _, _> =
+                StreamingCompiler::new(move || {
+                    let mut middlewares = MiddlewareChain::new();
+                    if opts.call_trace {
+                        use wasmer_middleware_common::call_trace::CallTrace;
+                        middlewares.push(CallTrace::new());
+                    }
+                    if opts.block_trace {
+                        use wasmer_middleware_common::block_trace::BlockTrace;
+                        middlewares.push(BlockTrace::new());
+                    }
+                    if let Some(limit) = opts.metering_limit() {
+                        use wasmer_middleware_common::metering::Metering;
+                        middlewares.push(Metering::new(limit));
+                    }
+                    middlewares
+                });
+            Box::new(c)
+        }
+        #[cfg(not(feature = "backend-interpreter"))]
+        Backend::Interpreter => return None,
         Backend::Auto => return None,
     })
 }
@@ -1068,6 +2616,34 @@ fn main() {
         CLIOptions::Validate(validate_options) => {
             validate(validate_options);
         }
+        CLIOptions::Inspect(inspect_options) => {
+            let output = inspect_options.output;
+            if let Err(diagnostic) = inspect_wasm(inspect_options) {
+                diagnostic.report(output);
+                exit(1);
+            }
+        }
+        CLIOptions::Compile(compile_options) => {
+            compile(compile_options);
+        }
+        CLIOptions::Sign(sign_options) => {
+            if let Err(message) = sign_wasm(sign_options) {
+                eprintln!("Error: {}", message);
+                exit(1);
+            }
+        }
+        CLIOptions::Repl(repl_options) => {
+            if let Err(message) = repl_wasm(repl_options) {
+                eprintln!("Error: {}", message);
+                exit(1);
+            }
+        }
+        CLIOptions::Wast(wast_options) => {
+            if let Err(message) = run_wast(wast_options) {
+                eprintln!("Error: {}", message);
+                exit(1);
+            }
+        }
     }
 }
 
@@ -0,0 +1,164 @@
+//! The errors the CLI reports, as text or as JSON for `--output json`
+
+use serde_json::{json, Value as JsonValue};
+use std::{fmt, str::FromStr};
+use wasmer_runtime::error::{CallError, RuntimeError};
+use wasmer_runtime_core::{
+    error::ValidationFailure, state::WasmFunctionStateDump, typed_func::WasmTrapInfo,
+};
+
+/// How the CLI prints its diagnostics and reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text
+    Text,
+    /// One JSON object per report
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("The output format {} isn't text or json", s)),
+        }
+    }
+}
+
+/// An error the CLI reports
+#[derive(Debug, Clone)]
+pub enum Diagnostic {
+    /// The wasm code trapped
+    Trap {
+        /// The kind of trap, see `WasmTrapInfo::code`
+        code: &'static str,
+        /// What happened
+        message: String,
+        /// The wasm frames at the trap, innermost first, when the backend
+        /// records them
+        backtrace: Vec<WasmFunctionStateDump>,
+    },
+    /// The module isn't valid
+    Validation {
+        /// What's invalid
+        message: String,
+        /// The offset in the module where validation failed, if known
+        offset: Option<usize>,
+    },
+    /// Any other error
+    Error {
+        /// What happened
+        message: String,
+    },
+}
+
+impl Diagnostic {
+    /// The diagnostic of a trap, or `None` if `error` isn't one
+    pub fn trap(error: &RuntimeError) -> Option<Self> {
+        match error {
+            RuntimeError::Trap { msg } => Some(Diagnostic::Trap {
+                code: WasmTrapInfo::from_message(msg).code(),
+                message: msg.to_string(),
+                backtrace: take_backtrace(),
+            }),
+            RuntimeError::Error { .. } => None,
+        }
+    }
+
+    /// The diagnostic of a failed call: a trap, or `message` for other errors
+    pub fn call(error: &CallError, message: String) -> Self {
+        match error {
+            CallError::Runtime(error) => Diagnostic::trap(error),
+            CallError::Resolve(_) => None,
+        }
+        .unwrap_or(Diagnostic::Error { message })
+    }
+
+    /// The diagnostic as a JSON object
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            Diagnostic::Trap {
+                code,
+                message,
+                backtrace,
+            } => json!({
+                "kind": "trap",
+                "code": code,
+                "message": message,
+                "backtrace": backtrace
+                    .iter()
+                    .map(|frame| json!({
+                        "local_function": frame.local_function_id,
+                        "offset": frame.wasm_inst_offset,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            Diagnostic::Validation { message, offset } => json!({
+                "kind": "validation",
+                "message": message,
+                "offset": offset,
+            }),
+            Diagnostic::Error { message } => json!({
+                "kind": "error",
+                "message": message,
+            }),
+        }
+    }
+
+    /// Prints the diagnostic to stderr in the given format
+    pub fn report(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Text => eprintln!("Error: {}", self),
+            OutputFormat::Json => eprintln!("{}", json!({ "error": self.to_json() })),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::Trap { message, .. } => write!(f, "wasm trap occured: {}", message),
+            Diagnostic::Validation { message, offset } => match offset {
+                Some(offset) => write!(f, "Validation failed: {} (at offset {})", message, offset),
+                None => write!(f, "Validation failed: {}", message),
+            },
+            Diagnostic::Error { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<String> for Diagnostic {
+    fn from(message: String) -> Self {
+        Diagnostic::Error { message }
+    }
+}
+
+impl From<&str> for Diagnostic {
+    fn from(message: &str) -> Self {
+        Diagnostic::Error {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<ValidationFailure> for Diagnostic {
+    fn from(failure: ValidationFailure) -> Self {
+        Diagnostic::Validation {
+            message: failure.message,
+            offset: failure.offset,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn take_backtrace() -> Vec<WasmFunctionStateDump> {
+    wasmer_runtime_core::fault::take_backtrace().map_or(vec![], |image| image.frames)
+}
+
+#[cfg(not(unix))]
+fn take_backtrace() -> Vec<WasmFunctionStateDump> {
+    vec![]
+}
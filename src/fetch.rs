@@ -0,0 +1,384 @@
+//! Downloads the modules `wasmer run` is given as URLs or registry packages
+//!
+//! Downloads go to the `downloads` directory of the wasmer cache, keyed by
+//! the source, and are reused by later runs.  A checksum is the hexadecimal
+//! blake2b hash the cache keys modules with, and one is required to run a
+//! download: cached files that don't match it are downloaded again, and
+//! downloads that don't match it are rejected.  Without one, the download is
+//! kept but not run, and its checksum is reported.
+//!
+//! Like `wasmer self-update`, this uses `curl`, and `tar` to unpack packages,
+//! whose entries must all be inside the package.
+
+use std::{
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+use wasmer_runtime_core::cache::WasmHash;
+
+/// The registry packages are downloaded from, unless `WASMER_REGISTRY` is set
+pub const DEFAULT_REGISTRY: &str = "https://registry-cdn.wapm.io/packages";
+
+/// A module to download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// An `http://` or `https://` URL
+    Url(String),
+    /// A `namespace/name@version` registry package
+    Package {
+        namespace: String,
+        name: String,
+        version: String,
+    },
+}
+
+impl Source {
+    /// Recognizes a URL or a `namespace/name@version` package, or returns
+    /// `None` for anything else, which is treated as a local path
+    pub fn parse(source: &str) -> Option<Self> {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            return Some(Source::Url(source.to_string()));
+        }
+        let at = source.find('@')?;
+        let (package, version) = (&source[..at], &source[at + 1..]);
+        let slash = package.find('/')?;
+        let (namespace, name) = (&package[..slash], &package[slash + 1..]);
+        if [namespace, name, version].iter().all(|part| is_name(part)) {
+            Some(Source::Package {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+                version: version.to_string(),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn url(&self) -> String {
+        match self {
+            Source::Url(url) => url.clone(),
+            Source::Package {
+                namespace,
+                name,
+                version,
+            } => {
+                let registry = std::env::var("WASMER_REGISTRY")
+                    .unwrap_or_else(|_| DEFAULT_REGISTRY.to_string());
+                format!(
+                    "{}/{}/{}/{}-{}.tar.gz",
+                    registry.trim_end_matches('/'),
+                    namespace,
+                    name,
+                    name,
+                    version
+                )
+            }
+        }
+    }
+
+    /// The name of the downloaded file, which keeps the extension of a URL
+    /// so text modules are recognized
+    fn file_name(&self) -> String {
+        match self {
+            Source::Url(url) => {
+                let path = url.split(|c| c == '?' || c == '#').next().unwrap();
+                match path.rsplit('/').next() {
+                    Some(name) if is_name(name) && name.contains('.') => name.to_string(),
+                    _ => "module.wasm".to_string(),
+                }
+            }
+            Source::Package { .. } => "package.tar.gz".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Url(url) => write!(f, "{}", url),
+            Source::Package {
+                namespace,
+                name,
+                version,
+            } => write!(f, "{}/{}@{}", namespace, name, version),
+        }
+    }
+}
+
+fn is_name(part: &str) -> bool {
+    !part.is_empty()
+        && !part.starts_with('.')
+        && part
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' || c == '+')
+}
+
+/// Downloads `source` into `cache_dir` unless it's already there, and
+/// returns the path of the module to run, if it has `checksum`
+pub fn fetch(source: &Source, checksum: Option<&str>, cache_dir: &Path) -> Result<PathBuf, String> {
+    let key = WasmHash::generate(source.to_string().as_bytes()).encode();
+    let dir = cache_dir.join("downloads").join(&key[..32]);
+    fs::create_dir_all(&dir).map_err(|err| {
+        format!(
+            "Can't create the download directory {}: {}",
+            dir.display(),
+            err
+        )
+    })?;
+
+    let file = dir.join(source.file_name());
+    let cached = match fs::read(&file) {
+        Ok(contents) => checksum.map_or(true, |checksum| matches(&contents, checksum)),
+        Err(_) => false,
+    };
+    if !cached {
+        download(source, &file, checksum)?;
+    }
+    if checksum.is_none() {
+        let contents = fs::read(&file)
+            .map_err(|err| format!("Can't read the download {}: {}", file.display(), err))?;
+        let hash = WasmHash::generate(&contents).encode();
+        return Err(format!(
+            "{} has checksum {}; run it with `--checksum {}` once you've checked it's the one you expect",
+            source, hash, hash
+        ));
+    }
+
+    match source {
+        Source::Url(_) => Ok(file),
+        Source::Package { .. } => {
+            let package_dir = dir.join("package");
+            if !cached || !package_dir.exists() {
+                unpack(&file, &package_dir)?;
+            }
+            find_module(source, &package_dir)
+        }
+    }
+}
+
+fn matches(contents: &[u8], checksum: &str) -> bool {
+    WasmHash::generate(contents)
+        .encode()
+        .eq_ignore_ascii_case(checksum.trim())
+}
+
+fn download(source: &Source, file: &Path, checksum: Option<&str>) -> Result<(), String> {
+    let url = source.url();
+    let partial = file.with_extension("partial");
+    let status = Command::new("curl")
+        .arg("-sSfL")
+        .arg("-o")
+        .arg(&partial)
+        .arg(&url)
+        .status()
+        .map_err(|err| format!("Can't run curl to download {}: {}", url, err))?;
+    if !status.success() {
+        let _ = fs::remove_file(&partial);
+        return Err(format!(
+            "Can't download {}: curl failed with {}",
+            url, status
+        ));
+    }
+
+    let contents = fs::read(&partial)
+        .map_err(|err| format!("Can't read the download {}: {}", partial.display(), err))?;
+    if let Some(checksum) = checksum {
+        if !matches(&contents, checksum) {
+            let _ = fs::remove_file(&partial);
+            return Err(format!(
+                "The checksum of {} is {}, not {}",
+                source,
+                WasmHash::generate(&contents).encode(),
+                checksum
+            ));
+        }
+    }
+    fs::rename(&partial, file)
+        .map_err(|err| format!("Can't move the download to {}: {}", file.display(), err))
+}
+
+/// Unpacks into a temporary directory, renamed to `package_dir` once the
+/// whole archive is unpacked
+fn unpack(archive: &Path, package_dir: &Path) -> Result<(), String> {
+    let output = Command::new("tar")
+        .arg("-tzf")
+        .arg(archive)
+        .output()
+        .map_err(|err| format!("Can't run tar to list {}: {}", archive.display(), err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Can't list {}: tar failed with {}",
+            archive.display(),
+            output.status
+        ));
+    }
+    let entries = String::from_utf8_lossy(&output.stdout);
+    if let Some(entry) = entries.lines().find(|entry| !is_inside(entry)) {
+        return Err(format!(
+            "Can't unpack {}: the entry {} isn't inside the package",
+            archive.display(),
+            entry
+        ));
+    }
+
+    let partial = package_dir.with_extension("partial");
+    remove_dir(&partial)?;
+    fs::create_dir_all(&partial).map_err(|err| {
+        format!(
+            "Can't create the package directory {}: {}",
+            partial.display(),
+            err
+        )
+    })?;
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(archive)
+        .arg("-C")
+        .arg(&partial)
+        .status()
+        .map_err(|err| format!("Can't run tar to unpack {}: {}", archive.display(), err))?;
+    if !status.success() {
+        let _ = fs::remove_dir_all(&partial);
+        return Err(format!(
+            "Can't unpack {}: tar failed with {}",
+            archive.display(),
+            status
+        ));
+    }
+    remove_dir(package_dir)?;
+    fs::rename(&partial, package_dir).map_err(|err| {
+        format!(
+            "Can't move the package to {}: {}",
+            package_dir.display(),
+            err
+        )
+    })
+}
+
+/// Whether an archive entry is unpacked inside the directory it's unpacked
+/// into: it must be relative, and never go up
+fn is_inside(entry: &str) -> bool {
+    Path::new(entry)
+        .components()
+        .all(|component| match component {
+            Component::Normal(_) | Component::CurDir => true,
+            Component::Prefix(_) | Component::RootDir | Component::ParentDir => false,
+        })
+}
+
+fn remove_dir(dir: &Path) -> Result<(), String> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    fs::remove_dir_all(dir)
+        .map_err(|err| format!("Can't remove the package {}: {}", dir.display(), err))
+}
+
+/// The package must contain exactly one module
+fn find_module(source: &Source, package_dir: &Path) -> Result<PathBuf, String> {
+    let mut modules = vec![];
+    find_wasm_files(package_dir, &mut modules)
+        .map_err(|err| format!("Can't read the package {}: {}", package_dir.display(), err))?;
+    match modules.len() {
+        0 => Err(format!("The package {} contains no module", source)),
+        1 => Ok(modules.pop().unwrap()),
+        _ => {
+            modules.sort();
+            Err(format!(
+                "The package {} contains several modules, run one of them by path:\n{}",
+                source,
+                modules
+                    .iter()
+                    .map(|module| format!("  {}", module.display()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ))
+        }
+    }
+}
+
+fn find_wasm_files(dir: &Path, modules: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_wasm_files(&path, modules)?;
+        } else if path.extension().map_or(false, |ext| ext == "wasm") {
+            modules.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_sources() {
+        assert_eq!(
+            Source::parse("https://example.com/module.wasm"),
+            Some(Source::Url("https://example.com/module.wasm".to_string()))
+        );
+        assert_eq!(
+            Source::parse("http://example.com/module.wat?raw=1"),
+            Some(Source::Url(
+                "http://example.com/module.wat?raw=1".to_string()
+            ))
+        );
+        assert_eq!(
+            Source::parse("wasmer/cowsay@0.2.0"),
+            Some(Source::Package {
+                namespace: "wasmer".to_string(),
+                name: "cowsay".to_string(),
+                version: "0.2.0".to_string(),
+            })
+        );
+        for path in &[
+            "module.wasm",
+            "./dir/module.wasm",
+            "wasmer/cowsay",
+            "cowsay@0.2.0",
+            "wasmer/cowsay@",
+            "../cowsay@0.2.0",
+            "wasmer/..@0.2.0",
+            "wasmer/cow say@0.2.0",
+            "ftp://example.com/module.wasm",
+        ] {
+            assert_eq!(Source::parse(path), None, "{} is a path", path);
+        }
+    }
+
+    #[test]
+    fn file_names() {
+        let file_name = |source| Source::parse(source).unwrap().file_name();
+        assert_eq!(
+            file_name("https://example.com/a/module.wat?x#y"),
+            "module.wat"
+        );
+        assert_eq!(file_name("https://example.com/download"), "module.wasm");
+        assert_eq!(file_name("https://example.com/"), "module.wasm");
+        assert_eq!(file_name("wasmer/cowsay@0.2.0"), "package.tar.gz");
+    }
+
+    #[test]
+    fn checksums() {
+        let hash = WasmHash::generate(b"\0asm").encode();
+        assert!(matches(b"\0asm", &hash));
+        assert!(matches(b"\0asm", &hash.to_uppercase()));
+        assert!(matches(b"\0asm", &format!(" {}\n", hash)));
+        assert!(!matches(b"\0asm\x01", &hash));
+        assert!(!matches(b"\0asm", &hash[1..]));
+        assert!(!matches(b"\0asm", ""));
+    }
+
+    #[test]
+    fn archive_entries() {
+        for entry in &["module.wasm", "./package/module.wasm", "a/b/", "a/./b"] {
+            assert!(is_inside(entry), "{} is inside", entry);
+        }
+        for entry in &["/etc/passwd", "../module.wasm", "a/../../b", ".."] {
+            assert!(!is_inside(entry), "{} isn't inside", entry);
+        }
+    }
+}
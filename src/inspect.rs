@@ -0,0 +1,274 @@
+//! Lists the imports, exports and other metadata of a module, as
+//! `wasmer inspect` does
+
+use crate::utils;
+use serde_json::{json, Value as JsonValue};
+use std::fmt;
+use wasmer_runtime::{
+    types::{GlobalDescriptor, MemoryDescriptor, TableDescriptor},
+    Module,
+};
+use wasmer_runtime_core::{
+    module::{ExportIndex, ImportName, ModuleInfo},
+    structures::TypedIndex,
+    types::{FuncIndex, LocalOrImport},
+};
+
+/// An import or an export of a module
+#[derive(Debug, Clone)]
+pub struct Entity {
+    /// The namespace of an import, `None` for an export
+    pub namespace: Option<String>,
+    /// The name of the import or export
+    pub name: String,
+    /// `function`, `memory`, `table` or `global`
+    pub kind: &'static str,
+    /// The signature of a function, or the limits or type of the others
+    pub ty: String,
+}
+
+/// The metadata of a module
+#[derive(Debug, Clone)]
+pub struct ModuleSummary {
+    /// The name of the module, from its name section
+    pub name: Option<String>,
+    /// The backend the module was compiled with
+    pub backend: String,
+    /// The imports, in the order of the module
+    pub imports: Vec<Entity>,
+    /// The exports, in the order of the module
+    pub exports: Vec<Entity>,
+    /// The types of the memories the module defines
+    pub memories: Vec<String>,
+    /// The types of the tables the module defines
+    pub tables: Vec<String>,
+    /// The types of the globals the module defines
+    pub globals: Vec<String>,
+    /// The number of functions the module defines
+    pub functions: usize,
+    /// The index of the start function, if any
+    pub start: Option<usize>,
+    /// The names of the custom sections, sorted
+    pub custom_sections: Vec<String>,
+}
+
+impl ModuleSummary {
+    /// Summarizes a compiled module
+    pub fn new(module: &Module) -> Self {
+        let info = module.info();
+        let import_name = |name: &ImportName| {
+            (
+                info.namespace_table.get(name.namespace_index).to_string(),
+                info.name_table.get(name.name_index).to_string(),
+            )
+        };
+
+        let mut imports = vec![];
+        for (index, name) in info.imported_functions.iter() {
+            let (namespace, name) = import_name(name);
+            imports.push(Entity {
+                namespace: Some(namespace),
+                name,
+                kind: "function",
+                ty: function_type(info, FuncIndex::new(index.index())),
+            });
+        }
+        for (_, (name, desc)) in info.imported_memories.iter() {
+            let (namespace, name) = import_name(name);
+            imports.push(Entity {
+                namespace: Some(namespace),
+                name,
+                kind: "memory",
+                ty: memory_type(desc),
+            });
+        }
+        for (_, (name, desc)) in info.imported_tables.iter() {
+            let (namespace, name) = import_name(name);
+            imports.push(Entity {
+                namespace: Some(namespace),
+                name,
+                kind: "table",
+                ty: table_type(desc),
+            });
+        }
+        for (_, (name, desc)) in info.imported_globals.iter() {
+            let (namespace, name) = import_name(name);
+            imports.push(Entity {
+                namespace: Some(namespace),
+                name,
+                kind: "global",
+                ty: global_type(desc),
+            });
+        }
+
+        let exports = info
+            .exports
+            .iter()
+            .map(|(name, index)| {
+                let (kind, ty) = export_type(info, *index);
+                Entity {
+                    namespace: None,
+                    name: name.clone(),
+                    kind,
+                    ty,
+                }
+            })
+            .collect();
+
+        let mut custom_sections: Vec<String> = info.custom_sections.keys().cloned().collect();
+        custom_sections.sort();
+        ModuleSummary {
+            name: module.module_name().map(str::to_string),
+            backend: info.backend.to_string(),
+            imports,
+            exports,
+            memories: info
+                .memories
+                .iter()
+                .map(|(_, desc)| memory_type(desc))
+                .collect(),
+            tables: info
+                .tables
+                .iter()
+                .map(|(_, desc)| table_type(desc))
+                .collect(),
+            globals: info
+                .globals
+                .iter()
+                .map(|(_, global)| global_type(&global.desc))
+                .collect(),
+            functions: info.func_assoc.len() - info.imported_functions.len(),
+            start: info.start_func.map(|index| index.index()),
+            custom_sections,
+        }
+    }
+
+    /// The summary as a JSON object
+    pub fn to_json(&self) -> JsonValue {
+        let entities = |entities: &[Entity]| {
+            entities
+                .iter()
+                .map(|entity| {
+                    let mut value = json!({
+                        "name": entity.name,
+                        "kind": entity.kind,
+                        "type": entity.ty,
+                    });
+                    if let Some(namespace) = &entity.namespace {
+                        value["namespace"] = json!(namespace);
+                    }
+                    value
+                })
+                .collect::<Vec<_>>()
+        };
+        json!({
+            "name": self.name,
+            "backend": self.backend,
+            "imports": entities(&self.imports),
+            "exports": entities(&self.exports),
+            "memories": self.memories,
+            "tables": self.tables,
+            "globals": self.globals,
+            "functions": self.functions,
+            "start": self.start,
+            "custom_sections": self.custom_sections,
+        })
+    }
+}
+
+impl fmt::Display for ModuleSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = &self.name {
+            writeln!(f, "Name: {}", name)?;
+        }
+        writeln!(f, "Backend: {}", self.backend)?;
+        writeln!(f, "Imports:")?;
+        for import in &self.imports {
+            writeln!(
+                f,
+                "  {} {}.{}: {}",
+                import.kind,
+                import.namespace.as_ref().map_or("", String::as_str),
+                import.name,
+                import.ty
+            )?;
+        }
+        writeln!(f, "Exports:")?;
+        for export in &self.exports {
+            writeln!(f, "  {} {}: {}", export.kind, export.name, export.ty)?;
+        }
+        writeln!(f, "Functions: {}", self.functions)?;
+        for (kind, types) in &[
+            ("Memories", &self.memories),
+            ("Tables", &self.tables),
+            ("Globals", &self.globals),
+        ] {
+            writeln!(f, "{}:", kind)?;
+            for ty in types.iter() {
+                writeln!(f, "  {}", ty)?;
+            }
+        }
+        if let Some(start) = self.start {
+            writeln!(f, "Start function: {}", start)?;
+        }
+        write!(f, "Custom sections: {}", self.custom_sections.join(", "))
+    }
+}
+
+fn function_type(info: &ModuleInfo, index: FuncIndex) -> String {
+    info.signatures[info.func_assoc[index]].to_string()
+}
+
+fn memory_type(desc: &MemoryDescriptor) -> String {
+    format!(
+        "{} pages{}",
+        limits(desc.minimum.0, desc.maximum.map(|pages| pages.0)),
+        if desc.shared { ", shared" } else { "" }
+    )
+}
+
+fn table_type(desc: &TableDescriptor) -> String {
+    format!("{} elements", limits(desc.minimum, desc.maximum))
+}
+
+fn global_type(desc: &GlobalDescriptor) -> String {
+    format!(
+        "{}{}",
+        if desc.mutable { "mut " } else { "" },
+        utils::type_name(desc.ty)
+    )
+}
+
+fn limits(minimum: u32, maximum: Option<u32>) -> String {
+    match maximum {
+        Some(maximum) => format!("{}..{}", minimum, maximum),
+        None => format!("{}..", minimum),
+    }
+}
+
+fn export_type(info: &ModuleInfo, index: ExportIndex) -> (&'static str, String) {
+    match index {
+        ExportIndex::Func(index) => ("function", function_type(info, index)),
+        ExportIndex::Memory(index) => (
+            "memory",
+            memory_type(&match index.local_or_import(info) {
+                LocalOrImport::Local(index) => info.memories[index],
+                LocalOrImport::Import(index) => info.imported_memories[index].1,
+            }),
+        ),
+        ExportIndex::Table(index) => (
+            "table",
+            table_type(&match index.local_or_import(info) {
+                LocalOrImport::Local(index) => info.tables[index],
+                LocalOrImport::Import(index) => info.imported_tables[index].1,
+            }),
+        ),
+        ExportIndex::Global(index) => (
+            "global",
+            global_type(&match index.local_or_import(info) {
+                LocalOrImport::Local(index) => info.globals[index].desc,
+                LocalOrImport::Import(index) => info.imported_globals[index].1,
+            }),
+        ),
+    }
+}
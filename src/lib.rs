@@ -14,7 +14,12 @@
 extern crate wasmer_runtime_core;
 // extern crate wasmer_emscripten;
 
+pub mod diagnostic;
+pub mod fetch;
+pub mod inspect;
+pub mod repl;
 #[macro_use]
 pub mod update;
 pub mod utils;
+pub mod wast;
 pub mod webassembly;
@@ -0,0 +1,218 @@
+//! The interactive prompt of `wasmer repl`, to call the exports of an
+//! instance and inspect its memories and globals
+
+use crate::utils;
+use std::io::{self, BufRead, Write};
+use wasmer_runtime::{types::Type, Export, Instance, Memory, Module};
+
+const HELP: &str = "\
+Commands:
+  call <function> [args...]        call an exported function
+  exports                          list the exports
+  global <name>                    read an exported global
+  memory <offset> [length] [name]  dump bytes of an exported memory
+  help                             show this message
+  exit                             leave the prompt";
+
+/// The number of bytes `memory` dumps when no length is given
+const DEFAULT_DUMP_LENGTH: usize = 64;
+
+/// Reads commands from the standard input until `exit` or the end of the
+/// input, and runs them against `instance`
+pub fn run(module: &Module, instance: &Instance) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    println!("Type `help` for the list of commands");
+    loop {
+        print!("wasmer> ");
+        stdout.flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+        match line.trim() {
+            "exit" | "quit" => return Ok(()),
+            line => match execute(module, instance, line) {
+                Ok(output) => {
+                    if !output.is_empty() {
+                        println!("{}", output)
+                    }
+                }
+                Err(message) => println!("Error: {}", message),
+            },
+        }
+    }
+}
+
+/// Runs a command and returns its output
+pub fn execute(module: &Module, instance: &Instance, line: &str) -> Result<String, String> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        Some(command) => command,
+        None => return Ok(String::new()),
+    };
+    let arguments: Vec<String> = words.map(str::to_string).collect();
+    match command {
+        "call" => call(module, instance, &arguments),
+        "exports" => Ok(exports(instance)),
+        "global" => match &arguments[..] {
+            [name] => global(instance, name),
+            _ => Err("Usage: global <name>".to_string()),
+        },
+        "memory" => memory(instance, &arguments),
+        "help" => Ok(HELP.to_string()),
+        _ => Err(format!(
+            "Unknown command `{}`, type `help` for the list of commands",
+            command
+        )),
+    }
+}
+
+fn call(module: &Module, instance: &Instance, arguments: &[String]) -> Result<String, String> {
+    let (name, arguments) = match arguments.split_first() {
+        Some(split) => split,
+        None => return Err("Usage: call <function> [args...]".to_string()),
+    };
+    let values = utils::parse_args(module, name, arguments)
+        .map_err(|e| format!("Can't call `{}`: {}", name, e))?;
+    let results = instance
+        .call(name, &values)
+        .map_err(|e| format!("`{}` failed: {}", name, e))?;
+    Ok(if results.is_empty() {
+        "nothing".to_string()
+    } else {
+        utils::format_values(&results)
+    })
+}
+
+fn exports(instance: &Instance) -> String {
+    instance
+        .exports()
+        .map(|(name, export)| match export {
+            Export::Function { signature, .. } => format!(
+                "func {}({}) -> ({})",
+                name,
+                type_names(signature.params()),
+                type_names(signature.returns())
+            ),
+            Export::Memory(memory) => format!("memory {}: {} pages", name, memory.size().0),
+            Export::Table(table) => format!("table {}: {} elements", name, table.size()),
+            Export::Global(global) => format!(
+                "global {}: {}{}",
+                name,
+                if global.descriptor().mutable {
+                    "mut "
+                } else {
+                    ""
+                },
+                utils::type_name(global.descriptor().ty)
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn type_names(types: &[Type]) -> String {
+    types
+        .iter()
+        .map(|ty| utils::type_name(*ty))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn global(instance: &Instance, name: &str) -> Result<String, String> {
+    match instance.exports().find(|(export, _)| export == name) {
+        Some((_, Export::Global(global))) => Ok(utils::format_values(&[global.get()])),
+        Some(_) => Err(format!("`{}` isn't a global", name)),
+        None => Err(format!("The instance exports no global `{}`", name)),
+    }
+}
+
+fn memory(instance: &Instance, arguments: &[String]) -> Result<String, String> {
+    let usage = || "Usage: memory <offset> [length] [name]".to_string();
+    let offset = match arguments.get(0) {
+        Some(offset) => parse_usize(offset)?,
+        None => return Err(usage()),
+    };
+    let length = match arguments.get(1) {
+        Some(length) => parse_usize(length)?,
+        None => DEFAULT_DUMP_LENGTH,
+    };
+    if arguments.len() > 3 {
+        return Err(usage());
+    }
+    let memory = find_memory(instance, arguments.get(2).map(String::as_str))?;
+
+    let view = memory.view::<u8>();
+    let end = offset
+        .checked_add(length)
+        .filter(|&end| end <= view.len())
+        .ok_or_else(|| {
+            format!(
+                "{} bytes at {:#x} are out of the {} bytes of the memory",
+                length,
+                offset,
+                view.len()
+            )
+        })?;
+    let bytes: Vec<u8> = view[offset..end].iter().map(|cell| cell.get()).collect();
+    Ok(bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii: String = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}  {:<47}  |{}|", offset + line * 16, hex, ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// The memory exported as `name`, or the one exported as `memory`, or the
+/// first one exported
+fn find_memory(instance: &Instance, name: Option<&str>) -> Result<Memory, String> {
+    let memories: Vec<_> = instance
+        .exports()
+        .filter_map(|(export, value)| match value {
+            Export::Memory(memory) => Some((export, memory)),
+            _ => None,
+        })
+        .collect();
+    let found = match name {
+        Some(name) => memories.into_iter().find(|(export, _)| export == name),
+        None => {
+            let position = memories
+                .iter()
+                .position(|(export, _)| export == "memory")
+                .unwrap_or(0);
+            memories.into_iter().nth(position)
+        }
+    };
+    found.map(|(_, memory)| memory).ok_or_else(|| match name {
+        Some(name) => format!("The instance exports no memory `{}`", name),
+        None => "The instance exports no memory".to_string(),
+    })
+}
+
+fn parse_usize(argument: &str) -> Result<usize, String> {
+    let parsed = if argument.starts_with("0x") {
+        usize::from_str_radix(&argument[2..], 16)
+    } else {
+        argument.parse()
+    };
+    parsed.map_err(|_| format!("Can't parse the number {:?}", argument))
+}
@@ -1,6 +1,7 @@
 //! Utility functions for the WebAssembly module
 
-use wasmer_runtime::{types::Type, Module, Value};
+use std::{fmt, time::Duration};
+use wasmer_runtime::{types::Type, units::Bytes, Module, Value};
 use wasmer_runtime_core::{backend::SigRegistry, module::ExportIndex};
 
 /// Detect if a provided binary is a Wasm file
@@ -12,11 +13,33 @@ pub fn is_wasm_binary(binary: &[u8]) -> bool {
 pub enum InvokeError {
     CouldNotFindFunction,
     ExportNotFunction,
-    WrongNumArgs { expected: u16, found: u16 },
+    WrongNumArgs { expected: Vec<Type>, found: usize },
     CouldNotParseArg(String),
 }
 
-/// Parses arguments for the `--invoke` flag on the run command
+impl fmt::Display for InvokeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvokeError::CouldNotFindFunction => write!(f, "the module exports no such function"),
+            InvokeError::ExportNotFunction => write!(f, "the export isn't a function"),
+            InvokeError::WrongNumArgs { expected, found } => write!(
+                f,
+                "the function takes {} argument(s) ({}), but {} were given",
+                expected.len(),
+                expected
+                    .iter()
+                    .map(|ty| type_name(*ty))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                found
+            ),
+            InvokeError::CouldNotParseArg(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Parses arguments for the `--invoke` flag on the run command, against the
+/// parameter types of the function
 pub fn parse_args(
     module: &Module,
     fn_name: &str,
@@ -40,82 +63,246 @@ pub fn parse_args(
     };
 
     let parameter_types = signature.params();
-    let mut arg_error = None;
-
     if args.len() != parameter_types.len() {
         return Err(InvokeError::WrongNumArgs {
-            expected: parameter_types.len() as _,
-            found: args.len() as _,
+            expected: parameter_types.to_vec(),
+            found: args.len(),
         });
+    }
+
+    args.iter()
+        .zip(parameter_types)
+        .enumerate()
+        .map(|(nth, (argument, ty))| {
+            parse_value(argument, *ty).map_err(|message| {
+                InvokeError::CouldNotParseArg(format!("argument {}: {}", nth, message))
+            })
+        })
+        .collect()
+}
+
+/// Parses a value of type `ty`.  The value can have the suffix of its type,
+/// e.g. `1.5f32` or `0x10i64`, and integers can be negative, or given in
+/// hexadecimal with `0x` and up to the maximum of the unsigned type.
+pub fn parse_value(argument: &str, ty: Type) -> Result<Value, String> {
+    let suffix = type_name(ty);
+    let unsuffixed = if argument.ends_with(suffix) {
+        &argument[..argument.len() - suffix.len()]
     } else {
-        args.iter()
-            .enumerate()
-            .try_fold(
-                Vec::with_capacity(args.len()),
-                |mut accumulator, (nth, argument)| {
-                    if let Some(value) = match parameter_types[nth] {
-                        Type::I32 => argument
-                            .parse::<i32>()
-                            .map(|v| Some(Value::I32(v)))
-                            .unwrap_or_else(|_| {
-                                arg_error = Some(InvokeError::CouldNotParseArg(format!(
-                                    "Failed to parse `{:?}` as an `i32`",
-                                    argument
-                                )));
-                                None
-                            }),
-                        Type::I64 => argument
-                            .parse::<i64>()
-                            .map(|v| Some(Value::I64(v)))
-                            .unwrap_or_else(|_| {
-                                arg_error = Some(InvokeError::CouldNotParseArg(format!(
-                                    "Failed to parse `{:?}` as an `i64`",
-                                    argument
-                                )));
-                                None
-                            }),
-                        Type::V128 => argument
-                            .parse::<u128>()
-                            .map(|v| Some(Value::V128(v)))
-                            .unwrap_or_else(|_| {
-                                arg_error = Some(InvokeError::CouldNotParseArg(format!(
-                                    "Failed to parse `{:?}` as an `i128`",
-                                    argument
-                                )));
-                                None
-                            }),
-                        Type::F32 => argument
-                            .parse::<f32>()
-                            .map(|v| Some(Value::F32(v)))
-                            .unwrap_or_else(|_| {
-                                arg_error = Some(InvokeError::CouldNotParseArg(format!(
-                                    "Failed to parse `{:?}` as an `f32`",
-                                    argument
-                                )));
-                                None
-                            }),
-                        Type::F64 => argument
-                            .parse::<f64>()
-                            .map(|v| Some(Value::F64(v)))
-                            .unwrap_or_else(|_| {
-                                arg_error = Some(InvokeError::CouldNotParseArg(format!(
-                                    "Failed to parse `{:?}` as an `f64`",
-                                    argument
-                                )));
-                                None
-                            }),
-                    } {
-                        accumulator.push(value);
-
-                        Some(accumulator)
-                    } else {
-                        None
-                    }
-                },
-            )
-            .map_or_else(
-                || Err(arg_error.unwrap()),
-                |arguments: Vec<Value>| Ok(arguments),
+        argument
+    };
+
+    let value = match ty {
+        Type::I32 => parse_integer(unsuffixed, 32).map(|v| Value::I32(v as i32)),
+        Type::I64 => parse_integer(unsuffixed, 64).map(|v| Value::I64(v as i64)),
+        Type::F32 => unsuffixed.parse::<f32>().ok().map(Value::F32),
+        Type::F64 => unsuffixed.parse::<f64>().ok().map(Value::F64),
+        Type::V128 => parse_v128(unsuffixed).ok().map(Value::V128),
+    };
+
+    value.ok_or_else(|| {
+        let other_suffix = [Type::I32, Type::I64, Type::F32, Type::F64, Type::V128]
+            .iter()
+            .find(|other| **other != ty && argument.ends_with(type_name(**other)));
+        match other_suffix {
+            Some(other) => format!(
+                "{:?} has type {}, but the parameter has type {}",
+                argument,
+                type_name(*other),
+                suffix
+            ),
+            None => format!("can't parse {:?} as {}", argument, suffix),
+        }
+    })
+}
+
+/// Parses an integer of `bits` bits, signed or unsigned, into its two's
+/// complement representation
+fn parse_integer(argument: &str, bits: u32) -> Option<u64> {
+    let (negative, magnitude) = if argument.starts_with('-') {
+        (true, &argument[1..])
+    } else {
+        (false, argument.trim_start_matches('+'))
+    };
+    let magnitude = if magnitude.starts_with("0x") || magnitude.starts_with("0X") {
+        u64::from_str_radix(&magnitude[2..], 16).ok()?
+    } else {
+        magnitude.parse::<u64>().ok()?
+    };
+
+    let max_unsigned = if bits == 64 {
+        u64::max_value()
+    } else {
+        (1 << bits) - 1
+    };
+    if negative {
+        if magnitude > 1 << (bits - 1) {
+            return None;
+        }
+        Some(magnitude.wrapping_neg() & max_unsigned)
+    } else if magnitude > max_unsigned {
+        None
+    } else {
+        Some(magnitude)
+    }
+}
+
+/// Parses a `v128` argument, given either in decimal or as `0x`-prefixed hexadecimal
+fn parse_v128(argument: &str) -> Result<u128, std::num::ParseIntError> {
+    if argument.starts_with("0x") || argument.starts_with("0X") {
+        u128::from_str_radix(&argument[2..], 16)
+    } else {
+        argument.parse::<u128>()
+    }
+}
+
+/// Formats values with the suffixes of their types, as `parse_value` reads
+/// them, e.g. `42i32, 1.5f64`
+pub fn format_values(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|value| match value {
+            Value::I32(v) => format!("{}i32", v),
+            Value::I64(v) => format!("{}i64", v),
+            Value::F32(v) => format!("{}f32", v),
+            Value::F64(v) => format!("{}f64", v),
+            Value::V128(v) => format!("0x{:x}v128", v),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The name of `ty` in the text format
+pub(crate) fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::F32 => "f32",
+        Type::F64 => "f64",
+        Type::V128 => "v128",
+    }
+}
+
+/// Parses a duration given as a number followed by a unit among `ms`, `s`,
+/// `m` and `h`, e.g. `500ms` or `5s`.  A number without a unit is in seconds
+pub fn parse_duration(argument: &str) -> Result<Duration, String> {
+    let split = argument
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or_else(|| argument.len());
+    let (number, unit) = argument.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Can't parse the duration {:?}", argument))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => {
+            return Err(format!(
+                "Unknown unit {:?} in the duration {:?}",
+                unit, argument
+            ))
+        }
+    };
+    if seconds > u64::max_value() as f64 {
+        return Err(format!("The duration {:?} is too long", argument));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Parses a size given as a number of bytes, optionally followed by a unit
+/// among `KiB`, `MiB` and `GiB`, e.g. `256MiB`
+pub fn parse_size(argument: &str) -> Result<Bytes, String> {
+    let split = argument
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or_else(|| argument.len());
+    let (number, unit) = argument.split_at(split);
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("Can't parse the size {:?}", argument))?;
+    let multiplier = match unit {
+        "" | "B" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        _ => {
+            return Err(format!(
+                "Unknown unit {:?} in the size {:?}",
+                unit, argument
+            ))
+        }
+    };
+    number
+        .checked_mul(multiplier)
+        .map(Bytes)
+        .ok_or_else(|| format!("The size {:?} is too large", argument))
+}
+
+/// Whether `name` matches `pattern`, in which `*` matches any sequence of
+/// characters and `?` any single character
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // The position after the last `*`, and the position in `name` it
+    // currently matches up to, to backtrack to.
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, n));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Parses the contents of an environment file: a `NAME=value` line per
+/// variable, optionally prefixed by `export`, with the value optionally in
+/// quotes.  Empty lines and lines starting with `#` are ignored
+pub fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = vec![];
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = if line.starts_with("export ") {
+            line["export ".len()..].trim_start()
+        } else {
+            line
+        };
+        let eq = line.find('=').ok_or_else(|| {
+            format!(
+                "Line {} must be of the form <var_name>=<value>, found {}",
+                number + 1,
+                line
             )
+        })?;
+        let (name, value) = (line[..eq].trim(), line[eq + 1..].trim());
+        let value = if value.len() >= 2
+            && (value.starts_with('"') && value.ends_with('"')
+                || value.starts_with('\'') && value.ends_with('\''))
+        {
+            &value[1..value.len() - 1]
+        } else {
+            value
+        };
+        vars.push((name.to_string(), value.to_string()));
     }
+    Ok(vars)
 }
@@ -0,0 +1,335 @@
+//! Runs spec-style `.wast` scripts, as `wasmer wast` does
+//!
+//! Modules are instantiated with the `spectest` imports of the spec
+//! interpreter, and with the exports of the modules the script registers.
+
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+use wabt::script::{Action, Command, CommandKind, ModuleBinary, ScriptParser};
+use wasmer_runtime::{
+    error::{CallError, Error, RuntimeError},
+    func, imports,
+    types::{ElementType, MemoryDescriptor, TableDescriptor},
+    units::Pages,
+    Ctx, Export, Global, ImportObject, Instance, Memory, Module, Table, Value,
+};
+use wasmer_runtime_core::backend::{Compiler, CompilerConfig, Features};
+
+type WabtValue = wabt::script::Value<f32, f64>;
+
+/// An assertion of a script that didn't hold
+#[derive(Debug, Clone)]
+pub struct WastFailure {
+    /// The line of the command in the script
+    pub line: u64,
+    /// The kind of command, e.g. `assert_return`
+    pub kind: &'static str,
+    /// What went wrong
+    pub message: String,
+}
+
+/// The outcome of a script
+#[derive(Debug, Clone, Default)]
+pub struct WastReport {
+    /// The number of commands that succeeded
+    pub passed: u32,
+    /// The commands that failed
+    pub failures: Vec<WastFailure>,
+}
+
+/// Runs the commands of a script, compiling its modules with `compiler`
+pub struct WastRunner<'a> {
+    compiler: &'a dyn Compiler,
+    features: Features,
+    current: Option<Arc<Mutex<Instance>>>,
+    named: HashMap<String, Arc<Mutex<Instance>>>,
+    registered: HashMap<String, Arc<Mutex<Instance>>>,
+}
+
+impl<'a> WastRunner<'a> {
+    /// A runner compiling modules with `compiler` and `features`
+    pub fn new(compiler: &'a dyn Compiler, features: Features) -> Self {
+        Self {
+            compiler,
+            features,
+            current: None,
+            named: HashMap::new(),
+            registered: HashMap::new(),
+        }
+    }
+
+    /// Runs the script `source`, named `filename` in parse errors.  Fails
+    /// only if the script can't be parsed; failed commands are reported.
+    pub fn run(
+        &mut self,
+        source: &[u8],
+        filename: &str,
+        wabt_features: wabt::Features,
+    ) -> Result<WastReport, String> {
+        let mut parser =
+            ScriptParser::from_source_and_name_with_features(source, filename, wabt_features)
+                .map_err(|e| format!("Can't parse {}: {:?}", filename, e))?;
+        let mut report = WastReport::default();
+        while let Some(Command { kind, line }) = parser
+            .next()
+            .map_err(|e| format!("Can't parse {}: {:?}", filename, e))?
+        {
+            let name = command_name(&kind);
+            let result = panic::catch_unwind(AssertUnwindSafe(|| self.command(kind)))
+                .unwrap_or_else(|_| Err("panicked".to_string()));
+            match result {
+                Ok(()) => report.passed += 1,
+                Err(message) => report.failures.push(WastFailure {
+                    line,
+                    kind: name,
+                    message,
+                }),
+            }
+        }
+        Ok(report)
+    }
+
+    fn command(&mut self, kind: CommandKind<f32, f64>) -> Result<(), String> {
+        match kind {
+            CommandKind::Module { module, name } => {
+                self.current = None;
+                let instance = self
+                    .compile(module)?
+                    .instantiate(&self.import_object())
+                    .map_err(|e| format!("Can't instantiate the module: {:?}", e))?;
+                let instance = Arc::new(Mutex::new(instance));
+                if let Some(name) = name {
+                    self.named.insert(name, instance.clone());
+                }
+                self.current = Some(instance);
+                Ok(())
+            }
+            CommandKind::AssertReturn { action, expected } => {
+                let values = self.perform(&action)?.map_err(call_failed)?;
+                if values.len() == expected.len()
+                    && values.iter().zip(&expected).all(|(v, e)| same_bits(v, e))
+                {
+                    Ok(())
+                } else {
+                    Err(format!("Expected {:?}, got {:?}", expected, values))
+                }
+            }
+            CommandKind::AssertReturnCanonicalNan { action } => {
+                self.assert_nan(&action, "canonical", |bits, quiet| bits == quiet)
+            }
+            CommandKind::AssertReturnArithmeticNan { action } => {
+                self.assert_nan(&action, "arithmetic", |bits, quiet| bits & quiet == quiet)
+            }
+            CommandKind::AssertTrap { action, message } => match self.perform(&action)? {
+                Err(CallError::Runtime(RuntimeError::Trap { .. })) => Ok(()),
+                other => Err(format!("Expected a trap ({}), got {:?}", message, other)),
+            },
+            CommandKind::AssertExhaustion { action, message } => match self.perform(&action)? {
+                Err(_) => Ok(()),
+                Ok(values) => Err(format!(
+                    "Expected an exhaustion ({}), got {:?}",
+                    message, values
+                )),
+            },
+            CommandKind::AssertInvalid { module, message }
+            | CommandKind::AssertMalformed { module, message } => match self.compile(module) {
+                Err(_) => Ok(()),
+                Ok(_) => Err(format!("Expected a compile error ({})", message)),
+            },
+            CommandKind::AssertUninstantiable { module, message } => {
+                match self.compile(module)?.instantiate(&self.import_object()) {
+                    Err(_) => Ok(()),
+                    Ok(_) => Err(format!("Expected an instantiation error ({})", message)),
+                }
+            }
+            CommandKind::AssertUnlinkable { module, message } => {
+                match self.compile(module)?.instantiate(&self.import_object()) {
+                    Err(Error::LinkError(_)) => Ok(()),
+                    other => Err(format!(
+                        "Expected a link error ({}), got {:?}",
+                        message,
+                        other.map(|_| ())
+                    )),
+                }
+            }
+            CommandKind::Register { name, as_name } => {
+                let instance = self.instance(&name)?;
+                self.registered.insert(as_name, instance);
+                Ok(())
+            }
+            CommandKind::PerformAction(action) => {
+                self.perform(&action)?.map_err(call_failed)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn compile(&self, module: ModuleBinary) -> Result<Module, String> {
+        let config = CompilerConfig {
+            features: self.features.clone(),
+            ..Default::default()
+        };
+        wasmer_runtime::compile_with_config_with(&module.into_vec(), config, self.compiler)
+            .map_err(|e| format!("Can't compile the module: {:?}", e))
+    }
+
+    /// The instance named `name`, or the last one instantiated
+    fn instance(&self, name: &Option<String>) -> Result<Arc<Mutex<Instance>>, String> {
+        match name {
+            Some(name) => self
+                .named
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("No module named {}", name)),
+            None => self
+                .current
+                .clone()
+                .ok_or_else(|| "No module instantiated".to_string()),
+        }
+    }
+
+    /// Performs `action`, or fails if it can't be performed at all
+    fn perform(&self, action: &Action<f32, f64>) -> Result<Result<Vec<Value>, CallError>, String> {
+        match action {
+            Action::Invoke {
+                module,
+                field,
+                args,
+            } => {
+                let instance = self.instance(module)?;
+                let args: Vec<Value> = args.iter().map(convert_value).collect();
+                let result = instance.lock().unwrap().call(field, &args);
+                Ok(result)
+            }
+            Action::Get { module, field } => {
+                let instance = self.instance(module)?;
+                let export = instance.lock().unwrap().get_export(field);
+                match export {
+                    Ok(Export::Global(global)) => Ok(Ok(vec![global.get()])),
+                    _ => Err(format!("No global named {}", field)),
+                }
+            }
+        }
+    }
+
+    /// Checks that `action` returns NaNs whose bits, with the bits of the
+    /// quiet NaN of their type, are accepted by `check`
+    fn assert_nan(
+        &self,
+        action: &Action<f32, f64>,
+        kind: &str,
+        check: fn(u64, u64) -> bool,
+    ) -> Result<(), String> {
+        let values = self.perform(action)?.map_err(call_failed)?;
+        let all_nan = values.iter().all(|value| match value {
+            Value::F32(x) => check(x.to_bits() as u64 & 0x7fff_ffff, 0x7fc0_0000),
+            Value::F64(x) => check(x.to_bits() & 0x7fff_ffff_ffff_ffff, 0x7ff8_0000_0000_0000),
+            _ => false,
+        });
+        if all_nan {
+            Ok(())
+        } else {
+            Err(format!("Expected {} NaNs, got {:?}", kind, values))
+        }
+    }
+
+    fn import_object(&self) -> ImportObject {
+        let memory =
+            Memory::new(MemoryDescriptor::new(Pages(1), Some(Pages(2)), false).unwrap()).unwrap();
+        let table = Table::new(TableDescriptor {
+            element: ElementType::Anyfunc,
+            minimum: 10,
+            maximum: Some(20),
+        })
+        .unwrap();
+        let mut import_object = imports! {
+            "spectest" => {
+                "print" => func!(print),
+                "print_i32" => func!(print_i32),
+                "print_f32" => func!(print_f32),
+                "print_f64" => func!(print_f64),
+                "print_i32_f32" => func!(print_i32_f32),
+                "print_f64_f64" => func!(print_f64_f64),
+                "table" => table,
+                "memory" => memory,
+                "global_i32" => Global::new(Value::I32(666)),
+                "global_f32" => Global::new(Value::F32(666.0)),
+                "global_f64" => Global::new(Value::F64(666.0)),
+            },
+        };
+        for (name, instance) in self.registered.iter() {
+            import_object.register(name.clone(), instance.clone());
+        }
+        import_object
+    }
+}
+
+fn command_name(kind: &CommandKind<f32, f64>) -> &'static str {
+    match kind {
+        CommandKind::Module { .. } => "module",
+        CommandKind::AssertReturn { .. } => "assert_return",
+        CommandKind::AssertReturnCanonicalNan { .. } => "assert_return_canonical_nan",
+        CommandKind::AssertReturnArithmeticNan { .. } => "assert_return_arithmetic_nan",
+        CommandKind::AssertTrap { .. } => "assert_trap",
+        CommandKind::AssertInvalid { .. } => "assert_invalid",
+        CommandKind::AssertMalformed { .. } => "assert_malformed",
+        CommandKind::AssertUninstantiable { .. } => "assert_uninstantiable",
+        CommandKind::AssertExhaustion { .. } => "assert_exhaustion",
+        CommandKind::AssertUnlinkable { .. } => "assert_unlinkable",
+        CommandKind::Register { .. } => "register",
+        CommandKind::PerformAction(_) => "invoke",
+    }
+}
+
+fn call_failed(error: CallError) -> String {
+    format!("The call failed: {}", error)
+}
+
+fn convert_value(value: &WabtValue) -> Value {
+    match *value {
+        WabtValue::I32(v) => Value::I32(v),
+        WabtValue::I64(v) => Value::I64(v),
+        WabtValue::F32(v) => Value::F32(v),
+        WabtValue::F64(v) => Value::F64(v),
+        WabtValue::V128(v) => Value::V128(v),
+    }
+}
+
+/// Compares floats by their bits, so NaNs match exactly
+fn same_bits(value: &Value, expected: &WabtValue) -> bool {
+    match (value, expected) {
+        (Value::I32(a), WabtValue::I32(b)) => a == b,
+        (Value::I64(a), WabtValue::I64(b)) => a == b,
+        (Value::F32(a), WabtValue::F32(b)) => a.to_bits() == b.to_bits(),
+        (Value::F64(a), WabtValue::F64(b)) => a.to_bits() == b.to_bits(),
+        (Value::V128(a), WabtValue::V128(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn print(_ctx: &mut Ctx) {
+    println!();
+}
+
+fn print_i32(_ctx: &mut Ctx, val: i32) {
+    println!("{}", val);
+}
+
+fn print_f32(_ctx: &mut Ctx, val: f32) {
+    println!("{}", val);
+}
+
+fn print_f64(_ctx: &mut Ctx, val: f64) {
+    println!("{}", val);
+}
+
+fn print_i32_f32(_ctx: &mut Ctx, val: i32, val2: f32) {
+    println!("{} {}", val, val2);
+}
+
+fn print_f64_f64(_ctx: &mut Ctx, val: f64, val2: f64) {
+    println!("{} {}", val, val2);
+}